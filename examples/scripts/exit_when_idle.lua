@@ -0,0 +1,20 @@
+-- gpuatop --launch python train.py --script examples/scripts/exit_when_idle.lua
+--
+-- Ends monitoring with code 10 once every GPU has been below 5% utilization for a minute of
+-- one-second cycles: a job that stalled rather than finished.
+
+local IDLE_PERCENT = 5
+local CYCLES = 60
+local idle_cycles = 0
+
+function on_snapshot(snapshots)
+  local busy = false
+  for _, snapshot in ipairs(snapshots) do
+    busy = busy or (snapshot.utilization or 0) >= IDLE_PERCENT
+  end
+  idle_cycles = busy and 0 or idle_cycles + 1
+  if idle_cycles == CYCLES then
+    alert("every GPU idle for " .. CYCLES .. " cycles")
+    exit(10)
+  end
+end
@@ -0,0 +1,22 @@
+-- gpuatop --script examples/scripts/hot_gpu_alert.lua
+--
+-- Alerts once a GPU has been above 80°C for three polling cycles in a row, a sustained
+-- condition --alert-temp cannot express.
+
+local LIMIT_C = 80
+local CYCLES = 3
+local hot = {}
+
+function on_snapshot(snapshots)
+  for _, snapshot in ipairs(snapshots) do
+    local gpu, temperature = snapshot.gpu, snapshot.temperature_c
+    if temperature and temperature > LIMIT_C then
+      hot[gpu] = (hot[gpu] or 0) + 1
+      if hot[gpu] == CYCLES then
+        alert(string.format("GPU %d above %d°C for %d cycles", gpu, LIMIT_C, CYCLES), gpu)
+      end
+    else
+      hot[gpu] = 0
+    end
+  end
+end
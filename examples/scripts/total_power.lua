@@ -0,0 +1,11 @@
+-- gpuatop --script examples/scripts/total_power.lua
+--
+-- Prints the power drawn by all GPUs together once per polling cycle.
+
+function on_snapshot(snapshots)
+  local total = 0
+  for _, snapshot in ipairs(snapshots) do
+    total = total + (snapshot.power_w or 0)
+  end
+  print(string.format("Total power: %.1f W", total))
+end
@@ -0,0 +1,27 @@
+#!/bin/sh
+# Builds, lints and tests the feature combinations gpuatop ships: the library alone,
+# the default binary and everything. vulkan opens its loader at runtime and is always
+# checked; opencl links libOpenCL and is only checked when EXTRA_FEATURES names it,
+# e.g. EXTRA_FEATURES=opencl.
+set -eu
+
+cd "$(dirname "$0")/.."
+
+check() {
+    echo "== cargo $*"
+    cargo build "$@"
+    cargo clippy --all-targets "$@" -- -D warnings
+    cargo test "$@"
+}
+
+check --no-default-features
+check --no-default-features --features cli
+check
+check --no-default-features --features cli,web
+check --no-default-features --features network
+check --no-default-features --features lua
+check --features full
+check --features full,vulkan
+if [ -n "${EXTRA_FEATURES:-}" ]; then
+    check --features "full,$EXTRA_FEATURES"
+fi
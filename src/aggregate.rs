@@ -0,0 +1,116 @@
+//! `--aggregate`: every GPU of the host in one table per tick, with a total row.
+
+use crate::display::table::{Column, Table};
+use crate::output::{format_snapshot, json_string, OutputContext, OutputFormat};
+use crate::schema::SCHEMA_VERSION;
+use crate::GpuSnapshot;
+
+/// Bold and reverse video for the total row on a terminal.
+const HIGHLIGHT: &str = "\x1b[1;7m";
+const RESET: &str = "\x1b[0m";
+
+/// Host-wide figures over the GPUs sampled in one tick. Memory and power sum the GPUs that
+/// report them and are `None` when none does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Totals {
+    pub gpus: usize,
+    pub mean_utilization: f64,
+    pub memory_used_mib: Option<u64>,
+    pub memory_total_mib: Option<u64>,
+    pub power_w: Option<f32>,
+}
+
+fn sum<T: std::iter::Sum<T>>(values: impl Iterator<Item = Option<T>>) -> Option<T> {
+    let mut values = values.flatten().peekable();
+    values.peek()?;
+    Some(values.sum())
+}
+
+pub fn totals(snapshots: &[GpuSnapshot]) -> Totals {
+    let utilization: f64 = snapshots.iter().map(|snapshot| snapshot.utilization).sum();
+
+    Totals {
+        gpus: snapshots.len(),
+        mean_utilization: if snapshots.is_empty() { 0.0 } else { utilization / snapshots.len() as f64 },
+        memory_used_mib: sum(snapshots.iter().map(|snapshot| snapshot.memory_used_mib)),
+        memory_total_mib: sum(snapshots.iter().map(|snapshot| snapshot.memory_total_mib)),
+        power_w: sum(snapshots.iter().map(|snapshot| snapshot.power_w)),
+    }
+}
+
+fn memory_cell(used: Option<u64>, total: Option<u64>) -> String {
+    match (used, total) {
+        (Some(used), Some(total)) => format!("{}/{} MiB", used, total),
+        (Some(used), None) => format!("{} MiB", used),
+        _ => "-".to_string(),
+    }
+}
+
+/// Sorts the snapshots by GPU index and formats them as a table whose last row holds the
+/// totals. With `highlight` the total row is shown in bold reverse video.
+pub fn format_table(snapshots: &mut [GpuSnapshot], highlight: bool) -> Vec<String> {
+    snapshots.sort_by_key(|snapshot| snapshot.gpu.index);
+    let totals = totals(snapshots);
+
+    let mut table = Table::new(["GPU", "Name", "Utilization", "Memory", "Temperature", "Power"].into_iter().map(Column::new).collect());
+    for snapshot in snapshots.iter() {
+        table.row(vec![
+            snapshot.gpu.index.to_string(),
+            snapshot.gpu.name.clone(),
+            format!("{:.1}%", snapshot.utilization),
+            memory_cell(snapshot.memory_used_mib, snapshot.memory_total_mib),
+            snapshot.temperature_c.map_or("-".to_string(), |temperature| format!("{}°C", temperature)),
+            snapshot.power_w.map_or("-".to_string(), |power| format!("{} W", power)),
+        ]);
+    }
+    table.row(vec![
+        "Total".to_string(),
+        format!("{} GPUs", totals.gpus),
+        format!("{:.1}% mean", totals.mean_utilization),
+        memory_cell(totals.memory_used_mib, totals.memory_total_mib),
+        String::new(),
+        totals.power_w.map_or("-".to_string(), |power| format!("{:.1} W", power)),
+    ]);
+
+    let mut table = table.render();
+    if highlight {
+        let total = table.pop().expect("the table has a total row");
+        table.push(format!("{}{}{}", HIGHLIGHT, total, RESET));
+    }
+
+    table
+}
+
+/// Sorts the snapshots by GPU index and formats them as one JSON object,
+/// `{"gpus": [...], "totals": {...}}`, with the GPU records as `--format json` writes them.
+pub fn format_json(snapshots: &mut [GpuSnapshot], context: &OutputContext) -> String {
+    snapshots.sort_by_key(|snapshot| snapshot.gpu.index);
+    let totals = totals(snapshots);
+
+    let record_context = OutputContext { format: OutputFormat::Json, ..context.clone() };
+    let records: Vec<String> = snapshots.iter().map(|snapshot| format_snapshot(snapshot, &record_context)).collect();
+
+    let mut fields = vec![format!("\"gpus\":{}", totals.gpus), format!("\"mean_utilization\":{}", totals.mean_utilization)];
+    if let Some(used) = totals.memory_used_mib {
+        fields.push(format!("\"memory_used_mib\":{}", used));
+    }
+    if let Some(total) = totals.memory_total_mib {
+        fields.push(format!("\"memory_total_mib\":{}", total));
+    }
+    if let Some(power) = totals.power_w {
+        fields.push(format!("\"power_w\":{}", power));
+    }
+
+    let mut document = format!("{{\"schema_version\":{},\"gpus\":[{}],\"totals\":{{{}}}", SCHEMA_VERSION, records.join(","), fields.join(","));
+    if let Some(hostname) = &context.hostname {
+        document.push_str(&format!(",\"hostname\":{}", json_string(hostname)));
+    }
+    if let Some(tick_seq) = context.tick_seq {
+        document.push_str(&format!(",\"tick_seq\":{}", tick_seq));
+    }
+    if let Some(timestamp) = &context.timestamp {
+        document.push_str(&format!(",\"ts\":{}", timestamp.to_json()));
+    }
+    document.push('}');
+    document
+}
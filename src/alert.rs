@@ -0,0 +1,445 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::idle::format_duration;
+use crate::syslog::format_timestamp;
+use crate::temperature::Sensor;
+use crate::{GpuInfo, GpuSnapshot};
+
+/// Memory usage, in percent of total, at which the built-in "VRAM nearly full" alert fires
+/// unless `--warn-vram` sets another threshold.
+pub const VRAM_NEARLY_FULL_PERCENT: f32 = 95.0;
+
+/// The exit code after a critical alert, unless `--critical-exit-code` sets another.
+pub const DEFAULT_CRITICAL_EXIT_CODE: i32 = 3;
+
+/// Number of samples the VRAM growth trend is computed over.
+const VRAM_TREND_SAMPLES: usize = 5;
+
+/// Minimum growth over the trend window for it to count as rising rather than noise.
+const VRAM_TREND_MIN_GROWTH_MIB: u64 = 64;
+
+/// A drop of more than this fraction of total memory (a freed cache, a finished job)
+/// discards the trend history.
+const VRAM_TREND_RESET_FRACTION: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    Temperature,
+    /// One sensor of `--fields temps`, e.g. the junction.
+    SensorTemperature(Sensor),
+    Utilization,
+    VramNearlyFull,
+}
+
+impl AlertKind {
+    /// The key of the watched metric in event records.
+    pub fn metric(self) -> &'static str {
+        match self {
+            AlertKind::Temperature => "temperature_c",
+            AlertKind::SensorTemperature(sensor) => sensor.alert_name(),
+            AlertKind::Utilization => "utilization",
+            AlertKind::VramNearlyFull => "vram_used_percent",
+        }
+    }
+}
+
+/// The names `--alert-severity` uses, after the options that enable each alert: `temp.junction`
+/// for `--alert-temp junction=...`.
+impl FromStr for AlertKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(sensor) = s.strip_prefix("temp.") {
+            return Ok(AlertKind::SensorTemperature(sensor.parse()?));
+        }
+        Ok(match s {
+            "temp" => AlertKind::Temperature,
+            "util" => AlertKind::Utilization,
+            "vram" => AlertKind::VramNearlyFull,
+            _ => return Err(format!("Unknown alert: {} (expected temp, temp.<sensor>, util or vram)", s)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "info" => Severity::Info,
+            "warning" => Severity::Warning,
+            "critical" => Severity::Critical,
+            _ => return Err(format!("Unknown severity: {} (expected info, warning or critical)", s)),
+        })
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertRule {
+    pub kind: AlertKind,
+    pub threshold: f32,
+    pub severity: Severity,
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub gpu: GpuInfo,
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub value: f32,
+    pub threshold: f32,
+    /// Growth rate and estimated time until memory is full, for rising VRAM usage.
+    pub vram_trend: Option<(f64, Duration)>,
+}
+
+fn format_rate(mib_per_min: f64) -> String {
+    if mib_per_min >= 1024.0 {
+        format!("{:.1} GiB/min", mib_per_min / 1024.0)
+    } else {
+        format!("{:.0} MiB/min", mib_per_min)
+    }
+}
+
+fn format_eta(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        _ => format!("{}h{}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+impl Alert {
+    pub fn message(&self) -> String {
+        match self.kind {
+            AlertKind::Temperature => format!("GPU {} ({}) temperature {}°C exceeds {}°C", self.gpu.index, self.gpu.name, self.value, self.threshold),
+            AlertKind::SensorTemperature(sensor) => {
+                format!("GPU {} ({}) {} temperature {}°C exceeds {}°C", self.gpu.index, self.gpu.name, sensor, self.value, self.threshold)
+            }
+            AlertKind::Utilization => format!("GPU {} ({}) utilization {}% exceeds {}%", self.gpu.index, self.gpu.name, self.value, self.threshold),
+            AlertKind::VramNearlyFull => match self.vram_trend {
+                Some((rate, eta)) => format!(
+                    "GPU {} ({}) VRAM {:.0}% and rising ~{} — full in ~{}",
+                    self.gpu.index,
+                    self.gpu.name,
+                    self.value,
+                    format_rate(rate),
+                    format_eta(eta)
+                ),
+                None => format!("GPU {} ({}) VRAM {:.1}% full", self.gpu.index, self.gpu.name, self.value),
+            },
+        }
+    }
+}
+
+/// Parses `--alert-severity`, e.g. `temp=warning,vram=critical`.
+pub fn parse_severities(value: &str) -> Result<Vec<(AlertKind, Severity)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (kind, severity) = pair.split_once('=').ok_or_else(|| format!("Invalid --alert-severity value: {}", pair))?;
+            Ok((kind.trim().parse()?, severity.trim().parse()?))
+        })
+        .collect()
+}
+
+/// Parses one `--alert-temp` value: a threshold for `temperature_c` (`95`), or for one sensor
+/// (`junction=105`).
+pub fn parse_temperature_threshold(value: &str) -> Result<(Option<Sensor>, f32), String> {
+    let invalid = || format!("Invalid --alert-temp value: {}", value);
+    match value.split_once('=') {
+        Some((sensor, threshold)) => Ok((Some(sensor.trim().parse()?), threshold.trim().parse().map_err(|_| invalid())?)),
+        None => Ok((None, value.trim().parse().map_err(|_| invalid())?)),
+    }
+}
+
+/// The rules enabled on the command line plus the built-in VRAM rule, with the default
+/// severities unless `severities` overrides them. `sensors` are the per-sensor temperature
+/// thresholds, critical like the plain one.
+pub fn rules(
+    temperature: Option<f32>,
+    sensors: &[(Sensor, f32)],
+    utilization: Option<f32>,
+    vram: Option<f32>,
+    severities: &[(AlertKind, Severity)],
+) -> Vec<AlertRule> {
+    let vram = vram.unwrap_or(VRAM_NEARLY_FULL_PERCENT);
+    let mut rules = vec![AlertRule { kind: AlertKind::VramNearlyFull, threshold: vram, severity: Severity::Warning }];
+
+    if let Some(threshold) = temperature {
+        rules.push(AlertRule { kind: AlertKind::Temperature, threshold, severity: Severity::Critical });
+    }
+    for &(sensor, threshold) in sensors {
+        rules.push(AlertRule { kind: AlertKind::SensorTemperature(sensor), threshold, severity: Severity::Critical });
+    }
+    if let Some(threshold) = utilization {
+        rules.push(AlertRule { kind: AlertKind::Utilization, threshold, severity: Severity::Info });
+    }
+    for rule in &mut rules {
+        if let Some((_, severity)) = severities.iter().rev().find(|(kind, _)| *kind == rule.kind) {
+            rule.severity = *severity;
+        }
+    }
+
+    rules
+}
+
+fn metric(kind: AlertKind, snapshot: &GpuSnapshot) -> Option<f32> {
+    match kind {
+        AlertKind::Temperature => snapshot.temperature_c,
+        AlertKind::SensorTemperature(sensor) => snapshot.temperatures.as_ref()?.get(&sensor).copied(),
+        AlertKind::Utilization => Some(snapshot.utilization as f32),
+        AlertKind::VramNearlyFull => match (snapshot.memory_used_mib, snapshot.memory_total_mib) {
+            (Some(used), Some(total)) if total > 0 => Some(used as f32 * 100.0 / total as f32),
+            _ => None,
+        },
+    }
+}
+
+/// Parses a percentage such as `90%` or `90`.
+pub fn parse_percent(value: &str) -> Result<f32, String> {
+    let percent: f32 = value.trim().trim_end_matches('%').parse().map_err(|_| format!("Invalid percentage: {}", value))?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(format!("Percentage out of range: {}", value));
+    }
+    Ok(percent)
+}
+
+/// Short-horizon VRAM usage history of one GPU, used to estimate when memory runs out.
+#[derive(Debug, Default)]
+pub struct VramTrend {
+    samples: VecDeque<(f64, u64)>,
+}
+
+impl VramTrend {
+    /// Records memory used at `seconds` since some fixed origin.
+    pub fn record(&mut self, seconds: f64, used_mib: u64, total_mib: u64) {
+        if let Some(&(_, last)) = self.samples.back() {
+            if last.saturating_sub(used_mib) as f64 > total_mib as f64 * VRAM_TREND_RESET_FRACTION {
+                self.samples.clear();
+            }
+        }
+
+        self.samples.push_back((seconds, used_mib));
+        if self.samples.len() > VRAM_TREND_SAMPLES {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Growth in MiB per minute, when usage has not decreased over a full window and grew by
+    /// more than noise.
+    pub fn growth_mib_per_min(&self) -> Option<f64> {
+        if self.samples.len() < VRAM_TREND_SAMPLES {
+            return None;
+        }
+
+        let monotonic = self.samples.iter().zip(self.samples.iter().skip(1)).all(|((_, a), (_, b))| b >= a);
+        let (first_time, first_used) = *self.samples.front()?;
+        let (last_time, last_used) = *self.samples.back()?;
+        if !monotonic || last_used - first_used < VRAM_TREND_MIN_GROWTH_MIB || last_time <= first_time {
+            return None;
+        }
+
+        Some((last_used - first_used) as f64 * 60.0 / (last_time - first_time))
+    }
+
+    /// Growth rate and estimated time until `total_mib` is reached.
+    pub fn time_to_full(&self, total_mib: u64) -> Option<(f64, Duration)> {
+        let rate = self.growth_mib_per_min()?;
+        let (_, used) = *self.samples.back()?;
+        let minutes = total_mib.saturating_sub(used) as f64 / rate;
+        Some((rate, Duration::from_secs_f64(minutes * 60.0)))
+    }
+}
+
+/// One alert from the sample that crossed its threshold to the first one back below it, so a
+/// 10-minute thermal event is one event of 10 minutes.
+#[derive(Debug, Clone)]
+pub struct AlertEvent {
+    pub gpu: GpuInfo,
+    pub kind: AlertKind,
+    pub severity: Severity,
+    pub threshold: f32,
+    /// The worst value of the event.
+    pub peak: f32,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    /// Whether the condition still held when the history was taken.
+    pub ongoing: bool,
+}
+
+impl AlertEvent {
+    /// The exit summary line: `2026-10-16T14:03:12.512Z critical: GPU 0 temperature above
+    /// 85°C for 00:10:00, peak 91°C`.
+    pub fn format_summary(&self) -> String {
+        let (metric, unit) = match self.kind {
+            AlertKind::Temperature => ("temperature".to_string(), "°C"),
+            AlertKind::SensorTemperature(sensor) => (format!("{} temperature", sensor), "°C"),
+            AlertKind::Utilization => ("utilization".to_string(), "%"),
+            AlertKind::VramNearlyFull => ("VRAM".to_string(), "%"),
+        };
+
+        format!(
+            "{} {}: GPU {} {} above {}{} for {}, peak {}{}{}",
+            format_timestamp(self.started_at),
+            self.severity,
+            self.gpu.index,
+            metric,
+            self.threshold,
+            unit,
+            format_duration(self.duration),
+            self.peak,
+            unit,
+            if self.ongoing { " (still active at exit)" } else { "" }
+        )
+    }
+}
+
+/// An alert whose condition holds.
+#[derive(Debug, Clone)]
+struct Episode {
+    alert: Alert,
+    started: Instant,
+    started_at: SystemTime,
+    peak: f32,
+}
+
+impl Episode {
+    fn event(&self, now: Instant, ongoing: bool) -> AlertEvent {
+        AlertEvent {
+            gpu: self.alert.gpu.clone(),
+            kind: self.alert.kind,
+            severity: self.alert.severity,
+            threshold: self.alert.threshold,
+            peak: self.peak,
+            started_at: self.started_at,
+            duration: now.saturating_duration_since(self.started),
+            ongoing,
+        }
+    }
+}
+
+/// What one sample changed: the alerts whose condition started to hold, and the events whose
+/// condition stopped, each with the alert that opened it.
+#[derive(Debug, Default)]
+pub struct AlertUpdate {
+    pub fired: Vec<Alert>,
+    pub resolved: Vec<(Alert, AlertEvent)>,
+}
+
+/// Evaluates alert rules and reports an alert only when its condition starts to hold, so a
+/// GPU staying hot does not fire every tick. Every alert is kept in the history, with the
+/// time its condition held.
+#[derive(Debug)]
+pub struct AlertTracker {
+    rules: Vec<AlertRule>,
+    active: HashMap<(u32, AlertKind), Episode>,
+    history: Vec<AlertEvent>,
+    started: Instant,
+    vram_trends: HashMap<u32, VramTrend>,
+}
+
+impl AlertTracker {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        AlertTracker { rules, active: HashMap::new(), history: Vec::new(), started: Instant::now(), vram_trends: HashMap::new() }
+    }
+
+    pub fn update(&mut self, snapshot: &GpuSnapshot) -> AlertUpdate {
+        self.update_at(snapshot, Instant::now(), SystemTime::now())
+    }
+
+    /// [`AlertTracker::update`] for a sample taken at `now`, `wall_clock` in real time.
+    pub fn update_at(&mut self, snapshot: &GpuSnapshot, now: Instant, wall_clock: SystemTime) -> AlertUpdate {
+        let mut update = AlertUpdate::default();
+
+        let vram_trend = match (snapshot.memory_used_mib, snapshot.memory_total_mib) {
+            (Some(used), Some(total)) => {
+                let trend = self.vram_trends.entry(snapshot.gpu.index).or_default();
+                trend.record(now.saturating_duration_since(self.started).as_secs_f64(), used, total);
+                trend.time_to_full(total)
+            }
+            _ => None,
+        };
+
+        for rule in &self.rules {
+            let key = (snapshot.gpu.index, rule.kind);
+
+            match metric(rule.kind, snapshot) {
+                Some(value) if value >= rule.threshold => match self.active.get_mut(&key) {
+                    Some(episode) => episode.peak = episode.peak.max(value),
+                    None => {
+                        let alert = Alert {
+                            gpu: snapshot.gpu.clone(),
+                            kind: rule.kind,
+                            severity: rule.severity,
+                            value,
+                            threshold: rule.threshold,
+                            vram_trend: if rule.kind == AlertKind::VramNearlyFull { vram_trend } else { None },
+                        };
+                        self.active.insert(key, Episode { alert: alert.clone(), started: now, started_at: wall_clock, peak: value });
+                        update.fired.push(alert);
+                    }
+                },
+                _ => {
+                    if let Some(episode) = self.active.remove(&key) {
+                        let event = episode.event(now, false);
+                        self.history.push(event.clone());
+                        update.resolved.push((episode.alert, event));
+                    }
+                }
+            }
+        }
+
+        update
+    }
+
+    /// Every alert so far in the order they fired, those still active lasting until `now`.
+    pub fn history(&self, now: Instant) -> Vec<AlertEvent> {
+        let mut history = self.history.clone();
+        history.extend(self.active.values().map(|episode| episode.event(now, true)));
+        history.sort_by_key(|event| event.started_at);
+        history
+    }
+
+    /// Whether a critical alert fired at any point.
+    pub fn critical_fired(&self) -> bool {
+        self.history
+            .iter()
+            .map(|event| event.severity)
+            .chain(self.active.values().map(|episode| episode.alert.severity))
+            .any(|severity| severity == Severity::Critical)
+    }
+}
+
+/// The exit summary section listing every alert, empty when none fired.
+pub fn format_history(history: &[AlertEvent]) -> Vec<String> {
+    if history.is_empty() {
+        return Vec::new();
+    }
+
+    let mut lines = vec!["Alerts:".to_string()];
+    lines.extend(history.iter().map(|event| format!("  {}", event.format_summary())));
+    lines
+}
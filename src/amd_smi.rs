@@ -0,0 +1,207 @@
+//! amd-smi, the ROCm CLI that replaces rocm-smi, read through its JSON output: `amd-smi list`
+//! for the devices and their PCI addresses, `amd-smi metric` for the samples.
+//!
+//! The metric schema has changed between releases: amd-smi 24.x wraps every reading in
+//! `{"value": 42, "unit": "%"}`, where earlier releases printed bare numbers, and both spell
+//! a missing sensor as `"N/A"`. Every reading is looked up by name and may be missing.
+//!
+//! amd-smi numbers the devices in its own order, which need not be the DRM card order or the
+//! order gpuatop detected them in, so samples are matched to GPUs by PCI address.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::backend::{results_for, Backend, Cost, RawOutput};
+use crate::json::{self, Value};
+use crate::pci;
+use crate::runner::{retry_command, CommandRunner, Retry};
+use crate::temperature::{Sensor, Temperatures};
+use crate::{clamp_percent, GpuInfo, GpuSnapshot, MemoryBandwidthMetrics, PollResult};
+
+pub const LIST_ARGS: [&str; 2] = ["list", "--json"];
+
+/// Only the metric groups gpuatop reports: a full `amd-smi metric` also reads ECC, PCIe and
+/// XGMI counters, which takes several times longer.
+pub const METRIC_ARGS: [&str; 6] = ["metric", "--usage", "--mem-usage", "--temperature", "--power", "--json"];
+
+/// A device as `amd-smi list` reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    /// amd-smi's own index, the `gpu` key of every record.
+    pub index: u32,
+    /// Normalized with [`pci::normalize_bus_id`].
+    pub bus_id: String,
+}
+
+fn member<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+/// The records of a document: a top-level array, or the `gpu_data` array some releases wrap
+/// it in.
+fn records(document: &Value) -> Option<&[Value]> {
+    match document {
+        Value::Array(records) => Some(records),
+        document => match member(document, "gpu_data")? {
+            Value::Array(records) => Some(records),
+            _ => None,
+        },
+    }
+}
+
+fn index(record: &Value) -> Option<u32> {
+    match member(record, "gpu")? {
+        Value::Number(index) if *index >= 0.0 => Some(*index as u32),
+        _ => None,
+    }
+}
+
+/// A reading in any of the shapes amd-smi has printed: `{"value": 42, "unit": "%"}`, `42`,
+/// or `"42 %"`. `"N/A"` and anything else unparsable is `None`.
+fn reading(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => Some(*number),
+        Value::String(text) => text.split_whitespace().next()?.parse().ok(),
+        Value::Object(_) => reading(member(value, "value")?),
+        _ => None,
+    }
+}
+
+fn metric(record: &Value, group: &str, name: &str) -> Option<f64> {
+    reading(member(member(record, group)?, name)?)
+}
+
+/// Parses `amd-smi list --json`.
+pub fn parse_list(output: &str) -> Result<Vec<Device>, String> {
+    let document = json::parse(output).map_err(|err| format!("Invalid amd-smi list output: {}", err))?;
+    let records = records(&document).ok_or("Unexpected amd-smi list output: no device list")?;
+
+    Ok(records
+        .iter()
+        .filter_map(|record| {
+            let Some(Value::String(bdf)) = member(record, "bdf") else { return None };
+            Some(Device { index: index(record)?, bus_id: pci::normalize_bus_id(bdf) })
+        })
+        .collect())
+}
+
+/// The GPUs of `amd-smi list`, in amd-smi's order and under its indices.
+pub fn gpus(devices: &[Device]) -> Vec<GpuInfo> {
+    devices.iter().map(|device| GpuInfo { index: device.index, name: "Amd GPU".to_string(), bus_id: Some(device.bus_id.clone()), render_offload: None }).collect()
+}
+
+/// The GPU a record of amd-smi's device `index` belongs to: the one at the same PCI address,
+/// or, for GPUs detected without one, the one with the same index.
+fn find_gpu<'g>(index: u32, devices: &[Device], gpus: &'g [GpuInfo]) -> Option<&'g GpuInfo> {
+    let bus_id = devices.iter().find(|device| device.index == index).map(|device| device.bus_id.as_str());
+    gpus.iter().find(|gpu| match (&gpu.bus_id, bus_id) {
+        (Some(gpu_bus_id), Some(bus_id)) => pci::normalize_bus_id(gpu_bus_id) == bus_id,
+        _ => gpu.index == index,
+    })
+}
+
+fn parse_record(record: &Value, gpu: &GpuInfo) -> Result<GpuSnapshot, String> {
+    let utilization = metric(record, "usage", "gfx_activity").ok_or_else(|| format!("No gfx_activity for GPU {} in amd-smi output", gpu.index))?;
+
+    let temperatures: Temperatures = [("edge", Sensor::Edge), ("hotspot", Sensor::Junction), ("mem", Sensor::Mem)]
+        .into_iter()
+        .filter_map(|(name, sensor)| Some((sensor, metric(record, "temperature", name)? as f32)))
+        .collect();
+    // Instinct accelerators have no edge sensor; their hotspot is the one that matters.
+    let temperature_c = temperatures.get(&Sensor::Edge).or_else(|| temperatures.get(&Sensor::Junction)).copied();
+
+    Ok(GpuSnapshot {
+        memory_used_mib: metric(record, "mem_usage", "used_vram").map(|mb| mb as u64),
+        memory_total_mib: metric(record, "mem_usage", "total_vram").map(|mb| mb as u64),
+        temperature_c,
+        power_w: metric(record, "power", "socket_power").or_else(|| metric(record, "power", "average_socket_power")).map(|watts| watts as f32),
+        memory_bandwidth: metric(record, "usage", "umc_activity")
+            .map(|percent| MemoryBandwidthMetrics { utilization_pct: Some(clamp_percent(percent)), ..Default::default() }),
+        temperatures: (!temperatures.is_empty()).then_some(temperatures),
+        ..GpuSnapshot::new(gpu.clone(), clamp_percent(utilization))
+    })
+}
+
+/// Parses `amd-smi metric --json` into a snapshot per GPU, keyed by the GPU's index in `gpus`.
+/// Records of devices that are not in `gpus` are skipped.
+pub fn parse_metrics(output: &str, devices: &[Device], gpus: &[GpuInfo]) -> Result<HashMap<u32, GpuSnapshot>, String> {
+    let document = json::parse(output).map_err(|err| format!("Invalid amd-smi metric output: {}", err))?;
+    let records = records(&document).ok_or("Unexpected amd-smi metric output: no device list")?;
+
+    let mut snapshots = HashMap::new();
+    for record in records {
+        let Some(gpu) = index(record).and_then(|index| find_gpu(index, devices, gpus)) else { continue };
+        snapshots.insert(gpu.index, parse_record(record, gpu)?);
+    }
+    Ok(snapshots)
+}
+
+/// Runs `amd-smi metric` once per tick. Preferred over radeontop where amd-smi is installed:
+/// it reports VRAM totals, power and every temperature sensor, and needs no root.
+pub struct AmdSmiBackend<'r> {
+    runner: &'r dyn CommandRunner,
+    devices: Vec<Device>,
+    retry: Retry,
+    last_output: Option<RawOutput>,
+}
+
+impl<'r> AmdSmiBackend<'r> {
+    /// Lists the devices; fails where amd-smi is missing or sees no GPU.
+    pub fn open(runner: &'r dyn CommandRunner) -> io::Result<Self> {
+        let output = runner.run("amd-smi", &LIST_ARGS)?;
+        if !output.success {
+            return Err(io::Error::other(format!("amd-smi list failed: {}", output.stderr.trim())));
+        }
+        let devices = parse_list(&output.stdout).map_err(io::Error::other)?;
+        if devices.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "amd-smi lists no GPU"));
+        }
+
+        Ok(AmdSmiBackend { runner, devices, retry: Retry::default(), last_output: None })
+    }
+
+    /// How often a failed `amd-smi metric` is attempted within one poll.
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+impl Backend for AmdSmiBackend<'_> {
+    fn name(&self) -> &'static str {
+        "amd-smi (per tick)"
+    }
+
+    fn source(&self) -> &'static str {
+        "amd-smi"
+    }
+
+    fn cost(&self) -> Cost {
+        Cost::SpawnPerTick
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        let error = |message: String| gpus.iter().map(|gpu| PollResult::TransientError { gpu: gpu.clone(), message: message.clone(), retries: 0 }).collect();
+
+        let output = match retry_command(self.runner, "amd-smi", &METRIC_ARGS, self.retry.max_attempts, self.retry.delay) {
+            Ok(output) => output,
+            Err(err) => return error(err.to_string()),
+        };
+        self.last_output = Some(RawOutput::from(&output));
+        if !output.success {
+            return error(format!("amd-smi metric failed: {}", output.stderr.trim()));
+        }
+
+        match parse_metrics(&output.stdout, &self.devices, gpus) {
+            Ok(snapshots) => results_for(gpus, snapshots, "No sample for the GPU in the amd-smi output"),
+            Err(message) => error(message),
+        }
+    }
+
+    fn last_output(&self) -> Option<RawOutput> {
+        self.last_output.clone()
+    }
+}
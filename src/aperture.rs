@@ -0,0 +1,104 @@
+//! `--fields bar1,vis_vram`: the CPU-visible part of video memory. Without resizable BAR the
+//! CPU maps VRAM through a small aperture (BAR1 on NVIDIA, visible VRAM on amdgpu), so an
+//! allocation can fail with plenty of VRAM free once that aperture is full.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::runner::CommandRunner;
+
+const SYSFS_DRM: &str = "/sys/class/drm";
+
+/// Aperture figures of one GPU. Each vendor reports a different subset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApertureMetrics {
+    /// VRAM the NVIDIA driver reserves for itself (`memory.reserved`).
+    pub reserved_mib: Option<u64>,
+    pub bar1_used_mib: Option<u64>,
+    pub bar1_total_mib: Option<u64>,
+    /// amdgpu's CPU-visible VRAM (`mem_info_vis_vram_used`).
+    pub vis_vram_used_mib: Option<u64>,
+    pub vis_vram_total_mib: Option<u64>,
+}
+
+/// `Total : 256 MiB` → 256.
+fn mib_value(value: &str) -> Option<u64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Parses `nvidia-smi -q -d MEMORY`: the `Reserved` line of each GPU's "FB Memory Usage"
+/// section and the "BAR1 Memory Usage" section. GPUs are keyed by their position in the
+/// report, which is nvidia-smi's index order.
+pub fn parse_nvidia_memory(output: &str) -> HashMap<u32, ApertureMetrics> {
+    let mut metrics: HashMap<u32, ApertureMetrics> = HashMap::new();
+    let mut gpu: Option<u32> = None;
+    let mut section = "";
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("GPU ") && !line.starts_with(' ') {
+            gpu = Some(gpu.map_or(0, |index| index + 1));
+            section = "";
+            continue;
+        }
+        let Some(gpu) = gpu else { continue };
+
+        match trimmed.split_once(':') {
+            None if !trimmed.is_empty() => section = trimmed,
+            None => {}
+            Some((key, value)) => {
+                let entry = metrics.entry(gpu).or_default();
+                match (section, key.trim()) {
+                    ("FB Memory Usage", "Reserved") => entry.reserved_mib = mib_value(value),
+                    ("BAR1 Memory Usage", "Used") => entry.bar1_used_mib = mib_value(value),
+                    ("BAR1 Memory Usage", "Total") => entry.bar1_total_mib = mib_value(value),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    metrics.retain(|_, entry| *entry != ApertureMetrics::default());
+    metrics
+}
+
+pub fn query_nvidia(runner: &dyn CommandRunner) -> HashMap<u32, ApertureMetrics> {
+    match runner.run("nvidia-smi", &["-q", "-d", "MEMORY"]) {
+        Ok(output) if output.success => parse_nvidia_memory(&output.stdout),
+        _ => HashMap::new(),
+    }
+}
+
+fn read_mib(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse::<u64>().ok().map(|bytes| bytes / (1024 * 1024))
+}
+
+/// Reads `mem_info_vis_vram_used` and `mem_info_vis_vram_total` from an amdgpu `device`
+/// directory.
+pub fn read_vis_vram(device: &Path) -> Option<ApertureMetrics> {
+    let used = read_mib(&device.join("mem_info_vis_vram_used"));
+    let total = read_mib(&device.join("mem_info_vis_vram_total"));
+
+    (used.is_some() || total.is_some()).then_some(ApertureMetrics { vis_vram_used_mib: used, vis_vram_total_mib: total, ..Default::default() })
+}
+
+/// Visible VRAM of every amdgpu card, keyed by the card's position among the cards that report
+/// it, as the sysfs backend numbers them.
+pub fn query_amdgpu() -> HashMap<u32, ApertureMetrics> {
+    let Ok(entries) = fs::read_dir(SYSFS_DRM) else {
+        return HashMap::new();
+    };
+
+    let mut cards: Vec<(u32, ApertureMetrics)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let number = entry.file_name().to_str()?.strip_prefix("card")?.parse().ok()?;
+            Some((number, read_vis_vram(&entry.path().join("device"))?))
+        })
+        .collect();
+    cards.sort_by_key(|(number, _)| *number);
+
+    cards.into_iter().enumerate().map(|(index, (_, metrics))| (index as u32, metrics)).collect()
+}
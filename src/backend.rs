@@ -0,0 +1,1060 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::amd_smi::AmdSmiBackend;
+use crate::config::{ConfigValue, Document};
+use crate::csv;
+use crate::rate::{Moment, Rate, RateTracker};
+use crate::runner::{CommandOutput, CommandRunner, Retry};
+use crate::videocore::VideoCoreBackend;
+use crate::{clamp_percent, parse_intel_gpu_top_output, parse_nvidia_smi_output, parse_tegrastats_output, poll_gpus_capturing, GpuInfo, GpuSnapshot, GpuType, MemoryBandwidthMetrics, PollResult, NVIDIA_SMI_QUERY};
+
+const SYSFS_DRM: &str = "/sys/class/drm";
+
+/// The energy counter of the RAPL MMIO package domain, in microjoules.
+const RAPL_MMIO_ENERGY: &str = "/sys/class/powercap/intel-rapl-mmio/energy_uj";
+
+/// How long the first poll of a streaming source waits for the child's first sample.
+const STREAM_STARTUP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Lines of raw tool output kept in a [`ProbeError`].
+pub const PROBE_OUTPUT_LINES: usize = 10;
+
+/// Relative cost of collecting one sample, cheapest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Cost {
+    /// Plain file reads, no process involved.
+    Sysfs,
+    /// One long-lived child process that reports every interval.
+    Streaming,
+    /// A new vendor tool process every tick.
+    SpawnPerTick,
+}
+
+/// What a vendor tool printed, and its exit code when it exited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawOutput {
+    pub text: String,
+    pub code: Option<i32>,
+}
+
+impl From<&CommandOutput> for RawOutput {
+    fn from(output: &CommandOutput) -> Self {
+        let text = [output.stdout.trim_end(), output.stderr.trim_end()].into_iter().filter(|text| !text.is_empty()).collect::<Vec<_>>().join("\n");
+        RawOutput { text, code: output.code }
+    }
+}
+
+/// A source of per-GPU metrics for one vendor.
+pub trait Backend {
+    fn name(&self) -> &'static str;
+    /// The `source` of the samples, one of [`SOURCES`]: what `--backend` selects and JSON
+    /// records carry.
+    fn source(&self) -> &'static str;
+    fn cost(&self) -> Cost;
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult>;
+
+    /// The tool output behind the last poll, for diagnostics. Sources reading files have none.
+    fn last_output(&self) -> Option<RawOutput> {
+        None
+    }
+}
+
+pub(crate) fn results_for(gpus: &[GpuInfo], mut snapshots: HashMap<u32, GpuSnapshot>, missing: &str) -> Vec<PollResult> {
+    gpus.iter()
+        .map(|gpu| match snapshots.remove(&gpu.index) {
+            Some(snapshot) => PollResult::Ok { snapshot, retries: 0 },
+            None => PollResult::TransientError { gpu: gpu.clone(), message: missing.to_string(), retries: 0 },
+        })
+        .collect()
+}
+
+/// Runs the vendor tool once per tick; always available, but the most expensive.
+pub struct SpawnBackend<'r> {
+    runner: &'r dyn CommandRunner,
+    gpu_type: GpuType,
+    retry: Retry,
+    last_output: Option<RawOutput>,
+}
+
+impl<'r> SpawnBackend<'r> {
+    pub fn new(runner: &'r dyn CommandRunner, gpu_type: &GpuType) -> Self {
+        SpawnBackend { runner, gpu_type: gpu_type.clone(), retry: Retry::default(), last_output: None }
+    }
+
+    /// How often a failed run of the vendor tool is attempted within one poll.
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+impl Backend for SpawnBackend<'_> {
+    fn name(&self) -> &'static str {
+        match self.gpu_type {
+            GpuType::Nvidia => "nvidia-smi (per tick)",
+            GpuType::Amd => "radeontop (per tick)",
+            GpuType::Intel => "intel_gpu_top (per tick)",
+            GpuType::JetsonGpu => "tegrastats (per tick)",
+            GpuType::AmdApu => "amdgpu_pm_info (debugfs)",
+            GpuType::IntelArc(_) | GpuType::Broadcom(_) | GpuType::Unknown(_) => "no vendor tool",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self.gpu_type {
+            GpuType::AmdApu => "amdgpu_pm_info",
+            _ => self.gpu_type.top_tool().unwrap_or("none"),
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        match self.gpu_type {
+            GpuType::AmdApu => Cost::Sysfs,
+            _ => Cost::SpawnPerTick,
+        }
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        let (results, output) = poll_gpus_capturing(self.runner, &self.gpu_type, gpus, self.retry);
+        self.last_output = output;
+        results
+    }
+
+    fn last_output(&self) -> Option<RawOutput> {
+        self.last_output.clone()
+    }
+}
+
+/// Keeps one vendor tool running in its own sampling loop and reads its latest report.
+#[derive(Debug)]
+pub struct StreamingBackend {
+    gpu_type: GpuType,
+    interval: Duration,
+    child: Child,
+    lines: Receiver<String>,
+    /// Column header lines (intel_gpu_top prints two before the samples).
+    header: Vec<String>,
+    /// Latest sample line per GPU index (nvidia-smi) or the latest line overall (index 0).
+    latest: HashMap<u32, String>,
+}
+
+impl StreamingBackend {
+    fn command(gpu_type: &GpuType, interval: Duration) -> Option<(&'static str, Vec<String>)> {
+        let milliseconds = interval.as_millis().max(1).to_string();
+
+        match gpu_type {
+            GpuType::Nvidia => Some((
+                "nvidia-smi",
+                vec![
+                    NVIDIA_SMI_QUERY.to_string(),
+                    "--format=csv,noheader,nounits".to_string(),
+                    "-lms".to_string(),
+                    milliseconds,
+                ],
+            )),
+            GpuType::Intel => Some(("intel_gpu_top", vec!["-s".to_string(), milliseconds, "-o".to_string(), "-".to_string()])),
+            GpuType::JetsonGpu => Some(("tegrastats", vec!["--interval".to_string(), milliseconds])),
+            GpuType::Amd | GpuType::AmdApu | GpuType::IntelArc(_) | GpuType::Broadcom(_) | GpuType::Unknown(_) => None,
+        }
+    }
+
+    fn spawn(gpu_type: &GpuType, interval: Duration) -> io::Result<(Child, Receiver<String>)> {
+        let (name, args) = Self::command(gpu_type, interval).ok_or_else(|| io::Error::other("No streaming source for this vendor"))?;
+        let mut child = Command::new(name).args(args).stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok((child, lines))
+    }
+
+    pub fn open(gpu_type: &GpuType, interval: Duration) -> io::Result<Self> {
+        let (child, lines) = Self::spawn(gpu_type, interval)?;
+        Ok(StreamingBackend { gpu_type: gpu_type.clone(), interval, child, lines, header: Vec::new(), latest: HashMap::new() })
+    }
+
+    fn accept(&mut self, line: String) {
+        match self.gpu_type {
+            GpuType::Intel if self.header.len() < 2 => self.header.push(line),
+            GpuType::Nvidia => {
+                if let Some(index) = csv::parse_line(&line).first().and_then(|index| csv::number(index)) {
+                    self.latest.insert(index, line);
+                }
+            }
+            _ => {
+                self.latest.insert(0, line);
+            }
+        }
+    }
+
+    /// Drains the lines reported since the last poll; restarts the child if it has exited.
+    fn drain(&mut self) -> Result<(), String> {
+        if self.latest.is_empty() {
+            match self.lines.recv_timeout(STREAM_STARTUP_TIMEOUT) {
+                Ok(line) => self.accept(line),
+                Err(RecvTimeoutError::Timeout) => return Err("No data from metrics stream yet".to_string()),
+                Err(RecvTimeoutError::Disconnected) => {}
+            }
+        }
+
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => self.accept(line),
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => {
+                    let _ = self.child.wait();
+                    let (child, lines) = Self::spawn(&self.gpu_type, self.interval).map_err(|err| format!("Metrics stream ended: {}", err))?;
+                    self.child = child;
+                    self.lines = lines;
+                    self.header.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for StreamingBackend {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Backend for StreamingBackend {
+    fn name(&self) -> &'static str {
+        match self.gpu_type {
+            GpuType::Intel => "intel_gpu_top (streaming)",
+            GpuType::JetsonGpu => "tegrastats (streaming)",
+            _ => "nvidia-smi (streaming)",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self.gpu_type {
+            GpuType::Intel => "intel_gpu_top:stream",
+            GpuType::JetsonGpu => "tegrastats:stream",
+            _ => "nvidia-smi:stream",
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost::Streaming
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        if let Err(message) = self.drain() {
+            return gpus.iter().map(|gpu| PollResult::TransientError { gpu: gpu.clone(), message: message.clone(), retries: 0 }).collect();
+        }
+
+        let snapshots = match self.gpu_type {
+            GpuType::Nvidia => {
+                let output: Vec<&str> = self.latest.values().map(String::as_str).collect();
+                parse_nvidia_smi_output(&output.join("\n"), gpus).unwrap_or_default()
+            }
+            GpuType::JetsonGpu => {
+                let output = self.latest.get(&0).cloned().unwrap_or_default();
+                gpus.iter().filter_map(|gpu| Some((gpu.index, parse_tegrastats_output(&output, gpu).ok()?))).collect()
+            }
+            _ => {
+                let mut output = self.header.clone();
+                output.extend(self.latest.get(&0).cloned());
+                let output = output.join("\n");
+                gpus.iter().filter_map(|gpu| Some((gpu.index, parse_intel_gpu_top_output(&output, gpu).ok()?))).collect()
+            }
+        };
+
+        results_for(gpus, snapshots, "No sample in metrics stream")
+    }
+
+    fn last_output(&self) -> Option<RawOutput> {
+        let mut lines = self.header.clone();
+        lines.extend(self.latest.values().cloned());
+        Some(RawOutput { text: lines.join("\n"), code: None })
+    }
+}
+
+/// Reads DRM metrics straight from sysfs (`gpu_busy_percent` and friends). amdgpu exposes
+/// them all; other drivers may only have some.
+#[derive(Debug)]
+pub struct SysfsBackend {
+    /// `device` directories of the cards, in card order.
+    devices: Vec<PathBuf>,
+    name: &'static str,
+}
+
+/// Whether the kernel exposes any DRM card, whatever its driver.
+pub fn drm_card_exists() -> bool {
+    fs::read_dir(SYSFS_DRM)
+        .map(|entries| entries.filter_map(Result::ok).any(|entry| entry.file_name().to_str().and_then(|name| name.strip_prefix("card")).is_some_and(|number| number.parse::<u32>().is_ok())))
+        .unwrap_or(false)
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn hwmon_value(device: &Path, file: &str) -> Option<u64> {
+    fs::read_dir(device.join("hwmon")).ok()?.find_map(|entry| read_number(&entry.ok()?.path().join(file)))
+}
+
+/// The position among the `device` directories of the card at the GPU's PCI address, else of
+/// the card at its index.
+fn device_position<'a>(mut devices: impl Iterator<Item = &'a PathBuf> + Clone, gpu: &GpuInfo) -> Option<usize> {
+    let at_bus_id = gpu.bus_id.as_deref().and_then(|bus_id| {
+        devices.clone().position(|device| fs::canonicalize(device).is_ok_and(|path| path.file_name().and_then(|name| name.to_str()) == Some(bus_id)))
+    });
+    at_bus_id.or_else(|| devices.nth(gpu.index as usize).map(|_| gpu.index as usize))
+}
+
+impl SysfsBackend {
+    pub fn open() -> io::Result<Self> {
+        let mut cards: Vec<(u32, PathBuf)> = fs::read_dir(SYSFS_DRM)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let number = entry.file_name().to_str()?.strip_prefix("card")?.parse().ok()?;
+                let device = entry.path().join("device");
+                device.join("gpu_busy_percent").exists().then_some((number, device))
+            })
+            .collect();
+
+        if cards.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No GPU exposes gpu_busy_percent"));
+        }
+
+        cards.sort();
+        let amdgpu = fs::read_link(cards[0].1.join("driver")).is_ok_and(|driver| driver.ends_with("amdgpu"));
+        let name = if amdgpu { "amdgpu sysfs" } else { "DRM sysfs" };
+        Ok(SysfsBackend { devices: cards.into_iter().map(|(_, device)| device).collect(), name })
+    }
+
+    fn read(&self, gpu: &GpuInfo) -> Option<GpuSnapshot> {
+        let device = &self.devices[device_position(self.devices.iter(), gpu)?];
+
+        let utilization = clamp_percent(read_number(&device.join("gpu_busy_percent"))? as f64);
+        Some(GpuSnapshot {
+            memory_used_mib: read_number(&device.join("mem_info_vram_used")).map(|bytes| bytes / (1024 * 1024)),
+            memory_total_mib: read_number(&device.join("mem_info_vram_total")).map(|bytes| bytes / (1024 * 1024)),
+            temperature_c: hwmon_value(device, "temp1_input").map(|millidegrees| millidegrees as f32 / 1000.0),
+            power_w: hwmon_value(device, "power1_average").map(|microwatts| microwatts as f32 / 1_000_000.0),
+            memory_bandwidth: read_number(&device.join("mem_busy_percent"))
+                .map(|percent| MemoryBandwidthMetrics { utilization_pct: Some(clamp_percent(percent as f64)), ..Default::default() }),
+            ..GpuSnapshot::new(gpu.clone(), utilization)
+        })
+    }
+}
+
+impl Backend for SysfsBackend {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn source(&self) -> &'static str {
+        "sysfs:gpu_busy_percent"
+    }
+
+    fn cost(&self) -> Cost {
+        Cost::Sysfs
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        let snapshots = gpus.iter().filter_map(|gpu| Some((gpu.index, self.read(gpu)?))).collect();
+        results_for(gpus, snapshots, "Failed to read gpu_busy_percent")
+    }
+}
+
+/// One GT (graphics tile) of an Intel card, where the driver reports how long it sat idle.
+#[derive(Debug, Clone)]
+struct IntelGt {
+    name: String,
+    /// Cumulative idle time in milliseconds: RC6 residency on i915, `gtidle` on xe.
+    idle_ms: PathBuf,
+    /// Frequency files, most accurate first: the actual frequency, then the requested one.
+    freq_mhz: Vec<PathBuf>,
+}
+
+/// The GTs of an Intel card: `gt/gt*` under i915, `device/tile*/gt*` under xe. i915 GTs with
+/// RC6 disabled are left out, as their residency never moves.
+fn intel_gts(card: &Path) -> Vec<IntelGt> {
+    let subdirectories = |path: &Path, prefix: &str| -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(path)
+            .map(|entries| entries.filter_map(Result::ok).filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with(prefix))).map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        paths.sort();
+        paths
+    };
+    let name = |gt: &Path| gt.strip_prefix(card).unwrap_or(gt).display().to_string();
+
+    let i915 = subdirectories(&card.join("gt"), "gt").into_iter().filter(|gt| read_number(&gt.join("rc6_enable")) != Some(0)).map(|gt| IntelGt {
+        name: name(&gt),
+        idle_ms: gt.join("rc6_residency_ms"),
+        freq_mhz: vec![gt.join("rps_act_freq_mhz"), card.join("gt_act_freq_mhz"), gt.join("rps_cur_freq_mhz")],
+    });
+    let xe = subdirectories(&card.join("device"), "tile").into_iter().flat_map(|tile| subdirectories(&tile, "gt")).map(|gt| IntelGt {
+        name: name(&gt),
+        idle_ms: gt.join("gtidle/idle_residency_ms"),
+        freq_mhz: vec![gt.join("freq0/act_freq"), gt.join("freq0/cur_freq")],
+    });
+
+    i915.chain(xe).filter(|gt| gt.idle_ms.exists()).collect()
+}
+
+/// An Intel Arc card in sysfs.
+#[derive(Debug)]
+struct IntelArcCard {
+    /// The `device` directory.
+    device: PathBuf,
+    gts: Vec<IntelGt>,
+}
+
+/// Reads Intel Arc GPUs (Alchemist, Battlemage) straight from sysfs, for either the i915 or
+/// the xe driver. Utilization is the busiest GT's share of the interval out of its idle state,
+/// the clock is the actual GT frequency, and power is the rate of the card's hwmon energy
+/// counter, else of the RAPL MMIO package domain.
+#[derive(Debug)]
+pub struct IntelArcBackend {
+    cards: Vec<IntelArcCard>,
+    rapl: PathBuf,
+    /// Idle milliseconds by card and GT.
+    idle: RateTracker<(usize, String)>,
+    /// Microjoules by card.
+    energy: RateTracker<usize>,
+}
+
+impl IntelArcBackend {
+    pub fn open() -> io::Result<Self> {
+        Self::open_in(Path::new(SYSFS_DRM), Path::new(RAPL_MMIO_ENERGY), Moment::now())
+    }
+
+    /// Opens the Intel cards under `drm`, reading the RAPL counter at `rapl`, and takes the
+    /// first reading `at`. i915 and xe only register hwmon for discrete cards, so where one
+    /// has it, integrated GPUs are left out.
+    pub fn open_in(drm: &Path, rapl: &Path, at: Moment) -> io::Result<Self> {
+        let mut cards: Vec<IntelArcCard> = fs::read_dir(drm)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let number = entry.file_name().to_str()?.strip_prefix("card")?.parse().ok()?;
+                let device = entry.path().join("device");
+                let driver = fs::read_link(device.join("driver")).ok()?;
+                let intel = fs::read_to_string(device.join("vendor")).is_ok_and(|vendor| vendor.trim() == "0x8086");
+                (intel && (driver.ends_with("i915") || driver.ends_with("xe"))).then(|| (number, IntelArcCard { gts: intel_gts(&entry.path()), device }))
+            })
+            .filter(|(_, card)| !card.gts.is_empty())
+            .collect::<BTreeMap<u32, _>>()
+            .into_values()
+            .collect();
+
+        if cards.iter().any(|card| card.device.join("hwmon").exists()) {
+            cards.retain(|card| card.device.join("hwmon").exists());
+        }
+        if cards.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No Intel GPU reports its GT idle time (is RC6 disabled?)"));
+        }
+
+        let mut backend = IntelArcBackend { cards, rapl: rapl.to_path_buf(), idle: RateTracker::default(), energy: RateTracker::default() };
+        backend.update(at);
+        Ok(backend)
+    }
+
+    /// Reads every card's counters taken `at`, returning the busiest GT's utilization and the
+    /// power of each card, where there is a rate.
+    fn update(&mut self, at: Moment) -> Vec<(Option<f64>, Option<f32>)> {
+        self.idle.tick(at);
+        self.energy.tick(at);
+
+        let mut readings = Vec::new();
+        for (position, card) in self.cards.iter().enumerate() {
+            let mut utilization: Option<f64> = None;
+            for gt in &card.gts {
+                let Some(idle_ms) = read_number(&gt.idle_ms) else { continue };
+                if let Some(rate) = self.idle.update((position, gt.name.clone()), idle_ms) {
+                    // Idle milliseconds per second, a tenth of the idle percentage.
+                    let busy = 100.0 - rate.per_second() / 10.0;
+                    utilization = Some(utilization.map_or(busy, |utilization| utilization.max(busy)));
+                }
+            }
+
+            let energy_uj = hwmon_value(&card.device, "energy1_input").or_else(|| read_number(&self.rapl));
+            // A counter that wrapped around gives no power for one tick.
+            let power_w = energy_uj.and_then(|energy_uj| self.energy.update(position, energy_uj)).map(|rate| (rate.per_second() / 1_000_000.0) as f32);
+            readings.push((utilization.map(clamp_percent), power_w));
+        }
+        readings
+    }
+
+    /// Polls the GPUs with the counters read `at`.
+    pub fn poll_at(&mut self, gpus: &[GpuInfo], at: Moment) -> Vec<PollResult> {
+        let readings = self.update(at);
+
+        gpus.iter()
+            .map(|gpu| {
+                let Some(position) = device_position(self.cards.iter().map(|card| &card.device), gpu) else {
+                    return PollResult::PermanentError { gpu: gpu.clone(), message: "No such Intel card in sysfs".to_string(), retries: 0 };
+                };
+                let card = &self.cards[position];
+                let Some(utilization) = readings[position].0 else {
+                    // The counters were reset, or the system was suspended: the next reading,
+                    // taken right away on a retry, has a rate again.
+                    return PollResult::TransientError { gpu: gpu.clone(), message: "No GT idle time across a reset or suspend".to_string(), retries: 0 };
+                };
+
+                PollResult::Ok { snapshot: GpuSnapshot {
+                    temperature_c: hwmon_value(&card.device, "temp1_input")
+                        .or_else(|| hwmon_value(&card.device, "temp2_input"))
+                        .map(|millidegrees| millidegrees as f32 / 1000.0),
+                    power_w: readings[position].1,
+                    clock_mhz: card.gts.iter().filter_map(|gt| gt.freq_mhz.iter().find_map(|path| read_number(path))).max().map(|mhz| mhz as u32),
+                    ..GpuSnapshot::new(gpu.clone(), utilization)
+                }, retries: 0 }
+            })
+            .collect()
+    }
+}
+
+impl Backend for IntelArcBackend {
+    fn name(&self) -> &'static str {
+        "Intel GT sysfs"
+    }
+
+    fn source(&self) -> &'static str {
+        "sysfs:intel_gt"
+    }
+
+    fn cost(&self) -> Cost {
+        Cost::Sysfs
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        self.poll_at(gpus, Moment::now())
+    }
+}
+
+/// One open DRM file as described by `/proc/<pid>/fdinfo/<fd>`, with the cumulative busy
+/// time of each engine it used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrmClient {
+    pub client_id: u64,
+    /// PCI address of the GPU (`drm-pdev`); absent for GPUs that are not PCI devices.
+    pub pdev: Option<String>,
+    /// Busy time per engine (`drm-engine-<name>`), in nanoseconds.
+    pub engines: Vec<(String, u64)>,
+    /// Device memory resident for the client, in bytes: `drm-resident-vram` (`drm-memory-vram`
+    /// before Linux 6.8), or `local0` on Intel discrete GPUs.
+    pub vram_bytes: Option<u64>,
+}
+
+/// A fdinfo memory size: bytes, or a number followed by `KiB`, `MiB` or `GiB`.
+fn fdinfo_bytes(value: &str) -> Option<u64> {
+    let (number, unit) = value.split_once(' ').unwrap_or((value, ""));
+    let scale = match unit.trim() {
+        "" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(scale)
+}
+
+/// Parses the fdinfo of a DRM file; `None` for any other kind of file.
+pub fn parse_drm_fdinfo(fdinfo: &str) -> Option<DrmClient> {
+    let mut client_id = None;
+    let mut pdev = None;
+    let mut engines = Vec::new();
+    let (mut resident, mut memory) = (None, None);
+
+    for (key, value) in fdinfo.lines().filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+        match key {
+            "drm-client-id" => client_id = value.parse().ok(),
+            "drm-pdev" => pdev = Some(value.to_lowercase()),
+            "drm-resident-vram" | "drm-resident-local0" => resident = fdinfo_bytes(value),
+            "drm-memory-vram" => memory = fdinfo_bytes(value),
+            // drm-engine-capacity-<name> is the number of engines of a kind, not a busy time.
+            key if key.starts_with("drm-engine-") && !key.starts_with("drm-engine-capacity-") => {
+                if let Some(nanoseconds) = value.strip_suffix("ns").and_then(|value| value.trim().parse().ok()) {
+                    engines.push((key["drm-engine-".len()..].to_string(), nanoseconds));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(DrmClient { client_id: client_id?, pdev, engines, vram_bytes: resident.or(memory) })
+}
+
+fn insert_process_clients(clients: &mut HashMap<(Option<String>, u64), DrmClient>, process: &Path) {
+    let Ok(fds) = fs::read_dir(process.join("fdinfo")) else { return };
+    for fd in fds.filter_map(Result::ok) {
+        if let Some(client) = fs::read_to_string(fd.path()).ok().as_deref().and_then(parse_drm_fdinfo) {
+            clients.insert((client.pdev.clone(), client.client_id), client);
+        }
+    }
+}
+
+/// Reads every DRM client on the system. A client shared between file descriptors or
+/// processes is only counted once.
+pub(crate) fn read_drm_clients() -> Vec<DrmClient> {
+    let mut clients = HashMap::new();
+    let Ok(processes) = fs::read_dir("/proc") else { return Vec::new() };
+
+    for process in processes.filter_map(Result::ok) {
+        insert_process_clients(&mut clients, &process.path());
+    }
+
+    clients.into_values().collect()
+}
+
+/// The DRM clients open in one process, each counted once.
+pub fn read_process_drm_clients(pid: u32) -> Vec<DrmClient> {
+    let mut clients = HashMap::new();
+    insert_process_clients(&mut clients, &Path::new("/proc").join(pid.to_string()));
+    clients.into_values().collect()
+}
+
+/// One DRM engine of a GPU, from the busy times its clients report in fdinfo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineBusy {
+    /// As fdinfo names it: `gfx`, `render`, `video`, `compute`...
+    pub engine: String,
+    /// Busy nanoseconds since monitoring started. The kernel only counts per client, and clients
+    /// come and go, so this adds up what each gained between readings: it never goes down.
+    pub ns_total: u64,
+    /// Share of the last interval, in percent; `None` for the first reading and across a suspend.
+    pub busy_pct: Option<f64>,
+}
+
+/// The engine busy times of the DRM clients from one reading to the next, per device and
+/// engine, through a [`RateTracker`] keyed by client.
+#[derive(Debug, Default)]
+pub struct DrmBusyTracker {
+    clients: RateTracker<(Option<String>, u64, String)>,
+    /// The busy time of the last interval and the total, by device and engine.
+    engines: BTreeMap<(Option<String>, String), (u64, u64)>,
+    elapsed: Option<Duration>,
+}
+
+impl DrmBusyTracker {
+    /// Records a reading of the clients taken `at`. Clients missing from the previous reading
+    /// started in between, so all of their busy time counts.
+    pub fn update(&mut self, clients: &[DrmClient], at: Moment) {
+        self.elapsed = self.clients.tick(at);
+        for (interval, _) in self.engines.values_mut() {
+            *interval = 0;
+        }
+
+        for client in clients {
+            for (engine, nanoseconds) in &client.engines {
+                let busy = self.engines.entry((client.pdev.clone(), engine.clone())).or_default();
+                if let Some(rate) = self.clients.update_from_zero((client.pdev.clone(), client.client_id, engine.clone()), *nanoseconds) {
+                    busy.0 += rate.increase;
+                    busy.1 += rate.increase;
+                }
+            }
+        }
+    }
+
+    /// The engines of the GPU at `bus_id`, or of every GPU together with `None`, by name.
+    pub fn engines(&self, bus_id: Option<&str>) -> Vec<EngineBusy> {
+        self.engines_on(|pdev| bus_id.is_none() || pdev == bus_id)
+    }
+
+    fn engines_on(&self, device: impl Fn(Option<&str>) -> bool) -> Vec<EngineBusy> {
+        let mut engines: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        for ((_, engine), (interval, total)) in self.engines.iter().filter(|((pdev, _), _)| device(pdev.as_deref())) {
+            let busy = engines.entry(engine).or_default();
+            busy.0 += interval;
+            busy.1 += total;
+        }
+
+        engines
+            .into_iter()
+            .map(|(engine, (interval, total))| EngineBusy {
+                engine: engine.to_string(),
+                ns_total: total,
+                // Busy times read just after the elapsed time was taken can add up to a little more.
+                busy_pct: self.elapsed.map(|elapsed| clamp_percent(Rate { increase: interval, elapsed }.percent_busy().min(100.0))),
+            })
+            .collect()
+    }
+
+    /// The busiest engine's share of the last interval, in percent; `None` where
+    /// [`EngineBusy::busy_pct`] is.
+    pub fn utilization(&self, bus_id: Option<&str>) -> Option<f64> {
+        self.elapsed?;
+        Some(busiest(&self.engines(bus_id)))
+    }
+
+    /// [`DrmBusyTracker::utilization`] of the clients of `pdev` only, with `None` of those that
+    /// are not on a PCI device.
+    pub fn device_utilization(&self, pdev: Option<&str>) -> Option<f64> {
+        self.elapsed?;
+        Some(busiest(&self.engines_on(|device| device == pdev)))
+    }
+}
+
+fn busiest(engines: &[EngineBusy]) -> f64 {
+    engines.iter().filter_map(|engine| engine.busy_pct).fold(0.0, f64::max)
+}
+
+/// Utilization of the GPU at `bus_id` (of every GPU with `None`) between two readings of the
+/// DRM clients taken `elapsed` apart: see [`DrmBusyTracker::utilization`].
+pub fn drm_utilization(previous: &[DrmClient], current: &[DrmClient], elapsed: Duration, bus_id: Option<&str>) -> f64 {
+    let mut tracker = DrmBusyTracker::default();
+    let at = Moment::now();
+    tracker.update(previous, at);
+    tracker.update(current, at.after(elapsed));
+    tracker.utilization(bus_id).unwrap_or(0.0)
+}
+
+/// Generic DRM utilization from the engine busy times the kernel reports per client in
+/// fdinfo. Works for any driver that implements it, but only yields utilization and the busy
+/// share of each engine.
+#[derive(Debug)]
+pub struct FdinfoBackend {
+    busy: DrmBusyTracker,
+}
+
+impl FdinfoBackend {
+    pub fn open() -> io::Result<Self> {
+        if !drm_card_exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No DRM card"));
+        }
+
+        let mut busy = DrmBusyTracker::default();
+        busy.update(&read_drm_clients(), Moment::now());
+        Ok(FdinfoBackend { busy })
+    }
+}
+
+impl Backend for FdinfoBackend {
+    fn name(&self) -> &'static str {
+        "DRM fdinfo"
+    }
+
+    fn source(&self) -> &'static str {
+        "fdinfo"
+    }
+
+    fn cost(&self) -> Cost {
+        Cost::Sysfs
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        self.busy.update(&read_drm_clients(), Moment::now());
+
+        gpus.iter()
+            .map(|gpu| {
+                let bus_id = gpu.bus_id.as_deref().map(str::to_lowercase);
+                let Some(utilization) = self.busy.utilization(bus_id.as_deref()) else {
+                    // The next reading, taken right away on a retry, has a rate again.
+                    return PollResult::TransientError { gpu: gpu.clone(), message: "No busy times across a suspend".to_string(), retries: 0 };
+                };
+                PollResult::Ok { snapshot: GpuSnapshot { engines: Some(self.busy.engines(bus_id.as_deref())), ..GpuSnapshot::new(gpu.clone(), utilization) }, retries: 0 }
+            })
+            .collect()
+    }
+}
+
+/// Opens every source available for `gpu_type`, cheapest first.
+fn candidates<'r>(runner: &'r dyn CommandRunner, gpu_type: &GpuType, interval: Duration, retry: Retry) -> Vec<Box<dyn Backend + 'r>> {
+    let mut backends: Vec<Box<dyn Backend + 'r>> = Vec::new();
+
+    if matches!(gpu_type, GpuType::Amd | GpuType::AmdApu | GpuType::Unknown(_)) {
+        if let Ok(backend) = SysfsBackend::open() {
+            backends.push(Box::new(backend));
+        }
+    }
+    if matches!(gpu_type, GpuType::IntelArc(_)) {
+        if let Ok(backend) = IntelArcBackend::open() {
+            backends.push(Box::new(backend));
+        }
+    }
+    if matches!(gpu_type, GpuType::Broadcom(_)) {
+        if let Ok(backend) = VideoCoreBackend::open(runner) {
+            backends.push(Box::new(backend.with_retry(retry)));
+        }
+    }
+    if matches!(gpu_type, GpuType::Broadcom(_) | GpuType::Unknown(_)) {
+        if let Ok(backend) = FdinfoBackend::open() {
+            backends.push(Box::new(backend));
+        }
+    }
+    if let Ok(backend) = StreamingBackend::open(gpu_type, interval) {
+        backends.push(Box::new(backend));
+    }
+    if *gpu_type == GpuType::Amd {
+        if let Ok(backend) = AmdSmiBackend::open(runner) {
+            backends.push(Box::new(backend.with_retry(retry)));
+        }
+    }
+    backends.push(Box::new(SpawnBackend::new(runner, gpu_type).with_retry(retry)));
+
+    // A stable sort, so the sysfs busy counter stays ahead of fdinfo, and amd-smi ahead of
+    // radeontop.
+    backends.sort_by_key(|backend| backend.cost());
+    backends
+}
+
+/// Picks the metrics source: the cheapest available one with `low_overhead` or when there is
+/// no vendor tool, otherwise the vendor tool run once per tick: on AMD, amd-smi where it is
+/// installed, else radeontop; on AMD APUs, `amdgpu_pm_info`; on a Raspberry Pi, `vcgencmd`
+/// where it is installed. Intel Arc GPUs have no vendor tool and are always read from sysfs.
+/// Streaming sources report every
+/// `interval`; the vendor tools run per tick are attempted again as `retry` allows when they
+/// fail.
+pub fn select<'r>(runner: &'r dyn CommandRunner, gpu_type: &GpuType, low_overhead: bool, interval: Duration, retry: Retry) -> Box<dyn Backend + 'r> {
+    if !low_overhead && (gpu_type.top_tool().is_some() || *gpu_type == GpuType::AmdApu) {
+        if *gpu_type == GpuType::Amd {
+            if let Ok(backend) = AmdSmiBackend::open(runner) {
+                return Box::new(backend.with_retry(retry));
+            }
+        }
+        return Box::new(SpawnBackend::new(runner, gpu_type).with_retry(retry));
+    }
+    if !low_overhead && matches!(gpu_type, GpuType::Broadcom(_)) {
+        if let Ok(backend) = VideoCoreBackend::open(runner) {
+            return Box::new(backend.with_retry(retry));
+        }
+    }
+
+    candidates(runner, gpu_type, interval, retry).into_iter().next().expect("the per-tick backend is always available")
+}
+
+/// The metrics a source reported in its self-check sample.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub memory: bool,
+    pub temperature: bool,
+    pub power: bool,
+    pub memory_bandwidth: bool,
+}
+
+impl Capabilities {
+    fn add(&mut self, snapshot: &GpuSnapshot) {
+        self.memory |= snapshot.memory_used_mib.is_some();
+        self.temperature |= snapshot.temperature_c.is_some();
+        self.power |= snapshot.power_w.is_some();
+        self.memory_bandwidth |= snapshot.memory_bandwidth.is_some();
+    }
+}
+
+/// Why a source failed its self-check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeError {
+    pub backend: &'static str,
+    pub message: String,
+    /// The first [`PROBE_OUTPUT_LINES`] lines of what the tool printed, if it ran.
+    pub output: Vec<String>,
+    pub code: Option<i32>,
+}
+
+impl ProbeError {
+    /// The tool's own exit code when it failed with one, so scripts can tell a missing
+    /// driver from a permission problem; 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        self.code.filter(|code| *code != 0).unwrap_or(1)
+    }
+}
+
+/// Takes one sample through the source's full parse path. Passes when at least one GPU
+/// parsed, and reports which metrics the sample had.
+pub fn probe(backend: &mut dyn Backend, gpus: &[GpuInfo]) -> Result<Capabilities, ProbeError> {
+    let mut capabilities = Capabilities::default();
+    let mut message = None;
+
+    if gpus.is_empty() {
+        return Ok(capabilities);
+    }
+
+    let results = backend.poll(gpus);
+    for result in &results {
+        match result {
+            PollResult::Ok { snapshot, .. } => capabilities.add(snapshot),
+            PollResult::TransientError { message: error, .. } | PollResult::PermanentError { message: error, .. } => {
+                message.get_or_insert_with(|| error.clone());
+            }
+        }
+    }
+
+    if results.iter().any(|result| matches!(result, PollResult::Ok { .. })) {
+        return Ok(capabilities);
+    }
+
+    let raw = backend.last_output();
+    let mut output: Vec<String> = raw.iter().flat_map(|raw| raw.text.lines()).map(str::to_string).collect();
+    if output.len() > PROBE_OUTPUT_LINES {
+        let more = output.len() - PROBE_OUTPUT_LINES;
+        output.truncate(PROBE_OUTPUT_LINES);
+        output.push(format!("... {} more lines", more));
+    }
+
+    Err(ProbeError {
+        backend: backend.name(),
+        message: message.unwrap_or_else(|| "No sample".to_string()),
+        output,
+        code: raw.and_then(|raw| raw.code),
+    })
+}
+
+/// Picks the metrics source like [`select`], then checks it with [`probe`]. A source failing
+/// the check is reported to `on_failure` and the next available one is tried, cheapest first.
+/// Fails with the last source's error when none passes.
+pub fn select_probed<'r>(
+    runner: &'r dyn CommandRunner,
+    gpu_type: &GpuType,
+    low_overhead: bool,
+    interval: Duration,
+    retry: Retry,
+    gpus: &[GpuInfo],
+    mut on_failure: impl FnMut(&ProbeError),
+) -> Result<(Box<dyn Backend + 'r>, Capabilities), ProbeError> {
+    let mut backend = select(runner, gpu_type, low_overhead, interval, retry);
+    let mut error = match probe(backend.as_mut(), gpus) {
+        Ok(capabilities) => return Ok((backend, capabilities)),
+        Err(error) => error,
+    };
+    on_failure(&error);
+    let mut tried = vec![backend.name()];
+    drop(backend);
+
+    for mut backend in candidates(runner, gpu_type, interval, retry) {
+        if tried.contains(&backend.name()) {
+            continue;
+        }
+        tried.push(backend.name());
+
+        match probe(backend.as_mut(), gpus) {
+            Ok(capabilities) => return Ok((backend, capabilities)),
+            Err(next) => {
+                on_failure(&next);
+                error = next;
+            }
+        }
+    }
+
+    Err(error)
+}
+
+/// Every [`Backend::source`], as `--backend` and the `[backend]` configuration table take them.
+pub const SOURCES: [&str; 13] = [
+    "nvidia-smi",
+    "nvidia-smi:stream",
+    "radeontop",
+    "amd-smi",
+    "amdgpu_pm_info",
+    "intel_gpu_top",
+    "intel_gpu_top:stream",
+    "tegrastats",
+    "tegrastats:stream",
+    "vcgencmd",
+    "sysfs:gpu_busy_percent",
+    "sysfs:intel_gt",
+    "fdinfo",
+];
+
+/// Parses a `--backend` value; `sysfs` is short for `sysfs:gpu_busy_percent`.
+pub fn parse_source(value: &str) -> Result<&'static str, String> {
+    let value = if value == "sysfs" { "sysfs:gpu_busy_percent" } else { value };
+    SOURCES
+        .iter()
+        .find(|source| **source == value)
+        .copied()
+        .ok_or(format!("Unknown backend: {} (expected one of {})", value, SOURCES.join(", ")))
+}
+
+/// The key of the vendor in the `[backend]` configuration table.
+fn config_key(gpu_type: &GpuType) -> &'static str {
+    match gpu_type {
+        GpuType::Nvidia => "nvidia",
+        GpuType::Amd => "amd",
+        GpuType::AmdApu => "amd_apu",
+        GpuType::Intel => "intel",
+        GpuType::IntelArc(_) => "intel_arc",
+        GpuType::JetsonGpu => "jetson",
+        GpuType::Broadcom(_) => "broadcom",
+        GpuType::Unknown(_) => "other",
+    }
+}
+
+/// The source the samples must come from: `--backend`, else the vendor's key in the
+/// `[backend]` configuration table; `None` leaves the choice to [`select_probed`].
+pub fn forced_source(config: &Document, gpu_type: &GpuType, flag: Option<&'static str>) -> Result<Option<&'static str>, String> {
+    if flag.is_some() {
+        return Ok(flag);
+    }
+
+    let key = config_key(gpu_type);
+    match config.tables.get("backend").and_then(|table| table.get(key)) {
+        None => Ok(None),
+        Some(ConfigValue::String(value)) => parse_source(value).map(Some).map_err(|err| format!("backend.{}: {}", key, err)),
+        Some(_) => Err(format!("backend.{} must be a string", key)),
+    }
+}
+
+/// Whether `source` can monitor `gpu_type` at all: a vendor tool only its own vendor's GPUs.
+pub fn fits(gpu_type: &GpuType, source: &str) -> bool {
+    match source {
+        "sysfs:gpu_busy_percent" | "fdinfo" => true,
+        "amd-smi" => *gpu_type == GpuType::Amd,
+        "amdgpu_pm_info" => *gpu_type == GpuType::AmdApu,
+        "sysfs:intel_gt" => matches!(gpu_type, GpuType::IntelArc(_)),
+        "vcgencmd" => matches!(gpu_type, GpuType::Broadcom(_)),
+        _ => gpu_type.top_tool() == source.split(':').next(),
+    }
+}
+
+/// Opens `source` for `gpu_type`, whatever it costs; fails where it cannot monitor that vendor.
+pub fn open<'r>(runner: &'r dyn CommandRunner, gpu_type: &GpuType, source: &str, interval: Duration, retry: Retry) -> io::Result<Box<dyn Backend + 'r>> {
+    if !fits(gpu_type, source) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} cannot monitor this GPU", source)));
+    }
+
+    Ok(match source {
+        "sysfs:gpu_busy_percent" => Box::new(SysfsBackend::open()?),
+        "sysfs:intel_gt" => Box::new(IntelArcBackend::open()?),
+        "fdinfo" => Box::new(FdinfoBackend::open()?),
+        "amd-smi" => Box::new(AmdSmiBackend::open(runner)?.with_retry(retry)),
+        "vcgencmd" => Box::new(VideoCoreBackend::open(runner)?.with_retry(retry)),
+        _ if source.ends_with(":stream") => Box::new(StreamingBackend::open(gpu_type, interval)?),
+        _ => Box::new(SpawnBackend::new(runner, gpu_type).with_retry(retry)),
+    })
+}
+
+/// Opens the source `--backend` forced and checks it with [`probe`]. There is no fallback: a
+/// source that cannot be opened or fails the check is an error.
+pub fn open_probed<'r>(
+    runner: &'r dyn CommandRunner,
+    gpu_type: &GpuType,
+    source: &'static str,
+    interval: Duration,
+    retry: Retry,
+    gpus: &[GpuInfo],
+) -> Result<(Box<dyn Backend + 'r>, Capabilities), ProbeError> {
+    let mut backend = open(runner, gpu_type, source, interval, retry).map_err(|err| ProbeError { backend: source, message: err.to_string(), output: Vec::new(), code: None })?;
+    let capabilities = probe(backend.as_mut(), gpus)?;
+    Ok((backend, capabilities))
+}
+
+/// The next source after `failed` stopped working mid-run: the cheapest other one available
+/// that passes [`probe`], if any.
+pub fn fallback<'r>(runner: &'r dyn CommandRunner, gpu_type: &GpuType, failed: &str, interval: Duration, retry: Retry, gpus: &[GpuInfo]) -> Option<(Box<dyn Backend + 'r>, Capabilities)> {
+    candidates(runner, gpu_type, interval, retry)
+        .into_iter()
+        .filter(|backend| backend.source() != failed)
+        .find_map(|mut backend| probe(backend.as_mut(), gpus).ok().map(|capabilities| (backend, capabilities)))
+}
@@ -0,0 +1,238 @@
+//! `--max-energy` and `--max-gpu-hours`: budgets on the energy the monitored GPUs draw and on
+//! their busy time, summed over every GPU gpuatop samples (see `--gpu`). In plain monitor mode
+//! an exceeded budget is only reported; with `gpuatop exec -- <command>` or `--launch`, the
+//! command's process group is signalled, then killed if it outlives the grace period, and
+//! gpuatop exits with [`EXIT_CODE`].
+//!
+//! Energy is the integral of the power draw and busy time that of utilization, both by the
+//! trapezoidal rule between consecutive samples of a GPU. A GPU that misses samples keeps its
+//! running totals, and the gap is bridged from the samples around it, up to a maximum so that
+//! a suspend is not billed as hours of work.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::runner::CommandRunner;
+use crate::GpuSnapshot;
+
+/// The exit code of `exec` and `--launch` after a budget stopped the command.
+pub const EXIT_CODE: i32 = 5;
+
+/// How long the command has to exit after the budget signal before it is killed, unless
+/// `--budget-grace` sets another.
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+/// The signal sent when a budget is exceeded, unless `--budget-signal` sets another.
+pub const DEFAULT_SIGNAL: &str = "TERM";
+
+/// Signals `--budget-signal` accepts, as `kill -s` names them.
+const SIGNALS: [&str; 7] = ["TERM", "INT", "HUP", "QUIT", "USR1", "USR2", "KILL"];
+
+/// Parses `--max-energy`: a number of watt-hours, with an optional `Wh` or `kWh` unit.
+pub fn parse_energy(value: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid --max-energy value: {} (expected e.g. 500Wh or 1.5kWh)", value);
+    let (number, factor) = if let Some(number) = value.strip_suffix("kWh") {
+        (number, 1000.0)
+    } else {
+        (value.strip_suffix("Wh").unwrap_or(value), 1.0)
+    };
+    let watt_hours = number.trim().parse::<f64>().map_err(|_| invalid())? * factor;
+
+    if watt_hours.is_finite() && watt_hours > 0.0 {
+        Ok(watt_hours)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Parses `--max-gpu-hours`: a number of hours one GPU is fully busy.
+pub fn parse_gpu_hours(value: &str) -> Result<f64, String> {
+    value
+        .strip_suffix('h')
+        .unwrap_or(value)
+        .parse::<f64>()
+        .ok()
+        .filter(|hours| hours.is_finite() && *hours > 0.0)
+        .ok_or(format!("Invalid --max-gpu-hours value: {}", value))
+}
+
+/// Parses `--budget-signal`: `TERM`, `SIGTERM` or `sigterm` are all `TERM`.
+pub fn parse_signal(value: &str) -> Result<String, String> {
+    let name = value.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+
+    SIGNALS
+        .iter()
+        .find(|signal| **signal == name)
+        .map(|signal| signal.to_string())
+        .ok_or(format!("Invalid --budget-signal value: {} (expected one of {})", value, SIGNALS.join(", ")))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub max_energy_wh: Option<f64>,
+    pub max_gpu_hours: Option<f64>,
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        self.max_energy_wh.is_none() && self.max_gpu_hours.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Energy,
+    GpuHours,
+}
+
+/// A budget that was just exceeded, with the total that exceeded it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exceeded {
+    pub kind: Kind,
+    pub limit: f64,
+    pub used: f64,
+}
+
+impl Exceeded {
+    pub fn message(&self) -> String {
+        match self.kind {
+            Kind::Energy => format!("Energy budget exceeded: {:.1} Wh of {} Wh", self.used, self.limit),
+            Kind::GpuHours => format!("GPU-hour budget exceeded: {:.3} of {} GPU-hours", self.used, self.limit),
+        }
+    }
+}
+
+/// The running totals of one GPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub energy_wh: f64,
+    pub gpu_hours: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LastSample {
+    at: Instant,
+    power_w: Option<f64>,
+    busy: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BudgetTracker {
+    limits: Limits,
+    /// The longest gap between two samples of a GPU that is bridged in full.
+    max_gap: Duration,
+    usage: BTreeMap<u32, Usage>,
+    last: BTreeMap<u32, LastSample>,
+    exceeded: Vec<Kind>,
+}
+
+impl BudgetTracker {
+    pub fn new(limits: Limits, max_gap: Duration) -> Self {
+        BudgetTracker { limits, max_gap, usage: BTreeMap::new(), last: BTreeMap::new(), exceeded: Vec::new() }
+    }
+
+    /// Adds the time since the GPU's previous sample to its totals. Returns the budgets this
+    /// sample exceeded; each is returned once.
+    pub fn record(&mut self, snapshot: &GpuSnapshot, now: Instant) -> Vec<Exceeded> {
+        let index = snapshot.gpu.index;
+        let sample = LastSample { at: now, power_w: snapshot.power_w.map(f64::from), busy: snapshot.utilization / 100.0 };
+        let usage = self.usage.entry(index).or_default();
+
+        if let Some(last) = self.last.insert(index, sample) {
+            let hours = now.saturating_duration_since(last.at).min(self.max_gap).as_secs_f64() / 3600.0;
+            if let (Some(before), Some(after)) = (last.power_w, sample.power_w) {
+                usage.energy_wh += (before + after) / 2.0 * hours;
+            }
+            usage.gpu_hours += (last.busy + sample.busy) / 2.0 * hours;
+        }
+
+        let total = self.total();
+        let mut exceeded = Vec::new();
+        for (kind, limit, used) in [(Kind::Energy, self.limits.max_energy_wh, total.energy_wh), (Kind::GpuHours, self.limits.max_gpu_hours, total.gpu_hours)] {
+            if let Some(limit) = limit.filter(|limit| used > *limit && !self.exceeded.contains(&kind)) {
+                self.exceeded.push(kind);
+                exceeded.push(Exceeded { kind, limit, used });
+            }
+        }
+        exceeded
+    }
+
+    pub fn usage(&self, gpu: u32) -> Option<Usage> {
+        self.usage.get(&gpu).copied()
+    }
+
+    /// The totals over every GPU.
+    pub fn total(&self) -> Usage {
+        self.usage.values().fold(Usage::default(), |total, usage| Usage { energy_wh: total.energy_wh + usage.energy_wh, gpu_hours: total.gpu_hours + usage.gpu_hours })
+    }
+
+    /// Exit summary lines: how much of each budget was consumed, then the totals per GPU.
+    pub fn format_summary(&self) -> Vec<String> {
+        let total = self.total();
+        let share = |used: f64, limit: f64| used / limit * 100.0;
+
+        let mut lines = vec!["Budget:".to_string()];
+        if let Some(limit) = self.limits.max_energy_wh {
+            lines.push(format!("  Energy: {:.1} of {} Wh ({:.0}%)", total.energy_wh, limit, share(total.energy_wh, limit)));
+        }
+        if let Some(limit) = self.limits.max_gpu_hours {
+            lines.push(format!("  GPU-hours: {:.3} of {} ({:.0}%)", total.gpu_hours, limit, share(total.gpu_hours, limit)));
+        }
+        lines.extend(self.usage.iter().map(|(gpu, usage)| format!("  GPU {}: {:.1} Wh, {:.3} GPU-hours", gpu, usage.energy_wh, usage.gpu_hours)));
+        lines
+    }
+}
+
+/// Sends `signal` to every process in the process group `pgid`, through `kill(1)`.
+pub fn signal_group(runner: &dyn CommandRunner, pgid: u32, signal: &str) -> io::Result<()> {
+    let output = runner.run("kill", &["-s", signal, "--", &format!("-{}", pgid)])?;
+    if !output.success {
+        return Err(io::Error::other(format!("kill -s {} failed: {}", signal, output.stderr.trim())));
+    }
+    Ok(())
+}
+
+/// Stops the command started by `exec` or `--launch` once a budget is exceeded: the budget
+/// signal first, `KILL` once the grace period is over.
+#[derive(Debug, Clone)]
+pub struct Enforcer {
+    pgid: u32,
+    signal: String,
+    grace: Duration,
+    kill_at: Option<Instant>,
+    killed: bool,
+}
+
+impl Enforcer {
+    pub fn new(pgid: u32, signal: String, grace: Duration) -> Self {
+        Enforcer { pgid, signal, grace, kill_at: None, killed: false }
+    }
+
+    /// Whether the command was signalled.
+    pub fn stopped(&self) -> bool {
+        self.kill_at.is_some() || self.killed
+    }
+
+    /// Sends the budget signal, once.
+    pub fn stop(&mut self, runner: &dyn CommandRunner, now: Instant) -> io::Result<()> {
+        if self.stopped() {
+            return Ok(());
+        }
+        self.kill_at = Some(now + self.grace);
+        signal_group(runner, self.pgid, &self.signal)
+    }
+
+    /// Kills the process group once the grace period is over. Returns whether it did.
+    pub fn tick(&mut self, runner: &dyn CommandRunner, now: Instant) -> io::Result<bool> {
+        match self.kill_at {
+            Some(kill_at) if now >= kill_at && self.signal != "KILL" => {
+                self.kill_at = None;
+                self.killed = true;
+                signal_group(runner, self.pgid, "KILL").map(|()| true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! `--read-only`: what gpuatop may change on the machine it runs on.
+//!
+//! Every mutating code path asks `Capabilities` instead of checking the flag itself: the
+//! install flow, device state changes (`fix-persistence`, `--wake`) and file writes. The
+//! options that write are checked once at startup, so a conflicting option fails before
+//! anything runs.
+
+use std::path::{Component, Path};
+
+use crate::config::{ConfigValue, Document};
+
+/// The exit code when `--read-only` refuses an option or an install.
+pub const READ_ONLY_EXIT_CODE: i32 = 4;
+
+/// Writes under this directory stay allowed in read-only mode: it is scratch space that is
+/// cleared on reboot.
+const SCRATCH_DIR: &str = "/tmp";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    read_only: bool,
+    /// `--read-only-allow-writes`: read-only for packages and devices, not for files.
+    allow_writes: bool,
+}
+
+impl Capabilities {
+    pub fn new(read_only: bool, allow_writes: bool) -> Self {
+        Capabilities { read_only, allow_writes }
+    }
+
+    /// The flags, or the top-level `read_only` and `read_only_allow_writes` configuration keys.
+    pub fn from_config(config: &Document, read_only: bool, allow_writes: bool) -> Result<Self, String> {
+        let key = |name: &str| match config.tables.get("").and_then(|table| table.get(name)) {
+            None => Ok(false),
+            Some(ConfigValue::Bool(value)) => Ok(*value),
+            Some(_) => Err(format!("{} must be true or false", name)),
+        };
+
+        Ok(Capabilities::new(read_only || key("read_only")?, allow_writes || key("read_only_allow_writes")?))
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn install(&self) -> Result<(), String> {
+        if self.read_only {
+            return Err("--read-only forbids installing the monitoring tool".to_string());
+        }
+        Ok(())
+    }
+
+    /// `what` changes the state of a GPU, e.g. its persistence or power mode.
+    pub fn change_device(&self, what: &str) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!("--read-only forbids {}, which changes the GPU state", what));
+        }
+        Ok(())
+    }
+
+    /// `what` writes to `path`. Paths under /tmp are always allowed.
+    pub fn write(&self, what: &str, path: &Path) -> Result<(), String> {
+        if !self.read_only || self.allow_writes || in_scratch_dir(path) {
+            return Ok(());
+        }
+        Err(format!("--read-only forbids writing {} ({}); use a path under {} or add --read-only-allow-writes", path.display(), what, SCRATCH_DIR))
+    }
+}
+
+/// Decided on the path as written: `..` is refused rather than resolved.
+fn in_scratch_dir(path: &Path) -> bool {
+    let absolute = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => match std::env::current_dir() {
+            Ok(dir) => dir.join(path),
+            Err(_) => return false,
+        },
+    };
+
+    absolute.starts_with(SCRATCH_DIR) && !absolute.components().any(|component| component == Component::ParentDir)
+}
@@ -0,0 +1,275 @@
+//! `gpuatop check`: one sample judged against `--warn` and `--crit` expressions, reported the
+//! way Nagios, Icinga and Zabbix run checks: a single status line with perfdata after the `|`,
+//! and the exit code 0, 1, 2 or 3 for OK, WARNING, CRITICAL or UNKNOWN.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::GpuSnapshot;
+
+/// A metric an expression can test, by its name in expressions and perfdata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Util,
+    /// Memory used, in percent of total.
+    Mem,
+    Temp,
+    Power,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [Metric::Util, Metric::Mem, Metric::Temp, Metric::Power];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Util => "util",
+            Metric::Mem => "mem",
+            Metric::Temp => "temp",
+            Metric::Power => "power",
+        }
+    }
+
+    /// The unit in the status line.
+    fn unit(self) -> &'static str {
+        match self {
+            Metric::Util | Metric::Mem => "%",
+            Metric::Temp => "C",
+            Metric::Power => "W",
+        }
+    }
+
+    pub fn value(self, snapshot: &GpuSnapshot) -> Option<f32> {
+        match self {
+            Metric::Util => Some(snapshot.utilization as f32),
+            Metric::Mem => match (snapshot.memory_used_mib, snapshot.memory_total_mib) {
+                (Some(used), Some(total)) if total > 0 => Some(used as f32 * 100.0 / total as f32),
+                _ => None,
+            },
+            Metric::Temp => snapshot.temperature_c,
+            Metric::Power => snapshot.power_w,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Metric::ALL.into_iter().find(|metric| metric.name() == s).ok_or_else(|| format!("Unknown metric: {} (expected util, mem, temp or power)", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+/// `metric op value`, e.g. `temp>85`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub value: f32,
+}
+
+impl Condition {
+    /// Whether the condition holds; a metric the GPU does not report never matches.
+    pub fn matches(&self, snapshot: &GpuSnapshot) -> bool {
+        let Some(value) = self.metric.value(snapshot) else {
+            return false;
+        };
+        match self.comparison {
+            Comparison::Above => value > self.value,
+            Comparison::AtLeast => value >= self.value,
+            Comparison::Below => value < self.value,
+            Comparison::AtMost => value <= self.value,
+        }
+    }
+
+    /// The condition as a Nagios threshold range, which alerts outside of it: `90` alerts above
+    /// 90, `10:` below 10.
+    fn range(&self) -> String {
+        match self.comparison {
+            Comparison::Above | Comparison::AtLeast => self.value.to_string(),
+            Comparison::Below | Comparison::AtMost => format!("{}:", self.value),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Two-character operators first, so `>=` is not read as `>` and `=85`.
+        let (at, operator, comparison) = [(">=", Comparison::AtLeast), ("<=", Comparison::AtMost), (">", Comparison::Above), ("<", Comparison::Below)]
+            .into_iter()
+            .find_map(|(operator, comparison)| s.find(operator).map(|at| (at, operator, comparison)))
+            .ok_or_else(|| format!("Invalid condition: {} (expected e.g. temp>85)", s))?;
+
+        let metric = s[..at].trim().parse()?;
+        let value = s[at + operator.len()..].trim().parse().map_err(|_| format!("Invalid number in condition: {}", s))?;
+        Ok(Condition { metric, comparison, value })
+    }
+}
+
+/// Conditions joined with `and` and `or`; `and` binds tighter, as usual. An empty expression
+/// never matches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expression {
+    /// Alternatives, each holding when all its conditions do.
+    any: Vec<Vec<Condition>>,
+}
+
+impl Expression {
+    pub fn matches(&self, snapshot: &GpuSnapshot) -> bool {
+        self.any.iter().any(|all| all.iter().all(|condition| condition.matches(snapshot)))
+    }
+
+    fn conditions(&self) -> impl Iterator<Item = &Condition> {
+        self.any.iter().flatten()
+    }
+
+    /// The threshold of `metric` for perfdata: its first condition. Empty without one.
+    pub fn range(&self, metric: Metric) -> String {
+        self.conditions().find(|condition| condition.metric == metric).map(Condition::range).unwrap_or_default()
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if words.is_empty() {
+            return Ok(Expression::default());
+        }
+
+        let any = words
+            .split(|word| word.eq_ignore_ascii_case("or"))
+            .map(|alternative| alternative.split(|word| word.eq_ignore_ascii_case("and")).map(|condition| condition.concat().parse()).collect::<Result<Vec<_>, _>>())
+            .collect::<Result<_, _>>()?;
+        Ok(Expression { any })
+    }
+}
+
+/// The states of a check, ordered from best to worst. UNKNOWN ranks below CRITICAL: a GPU that
+/// could not be sampled must not hide one that is on fire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Warning,
+    Unknown,
+    Critical,
+}
+
+impl Status {
+    /// The plugin exit code.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::Ok => 0,
+            Status::Warning => 1,
+            Status::Critical => 2,
+            Status::Unknown => 3,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Unknown => "UNKNOWN",
+            Status::Critical => "CRITICAL",
+        })
+    }
+}
+
+/// The `--warn` and `--crit` expressions of a check.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Thresholds {
+    pub warn: Expression,
+    pub crit: Expression,
+}
+
+impl Thresholds {
+    pub fn status(&self, snapshot: &GpuSnapshot) -> Status {
+        if self.crit.matches(snapshot) {
+            Status::Critical
+        } else if self.warn.matches(snapshot) {
+            Status::Warning
+        } else {
+            Status::Ok
+        }
+    }
+
+    /// The metrics the status line shows: utilization and whatever the expressions test.
+    fn shown(&self) -> Vec<Metric> {
+        Metric::ALL
+            .into_iter()
+            .filter(|metric| *metric == Metric::Util || self.warn.conditions().chain(self.crit.conditions()).any(|condition| condition.metric == *metric))
+            .collect()
+    }
+}
+
+/// One perfdata item: `label=value;warn;crit`, with trailing empty fields dropped and the label
+/// quoted when it is not a plain word.
+pub fn format_perfdata(label: &str, value: f32, warn: &str, crit: &str) -> String {
+    let label = if label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        label.to_string()
+    } else {
+        format!("'{}'", label.replace('\'', "''"))
+    };
+    let item = format!("{}={};{};{}", label, format_value(value), warn, crit);
+    item.trim_end_matches(';').to_string()
+}
+
+/// Rounded to two decimals without trailing zeros: `42`, `120.5`.
+fn format_value(value: f32) -> String {
+    let value = format!("{:.2}", value);
+    value.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// The status line and exit status of the samples of every GPU: the worst GPU in the text,
+/// every GPU in the perfdata. Perfdata labels carry a `gpuN_` prefix when there are several
+/// GPUs. A GPU that failed to sample is UNKNOWN.
+pub fn evaluate(samples: &[Result<GpuSnapshot, String>], thresholds: &Thresholds) -> (Status, String) {
+    if samples.is_empty() {
+        return (Status::Unknown, format!("GPU {} - no GPU found", Status::Unknown));
+    }
+
+    let shown = thresholds.shown();
+    let mut worst: Option<(Status, String)> = None;
+    let mut perfdata = Vec::new();
+    for sample in samples {
+        let (status, summary) = match sample {
+            Ok(snapshot) => {
+                let values: Vec<String> = shown
+                    .iter()
+                    .filter_map(|metric| Some(format!("{}={}{}", metric.name(), format_value(metric.value(snapshot)?), metric.unit())))
+                    .collect();
+                for metric in Metric::ALL {
+                    if let Some(value) = metric.value(snapshot) {
+                        let label = if samples.len() > 1 { format!("gpu{}_{}", snapshot.gpu.index, metric.name()) } else { metric.name().to_string() };
+                        perfdata.push(format_perfdata(&label, value, &thresholds.warn.range(metric), &thresholds.crit.range(metric)));
+                    }
+                }
+                (thresholds.status(snapshot), format!("gpu{} {}", snapshot.gpu.index, values.join(" ")))
+            }
+            Err(message) => (Status::Unknown, message.clone()),
+        };
+        if worst.as_ref().is_none_or(|(worst, _)| status > *worst) {
+            worst = Some((status, summary));
+        }
+    }
+
+    let (status, summary) = worst.unwrap_or((Status::Unknown, String::new()));
+    let mut line = format!("GPU {} - {}", status, summary);
+    if !perfdata.is_empty() {
+        line.push_str(&format!(" | {}", perfdata.join(" ")));
+    }
+    (status, line)
+}
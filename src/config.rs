@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The annotated configuration shipped with gpuatop, printed by `gpuatop default-config`.
+pub const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<ConfigValue>),
+}
+
+impl ConfigValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfigValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Key/value pairs of one TOML table, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub entries: Vec<(String, ConfigValue)>,
+}
+
+impl Table {
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        self.entries.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(ConfigValue::as_str)
+    }
+}
+
+/// A parsed TOML document. Only the subset gpuatop's configuration uses is supported:
+/// `[table]` and `[[array-of-tables]]` headers, dotted keys kept verbatim, strings (basic
+/// and literal), numbers, booleans, and single-line arrays.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Document {
+    pub tables: HashMap<String, Table>,
+    pub table_arrays: HashMap<String, Vec<Table>>,
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+fn parse_string(input: &str) -> Result<(String, &str), String> {
+    let quote = input.chars().next().ok_or("Expected string")?;
+    let mut value = String::new();
+    let mut chars = input[1..].char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((value, &input[index + 2..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                other => return Err(format!("Unsupported escape: \\{}", other.unwrap_or(' '))),
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err("Unterminated string".to_string())
+}
+
+fn parse_value(input: &str) -> Result<(ConfigValue, &str), String> {
+    let input = input.trim_start();
+
+    match input.chars().next() {
+        Some('"' | '\'') => parse_string(input).map(|(value, rest)| (ConfigValue::String(value), rest)),
+        Some('[') => {
+            let mut items = Vec::new();
+            let mut rest = input[1..].trim_start();
+
+            while !rest.starts_with(']') {
+                let (item, after) = parse_value(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+                if rest.is_empty() {
+                    return Err("Unterminated array".to_string());
+                }
+            }
+
+            Ok((ConfigValue::Array(items), &rest[1..]))
+        }
+        _ => {
+            let end = input.find([',', ']']).unwrap_or(input.len());
+            let (token, rest) = input.split_at(end);
+            let token = token.trim();
+
+            let value = match token {
+                "true" => ConfigValue::Bool(true),
+                "false" => ConfigValue::Bool(false),
+                _ => match token.replace('_', "").parse::<i64>() {
+                    Ok(integer) => ConfigValue::Integer(integer),
+                    Err(_) => ConfigValue::Float(token.parse().map_err(|_| format!("Invalid value: {}", token))?),
+                },
+            };
+
+            Ok((value, rest))
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Document, String> {
+    let mut document = Document::default();
+    let mut current = Table::default();
+    let mut current_name: Option<(String, bool)> = None;
+
+    let finish = |document: &mut Document, name: Option<(String, bool)>, table: Table| match name {
+        None => document.tables.entry(String::new()).or_default().entries.extend(table.entries),
+        Some((name, true)) => document.table_arrays.entry(name).or_default().push(table),
+        Some((name, false)) => document.tables.entry(name).or_default().entries.extend(table.entries),
+    };
+
+    for (number, line) in input.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|line| line.strip_suffix("]]")) {
+            finish(&mut document, current_name.take(), std::mem::take(&mut current));
+            current_name = Some((name.trim().to_string(), true));
+        } else if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            finish(&mut document, current_name.take(), std::mem::take(&mut current));
+            current_name = Some((name.trim().to_string(), false));
+        } else {
+            let (key, value) = line.split_once('=').ok_or_else(|| error(format!("Expected key = value: {}", line)))?;
+            let (value, rest) = parse_value(value).map_err(error)?;
+
+            if !rest.trim().is_empty() {
+                return Err(error(format!("Unexpected trailing characters: {}", rest.trim())));
+            }
+            current.entries.push((key.trim().trim_matches('"').to_string(), value));
+        }
+    }
+
+    finish(&mut document, current_name, current);
+    Ok(document)
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("gpuatop").join("config.toml"))
+}
+
+/// Loads the configuration from `path`, or from `$XDG_CONFIG_HOME/gpuatop/config.toml` when
+/// no path is given. A missing default file yields an empty configuration.
+pub fn load(path: Option<&str>) -> Result<Document, String> {
+    let (path, explicit) = match path {
+        Some(path) => (PathBuf::from(path), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Document::default()),
+        },
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents).map_err(|err| format!("{}: {}", path.display(), err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !explicit => Ok(Document::default()),
+        Err(err) => Err(format!("{}: {}", path.display(), err)),
+    }
+}
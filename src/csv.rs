@@ -0,0 +1,53 @@
+//! The CSV `nvidia-smi --format=csv` writes: comma-separated fields padded with a space,
+//! quoted when they contain a comma (`"NVIDIA GeForce RTX 4090, rev A"`), with `""` for a
+//! quote inside quotes.
+
+use std::str::FromStr;
+
+/// Splits a line into its fields, unquoted and trimmed. An unterminated quote runs to the end
+/// of the line; anything between a closing quote and the next comma is dropped.
+pub fn parse_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    Some('"') | None => break,
+                    Some(c) => field.push(c),
+                }
+            }
+            while chars.next_if(|c| *c != ',').is_some() {}
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                field.push(c);
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+
+        // The separating comma, or the end of the line.
+        if chars.next().is_none() {
+            return fields;
+        }
+    }
+}
+
+/// Parses a numeric field. Some drivers keep the unit even with `nounits` (`45 %`,
+/// `120.50 W`); `[N/A]` and `[Not Supported]` are `None`.
+pub fn number<T: FromStr>(field: &str) -> Option<T> {
+    field.trim().trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '%').trim_end().parse().ok()
+}
+
+/// Parses a decimal field as [`number`] does, also with a comma as the decimal separator, as
+/// tools print under a locale such as `de_DE`: `42`, `42.37` and `42,37` are all accepted.
+pub fn decimal(field: &str) -> Option<f64> {
+    let field = field.trim().trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '%').trim_end();
+    let field = if field.contains('.') { field.to_string() } else { field.replacen(',', ".", 1) };
+    field.parse().ok().filter(|value: &f64| value.is_finite())
+}
@@ -0,0 +1,220 @@
+use std::io;
+
+use crate::config::{ConfigValue, Document, Table};
+use crate::json::{self, Value};
+use crate::regex::Regex;
+use crate::runner::CommandRunner;
+use crate::{clamp_percent, GpuInfo, GpuSnapshot, PollResult};
+
+const FIELDS: [&str; 6] = ["util", "mem_used_mib", "mem_total_mib", "temp_c", "power_w", "name"];
+
+#[derive(Debug, Clone)]
+enum Parser {
+    Regex(Regex),
+    Json { devices: String, fields: Vec<(String, String)> },
+}
+
+/// A user-defined backend from the `[[custom_backend]]` configuration tables.
+#[derive(Debug, Clone)]
+pub struct CustomBackend {
+    pub name: String,
+    command: Vec<String>,
+    parser: Parser,
+}
+
+/// Raw field values of one device, keyed by sample field name.
+type DeviceFields = Vec<(String, String)>;
+
+fn from_table(table: &Table) -> Result<CustomBackend, String> {
+    let name = table.get_str("name").ok_or("custom_backend requires a name")?.to_string();
+    let error = |message: &str| format!("custom_backend '{}': {}", name, message);
+
+    let command: Vec<String> = match table.get("command") {
+        Some(ConfigValue::Array(items)) => items.iter().filter_map(ConfigValue::as_str).map(str::to_string).collect(),
+        Some(ConfigValue::String(command)) => command.split_whitespace().map(str::to_string).collect(),
+        _ => return Err(error("requires a command")),
+    };
+    if command.is_empty() {
+        return Err(error("command is empty"));
+    }
+
+    let parser = match (table.get_str("regex"), table.get_str("json_devices")) {
+        (Some(pattern), None) => {
+            let regex = Regex::new(pattern).map_err(|err| error(&format!("invalid regex: {}", err)))?;
+            if let Some(unknown) = regex.group_names().find(|group| !FIELDS.contains(group)) {
+                return Err(error(&format!("unknown field '{}' in regex", unknown)));
+            }
+            if !regex.group_names().any(|group| group == "util") {
+                return Err(error("regex must capture 'util'"));
+            }
+            Parser::Regex(regex)
+        }
+        (None, Some(devices)) => {
+            let fields: Vec<(String, String)> = table
+                .entries
+                .iter()
+                .filter_map(|(key, value)| Some((key.strip_prefix("field.")?.to_string(), value.as_str()?.to_string())))
+                .collect();
+            if let Some((unknown, _)) = fields.iter().find(|(field, _)| !FIELDS.contains(&field.as_str())) {
+                return Err(error(&format!("unknown field '{}'", unknown)));
+            }
+            if !fields.iter().any(|(field, _)| field == "util") {
+                return Err(error("requires field.util"));
+            }
+            Parser::Json { devices: devices.to_string(), fields }
+        }
+        _ => return Err(error("requires exactly one of regex or json_devices")),
+    };
+
+    Ok(CustomBackend { name, command, parser })
+}
+
+pub fn from_config(config: &Document) -> Result<Vec<CustomBackend>, String> {
+    config.table_arrays.get("custom_backend").into_iter().flatten().map(from_table).collect()
+}
+
+/// Resolves a jq-style path such as `.gpus[0].memory.used` against `value`.
+pub fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (key, indices) = segment.split_once('[').map_or((segment, ""), |(key, rest)| (key, rest));
+
+        if !key.is_empty() {
+            current = match current {
+                Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value)?,
+                _ => return None,
+            };
+        }
+
+        for index in indices.split('[').filter(|index| !index.is_empty()) {
+            let index: usize = index.trim_end_matches(']').parse().ok()?;
+            current = match current {
+                Value::Array(items) => items.get(index)?,
+                _ => return None,
+            };
+        }
+    }
+
+    Some(current)
+}
+
+impl CustomBackend {
+    fn parse(&self, output: &str) -> Result<Vec<DeviceFields>, String> {
+        match &self.parser {
+            Parser::Regex(regex) => Ok(regex
+                .captures_iter(output)
+                .map(|captures| {
+                    FIELDS
+                        .iter()
+                        .filter_map(|field| Some((field.to_string(), captures.name(field)?.trim().to_string())))
+                        .collect()
+                })
+                .collect()),
+            Parser::Json { devices, fields } => {
+                let document = json::parse(output)?;
+                let devices = match json_path(&document, devices) {
+                    Some(Value::Array(devices)) => devices.iter().collect(),
+                    Some(device @ Value::Object(_)) => vec![device],
+                    _ => return Err(format!("'{}' is not an array of devices", devices)),
+                };
+
+                Ok(devices
+                    .into_iter()
+                    .map(|device| {
+                        fields
+                            .iter()
+                            .filter_map(|(field, path)| Some((field.clone(), json_path(device, path)?.to_display())))
+                            .collect()
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    fn run(&self, runner: &dyn CommandRunner) -> io::Result<String> {
+        let args: Vec<&str> = self.command[1..].iter().map(String::as_str).collect();
+        let output = runner.run(&self.command[0], &args)?;
+
+        if !output.success {
+            return Err(io::Error::other(match output.code {
+                Some(code) => format!("{} exited with code {}", self.command[0], code),
+                None => format!("{} was terminated by a signal", self.command[0]),
+            }));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Runs the command once to discover the backend's devices, numbering them from `first_index`.
+    pub fn enumerate(&self, runner: &dyn CommandRunner, first_index: u32) -> Result<Vec<GpuInfo>, String> {
+        let devices = self.run(runner).map_err(|err| err.to_string()).and_then(|output| self.parse(&output))?;
+
+        Ok(devices
+            .iter()
+            .enumerate()
+            .map(|(position, fields)| GpuInfo {
+                index: first_index + position as u32,
+                name: field(fields, "name").map_or_else(|| format!("{} {}", self.name, position), str::to_string),
+                bus_id: None,
+                render_offload: None,
+            })
+            .collect())
+    }
+
+    /// Samples the backend's devices. Devices are matched to `gpus` by position in the output.
+    pub fn poll(&self, runner: &dyn CommandRunner, gpus: &[GpuInfo], all_gpus: &[GpuInfo]) -> Vec<PollResult> {
+        let output = match self.run(runner) {
+            Ok(output) => output,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return gpus.iter().map(|gpu| PollResult::PermanentError { gpu: gpu.clone(), message: err.to_string(), retries: 0 }).collect();
+            }
+            Err(err) => {
+                return gpus
+                    .iter()
+                    .map(|gpu| PollResult::TransientError { gpu: gpu.clone(), message: err.to_string(), retries: 0 })
+                    .collect();
+            }
+        };
+
+        let devices = self.parse(&output);
+
+        gpus.iter()
+            .map(|gpu| {
+                let position = all_gpus.iter().position(|candidate| candidate.index == gpu.index);
+                let fields = match (&devices, position) {
+                    (Ok(devices), Some(position)) => devices.get(position),
+                    _ => None,
+                };
+                let utilization = fields.and_then(|fields| parse_field(fields, "util")).map(clamp_percent);
+
+                match (fields, utilization) {
+                    (Some(fields), Some(utilization)) => PollResult::Ok { snapshot: GpuSnapshot {
+                        memory_used_mib: parse_field(fields, "mem_used_mib"),
+                        memory_total_mib: parse_field(fields, "mem_total_mib"),
+                        temperature_c: parse_field(fields, "temp_c"),
+                        power_w: parse_field(fields, "power_w"),
+                        ..GpuSnapshot::new(gpu.clone(), utilization)
+                    }, retries: 0 },
+                    _ => PollResult::TransientError {
+                        gpu: gpu.clone(),
+                        message: match &devices {
+                            Err(err) => format!("{}: {}", self.name, err),
+                            Ok(_) => format!("{}: no value for device in output", self.name),
+                        },
+                        retries: 0,
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+fn field<'a>(fields: &'a DeviceFields, name: &str) -> Option<&'a str> {
+    fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+}
+
+fn parse_field<T: std::str::FromStr>(fields: &DeviceFields, name: &str) -> Option<T> {
+    let value = field(fields, name)?;
+    value.parse().ok().or_else(|| value.parse::<f64>().ok().and_then(|value| value.round().to_string().parse().ok()))
+}
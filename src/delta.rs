@@ -0,0 +1,131 @@
+//! `--diff-output`: a GPU's sample is only printed when one of its metrics changed by more
+//! than `--diff-threshold` percent since it was last printed.
+
+use std::collections::HashMap;
+
+use crate::output::{json_string, OutputContext};
+use crate::schema::SCHEMA_VERSION;
+use crate::{widen, GpuSnapshot};
+
+/// A metric that changed, with its last printed and its current value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub previous: Option<f64>,
+    pub current: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDelta {
+    pub gpu: u32,
+    pub changes: Vec<FieldChange>,
+}
+
+impl SnapshotDelta {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// The compared metrics, named by their JSON keys.
+fn metrics(snapshot: &GpuSnapshot) -> [(&'static str, Option<f64>); 8] {
+    [
+        ("utilization", Some(snapshot.utilization)),
+        ("memory_used_mib", snapshot.memory_used_mib.map(|value| value as f64)),
+        ("memory_total_mib", snapshot.memory_total_mib.map(|value| value as f64)),
+        ("temperature_c", snapshot.temperature_c.map(widen)),
+        ("power_w", snapshot.power_w.map(widen)),
+        ("memory_bandwidth_utilization", snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct)),
+        ("desktop_utilization", snapshot.usage_split.as_ref().map(|split| split.desktop)),
+        ("apps_utilization", snapshot.usage_split.as_ref().map(|split| split.apps)),
+    ]
+}
+
+/// Whether `current` differs from `previous` by more than `threshold` percent of `previous`.
+/// A metric appearing or disappearing is always a change.
+fn changed(previous: Option<f64>, current: Option<f64>, threshold: f32) -> bool {
+    match (previous, current) {
+        (Some(previous), Some(current)) => (current - previous).abs() > f64::from(threshold) / 100.0 * previous.abs(),
+        (previous, current) => previous.is_some() != current.is_some(),
+    }
+}
+
+/// Lists the metrics of `curr` that differ from `prev` by more than `threshold` percent.
+pub fn diff_snapshots(prev: &GpuSnapshot, curr: &GpuSnapshot, threshold: f32) -> SnapshotDelta {
+    let changes = metrics(prev)
+        .into_iter()
+        .zip(metrics(curr))
+        .filter(|((_, previous), (_, current))| changed(*previous, *current, threshold))
+        .map(|((field, previous), (_, current))| FieldChange { field, previous, current })
+        .collect();
+
+    SnapshotDelta { gpu: curr.gpu.index, changes }
+}
+
+/// What to print for a sample in `--diff-output` mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The GPU's first sample, printed in full.
+    First,
+    Changed(SnapshotDelta),
+    Unchanged,
+}
+
+/// Remembers the last printed sample of every GPU. Samples are compared against that rather
+/// than the previous sample, so a slow drift is printed once it adds up to the threshold.
+#[derive(Debug, Default)]
+pub struct DeltaTracker {
+    threshold: f32,
+    printed: HashMap<u32, GpuSnapshot>,
+}
+
+impl DeltaTracker {
+    pub fn new(threshold: f32) -> Self {
+        DeltaTracker { threshold, printed: HashMap::new() }
+    }
+
+    pub fn update(&mut self, snapshot: &GpuSnapshot) -> Change {
+        let change = match self.printed.get(&snapshot.gpu.index) {
+            None => Change::First,
+            Some(printed) => match diff_snapshots(printed, snapshot, self.threshold) {
+                delta if delta.is_empty() => return Change::Unchanged,
+                delta => Change::Changed(delta),
+            },
+        };
+
+        self.printed.insert(snapshot.gpu.index, snapshot.clone());
+        change
+    }
+}
+
+/// Formats a delta as a JSON record whose `delta` object holds the changed metrics only;
+/// metrics that disappeared are `null`.
+pub fn format_json(delta: &SnapshotDelta, context: &OutputContext) -> String {
+    let mut fields = vec![format!("\"schema_version\":{}", SCHEMA_VERSION)];
+
+    if let Some(hostname) = &context.hostname {
+        fields.push(format!("\"hostname\":{}", json_string(hostname)));
+    }
+    fields.push(format!("\"gpu\":{}", delta.gpu));
+    if !context.labels.is_empty() {
+        fields.push(format!("\"labels\":{}", context.labels.to_json()));
+    }
+
+    let changes: Vec<String> = delta
+        .changes
+        .iter()
+        .map(|change| match change.current {
+            Some(current) => format!("{}:{}", json_string(change.field), current),
+            None => format!("{}:null", json_string(change.field)),
+        })
+        .collect();
+    fields.push(format!("\"delta\":{{{}}}", changes.join(",")));
+    if let Some(tick_seq) = context.tick_seq {
+        fields.push(format!("\"tick_seq\":{}", tick_seq));
+    }
+    if let Some(timestamp) = &context.timestamp {
+        fields.push(format!("\"ts\":{}", timestamp.to_json()));
+    }
+
+    format!("{{{}}}", fields.join(","))
+}
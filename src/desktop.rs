@@ -0,0 +1,91 @@
+//! Splits GPU utilization into desktop overhead (compositors and display servers) and
+//! application usage, from the per-process utilization reported by the vendor tool.
+//!
+//! Every process is counted exactly once: a process whose name is in the desktop list is
+//! desktop, everything else is an application. Xwayland is in the default list. The GPU time
+//! attributed to its PID is spent presenting X11 windows, while the rendering of the X11
+//! clients themselves is attributed to their own PIDs and counted under apps.
+
+use std::collections::HashMap;
+
+use crate::config::{ConfigValue, Document};
+use crate::process::GpuProcess;
+use crate::{clamp_percent, widen};
+
+/// Compositors and display servers classified as desktop out of the box.
+pub const DEFAULT_DESKTOP_PROCESSES: [&str; 8] = ["Xorg", "Xwayland", "gnome-shell", "kwin_wayland", "kwin_x11", "sway", "Hyprland", "mutter"];
+
+/// Length the kernel truncates process names (`comm`) to, which is what vendor tools report.
+const COMM_LENGTH: usize = 15;
+
+/// Utilization of one GPU split by process class, in percent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UsageSplit {
+    pub desktop: f64,
+    pub apps: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopClassifier {
+    names: Vec<String>,
+}
+
+impl Default for DesktopClassifier {
+    fn default() -> Self {
+        DesktopClassifier { names: DEFAULT_DESKTOP_PROCESSES.iter().map(|name| name.to_string()).collect() }
+    }
+}
+
+impl DesktopClassifier {
+    /// The default list extended with `processes` from the `[desktop]` configuration table.
+    pub fn from_config(config: &Document) -> Result<Self, String> {
+        let mut classifier = DesktopClassifier::default();
+
+        match config.tables.get("desktop").and_then(|table| table.get("processes")) {
+            None => {}
+            Some(ConfigValue::Array(items)) => {
+                for item in items {
+                    let name = item.as_str().ok_or("desktop.processes must be an array of strings")?;
+                    classifier.names.push(name.to_string());
+                }
+            }
+            Some(_) => return Err("desktop.processes must be an array of strings".to_string()),
+        }
+
+        Ok(classifier)
+    }
+
+    /// Matches process names exactly, or by their first 15 characters when the reported name
+    /// was truncated to the kernel's `comm` length. A leading path is ignored.
+    pub fn is_desktop(&self, name: &str) -> bool {
+        let name = name.rsplit('/').next().unwrap_or(name);
+
+        self.names.iter().any(|desktop| {
+            desktop == name || (name.len() == COMM_LENGTH && desktop.len() > COMM_LENGTH && desktop.starts_with(name))
+        })
+    }
+
+    /// Sums per-process utilization by class for every GPU with at least one process that
+    /// reports utilization.
+    pub fn split(&self, processes: &[GpuProcess]) -> HashMap<u32, UsageSplit> {
+        let mut splits: HashMap<u32, UsageSplit> = HashMap::new();
+
+        for process in processes {
+            let Some(utilization) = process.utilization else { continue };
+            let split = splits.entry(process.gpu_index).or_default();
+
+            if self.is_desktop(&process.name) {
+                split.desktop += widen(utilization);
+            } else {
+                split.apps += widen(utilization);
+            }
+        }
+
+        // Per-process shares are sampled apart and can add up to a little over 100%.
+        for split in splits.values_mut() {
+            split.desktop = clamp_percent(split.desktop.min(100.0));
+            split.apps = clamp_percent(split.apps.min(100.0));
+        }
+        splits
+    }
+}
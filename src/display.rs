@@ -0,0 +1,28 @@
+//! The text display: its layouts, and whether gpuatop writes ANSI colors (today the
+//! highlighted total row of `--aggregate`).
+//!
+//! Colors are decided once at startup: `--force-color`, then `--no-color`, then the `NO_COLOR`
+//! convention (<https://no-color.org>, any non-empty value), then whether stdout is a terminal.
+
+pub mod detail;
+pub mod grid;
+pub mod layout;
+pub mod table;
+
+use std::ffi::OsStr;
+use std::io::{self, IsTerminal};
+
+pub fn should_use_color(force_color: bool, no_color: bool) -> bool {
+    decide(force_color, no_color, std::env::var_os("NO_COLOR").as_deref(), io::stdout().is_terminal())
+}
+
+/// `should_use_color` without the environment, for tests.
+pub fn decide(force_color: bool, no_color: bool, no_color_env: Option<&OsStr>, terminal: bool) -> bool {
+    if force_color {
+        return true;
+    }
+    if no_color || no_color_env.is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    terminal
+}
@@ -0,0 +1,208 @@
+//! The detail view the keyboard zooms into: one GPU's `verbose` block, sparklines of its last
+//! minute with its VRAM headroom, and its processes, printed instead of every GPU's line.
+//!
+//! `--persist-history <file>` keeps the sparklines across restarts: the history is saved on
+//! exit and loaded on startup, encoded with bincode. The file starts with [`HISTORY_MAGIC`]
+//! and [`HISTORY_VERSION`], so that one written in another format is rejected rather than read
+//! as garbage.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use super::layout::{self, Layout, BLOCKS};
+use crate::process::GpuProcess;
+use crate::GpuSnapshot;
+
+/// How far back the sparklines reach.
+pub const HISTORY_SPAN: Duration = Duration::from_secs(60);
+
+/// The widest sparkline; intervals shorter than a second show less than [`HISTORY_SPAN`].
+const MAX_POINTS: usize = 60;
+
+/// The first bytes of a `--persist-history` file.
+pub const HISTORY_MAGIC: &[u8; 8] = b"GPUATOPH";
+
+/// The layout of the points in a `--persist-history` file, raised with every change to it.
+pub const HISTORY_VERSION: u32 = 2;
+
+/// What the text monitor shows: every GPU, or one GPU in detail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    Overview,
+    Detail(u32),
+}
+
+/// The metrics a sparkline is drawn for, in the order of the verbose block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct Point {
+    utilization: f32,
+    memory_percent: Option<f32>,
+    temperature_c: Option<f32>,
+    power_w: Option<f32>,
+    /// Not drawn: the headroom line reads it.
+    free_mib: Option<u64>,
+}
+
+/// The last [`HISTORY_SPAN`] of samples of every GPU, recorded whatever the view so that
+/// zooming in shows a full minute at once.
+#[derive(Debug)]
+pub struct History {
+    capacity: usize,
+    span: Duration,
+    points: HashMap<u32, VecDeque<Point>>,
+}
+
+impl History {
+    /// A history for one sample per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        let capacity = ((HISTORY_SPAN.as_secs_f64() / interval.as_secs_f64().max(0.001)).ceil() as usize).clamp(2, MAX_POINTS);
+        Self::with_capacity(interval, capacity)
+    }
+
+    /// A history of the last `capacity` samples, `--max-util-history`.
+    pub fn with_capacity(interval: Duration, capacity: usize) -> Self {
+        History { capacity, span: interval * capacity as u32, points: HashMap::new() }
+    }
+
+    pub fn record(&mut self, snapshot: &GpuSnapshot) {
+        let memory_percent = match (snapshot.memory_used_mib, snapshot.memory_total_mib) {
+            (Some(used), Some(total)) if total > 0 => Some(used as f32 * 100.0 / total as f32),
+            _ => None,
+        };
+        let free_mib = snapshot.memory_total_mib.zip(snapshot.memory_used_mib).map(|(total, used)| total.saturating_sub(used));
+        let point = Point { utilization: snapshot.utilization as f32, memory_percent, temperature_c: snapshot.temperature_c, power_w: snapshot.power_w, free_mib };
+
+        self.push(snapshot.gpu.index, point);
+    }
+
+    fn push(&mut self, gpu: u32, point: Point) {
+        let points = self.points.entry(gpu).or_default();
+        points.push_back(point);
+        if points.len() > self.capacity {
+            points.pop_front();
+        }
+    }
+
+    /// The history in the `--persist-history` format: the header, then the bincode encoding of
+    /// every GPU's index and points, oldest first.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut gpus: Vec<(&u32, &VecDeque<Point>)> = self.points.iter().collect();
+        gpus.sort_by_key(|(gpu, _)| **gpu);
+
+        let mut bytes = HISTORY_MAGIC.to_vec();
+        bytes.extend(HISTORY_VERSION.to_le_bytes());
+        // Serializing plain numbers and sequences into memory cannot fail.
+        bytes.extend(bincode_options().serialize(&gpus).expect("the history serializes"));
+        bytes
+    }
+
+    /// Adds the points of a `--persist-history` file, keeping the last `capacity` per GPU.
+    /// Nothing is added from a file that is not one, has another version or is cut short.
+    pub fn extend_from_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let Some(rest) = bytes.strip_prefix(HISTORY_MAGIC) else {
+            return Err("not a gpuatop history file".to_string());
+        };
+        let (version, body) = rest.split_first_chunk::<4>().ok_or("the history file is truncated")?;
+        let version = u32::from_le_bytes(*version);
+        if version != HISTORY_VERSION {
+            return Err(format!("history format version {} is not supported (expected {})", version, HISTORY_VERSION));
+        }
+
+        let loaded: Vec<(u32, Vec<Point>)> = bincode_options().with_limit(body.len() as u64).deserialize(body).map_err(|err| match *err {
+            bincode::ErrorKind::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => "the history file is truncated".to_string(),
+            bincode::ErrorKind::SizeLimit => "the history file is truncated".to_string(),
+            err => format!("invalid history file: {}", err),
+        })?;
+        for (gpu, points) in loaded {
+            for point in points {
+                self.push(gpu, point);
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a `--persist-history` file; a file that does not exist yet is an empty history.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        match fs::read(path) {
+            Ok(bytes) => self.extend_from_bytes(&bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// One labelled sparkline per metric the GPU reported in the window. Utilization and
+    /// memory are drawn against 100%, temperature and power against their peak.
+    pub fn sparklines(&self, gpu: u32) -> Vec<String> {
+        let Some(points) = self.points.get(&gpu) else {
+            return Vec::new();
+        };
+        let series = [
+            ("Utilization", points.iter().map(|point| Some(point.utilization)).collect::<Vec<_>>(), Some(100.0)),
+            ("Memory", points.iter().map(|point| point.memory_percent).collect(), Some(100.0)),
+            ("Temperature", points.iter().map(|point| point.temperature_c).collect(), None),
+            ("Power", points.iter().map(|point| point.power_w).collect(), None),
+        ];
+
+        let mut lines = vec![format!("  Last {}s:", self.span.as_secs())];
+        for (label, values, max) in series {
+            if values.iter().all(Option::is_none) {
+                continue;
+            }
+            let max = max.or_else(|| values.iter().flatten().copied().reduce(f32::max)).unwrap_or_default();
+            lines.push(format!("    {:<12} {}", format!("{}:", label), sparkline(&values, max)));
+        }
+        if let Some((now, least)) = self.headroom(gpu) {
+            lines.push(format!("    {:<12} {} MiB free, at least {} MiB", "Headroom:", now, least));
+        }
+        lines
+    }
+
+    /// The free VRAM of the GPU's latest sample, and the least free over the window: what is
+    /// safe to claim for another job.
+    pub fn headroom(&self, gpu: u32) -> Option<(u64, u64)> {
+        let points = self.points.get(&gpu)?;
+        let now = points.back()?.free_mib?;
+        let least = points.iter().filter_map(|point| point.free_mib).min()?;
+        Some((now, least))
+    }
+}
+
+/// The bincode settings of the `--persist-history` format: fixed-width little-endian numbers,
+/// and no bytes after the history.
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_fixint_encoding().with_little_endian().reject_trailing_bytes()
+}
+
+/// One block per value from 0 to `max`; a missing value is a space.
+pub fn sparkline(values: &[Option<f32>], max: f32) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            Some(value) if max > 0.0 => {
+                let level = (value.clamp(0.0, max) / max * (BLOCKS.len() - 1) as f32).round();
+                BLOCKS[level as usize]
+            }
+            Some(_) => BLOCKS[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+/// The lines of the detail view of `snapshot`'s GPU.
+pub fn format_detail(snapshot: &GpuSnapshot, history: &History, processes: &[&GpuProcess]) -> Vec<String> {
+    let mut lines: Vec<String> = layout::format_snapshot(snapshot, Layout::Verbose).lines().map(str::to_string).collect();
+    lines.extend(history.sparklines(snapshot.gpu.index));
+    lines.extend(layout::format_processes(processes));
+    lines
+}
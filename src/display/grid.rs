@@ -0,0 +1,70 @@
+//! `--format table`: every tick as one bordered table with a row per GPU, box-drawn when the
+//! locale is UTF-8 and in ASCII otherwise. On a terminal, each table is drawn over the last
+//! one, like `watch`, with warnings and events scrolling above it (see [`crate::live`]); piped,
+//! the tables follow one another.
+
+use crate::display::table::{Align, Column, Table, Width};
+use crate::power::DeviceState;
+use crate::{GpuInfo, GpuSnapshot};
+
+/// Long GPU names are cut so that the table fits a terminal.
+const NAME_WIDTH: usize = 32;
+
+/// Whether the terminal can show box-drawing characters, from the locale.
+pub fn supports_unicode() -> bool {
+    let variable = |name: &str| std::env::var(name).ok();
+    decide_unicode(variable("LC_ALL").as_deref(), variable("LC_CTYPE").as_deref(), variable("LANG").as_deref())
+}
+
+/// `supports_unicode` without the environment, for tests: the first of `LC_ALL`, `LC_CTYPE`
+/// and `LANG` that is set decides, as it does for the C library.
+pub fn decide_unicode(lc_all: Option<&str>, lc_ctype: Option<&str>, lang: Option<&str>) -> bool {
+    [lc_all, lc_ctype, lang].into_iter().flatten().find(|value| !value.is_empty()).is_some_and(|locale| {
+        let locale = locale.to_ascii_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+/// One row of the table, collected until the end of the tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    index: u32,
+    cells: Vec<String>,
+}
+
+fn gpu_cell(gpu: &GpuInfo) -> String {
+    format!("{} {}", gpu.index, gpu.name)
+}
+
+pub fn snapshot_row(snapshot: &GpuSnapshot) -> Row {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    Row {
+        index: snapshot.gpu.index,
+        cells: vec![
+            gpu_cell(&snapshot.gpu),
+            format!("{:.1}", snapshot.utilization),
+            optional(snapshot.memory_used_mib.map(|used| format!("{} MiB", used))),
+            optional(snapshot.memory_total_mib.map(|total| format!("{} MiB", total))),
+            optional(snapshot.temperature_c.map(|temperature| format!("{:.0}", temperature))),
+            optional(snapshot.power_w.map(|power| format!("{:.1}", power))),
+        ],
+    }
+}
+
+/// The row of a GPU that was not sampled: its state where the utilization would be.
+pub fn state_row(gpu: &GpuInfo, state: DeviceState) -> Row {
+    Row { index: gpu.index, cells: vec![gpu_cell(gpu), state.as_str().to_string()] }
+}
+
+/// Sorts the rows by GPU index and draws them as a table.
+pub fn format_grid(rows: &mut [Row], box_drawing: bool) -> Vec<String> {
+    rows.sort_by_key(|row| row.index);
+
+    let right = |name: &str| Column::new(name).align(Align::Right);
+    let columns = vec![Column::new("GPU").width(Width::Max(NAME_WIDTH)), right("Util%"), right("Mem Used"), right("Mem Total"), right("Temp °C"), right("Power W")];
+    let mut table = Table::new(columns).borders(true).box_drawing(box_drawing);
+    for row in rows.iter() {
+        table.row(row.cells.clone());
+    }
+    table.render()
+}
@@ -0,0 +1,163 @@
+//! `--layout`: how much of each sample the text output shows.
+
+use std::str::FromStr;
+
+use super::table::{Align, Column, Table};
+use crate::efficiency::Efficiency;
+use crate::idle::{self, Activity};
+use crate::output;
+use crate::prime::RenderOffloadMode;
+use crate::process::{count_contexts, GpuProcess};
+use crate::GpuSnapshot;
+
+/// The eight block elements `compact` draws the utilization with, from 0 to 100%, and the
+/// detail view its sparklines.
+pub const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// One character per GPU, all GPUs of a tick on one line: for status bars and scripts.
+    Compact,
+    /// One line per GPU.
+    #[default]
+    Normal,
+    /// A block per GPU with one metric per line and the GPU's processes.
+    Verbose,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "compact" => Layout::Compact,
+            "normal" => Layout::Normal,
+            "verbose" => Layout::Verbose,
+            _ => return Err(format!("Unknown layout: {} (expected compact, normal or verbose)", s)),
+        })
+    }
+}
+
+/// Formats a sample in `layout`, without the `--machine-hostname` prefix. `verbose` returns
+/// several lines.
+pub fn format_snapshot(snapshot: &GpuSnapshot, layout: Layout) -> String {
+    match layout {
+        Layout::Compact => block(snapshot.utilization as f32).to_string(),
+        Layout::Normal => output::format_text(snapshot, output::DEFAULT_PRECISION),
+        Layout::Verbose => format_verbose(snapshot).join("\n"),
+    }
+}
+
+fn block(utilization: f32) -> char {
+    let level = (utilization.clamp(0.0, 100.0) / 100.0 * (BLOCKS.len() - 1) as f32).round();
+    BLOCKS[level as usize]
+}
+
+fn format_verbose(snapshot: &GpuSnapshot) -> Vec<String> {
+    let mut metrics = Vec::new();
+
+    metrics.push(match snapshot.utilization_max {
+        Some(max) => ("Utilization", format!("{:.1}% (max {:.1}%)", snapshot.utilization, max)),
+        None => ("Utilization", format!("{:.1}%", snapshot.utilization)),
+    });
+    match (snapshot.memory_used_mib, snapshot.memory_total_mib) {
+        (Some(used), Some(total)) if total > 0 => {
+            metrics.push(("Memory", format!("{}/{} MiB ({:.1}%)", used, total, used as f64 / total as f64 * 100.0)))
+        }
+        (Some(used), _) => metrics.push(("Memory", format!("{} MiB", used))),
+        _ => {}
+    }
+    if let Some(temperature) = snapshot.temperature_c {
+        metrics.push(("Temperature", format!("{}°C", temperature)));
+    }
+    if let Some(temperatures) = snapshot.temperatures.as_ref().filter(|temperatures| !temperatures.is_empty()) {
+        metrics.push(("Sensors", output::format_temperatures(temperatures)));
+    }
+    if let Some(power) = snapshot.power_w {
+        metrics.push(("Power", format!("{} W", power)));
+    }
+    if let Some(nvlink) = &snapshot.nvlink {
+        metrics.push(("NVLink", format!("TX {:.0} KiB/s, RX {:.0} KiB/s", nvlink.tx_kib_per_s, nvlink.rx_kib_per_s)));
+        metrics.push(("NVLink errors", format!("{} replay, {} CRC", nvlink.replay_errors, nvlink.crc_errors)));
+    }
+    if let Some(split) = &snapshot.usage_split {
+        metrics.push(("Desktop", format!("{:.1}%", split.desktop)));
+        metrics.push(("Apps", format!("{:.1}%", split.apps)));
+    }
+    if let Some(bandwidth) = &snapshot.memory_bandwidth {
+        if let Some(utilization) = bandwidth.utilization_pct {
+            metrics.push(("Memory bandwidth", format!("{:.1}%", utilization)));
+        }
+        if let (Some(read), Some(write)) = (bandwidth.read_gbps, bandwidth.write_gbps) {
+            metrics.push(("Memory bandwidth", format!("{:.2} GB/s read, {:.2} GB/s write", read, write)));
+        }
+    }
+    if let Some(aperture) = &snapshot.aperture {
+        if let Some(reserved) = aperture.reserved_mib {
+            metrics.push(("Reserved", format!("{} MiB", reserved)));
+        }
+        if let (Some(used), Some(total)) = (aperture.bar1_used_mib, aperture.bar1_total_mib) {
+            metrics.push(("BAR1", format!("{}/{} MiB", used, total)));
+        }
+        if let (Some(used), Some(total)) = (aperture.vis_vram_used_mib, aperture.vis_vram_total_mib) {
+            metrics.push(("Visible VRAM", format!("{}/{} MiB", used, total)));
+        }
+    }
+    match snapshot.activity {
+        Some(Activity::Active) => metrics.push(("Idle", "no".to_string())),
+        Some(Activity::Idle(idle)) => metrics.push(("Idle", format!("for {}", idle::format_duration(idle)))),
+        None => {}
+    }
+    match snapshot.efficiency {
+        Some(Efficiency::TflopsPerWatt(efficiency)) => metrics.push(("Efficiency", format!("{:.2} TFLOPS/W", efficiency))),
+        Some(Efficiency::Unavailable) => metrics.push(("Efficiency", "N/A".to_string())),
+        None => {}
+    }
+
+    let mut header = format!("GPU {} ({})", snapshot.gpu.index, snapshot.gpu.name);
+    if let Some(bus_id) = &snapshot.gpu.bus_id {
+        header.push_str(&format!(" at {}", bus_id));
+    }
+    if snapshot.gpu.render_offload == Some(RenderOffloadMode::OffloadGpu) {
+        header.push_str(" [PRIME offload]");
+    }
+
+    let width = metrics.iter().map(|(label, _)| label.len() + 1).max().unwrap_or(0);
+    let mut lines = vec![header];
+    lines.extend(metrics.into_iter().map(|(label, value)| format!("  {:<width$} {}", format!("{}:", label), value, width = width)));
+    lines
+}
+
+/// The `verbose` process table of one GPU, indented under its block. Empty without processes.
+pub fn format_processes(processes: &[&GpuProcess]) -> Vec<String> {
+    if processes.is_empty() {
+        return Vec::new();
+    }
+
+    let columns = vec![
+        Column::new("PID").align(Align::Right),
+        Column::new("Name"),
+        Column::new("Busy").align(Align::Right),
+        Column::new("Memory").align(Align::Right),
+    ];
+    let mut table = Table::new(columns).indent(4);
+    for process in processes {
+        table.row(vec![
+            process.pid.to_string(),
+            process.name.clone(),
+            process.utilization.map_or("-".to_string(), |utilization| format!("{}%", utilization)),
+            process.memory_used_mib.map_or("-".to_string(), |memory| format!("{} MiB", memory)),
+        ]);
+    }
+
+    // The counts only where the source tells the context kinds apart.
+    let header = if processes.iter().all(|process| process.kind.is_none()) {
+        "  Processes:".to_string()
+    } else {
+        let (compute, graphics) = count_contexts(processes);
+        format!("  Processes: {} compute, {} graphics", compute, graphics)
+    };
+    let mut lines = vec![header];
+    lines.extend(table.render());
+    lines
+}
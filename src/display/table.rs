@@ -0,0 +1,158 @@
+//! Aligned columns for the text output: the `--aggregate` and `--by-user` tables, the
+//! `verbose` process lists, the topology matrix, the exit summary and `--format table`.
+
+/// How a column's cells are padded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Right,
+}
+
+/// How wide a column is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Width {
+    /// As wide as its widest cell, header included.
+    #[default]
+    Fit,
+    /// As wide as its widest cell, up to a limit; longer cells are cut and end in `…`.
+    Max(usize),
+    /// Exactly this wide, cutting longer cells like `Max`.
+    Fixed(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    name: String,
+    align: Align,
+    width: Width,
+}
+
+impl Column {
+    pub fn new(name: &str) -> Self {
+        Column { name: name.to_string(), align: Align::Left, width: Width::Fit }
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn width(mut self, width: Width) -> Self {
+        self.width = width;
+        self
+    }
+}
+
+/// A table built column by column and row by row, rendered as lines by `comfy_table`. Without borders the
+/// columns are separated by two spaces and lines carry no trailing whitespace; with borders
+/// it is an ASCII grid, or a box-drawn one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+    header: bool,
+    borders: bool,
+    box_drawing: bool,
+    indent: usize,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>) -> Self {
+        Table { columns, rows: Vec::new(), header: true, borders: false, box_drawing: false, indent: 0 }
+    }
+
+    /// Leaves out the header line, for tables whose cells speak for themselves.
+    pub fn without_header(mut self) -> Self {
+        self.header = false;
+        self
+    }
+
+    pub fn borders(mut self, borders: bool) -> Self {
+        self.borders = borders;
+        self
+    }
+
+    /// Draws the borders with Unicode box-drawing characters instead of `+`, `-` and `|`.
+    pub fn box_drawing(mut self, box_drawing: bool) -> Self {
+        self.box_drawing = box_drawing;
+        self
+    }
+
+    /// Spaces before every line, for a table nested under a heading.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Adds a row; missing cells are empty and extra cells are dropped.
+    pub fn row(&mut self, mut cells: Vec<String>) {
+        cells.resize(self.columns.len(), String::new());
+        self.rows.push(cells);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// The most every column may show, or `None` where it fits its content.
+    fn limits(&self) -> Vec<Option<usize>> {
+        self.columns
+            .iter()
+            .map(|column| match column.width {
+                Width::Fit => None,
+                Width::Max(limit) | Width::Fixed(limit) => Some(limit),
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> Vec<String> {
+        let limits = self.limits();
+        let cut = |cells: &[String]| -> Vec<String> { cells.iter().zip(&limits).map(|(cell, limit)| limit.map_or_else(|| cell.clone(), |limit| truncate(cell, limit))).collect() };
+
+        let mut table = comfy_table::Table::new();
+        table.load_preset(match (self.borders, self.box_drawing) {
+            (false, _) => comfy_table::presets::NOTHING,
+            (true, false) => ASCII_GRID,
+            (true, true) => BOX_GRID,
+        });
+        if self.header {
+            table.set_header(cut(&self.columns.iter().map(|column| column.name.clone()).collect::<Vec<_>>()));
+        }
+        for row in &self.rows {
+            table.add_row(cut(row));
+        }
+        // Without borders, columns are two spaces apart and nothing pads their left.
+        let padding = if self.borders { (1, 1) } else { (0, 2) };
+        for (column, settings) in table.column_iter_mut().zip(&self.columns) {
+            column.set_padding(padding);
+            column.set_cell_alignment(match settings.align {
+                Align::Left => comfy_table::CellAlignment::Left,
+                Align::Right => comfy_table::CellAlignment::Right,
+            });
+            if let Width::Fixed(width) = settings.width {
+                let width = (width + padding.0 as usize + padding.1 as usize) as u16;
+                column.set_constraint(comfy_table::ColumnConstraint::Absolute(comfy_table::Width::Fixed(width)));
+            }
+        }
+
+        let indent = " ".repeat(self.indent);
+        table.lines().map(|line| format!("{}{}", indent, if self.borders { &line } else { line.trim_end() })).collect()
+    }
+}
+
+/// `comfy_table` styles: the outer border and a rule under the header, without rules between
+/// rows.
+const ASCII_GRID: &str = "||--+-++|    ++++++";
+const BOX_GRID: &str = "││──├─┼┤│    ┬┴┌┐└┘";
+
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.chars().count() <= width {
+        return cell.to_string();
+    }
+    let mut cut: String = cell.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        cut.push('…');
+    }
+    cut
+}
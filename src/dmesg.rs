@@ -0,0 +1,160 @@
+//! The GPU drivers' kernel errors and warnings, shown in the text monitor with `d`: resets, Xid
+//! errors and PCIe faults are often why a GPU misbehaves, and they only appear in the kernel
+//! log.
+
+use std::time::{Duration, Instant};
+
+use crate::regex::Regex;
+use crate::runner::CommandRunner;
+
+/// `--decode` prefixes each line with its facility and level, which colors it; the ISO time is
+/// kept rather than dropped with `--notime`, to line messages up with the samples.
+pub const DMESG_ARGS: [&str; 7] = ["--kernel", "--level", "err,warn", "--decode", "--time-format", "iso", "--nopager"];
+
+/// How often the kernel log is read while shown.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Messages shown when the log is opened.
+pub const BACKLOG_LINES: usize = 10;
+
+/// The drivers whose messages are shown. NVIDIA's kernel module logs as `NVRM`.
+const GPU_DRIVERS: &str = "nvidia|NVRM|amdgpu|i915|drm";
+
+/// Width when `COLUMNS` is not set.
+const DEFAULT_WIDTH: usize = 120;
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// `err` and the more severe `crit`, `alert` and `emerg`.
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelMessage {
+    pub level: Level,
+    /// To the second, e.g. `2026-10-16T14:03:12`.
+    pub time: Option<String>,
+    pub text: String,
+}
+
+/// Parses `dmesg --decode --time-format iso` output, keeping the messages of GPU drivers:
+/// `kern  :err   : 2026-10-16T14:03:12,512345+00:00 NVRM: Xid (PCI:0000:3b:00): 79, ...`.
+pub fn parse_dmesg(output: &str) -> Vec<KernelMessage> {
+    let drivers = Regex::new(GPU_DRIVERS).expect("the driver pattern is valid");
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let (_facility, level, rest) = (fields.next()?, fields.next()?, fields.next()?.trim());
+            let level = match level.trim() {
+                "emerg" | "alert" | "crit" | "err" => Level::Error,
+                "warn" => Level::Warning,
+                _ => return None,
+            };
+
+            let (time, text) = match rest.split_once(' ') {
+                Some((time, text)) if time.len() >= 19 && time.as_bytes()[10] == b'T' && time.starts_with(|c: char| c.is_ascii_digit()) => {
+                    (Some(time[..19].to_string()), text.trim())
+                }
+                _ => (None, rest),
+            };
+
+            drivers.captures_at(text, 0).is_some().then(|| KernelMessage { level, time, text: text.to_string() })
+        })
+        .collect()
+}
+
+pub fn query(runner: &dyn CommandRunner) -> Result<Vec<KernelMessage>, String> {
+    let output = runner.run("dmesg", &DMESG_ARGS).map_err(|err| format!("cannot run dmesg: {}", err))?;
+    if !output.success {
+        return Err(output.stderr.trim().to_string());
+    }
+    Ok(parse_dmesg(&output.stdout))
+}
+
+/// The terminal width from `COLUMNS`.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).filter(|width| *width > 0).unwrap_or(DEFAULT_WIDTH)
+}
+
+/// One message as a line of at most `width` characters, cut with `…`, red for errors and
+/// yellow for warnings when `color` is set.
+pub fn format_message(message: &KernelMessage, width: usize, color: bool) -> String {
+    let line = match &message.time {
+        Some(time) => format!("[kernel] {} {}", time, message.text),
+        None => format!("[kernel] {}", message.text),
+    };
+    let line = if line.chars().count() > width {
+        let mut cut: String = line.chars().take(width.saturating_sub(1)).collect();
+        cut.push('…');
+        cut
+    } else {
+        line
+    };
+
+    match (color, message.level) {
+        (false, _) => line,
+        (true, Level::Error) => format!("{}{}{}", RED, line, RESET),
+        (true, Level::Warning) => format!("{}{}{}", YELLOW, line, RESET),
+    }
+}
+
+/// What of the kernel log has been shown since it was opened.
+#[derive(Debug, Default)]
+pub struct KernelLog {
+    open: bool,
+    last_poll: Option<Instant>,
+    last_seen: Option<KernelMessage>,
+    failed: bool,
+}
+
+impl KernelLog {
+    pub fn new() -> Self {
+        KernelLog::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Forgets what was shown, so that opening it again shows the backlog.
+    pub fn close(&mut self) {
+        *self = KernelLog::default();
+    }
+
+    /// Whether the log is due to be read at `now`: at once when opened, then every
+    /// [`POLL_INTERVAL`]. Marks it open.
+    pub fn due(&mut self, now: Instant) -> bool {
+        self.open = true;
+        let due = !self.failed && self.last_poll.is_none_or(|last| now.saturating_duration_since(last) >= POLL_INTERVAL);
+        if due {
+            self.last_poll = Some(now);
+        }
+        due
+    }
+
+    /// Records that dmesg failed, so it is not run again until the log is reopened.
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// The messages to show from a fresh read: the last [`BACKLOG_LINES`] when just opened,
+    /// then those after the last one shown. When that one has rotated out of the kernel's
+    /// buffer, everything read is new.
+    pub fn update(&mut self, messages: Vec<KernelMessage>) -> Vec<KernelMessage> {
+        let start = match &self.last_seen {
+            None => messages.len().saturating_sub(BACKLOG_LINES),
+            Some(last) => messages.iter().rposition(|message| message == last).map_or(0, |position| position + 1),
+        };
+        if let Some(last) = messages.last() {
+            self.last_seen = Some(last.clone());
+        }
+        messages.into_iter().skip(start).collect()
+    }
+}
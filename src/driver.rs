@@ -0,0 +1,91 @@
+//! The NVIDIA driver version, and the metrics that need a recent one. `nvidia-smi` fails a
+//! whole query over a single field it does not know, so metrics an older driver lacks are
+//! skipped up front rather than breaking every poll.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::nvidia_driver_version;
+use crate::runner::CommandRunner;
+
+/// An NVIDIA driver version such as `535.129.03`, ordered like semver: major, then minor, then
+/// patch. The minor and patch default to 0 where the version string stops short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DriverVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl DriverVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        DriverVersion { major, minor, patch }
+    }
+}
+
+impl FromStr for DriverVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid driver version: {}", s);
+        let mut parts = s.trim().split('.');
+        let mut part = |required: bool| match parts.next() {
+            Some(part) => part.parse::<u32>().map_err(|_| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        };
+        let version = DriverVersion { major: part(true)?, minor: part(false)?, patch: part(false)? };
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+/// NVIDIA's notation: the minor and patch take at least two digits, as in `470.57.02`.
+impl fmt::Display for DriverVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}.{:02}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Metrics that only some driver versions report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// `nvidia-smi nvlink -gt d`, for `--fields nvlink`.
+    NvLinkThroughput,
+    /// The `temperature.memory` query field, for `--fields temps`.
+    MemoryTemperature,
+}
+
+impl Metric {
+    /// The oldest driver whose `nvidia-smi` reports the metric.
+    pub fn min_version(self) -> DriverVersion {
+        match self {
+            Metric::NvLinkThroughput => DriverVersion::new(465, 0, 0),
+            Metric::MemoryTemperature => DriverVersion::new(460, 0, 0),
+        }
+    }
+}
+
+/// Whether `metric` needs a newer driver than `version`.
+pub fn requires_driver_version(metric: Metric, version: DriverVersion) -> bool {
+    version < metric.min_version()
+}
+
+/// Parses `nvidia-smi --version`, which has a `DRIVER version : 535.129.03` line.
+pub fn parse_nvidia_smi_version(output: &str) -> Option<DriverVersion> {
+    output.lines().find_map(|line| line.split_once(':').filter(|(key, _)| key.trim().eq_ignore_ascii_case("driver version"))?.1.parse().ok())
+}
+
+/// The installed driver's version, from `nvidia-smi --version`. Where an older nvidia-smi does
+/// not know `--version`, the version of the loaded kernel module stands in.
+pub fn query_nvidia_version(runner: &dyn CommandRunner) -> Option<DriverVersion> {
+    runner
+        .run("nvidia-smi", &["--version"])
+        .ok()
+        .filter(|output| output.success)
+        .and_then(|output| parse_nvidia_smi_version(&output.stdout))
+        .or_else(|| nvidia_driver_version()?.parse().ok())
+}
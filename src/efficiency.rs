@@ -0,0 +1,149 @@
+//! `--show-efficiency`: the Tensor Core throughput a GPU delivers per watt, estimated as its
+//! datasheet peak scaled by utilization, divided by the power draw. The peaks come from
+//! [`GPU_MODELS`], a table of known GPUs; other GPUs show `N/A`.
+//!
+//! Utilization is the share of time a kernel ran, not the share of the peak it reached, so
+//! the figure is an upper bound: it tells a card idling at full clocks from one doing work,
+//! not a well-tuned kernel from a memory-bound one.
+
+use std::str::FromStr;
+
+use crate::config::{self, ConfigValue, Table};
+use crate::GpuSnapshot;
+
+/// The peak throughput of known GPUs, see the comments in the file.
+pub const GPU_MODELS: &str = include_str!("gpu_models.toml");
+
+/// `--efficiency-precision`: which peak the efficiency is computed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    #[default]
+    Fp16,
+    Bf16,
+    Int8,
+}
+
+impl Precision {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Precision::Fp16 => "fp16",
+            Precision::Bf16 => "bf16",
+            Precision::Int8 => "int8",
+        }
+    }
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fp16" => Ok(Precision::Fp16),
+            "bf16" => Ok(Precision::Bf16),
+            "int8" => Ok(Precision::Int8),
+            _ => Err(format!("Invalid --efficiency-precision value: {} (expected fp16, bf16 or int8)", value)),
+        }
+    }
+}
+
+/// One entry of [`GPU_MODELS`]; a precision without Tensor Core support is `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuModel {
+    pub pattern: String,
+    pub fp16_tflops: Option<f64>,
+    pub bf16_tflops: Option<f64>,
+    pub int8_tops: Option<f64>,
+}
+
+impl GpuModel {
+    pub fn peak(&self, precision: Precision) -> Option<f64> {
+        match precision {
+            Precision::Fp16 => self.fp16_tflops,
+            Precision::Bf16 => self.bf16_tflops,
+            Precision::Int8 => self.int8_tops,
+        }
+    }
+}
+
+/// The efficiency of one sample, set by the monitor loop with `--show-efficiency`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Efficiency {
+    TflopsPerWatt(f64),
+    /// The GPU is not in the table, has no peak at the precision, or reports no power draw.
+    Unavailable,
+}
+
+impl Efficiency {
+    pub fn value(self) -> Option<f64> {
+        match self {
+            Efficiency::TflopsPerWatt(value) => Some(value),
+            Efficiency::Unavailable => None,
+        }
+    }
+}
+
+fn number(table: &Table, key: &str) -> Result<Option<f64>, String> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(ConfigValue::Integer(value)) => Ok(Some(*value as f64)),
+        Some(ConfigValue::Float(value)) => Ok(Some(*value)),
+        Some(_) => Err(format!("{} must be a number", key)),
+    }
+}
+
+/// Parses a table in the format of [`GPU_MODELS`].
+pub fn parse_models(input: &str) -> Result<Vec<GpuModel>, String> {
+    let document = config::parse(input)?;
+
+    document
+        .table_arrays
+        .get("gpu")
+        .into_iter()
+        .flatten()
+        .map(|table| {
+            let pattern = table.get_str("match").ok_or("A [[gpu]] entry has no match")?;
+            let error = |message: String| format!("GPU model {}: {}", pattern, message);
+            Ok(GpuModel {
+                pattern: pattern.to_string(),
+                fp16_tflops: number(table, "fp16").map_err(error)?,
+                bf16_tflops: number(table, "bf16").map_err(error)?,
+                int8_tops: number(table, "int8").map_err(error)?,
+            })
+        })
+        .collect()
+}
+
+/// The embedded table, parsed.
+pub fn known_models() -> Vec<GpuModel> {
+    parse_models(GPU_MODELS).expect("the embedded GPU model table is valid")
+}
+
+/// Whether `pattern` occurs in `name` as whole words, ignoring case: "A10" is found in
+/// "NVIDIA A10" but not in "NVIDIA A100" or "RTX A1000".
+fn contains_words(name: &str, pattern: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+
+    name.match_indices(&pattern).any(|(start, _)| !is_word(name[..start].chars().next_back()) && !is_word(name[start + pattern.len()..].chars().next()))
+}
+
+/// The model of a GPU named `name`: of the entries that match, the one with the longest
+/// pattern.
+pub fn find_model<'m>(models: &'m [GpuModel], name: &str) -> Option<&'m GpuModel> {
+    models.iter().filter(|model| contains_words(name, &model.pattern)).max_by_key(|model| model.pattern.len())
+}
+
+/// The peak at `precision` scaled by `utilization` (percent), per watt drawn.
+pub fn compute(peak: f64, utilization: f64, power_w: f32) -> Option<f64> {
+    (power_w > 0.0).then(|| peak * utilization / 100.0 / f64::from(power_w))
+}
+
+/// The efficiency of `snapshot` against the peaks in `models`.
+pub fn efficiency(models: &[GpuModel], snapshot: &GpuSnapshot, precision: Precision) -> Efficiency {
+    let peak = find_model(models, &snapshot.gpu.name).and_then(|model| model.peak(precision));
+    match (peak, snapshot.power_w) {
+        (Some(peak), Some(power)) => compute(peak, snapshot.utilization, power).map_or(Efficiency::Unavailable, Efficiency::TflopsPerWatt),
+        _ => Efficiency::Unavailable,
+    }
+}
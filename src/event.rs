@@ -0,0 +1,292 @@
+//! Discrete events, as opposed to the samples measured every tick: a GPU disappearing, starting or
+//! stopping to throttle, its metrics source changing, an alert firing and resolving, or a
+//! `--script` raising one of its own. They all share one record type so that every sink reports them the same way:
+//! a `"type":"event"` record in JSON output, a timestamped `[KIND severity]` line in text mode,
+//! and a syslog message.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
+
+use crate::alert::{Alert, AlertEvent, Severity};
+use crate::idle::format_duration;
+use crate::json::Value;
+use crate::metadata::Labels;
+use crate::output::{json_string, OutputContext};
+use crate::schema::SCHEMA_VERSION;
+use crate::syslog::format_timestamp;
+use crate::{GpuInfo, GpuSnapshot};
+
+/// What happened, with what is specific to it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    /// The driver was unbound or the device removed.
+    Detached,
+    /// The GPU's `throttle_reasons` started to hold, or changed while it throttled.
+    ThrottleStarted { reasons: Vec<String> },
+    ThrottleStopped,
+    /// The metrics source stopped working mid-run and the next one took over.
+    SourceChanged { from: String, to: String },
+    AlertFiring { metric: String, threshold: f32, value: f32 },
+    /// The alert's condition stopped holding: the peak and duration of the whole episode.
+    AlertResolved { metric: String, threshold: f32, started: String, peak: f32, duration_s: f64 },
+    /// `alert(message)` in the `--script`, which words the message itself.
+    ScriptAlert,
+}
+
+impl EventKind {
+    /// The `event` value of the JSON records; both alert kinds are `alert`, told apart by
+    /// their `state`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Detached => "detached",
+            EventKind::ThrottleStarted { .. } => "throttle_start",
+            EventKind::ThrottleStopped => "throttle_stop",
+            EventKind::SourceChanged { .. } => "source_change",
+            EventKind::AlertFiring { .. } | EventKind::AlertResolved { .. } => "alert",
+            EventKind::ScriptAlert => "script_alert",
+        }
+    }
+
+    /// The tag of the text line.
+    fn label(&self) -> &'static str {
+        match self {
+            EventKind::Detached => "DETACHED",
+            EventKind::ThrottleStarted { .. } => "THROTTLE",
+            EventKind::ThrottleStopped => "THROTTLE END",
+            EventKind::SourceChanged { .. } => "SOURCE",
+            EventKind::AlertFiring { .. } => "ALERT",
+            EventKind::AlertResolved { .. } => "RESOLVED",
+            EventKind::ScriptAlert => "SCRIPT",
+        }
+    }
+
+    fn describe(&self, gpu: u32, name: &str) -> String {
+        let gpu = format!("GPU {} ({})", gpu, name);
+        match self {
+            EventKind::Detached => format!("{} was lost (driver unloaded or device removed)", gpu),
+            EventKind::ThrottleStarted { reasons } => format!("{} is throttling: {}", gpu, reasons.join(", ")),
+            EventKind::ThrottleStopped => format!("{} stopped throttling", gpu),
+            EventKind::SourceChanged { from, to } => format!("{} is now sampled from {} ({} stopped working)", gpu, to, from),
+            EventKind::AlertFiring { metric, threshold, value } => format!("{} {} {} exceeds {}", gpu, metric, value, threshold),
+            EventKind::AlertResolved { metric, threshold, peak, duration_s, .. } => format!(
+                "{} {} back below {} after {}, peak {}",
+                gpu,
+                metric,
+                threshold,
+                format_duration(std::time::Duration::from_secs_f64(*duration_s)),
+                peak
+            ),
+            EventKind::ScriptAlert => format!("{} alert from the script", gpu),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub gpu: u32,
+    pub name: String,
+    pub severity: Severity,
+    pub kind: EventKind,
+    pub message: String,
+    /// When it happened, as RFC 3339 UTC with milliseconds.
+    pub time: String,
+}
+
+impl Event {
+    pub fn new(gpu: &GpuInfo, severity: Severity, kind: EventKind) -> Self {
+        Event {
+            gpu: gpu.index,
+            name: gpu.name.clone(),
+            severity,
+            message: kind.describe(gpu.index, &gpu.name),
+            kind,
+            time: format_timestamp(SystemTime::now()),
+        }
+    }
+
+    /// An alert that started to hold, with its own message.
+    pub fn alert_firing(alert: &Alert) -> Self {
+        let kind = EventKind::AlertFiring { metric: alert.kind.metric().to_string(), threshold: alert.threshold, value: alert.value };
+        Event { message: alert.message(), ..Event::new(&alert.gpu, alert.severity, kind) }
+    }
+
+    pub fn alert_resolved(alert: &Alert, episode: &AlertEvent) -> Self {
+        let kind = EventKind::AlertResolved {
+            metric: alert.kind.metric().to_string(),
+            threshold: alert.threshold,
+            started: format_timestamp(episode.started_at),
+            peak: episode.peak,
+            duration_s: episode.duration.as_secs_f64(),
+        };
+        Event::new(&alert.gpu, alert.severity, kind)
+    }
+
+    /// An `alert(message)` of the `--script` about `gpu`, as a warning.
+    pub fn script_alert(gpu: &GpuInfo, message: &str) -> Self {
+        Event { message: message.to_string(), ..Event::new(gpu, Severity::Warning, EventKind::ScriptAlert) }
+    }
+
+    /// The text line: `2026-10-16T14:03:12.512Z [DETACHED warning] GPU 1 (...) was lost ...`.
+    pub fn format_text(&self) -> String {
+        format!("{} [{} {}] {}", self.time, self.kind.label(), self.severity, self.message)
+    }
+
+    /// Structured data parameters for syslog: the GPU and the labels.
+    pub fn syslog_params(&self, labels: &Labels) -> Vec<(String, String)> {
+        let mut params = vec![("gpu".to_string(), self.gpu.to_string()), ("name".to_string(), self.name.clone())];
+        params.extend(labels.sorted().into_iter().map(|(key, value)| (key.to_string(), value.to_string())));
+        params
+    }
+
+    /// The `"type":"event"` record in JSON output. A firing alert carries its current `value`;
+    /// once resolved, the record carries the `peak` and `duration_s` of the whole episode instead.
+    pub fn to_json(&self, context: &OutputContext) -> String {
+        let mut fields = vec![format!("\"schema_version\":{}", SCHEMA_VERSION)];
+
+        if let Some(hostname) = &context.hostname {
+            fields.push(format!("\"hostname\":{}", json_string(hostname)));
+        }
+        fields.push("\"type\":\"event\"".to_string());
+        fields.push(format!("\"event\":\"{}\"", self.kind.name()));
+        match &self.kind {
+            EventKind::AlertFiring { .. } => fields.push("\"state\":\"firing\"".to_string()),
+            EventKind::AlertResolved { .. } => fields.push("\"state\":\"resolved\"".to_string()),
+            _ => {}
+        }
+        fields.push(format!("\"severity\":\"{}\"", self.severity));
+        fields.push(format!("\"gpu\":{}", self.gpu));
+        fields.push(format!("\"name\":{}", json_string(&self.name)));
+        match &self.kind {
+            EventKind::ThrottleStarted { reasons } => {
+                fields.push(format!("\"reasons\":[{}]", reasons.iter().map(|reason| json_string(reason)).collect::<Vec<_>>().join(",")));
+            }
+            EventKind::SourceChanged { from, to } => {
+                fields.push(format!("\"from\":{}", json_string(from)));
+                fields.push(format!("\"to\":{}", json_string(to)));
+            }
+            EventKind::AlertFiring { metric, threshold, value } => {
+                fields.push(format!("\"metric\":{}", json_string(metric)));
+                fields.push(format!("\"threshold\":{}", threshold));
+                fields.push(format!("\"value\":{}", value));
+            }
+            EventKind::AlertResolved { metric, threshold, started, peak, duration_s } => {
+                fields.push(format!("\"metric\":{}", json_string(metric)));
+                fields.push(format!("\"threshold\":{}", threshold));
+                fields.push(format!("\"started\":{}", json_string(started)));
+                fields.push(format!("\"peak\":{}", peak));
+                fields.push(format!("\"duration_s\":{}", duration_s));
+            }
+            EventKind::Detached | EventKind::ThrottleStopped | EventKind::ScriptAlert => {}
+        }
+        fields.push(format!("\"message\":{}", json_string(&self.message)));
+        fields.push(format!("\"time\":{}", json_string(&self.time)));
+        if !context.labels.is_empty() {
+            fields.push(format!("\"labels\":{}", context.labels.to_json()));
+        }
+        if let Some(tick_seq) = context.tick_seq {
+            fields.push(format!("\"tick_seq\":{}", tick_seq));
+        }
+        if let Some(timestamp) = &context.timestamp {
+            fields.push(format!("\"ts\":{}", timestamp.to_json()));
+        }
+
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Reads an event back from its JSON record.
+    pub fn from_json(record: &Value) -> Result<Self, String> {
+        let Value::Object(members) = record else { return Err("Event record is not an object".to_string()) };
+        let member = |key: &str| members.iter().find(|(name, _)| name == key).map(|(_, value)| value);
+        let string = |key: &str| match member(key) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            _ => Err(format!("Event record has no \"{}\"", key)),
+        };
+        let number = |key: &str| match member(key) {
+            Some(Value::Number(value)) => Ok(*value),
+            _ => Err(format!("Event record has no \"{}\"", key)),
+        };
+
+        if string("type")? != "event" {
+            return Err("Record is not an event".to_string());
+        }
+        let kind = match (string("event")?.as_str(), string("state").ok().as_deref()) {
+            ("detached", _) => EventKind::Detached,
+            ("throttle_start", _) => {
+                let reasons = match member("reasons") {
+                    Some(Value::Array(reasons)) => reasons.iter().filter_map(|reason| if let Value::String(reason) = reason { Some(reason.clone()) } else { None }).collect(),
+                    _ => Vec::new(),
+                };
+                EventKind::ThrottleStarted { reasons }
+            }
+            ("throttle_stop", _) => EventKind::ThrottleStopped,
+            ("source_change", _) => EventKind::SourceChanged { from: string("from")?, to: string("to")? },
+            ("alert", Some("firing")) => EventKind::AlertFiring { metric: string("metric")?, threshold: number("threshold")? as f32, value: number("value")? as f32 },
+            ("alert", Some("resolved")) => EventKind::AlertResolved {
+                metric: string("metric")?,
+                threshold: number("threshold")? as f32,
+                started: string("started")?,
+                peak: number("peak")? as f32,
+                duration_s: number("duration_s")?,
+            },
+            ("script_alert", _) => EventKind::ScriptAlert,
+            (event, _) => return Err(format!("Unknown event: {}", event)),
+        };
+
+        Ok(Event {
+            gpu: number("gpu")? as u32,
+            name: string("name")?,
+            severity: string("severity")?.parse()?,
+            kind,
+            message: string("message")?,
+            time: string("time")?,
+        })
+    }
+}
+
+/// Turns the `throttle_reasons` of each GPU's samples into throttle events.
+#[derive(Debug, Default)]
+pub struct ThrottleTracker {
+    reasons: HashMap<u32, Vec<String>>,
+}
+
+impl ThrottleTracker {
+    /// A `throttle_start` warning when the sample's reasons differ from the last ones and are not
+    /// empty, a `throttle_stop` when they became empty. Samples without reasons, from sources
+    /// that do not report them, change nothing.
+    pub fn update(&mut self, snapshot: &GpuSnapshot) -> Option<Event> {
+        let reasons = snapshot.throttle_reasons.as_ref()?;
+        let previous = self.reasons.insert(snapshot.gpu.index, reasons.clone()).unwrap_or_default();
+        if *reasons == previous {
+            None
+        } else if reasons.is_empty() {
+            Some(Event::new(&snapshot.gpu, Severity::Info, EventKind::ThrottleStopped))
+        } else {
+            Some(Event::new(&snapshot.gpu, Severity::Warning, EventKind::ThrottleStarted { reasons: reasons.clone() }))
+        }
+    }
+}
+
+/// Events by their `event` name, for the exit summary.
+#[derive(Debug, Default)]
+pub struct EventCounts {
+    counts: BTreeMap<&'static str, u64>,
+}
+
+impl EventCounts {
+    pub fn record(&mut self, event: &Event) {
+        *self.counts.entry(event.kind.name()).or_insert(0) += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// `Events: 3 (2 alert, 1 detached)`, or nothing without events.
+    pub fn format_summary(&self) -> Vec<String> {
+        if self.counts.is_empty() {
+            return Vec::new();
+        }
+        let counts: Vec<String> = self.counts.iter().map(|(name, count)| format!("{} {}", count, name)).collect();
+        vec![format!("Events: {} ({})", self.total(), counts.join(", "))]
+    }
+}
@@ -0,0 +1,132 @@
+//! Regression checks of collected metrics against a golden file (`--golden-file`).
+
+use std::collections::HashMap;
+use std::fs;
+
+use crate::json::{self, Value};
+use crate::{GpuInfo, GpuSnapshot};
+
+/// Default relative tolerance for `--golden-file` comparisons.
+pub const DEFAULT_TOLERANCE: f32 = 0.05;
+
+/// A numeric field whose actual value differs from the expected one by more than the
+/// tolerance, or is missing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub expected: f64,
+    pub actual: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDiff {
+    pub gpu: u32,
+    pub fields: Vec<FieldDiff>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+fn numeric_fields(snapshot: &GpuSnapshot) -> [(&'static str, Option<f64>); 5] {
+    [
+        ("utilization", Some(snapshot.utilization)),
+        ("memory_used_mib", snapshot.memory_used_mib.map(|value| value as f64)),
+        ("memory_total_mib", snapshot.memory_total_mib.map(|value| value as f64)),
+        ("temperature_c", snapshot.temperature_c.map(f64::from)),
+        ("power_w", snapshot.power_w.map(f64::from)),
+    ]
+}
+
+fn within(expected: f64, actual: f64, tolerance: f32) -> bool {
+    (actual - expected).abs() <= tolerance as f64 * expected.abs()
+}
+
+/// Compares every numeric field the expected snapshot has against `actual`, allowing a
+/// relative difference of `tolerance` (0.05 = 5%). Fields absent from `expected` are not
+/// checked.
+pub fn compare_snapshots(expected: &GpuSnapshot, actual: &GpuSnapshot, tolerance: f32) -> SnapshotDiff {
+    let fields = numeric_fields(expected)
+        .into_iter()
+        .zip(numeric_fields(actual))
+        .filter_map(|((field, expected), (_, actual))| {
+            let expected = expected?;
+            match actual {
+                Some(actual) if within(expected, actual, tolerance) => None,
+                actual => Some(FieldDiff { field, expected, actual }),
+            }
+        })
+        .collect();
+
+    SnapshotDiff { gpu: actual.gpu.index, fields }
+}
+
+pub fn format_diff(diff: &SnapshotDiff) -> Vec<String> {
+    diff.fields
+        .iter()
+        .map(|field| match field.actual {
+            Some(actual) => format!("GPU {} {}: expected {}, got {}", diff.gpu, field.field, field.expected, actual),
+            None => format!("GPU {} {}: expected {}, got nothing", diff.gpu, field.field, field.expected),
+        })
+        .collect()
+}
+
+fn member<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+fn number(value: &Value, key: &str) -> Option<f64> {
+    match member(value, key)? {
+        Value::Number(number) => Some(*number),
+        _ => None,
+    }
+}
+
+/// Reads a snapshot from a JSON record as written by `--format json`.
+pub fn snapshot_from_json(record: &Value) -> Result<GpuSnapshot, String> {
+    let index = number(record, "gpu").ok_or("Record has no \"gpu\" index")?;
+    let name = match member(record, "name") {
+        Some(Value::String(name)) => name.clone(),
+        _ => String::new(),
+    };
+    let utilization = number(record, "utilization").ok_or("Record has no \"utilization\"")?;
+
+    Ok(GpuSnapshot {
+        utilization_max: number(record, "utilization_max"),
+        memory_used_mib: number(record, "memory_used_mib").map(|value| value as u64),
+        memory_total_mib: number(record, "memory_total_mib").map(|value| value as u64),
+        temperature_c: number(record, "temperature_c").map(|value| value as f32),
+        power_w: number(record, "power_w").map(|value| value as f32),
+        ..GpuSnapshot::new(GpuInfo { index: index as u32, name, bus_id: None, render_offload: None }, utilization)
+    })
+}
+
+/// Parses golden records: NDJSON (`--format ndjson`) or one JSON document, either a record or
+/// an array of records (`--format json --count 1`). Later records for a GPU replace earlier ones.
+pub fn parse_golden(content: &str) -> Result<HashMap<u32, GpuSnapshot>, String> {
+    let records = match json::parse(content) {
+        Ok(Value::Array(records)) => records,
+        Ok(record) => vec![record],
+        Err(_) => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| json::parse(line).map_err(|err| format!("line {}: {}", number + 1, err)))
+            .collect::<Result<_, _>>()?,
+    };
+
+    records
+        .iter()
+        .map(|record| snapshot_from_json(record).map(|snapshot| (snapshot.gpu.index, snapshot)))
+        .collect()
+}
+
+pub fn load_golden(path: &str) -> Result<HashMap<u32, GpuSnapshot>, String> {
+    let content = fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path, err))?;
+    parse_golden(&content).map_err(|err| format!("Invalid golden file {}: {}", path, err))
+}
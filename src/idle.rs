@@ -0,0 +1,56 @@
+//! `--idle-threshold`: how long each GPU has been idle, i.e. since its utilization was last
+//! above the threshold. Meant for job monitoring: a GPU idle for minutes is a stalled or
+//! finished job.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::GpuSnapshot;
+
+/// Whether a GPU is above the idle threshold, or for how long it has not been.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Active,
+    Idle(Duration),
+}
+
+impl Activity {
+    /// `idle_seconds` in the machine-readable formats; 0 while active.
+    pub fn idle_seconds(self) -> u64 {
+        match self {
+            Activity::Active => 0,
+            Activity::Idle(idle) => idle.as_secs(),
+        }
+    }
+}
+
+/// `HH:MM:SS`; the hours grow past two digits after four days.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[derive(Debug, Clone)]
+pub struct IdleTracker {
+    threshold: f32,
+    started: Instant,
+    /// When each GPU was last above the threshold; `None` until it first is, in which case it
+    /// counts as idle since monitoring started.
+    last_active: HashMap<u32, Option<Instant>>,
+}
+
+impl IdleTracker {
+    pub fn new(threshold: f32, started: Instant) -> Self {
+        IdleTracker { threshold, started, last_active: HashMap::new() }
+    }
+
+    pub fn update(&mut self, snapshot: &GpuSnapshot, now: Instant) -> Activity {
+        let last_active = self.last_active.entry(snapshot.gpu.index).or_insert(None);
+
+        if snapshot.utilization > f64::from(self.threshold) {
+            *last_active = Some(now);
+            return Activity::Active;
+        }
+        Activity::Idle(now.saturating_duration_since(last_active.unwrap_or(self.started)))
+    }
+}
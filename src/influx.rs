@@ -0,0 +1,218 @@
+//! `--export-influx <url>` pushes samples to an InfluxDB 2.x server through its
+//! `/api/v2/write` HTTP API, as the lines `--format influx` prints.
+//!
+//! Lines are queued and a background thread writes them in one request every
+//! `--influx-flush-interval`, so a slow server never stalls sampling. A rate-limited (429) or
+//! unavailable (503) server keeps the batch queued and is retried after its `Retry-After`, or
+//! after 1, 2, 4, ... seconds without one; a write the server rejects for good, such as a bad
+//! token, is reported and dropped.
+
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use reqwest::blocking::Client;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::Url;
+
+use crate::live;
+use crate::tcp::{Backoff, Outbox};
+
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Lines kept while the server is unreachable or rate limiting, the oldest dropped first.
+pub const BUFFER_SIZE: usize = 100_000;
+
+/// The environment variable the token is read from without `--influx-token`, as the `influx`
+/// CLI does.
+pub const TOKEN_VARIABLE: &str = "INFLUX_TOKEN";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and as whom to write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfluxTarget {
+    /// The server's URL, with the path prefix it may sit under behind a proxy.
+    pub url: Url,
+    pub org: String,
+    pub bucket: String,
+    pub token: Option<String>,
+}
+
+/// `--export-influx`: an `http://` or `https://` URL, `host[:port][/prefix]`.
+pub fn parse_url(value: &str) -> Result<Url, String> {
+    let invalid = || format!("Invalid --export-influx URL: {} (expected http://host:port or https://host:port)", value);
+    let url = Url::parse(value).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() || url.query().is_some() {
+        return Err(invalid());
+    }
+    Ok(url)
+}
+
+impl InfluxTarget {
+    /// The `/api/v2/write` URL for nanosecond timestamps.
+    pub fn write_url(&self) -> Url {
+        let mut url = self.url.clone();
+        let path = format!("{}/api/v2/write", url.path().trim_end_matches('/'));
+        url.set_path(&path);
+        url.query_pairs_mut().append_pair("org", &self.org).append_pair("bucket", &self.bucket).append_pair("precision", "ns");
+        url
+    }
+
+    /// `host:port`, to check at startup that the host resolves.
+    fn address(&self) -> String {
+        format!("{}:{}", self.url.host_str().unwrap_or_default(), self.url.port_or_known_default().unwrap_or(80))
+    }
+}
+
+/// What the server answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub retry_after: Option<Duration>,
+    /// The first line of the body, InfluxDB's error message.
+    pub message: String,
+}
+
+impl Response {
+    /// Whether the batch should be kept and sent again: rate limiting and a server that is
+    /// temporarily unavailable.
+    pub fn retryable(&self) -> bool {
+        matches!(self.status, 429 | 502 | 503 | 504)
+    }
+}
+
+fn post(client: &Client, target: &InfluxTarget, body: String) -> reqwest::Result<Response> {
+    let mut request = client.post(target.write_url()).header(CONTENT_TYPE, "text/plain; charset=utf-8").body(body);
+    if let Some(token) = &target.token {
+        request = request.header(AUTHORIZATION, format!("Token {}", token));
+    }
+    let response = request.send()?;
+    let status = response.status().as_u16();
+    let retry_after = response.headers().get(RETRY_AFTER).and_then(|value| value.to_str().ok()?.trim().parse().ok()).map(Duration::from_secs);
+    let message = response.text().unwrap_or_default().lines().next().unwrap_or("").trim().to_string();
+    Ok(Response { status, retry_after, message })
+}
+
+#[derive(Debug)]
+struct State {
+    outbox: Outbox,
+    closing: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hands lines to the background thread that writes them. Dropping it makes one last attempt
+/// to write what is queued, and stops the thread.
+#[derive(Debug)]
+pub struct InfluxWriter {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InfluxWriter {
+    pub fn new(target: InfluxTarget, flush_interval: Duration) -> io::Result<Self> {
+        // Resolving up front reports a misspelt host at startup; later writes resolve again.
+        target.address().to_socket_addrs()?;
+        let client = Client::builder().connect_timeout(CONNECT_TIMEOUT).timeout(REQUEST_TIMEOUT).build().map_err(io::Error::other)?;
+
+        let shared = Arc::new(Shared { state: Mutex::new(State { outbox: Outbox::new(BUFFER_SIZE), closing: false }), wake: Condvar::new() });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || run(&client, &target, &shared, flush_interval))
+        };
+
+        Ok(InfluxWriter { shared, thread: Some(thread) })
+    }
+
+    /// Queues the lines of a sample, written with the next batch.
+    pub fn send(&self, lines: &str) {
+        let mut state = self.shared.lock();
+        for line in lines.lines() {
+            state.outbox.push(line.to_string());
+        }
+    }
+}
+
+impl Drop for InfluxWriter {
+    fn drop(&mut self) {
+        self.shared.lock().closing = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Writes the queued lines; returns how long to wait before the next batch, `None` when it was
+/// written or dropped.
+fn flush(client: &Client, target: &InfluxTarget, shared: &Shared, backoff: &mut Backoff) -> Option<Duration> {
+    let batch: Vec<String> = {
+        let mut state = shared.lock();
+        std::iter::from_fn(|| state.outbox.pop()).collect()
+    };
+    if batch.is_empty() {
+        return None;
+    }
+
+    let requeue = |batch: Vec<String>| {
+        let mut state = shared.lock();
+        for line in batch.into_iter().rev() {
+            state.outbox.requeue(line);
+        }
+    };
+    match post(client, target, batch.join("\n") + "\n") {
+        Ok(response) if (200..300).contains(&response.status) => {
+            backoff.reset();
+            None
+        }
+        Ok(response) if response.retryable() => {
+            let delay = response.retry_after.unwrap_or_else(|| backoff.fail());
+            live::eprintln(&format!("Warning: InfluxDB answered {}, retrying {} lines in {}s", response.status, batch.len(), delay.as_secs()));
+            requeue(batch);
+            Some(delay)
+        }
+        Ok(response) => {
+            live::eprintln(&format!("Error: InfluxDB rejected {} lines with {}: {}", batch.len(), response.status, response.message));
+            None
+        }
+        Err(err) => {
+            let delay = backoff.fail();
+            live::eprintln(&format!("Warning: Writing to InfluxDB at {} failed: {}, retrying in {}s", target.url, err, delay.as_secs()));
+            requeue(batch);
+            Some(delay)
+        }
+    }
+}
+
+fn run(client: &Client, target: &InfluxTarget, shared: &Shared, flush_interval: Duration) {
+    let mut backoff = Backoff::default();
+    let mut next_flush = Instant::now() + flush_interval;
+
+    loop {
+        let closing = {
+            let state = shared.lock();
+            let wait = next_flush.saturating_duration_since(Instant::now());
+            let (state, _) = shared.wake.wait_timeout_while(state, wait, |state| !state.closing).unwrap_or_else(|poisoned| poisoned.into_inner());
+            state.closing
+        };
+        let delay = flush(client, target, shared, &mut backoff);
+        if closing {
+            return;
+        }
+        next_flush = Instant::now() + delay.unwrap_or(flush_interval);
+    }
+}
@@ -0,0 +1,43 @@
+//! Randomized polling delays for `--interval-jitter`.
+//!
+//! Mainly useful in large Prometheus deployments: hundreds of hosts started at the same time
+//! otherwise sample and get scraped in lockstep. The random sequence is seeded from the
+//! hostname, so each host drifts differently but reproducibly.
+//!
+//! The generator is `rand`'s `StdRng`, seeded with a hash of the hostname: jitter needs
+//! neither OS entropy nor a fresh sequence per run.
+
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// FNV-1a, used instead of `DefaultHasher` because its output must stay stable across builds.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[derive(Debug, Clone)]
+pub struct Jitter {
+    fraction: f64,
+    rng: StdRng,
+}
+
+impl Jitter {
+    pub fn new(fraction: f64, hostname: &str) -> Self {
+        Jitter { fraction, rng: StdRng::seed_from_u64(fnv1a(hostname.as_bytes())) }
+    }
+
+    /// Returns `interval` shifted by a random offset within ±`fraction` of it.
+    pub fn apply(&mut self, interval: Duration) -> Duration {
+        let offset = self.rng.random_range(-self.fraction..=self.fraction);
+        interval.mul_f64((1.0 + offset).max(0.0))
+    }
+}
+
+pub fn parse_fraction(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("Invalid --interval-jitter value (expected 0.0 to 1.0): {}", value)),
+    }
+}
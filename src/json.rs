@@ -0,0 +1,264 @@
+
+/// Minimal JSON document model used for structured, schema-less data such as snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Object members in insertion order.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(value) => json_string(value),
+            Value::Array(items) => format!("[{}]", items.iter().map(Value::to_json).collect::<Vec<_>>().join(",")),
+            Value::Object(members) => format!(
+                "{{{}}}",
+                members
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", json_string(key), value.to_json()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+
+    /// Renders the value as JSON indented by two spaces per level, for reading rather than
+    /// parsing.
+    pub fn to_json_pretty(&self) -> String {
+        let mut output = String::new();
+        self.write_pretty(&mut output, 0);
+        output
+    }
+
+    fn write_pretty(&self, output: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth + 1);
+
+        match self {
+            Value::Array(items) if !items.is_empty() => {
+                output.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    output.push_str(if i == 0 { "\n" } else { ",\n" });
+                    output.push_str(&indent);
+                    item.write_pretty(output, depth + 1);
+                }
+                output.push('\n');
+                output.push_str(&"  ".repeat(depth));
+                output.push(']');
+            }
+            Value::Object(members) if !members.is_empty() => {
+                output.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    output.push_str(if i == 0 { "\n" } else { ",\n" });
+                    output.push_str(&indent);
+                    output.push_str(&json_string(key));
+                    output.push_str(": ");
+                    value.write_pretty(output, depth + 1);
+                }
+                output.push('\n');
+                output.push_str(&"  ".repeat(depth));
+                output.push('}');
+            }
+            other => output.push_str(&other.to_json()),
+        }
+    }
+
+    /// Renders scalars the way they read in diffs and tables: strings without quotes.
+    pub fn to_display(&self) -> String {
+        match self {
+            Value::String(value) => value.clone(),
+            other => other.to_json(),
+        }
+    }
+}
+
+/// How deeply arrays and objects may nest. Parsing recurses once per level, so this keeps a
+/// hostile document from overflowing the stack; gpuatop's own records nest three levels deep.
+pub const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.input.get(self.position).is_some_and(u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.input[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", literal)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+
+        match self.input.get(self.position) {
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.parse_string().map(Value::String),
+            Some(b'[') => self.parse_nested(Self::parse_array),
+            Some(b'{') => self.parse_nested(Self::parse_object),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn parse_nested(&mut self, parse: fn(&mut Self) -> Result<Value, String>) -> Result<Value, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Nested too deeply"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while self.input.get(self.position).is_some_and(|c| c.is_ascii_digit() || b"+-.eE".contains(c)) {
+            self.position += 1;
+        }
+
+        let number = std::str::from_utf8(&self.input[start..self.position]).map_err(|_| self.error("Invalid number"))?;
+        number.parse().map(Value::Number).map_err(|_| self.error("Invalid number"))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut bytes = Vec::new();
+
+        loop {
+            let c = *self.input.get(self.position).ok_or_else(|| self.error("Unterminated string"))?;
+            self.position += 1;
+
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.input.get(self.position).ok_or_else(|| self.error("Unterminated string"))?;
+                    self.position += 1;
+
+                    let unescaped = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let hex = self.input.get(self.position..self.position + 4).ok_or_else(|| self.error("Invalid escape"))?;
+                            let hex = std::str::from_utf8(hex).map_err(|_| self.error("Invalid escape"))?;
+                            self.position += 4;
+                            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32).unwrap_or('\u{fffd}')
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+                    bytes.extend_from_slice(unescaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c => bytes.push(c),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in string"))
+    }
+
+    fn parse_array(&mut self) -> Result<Value, String> {
+        self.expect("[")?;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.expect("]").is_ok() {
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+
+            if self.expect(",").is_err() {
+                self.expect("]")?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, String> {
+        self.expect("{")?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.expect("}").is_ok() {
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+
+            if self.expect(",").is_err() {
+                self.expect("}")?;
+                return Ok(Value::Object(members));
+            }
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Value, String> {
+    let mut parser = Parser { input: input.as_bytes(), position: 0, depth: 0 };
+    let value = parser.parse_value()?;
+
+    parser.skip_whitespace();
+    if parser.position != parser.input.len() {
+        return Err(parser.error("Trailing characters"));
+    }
+
+    Ok(value)
+}
+
+/// A string as a JSON string literal, quoted and escaped.
+pub fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,1364 @@
+//! GPU detection and sampling for NVIDIA, AMD and Intel GPUs, as used by the `gpuatop`
+//! command-line tool.
+//!
+//! The stable API is [`detect`], [`Sampler`], [`SamplerBuilder`] and [`Sample`], with the
+//! types they expose. Everything else is public only for the `gpuatop` binary and its tests,
+//! is hidden from the documentation, and may change in any release.
+//!
+//! ```
+//! use gpu_auto_top::Sampler;
+//!
+//! let Ok(mut sampler) = Sampler::builder().build() else {
+//!     println!("No GPU, nothing to sample");
+//!     return;
+//! };
+//!
+//! for sample in sampler.sample().into_iter().flatten() {
+//!     println!("GPU {} ({}): {}%", sample.gpu.index, sample.gpu.name, sample.utilization);
+//! }
+//! ```
+//!
+//! # Features
+//!
+//! - `cli` (default): the `gpuatop` binary and the modules only it needs (output formats,
+//!   sinks, alerts, reports). Library users can turn it off with `default-features = false`.
+//! - `web` (default, implies `cli`): `gpuatop web`.
+//! - `opencl`, `vulkan`: GPU identification fallbacks through the system OpenCL and Vulkan loaders.
+
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod aggregate;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod alert;
+#[doc(hidden)]
+pub mod amd_smi;
+#[doc(hidden)]
+pub mod aperture;
+#[doc(hidden)]
+pub mod backend;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod budget;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod capabilities;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod check;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod csv;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod custom;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod delta;
+#[doc(hidden)]
+pub mod desktop;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod display;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod dmesg;
+#[doc(hidden)]
+pub mod driver;
+#[doc(hidden)]
+pub mod efficiency;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod event;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod golden;
+#[doc(hidden)]
+pub mod idle;
+#[cfg(feature = "network")]
+#[doc(hidden)]
+pub mod influx;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod jitter;
+#[doc(hidden)]
+pub mod json;
+#[doc(hidden)]
+pub mod live;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod metadata;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod mirror;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod msgpack;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod notify;
+#[doc(hidden)]
+pub mod nvlink;
+#[cfg(feature = "opencl")]
+#[doc(hidden)]
+pub mod opencl;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod output;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod overhead;
+#[doc(hidden)]
+pub mod pci;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod pause;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod persistence;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod pollers;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod power;
+#[doc(hidden)]
+pub mod process;
+#[doc(hidden)]
+pub mod prime;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod privileges;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod prometheus;
+#[doc(hidden)]
+pub mod rate;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod regex;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod report;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod requirements;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod rollup;
+#[doc(hidden)]
+pub mod runner;
+mod sampler;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod sampling;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod schedule;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod schema;
+#[cfg(feature = "lua")]
+#[doc(hidden)]
+pub mod script;
+#[cfg(feature = "network")]
+#[doc(hidden)]
+pub mod server;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod sink;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod snapshot;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod stats;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod startup;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod statsd;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod syslog;
+#[cfg(feature = "network")]
+#[doc(hidden)]
+pub mod tcp;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod template;
+#[doc(hidden)]
+pub mod temperature;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod terminal;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod topology;
+#[cfg(feature = "network")]
+#[doc(hidden)]
+pub mod udp;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod users;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod vgpu;
+#[doc(hidden)]
+pub mod videocore;
+#[cfg(feature = "vulkan")]
+#[doc(hidden)]
+pub mod vulkan;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod watch;
+#[cfg(feature = "web")]
+#[doc(hidden)]
+pub mod web;
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod xml;
+
+pub use desktop::UsageSplit;
+pub use nvlink::NvLinkMetrics;
+pub use sampler::{detect, BackendPreference, Device, Error, Sample, SampleError, Sampler, SamplerBuilder};
+
+use std::{io, str};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::Duration;
+
+use runner::{retry_command, CommandOutput, CommandRunner, Retry};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpuType {
+    Nvidia,
+    Amd,
+    /// The integrated GPU of an AMD Ryzen APU (Renoir, Cezanne, Rembrandt, Phoenix), which
+    /// radeontop and amd-smi barely support. It is read from `amdgpu_pm_info` in debugfs.
+    AmdApu,
+    Intel,
+    /// A discrete Intel Arc GPU (Alchemist, Battlemage) on the i915 or xe driver, named as
+    /// `lspci` does (`Arc A770`). It is read from sysfs rather than `intel_gpu_top`.
+    IntelArc(String),
+    /// The integrated GPU of an NVIDIA Jetson board (Nano, Xavier, Orin), which has no
+    /// `nvidia-smi` and is read through `tegrastats`.
+    JetsonGpu,
+    /// The VideoCore GPU of a Raspberry Pi, named after the board's device tree model
+    /// (`Raspberry Pi 5 Model B Rev 1.0`). It is read through `vcgencmd` and DRM fdinfo.
+    Broadcom(String),
+    /// A GPU from another vendor, described by its PCI vendor ID and name or its `lspci` line.
+    /// It has no vendor tool and is monitored through the generic DRM metrics.
+    Unknown(String),
+}
+
+impl GpuType {
+    /// The vendor tool gpuatop reads the metrics from; `None` for [`GpuType::AmdApu`] and
+    /// [`GpuType::IntelArc`], read from debugfs and sysfs, [`GpuType::Broadcom`], whose
+    /// `vcgencmd` comes with the system rather than a package, and [`GpuType::Unknown`].
+    pub fn top_tool(&self) -> Option<&'static str> {
+        match self {
+            GpuType::Nvidia => Some("nvidia-smi"),
+            GpuType::Amd => Some("radeontop"),
+            GpuType::Intel => Some("intel_gpu_top"),
+            GpuType::JetsonGpu => Some("tegrastats"),
+            GpuType::AmdApu | GpuType::IntelArc(_) | GpuType::Broadcom(_) | GpuType::Unknown(_) => None,
+        }
+    }
+
+    /// The package that provides [`GpuType::top_tool`].
+    pub fn top_package(&self) -> Option<&'static str> {
+        match self {
+            GpuType::Nvidia => Some("nvidia-smi"),
+            GpuType::Amd => Some("radeontop"),
+            GpuType::Intel => Some("intel-gpu-tools"),
+            GpuType::JetsonGpu => Some("nvidia-l4t-tools"),
+            GpuType::AmdApu | GpuType::IntelArc(_) | GpuType::Broadcom(_) | GpuType::Unknown(_) => None,
+        }
+    }
+
+    /// The package of the vendor tool under the name `installer` knows it by: Fedora and RHEL
+    /// ship intel-gpu-tools as `igt-gpu-tools`.
+    pub fn top_package_for(&self, installer: Installer) -> Option<&'static str> {
+        match (self, installer) {
+            (GpuType::Intel, Installer::PackageManager(PackageManager::Dnf | PackageManager::Yum) | Installer::Immutable(ImmutableSystem::RpmOstree)) => Some("igt-gpu-tools"),
+            _ => self.top_package(),
+        }
+    }
+
+    /// Maps a PCI vendor ID to a GPU type; vendors without a supported top tool map to `None`.
+    pub fn from_pci_vendor(vendor_id: u16) -> Option<GpuType> {
+        match vendor_id {
+            0x10de => Some(GpuType::Nvidia),
+            0x1002 | 0x1022 => Some(GpuType::Amd),
+            0x8086 => Some(GpuType::Intel),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[doc(hidden)]
+pub enum PackageManager {
+    Apt,
+    Pacman,
+    Dnf,
+    Yum,
+    Zypper,
+}
+
+impl FromStr for PackageManager {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "apt" => PackageManager::Apt,
+            "pacman" => PackageManager::Pacman,
+            "dnf" => PackageManager::Dnf,
+            "yum" => PackageManager::Yum,
+            "zypper" => PackageManager::Zypper,
+            _ => return Err("Package manager not found".to_string()),
+        })
+    }
+}
+
+/// An image-based distribution, where the regular package manager is missing or installing
+/// with it is wrong. gpuatop prints the install command instead of running it, since it needs
+/// a reboot or changes the user's own profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[doc(hidden)]
+pub enum ImmutableSystem {
+    /// Fedora Silverblue, Kinoite and other rpm-ostree variants.
+    RpmOstree,
+    Nix,
+    /// openSUSE MicroOS and Aeon.
+    TransactionalUpdate,
+}
+
+impl ImmutableSystem {
+    pub fn install_command(self, package: &str) -> String {
+        match self {
+            ImmutableSystem::RpmOstree => format!("rpm-ostree install {} && reboot", package),
+            ImmutableSystem::Nix => format!("nix-env -iA nixpkgs.{}", package),
+            ImmutableSystem::TransactionalUpdate => format!("transactional-update pkg install {} && reboot", package),
+        }
+    }
+}
+
+/// How the vendor tool gets installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[doc(hidden)]
+pub enum Installer {
+    PackageManager(PackageManager),
+    Immutable(ImmutableSystem),
+}
+
+impl Installer {
+    /// The command that installs `package`, as `gpuatop install --print-command` prints it.
+    pub fn install_command(self, package: &str) -> String {
+        match self {
+            Installer::PackageManager(package_manager) => {
+                format!("{} {} {}", package_manager_command(package_manager), package_manager_install_args(package_manager).join(" "), package)
+            }
+            Installer::Immutable(system) => system.install_command(package),
+        }
+    }
+}
+
+/// What became of the vendor tool installation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[doc(hidden)]
+pub enum InstallResult {
+    Installed,
+    /// The install command was printed for the user to run themselves.
+    InstructionsPrinted,
+    /// The user did not confirm the installation.
+    Declined,
+    /// Another process holds the package manager's lock, e.g. unattended upgrades running dpkg.
+    Locked,
+    /// The package is in EPEL, which is not enabled; `hint` holds the commands that enable it
+    /// and install the package.
+    EpelRequired { hint: String },
+    /// `output` holds the last [`INSTALL_OUTPUT_TAIL`] lines the package manager printed.
+    Failed { message: String, output: Vec<String> },
+}
+
+/// Number of package manager output lines kept for the failure message.
+#[doc(hidden)]
+pub const INSTALL_OUTPUT_TAIL: usize = 20;
+
+/// How long the package manager may run before it is killed. Installing one small package
+/// takes well under a minute; an apt waiting on an unreachable mirror would take far longer.
+#[doc(hidden)]
+pub const PACKAGE_MANAGER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The version of the loaded NVIDIA kernel module, which `nvidia-smi` has to match.
+const NVIDIA_DRIVER_VERSION: &str = "/sys/module/nvidia/version";
+
+/// What apt, pacman and zypper print when another process holds their lock.
+const LOCK_MESSAGES: [&str; 3] = ["could not get lock", "unable to lock database", "system management is locked"];
+
+/// What dnf and yum print when no enabled repository has the package.
+const NO_MATCH_MESSAGES: [&str; 2] = ["no match for argument", "unable to find a match"];
+
+/// Vendor tool packages that RHEL and its rebuilds only ship in EPEL.
+const EPEL_PACKAGES: [&str; 2] = ["radeontop", "igt-gpu-tools"];
+
+/// The EPEL repository file `epel-release` installs, checked when the repositories cannot be
+/// listed.
+const EPEL_REPO_FILE: &str = "/etc/yum.repos.d/epel.repo";
+
+#[derive(Debug, Clone)]
+pub struct GpuInfo {
+    pub index: u32,
+    pub name: String,
+    pub bus_id: Option<String>,
+    /// On hybrid graphics laptops, whether the GPU drives the display.
+    pub render_offload: Option<prime::RenderOffloadMode>,
+}
+
+/// A utilization or other percentage as stored in a [`GpuSnapshot`]: within 0 to 100, and
+/// never `-0.0`, so float noise such as `100.000000001` is never printed. Every source goes
+/// through it; vendor tools do report nonsense now and then, which must not panic.
+pub fn clamp_percent(value: f64) -> f64 {
+    if value.is_nan() {
+        return 0.0;
+    }
+    // Adding 0.0 turns -0.0 into 0.0.
+    value.clamp(0.0, 100.0) + 0.0
+}
+
+/// Widens a metric kept as `f32` through its shortest decimal form, so that `60.1` stays
+/// `60.1` next to `f64` percentages rather than becoming `60.099998474121094`.
+#[doc(hidden)]
+pub fn widen(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value.into())
+}
+
+/// Memory controller load. Drivers report either the share of time memory was being read or
+/// written (NVIDIA, amdgpu) or the read and write rates (Intel), so every value is optional.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryBandwidthMetrics {
+    pub read_gbps: Option<f32>,
+    pub write_gbps: Option<f32>,
+    pub utilization_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GpuSnapshot {
+    pub gpu: GpuInfo,
+    /// Percent, as precise as the source reports it; see [`clamp_percent`].
+    pub utilization: f64,
+    /// Peak utilization within the display interval when high-frequency samples are aggregated.
+    pub utilization_max: Option<f64>,
+    pub memory_used_mib: Option<u64>,
+    pub memory_total_mib: Option<u64>,
+    pub temperature_c: Option<f32>,
+    pub power_w: Option<f32>,
+    pub nvlink: Option<nvlink::NvLinkMetrics>,
+    pub usage_split: Option<desktop::UsageSplit>,
+    pub memory_bandwidth: Option<MemoryBandwidthMetrics>,
+    pub aperture: Option<aperture::ApertureMetrics>,
+    /// Every temperature sensor, with `--fields temps`.
+    pub temperatures: Option<temperature::Temperatures>,
+    /// Time since utilization was last above `--idle-threshold`, set by the monitor loop.
+    pub activity: Option<idle::Activity>,
+    /// Tensor Core throughput per watt with `--show-efficiency`, set by the monitor loop.
+    pub efficiency: Option<efficiency::Efficiency>,
+    /// The DRM engines' busy totals and shares, from fdinfo.
+    pub engines: Option<Vec<backend::EngineBusy>>,
+    /// The actual graphics clock, where the source reports it.
+    pub clock_mhz: Option<u32>,
+    /// Why the GPU is held back right now (`under_voltage`, `soft_temp_limit`...), where the
+    /// source reports it; empty when nothing is.
+    pub throttle_reasons: Option<Vec<String>>,
+    /// The [`backend::Backend::source`] the sample came from, set by the monitor loop.
+    pub source: Option<String>,
+}
+
+impl GpuSnapshot {
+    /// A sample of `gpu` with only its utilization; sources fill in the rest with struct-update
+    /// syntax.
+    pub fn new(gpu: GpuInfo, utilization: f64) -> Self {
+        GpuSnapshot {
+            gpu,
+            utilization,
+            utilization_max: None,
+            memory_used_mib: None,
+            memory_total_mib: None,
+            temperature_c: None,
+            power_w: None,
+            nvlink: None,
+            usage_split: None,
+            memory_bandwidth: None,
+            aperture: None,
+            temperatures: None,
+            activity: None,
+            efficiency: None,
+            engines: None,
+            clock_mhz: None,
+            throttle_reasons: None,
+            source: None,
+        }
+    }
+}
+
+/// The outcome of polling one GPU. `retries` counts the polls [`poll_gpus_with_retries`] repeated
+/// for it after transient errors, for the caller to report.
+// Nearly every poll succeeds, so boxing the snapshot would only add an allocation per sample.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+#[doc(hidden)]
+pub enum PollResult {
+    Ok { snapshot: GpuSnapshot, retries: u32 },
+    TransientError { gpu: GpuInfo, message: String, retries: u32 },
+    PermanentError { gpu: GpuInfo, message: String, retries: u32 },
+}
+
+impl PollResult {
+    pub fn gpu(&self) -> &GpuInfo {
+        match self {
+            PollResult::Ok { snapshot, .. } => &snapshot.gpu,
+            PollResult::TransientError { gpu, .. } | PollResult::PermanentError { gpu, .. } => gpu,
+        }
+    }
+
+    pub fn retries(&self) -> u32 {
+        match self {
+            PollResult::Ok { retries, .. } | PollResult::TransientError { retries, .. } | PollResult::PermanentError { retries, .. } => *retries,
+        }
+    }
+
+    fn with_retries(self, retries: u32) -> Self {
+        match self {
+            PollResult::Ok { snapshot, .. } => PollResult::Ok { snapshot, retries },
+            PollResult::TransientError { gpu, message, .. } => PollResult::TransientError { gpu, message, retries },
+            PollResult::PermanentError { gpu, message, .. } => PollResult::PermanentError { gpu, message, retries },
+        }
+    }
+}
+
+/// Number of consecutive permanent failures after which a GPU is dropped from monitoring.
+#[doc(hidden)]
+pub const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+#[doc(hidden)]
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Fields polled from `nvidia-smi`, in the column order [`parse_nvidia_smi_output`] expects.
+/// `utilization.memory` is the memory controller utilization. Lines come back in nvidia-smi's
+/// index order, so each names its GPU by index and PCI bus ID.
+#[doc(hidden)]
+pub const NVIDIA_SMI_QUERY: &str = "--query-gpu=index,utilization.gpu,memory.used,memory.total,temperature.gpu,power.draw,utilization.memory,pci.bus_id";
+
+/// Package managers in order of preference: dnf before yum, which Fedora keeps as an alias.
+#[doc(hidden)]
+pub const PACKAGE_MANAGERS: [&str; 5] = ["apt", "pacman", "dnf", "yum", "zypper"];
+
+#[doc(hidden)]
+pub const OS_RELEASE_PATH: &str = "/etc/os-release";
+
+fn command_exists(runner: &dyn CommandRunner, command: &str) -> bool {
+    runner.run("which", &[command]).is_ok_and(|output| output.success)
+}
+
+#[doc(hidden)]
+pub fn identify_package_manager(runner: &dyn CommandRunner) -> PackageManager {
+    try_identify_package_manager(runner).expect("Package manager not found")
+}
+
+#[doc(hidden)]
+pub fn try_identify_package_manager(runner: &dyn CommandRunner) -> Option<PackageManager> {
+    PACKAGE_MANAGERS
+        .into_iter()
+        .find(|package_manager| command_exists(runner, package_manager))
+        .map(|package_manager| package_manager.parse().expect("PACKAGE_MANAGERS are all parseable"))
+}
+
+/// Reads `KEY=value` lines of an os-release file, unquoting the values.
+#[doc(hidden)]
+pub fn parse_os_release(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter(|(key, _)| !key.starts_with('#'))
+        .map(|(key, value)| (key.to_string(), value.trim_matches(|c| c == '"' || c == '\'').to_string()))
+        .collect()
+}
+
+/// Recognizes an immutable distribution from its os-release `ID` and `VARIANT_ID`.
+#[doc(hidden)]
+pub fn immutable_system_from_os_release(os_release: &str) -> Option<ImmutableSystem> {
+    let fields = parse_os_release(os_release);
+    let id = fields.get("ID").map(String::as_str).unwrap_or_default();
+    let variant = fields.get("VARIANT_ID").map(String::as_str).unwrap_or_default();
+
+    match (id, variant) {
+        ("nixos", _) => Some(ImmutableSystem::Nix),
+        ("opensuse-microos" | "opensuse-aeon", _) | (_, "microos") => Some(ImmutableSystem::TransactionalUpdate),
+        (_, "silverblue" | "kinoite" | "sericea" | "onyx" | "coreos" | "iot") | ("fedora-coreos", _) => Some(ImmutableSystem::RpmOstree),
+        _ => None,
+    }
+}
+
+/// Whether os-release describes RHEL or a rebuild of it (CentOS Stream, Rocky, AlmaLinux,
+/// Oracle Linux), whose repositories lack what Fedora ships.
+#[doc(hidden)]
+pub fn is_rhel_family(os_release: &str) -> bool {
+    let fields = parse_os_release(os_release);
+    let id = fields.get("ID").map(String::as_str).unwrap_or_default();
+    let id_like = fields.get("ID_LIKE").map(String::as_str).unwrap_or_default();
+
+    id != "fedora" && (matches!(id, "rhel" | "centos" | "rocky" | "almalinux" | "ol") || id_like.split_whitespace().any(|like| like == "rhel" || like == "centos"))
+}
+
+/// Whether `<package manager> repolist` lists an enabled EPEL repository, e.g. `epel` or yum's
+/// `epel/x86_64`. When the repositories cannot be listed, whether EPEL's repository file is
+/// there.
+#[doc(hidden)]
+pub fn epel_enabled(runner: &dyn CommandRunner, package_manager: PackageManager) -> bool {
+    match runner.run(package_manager_command(package_manager), &["repolist"]) {
+        Ok(output) if output.success => output.stdout.lines().filter_map(|line| line.split_whitespace().next()).any(|id| id.trim_start_matches(['!', '*']).starts_with("epel")),
+        _ => std::path::Path::new(EPEL_REPO_FILE).exists(),
+    }
+}
+
+/// What installs EPEL: `epel-release` on the rebuilds, which carry it in their own
+/// repositories, and the package from the Fedora project on RHEL itself, which does not.
+#[doc(hidden)]
+pub fn epel_release_package(os_release: Option<&str>) -> String {
+    let fields = os_release.map(parse_os_release).unwrap_or_default();
+    let major = fields.get("VERSION_ID").and_then(|version| version.split('.').next()).filter(|major| !major.is_empty());
+
+    match (fields.get("ID").map(String::as_str), major) {
+        (Some("rhel"), Some(major)) => format!("https://dl.fedoraproject.org/pub/epel/epel-release-latest-{}.noarch.rpm", major),
+        _ => "epel-release".to_string(),
+    }
+}
+
+/// The package that has to be installed first to enable EPEL, when `package` needs it: dnf or
+/// yum on a RHEL-family system without EPEL enabled.
+#[doc(hidden)]
+pub fn epel_required(runner: &dyn CommandRunner, installer: Installer, package: &str, os_release: Option<&str>) -> Option<String> {
+    let Installer::PackageManager(package_manager @ (PackageManager::Dnf | PackageManager::Yum)) = installer else {
+        return None;
+    };
+    let needed = EPEL_PACKAGES.contains(&package) && os_release.is_some_and(is_rhel_family) && !epel_enabled(runner, package_manager);
+    needed.then(|| epel_release_package(os_release))
+}
+
+/// The two commands that enable EPEL and install `package`.
+fn epel_hint(installer: Installer, package: &str, epel_release: &str) -> String {
+    format!("{} is in EPEL, which is not enabled. Enable it and install {} with:\n  {}\n  {}", package, package, installer.install_command(epel_release), installer.install_command(package))
+}
+
+/// Picks how to install the vendor tool: an immutable system recognized from `os_release` or
+/// from its tools comes first, then the regular package managers. `nix-env` is only used when
+/// there is no package manager, as Nix is also installed alongside regular distributions.
+#[doc(hidden)]
+pub fn identify_installer(runner: &dyn CommandRunner, os_release: Option<&str>) -> Option<Installer> {
+    if let Some(system) = os_release.and_then(immutable_system_from_os_release) {
+        return Some(Installer::Immutable(system));
+    }
+    if command_exists(runner, "rpm-ostree") {
+        return Some(Installer::Immutable(ImmutableSystem::RpmOstree));
+    }
+    if command_exists(runner, "transactional-update") {
+        return Some(Installer::Immutable(ImmutableSystem::TransactionalUpdate));
+    }
+    if let Some(package_manager) = try_identify_package_manager(runner) {
+        return Some(Installer::PackageManager(package_manager));
+    }
+    command_exists(runner, "nix-env").then_some(Installer::Immutable(ImmutableSystem::Nix))
+}
+
+#[doc(hidden)]
+pub fn identify_gpu_card(runner: &dyn CommandRunner) -> GpuType {
+    try_identify_gpu_card(runner).expect("GPU not found")
+}
+
+/// Present on every Jetson Linux (L4T) install.
+const TEGRA_RELEASE: &str = "/etc/nv_tegra_release";
+const DEVICE_TREE_COMPATIBLE: &str = "/proc/device-tree/compatible";
+const DEVICE_TREE_MODEL: &str = "/proc/device-tree/model";
+
+/// Whether a device tree `compatible` list (NUL-separated strings such as
+/// `nvidia,p3768-0000+p3767-0005\0nvidia,tegra234\0`) names an NVIDIA board.
+#[doc(hidden)]
+pub fn is_jetson_compatible(compatible: &str) -> bool {
+    compatible.split('\0').any(|entry| entry.to_lowercase().contains("nvidia"))
+}
+
+/// The Raspberry Pi model the device tree names, e.g. `Raspberry Pi 4 Model B Rev 1.4`.
+fn raspberry_pi() -> Option<String> {
+    std::fs::read(DEVICE_TREE_MODEL).ok().and_then(|model| videocore::raspberry_pi_model(&String::from_utf8_lossy(&model)))
+}
+
+fn is_jetson() -> bool {
+    std::path::Path::new(TEGRA_RELEASE).exists()
+        || std::fs::read(DEVICE_TREE_COMPATIBLE).is_ok_and(|compatible| is_jetson_compatible(&String::from_utf8_lossy(&compatible)))
+}
+
+/// Identifies the GPU vendor from `lspci`, then the fallbacks; `None` when nothing is found.
+/// Jetson boards and Raspberry Pis are checked first: a Jetson's PCIe root ports show up as
+/// NVIDIA devices in `lspci`, but there is no `nvidia-smi` to read, and a Pi's GPU is not a
+/// PCI device at all.
+///
+/// Only display controllers count, by PCI class: NVSwitch bridges, host bridges and audio
+/// functions carry GPU vendors' names too. Where GPUs of several vendors are found, as on
+/// hybrid laptops, NVIDIA comes first, then AMD, then Intel Arc, then integrated Intel; an AMD
+/// APU only counts without a discrete GPU.
+#[doc(hidden)]
+pub fn try_identify_gpu_card(runner: &dyn CommandRunner) -> Option<GpuType> {
+    if is_jetson() {
+        return Some(GpuType::JetsonGpu);
+    }
+    if let Some(model) = raspberry_pi() {
+        return Some(GpuType::Broadcom(model));
+    }
+
+    let output = runner.run("lspci", &["-Dnn"]).map(|output| output.stdout).unwrap_or_default();
+    let rank = |gpu_type: &GpuType| match gpu_type {
+        GpuType::Nvidia => 0,
+        GpuType::Amd => 1,
+        GpuType::IntelArc(_) => 2,
+        GpuType::Intel => 3,
+        _ => 4,
+    };
+
+    pci::lspci_gpus(&output)
+        .iter()
+        .filter_map(|gpu| match GpuType::from_pci_vendor(gpu.vendor_id?)? {
+            GpuType::Amd if is_amd_apu(gpu.description) => Some(GpuType::AmdApu),
+            GpuType::Intel => Some(intel_arc_model(gpu.description).map_or(GpuType::Intel, GpuType::IntelArc)),
+            gpu_type => Some(gpu_type),
+        })
+        .min_by_key(rank)
+        .or_else(|| identify_gpu_fallback().or_else(|| identify_unknown_gpu(&output)))
+}
+
+/// Code names of the Ryzen APUs' integrated GPUs, as `lspci` describes them.
+const AMD_APU_CODENAMES: [&str; 4] = ["Renoir", "Cezanne", "Rembrandt", "Phoenix"];
+
+/// Whether an AMD GPU's `lspci` description (`Advanced Micro Devices, Inc. [AMD/ATI] Renoir`)
+/// names the integrated GPU of an APU.
+#[doc(hidden)]
+pub fn is_amd_apu(description: &str) -> bool {
+    AMD_APU_CODENAMES.iter().any(|codename| description.contains(codename))
+}
+
+/// Code names of the Arc GPUs, for `lspci` descriptions that do not name the model.
+const INTEL_ARC_CODENAMES: [&str; 4] = ["DG2", "Alchemist", "Battlemage", "BMG"];
+
+/// The model of an Intel Arc GPU from its `lspci` description: `Arc A770` from `Intel
+/// Corporation DG2 [Arc A770]`, else the description itself; `None` for integrated GPUs.
+#[doc(hidden)]
+pub fn intel_arc_model(description: &str) -> Option<String> {
+    let model = description.split('[').filter_map(|part| part.split_once(']')).map(|(model, _)| model).find(|model| model.starts_with("Arc "));
+    if let Some(model) = model {
+        return Some(model.to_string());
+    }
+    INTEL_ARC_CODENAMES
+        .iter()
+        .any(|codename| description.split_whitespace().any(|word| word == *codename))
+        .then(|| description.strip_prefix("Intel Corporation ").unwrap_or(description).to_string())
+}
+
+/// Detection that works without `lspci`, tried last: Vulkan, then OpenCL.
+fn identify_gpu_fallback() -> Option<GpuType> {
+    #[cfg(feature = "vulkan")]
+    if let Some(gpu_type) = vulkan::identify_gpu_type() {
+        return Some(gpu_type);
+    }
+    #[cfg(feature = "opencl")]
+    if let Some(gpu_type) = opencl::identify_gpu_type() {
+        return Some(gpu_type);
+    }
+    None
+}
+
+/// Describes a GPU from an unsupported vendor: the first display-class PCI device in sysfs,
+/// else the first display controller `lspci` lists, else a DRM card without a PCI device
+/// (e.g. an ARM SoC GPU).
+fn identify_unknown_gpu(lspci: &str) -> Option<GpuType> {
+    if let Some(device) = pci::list_display_devices().ok().and_then(|devices| devices.into_iter().next()) {
+        return Some(GpuType::Unknown(format!("0x{:04x} ({})", device.vendor_id, pci::vendor_name(device.vendor_id))));
+    }
+    if let Some(description) = lspci_display_controller(lspci) {
+        return Some(GpuType::Unknown(description.to_string()));
+    }
+    backend::drm_card_exists().then(|| GpuType::Unknown("DRM device".to_string()))
+}
+
+/// The vendor and device of the first GPU in `lspci` output.
+#[doc(hidden)]
+pub fn lspci_display_controller(lspci: &str) -> Option<&str> {
+    pci::lspci_gpus(lspci).first().map(|gpu| gpu.description)
+}
+
+/// Whether the vendor tool is installed. GPUs without a vendor tool need none, so this is
+/// `true` for them; on AMD, amd-smi does instead of radeontop.
+#[doc(hidden)]
+pub fn check_top_exists_local(runner: &dyn CommandRunner, gpu_type: &GpuType) -> io::Result<bool>  {
+    let Some(cmd) = gpu_type.top_tool() else {
+        return Ok(true);
+    };
+
+    if runner.run("which", &[cmd])?.success {
+        return Ok(true);
+    }
+    Ok(*gpu_type == GpuType::Amd && runner.run("which", &["amd-smi"]).is_ok_and(|output| output.success))
+}
+
+#[doc(hidden)]
+pub fn package_manager_command(package_manager: PackageManager) -> &'static str {
+    match package_manager {
+        PackageManager::Apt => "apt",
+        PackageManager::Pacman => "pacman",
+        PackageManager::Dnf => "dnf",
+        PackageManager::Yum => "yum",
+        PackageManager::Zypper => "zypper",
+    }
+}
+
+/// The install subcommand and the flag that answers its prompt.
+fn package_manager_install_args(package_manager: PackageManager) -> [&'static str; 2] {
+    let package_manager_install_command = match package_manager {
+        PackageManager::Apt => "install",
+        PackageManager::Pacman => "-S",
+        PackageManager::Dnf => "install",
+        PackageManager::Yum => "install",
+        PackageManager::Zypper => "install",
+    };
+
+    let package_manager_install_without_confirm_command = match package_manager {
+        PackageManager::Apt => "-y",
+        PackageManager::Pacman => "--noconfirm",
+        PackageManager::Dnf => "-y",
+        PackageManager::Yum => "-y",
+        PackageManager::Zypper => "-y",
+    };
+
+    [package_manager_install_command, package_manager_install_without_confirm_command]
+}
+
+/// Runs the package manager, handing its output to `on_line` line by line as it is printed.
+/// It is killed after [`PACKAGE_MANAGER_TIMEOUT`].
+#[doc(hidden)]
+pub fn install_package_for_gpu(runner: &dyn CommandRunner, package_manager: PackageManager, package_name: &str, on_line: &mut dyn FnMut(&str)) -> io::Result<CommandOutput>{
+    let [install, without_confirm] = package_manager_install_args(package_manager);
+
+    runner.run_streaming(package_manager_command(package_manager), &[install, without_confirm, package_name], PACKAGE_MANAGER_TIMEOUT, on_line)
+}
+
+/// What to install on a machine that cannot reach its package mirror: the package, the
+/// version it has to match where there is one, and the command that installs it once the
+/// package is available, e.g. from a local mirror or baked into the image.
+#[doc(hidden)]
+pub fn offline_instructions(gpu_type: &GpuType, installer: Installer, driver_version: Option<&str>) -> Option<String> {
+    let package = gpu_type.top_package_for(installer)?;
+    let version = match (gpu_type, driver_version) {
+        (GpuType::Nvidia, Some(version)) => format!(" version {} (the loaded driver's)", version),
+        _ => String::new(),
+    };
+
+    Some(format!("Install {}{} from a local mirror or bake it into the image with:\n  {}", package, version, installer.install_command(package)))
+}
+
+/// The version of the loaded NVIDIA driver, e.g. `535.129.03`.
+#[doc(hidden)]
+pub fn nvidia_driver_version() -> Option<String> {
+    std::fs::read_to_string(NVIDIA_DRIVER_VERSION).ok().map(|version| version.trim().to_string()).filter(|version| !version.is_empty())
+}
+
+/// Installs the vendor tool with a package manager once `confirm` agrees to the prompt it is
+/// given, handing the package manager's output to `print` as it runs. On immutable systems
+/// nothing is run; the install command goes to `print` instead. On RHEL-family systems
+/// (recognized from `os_release`) a package that is only in EPEL gets `epel-release` installed
+/// first, under the same confirmation.
+#[doc(hidden)]
+pub fn install_top_for_gpu_to(
+    runner: &dyn CommandRunner,
+    gpu_type: &GpuType,
+    installer: Installer,
+    os_release: Option<&str>,
+    confirm: impl FnOnce(&str) -> bool,
+    mut print: impl FnMut(&str),
+) -> InstallResult {
+    let Some(package) = gpu_type.top_package_for(installer) else {
+        return InstallResult::Failed { message: "There is no monitoring tool for this GPU".to_string(), output: Vec::new() };
+    };
+
+    let package_manager = match installer {
+        Installer::PackageManager(package_manager) => package_manager,
+        Installer::Immutable(system) => {
+            print(&format!("This system is immutable; install {} with:\n  {}", package, system.install_command(package)));
+            return InstallResult::InstructionsPrinted;
+        }
+    };
+
+    let epel_release = epel_required(runner, installer, package, os_release);
+    let prompt = match &epel_release {
+        Some(epel_release) => format!("Install {} with {:?}? It is in EPEL, so {} is installed first to enable it.", package, package_manager, epel_release),
+        None => format!("Install {} with {:?}?", package, package_manager),
+    };
+    if !confirm(&prompt) {
+        if let Some(epel_release) = &epel_release {
+            print(&epel_hint(installer, package, epel_release));
+        }
+        return InstallResult::Declined;
+    }
+
+    if let Some(epel_release) = &epel_release {
+        match install_package(runner, package_manager, epel_release, &mut print) {
+            (InstallResult::Installed, _) => {}
+            (result, _) => return result,
+        }
+    }
+
+    match install_package(runner, package_manager, package, &mut print) {
+        (InstallResult::Failed { .. }, true) if EPEL_PACKAGES.contains(&package) => InstallResult::EpelRequired { hint: epel_hint(installer, package, &epel_release_package(os_release)) },
+        (result, _) => result,
+    }
+}
+
+/// Installs one package, and whether the package manager found no package by that name.
+fn install_package(runner: &dyn CommandRunner, package_manager: PackageManager, package: &str, print: &mut dyn FnMut(&str)) -> (InstallResult, bool) {
+    let command = package_manager_command(package_manager);
+    let mut tail = VecDeque::with_capacity(INSTALL_OUTPUT_TAIL);
+    let mut locked = false;
+    let mut no_match = false;
+    let result = install_package_for_gpu(runner, package_manager, package, &mut |line| {
+        print(&format!("{}: {}", command, line));
+        let lowercase = line.to_lowercase();
+        locked |= LOCK_MESSAGES.iter().any(|message| lowercase.contains(message));
+        no_match |= NO_MATCH_MESSAGES.iter().any(|message| lowercase.contains(message)) || lowercase.contains(&format!("no package {} available", package));
+        if tail.len() == INSTALL_OUTPUT_TAIL {
+            tail.pop_front();
+        }
+        tail.push_back(line.to_string());
+    });
+
+    let result = match result {
+        Ok(output) if output.success => InstallResult::Installed,
+        Ok(_) if locked => InstallResult::Locked,
+        Ok(output) => InstallResult::Failed {
+            message: match output.code {
+                Some(code) => format!("{} exited with code {}", command, code),
+                None => format!("{} was terminated by a signal", command),
+            },
+            output: tail.into(),
+        },
+        Err(err) if err.kind() == io::ErrorKind::TimedOut => InstallResult::Failed { message: err.to_string(), output: tail.into() },
+        Err(err) => InstallResult::Failed { message: format!("Failed to run {}: {}", command, err), output: Vec::new() },
+    };
+    (result, no_match)
+}
+
+#[doc(hidden)]
+pub fn enumerate_gpus(runner: &dyn CommandRunner, gpu_type: &GpuType) -> Vec<GpuInfo> {
+    if let GpuType::Nvidia = gpu_type {
+        if let Ok(output) = runner.run("nvidia-smi", &["--query-gpu=index,pci.bus_id,name", "--format=csv,noheader"]) {
+            let gpus: Vec<GpuInfo> = output
+                .stdout
+                .lines()
+                .filter_map(|line| {
+                    let fields = csv::parse_line(line);
+                    // An unquoted name with a comma spills into further fields.
+                    let name = fields.get(2..)?.join(", ");
+                    Some(GpuInfo {
+                        index: csv::number(&fields[0])?,
+                        bus_id: Some(pci::normalize_bus_id(&fields[1])),
+                        name,
+                        render_offload: None,
+                    })
+                })
+                .collect();
+
+            if !gpus.is_empty() {
+                return gpus;
+            }
+        }
+    }
+    if let GpuType::Amd = gpu_type {
+        let devices = runner.run("amd-smi", &amd_smi::LIST_ARGS).ok().filter(|output| output.success).and_then(|output| amd_smi::parse_list(&output.stdout).ok());
+        if let Some(devices) = devices.filter(|devices| !devices.is_empty()) {
+            return amd_smi::gpus(&devices);
+        }
+    }
+
+    let name = match gpu_type {
+        GpuType::Unknown(description) => description.clone(),
+        // "NVIDIA Jetson Orin Nano Developer Kit", NUL-terminated.
+        GpuType::JetsonGpu => std::fs::read_to_string(DEVICE_TREE_MODEL)
+            .ok()
+            .map(|model| model.trim_end_matches('\0').trim().to_string())
+            .filter(|model| !model.is_empty())
+            .unwrap_or_else(|| "Jetson GPU".to_string()),
+        // The marketing name of every Ryzen APU's GPU.
+        GpuType::AmdApu => "AMD Radeon Graphics".to_string(),
+        GpuType::IntelArc(model) => format!("Intel {}", model),
+        GpuType::Broadcom(model) => format!("{} VideoCore", model),
+        known => format!("{:?} GPU", known),
+    };
+    vec![GpuInfo { index: 0, name, bus_id: None, render_offload: None }]
+}
+
+/// Runs a tool that streams samples forever and returns its first `lines` lines of output.
+#[doc(hidden)]
+pub fn read_streaming_output(name: &str, args: &[&str], lines: usize) -> io::Result<String> {
+    let mut child = Command::new(name).args(args).stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let output: Vec<String> = BufReader::new(stdout).lines().take(lines).collect::<io::Result<_>>()?;
+
+    let _ = child.kill();
+    let _ = child.wait();
+
+    Ok(output.join("\n"))
+}
+
+#[doc(hidden)]
+pub fn parse_optional<T: FromStr>(value: Option<&str>) -> Option<T> {
+    value.and_then(|value| value.trim().parse().ok())
+}
+
+/// Parses `nvidia-smi --query-gpu` CSV output, in the [`NVIDIA_SMI_QUERY`] column order.
+/// Each line is matched to a GPU by its PCI bus ID, or by its index where either side lacks
+/// one. Lines for GPUs not in `gpus` are ignored; it is an error when no line parses at all.
+#[doc(hidden)]
+pub fn parse_nvidia_smi_output(output: &str, gpus: &[GpuInfo]) -> Result<HashMap<u32, GpuSnapshot>, String> {
+    let mut snapshots = HashMap::new();
+    let mut parsed_any = false;
+
+    for line in output.lines() {
+        let fields = csv::parse_line(line);
+        let field = |column: usize| fields.get(column).map(String::as_str).unwrap_or_default();
+
+        let Some(index) = csv::number::<u32>(field(0)) else { continue };
+        let Some(utilization) = csv::decimal(field(1)).map(clamp_percent) else { continue };
+        parsed_any = true;
+        let bus_id = Some(field(7)).filter(|bus_id| !bus_id.is_empty()).map(pci::normalize_bus_id);
+        let Some(gpu) = gpus.iter().find(|gpu| match (&bus_id, &gpu.bus_id) {
+            (Some(bus_id), Some(gpu_bus_id)) => bus_id == gpu_bus_id,
+            _ => gpu.index == index,
+        }) else {
+            continue;
+        };
+
+        snapshots.insert(gpu.index, GpuSnapshot {
+            memory_used_mib: csv::number(field(2)),
+            memory_total_mib: csv::number(field(3)),
+            temperature_c: csv::number(field(4)),
+            power_w: csv::number(field(5)),
+            memory_bandwidth: csv::decimal(field(6))
+                .map(|utilization| MemoryBandwidthMetrics { utilization_pct: Some(clamp_percent(utilization)), ..Default::default() }),
+            ..GpuSnapshot::new(gpu.clone(), utilization)
+        });
+    }
+
+    if !parsed_any {
+        return Err(format!("Unexpected nvidia-smi output: {}", output.lines().find(|line| !line.trim().is_empty()).unwrap_or_default().trim()));
+    }
+    Ok(snapshots)
+}
+
+/// Extracts the number preceding `suffix` in the radeontop field named `key`, e.g. `gpu 12.50%`.
+/// Fields are separated by `, `, so a locale's decimal comma (`gpu 12,50%`) stays in its field.
+#[doc(hidden)]
+pub fn radeontop_field<'a>(output: &'a str, key: &str, suffix: &str) -> Option<&'a str> {
+    output
+        .split(", ")
+        .map(str::trim)
+        .find_map(|field| field.strip_prefix(key)?.split_whitespace().find_map(|value| value.strip_suffix(suffix)))
+}
+
+#[doc(hidden)]
+pub fn parse_radeontop_output(output: &str, gpu: &GpuInfo) -> Result<GpuSnapshot, String> {
+    let line = output
+        .lines()
+        .rev()
+        .find(|line| line.contains("gpu "))
+        .ok_or_else(|| format!("Unexpected radeontop output: {}", output.trim()))?;
+    let utilization = radeontop_field(line, "gpu", "%")
+        .and_then(csv::decimal)
+        .map(clamp_percent)
+        .ok_or_else(|| format!("No GPU utilization in radeontop output: {}", line.trim()))?;
+
+    Ok(GpuSnapshot { memory_used_mib: radeontop_field(line, "vram", "mb").and_then(csv::decimal).map(|mb| mb as u64), ..GpuSnapshot::new(gpu.clone(), utilization) })
+}
+
+/// The power management state of the first DRM device; APUs have no other.
+#[doc(hidden)]
+pub const AMDGPU_PM_INFO: &str = "/sys/kernel/debug/dri/0/amdgpu_pm_info";
+
+/// A `GPU Load: 7 %` or `GPU Temperature: 44 C` line of `amdgpu_pm_info`.
+fn pm_info_value(output: &str, key: &str, unit: &str) -> Option<f64> {
+    output.lines().find_map(|line| line.trim().strip_prefix(key)?.strip_prefix(':')?.trim().strip_suffix(unit)?.trim().parse().ok())
+}
+
+/// Parses `amdgpu_pm_info`. The load and temperature have lines of their own; the power is one
+/// of the `3.0 W (average SoC)` lines under `GFX Clocks and Power`, for the SoC on APUs.
+#[doc(hidden)]
+pub fn parse_amd_pm_info(output: &str, gpu: &GpuInfo) -> Result<GpuSnapshot, String> {
+    let utilization = pm_info_value(output, "GPU Load", "%").map(clamp_percent).ok_or_else(|| format!("No GPU load in amdgpu_pm_info: {}", output.trim()))?;
+    let power_w = output.lines().find_map(|line| {
+        let (value, label) = line.trim().split_once(" (")?;
+        label.starts_with("average").then_some(value.strip_suffix(" W")?.parse::<f32>().ok()?)
+    });
+
+    Ok(GpuSnapshot {
+        temperature_c: pm_info_value(output, "GPU Temperature", "C").map(|celsius| celsius as f32),
+        power_w,
+        memory_bandwidth: pm_info_value(output, "MEM Load", "%")
+            .map(|load| MemoryBandwidthMetrics { utilization_pct: Some(clamp_percent(load)), ..Default::default() }),
+        ..GpuSnapshot::new(gpu.clone(), utilization)
+    })
+}
+
+const MIB_PER_S_IN_GBPS: f32 = 1_048_576.0 / 1e9;
+
+#[doc(hidden)]
+pub fn parse_intel_gpu_top_output(output: &str, gpu: &GpuInfo) -> Result<GpuSnapshot, String> {
+    let mut lines = output.lines();
+    let header = lines.next().ok_or("Empty intel_gpu_top output")?;
+    let units: Vec<&str> = lines.next().ok_or("Missing units line in intel_gpu_top output")?.split_whitespace().collect();
+    let values: Vec<&str> = lines.last().ok_or("No sample in intel_gpu_top output")?.split_whitespace().collect();
+
+    // Each engine (`RCS/0`, `BCS/0`, ...) has `% se wa` columns; the units line lines up with
+    // the values, so the render engine is the n-th `% se` pair, whatever columns come first.
+    let is_engine = |column: &&str| column.split_once('/').is_some_and(|(_, instance)| instance.parse::<u32>().is_ok());
+    let render_index = header
+        .split_whitespace()
+        .filter(is_engine)
+        .position(|column| column.starts_with("RCS"))
+        .and_then(|engine| (0..units.len()).filter(|&index| units[index] == "%" && units.get(index + 1) == Some(&"se")).nth(engine))
+        .ok_or_else(|| format!("No render engine column in intel_gpu_top header: {}", header.trim()))?;
+    let utilization = values
+        .get(render_index)
+        .and_then(|value| csv::decimal(value))
+        .map(clamp_percent)
+        .ok_or_else(|| format!("No render engine value in intel_gpu_top output: {}", values.join(" ")))?;
+
+    // Newer versions add "IMC MiB/s" columns, whose `rd` and `wr` units line up with the values.
+    let imc = |unit: &str| -> Option<f32> {
+        let value: f32 = values.get(units.iter().position(|candidate| *candidate == unit)?)?.parse().ok()?;
+        Some(value * MIB_PER_S_IN_GBPS)
+    };
+    let (read_gbps, write_gbps) = (imc("rd"), imc("wr"));
+
+    Ok(GpuSnapshot {
+        memory_bandwidth: (read_gbps.is_some() || write_gbps.is_some()).then_some(MemoryBandwidthMetrics { read_gbps, write_gbps, utilization_pct: None }),
+        ..GpuSnapshot::new(gpu.clone(), utilization)
+    })
+}
+
+/// The value following `key` in a tegrastats line, e.g. `45%@921` for `GR3D_FREQ`.
+fn tegrastats_value<'a>(tokens: &[&'a str], key: &str) -> Option<&'a str> {
+    tokens.iter().position(|token| *token == key).and_then(|position| tokens.get(position + 1).copied())
+}
+
+/// `45%@921`, `45%@[921,921]` or `45%` → 45.
+fn tegrastats_percent(value: &str) -> Option<f64> {
+    csv::decimal(value.split_once('%')?.0)
+}
+
+/// Rails that power the GPU alone: `POM_5V_GPU` (Nano, TX2), `GPU` (Xavier), `VDD_GPU_SOC` and
+/// `VDD_GPU` (AGX Orin). Orin Nano and NX only measure the GPU together with the CPU, so they
+/// report no GPU power.
+const TEGRASTATS_GPU_RAILS: [&str; 4] = ["POM_5V_GPU", "GPU", "VDD_GPU_SOC", "VDD_GPU"];
+
+/// The last sample line of `tegrastats` output, which is one line of `KEY value` pairs:
+///
+/// `RAM 2257/3964MB (lfb 25x4MB) CPU [9%@1479,off] EMC_FREQ 3%@1600 GR3D_FREQ 45%@921 GPU@34C POM_5V_GPU 840/812`
+///
+/// The GPU shares the board's RAM, so memory is the `RAM` figure. `EMC_FREQ` is the load of
+/// the external memory controller.
+#[doc(hidden)]
+pub fn parse_tegrastats_output(output: &str, gpu: &GpuInfo) -> Result<GpuSnapshot, String> {
+    let line = output
+        .lines()
+        .rev()
+        .find(|line| line.contains("GR3D_FREQ"))
+        .ok_or_else(|| format!("Unexpected tegrastats output: {}", output.trim()))?;
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    let utilization = tegrastats_value(&tokens, "GR3D_FREQ")
+        .and_then(tegrastats_percent)
+        .map(clamp_percent)
+        .ok_or_else(|| format!("No GR3D_FREQ load in tegrastats output: {}", line.trim()))?;
+    let memory = tegrastats_value(&tokens, "RAM")
+        .and_then(|ram| ram.strip_suffix("MB")?.split_once('/'))
+        .and_then(|(used, total)| Some((used.parse::<u64>().ok()?, total.parse::<u64>().ok()?)));
+    // `GPU@34C` on Nano and Xavier, `gpu@47.5C` on Orin.
+    let temperature_c = tokens.iter().find_map(|token| {
+        let (sensor, value) = token.split_once('@')?;
+        sensor.eq_ignore_ascii_case("gpu").then_some(())?;
+        value.strip_suffix('C')?.parse().ok()
+    });
+    // `840/812` (Nano, Xavier) or `840mW/812mW` (Orin): current and average milliwatts.
+    let power_w = TEGRASTATS_GPU_RAILS.iter().find_map(|rail| {
+        let milliwatts: f32 = tegrastats_value(&tokens, rail)?.split('/').next()?.trim_end_matches("mW").parse().ok()?;
+        Some(milliwatts / 1000.0)
+    });
+
+    Ok(GpuSnapshot {
+        memory_used_mib: memory.map(|(used, _)| used),
+        memory_total_mib: memory.map(|(_, total)| total),
+        temperature_c,
+        power_w,
+        memory_bandwidth: tegrastats_value(&tokens, "EMC_FREQ")
+            .and_then(tegrastats_percent)
+            .map(|utilization| MemoryBandwidthMetrics { utilization_pct: Some(clamp_percent(utilization)), ..Default::default() }),
+        ..GpuSnapshot::new(gpu.clone(), utilization)
+    })
+}
+
+/// The load of each CPU core in the last tegrastats line (`CPU [9%@1479,6%@1479,off,off]`);
+/// `None` for cores that are offline.
+#[doc(hidden)]
+pub fn parse_tegrastats_cpu(output: &str) -> Option<Vec<Option<f64>>> {
+    let line = output.lines().rev().find(|line| line.contains("CPU ["))?;
+    let cores = line.split_once("CPU [")?.1.split_once(']')?.0;
+
+    Some(cores.split(',').map(|core| tegrastats_percent(core.trim())).collect())
+}
+
+/// Polls the GPUs with one run of the vendor tool. intel_gpu_top and tegrastats never exit on
+/// their own, so they are read as a stream rather than through `runner`.
+#[doc(hidden)]
+pub fn poll_gpus(runner: &dyn CommandRunner, gpu_type: &GpuType, gpus: &[GpuInfo]) -> Vec<PollResult> {
+    poll_gpus_capturing(runner, gpu_type, gpus, Retry::ONCE).0
+}
+
+/// Same as [`poll_gpus`], also returning what the vendor tool printed. A run that fails, or a
+/// stream that ends without output, is attempted again as `retry` allows.
+pub(crate) fn poll_gpus_capturing(runner: &dyn CommandRunner, gpu_type: &GpuType, gpus: &[GpuInfo], retry: Retry) -> (Vec<PollResult>, Option<backend::RawOutput>) {
+    let mut raw = None;
+    let output = match gpu_type {
+        GpuType::Nvidia => retry_command(runner, "nvidia-smi", &[NVIDIA_SMI_QUERY, "--format=csv,noheader,nounits"], retry.max_attempts, retry.delay)
+            .and_then(|output| {
+                raw = Some(backend::RawOutput::from(&output));
+                if output.success {
+                    return Ok(output.stdout);
+                }
+                // nvidia-smi reports most failures ("No devices were found") on stdout.
+                let message = if output.stderr.trim().is_empty() { &output.stdout } else { &output.stderr };
+                Err(io::Error::other(message.trim().to_string()))
+            }),
+        GpuType::Amd => retry_command(runner, "radeontop", &["-d", "-", "-l", "1"], retry.max_attempts, retry.delay).and_then(|output| {
+            raw = Some(backend::RawOutput::from(&output));
+            if output.success {
+                return Ok(output.stdout);
+            }
+            Err(io::Error::other(format!("radeontop failed: {}", output.stderr.trim())))
+        }),
+        GpuType::Intel => retry.run(|| read_streaming_output("intel_gpu_top", &["-s", "1000", "-o", "-"], 4), |output| !output.is_empty()).inspect(|output| {
+            raw = Some(backend::RawOutput { text: output.clone(), code: None });
+        }),
+        GpuType::JetsonGpu => retry.run(|| read_streaming_output("tegrastats", &["--interval", "1000"], 1), |output| !output.is_empty()).inspect(|output| {
+            raw = Some(backend::RawOutput { text: output.clone(), code: None });
+        }),
+        GpuType::AmdApu => std::fs::read_to_string(AMDGPU_PM_INFO)
+            .map_err(|err| io::Error::new(err.kind(), format!("Cannot read {}: {} (debugfs is only readable by root)", AMDGPU_PM_INFO, err)))
+            .inspect(|output| raw = Some(backend::RawOutput { text: output.clone(), code: None })),
+        GpuType::IntelArc(_) | GpuType::Broadcom(_) | GpuType::Unknown(_) => Err(io::Error::new(io::ErrorKind::NotFound, "There is no monitoring tool for this GPU")),
+    };
+
+    let output = match output {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let results = gpus
+                .iter()
+                .map(|gpu| PollResult::PermanentError { gpu: gpu.clone(), message: err.to_string(), retries: 0 })
+                .collect();
+            return (results, raw);
+        }
+        Err(err) => {
+            let results = gpus
+                .iter()
+                .map(|gpu| PollResult::TransientError { gpu: gpu.clone(), message: err.to_string(), retries: 0 })
+                .collect();
+            return (results, raw);
+        }
+    };
+
+    let parsed = match gpu_type {
+        GpuType::Nvidia => parse_nvidia_smi_output(&output, gpus),
+        GpuType::Amd => gpus.iter().map(|gpu| Ok((gpu.index, parse_radeontop_output(&output, gpu)?))).collect(),
+        GpuType::Intel => gpus.iter().map(|gpu| Ok((gpu.index, parse_intel_gpu_top_output(&output, gpu)?))).collect(),
+        GpuType::JetsonGpu => gpus.iter().map(|gpu| Ok((gpu.index, parse_tegrastats_output(&output, gpu)?))).collect(),
+        GpuType::AmdApu => gpus.iter().map(|gpu| Ok((gpu.index, parse_amd_pm_info(&output, gpu)?))).collect(),
+        GpuType::IntelArc(_) | GpuType::Broadcom(_) | GpuType::Unknown(_) => Err("There is no monitoring tool for this GPU".to_string()),
+    };
+    let mut snapshots = match parsed {
+        Ok(snapshots) => snapshots,
+        Err(message) => {
+            let results = gpus
+                .iter()
+                .map(|gpu| PollResult::TransientError { gpu: gpu.clone(), message: message.clone(), retries: 0 })
+                .collect();
+            return (results, raw);
+        }
+    };
+
+    let results = gpus
+        .iter()
+        .map(|gpu| match snapshots.remove(&gpu.index) {
+            Some(snapshot) => PollResult::Ok { snapshot, retries: 0 },
+            None => PollResult::TransientError {
+                gpu: gpu.clone(),
+                message: format!("No sample for GPU {} in the output", gpu.index),
+                retries: 0,
+            },
+        })
+        .collect();
+    (results, raw)
+}
+
+/// Polls the GPUs, retrying transient errors up to `max_retries` times. Each result carries the
+/// number of retries its GPU took; errors that persist past the retries are reported as
+/// permanent.
+#[doc(hidden)]
+pub fn poll_gpus_with_retries(gpus: &[GpuInfo], max_retries: u32, mut poll: impl FnMut(&[GpuInfo]) -> Vec<PollResult>) -> Vec<PollResult> {
+    let mut results = poll(gpus);
+
+    for attempt in 1..=max_retries {
+        let failed: Vec<GpuInfo> = results
+            .iter()
+            .filter_map(|result| match result {
+                PollResult::TransientError { gpu, .. } => Some(gpu.clone()),
+                _ => None,
+            })
+            .collect();
+
+        if failed.is_empty() {
+            return results;
+        }
+
+        // A GPU the retry has no result for keeps its error, as if it had failed again.
+        let mut retried: HashMap<u32, PollResult> = poll(&failed).into_iter().map(|result| (result.gpu().index, result)).collect();
+        results = results
+            .into_iter()
+            .map(|result| match result {
+                PollResult::TransientError { ref gpu, .. } => retried.remove(&gpu.index).unwrap_or(result).with_retries(attempt),
+                other => other,
+            })
+            .collect();
+    }
+
+    results
+        .into_iter()
+        .map(|result| match result {
+            PollResult::TransientError { gpu, message, retries } => PollResult::PermanentError {
+                gpu,
+                message: format!("{} (after {} retries)", message, retries),
+                retries,
+            },
+            other => other,
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+//! The block of lines redrawn in place on a terminal: `--format table` refreshing every tick.
+//! Warnings, alerts and events printed meanwhile, from the sampling loop or from the threads of
+//! the sinks, would land in the middle of it and be overwritten by the next redraw. So while a
+//! block is on screen every line goes through here, one writer at a time: the block is erased,
+//! the line printed where it stood, and the block drawn again below it, as indicatif does for
+//! its progress bars. Without a block the lines are printed as usual.
+
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Moves the cursor to the start of the line `count` lines up, then clears to the end of the
+/// screen.
+fn erase(count: usize) -> String {
+    if count == 0 {
+        String::new()
+    } else {
+        format!("\x1b[{}F\x1b[J", count)
+    }
+}
+
+/// The lines last drawn, and the sequences that keep other output clear of them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Block {
+    lines: Vec<String>,
+}
+
+impl Block {
+    pub const fn new() -> Self {
+        Block { lines: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// What draws `lines` over the block, which they become.
+    pub fn replace(&mut self, lines: Vec<String>) -> String {
+        let text = erase(self.lines.len()) + &lines.iter().map(|line| format!("{}\n", line)).collect::<String>();
+        self.lines = lines;
+        text
+    }
+
+    /// What goes before a message, erasing the block, and after it, drawing the block again.
+    pub fn around(&self) -> (String, String) {
+        (erase(self.lines.len()), self.lines.iter().map(|line| format!("{}\n", line)).collect())
+    }
+}
+
+static BLOCK: Mutex<Block> = Mutex::new(Block::new());
+
+fn block() -> MutexGuard<'static, Block> {
+    // A writer that panicked leaves the block as it was, which is still what is on screen.
+    BLOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Draws `lines` over the block on stdout.
+pub fn draw(lines: Vec<String>) -> io::Result<()> {
+    let mut block = block();
+    let text = block.replace(lines);
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()
+}
+
+/// Leaves the block on screen as it is; later output follows it.
+pub fn release() {
+    block().replace(Vec::new());
+}
+
+/// Writes `text` to stdout, or stderr, between erasing the block and drawing it again. Fails
+/// when stdout does, e.g. once the reader of a pipe went away; the text still goes to stderr.
+fn write(text: &str, stderr: bool) -> io::Result<()> {
+    let block = block();
+    let (before, after) = block.around();
+    let mut stdout = io::stdout().lock();
+    let erased = stdout.write_all(before.as_bytes()).and_then(|_| stdout.flush());
+    if stderr {
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(text.as_bytes());
+        let _ = stderr.flush();
+    } else {
+        erased?;
+        stdout.write_all(text.as_bytes())?;
+    }
+    stdout.write_all(after.as_bytes())?;
+    stdout.flush()
+}
+
+/// Prints `text` to stdout above the block.
+pub fn print(text: &str) -> io::Result<()> {
+    write(text, false)
+}
+
+pub fn println(line: &str) -> io::Result<()> {
+    write(&format!("{}\n", line), false)
+}
+
+/// Prints `line` to stderr above the block.
+pub fn eprintln(line: &str) {
+    let _ = write(&format!("{}\n", line), true);
+}
+
+/// Prints `text`, a marker that does not end its line, to stderr. It has no room while a block
+/// is on screen and is left out then.
+pub fn eprint(text: &str) {
+    if block().is_empty() {
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(text.as_bytes());
+        let _ = stderr.flush();
+    }
+}
@@ -1,158 +1,1615 @@
-use std::{io, str};
-use std::process::{Command, ExitStatus, Output};
-use std::str::FromStr;
+mod monitor;
+
+use std::{env, fs, io};
+use std::io::{IsTerminal, Write};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+#[cfg(feature = "network")]
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "network")]
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::runner::{self, RealRunner};
+use gpu_auto_top::{alert, backend, budget, capabilities, check, config, custom, desktop, efficiency, display, golden, jitter, json, metadata, mirror, msgpack, output, pause, pci, persistence, pollers, prime, privileges, process, requirements, rollup, sampling, schema, snapshot, startup, statsd, syslog, template, temperature, topology, vgpu, watch};
+#[cfg(feature = "network")]
+use gpu_auto_top::{influx, server, tcp, udp};
+use gpu_auto_top::{
+    check_top_exists_local, enumerate_gpus, epel_required, identify_gpu_card, identify_installer, install_top_for_gpu_to, nvidia_driver_version, offline_instructions, try_identify_gpu_card,
+    BackendPreference, GpuType, InstallResult, Installer, SamplerBuilder, DEFAULT_MAX_RETRIES, OS_RELEASE_PATH,
+};
+
+/// How often a command started with `--duration` is checked for having exited.
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
-#[derive(Debug, Clone, Copy)]
-enum GpuType {
-    Nvidia,
-    Amd,
-    Intel,
+#[derive(Debug, PartialEq, Eq)]
+enum Subcommand {
+    Monitor,
+    Topology,
+    Snapshot,
+    FixPersistence,
+    DefaultConfig,
+    Schema,
+    /// Reads `--format msgpack` output from stdin and prints it as JSON.
+    DecodeMsgpack,
+    /// Monitoring with the live web dashboard.
+    Web,
+    /// Installs the vendor tool, or prints the command with `--print-command`, and exits.
+    Install,
+    /// One sample judged against `--warn` and `--crit`, as a Nagios check plugin.
+    Check,
+    /// Follows one process's GPU usage until it exits.
+    WatchPid,
+    /// Collects and shows the samples of remote `--send-to-tcp` clients.
+    Server,
 }
 
 #[derive(Debug)]
-enum PackageManager {
-    Apt,
-    Pacman,
-    Yum,
+struct Args {
+    subcommand: Subcommand,
+    max_retries: u32,
+    /// `--retry-count` and `--retry-delay`: attempts of a failed vendor tool run within a poll.
+    retry: runner::Retry,
+    fields: Vec<output::Field>,
+    /// `--output-fields`: the metrics printed.
+    output_fields: output::FieldSet,
+    format: output::OutputFormat,
+    /// `--layout`: how much of each sample the text output shows.
+    layout: display::layout::Layout,
+    /// `--bell-on`: rings the terminal bell when it starts to hold.
+    bell_on: Option<check::Expression>,
+    /// `--set-title`: GPU status in the terminal window title.
+    set_title: bool,
+    /// `--timestamp-format`; without it only text log files are stamped, in ISO 8601.
+    timestamp_format: Option<output::TimestampFormat>,
+    machine_hostname: bool,
+    labels: metadata::Labels,
+    gpu: Option<u32>,
+    json: bool,
+    pid_filter: Vec<u32>,
+    exit_on_pid_exit: bool,
+    save: Option<String>,
+    diff: Option<String>,
+    all: bool,
+    follow_pid: Option<u32>,
+    log_file: Option<String>,
+    /// `--max-util-history`: the samples per GPU the detail view's sparklines keep.
+    max_util_history: Option<usize>,
+    /// `--persist-history`: where the sparklines' history is saved on exit and loaded from.
+    persist_history: Option<String>,
+    yes: bool,
+    /// `--offline`: skip the mirror check and the install, printing what to install instead.
+    offline: bool,
+    /// `install --print-command`.
+    print_command: bool,
+    /// `check --warn` and `--crit`: threshold expressions, parsed when the check runs so that
+    /// a bad one exits UNKNOWN.
+    check_warn: Option<String>,
+    check_crit: Option<String>,
+    /// `watch-pid <pid>`, or the newest process named `--comm`.
+    watch_pid: Option<u32>,
+    watch_comm: Option<String>,
+    /// `--output csv`: watch-pid prints CSV rows.
+    watch_csv: bool,
+    /// `server --port`, and the port of its Prometheus endpoint.
+    server_port: Option<u16>,
+    prometheus_port: Option<u16>,
+    launch: Option<Vec<String>>,
+    /// `exec [options] -- <command>`: `--launch`, spelled as a subcommand.
+    exec: bool,
+    /// `--max-energy` and `--max-gpu-hours`.
+    budget: budget::Limits,
+    budget_signal: Option<String>,
+    budget_grace: Option<Duration>,
+    config: Option<String>,
+    count: Option<u64>,
+    /// `--duration`: stop after this much wall-clock time, however many ticks that is.
+    duration: Option<Duration>,
+    export_html: Option<String>,
+    alert_temp: Option<f32>,
+    /// `--alert-temp sensor=°C`: thresholds of single `--fields temps` sensors.
+    alert_sensor_temps: Vec<(temperature::Sensor, f32)>,
+    alert_util: Option<f32>,
+    warn_vram: Option<f32>,
+    /// `--alert-severity`: severities replacing the defaults, by alert.
+    alert_severities: Vec<(alert::AlertKind, alert::Severity)>,
+    /// `--critical-exit-code`: the exit code after a critical alert; 0 keeps the normal one.
+    critical_exit_code: i32,
+    notify: bool,
+    interval_jitter: Option<f64>,
+    low_overhead: bool,
+    /// `--backend`, or the vendor's key in the `[backend]` configuration table: the only metrics
+    /// source sampled, without a fallback.
+    backend: Option<&'static str>,
+    /// `--require-gpu`: the GPUs that must be present for monitoring to start.
+    require_gpu: Option<requirements::GpuRequirement>,
+    self_stats: bool,
+    /// `--wake`: sample runtime-suspended GPUs anyway, waking them up.
+    wake: bool,
+    /// `--max-startup-wait`: with `--count 1`, print `n/a` if there is no sample by then.
+    max_startup_wait: Option<Duration>,
+    /// `--grace-period`: how long to let the driver settle before the first poll.
+    grace_period: Duration,
+    no_grace: bool,
+    /// `--allow-multiple`: monitor GPUs another gpuatop already monitors.
+    allow_multiple: bool,
+    /// `--read-only` and `--read-only-allow-writes`, see `capabilities::Capabilities`.
+    read_only: bool,
+    read_only_allow_writes: bool,
+    /// `--force-color` and `--no-color`, see `display::should_use_color`.
+    force_color: bool,
+    no_color: bool,
+    interval: Option<Duration>,
+    /// `--allow-fast-poll`: `--interval` may go below `sampling::MIN_INTERVAL`.
+    allow_fast_poll: bool,
+    display_interval: Option<Duration>,
+    dump_raw: Option<String>,
+    buffer_samples: usize,
+    /// `--rollup`: the length of the aggregate windows.
+    rollup: Option<Duration>,
+    rollup_only: bool,
+    output_socket: Option<String>,
+    output_fifo: Option<String>,
+    output_syslog: Option<syslog::Facility>,
+    syslog_server: Option<String>,
+    send_to: Option<String>,
+    send_to_tcp: Option<String>,
+    tcp_buffer_size: Option<usize>,
+    /// `--export-influx`: the server's URL.
+    #[cfg(feature = "network")]
+    export_influx: Option<reqwest::Url>,
+    #[cfg(feature = "network")]
+    influx_bucket: Option<String>,
+    #[cfg(feature = "network")]
+    influx_org: Option<String>,
+    #[cfg(feature = "network")]
+    influx_token: Option<String>,
+    #[cfg(feature = "network")]
+    influx_flush_interval: Option<Duration>,
+    receive: Option<u16>,
+    debug: bool,
+    quiet: u8,
+    verbose: u8,
+    /// `--version`: print the version, and with `--verbose` the cargo features, then exit.
+    version: bool,
+    /// `-h`/`--help`: print the usage and exit.
+    help: bool,
+    golden_file: Option<String>,
+    golden_tolerance: f32,
+    diff_output: bool,
+    diff_threshold: f32,
+    /// `--idle-threshold`: the utilization up to which a GPU counts as idle.
+    idle_threshold: Option<f32>,
+    /// `--show-efficiency`: Tensor Core TFLOPS per watt, at `--efficiency-precision`.
+    show_efficiency: bool,
+    efficiency_precision: efficiency::Precision,
+    aggregate: bool,
+    /// `--by-user`: a per-user table (a `users` record in JSON) every tick.
+    by_user: bool,
+    exclude_desktop: bool,
+    prometheus_file: Option<String>,
+    statsd: statsd::StatsdOptions,
+    precision: Option<usize>,
+    /// `--format "<template>"`: the text line built from a user template.
+    template: Option<template::Template>,
+    /// `--script <file.lua>`: called every polling cycle, see `script`.
+    #[cfg(feature = "lua")]
+    script: Option<String>,
+    #[cfg(feature = "web")]
+    listen: String,
 }
 
-impl FromStr for PackageManager {
-    type Err = String;
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        subcommand: Subcommand::Monitor,
+        max_retries: DEFAULT_MAX_RETRIES,
+        retry: runner::Retry::default(),
+        fields: Vec::new(),
+        output_fields: output::FieldSet::ALL,
+        format: output::OutputFormat::Text,
+        layout: display::layout::Layout::Normal,
+        bell_on: None,
+        set_title: false,
+        timestamp_format: None,
+        machine_hostname: false,
+        labels: metadata::Labels::default(),
+        gpu: None,
+        json: false,
+        pid_filter: Vec::new(),
+        exit_on_pid_exit: false,
+        save: None,
+        diff: None,
+        all: false,
+        follow_pid: None,
+        log_file: None,
+        max_util_history: None,
+        persist_history: None,
+        yes: false,
+        offline: false,
+        print_command: false,
+        check_warn: None,
+        check_crit: None,
+        watch_pid: None,
+        watch_comm: None,
+        watch_csv: false,
+        server_port: None,
+        prometheus_port: None,
+        launch: None,
+        exec: false,
+        budget: budget::Limits::default(),
+        budget_signal: None,
+        budget_grace: None,
+        config: None,
+        count: None,
+        duration: None,
+        export_html: None,
+        alert_temp: None,
+        alert_sensor_temps: Vec::new(),
+        alert_util: None,
+        warn_vram: None,
+        alert_severities: Vec::new(),
+        critical_exit_code: alert::DEFAULT_CRITICAL_EXIT_CODE,
+        notify: false,
+        interval_jitter: None,
+        low_overhead: false,
+        backend: None,
+        require_gpu: None,
+        self_stats: false,
+        wake: false,
+        max_startup_wait: None,
+        grace_period: Duration::ZERO,
+        no_grace: false,
+        allow_multiple: false,
+        read_only: false,
+        read_only_allow_writes: false,
+        force_color: false,
+        no_color: false,
+        interval: None,
+        allow_fast_poll: false,
+        display_interval: None,
+        dump_raw: None,
+        buffer_samples: sampling::DEFAULT_BUFFER_SAMPLES,
+        rollup: None,
+        rollup_only: false,
+        output_socket: None,
+        output_fifo: None,
+        output_syslog: None,
+        syslog_server: None,
+        send_to: None,
+        send_to_tcp: None,
+        tcp_buffer_size: None,
+        #[cfg(feature = "network")]
+        export_influx: None,
+        #[cfg(feature = "network")]
+        influx_bucket: None,
+        #[cfg(feature = "network")]
+        influx_org: None,
+        #[cfg(feature = "network")]
+        influx_token: None,
+        #[cfg(feature = "network")]
+        influx_flush_interval: None,
+        receive: None,
+        debug: false,
+        quiet: 0,
+        verbose: 0,
+        version: false,
+        help: false,
+        golden_file: None,
+        golden_tolerance: golden::DEFAULT_TOLERANCE,
+        diff_output: false,
+        diff_threshold: 0.0,
+        idle_threshold: None,
+        show_efficiency: false,
+        efficiency_precision: efficiency::Precision::default(),
+        aggregate: false,
+        by_user: false,
+        exclude_desktop: false,
+        prometheus_file: None,
+        statsd: statsd::StatsdOptions::default(),
+        precision: None,
+        template: None,
+        #[cfg(feature = "lua")]
+        script: None,
+        #[cfg(feature = "web")]
+        listen: "127.0.0.1:8080".to_string(),
+    };
+    let mut iter = env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--max-retries" => {
+                let value = iter.next().ok_or("--max-retries requires a value")?;
+                args.max_retries = value.parse().map_err(|_| format!("Invalid --max-retries value: {}", value))?;
+            }
+            "--retry-count" => {
+                let value = iter.next().ok_or("--retry-count requires a value")?;
+                args.retry.max_attempts = value.parse().ok().filter(|attempts| *attempts > 0).ok_or(format!("Invalid --retry-count value: {} (expected at least 1)", value))?;
+            }
+            "--retry-delay" => {
+                let value = iter.next().ok_or("--retry-delay requires a duration")?;
+                args.retry.delay = sampling::parse_duration(&value).map_err(|_| format!("Invalid --retry-delay value: {}", value))?;
+            }
+            "--format" => {
+                let value = iter.next().ok_or("--format requires a value")?;
+                if template::is_template(&value) {
+                    let parsed = template::Template::parse(&value).map_err(|err| format!("Invalid --format template: {}", err.with_caret(&value)))?;
+                    args.template = Some(parsed);
+                    args.format = output::OutputFormat::Text;
+                    continue;
+                }
+                args.template = None;
+                args.format = value.parse()?;
+            }
+            "--layout" => args.layout = iter.next().ok_or("--layout requires compact, normal or verbose")?.parse()?,
+            "--bell-on" => args.bell_on = Some(iter.next().ok_or("--bell-on requires an expression")?.parse()?),
+            "--set-title" => args.set_title = true,
+            "--timestamp-format" => {
+                args.timestamp_format = Some(iter.next().ok_or("--timestamp-format requires iso8601, unix, unix-ms, relative or none")?.parse()?)
+            }
+            "--machine-hostname" => args.machine_hostname = true,
+            "--label" => {
+                let value = iter.next().ok_or("--label requires a value")?;
+                args.labels.try_extend(value.parse()?)?;
+            }
+            "--fields" => {
+                let value = iter.next().ok_or("--fields requires a value")?;
+                args.fields = output::parse_fields(&value)?;
+            }
+            "--output-fields" => args.output_fields = iter.next().ok_or("--output-fields requires a list of fields")?.parse()?,
+            "--gpu" => {
+                let value = iter.next().ok_or("--gpu requires a value")?;
+                args.gpu = Some(value.parse().map_err(|_| format!("Invalid --gpu value: {}", value))?);
+            }
+            "--json" => args.json = true,
+            "--pid-filter" => {
+                let value = iter.next().ok_or("--pid-filter requires a value")?;
+                args.pid_filter = process::parse_pid_list(&value)?;
+            }
+            "--exit-on-pid-exit" => args.exit_on_pid_exit = true,
+            "--save" => args.save = Some(iter.next().ok_or("--save requires a path")?),
+            "--diff" => args.diff = Some(iter.next().ok_or("--diff requires a path")?),
+            "--all" => args.all = true,
+            "--follow-pid" => {
+                let value = iter.next().ok_or("--follow-pid requires a value")?;
+                args.follow_pid = Some(value.parse().map_err(|_| format!("Invalid PID: {}", value))?);
+            }
+            "--log-file" => args.log_file = Some(iter.next().ok_or("--log-file requires a path")?),
+            "--max-util-history" => {
+                let value = iter.next().ok_or("--max-util-history requires a number of samples")?;
+                args.max_util_history = Some(value.parse().ok().filter(|samples| *samples >= 2).ok_or(format!("Invalid --max-util-history value: {} (expected at least 2)", value))?);
+            }
+            "--persist-history" => args.persist_history = Some(iter.next().ok_or("--persist-history requires a path")?),
+            "--yes" | "-y" => args.yes = true,
+            "--offline" => args.offline = true,
+            "--print-command" => args.print_command = true,
+            "--warn" => args.check_warn = Some(iter.next().ok_or("--warn requires an expression")?),
+            "--crit" => args.check_crit = Some(iter.next().ok_or("--crit requires an expression")?),
+            "--count" => {
+                let value = iter.next().ok_or("--count requires a value")?;
+                args.count = Some(value.parse().map_err(|_| format!("Invalid --count value: {}", value))?);
+            }
+            "--duration" => {
+                let value = iter.next().ok_or("--duration requires a value")?;
+                args.duration = Some(sampling::parse_duration(&value).map_err(|_| format!("Invalid --duration value: {} (expected e.g. 30s, 10m or 2h)", value))?);
+            }
+            "--export-html" => args.export_html = Some(iter.next().ok_or("--export-html requires a path")?),
+            "--alert-temp" => {
+                match alert::parse_temperature_threshold(&iter.next().ok_or("--alert-temp requires a value")?)? {
+                    (Some(sensor), threshold) => args.alert_sensor_temps.push((sensor, threshold)),
+                    (None, threshold) => args.alert_temp = Some(threshold),
+                }
+            }
+            "--alert-util" => {
+                let value = iter.next().ok_or("--alert-util requires a value")?;
+                args.alert_util = Some(value.parse().map_err(|_| format!("Invalid --alert-util value: {}", value))?);
+            }
+            "--warn-vram" => args.warn_vram = Some(alert::parse_percent(&iter.next().ok_or("--warn-vram requires a percentage")?)?),
+            "--alert-severity" => args.alert_severities.extend(alert::parse_severities(&iter.next().ok_or("--alert-severity requires alert=severity pairs")?)?),
+            "--critical-exit-code" => {
+                let value = iter.next().ok_or("--critical-exit-code requires a value")?;
+                args.critical_exit_code = value.parse().ok().filter(|code| (0..=255).contains(code)).ok_or(format!("Invalid --critical-exit-code value: {}", value))?;
+            }
+            "--notify" => args.notify = true,
+            "--low-overhead" => args.low_overhead = true,
+            "--require-gpu" => args.require_gpu = Some(iter.next().ok_or("--require-gpu requires a vendor")?.parse()?),
+            "--backend" => args.backend = Some(backend::parse_source(&iter.next().ok_or("--backend requires a metrics source")?)?),
+            "--self-stats" => args.self_stats = true,
+            "--wake" => args.wake = true,
+            "--max-startup-wait" => {
+                let value = iter.next().ok_or("--max-startup-wait requires a duration")?;
+                args.max_startup_wait = Some(sampling::parse_duration(&value).map_err(|_| format!("Invalid --max-startup-wait value: {}", value))?);
+            }
+            "--grace-period" => {
+                let value = iter.next().ok_or("--grace-period requires a number of seconds")?;
+                args.grace_period = match value.as_str() {
+                    "0" => Duration::ZERO,
+                    _ => sampling::parse_duration(&value).map_err(|_| format!("Invalid --grace-period value: {}", value))?,
+                };
+            }
+            "--no-grace" => args.no_grace = true,
+            "--allow-multiple" => args.allow_multiple = true,
+            "--read-only" => args.read_only = true,
+            "--read-only-allow-writes" => args.read_only_allow_writes = true,
+            "--force-color" => args.force_color = true,
+            "--no-color" => args.no_color = true,
+            "--interval" => args.interval = Some(sampling::parse_duration(&iter.next().ok_or("--interval requires a duration")?)?),
+            "--allow-fast-poll" => args.allow_fast_poll = true,
+            "--display-interval" => {
+                args.display_interval = Some(sampling::parse_duration(&iter.next().ok_or("--display-interval requires a duration")?)?)
+            }
+            "--output-socket" => args.output_socket = Some(iter.next().ok_or("--output-socket requires a path")?),
+            "--output-fifo" => args.output_fifo = Some(iter.next().ok_or("--output-fifo requires a path")?),
+            "--output" => {
+                let value = iter.next().ok_or("--output requires a sink")?;
+                args.output_syslog = match value.split_once(':') {
+                    None if value == "csv" => {
+                        args.watch_csv = true;
+                        None
+                    }
+                    None if value == "syslog" => Some(syslog::Facility::default()),
+                    Some(("syslog", facility)) => Some(facility.parse()?),
+                    _ => return Err(format!("Unknown output: {}", value)),
+                };
+            }
+            "--syslog-server" => args.syslog_server = Some(iter.next().ok_or("--syslog-server requires an address")?),
+            #[cfg(feature = "network")]
+            "--send-to" => args.send_to = Some(udp::parse_target(&iter.next().ok_or("--send-to requires host:port")?)?),
+            #[cfg(feature = "network")]
+            "--send-to-tcp" => args.send_to_tcp = Some(tcp::parse_target(&iter.next().ok_or("--send-to-tcp requires host:port")?)?),
+            #[cfg(feature = "network")]
+            "--tcp-buffer-size" => args.tcp_buffer_size = Some(tcp::parse_buffer_size(&iter.next().ok_or("--tcp-buffer-size requires a number of records")?)?),
+            #[cfg(feature = "network")]
+            "--export-influx" => args.export_influx = Some(influx::parse_url(&iter.next().ok_or("--export-influx requires a URL")?)?),
+            #[cfg(feature = "network")]
+            "--influx-bucket" => args.influx_bucket = Some(iter.next().ok_or("--influx-bucket requires a bucket name")?),
+            #[cfg(feature = "network")]
+            "--influx-org" => args.influx_org = Some(iter.next().ok_or("--influx-org requires an organization")?),
+            #[cfg(feature = "network")]
+            "--influx-token" => args.influx_token = Some(iter.next().ok_or("--influx-token requires a token")?),
+            #[cfg(feature = "network")]
+            "--influx-flush-interval" => {
+                let value = iter.next().ok_or("--influx-flush-interval requires a number of seconds")?;
+                let seconds = value.parse::<u64>().ok().filter(|seconds| *seconds > 0).ok_or_else(|| format!("Invalid --influx-flush-interval value: {}", value))?;
+                args.influx_flush_interval = Some(Duration::from_secs(seconds));
+            }
+            #[cfg(feature = "network")]
+            "--receive" => args.receive = Some(udp::parse_port(&iter.next().ok_or("--receive requires a port")?)?),
+            "--debug" => args.debug = true,
+            "-q" | "--quiet" => args.quiet = args.quiet.saturating_add(1),
+            "-v" | "--verbose" => args.verbose = args.verbose.saturating_add(1),
+            "-vv" => args.verbose = args.verbose.saturating_add(2),
+            "-V" | "--version" => args.version = true,
+            // Nothing after `--help` is parsed or checked: it answers even a broken command line.
+            "-h" | "--help" => {
+                args.help = true;
+                return Ok(args);
+            }
+            "--golden-file" => args.golden_file = Some(iter.next().ok_or("--golden-file requires a path")?),
+            "--golden-tolerance" => {
+                let value = iter.next().ok_or("--golden-tolerance requires a value")?;
+                args.golden_tolerance = value
+                    .parse()
+                    .ok()
+                    .filter(|tolerance: &f32| *tolerance >= 0.0)
+                    .ok_or(format!("Invalid --golden-tolerance value: {}", value))?;
+            }
+            "--diff-output" => args.diff_output = true,
+            "--aggregate" => args.aggregate = true,
+            "--by-user" => args.by_user = true,
+            "--exclude-desktop" => args.exclude_desktop = true,
+            "--prometheus-file" => args.prometheus_file = Some(iter.next().ok_or("--prometheus-file requires a path")?),
+            "--statsd-prefix" => args.statsd.prefix = statsd::parse_prefix(&iter.next().ok_or("--statsd-prefix requires a prefix")?)?,
+            "--precision" => args.precision = Some(output::parse_precision(&iter.next().ok_or("--precision requires a number of decimals")?)?),
+            "--statsd-tags" => args.statsd.tags = iter.next().ok_or("--statsd-tags requires key=value pairs")?.parse()?,
+            "--diff-threshold" => {
+                let value = iter.next().ok_or("--diff-threshold requires a percentage")?;
+                args.diff_threshold = value
+                    .parse()
+                    .ok()
+                    .filter(|threshold: &f32| *threshold >= 0.0)
+                    .ok_or(format!("Invalid --diff-threshold value: {}", value))?;
+            }
+            "--idle-threshold" => {
+                let value = iter.next().ok_or("--idle-threshold requires a percentage")?;
+                args.idle_threshold = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|threshold: &f32| (0.0..100.0).contains(threshold))
+                        .ok_or(format!("Invalid --idle-threshold value: {}", value))?,
+                );
+            }
+            "--show-efficiency" => args.show_efficiency = true,
+            "--efficiency-precision" => args.efficiency_precision = iter.next().ok_or("--efficiency-precision requires fp16, bf16 or int8")?.parse()?,
+            "--dump-raw" => args.dump_raw = Some(iter.next().ok_or("--dump-raw requires a path")?),
+            "--buffer-samples" => {
+                let value = iter.next().ok_or("--buffer-samples requires a value")?;
+                args.buffer_samples = value.parse().ok().filter(|samples| *samples > 0).ok_or(format!("Invalid --buffer-samples value: {}", value))?;
+            }
+            "--rollup" => args.rollup = Some(rollup::parse_window(&iter.next().ok_or("--rollup requires a window, e.g. 1m")?)?),
+            "--rollup-only" => args.rollup_only = true,
+            "--interval-jitter" => {
+                args.interval_jitter = Some(jitter::parse_fraction(&iter.next().ok_or("--interval-jitter requires a value")?)?)
+            }
+            #[cfg(feature = "lua")]
+            "--script" => args.script = Some(iter.next().ok_or("--script requires a Lua file")?),
+            "--config" => args.config = Some(iter.next().ok_or("--config requires a path")?),
+            "--decode-msgpack" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::DecodeMsgpack,
+            "default-config" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::DefaultConfig,
+            "--schema" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Schema,
+            "--launch" => args.launch = Some(iter.by_ref().collect()),
+            "exec" if args.subcommand == Subcommand::Monitor && !args.exec => args.exec = true,
+            "--" if args.exec => args.launch = Some(iter.by_ref().collect()),
+            "--max-energy" => args.budget.max_energy_wh = Some(budget::parse_energy(&iter.next().ok_or("--max-energy requires a value, e.g. 500Wh")?)?),
+            "--max-gpu-hours" => args.budget.max_gpu_hours = Some(budget::parse_gpu_hours(&iter.next().ok_or("--max-gpu-hours requires a value")?)?),
+            "--budget-signal" => args.budget_signal = Some(budget::parse_signal(&iter.next().ok_or("--budget-signal requires a signal name")?)?),
+            "--budget-grace" => args.budget_grace = Some(sampling::parse_duration(&iter.next().ok_or("--budget-grace requires a duration")?)?),
+            "fix-persistence" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::FixPersistence,
+            "topology" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Topology,
+            "snapshot" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Snapshot,
+            "web" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Web,
+            "install" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Install,
+            "check" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Check,
+            "watch-pid" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::WatchPid,
+            #[cfg(feature = "network")]
+            "server" if args.subcommand == Subcommand::Monitor => args.subcommand = Subcommand::Server,
+            #[cfg(feature = "network")]
+            "--port" => args.server_port = Some(server::parse_port("--port", &iter.next().ok_or("--port requires a port")?)?),
+            #[cfg(feature = "network")]
+            "--prometheus-port" => args.prometheus_port = Some(server::parse_port("--prometheus-port", &iter.next().ok_or("--prometheus-port requires a port")?)?),
+            "--comm" => args.watch_comm = Some(iter.next().ok_or("--comm requires a process name")?),
+            pid if args.subcommand == Subcommand::WatchPid && args.watch_pid.is_none() && !pid.starts_with('-') => {
+                args.watch_pid = Some(pid.parse().map_err(|_| format!("Invalid PID: {}", pid))?)
+            }
+            #[cfg(feature = "web")]
+            "--listen" => args.listen = iter.next().ok_or("--listen requires an address")?,
+            #[cfg(not(feature = "network"))]
+            "server" | "--port" | "--prometheus-port" | "--send-to" | "--send-to-tcp" | "--tcp-buffer-size" | "--receive" | "--export-influx" | "--influx-bucket" | "--influx-org" | "--influx-token"
+            | "--influx-flush-interval" => return Err(format!("{} requires a gpuatop built with the network feature", arg)),
+            #[cfg(not(feature = "lua"))]
+            "--script" => return Err("--script requires a gpuatop built with the lua feature".to_string()),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if args.no_grace {
+        args.grace_period = Duration::ZERO;
+    }
+    if args.print_command && args.subcommand != Subcommand::Install {
+        return Err("--print-command requires the install subcommand".to_string());
+    }
+    if (args.check_warn.is_some() || args.check_crit.is_some()) && args.subcommand != Subcommand::Check {
+        return Err("--warn and --crit require the check subcommand".to_string());
+    }
+    if args.subcommand == Subcommand::WatchPid && args.watch_pid.is_some() == args.watch_comm.is_some() {
+        return Err("watch-pid requires either a PID or --comm <name>".to_string());
+    }
+    if (args.watch_comm.is_some() || args.watch_csv) && args.subcommand != Subcommand::WatchPid {
+        return Err("--comm and --output csv require the watch-pid subcommand".to_string());
+    }
+    if args.subcommand == Subcommand::Server && args.server_port.is_none() {
+        return Err("server requires --port <port> to listen on".to_string());
+    }
+    if (args.server_port.is_some() || args.prometheus_port.is_some()) && args.subcommand != Subcommand::Server {
+        return Err("--port and --prometheus-port require the server subcommand".to_string());
+    }
+    if args.receive.is_some() && (args.send_to.is_some() || args.send_to_tcp.is_some() || args.subcommand != Subcommand::Monitor) {
+        return Err("--receive only prints what other gpuatop instances send and takes no subcommand, --send-to or --send-to-tcp".to_string());
+    }
+    if args.tcp_buffer_size.is_some() && args.send_to_tcp.is_none() {
+        return Err("--tcp-buffer-size requires --send-to-tcp".to_string());
+    }
+    #[cfg(feature = "network")]
+    {
+        let influx_options = args.influx_bucket.is_some() || args.influx_org.is_some() || args.influx_token.is_some() || args.influx_flush_interval.is_some();
+        if influx_options && args.export_influx.is_none() {
+            return Err("--influx-bucket, --influx-org, --influx-token and --influx-flush-interval require --export-influx".to_string());
+        }
+        if args.export_influx.is_some() && (args.influx_bucket.is_none() || args.influx_org.is_none()) {
+            return Err("--export-influx requires --influx-bucket and --influx-org".to_string());
+        }
+        if args.export_influx.is_some() && args.influx_token.is_none() {
+            args.influx_token = std::env::var(influx::TOKEN_VARIABLE).ok().filter(|token| !token.is_empty());
+        }
+    }
+
+    if args.duration.is_some() {
+        if args.count.is_some() {
+            return Err("--duration and --count cannot be combined".to_string());
+        }
+        if args.subcommand == Subcommand::Server {
+            return Err("--duration is not supported by server, which runs until it is stopped".to_string());
+        }
+        if args.format == output::OutputFormat::Prometheus {
+            return Err("--format prometheus writes a single snapshot and cannot be combined with --duration".to_string());
+        }
+    }
+
+    if args.max_startup_wait.is_some() {
+        if args.count != Some(1) {
+            return Err("--max-startup-wait requires --count 1".to_string());
+        }
+        if args.format != output::OutputFormat::Text {
+            return Err("--max-startup-wait requires the text format".to_string());
+        }
+    }
+
+    if args.format == output::OutputFormat::Prometheus {
+        if args.count.is_some_and(|count| count != 1) {
+            return Err("--format prometheus writes a single snapshot and cannot be combined with --count".to_string());
+        }
+        args.count = Some(1);
+    } else if args.prometheus_file.is_some() {
+        return Err("--prometheus-file requires --format prometheus".to_string());
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "apt" => PackageManager::Apt,
-            "pacman" => PackageManager::Pacman,
-            "yum" => PackageManager::Yum,
-            _ => return Err("Package manager not found".to_string()),
-        })
+    if args.precision.is_some() && args.format != output::OutputFormat::Text {
+        return Err("--precision requires the text format; the other formats keep every digit".to_string());
+    }
+    if args.format != output::OutputFormat::Statsd && args.statsd != statsd::StatsdOptions::default() {
+        return Err("--statsd-prefix and --statsd-tags require --format statsd".to_string());
+    }
+
+    if args.by_user && !matches!(args.format, output::OutputFormat::Text | output::OutputFormat::Ndjson | output::OutputFormat::Json) {
+        return Err("--by-user supports the text, ndjson and json formats".to_string());
+    }
+    if args.exclude_desktop && !args.by_user {
+        return Err("--exclude-desktop requires --by-user".to_string());
+    }
+
+    if args.format == output::OutputFormat::Table && args.diff_output {
+        return Err("--format table shows every GPU and cannot be combined with --diff-output".to_string());
+    }
+
+    if args.timestamp_format.is_some() && matches!(args.format, output::OutputFormat::Influx | output::OutputFormat::Prometheus | output::OutputFormat::Statsd | output::OutputFormat::Table) {
+        return Err("--timestamp-format supports the text, ndjson, json and msgpack formats".to_string());
+    }
+
+    if args.layout != display::layout::Layout::Normal {
+        if args.format != output::OutputFormat::Text || args.template.is_some() {
+            return Err("--layout requires the text format".to_string());
+        }
+        if args.aggregate {
+            return Err("--aggregate cannot be combined with --layout".to_string());
+        }
+        if args.layout == display::layout::Layout::Compact && args.by_user {
+            return Err("--by-user cannot be combined with --layout compact".to_string());
+        }
+    }
+
+    if args.exec && args.launch.as_ref().is_none_or(|command| command.is_empty()) {
+        return Err("exec requires a command: gpuatop exec [options] -- <command>".to_string());
+    }
+    if (args.budget_signal.is_some() || args.budget_grace.is_some()) && args.launch.is_none() {
+        return Err("--budget-signal and --budget-grace require a command to stop, from exec or --launch".to_string());
+    }
+    if (args.budget_signal.is_some() || args.budget_grace.is_some()) && args.budget.is_empty() {
+        return Err("--budget-signal and --budget-grace require --max-energy or --max-gpu-hours".to_string());
+    }
+
+    if args.aggregate {
+        if !matches!(args.format, output::OutputFormat::Text | output::OutputFormat::Ndjson | output::OutputFormat::Json) {
+            return Err("--aggregate supports the text, ndjson and json formats".to_string());
+        }
+        if args.diff_output {
+            return Err("--aggregate cannot be combined with --diff-output".to_string());
+        }
+    }
+
+    if args.rollup_only && args.rollup.is_none() {
+        return Err("--rollup-only requires --rollup".to_string());
+    }
+    if args.rollup.is_some() && !matches!(args.format, output::OutputFormat::Text | output::OutputFormat::Ndjson | output::OutputFormat::Json) {
+        return Err("--rollup supports the text, ndjson and json formats".to_string());
+    }
+
+    Ok(args)
+}
+
+/// Checks every option that changes the system against `--read-only`, before anything runs.
+fn check_capabilities(args: &Args, capabilities: &capabilities::Capabilities) -> Result<(), String> {
+    if args.subcommand == Subcommand::FixPersistence {
+        capabilities.change_device("fix-persistence")?;
+    }
+    if args.wake {
+        capabilities.change_device("--wake")?;
+    }
+
+    let writes = [
+        ("--save", &args.save),
+        ("--log-file", &args.log_file),
+        ("--persist-history", &args.persist_history),
+        ("--dump-raw", &args.dump_raw),
+        ("--export-html", &args.export_html),
+        ("--prometheus-file", &args.prometheus_file),
+        ("--output-socket", &args.output_socket),
+        ("--output-fifo", &args.output_fifo),
+    ];
+    for (option, path) in writes {
+        if let Some(path) = path {
+            capabilities.write(option, Path::new(path))?;
+        }
     }
+    Ok(())
 }
 
-const PACKAGE_MANAGERS: [&str; 3] = ["apt", "pacman", "yum"];
+/// Sleeps through `--grace-period`, counting down the seconds left. On a terminal the countdown
+/// rewrites one line; elsewhere only the first line is printed.
+fn wait_for_driver(console: &output::Console, period: Duration) {
+    if !console.shows_info() {
+        thread::sleep(period);
+        return;
+    }
 
-fn identify_package_manager() -> PackageManager {
-    for package_manager in PACKAGE_MANAGERS {
-        let output = Command::new("which")
-            .arg(package_manager)
-            .output()
-            .expect("Failed to execute command");
+    let countdown = |remaining: Duration| format!("Waiting {}s for GPU driver to initialize...", remaining.as_secs_f64().ceil());
+    if !console.is_terminal() {
+        console.info(&countdown(period));
+        thread::sleep(period);
+        return;
+    }
 
-        if output.status.success() {
-            return package_manager.parse().expect("Failed to parse package manager");
+    let deadline = Instant::now() + period;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
         }
+        // The trailing space clears the last character when the count loses a digit.
+        console.prompt(&format!("\r{} ", countdown(remaining)));
+        // Wake on the next whole second of the countdown.
+        let fraction = Duration::from_secs_f64(remaining.as_secs_f64().fract());
+        thread::sleep(if fraction.is_zero() { Duration::from_secs(1) } else { fraction }.min(remaining));
     }
+    console.emit("");
+}
+
+/// Asks the user to confirm a system change. `--yes` answers for them; without a terminal to
+/// ask on, the change is declined.
+fn confirm(console: &output::Console, prompt: &str, assume_yes: bool) -> bool {
+    if assume_yes {
+        return true;
+    }
+
+    if !io::stdin().is_terminal() {
+        console.error(&format!("{} Not a terminal, re-run with --yes to proceed.", prompt));
+        return false;
+    }
+
+    console.prompt(&format!("{} [y/N] ", prompt));
 
-    panic!("Package manager not found");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
 }
 
-fn identify_gpu_card() -> GpuType {
-    let output = Command::new("lspci")
-        .arg("-v")
-        .output()
-        .expect("Failed to execute command");
+fn fix_persistence(assume_yes: bool) -> i32 {
+    if process::effective_uid() != Some(0) {
+        eprintln!("Error: Enabling persistence mode requires root, re-run with sudo");
+        return 1;
+    }
+
+    let disabled: Vec<persistence::GpuModes> = persistence::query_modes(&RealRunner).into_iter().filter(|modes| !modes.persistence_enabled()).collect();
+    if disabled.is_empty() {
+        println!("Persistence mode is already enabled on all GPUs");
+        return 0;
+    }
 
-    let output = str::from_utf8(&output.stdout).expect("Not UTF-8");
+    let indices: Vec<String> = disabled.iter().map(|modes| modes.index.to_string()).collect();
+    if !confirm(&output::Console::new(output::OutputFormat::Text, 0), &format!("Enable persistence mode on GPU {}?", indices.join(", ")), assume_yes) {
+        return 1;
+    }
 
-    if output.contains("NVIDIA") {
-        GpuType::Nvidia
-    } else if output.contains("AMD") {
-        GpuType::Amd
-    } else if output.contains("Intel") {
-        GpuType::Intel
-    } else {
-        panic!("GPU not found");
+    match persistence::enable_persistence() {
+        Ok(true) => {
+            println!("Persistence mode enabled");
+            0
+        }
+        Ok(false) => {
+            eprintln!("Error: nvidia-smi -pm 1 failed");
+            1
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            1
+        }
     }
 }
 
-fn check_top_exists_local(gpu_type: GpuType) -> io::Result<ExitStatus>  {
-    let cmd = match gpu_type {
-        GpuType::Nvidia => "nvidia-smi",
-        GpuType::Amd => "radeontop",
-        GpuType::Intel => "intel_gpu_top",
-    };
+/// Prints every frame on stdin as pretty JSON. Returns the exit code.
+fn decode_msgpack() -> i32 {
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+
+    loop {
+        let value = match msgpack::read_frame(&mut stdin) {
+            Ok(Some(frame)) => msgpack::decode(&frame),
+            Ok(None) => return 0,
+            Err(err) => Err(err.to_string()),
+        };
 
-    Command::new("which").arg(cmd).spawn()?.wait()
+        match value {
+            Ok(value) => match writeln!(stdout, "{}", value.to_json_pretty()).and_then(|_| stdout.flush()) {
+                Ok(()) => {}
+                // The reader has what it wanted, as with `| head`.
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return 0,
+                Err(err) => {
+                    eprintln!("Error: Failed to write the decoded records: {}", err);
+                    return 1;
+                }
+            },
+            Err(err) => {
+                eprintln!("Error: Invalid MessagePack input: {}", err);
+                return 1;
+            }
+        }
+    }
 }
 
-fn install_package_for_gpu(package_manager: PackageManager, package_name: &str) -> io::Result<Output>{
-    let package_manager_command = match package_manager {
-        PackageManager::Apt => "apt",
-        PackageManager::Pacman => "pacman",
-        PackageManager::Yum => "yum",
+/// `gpuatop check`: prints the status line and returns the plugin exit code.
+fn run_check(args: &Args) -> i32 {
+    let parse = |expression: &Option<String>| expression.as_deref().map_or(Ok(check::Expression::default()), str::parse);
+    let thresholds = match (parse(&args.check_warn), parse(&args.check_crit)) {
+        (Ok(warn), Ok(crit)) => check::Thresholds { warn, crit },
+        (Err(err), _) | (_, Err(err)) => {
+            println!("GPU {} - {}", check::Status::Unknown, err);
+            return check::Status::Unknown.exit_code();
+        }
+    };
+
+    let backend = if args.low_overhead { BackendPreference::LowOverhead } else { BackendPreference::PerSample };
+    let builder = SamplerBuilder::new().backend(backend);
+    let builder = match args.gpu {
+        Some(gpu) => builder.devices([gpu]),
+        None => builder,
+    };
+    let samples: Vec<_> = match builder.build() {
+        Ok(mut sampler) => sampler.sample().into_iter().map(|sample| sample.map_err(|err| err.to_string())).collect(),
+        Err(err) => vec![Err(err.to_string())],
     };
 
-    let package_manager_install_command = match package_manager {
-        PackageManager::Apt => "install",
-        PackageManager::Pacman => "-S",
-        PackageManager::Yum => "install",
+    let (status, line) = check::evaluate(&samples, &thresholds);
+    println!("{}", line);
+    status.exit_code()
+}
+
+fn run_watch_pid(args: &Args, console: &output::Console) {
+    let target = match (args.watch_pid, &args.watch_comm) {
+        (Some(pid), _) => watch::Target::new(pid),
+        (None, Some(name)) => watch::Target::newest_named(name),
+        (None, None) => unreachable!("checked in parse_args"),
+    };
+    let target = match target {
+        Ok(target) => target,
+        Err(err) => {
+            console.error(&format!("Error: {}", err));
+            std::process::exit(1);
+        }
     };
 
-    let package_manager_install_without_confirm_command = match package_manager {
-        PackageManager::Apt => "-y",
-        PackageManager::Pacman => "--noconfirm",
-        PackageManager::Yum => "-y",
+    let runner = RealRunner;
+    let Some(gpu_type) = try_identify_gpu_card(&runner) else {
+        eprintln!("Error: GPU not found");
+        std::process::exit(1);
     };
+    let interval = args.interval.unwrap_or(Duration::from_secs(1));
+    let mut reader = watch::UsageReader::new(&gpu_type, target.pid);
+    let mut summary = watch::Summary::default();
+    let started = Instant::now();
+    let mut last_sample = started;
 
-    Command::new(package_manager_command)
-        .args([package_manager_install_command, package_manager_install_without_confirm_command, package_name])
-        .output()
+    console.info(&format!("Watching PID {} ({}) until it exits", target.pid, target.name));
+    if args.watch_csv {
+        println!("{}", watch::CSV_HEADER);
+    }
+    // The process keeps being watched while it is not on the GPU: it may start using it later.
+    loop {
+        thread::sleep(interval);
+        if !target.is_running() {
+            break;
+        }
+        let usage = reader.read(&runner, target.pid);
+        let now = Instant::now();
+        summary.record(&usage, now - last_sample);
+        last_sample = now;
+
+        if args.watch_csv {
+            println!("{}", watch::format_csv(&target, &usage, now - started));
+        } else {
+            println!("{}", watch::format_line(&target, &usage));
+        }
+    }
+
+    // In CSV mode the summary goes to stderr, keeping stdout one table.
+    for line in summary.format(&target, started.elapsed()) {
+        if args.watch_csv {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
 }
 
-fn install_top_for_gpu_to(gpu_type: GpuType, package_manager: PackageManager) -> io::Result<Output>{
-    match gpu_type {
-        GpuType::Nvidia => install_package_for_gpu( package_manager, "nvidia-smi"),
-        GpuType::Amd => install_package_for_gpu( package_manager, "radeontop"),
-        GpuType::Intel => install_package_for_gpu( package_manager, "intel_gpu_top"),
+#[cfg(feature = "network")]
+fn run_server(args: &Args, console: &output::Console, output_context: &output::OutputContext) -> io::Result<()> {
+    let port = args.server_port.expect("checked in parse_args");
+    let fleet = server::SharedFleet::default();
+    server::accept_clients(TcpListener::bind(("0.0.0.0", port))?, Arc::clone(&fleet));
+    console.info(&format!("Waiting for gpuatop --send-to-tcp clients on port {}", port));
+    if let Some(prometheus_port) = args.prometheus_port {
+        server::serve_metrics(TcpListener::bind(("0.0.0.0", prometheus_port))?, Arc::clone(&fleet), output_context.clone());
+        console.info(&format!("Prometheus metrics: http://0.0.0.0:{}/metrics", prometheus_port));
     }
+
+    let interval = args.interval.unwrap_or(Duration::from_secs(1));
+    for _ in 0..args.count.unwrap_or(u64::MAX) {
+        thread::sleep(interval);
+        let now = Instant::now();
+        let lines = {
+            let mut fleet = server::lock(&fleet);
+            fleet.expire(now);
+            fleet.format(now, output_context.precision)
+        };
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// The `--help` text. The `--output-fields` names come from [`output::FieldSet::NAMES`], the
+/// table the flag is parsed with.
+fn usage() -> String {
+    let output_fields: String = output::FieldSet::NAMES.iter().map(|(name, _, help)| format!("      {:<10} {}\n", name, help)).collect();
+    format!(
+        "\
+gpuatop {version}
+Monitors GPU usage in real time.
+
+Usage: gpuatop [options]
+       gpuatop <subcommand> [options]
+
+Subcommands:
+  snapshot                 Every detail of every GPU (--save, --diff, --all)
+  topology                 The NVLink/PCIe topology matrix
+  check                    One sample judged against --warn and --crit, as a Nagios plugin
+  watch-pid <pid>          One process's GPU usage until it exits (or --comm <name>)
+  exec [options] -- <cmd>  Runs a command and monitors until it exits
+  install                  Installs the vendor tool (--print-command, --yes, --offline)
+  fix-persistence          Enables NVIDIA persistence mode
+  default-config           Prints the default configuration file
+  web                      Monitoring with a live dashboard (--listen, web feature)
+  server                   Collects --send-to-tcp clients (--port, network feature)
+
+Sampling:
+  --interval <duration>        Time between samples: 500ms, 2s, 1m (1s by default)
+  --allow-fast-poll            Allows --interval below {min_interval}ms
+  --display-interval <dur>     Prints one aggregate per window of --interval samples
+
+  Every NVIDIA poll runs nvidia-smi, and the driver answers it at the expense of the work on
+  the GPU: polling faster slows down what is being monitored, and each poll also costs a
+  process start on the CPU. An --interval below {min_interval}ms is raised to {min_interval}ms with a warning
+  unless --allow-fast-poll is given. With an --interval shorter than --display-interval
+  (high-frequency mode), samples come from the cheapest source available, as with
+  --low-overhead, and only the window's mean and peak (util) are printed.
+
+  --interval-jitter <fraction> Shifts each poll by a random share of the interval
+  --count <n>                  Stops after n samples
+  --duration <duration>        Stops after that much time
+  --gpu <index>                Monitors one GPU
+  --backend <source>           Forces a metrics source
+  --low-overhead               Prefers the cheapest metrics source
+  --fields <field,...>         Collects optional metrics: nvlink, split, bar1, vis_vram, temps.
+                               bar1 (NVIDIA BAR1 and reserved memory) and vis_vram (amdgpu
+                               CPU-visible VRAM) tell \"VRAM full\" apart from \"visible aperture
+                               full\": without resizable BAR the CPU maps only a 256 MiB window
+                               of VRAM, and allocations fail once it is full although VRAM
+                               looks free. Enable them when that happens.
+  --max-retries <n>            Failed polls before a GPU is marked lost
+  --retry-count <n>            Attempts per poll
+  --retry-delay <duration>     Delay between attempts
+  --wake                       Wakes runtime-suspended GPUs
+  --max-startup-wait <dur>     Waits for the driver to come up
+  --require-gpu <vendor>       Fails unless such a GPU is present
+  --config <path>              Reads options from a configuration file
+
+Output:
+  --format <format>            text, table, json, ndjson, influx, msgpack, prometheus, statsd,
+                               or a template such as \"{{index}}: {{util}}%\"
+  --layout <layout>            compact, normal or verbose
+  --output-fields <field,...>  Prints only these metrics, or all (the default):
+{output_fields}  --precision <0-6>            Decimals of utilization in text
+  --timestamp-format <format>  iso8601, unix, unix-ms, relative or none
+  --label <key=value>          Adds a label to every record
+  --machine-hostname           Adds the hostname to every record
+  --aggregate                  Prints every GPU of a tick as one table
+  --by-user                    Sums process usage by user
+  --exclude-desktop            Leaves out compositors and display servers
+  --show-efficiency            Adds Tensor Core throughput per watt
+  --efficiency-precision <p>   fp16, bf16 or int8
+  --idle-threshold <percent>   Tracks how long each GPU has been idle
+  --diff-output                Prints only samples that changed (--diff-threshold <percent>)
+  --rollup <window>            Prints rollups over the window (--rollup-only)
+  --json                       topology and snapshot print JSON
+  --pid-filter <pid,...>       Shows only these processes
+  --max-util-history <n>       Samples kept for the sparklines
+  --persist-history <path>     Keeps the sparklines across restarts
+  --export-html <path>         Writes an HTML report on exit
+  --log-file <path>            Appends the output to a file
+  --dump-raw <path>            Writes the raw samples as CSV
+  --output <sink>              csv, syslog or syslog:<facility>
+  --syslog-server <address>    Sends syslog to a remote server
+  --output-socket <path>       Serves records on a Unix socket
+  --output-fifo <path>         Writes records to a named pipe
+  --prometheus-file <path>     Writes --format prometheus to a file
+  --statsd-prefix <prefix>     Prefix of --format statsd metrics
+  --statsd-tags <key=value,..> Tags of --format statsd metrics
+  --golden-file <path>         Compares samples with a golden file (--golden-tolerance)
+  --force-color, --no-color    Colors even when piped, or never
+  --set-title                  Shows utilization in the terminal title
+  --bell-on <expression>       Rings the bell when the expression holds
+  --self-stats                 Reports gpuatop's own CPU time in the summary and Prometheus output
+  -q, --quiet                  No banner or summary; twice, no warnings
+  -v, --verbose                More detail; -vv for timing statistics
+
+Alerts and budgets:
+  --alert-temp <[sensor=]celsius>  --alert-util <percent>  --warn-vram <percent>
+  --alert-severity <alert=severity,...>  --critical-exit-code <code>  --notify
+  --max-energy <Wh>  --max-gpu-hours <hours>  --budget-signal <signal>  --budget-grace <dur>
+  --launch <cmd...>  --exit-on-pid-exit  --follow-pid <pid>  --script <file.lua> (lua feature)
+
+Network (network feature):
+  --send-to <host:port>  --send-to-tcp <host:port>  --tcp-buffer-size <n>  --receive <port>
+  --export-influx <url>  --influx-bucket <bucket>  --influx-org <org>  --influx-token <token>
+  --influx-flush-interval <seconds>
+
+Other:
+  --read-only  --read-only-allow-writes  --allow-multiple  --buffer-samples <n>
+  --grace-period <dur>  --no-grace  --debug  --decode-msgpack  --schema
+  -V, --version                Prints the version
+  -h, --help                   Prints this help
+",
+        version = env!("CARGO_PKG_VERSION"),
+        min_interval = sampling::MIN_INTERVAL.as_millis(),
+        output_fields = output_fields,
+    )
+}
+
+/// The cargo features this binary was built with, for `--version --verbose`.
+fn enabled_features() -> Vec<&'static str> {
+    [("cli", cfg!(feature = "cli")), ("web", cfg!(feature = "web")), ("network", cfg!(feature = "network")), ("lua", cfg!(feature = "lua")), ("opencl", cfg!(feature = "opencl")), ("vulkan", cfg!(feature = "vulkan"))]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect()
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>>{
-    println!("Identifying GPU type...");
-    let gpu_type = identify_gpu_card();
-    println!("GPU type: {:?}", gpu_type);
+    let mut args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(2);
+        }
+    };
+    let console = output::Console::new(args.format, args.quiet);
+
+    if args.help {
+        print!("{}", usage());
+        return Ok(());
+    }
+    if args.version {
+        println!("gpuatop {}", env!("CARGO_PKG_VERSION"));
+        if args.verbose > 0 {
+            println!("features: {}", enabled_features().join(", "));
+        }
+        return Ok(());
+    }
 
-    println!("Checking if top exists locally...");
-    let top_exists = match check_top_exists_local(gpu_type) {
-        Ok(ok) => ok.success(),
+    if let Some(interval) = args.interval {
+        let clamped = sampling::clamp_interval(interval, args.allow_fast_poll);
+        if clamped != interval {
+            console.warning(&format!(
+                "Warning: --interval {}ms is below {}ms and would slow down the GPU, polling every {}ms instead (--allow-fast-poll overrides)",
+                interval.as_secs_f64() * 1000.0,
+                sampling::MIN_INTERVAL.as_millis(),
+                clamped.as_millis()
+            ));
+            args.interval = Some(clamped);
+        }
+    }
+
+    if let Some(wait) = args.max_startup_wait.filter(|_| args.subcommand == Subcommand::Monitor) {
+        startup::exit_unless_sampled_within(wait, "n/a".to_string());
+    }
+
+    if args.subcommand == Subcommand::Topology {
+        let runner = RealRunner;
+        let Some(gpu_type) = try_identify_gpu_card(&runner) else {
+            eprintln!("Error: GPU not found");
+            std::process::exit(1);
+        };
+        let gpus = enumerate_gpus(&runner, &gpu_type);
+
+        match topology::query_topology(&runner, &gpu_type, &gpus) {
+            Ok(matrix) if args.json || args.format == output::OutputFormat::Json => println!("{}", topology::to_json(&matrix)),
+            Ok(matrix) => println!("{}", topology::format_topology(&matrix)),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    #[cfg(not(feature = "web"))]
+    if args.subcommand == Subcommand::Web {
+        console.error("Error: This gpuatop was built without the web feature");
+        std::process::exit(1);
+    }
+
+    if args.subcommand == Subcommand::DecodeMsgpack {
+        std::process::exit(decode_msgpack());
+    }
+
+    // Prints the records other gpuatop instances send with --send-to; no GPU is needed here.
+    #[cfg(feature = "network")]
+    if let Some(port) = args.receive {
+        console.info(&format!("Listening for gpuatop records on UDP port {}", port));
+        if let Err(err) = udp::receive(port, |record| println!("{}", record)) {
+            console.error(&format!("Error: Cannot receive on UDP port {}: {}", port, err));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Only the command on stdout, for provisioning scripts to run or bake into an image.
+    if args.print_command {
+        let runner = RealRunner;
+        let Some(gpu_type) = try_identify_gpu_card(&runner) else {
+            console.error("Error: GPU not found");
+            std::process::exit(1);
+        };
+        let os_release = fs::read_to_string(OS_RELEASE_PATH).ok();
+        let Some(installer) = identify_installer(&runner, os_release.as_deref()) else {
+            console.error("Error: Package manager not found");
+            std::process::exit(1);
+        };
+        let Some(package) = gpu_type.top_package_for(installer) else {
+            console.error("Error: There is no monitoring tool for this GPU");
+            std::process::exit(1);
+        };
+        match epel_required(&runner, installer, package, os_release.as_deref()) {
+            Some(epel_release) => println!("{} && {}", installer.install_command(&epel_release), installer.install_command(package)),
+            None => println!("{}", installer.install_command(package)),
+        }
+        return Ok(());
+    }
+
+    if args.subcommand == Subcommand::DefaultConfig {
+        print!("{}", config::DEFAULT_CONFIG);
+        return Ok(());
+    }
+
+    if args.subcommand == Subcommand::Schema {
+        print!("{}", schema::SCHEMA);
+        return Ok(());
+    }
+
+    let config = match config::load(args.config.as_deref()) {
+        Ok(config) => config,
         Err(err) => {
-            println!("Error: {}", err);
-            return Ok(());
+            console.error(&format!("Error: Invalid configuration: {}", err));
+            std::process::exit(1);
         }
     };
 
-    println!("Top exists locally: {}", top_exists);
+    let custom_backends = match custom::from_config(&config) {
+        Ok(backends) => backends,
+        Err(err) => {
+            console.error(&format!("Error: Invalid configuration: {}", err));
+            std::process::exit(1);
+        }
+    };
 
-    if !top_exists {
-        println!("Identifying package manager...");
-        let package_manager = identify_package_manager();
-        println!("Package manager: {:?}", package_manager);
-
-        println!("Installing top for GPU type...");
-        let is_ok = match install_top_for_gpu_to(gpu_type, package_manager) {
-            Ok(e) => e.status.success(),
-            Err(er) => {  println!("Error: {}", er); return Ok(()); }
+    let desktop = match desktop::DesktopClassifier::from_config(&config) {
+        Ok(desktop) => desktop,
+        Err(err) => {
+            console.error(&format!("Error: Invalid configuration: {}", err));
+            std::process::exit(1);
+        }
+    };
+
+    let capabilities = match capabilities::Capabilities::from_config(&config, args.read_only, args.read_only_allow_writes) {
+        Ok(capabilities) => capabilities,
+        Err(err) => {
+            console.error(&format!("Error: Invalid configuration: {}", err));
+            std::process::exit(1);
+        }
+    };
+    if let Err(err) = check_capabilities(&args, &capabilities) {
+        console.error(&format!("Error: {}", err));
+        std::process::exit(capabilities::READ_ONLY_EXIT_CODE);
+    }
+
+    if args.subcommand == Subcommand::Check {
+        std::process::exit(run_check(&args));
+    }
+
+    if args.subcommand == Subcommand::WatchPid {
+        run_watch_pid(&args, &console);
+        return Ok(());
+    }
+
+    if args.subcommand == Subcommand::Snapshot {
+        let snapshot = snapshot::query_snapshot_xml(&RealRunner)
+            .map_err(|err| err.to_string())
+            .and_then(|xml| snapshot::build_snapshot(&xml, args.gpu));
+
+        let snapshot = match snapshot {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(2);
+            }
         };
 
-        if !is_ok {
-            println!("Error: Failed to install top for GPU type");
-            return Ok(());
+        if let Some(path) = &args.save {
+            fs::write(path, snapshot.to_json())?;
+            println!("Snapshot saved to {}", path);
+        } else if let Some(path) = &args.diff {
+            // Exit code 1 means that the configuration changed, so an unreadable snapshot is 2.
+            let before = match fs::read_to_string(path).map_err(|err| err.to_string()).and_then(|content| json::parse(&content)) {
+                Ok(before) => before,
+                Err(err) => {
+                    eprintln!("Error: Invalid snapshot {}: {}", path, err);
+                    std::process::exit(2);
+                }
+            };
+            let changes = snapshot::diff_snapshots(&before, &snapshot, args.all);
+
+            for change in &changes {
+                println!("{}", snapshot::format_change(change));
+            }
+
+            if changes.iter().any(|change| !change.volatile) {
+                std::process::exit(1);
+            }
+        } else if args.json {
+            println!("{}", snapshot.to_json());
+        } else {
+            println!("{}", snapshot::format_text(&snapshot));
         }
+        return Ok(());
     }
 
-    loop {
-        let (name, args): (&str, &[&str]) = match gpu_type {
-            GpuType::Nvidia => ("nvidia-smi", &["--query-gpu=utilization.gpu", "--format=csv,noheader,nounits"]),
-            GpuType::Amd => ("radeontop", &["-d -"]),
-            GpuType::Intel => ("intel_gpu_top", &["-s", "1","-o", "-"]),
+    if args.subcommand == Subcommand::FixPersistence {
+        std::process::exit(fix_persistence(args.yes));
+    }
+
+    let output_context = output::OutputContext {
+        format: args.format,
+        hostname: if args.machine_hostname { output::read_hostname() } else { None },
+        labels: args.labels.clone(),
+        tick_seq: None,
+        timestamp: None,
+        precision: args.precision.unwrap_or(output::DEFAULT_PRECISION),
+    };
+
+    #[cfg(feature = "network")]
+    if args.subcommand == Subcommand::Server {
+        if let Err(err) = run_server(&args, &console, &output_context) {
+            console.error(&format!("Error: {}", err));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let runner = RealRunner;
+
+    if let Some(requirement) = &args.require_gpu {
+        if let Err(err) = requirement.check(&requirements::detect_vendors(&runner)) {
+            console.error(&format!("Error: {}", err));
+            std::process::exit(1);
+        }
+    }
+
+    console.info("Identifying GPU type...");
+    let gpu_type = identify_gpu_card(&runner);
+    match &gpu_type {
+        GpuType::Unknown(description) => console.info(&format!("Unknown GPU vendor {} — using generic DRM metrics", description)),
+        known => console.info(&format!("GPU type: {:?}", known)),
+    }
+
+    args.backend = match backend::forced_source(&config, &gpu_type, args.backend) {
+        Ok(source) => source,
+        Err(err) => {
+            console.error(&format!("Error: Invalid configuration: {}", err));
+            std::process::exit(1);
+        }
+    };
+    if let Some(source) = args.backend.filter(|source| !backend::fits(&gpu_type, source)) {
+        console.error(&format!("Error: The {} backend cannot monitor this GPU", source));
+        std::process::exit(1);
+    }
+
+    if let Ok(devices) = pci::list_display_devices() {
+        for group in pci::group_virtual_functions(&devices) {
+            console.info(&format!("  {}", pci::format_device_group(&group)));
+        }
+    }
+
+    // GPUs without a vendor tool have nothing to check or install.
+    let top_exists = gpu_type.top_tool().is_none() || args.backend.is_some_and(|source| !source.starts_with(gpu_type.top_tool().unwrap_or_default())) || {
+        console.info("Checking if top exists locally...");
+        let top_exists = match check_top_exists_local(&runner, &gpu_type) {
+            Ok(exists) => exists,
+            Err(err) => {
+                console.error(&format!("Error: {}", err));
+                std::process::exit(1);
+            }
         };
 
-        let output = Command::new(name).args(args).output()?;
+        console.info(&format!("Top exists locally: {}", top_exists));
+        top_exists
+    };
+
+    if !top_exists {
+        if let Err(err) = capabilities.install() {
+            console.error(&format!("Error: {} is not installed and {}", gpu_type.top_tool().unwrap_or_default(), err));
+            std::process::exit(capabilities::READ_ONLY_EXIT_CODE);
+        }
+        console.info("Identifying package manager...");
+        let os_release = fs::read_to_string(OS_RELEASE_PATH).ok();
+        let Some(installer) = identify_installer(&runner, os_release.as_deref()) else {
+            console.error("Error: Package manager not found");
+            std::process::exit(1);
+        };
+        console.info(&format!("Package manager: {:?}", installer));
 
-        print!("GPU Utilization (percent): {}", str::from_utf8(&output.stdout).expect("Not UTF-8"));
+        // Air-gapped machines get the instructions right away rather than after the package
+        // manager's own network timeouts.
+        let offline = match installer {
+            Installer::PackageManager(_) if args.offline => Some("--offline".to_string()),
+            Installer::PackageManager(package_manager) => {
+                console.info("Checking the package mirror...");
+                mirror::check_reachable(&mirror::configured_urls(Path::new("/"), package_manager), mirror::REACHABILITY_TIMEOUT).err()
+            }
+            Installer::Immutable(_) => None,
+        };
+
+        let confirm_install = |prompt: &str| {
+            let confirmed = confirm(&console, prompt, args.yes);
+            if confirmed {
+                console.info("Installing top for GPU type...");
+            }
+            confirmed
+        };
+        let result = match offline {
+            Some(reason) => {
+                console.warning(&format!("Warning: Not installing {}: {}", gpu_type.top_tool().unwrap_or_default(), reason));
+                if let Some(instructions) = offline_instructions(&gpu_type, installer, nvidia_driver_version().as_deref()) {
+                    console.emit(&instructions);
+                }
+                InstallResult::InstructionsPrinted
+            }
+            None => install_top_for_gpu_to(&runner, &gpu_type, installer, os_release.as_deref(), confirm_install, |instructions| console.emit(instructions)),
+        };
+        match result {
+            InstallResult::Installed => {}
+            // Until the tool is installed, monitoring can go on where the kernel exposes the
+            // metrics in sysfs.
+            InstallResult::InstructionsPrinted | InstallResult::Declined if backend::SysfsBackend::open().is_ok() => {
+                console.warning("Warning: Falling back to sysfs metrics until the tool is installed");
+                args.low_overhead = true;
+            }
+            InstallResult::InstructionsPrinted | InstallResult::Declined => return Ok(()),
+            InstallResult::Locked => {
+                console.error("Error: The package manager is locked by another process, e.g. an automatic update. Wait for it to finish and run gpuatop again.");
+                std::process::exit(1);
+            }
+            InstallResult::EpelRequired { hint } => {
+                console.error(&format!("Error: {}", hint));
+                std::process::exit(1);
+            }
+            InstallResult::Failed { message, output } => {
+                console.error(&format!("Error: Failed to install top for GPU type: {}", message));
+                for line in output {
+                    console.error(&format!("  {}", line));
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.subcommand == Subcommand::Install {
+        return Ok(());
+    }
 
-        thread::sleep(Duration::from_secs(1));
+    // Without root, intel_gpu_top and radeontop hang or print zeros rather than fail. On AMD the
+    // amdgpu sysfs metrics need no privileges, so they take over where the kernel has them.
+    let mut dri_hint = if args.low_overhead { None } else { privileges::dri_preflight(&gpu_type) };
+    if !args.low_overhead
+        && privileges::check_privileges(&gpu_type) == privileges::PrivilegeCheck::RequiresRoot
+        && process::effective_uid() != Some(0)
+    {
+        // radeontop only lacks the group, which the preflight names more precisely.
+        let message = dri_hint
+            .take_if(|_| gpu_type == GpuType::Amd)
+            .or_else(|| privileges::message(&gpu_type))
+            .unwrap_or_else(|| "The monitoring tool requires root; re-run with sudo".to_string());
+        if let Some(hint) = dri_hint.take() {
+            console.warning(&format!("Warning: {}", hint));
+        }
+        if gpu_type == GpuType::Amd && backend::SysfsBackend::open().is_ok() {
+            console.warning(&format!("Warning: {}", message));
+            console.warning("Warning: Falling back to sysfs metrics");
+            args.low_overhead = true;
+        } else {
+            console.error(&format!("Error: {}", message));
+            std::process::exit(1);
+        }
+    }
+    if let Some(hint) = dri_hint {
+        console.warning(&format!("Warning: {}", hint));
+    }
+
+    // Freshly loaded drivers report spurious values for a few seconds.
+    if !args.grace_period.is_zero() {
+        wait_for_driver(&console, args.grace_period);
+    }
+
+    // The first vendor tool call, which wakes up the driver.
+    let waiting = || {
+        if console.shows_info() {
+            eprintln!("Waiting for the first sample...");
+        }
+    };
+    let (mut gpus, wake_up) = startup::timed(startup::NOTICE_AFTER, waiting, || enumerate_gpus(&runner, &gpu_type));
+    if wake_up >= startup::NOTICE_AFTER {
+        let hint = if gpu_type == GpuType::Nvidia { "; persistence mode avoids it (gpuatop fix-persistence)" } else { "" };
+        console.info(&format!("GPU driver wake-up took {:.1}s{}", wake_up.as_secs_f64(), hint));
+    }
+    if let Some(requirement) = args.require_gpu.as_ref().filter(|requirement| requirement.min_vram_mib.is_some()) {
+        if let Err(err) = requirement.check_vram(&requirements::sample_gpus(&runner, &gpu_type, &gpus)) {
+            console.error(&format!("Error: {}", err));
+            std::process::exit(1);
+        }
+    }
+    prime::annotate(&runner, &gpu_type, &mut gpus);
+    for gpu in gpus.iter().filter(|gpu| gpu.render_offload == Some(prime::RenderOffloadMode::OffloadGpu)) {
+        console.info(&format!("GPU {} renders offloaded applications only (PRIME), the desktop runs on the other GPU", gpu.index));
+    }
+
+    let mut custom_devices = Vec::new();
+    let mut next_index = gpus.iter().map(|gpu| gpu.index + 1).max().unwrap_or(0);
+
+    for backend in custom_backends {
+        match backend.enumerate(&runner, next_index) {
+            Ok(devices) => {
+                for device in &devices {
+                    console.info(&format!("Custom backend {}: GPU {} ({})", backend.name, device.index, device.name));
+                }
+                next_index += devices.len() as u32;
+                custom_devices.push((backend, devices));
+            }
+            Err(err) => console.warning(&format!("Warning: Custom backend {} unavailable: {}", backend.name, err)),
+        }
+    }
+
+    let virtualization = match gpu_type {
+        GpuType::Nvidia => vgpu::query_virtualization_info(&runner),
+        _ => None,
+    };
+    let vgpu_host = matches!(virtualization, Some(vgpu::VirtualizationInfo { mode: vgpu::VirtualizationMode::Host, .. }));
+
+    if let Some(vgpu::VirtualizationInfo { mode: vgpu::VirtualizationMode::Guest, license_status }) = &virtualization {
+        for gpu in &gpus {
+            console.info(&format!("vGPU guest device: {}", gpu.name));
+        }
+        console.info(&format!("vGPU license status: {}", license_status.as_deref().unwrap_or("Unknown")));
+    }
+
+    if let GpuType::Nvidia = gpu_type {
+        let modes = persistence::query_modes(&runner);
+
+        for modes in &modes {
+            console.info(&format!("GPU {} Persistence mode: {}, Compute mode: {}", modes.index, modes.persistence_mode, modes.compute_mode));
+        }
+
+        if modes.iter().any(|modes| !modes.persistence_enabled()) && persistence::is_headless() {
+            console.warning(persistence::PERSISTENCE_WARNING);
+        }
+    }
+
+    for monitor in pollers::find_monitors() {
+        console.info(&format!("Note: {} (PID {}) is also polling the GPUs, its load can skew the measurements", monitor.name, monitor.pid));
+    }
+    // Held until gpuatop exits.
+    let lock_dir = pollers::lock_dir();
+    let _locks = match capabilities.write("GPU locks", &lock_dir).map(|()| pollers::DeviceLocks::acquire(&lock_dir, &gpus)) {
+        Err(err) => {
+            console.info(&format!("Note: Not locking the GPUs against other gpuatop instances: {}", err));
+            None
+        }
+        Ok(Ok((_, busy))) if !busy.is_empty() && !args.allow_multiple => {
+            for busy in &busy {
+                console.error(&format!("Error: GPU {} is already monitored by another gpuatop{}", busy.gpu, busy.pid.map(|pid| format!(" (PID {})", pid)).unwrap_or_default()));
+            }
+            console.error("Read that gpuatop's records through --output-socket instead of polling the GPU twice, or pass --allow-multiple");
+            std::process::exit(1);
+        }
+        Ok(Ok((locks, busy))) => {
+            for busy in &busy {
+                console.info(&format!("Note: GPU {} is also monitored by another gpuatop{}", busy.gpu, busy.pid.map(|pid| format!(" (PID {})", pid)).unwrap_or_default()));
+            }
+            Some(locks)
+        }
+        Ok(Err(err)) => {
+            console.warning(&format!("Warning: Cannot lock the GPUs against other gpuatop instances: {}", err));
+            None
+        }
+    };
+    let stop = AtomicBool::new(false);
+
+    let Some(command) = &args.launch else {
+        // A command started with --launch keeps the terminal's input to itself.
+        if args.format == output::OutputFormat::Text && io::stdin().is_terminal() && pause::in_foreground() {
+            console.info("Press p to pause, r to resume, a GPU index to zoom in, o for the overview, d for kernel messages");
+            pause::listen();
+        }
+        std::process::exit(monitor::run(&args, &output_context, &runner, &gpu_type, gpus, custom_devices, vgpu_host, &desktop, None, &stop)?);
+    };
+
+    let (program, command_args) = command.split_first().ok_or("--launch requires a command")?;
+    let mut command = Command::new(program);
+    // In a process group of its own, a budget signal reaches every process the command starts
+    // and spares gpuatop.
+    if !args.budget.is_empty() {
+        command.process_group(0);
+    }
+    let mut child = command.args(command_args).spawn()?;
+    let child_id = child.id();
+
+    let (status, monitor_code) = thread::scope(|scope| {
+        let monitor = scope.spawn(|| monitor::run(&args, &output_context, &runner, &gpu_type, gpus, custom_devices, vgpu_host, &desktop, Some(child_id), &stop));
+        // Without --duration monitoring lasts as long as the command. With it, whichever ends
+        // first wins: a command still running at the end of the duration is left running.
+        let status = match args.duration {
+            None => child.wait().map(Some),
+            Some(_) => loop {
+                match child.try_wait() {
+                    Ok(None) if monitor.is_finished() => break Ok(None),
+                    Ok(None) => thread::sleep(CHILD_POLL_INTERVAL),
+                    result => break result,
+                }
+            },
+        };
+        stop.store(true, Ordering::Relaxed);
+
+        let monitor_code = match monitor.join() {
+            Ok(Ok(code)) => Some(code),
+            Ok(Err(err)) => {
+                console.error(&format!("Error: {}", err));
+                None
+            }
+            Err(_) => None,
+        };
+        status.map(|status| (status, monitor_code))
+    })?;
+
+    // A command stopped by a budget exits with the budget code, whatever the signal made of it.
+    // Otherwise the command's own failure wins; a critical alert during a successful run fails
+    // it too.
+    if monitor_code == Some(budget::EXIT_CODE) {
+        std::process::exit(budget::EXIT_CODE);
+    }
+    let Some(status) = status else {
+        console.info(&format!("The command keeps running as PID {}", child_id));
+        std::process::exit(monitor_code.unwrap_or(1));
+    };
+    match status.code() {
+        Some(0) if args.critical_exit_code != 0 && monitor_code == Some(args.critical_exit_code) => std::process::exit(args.critical_exit_code),
+        code => std::process::exit(code.unwrap_or(1)),
     }
 }
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::output::{influx_escape, json_string};
+
+/// User-defined `key=value` tags attached to every output record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Labels(HashMap<String, String>);
+
+/// Keys every record already carries as a label or tag of its own.
+const RESERVED_KEYS: [&str; 3] = ["gpu", "name", "hostname"];
+
+/// The Prometheus label name of a key: `--label` keys may contain `-`, which Prometheus label
+/// names do not allow, and may start with a digit.
+pub fn label_name(key: &str) -> String {
+    let name = key.replace('-', "_");
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid label key '{}': only letters, digits, and '-' are allowed", key));
+    }
+    if RESERVED_KEYS.contains(&key) {
+        return Err(format!("Invalid label key '{}': {} are set by gpuatop", key, RESERVED_KEYS.join(", ")));
+    }
+    Ok(())
+}
+
+/// Fails when `key` and another key of `labels` would be the same Prometheus label.
+fn check_collision(labels: &HashMap<String, String>, key: &str) -> Result<(), String> {
+    let name = label_name(key);
+    match labels.keys().find(|other| *other != key && label_name(other) == name) {
+        Some(other) => Err(format!("Label keys '{}' and '{}' are both the label '{}' in Prometheus output", other, key, name)),
+        None => Ok(()),
+    }
+}
+
+fn validate_value(key: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err(format!("Label '{}' has an empty value", key));
+    }
+    // Control characters would split NDJSON records and InfluxDB lines; quotes and
+    // backslashes are escaped by the formatters.
+    if value.chars().any(char::is_control) {
+        return Err(format!("Label '{}' contains control characters", key));
+    }
+    Ok(())
+}
+
+impl FromStr for Labels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut labels = HashMap::new();
+
+        for pair in s.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| format!("Invalid label '{}': expected key=value", pair))?;
+            validate_key(key)?;
+            validate_value(key, value)?;
+            check_collision(&labels, key)?;
+            labels.insert(key.to_string(), value.to_string());
+        }
+
+        Ok(Labels(labels))
+    }
+}
+
+impl Labels {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn extend(&mut self, other: Labels) {
+        self.0.extend(other.0);
+    }
+
+    /// [`Labels::extend`], failing like parsing when a key of `other` would be the same
+    /// Prometheus label as another key: for `--label` repeated.
+    pub fn try_extend(&mut self, other: Labels) -> Result<(), String> {
+        for key in other.0.keys() {
+            check_collision(&self.0, key)?;
+        }
+        self.extend(other);
+        Ok(())
+    }
+
+    /// Labels sorted by key so output is stable between samples.
+    pub fn sorted(&self) -> Vec<(&String, &String)> {
+        let mut labels: Vec<_> = self.0.iter().collect();
+        labels.sort();
+        labels
+    }
+
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .sorted()
+            .into_iter()
+            .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+            .collect();
+
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Formats the labels as InfluxDB tags, each preceded by a comma.
+    pub fn to_influx_tags(&self) -> String {
+        self.sorted()
+            .into_iter()
+            .map(|(key, value)| format!(",{}={}", key, influx_escape(value)))
+            .collect()
+    }
+}
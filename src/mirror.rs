@@ -0,0 +1,189 @@
+//! Whether the package manager can reach its mirrors, checked before installing the vendor
+//! tool. On an air-gapped machine the package manager would wait minutes on its own network
+//! timeouts before failing; gpuatop prints the offline instructions right away instead.
+
+use std::fs;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::PackageManager;
+
+/// How long resolving and connecting to all mirrors may take together.
+pub const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Mirrors tried before giving up; a machine that reaches none of the first few reaches
+/// none.
+const MAX_MIRRORS: usize = 3;
+
+/// A host the package manager downloads from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    pub host: String,
+    pub port: u16,
+}
+
+/// Where a repository URL points: a host on the network, or something local such as a
+/// `file:` repository or a mounted disc, which needs no network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Remote(Mirror),
+    Local,
+}
+
+/// Parses the scheme and host of a repository URL; `None` for what is not a URL.
+pub fn parse_url(url: &str) -> Option<Source> {
+    if url.starts_with('/') {
+        return Some(Source::Local);
+    }
+    let (scheme, rest) = url.split_once(':')?;
+    // apt's `mirror+http://` and similar method prefixes download over the inner scheme.
+    let port = match scheme.rsplit('+').next().unwrap_or(scheme) {
+        "http" | "mirror" => 80,
+        "https" => 443,
+        "ftp" => 21,
+        "file" | "cdrom" | "copy" => return Some(Source::Local),
+        _ => return None,
+    };
+
+    let authority = rest.strip_prefix("//")?.split('/').next().unwrap_or_default();
+    let authority = authority.rsplit('@').next().unwrap_or_default();
+    let (host, port) = match authority.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((host, after)) => (host, after.strip_prefix(':').and_then(|port| port.parse().ok()).unwrap_or(port)),
+        None => match authority.split_once(':') {
+            Some((host, after)) => (host, after.parse().unwrap_or(port)),
+            None => (authority, port),
+        },
+    };
+    (!host.is_empty()).then(|| Source::Remote(Mirror { host: host.to_string(), port }))
+}
+
+/// The URLs of an apt sources file, one-line (`deb [options] URL suite ...`) or deb822
+/// (`URIs: URL ...`) style. Commented and `deb-src` entries are left out.
+pub fn apt_source_urls(content: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("deb ") {
+            let rest = match rest.trim_start().strip_prefix('[') {
+                Some(options) => options.split_once(']').map(|(_, rest)| rest).unwrap_or_default(),
+                None => rest,
+            };
+            urls.extend(rest.split_whitespace().next().map(str::to_string));
+        } else if let Some(rest) = line.strip_prefix("URIs:") {
+            urls.extend(rest.split_whitespace().map(str::to_string));
+        }
+    }
+    urls
+}
+
+/// The `baseurl`, `mirrorlist` and `metalink` URLs of a dnf, yum or zypper `.repo` file,
+/// from repositories that are not `enabled=0`.
+pub fn repo_file_urls(content: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut section = Vec::new();
+    let mut enabled = true;
+    for line in content.lines().map(str::trim).chain(["["]) {
+        if line.starts_with('[') {
+            if enabled {
+                urls.append(&mut section);
+            }
+            section.clear();
+            enabled = true;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "baseurl" | "mirrorlist" | "metalink" => section.extend(value.split_whitespace().map(str::to_string)),
+            "enabled" => enabled = value.trim() != "0",
+            _ => {}
+        }
+    }
+    urls
+}
+
+/// The `Server = URL` lines of pacman's mirrorlist that are not commented out.
+pub fn pacman_mirror_urls(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter(|(key, _)| key.trim() == "Server")
+        .map(|(_, url)| url.trim().to_string())
+        .collect()
+}
+
+/// Reads the repository URLs `package_manager` is configured with under `root`, normally `/`.
+pub fn configured_urls(root: &Path, package_manager: PackageManager) -> Vec<String> {
+    let read = |path: &str| fs::read_to_string(root.join(path)).unwrap_or_default();
+    let read_dir = |dir: &str, extensions: &[&str]| {
+        let mut paths: Vec<_> = fs::read_dir(root.join(dir))
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|extension| extension.to_str()).is_some_and(|extension| extensions.contains(&extension)))
+            .collect();
+        paths.sort();
+        paths.into_iter().map(|path| fs::read_to_string(path).unwrap_or_default()).collect::<Vec<_>>()
+    };
+
+    match package_manager {
+        PackageManager::Apt => {
+            let mut urls = apt_source_urls(&read("etc/apt/sources.list"));
+            for content in read_dir("etc/apt/sources.list.d", &["list", "sources"]) {
+                urls.extend(apt_source_urls(&content));
+            }
+            urls
+        }
+        PackageManager::Dnf | PackageManager::Yum => read_dir("etc/yum.repos.d", &["repo"]).iter().flat_map(|content| repo_file_urls(content)).collect(),
+        PackageManager::Zypper => read_dir("etc/zypp/repos.d", &["repo"]).iter().flat_map(|content| repo_file_urls(content)).collect(),
+        PackageManager::Pacman => pacman_mirror_urls(&read("etc/pacman.d/mirrorlist")),
+    }
+}
+
+/// Checks that at least one of `urls` can be reached within `timeout`: a local repository
+/// always can, a remote one when its host resolves and accepts a TCP connection. Without any
+/// URL there is nothing to tell, and the package manager gets to try. The error names the
+/// hosts that were tried.
+pub fn check_reachable(urls: &[String], timeout: Duration) -> Result<(), String> {
+    let sources: Vec<Source> = urls.iter().filter_map(|url| parse_url(url)).collect();
+    if sources.is_empty() || sources.contains(&Source::Local) {
+        return Ok(());
+    }
+
+    let mut mirrors: Vec<Mirror> = Vec::new();
+    for source in sources {
+        if let Source::Remote(mirror) = source {
+            if !mirrors.contains(&mirror) && mirrors.len() < MAX_MIRRORS {
+                mirrors.push(mirror);
+            }
+        }
+    }
+
+    // Name resolution has no timeout of its own, so the mirrors are tried on threads that are
+    // abandoned once the time is up.
+    let (sender, results) = mpsc::channel();
+    for mirror in mirrors.clone() {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let reachable = (mirror.host.as_str(), mirror.port)
+                .to_socket_addrs()
+                .is_ok_and(|mut addresses| addresses.any(|address| TcpStream::connect_timeout(&address, timeout).is_ok()));
+            let _ = sender.send(reachable);
+        });
+    }
+    drop(sender);
+
+    let deadline = Instant::now() + timeout;
+    while let Ok(reachable) = results.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        if reachable {
+            return Ok(());
+        }
+    }
+
+    let hosts: Vec<String> = mirrors.iter().map(|mirror| format!("{}:{}", mirror.host, mirror.port)).collect();
+    Err(format!("cannot reach the package mirror ({})", hosts.join(", ")))
+}
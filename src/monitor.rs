@@ -0,0 +1,1068 @@
+use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use gpu_auto_top::custom::CustomBackend;
+use gpu_auto_top::display::detail::{self, View};
+use gpu_auto_top::display::layout::{self, Layout};
+use gpu_auto_top::runner::CommandRunner;
+use gpu_auto_top::{aggregate, alert, aperture, backend, budget, delta, desktop, display, dmesg, driver, efficiency, event, golden, idle, jitter, live, msgpack, notify, nvlink, output, overhead, pause, power, process, prometheus, report, rollup, sampling, schedule, sink, startup, stats, statsd, syslog, temperature, terminal, users, vgpu};
+#[cfg(feature = "lua")]
+use gpu_auto_top::script;
+#[cfg(feature = "network")]
+use gpu_auto_top::{influx, tcp, udp};
+use gpu_auto_top::{clamp_percent, poll_gpus_with_retries, widen, GpuInfo, GpuSnapshot, GpuType, PollResult, MAX_CONSECUTIVE_FAILURES};
+
+use crate::Args;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sleeps for `duration`, waking early when `stop` is set. Returns whether it was stopped.
+fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    sleep_until_unless_stopped(Instant::now() + duration, stop)
+}
+
+/// Sleeps until `deadline`, waking early when `stop` is set. Returns whether it was stopped.
+fn sleep_until_unless_stopped(deadline: Instant, stop: &AtomicBool) -> bool {
+    while !stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(100)));
+    }
+
+    true
+}
+
+/// Polls the vendor backend and every custom backend once, attributing each sample to its source.
+fn poll_all(
+    runner: &dyn CommandRunner,
+    backend: &mut dyn backend::Backend,
+    gpus: &[GpuInfo],
+    custom_devices: &[(CustomBackend, Vec<GpuInfo>)],
+    max_retries: u32,
+) -> Vec<PollResult> {
+    let mut results = Vec::new();
+
+    let attribute = |source: String| {
+        move |mut result: PollResult| {
+            if let PollResult::Ok { snapshot, .. } = &mut result {
+                snapshot.source = Some(source.clone());
+            }
+            result
+        }
+    };
+
+    if !gpus.is_empty() {
+        let polled = poll_gpus_with_retries(gpus, max_retries, |gpus| backend.poll(gpus));
+        mark_retries(&polled);
+        results.extend(polled.into_iter().map(attribute(backend.source().to_string())));
+    }
+    for (backend, devices) in custom_devices {
+        if !devices.is_empty() {
+            let polled = poll_gpus_with_retries(devices, max_retries, |failed| backend.poll(runner, failed, devices));
+            mark_retries(&polled);
+            results.extend(polled.into_iter().map(attribute(format!("custom:{}", backend.name))));
+        }
+    }
+
+    results
+}
+
+/// Prints a `!` for each time a poll was retried, on stderr so machine-readable formats keep
+/// stdout clean.
+fn mark_retries(results: &[PollResult]) {
+    let retries = results.iter().map(PollResult::retries).max().unwrap_or(0);
+    if retries > 0 {
+        live::eprint(&"!".repeat(retries as usize));
+    }
+}
+
+/// Writes a per-tick status line such as an alert. In text mode it is part of the output and
+/// goes to the log file with it; machine-readable formats keep it off stdout.
+fn status(writer: &mut output::Writer, console: &output::Console, context: &output::OutputContext, line: &str) {
+    if console.is_machine() {
+        console.emit(&output::prefix_text(line, context));
+    } else {
+        writer.line(&output::prefix_text(line, context));
+    }
+}
+
+/// Reports an event: a status line, a record among the JSON output when `records` is given (the
+/// single document's when it holds one), and a syslog message.
+#[allow(clippy::too_many_arguments)]
+fn report_event(
+    event: &event::Event,
+    writer: &mut output::Writer,
+    console: &output::Console,
+    context: &output::OutputContext,
+    records: Option<Option<&mut Vec<String>>>,
+    syslog: Option<&mut syslog::SyslogSink>,
+    params: Vec<(String, String)>,
+    counts: &mut event::EventCounts,
+) {
+    counts.record(event);
+    status(writer, console, context, &event.format_text());
+    match records {
+        Some(Some(document)) => document.push(event.to_json(context)),
+        Some(None) => writer.line(&event.to_json(context)),
+        None => {}
+    }
+    if let Some(syslog) = syslog {
+        let severity = if event.severity == alert::Severity::Info { syslog::Severity::Info } else { syslog::Severity::Warning };
+        syslog.send(severity, event.kind.name(), params, &event.message);
+    }
+}
+
+/// Reports a completed `--rollup` window: a record among the JSON output (the single document's
+/// when it holds one) or a line in text mode, and a row of the roll-up CSV, which is dropped
+/// after a failed write.
+fn report_rollup(
+    rollup: &rollup::Rollup,
+    writer: &mut output::Writer,
+    console: &output::Console,
+    context: &output::OutputContext,
+    document: Option<&mut Vec<String>>,
+    csv: &mut Option<rollup::RollupCsv>,
+) {
+    match document {
+        _ if !matches!(context.format, output::OutputFormat::Ndjson | output::OutputFormat::Json) => {
+            writer.line(&output::prefix_text(&rollup.format_text(context.precision), context));
+        }
+        Some(document) => document.push(rollup.to_json(context)),
+        None => writer.line(&rollup.to_json(context)),
+    }
+    if let Some(file) = csv {
+        if let Err(err) = file.write(rollup) {
+            console.error(&format!("Error: Failed to write the roll-up CSV: {}", err));
+            *csv = None;
+        }
+    }
+}
+
+/// Runs the sampling loop until a stop condition is met (all GPUs dropped, followed PIDs
+/// exited, or `stop` set by the caller) and prints the exit summary. Returns the exit code.
+/// `child` is the command started by `exec` or `--launch`, which an exceeded budget stops.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    args: &Args,
+    output_context: &output::OutputContext,
+    runner: &dyn CommandRunner,
+    gpu_type: &GpuType,
+    mut gpus: Vec<GpuInfo>,
+    mut custom_devices: Vec<(CustomBackend, Vec<GpuInfo>)>,
+    vgpu_host: bool,
+    desktop: &desktop::DesktopClassifier,
+    child: Option<u32>,
+    stop: &AtomicBool,
+) -> io::Result<i32> {
+    let stamp_log = args.format == output::OutputFormat::Text && args.timestamp_format.is_none();
+    let mut writer = output::Writer::new(args.log_file.as_deref(), stamp_log)?;
+    let console = output::Console::new(args.format, args.quiet);
+    let golden = match args.golden_file.as_deref().map(golden::load_golden).transpose() {
+        Ok(golden) => golden,
+        Err(err) => {
+            console.error(&format!("Error: {}", err));
+            return Ok(1);
+        }
+    };
+    let mut diverged = false;
+    // With `--interval` shorter than the display interval, samples are taken at high frequency
+    // from the cheapest source and aggregated over each display interval.
+    let sample_interval = args.interval.unwrap_or(POLL_INTERVAL);
+    let display_interval = args.display_interval.unwrap_or(POLL_INTERVAL).max(sample_interval);
+    let high_frequency = sample_interval < display_interval;
+    let low_overhead = args.low_overhead || high_frequency;
+    // One sample through the full parse path before anything reports readiness: the sinks and
+    // the dashboard are only opened once a source passed.
+    let mut fell_back = false;
+    let report_failure = |error: &backend::ProbeError| {
+        console.error(&format!("Error: Self-check of {} failed: {}", error.backend, error.message));
+        for line in &error.output {
+            console.error(&format!("  {}", line));
+        }
+    };
+    // Runtime-suspended GPUs are left asleep unless `--wake`, the probe included.
+    let watch = power::DeviceWatch::new(&gpus);
+    let probed: Vec<GpuInfo> = gpus.iter().filter(|gpu| args.wake || watch.state(gpu) == power::DeviceState::Active).cloned().collect();
+    // A source forced by `--backend` or the configuration has no fallback.
+    let selected = match args.backend {
+        Some(source) => backend::open_probed(runner, gpu_type, source, sample_interval, args.retry, &probed).inspect_err(report_failure),
+        None => backend::select_probed(runner, gpu_type, low_overhead, sample_interval, args.retry, &probed, |error| {
+            report_failure(error);
+            fell_back = true;
+        }),
+    };
+    let (mut backend, capabilities) = match selected {
+        Ok(selected) => selected,
+        Err(error) => {
+            console.error("Error: No metrics source passed the self-check");
+            return Ok(error.exit_code());
+        }
+    };
+    if (low_overhead || fell_back || args.verbose > 0) && console.shows_info() {
+        status(&mut writer, &console, output_context, &format!("Metrics source: {}", backend.name()));
+    }
+    for gpu in &gpus {
+        console.info(&format!("GPU {} ({}): sampled from {}", gpu.index, gpu.name, backend.source()));
+    }
+    if args.fields.contains(&output::Field::NvLink) && *gpu_type != GpuType::Nvidia {
+        console.warning("Warning: --fields nvlink needs an NVIDIA GPU and is ignored");
+    }
+    if args.fields.contains(&output::Field::Bar1) && *gpu_type != GpuType::Nvidia {
+        console.warning("Warning: --fields bar1 needs an NVIDIA GPU and is ignored");
+    }
+    if args.fields.contains(&output::Field::VisVram) && *gpu_type != GpuType::Amd {
+        console.warning("Warning: --fields vis_vram needs an AMD GPU and is ignored");
+    }
+    if (args.fields.contains(&output::Field::Temps) || !args.alert_sensor_temps.is_empty()) && !matches!(gpu_type, GpuType::Nvidia | GpuType::Amd) {
+        console.warning("Warning: --fields temps and --alert-temp sensor=... need an NVIDIA or AMD GPU and are ignored");
+    }
+    // Fields the installed NVIDIA driver is too old for would fail their query on every tick.
+    let mut fields = args.fields.clone();
+    let mut sensor_temps = !args.alert_sensor_temps.is_empty();
+    let gated = fields.contains(&output::Field::NvLink) || fields.contains(&output::Field::Temps) || sensor_temps;
+    if let Some(version) = (*gpu_type == GpuType::Nvidia && gated).then(|| driver::query_nvidia_version(runner)).flatten() {
+        let too_old = |metric: driver::Metric| driver::requires_driver_version(metric, version).then(|| metric.min_version().major);
+        if let Some(major) = too_old(driver::Metric::NvLinkThroughput).filter(|_| fields.contains(&output::Field::NvLink)) {
+            console.warning(&format!("Warning: --fields nvlink needs NVIDIA driver {} or newer, {} is installed; it is skipped", major, version));
+            fields.retain(|field| *field != output::Field::NvLink);
+        }
+        if let Some(major) = too_old(driver::Metric::MemoryTemperature).filter(|_| fields.contains(&output::Field::Temps) || sensor_temps) {
+            console.warning(&format!("Warning: --fields temps and --alert-temp sensor=... need NVIDIA driver {} or newer, {} is installed; they are skipped", major, version));
+            fields.retain(|field| *field != output::Field::Temps);
+            sensor_temps = false;
+        }
+    }
+    if args.by_user && !matches!(gpu_type, GpuType::Nvidia | GpuType::Amd) {
+        console.warning("Warning: --by-user needs per-process metrics, which only NVIDIA and AMD GPUs report");
+    }
+    if !args.pid_filter.is_empty() && *gpu_type == GpuType::Amd {
+        console.warning("Warning: rocm-smi reports no per-process utilization, --pid-filter shows the utilization of the whole GPU");
+    }
+    if args.show_efficiency && !capabilities.power {
+        console.warning(&format!("Warning: {} reports no power draw, --show-efficiency shows N/A", backend.name()));
+    }
+    if args.budget.max_energy_wh.is_some() && !capabilities.power {
+        console.warning(&format!("Warning: {} reports no power draw, --max-energy cannot trigger", backend.name()));
+    }
+    if args.alert_temp.is_some() && !capabilities.temperature {
+        console.warning(&format!("Warning: {} reports no temperature, --alert-temp cannot trigger", backend.name()));
+    }
+    if args.warn_vram.is_some() && !capabilities.memory {
+        console.warning(&format!("Warning: {} reports no memory usage, --warn-vram cannot trigger", backend.name()));
+    }
+    let mut socket = args.output_socket.as_deref().map(|path| sink::SocketSink::bind(path, args.debug)).transpose()?;
+    let mut fifo = args.output_fifo.as_deref().map(|path| sink::FifoSink::open(path, args.debug)).transpose()?;
+    // `--syslog-server` sends to a remote collector over UDP instead of the local socket.
+    let mut syslog = match (args.output_syslog, &args.syslog_server) {
+        (None, None) => None,
+        (facility, server) => Some(syslog::SyslogSink::new(facility.unwrap_or_default(), server.as_deref(), output_context.hostname.clone().or_else(output::read_hostname))?),
+    };
+    #[cfg(feature = "network")]
+    let mut udp = args.send_to.as_deref().map(udp::UdpSender::new).transpose()?;
+    #[cfg(feature = "network")]
+    let tcp = args.send_to_tcp.as_deref().map(|target| tcp::TcpSender::new(target, args.tcp_buffer_size.unwrap_or(tcp::DEFAULT_BUFFER_SIZE), args.debug)).transpose()?;
+    #[cfg(not(feature = "network"))]
+    let (udp, tcp): (Option<()>, Option<()>) = (None, None);
+    #[cfg(feature = "network")]
+    let influx = match (&args.export_influx, &args.influx_bucket, &args.influx_org) {
+        (Some(url), Some(bucket), Some(org)) => {
+            let target = influx::InfluxTarget { url: url.clone(), org: org.clone(), bucket: bucket.clone(), token: args.influx_token.clone() };
+            Some(influx::InfluxWriter::new(target, args.influx_flush_interval.unwrap_or(influx::DEFAULT_FLUSH_INTERVAL))?)
+        }
+        _ => None,
+    };
+    #[cfg(feature = "web")]
+    let web = match args.subcommand {
+        crate::Subcommand::Web => {
+            let server = gpu_auto_top::web::Server::bind(&args.listen)?;
+            console.info(&format!("Dashboard: http://{}/", gpu_auto_top::web::listen_address(&args.listen)));
+            Some(server)
+        }
+        _ => None,
+    };
+    #[cfg(not(feature = "web"))]
+    let web: Option<()> = None;
+    let mut statistics = stats::Statistics::default();
+    let mut html_report = args.export_html.as_ref().map(|_| report::HtmlReport::default());
+    let mut ticks = 0;
+    let started = Instant::now();
+    // `--duration` ends monitoring on the clock, between ticks or within a sampling window.
+    let end = args.duration.map(|duration| started + duration);
+    let mut self_stats = args.self_stats.then(overhead::SelfStats::new);
+    let mut raw_samples = args.dump_raw.as_ref().map(|_| sampling::RingBuffer::new(args.buffer_samples));
+    let mut alerts = alert::AlertTracker::new(alert::rules(args.alert_temp, &args.alert_sensor_temps, args.alert_util, args.warn_vram, &args.alert_severities));
+    let mut notifier = args.notify.then(notify::Notifier::new);
+    let mut jitter = args.interval_jitter.map(|fraction| {
+        let hostname = output_context.hostname.clone().or_else(output::read_hostname).unwrap_or_default();
+        jitter::Jitter::new(fraction, &hostname)
+    });
+    let mut failures: HashMap<u32, u32> = HashMap::new();
+    let mut throttling = event::ThrottleTracker::default();
+    let mut user_names = users::UserNames::default();
+    let mut gpu_hours = users::GpuHours::default();
+    let mut nvlink_tracker = nvlink::NvLinkTracker::default();
+    let mut exited_pids: Vec<u32> = Vec::new();
+    // `--format json --count 1` prints one JSON document instead of an NDJSON stream.
+    let single_document = output_context.format == output::OutputFormat::Json && args.count == Some(1);
+    let mut document = Vec::new();
+    // `--format prometheus` collects the tick into one page, written once the loop ends.
+    let mut page = Vec::new();
+    let mut deltas = args.diff_output.then(|| delta::DeltaTracker::new(args.diff_threshold));
+    let mut idle = args.idle_threshold.map(|threshold| idle::IdleTracker::new(threshold, started));
+    // Gaps of up to ten intervals are bridged; a longer one is most likely a suspend.
+    let mut budgets = (!args.budget.is_empty()).then(|| budget::BudgetTracker::new(args.budget, display_interval * 10));
+    let mut enforcer = child.filter(|_| budgets.is_some()).map(|pgid| {
+        let signal = args.budget_signal.clone().unwrap_or_else(|| budget::DEFAULT_SIGNAL.to_string());
+        budget::Enforcer::new(pgid, signal, args.budget_grace.unwrap_or(budget::DEFAULT_GRACE))
+    });
+    let mut rollups = args.rollup.map(|window| rollup::RollupTracker::new(window, display_interval * 10));
+    #[cfg(feature = "lua")]
+    let mut script = match args.script.as_deref().map(script::Script::load).transpose() {
+        Ok(script) => script,
+        Err(err) => {
+            console.error(&format!("Error: {}", err));
+            return Ok(1);
+        }
+    };
+    #[cfg(not(feature = "lua"))]
+    let script: Option<()> = None;
+    // With `--dump-raw`, the windows are written to a CSV next to it as they close.
+    let mut rollup_csv = match (&rollups, &args.dump_raw) {
+        (Some(_), Some(path)) => match rollup::RollupCsv::create(&rollup::csv_path(path)) {
+            Ok(csv) => Some(csv),
+            Err(err) => {
+                console.error(&format!("Error: Failed to create {}: {}", rollup::csv_path(path), err));
+                return Ok(1);
+            }
+        },
+        _ => None,
+    };
+    let gpu_models = if args.show_efficiency { efficiency::known_models() } else { Vec::new() };
+    let highlight = display::should_use_color(args.force_color, args.no_color);
+    let json_format = matches!(output_context.format, output::OutputFormat::Ndjson | output::OutputFormat::Json);
+    let nvlink_enabled = fields.contains(&output::Field::NvLink) && *gpu_type == GpuType::Nvidia;
+    let split_enabled = fields.contains(&output::Field::Split);
+    let bar1_enabled = fields.contains(&output::Field::Bar1) && *gpu_type == GpuType::Nvidia;
+    let vis_vram_enabled = fields.contains(&output::Field::VisVram) && *gpu_type == GpuType::Amd;
+    // Sensor alerts need the readings even when they are not printed.
+    let temps_enabled = fields.contains(&output::Field::Temps) || sensor_temps;
+    let mut schedule = schedule::TickSchedule::new(Instant::now(), display_interval);
+    let mut diagnostics = (args.verbose >= 2).then(|| schedule::Diagnostics::new(Instant::now()));
+    let mut history = match args.max_util_history {
+        Some(samples) => detail::History::with_capacity(display_interval, samples),
+        None => detail::History::new(display_interval),
+    };
+    if let Some(path) = &args.persist_history {
+        if let Err(err) = history.load(Path::new(path)) {
+            console.warning(&format!("Warning: Ignoring the history in {}: {}", path, err));
+        }
+    }
+    let mut shown_view = View::Overview;
+    let mut kernel_log = dmesg::KernelLog::new();
+    let mut event_counts = event::EventCounts::default();
+    // `--bell-on` and `--set-title` only talk to a terminal.
+    let on_terminal = io::stdout().is_terminal();
+    let mut bell = args.bell_on.clone().filter(|_| on_terminal).map(terminal::Bell::new);
+    let title = (args.set_title && on_terminal).then(|| terminal::TitleSetter::new(std::env::var_os("TMUX").is_some()));
+    if let Some(title) = &title {
+        writer.terminal(&title.save());
+    }
+    // `--format table` draws each tick over the last on a terminal, unless it is the only one.
+    let table_format = output_context.format == output::OutputFormat::Table;
+    let redraw = table_format && on_terminal && args.count != Some(1);
+    let box_drawing = table_format && display::grid::supports_unicode();
+
+    let mut exit_code = loop {
+        // A reader that closed the pipe, as `head` does, has all the output it wants.
+        if stop.load(Ordering::Relaxed) || writer.is_closed() {
+            break 0;
+        }
+
+        let tick_started = Instant::now();
+        let tick_seq = schedule.seq();
+        let timestamp = args.timestamp_format.and_then(|format| format.stamp(SystemTime::now(), started.elapsed()));
+        let output_context = &output::OutputContext { tick_seq: Some(tick_seq), timestamp, ..output_context.clone() };
+        // The socket, FIFO, web and UDP sinks always carry NDJSON, whatever the terminal format.
+        let sink_context = output::OutputContext { format: output::OutputFormat::Ndjson, ..output_context.clone() };
+        #[cfg(feature = "network")]
+        let influx_context = output::OutputContext { format: output::OutputFormat::Influx, ..output_context.clone() };
+        if let Some(line) = diagnostics.as_mut().and_then(|diagnostics| diagnostics.report(tick_started, &schedule)) {
+            console.emit(&line);
+        }
+        // GPUs that are not sampled this tick: an asleep one is reported on every tick, a lost
+        // one once before it is dropped.
+        let unsampled: Vec<(GpuInfo, power::DeviceState)> = gpus
+            .iter()
+            .map(|gpu| (gpu.clone(), watch.state(gpu)))
+            .filter(|(_, state)| match state {
+                power::DeviceState::Active => false,
+                power::DeviceState::Asleep => !args.wake,
+                power::DeviceState::Lost => true,
+            })
+            .collect();
+        let awake: Vec<GpuInfo> = gpus.iter().filter(|gpu| unsampled.iter().all(|(other, _)| other.index != gpu.index)).cloned().collect();
+        let vgpus = if vgpu_host { vgpu::query_vgpus(runner) } else { Vec::new() };
+        // A GPU index entered on the keyboard shows that GPU in detail.
+        let view = match pause::view() {
+            View::Detail(index) if !gpus.iter().any(|gpu| gpu.index == index) => View::Overview,
+            view => view,
+        };
+        if pause::view() != shown_view {
+            shown_view = pause::view();
+            let message = match (shown_view, view) {
+                (View::Detail(index), View::Overview) => format!("[NO GPU {}] o and Enter for the overview", index),
+                (View::Detail(index), _) => format!("[GPU {}] o and Enter for the overview", index),
+                (View::Overview, _) => "[OVERVIEW]".to_string(),
+            };
+            status(&mut writer, &console, output_context, &message);
+        }
+        let processes = if args.pid_filter.is_empty() && !split_enabled && !args.by_user && args.layout != Layout::Verbose && view == View::Overview {
+            Vec::new()
+        } else {
+            match process::query_processes(runner, gpu_type) {
+                Ok(processes) => processes,
+                // Without a PID filter the processes only feed the desktop/apps split, the
+                // per-user table and the verbose process tables, which are simply left out where
+                // per-process metrics are unavailable.
+                Err(_) if args.pid_filter.is_empty() => Vec::new(),
+                Err(err) => {
+                    console.error(&format!("Error: {}", err));
+                    break 1;
+                }
+            }
+        };
+        let mut usage_splits = if split_enabled { desktop.split(&processes) } else { HashMap::new() };
+        let mut nvlink_metrics = if nvlink_enabled { nvlink_tracker.update(nvlink::query_nvlink_counters(runner)) } else { HashMap::new() };
+        let mut apertures = match (bar1_enabled, vis_vram_enabled) {
+            (true, _) => aperture::query_nvidia(runner),
+            (_, true) => aperture::query_amdgpu(),
+            _ => HashMap::new(),
+        };
+        let mut temperatures = match gpu_type {
+            GpuType::Nvidia if temps_enabled => temperature::query_nvidia(runner),
+            GpuType::Amd if temps_enabled => temperature::query_amdgpu(),
+            _ => HashMap::new(),
+        };
+
+        let mut collect_time = tick_started.elapsed();
+
+        let results = if high_frequency {
+            let window_end = end.map_or(schedule.next_deadline(), |end| schedule.next_deadline().min(end));
+            let mut window: HashMap<u32, Vec<GpuSnapshot>> = HashMap::new();
+            let mut errors: HashMap<u32, PollResult> = HashMap::new();
+
+            loop {
+                let poll_started = Instant::now();
+                for result in poll_all(runner, backend.as_mut(), &awake, &custom_devices, args.max_retries) {
+                    match result {
+                        PollResult::Ok { snapshot, .. } => {
+                            if let Some(raw_samples) = &mut raw_samples {
+                                raw_samples.push(sampling::RawSample::new(started.elapsed(), tick_seq, &snapshot));
+                            }
+                            window.entry(snapshot.gpu.index).or_default().push(snapshot);
+                        }
+                        PollResult::TransientError { ref gpu, .. } | PollResult::PermanentError { ref gpu, .. } => {
+                            errors.insert(gpu.index, result);
+                        }
+                    }
+                }
+                collect_time += poll_started.elapsed();
+
+                if Instant::now() + sample_interval >= window_end || sleep_unless_stopped(sample_interval, stop) {
+                    break;
+                }
+            }
+
+            awake
+                .iter()
+                .chain(custom_devices.iter().flat_map(|(_, devices)| devices))
+                .filter_map(|gpu| match window.get(&gpu.index).and_then(|samples| sampling::aggregate(samples)) {
+                    Some(snapshot) => Some(PollResult::Ok { snapshot, retries: 0 }),
+                    None => errors.remove(&gpu.index),
+                })
+                .collect()
+        } else {
+            let results = poll_all(runner, backend.as_mut(), &awake, &custom_devices, args.max_retries);
+            collect_time = tick_started.elapsed();
+            if let Some(raw_samples) = &mut raw_samples {
+                for result in &results {
+                    if let PollResult::Ok { snapshot, .. } = result {
+                        raw_samples.push(sampling::RawSample::new(started.elapsed(), tick_seq, snapshot));
+                    }
+                }
+            }
+            results
+        };
+
+        if let Some(self_stats) = &mut self_stats {
+            self_stats.record_tick(collect_time);
+        }
+        if let Some(diagnostics) = &mut diagnostics {
+            diagnostics.record(collect_time);
+        }
+        startup::claim_first_sample();
+
+        let mut all_unchanged = deltas.is_some();
+        // `--aggregate` collects the tick's samples and prints them together at its end.
+        let mut aggregated = args.aggregate.then(Vec::new);
+        // `--layout compact` collects one character per GPU, printed as one line at its end.
+        let mut compact = (args.layout == Layout::Compact).then(Vec::new);
+        // `--format table` collects a row per GPU, drawn as one table at its end.
+        let mut grid = table_format.then(Vec::new);
+        #[cfg(feature = "network")]
+        let mut udp_records = Vec::new();
+        #[cfg(feature = "lua")]
+        let mut script_records = Vec::new();
+        // The tick's samples, for the bell and the title.
+        let mut sampled = (bell.is_some() || title.is_some()).then(Vec::new);
+        // A source that stopped working for every GPU is replaced by the next one before they
+        // would be dropped, unless it was forced.
+        let failed = |gpu: &GpuInfo| results.iter().any(|result| matches!(result, PollResult::TransientError { gpu: failed, .. } | PollResult::PermanentError { gpu: failed, .. } if failed.index == gpu.index));
+        let failing = args.backend.is_none() && !awake.is_empty() && awake.iter().all(|gpu| failed(gpu) && failures.get(&gpu.index).is_some_and(|count| count + 2 >= MAX_CONSECUTIVE_FAILURES));
+        if let Some((replacement, _)) = failing.then(|| backend::fallback(runner, gpu_type, backend.source(), sample_interval, args.retry, &awake)).flatten() {
+            for gpu in &awake {
+                let kind = event::EventKind::SourceChanged { from: backend.source().to_string(), to: replacement.source().to_string() };
+                let changed = event::Event::new(gpu, alert::Severity::Warning, kind);
+                let records = json_format.then(|| single_document.then_some(&mut document));
+                let params = changed.syslog_params(&output_context.labels);
+                report_event(&changed, &mut writer, &console, output_context, records, syslog.as_mut(), params, &mut event_counts);
+                failures.remove(&gpu.index);
+            }
+            backend = replacement;
+        }
+        for result in results {
+            match result {
+                PollResult::Ok { mut snapshot, .. } => {
+                    failures.remove(&snapshot.gpu.index);
+                    snapshot.nvlink = nvlink_metrics.remove(&snapshot.gpu.index);
+                    snapshot.usage_split = usage_splits.remove(&snapshot.gpu.index);
+                    snapshot.aperture = apertures.remove(&snapshot.gpu.index);
+                    // amd-smi reports every sensor itself; they are kept where sysfs has none.
+                    let queried = temperatures.remove(&snapshot.gpu.index);
+                    snapshot.temperatures = if temps_enabled { queried.or(snapshot.temperatures.take()) } else { None };
+
+                    // Where the tool has no per-process utilization, a sum over the PIDs would be
+                    // a made-up 0%; the whole GPU's is kept, as the warning at startup says.
+                    if !args.pid_filter.is_empty() && process::reports_utilization(gpu_type) {
+                        let utilization = processes
+                            .iter()
+                            .filter(|process| process.gpu_index == snapshot.gpu.index && args.pid_filter.contains(&process.pid))
+                            .filter_map(|process| process.utilization)
+                            .fold(0.0, |total, utilization| total + widen(utilization));
+                        snapshot.utilization = clamp_percent(utilization.min(100.0));
+                    }
+                    if let Some(idle) = &mut idle {
+                        snapshot.activity = Some(idle.update(&snapshot, Instant::now()));
+                    }
+                    if args.show_efficiency {
+                        snapshot.efficiency = Some(efficiency::efficiency(&gpu_models, &snapshot, args.efficiency_precision));
+                    }
+                    statistics.record(&snapshot);
+                    if let Some(closed) = rollups.as_mut().and_then(|rollups| rollups.record(&snapshot, SystemTime::now())) {
+                        report_rollup(&closed, &mut writer, &console, output_context, single_document.then_some(&mut document), &mut rollup_csv);
+                    }
+                    if let Some(sampled) = &mut sampled {
+                        sampled.push(snapshot.clone());
+                    }
+                    if let Some(report) = &mut html_report {
+                        report.record(&snapshot);
+                    }
+                    let alert_update = alerts.update(&snapshot);
+                    for alert in &alert_update.fired {
+                        if let Some(notifier) = &mut notifier {
+                            notifier.notify(alert);
+                        }
+                    }
+                    // Each alert is an event when it fires and when it resolves; JSON output
+                    // records both, and throttling starting or stopping.
+                    let fired = alert_update.fired.iter().map(event::Event::alert_firing);
+                    let resolved = alert_update.resolved.iter().map(|(alert, episode)| event::Event::alert_resolved(alert, episode));
+                    for event in fired.chain(resolved).chain(throttling.update(&snapshot)) {
+                        let records = (json_format && golden.is_none()).then(|| single_document.then_some(&mut document));
+                        let params = syslog::sample_params(&snapshot, &output_context.labels);
+                        report_event(&event, &mut writer, &console, output_context, records, syslog.as_mut(), params, &mut event_counts);
+                    }
+                    for exceeded in budgets.as_mut().map(|budgets| budgets.record(&snapshot, Instant::now())).unwrap_or_default() {
+                        status(&mut writer, &console, output_context, &exceeded.message());
+                        if let Some(enforcer) = enforcer.as_mut().filter(|enforcer| !enforcer.stopped()) {
+                            status(&mut writer, &console, output_context, &format!("Stopping the command: SIG{} to its process group", args.budget_signal.as_deref().unwrap_or(budget::DEFAULT_SIGNAL)));
+                            if let Err(err) = enforcer.stop(runner, Instant::now()) {
+                                console.error(&format!("Error: {}", err));
+                            }
+                        }
+                    }
+                    if let Some(syslog) = &mut syslog {
+                        let text = format!("GPU {} utilization {:.1}%", snapshot.gpu.index, snapshot.utilization);
+                        syslog.send(syslog::Severity::Info, "sample", syslog::sample_params(&snapshot, &output_context.labels), &text);
+                    }
+                    // `--output-fields` trims what is printed; alerts, statistics and golden files
+                    // still see every metric.
+                    history.record(&snapshot);
+                    let mut printed = args.output_fields.apply(&snapshot);
+                    if !fields.contains(&output::Field::Temps) {
+                        // Collected for `--alert-temp sensor=...` only.
+                        printed.temperatures = None;
+                    }
+                    if socket.is_some() || fifo.is_some() || web.is_some() || udp.is_some() || tcp.is_some() || script.is_some() {
+                        let record = output::format_snapshot(&printed, &sink_context);
+                        #[cfg(feature = "web")]
+                        if let Some(web) = &web {
+                            web.publish(&record);
+                        }
+                        if let Some(socket) = &mut socket {
+                            socket.send(&record);
+                        }
+                        if let Some(fifo) = &mut fifo {
+                            fifo.send(&record);
+                        }
+                        #[cfg(feature = "network")]
+                        if let Some(tcp) = &tcp {
+                            tcp.send(&record);
+                        }
+                        #[cfg(feature = "lua")]
+                        if script.is_some() {
+                            script_records.push(record.clone());
+                        }
+                        #[cfg(feature = "network")]
+                        if udp.is_some() {
+                            udp_records.push(record);
+                        }
+                    }
+                    #[cfg(feature = "network")]
+                    if let Some(influx) = &influx {
+                        influx.send(&output::format_snapshot(&printed, &influx_context));
+                    }
+                    let change = deltas.as_mut().map(|deltas| deltas.update(&printed));
+                    let unchanged = change == Some(delta::Change::Unchanged);
+                    all_unchanged &= unchanged;
+
+                    if let Some(golden) = &golden {
+                        // Golden-file mode reports divergences instead of the metrics.
+                        let lines = match golden.get(&snapshot.gpu.index) {
+                            Some(expected) => golden::format_diff(&golden::compare_snapshots(expected, &snapshot, args.golden_tolerance)),
+                            None => vec![format!("GPU {} is not in the golden file", snapshot.gpu.index)],
+                        };
+                        diverged |= !lines.is_empty();
+                        for line in lines {
+                            writer.line(&output::prefix_text(&line, output_context));
+                        }
+                    } else if args.rollup_only {
+                        // `--rollup-only` prints the windows instead.
+                    } else if let Some(aggregated) = &mut aggregated {
+                        aggregated.push(printed);
+                    } else if let Some(grid) = &mut grid {
+                        grid.push(display::grid::snapshot_row(&printed));
+                    } else if unchanged {
+                        // `--diff-output` skips samples without changes.
+                    } else if let (Some(delta::Change::Changed(delta)), true) = (&change, json_format) {
+                        writer.line(&delta::format_json(delta, output_context));
+                    } else if output_context.format == output::OutputFormat::Msgpack {
+                        writer.bytes(&msgpack::encode_snapshot(&printed, output_context));
+                    } else if output_context.format == output::OutputFormat::Prometheus {
+                        page.push(printed);
+                    } else if output_context.format == output::OutputFormat::Statsd {
+                        writer.line(&statsd::format_metrics(&printed, &args.statsd, output_context));
+                    } else if single_document {
+                        document.push(output::format_snapshot(&printed, output_context));
+                    } else if let Some(template) = &args.template {
+                        writer.line(&output::prefix_text(&template.render(&printed), output_context));
+                    } else if let Some(compact) = &mut compact {
+                        compact.push((printed.gpu.index, layout::format_snapshot(&printed, Layout::Compact)));
+                    } else if let View::Detail(index) = view {
+                        if printed.gpu.index == index {
+                            let gpu_processes: Vec<&process::GpuProcess> = processes.iter().filter(|process| process.gpu_index == index).collect();
+                            for line in detail::format_detail(&printed, &history, &gpu_processes) {
+                                writer.line(&output::prefix_text(&line, output_context));
+                            }
+                        }
+                    } else if args.layout == Layout::Verbose {
+                        let gpu_processes: Vec<&process::GpuProcess> = processes.iter().filter(|process| process.gpu_index == printed.gpu.index).collect();
+                        let block = layout::format_snapshot(&printed, Layout::Verbose);
+                        for line in block.lines().map(str::to_string).chain(layout::format_processes(&gpu_processes)) {
+                            writer.line(&output::prefix_text(&line, output_context));
+                        }
+                    } else {
+                        writer.line(&output::format_snapshot(&printed, output_context));
+                    }
+
+                    let hidden = matches!(view, View::Detail(index) if index != snapshot.gpu.index);
+                    if output_context.format == output::OutputFormat::Text && !unchanged && !args.rollup_only && aggregated.is_none() && compact.is_none() && !hidden {
+                        for vgpu in vgpus.iter().filter(|vgpu| Some(&vgpu.parent_bus_id) == snapshot.gpu.bus_id.as_ref()) {
+                            writer.line(&output::prefix_text(&vgpu::format_vgpu(vgpu), output_context));
+                        }
+                    }
+                }
+                PollResult::TransientError { gpu, message, .. } | PollResult::PermanentError { gpu, message, .. } => {
+                    all_unchanged = false;
+                    let count = failures.entry(gpu.index).or_insert(0);
+                    *count += 1;
+                    // A status bar has room for one character, not the error message.
+                    if let Some(compact) = &mut compact {
+                        compact.push((gpu.index, "?".to_string()));
+                    } else if console.shows_warnings() {
+                        status(&mut writer, &console, output_context, &format!("GPU {} Error: {}", gpu.index, message));
+                    }
+
+                    if *count >= MAX_CONSECUTIVE_FAILURES {
+                        console.warning(&format!("GPU {} failed {} times in a row, dropping it from monitoring", gpu.index, count));
+                        gpus.retain(|g| g.index != gpu.index);
+                        for (_, devices) in custom_devices.iter_mut() {
+                            devices.retain(|g| g.index != gpu.index);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (gpu, state) in &unsampled {
+            if *state == power::DeviceState::Lost {
+                all_unchanged = false;
+                console.warning(&format!("Warning: GPU {} was lost (driver unloaded or device removed), dropping it from monitoring", gpu.index));
+                let detached = event::Event::new(gpu, alert::Severity::Warning, event::EventKind::Detached);
+                let records = json_format.then(|| single_document.then_some(&mut document));
+                let params = detached.syslog_params(&output_context.labels);
+                report_event(&detached, &mut writer, &console, output_context, records, syslog.as_mut(), params, &mut event_counts);
+                gpus.retain(|g| g.index != gpu.index);
+                failures.remove(&gpu.index);
+            }
+            if socket.is_some() || fifo.is_some() || web.is_some() || udp.is_some() || tcp.is_some() {
+                let record = output::format_state(gpu, *state, &sink_context);
+                #[cfg(feature = "web")]
+                if let Some(web) = &web {
+                    web.publish(&record);
+                }
+                if let Some(socket) = &mut socket {
+                    socket.send(&record);
+                }
+                if let Some(fifo) = &mut fifo {
+                    fifo.send(&record);
+                }
+                #[cfg(feature = "network")]
+                if let Some(tcp) = &tcp {
+                    tcp.send(&record);
+                }
+                #[cfg(feature = "network")]
+                if udp.is_some() {
+                    udp_records.push(record);
+                }
+            }
+            #[cfg(feature = "network")]
+            if let Some(influx) = &influx {
+                influx.send(&output::format_state(gpu, *state, &influx_context));
+            }
+
+            // Golden files, aggregates, Prometheus pages and StatsD gauges only hold metrics; a
+            // compact line marks the GPU with `-`.
+            match (output_context.format, &mut compact) {
+                _ if golden.is_some() || aggregated.is_some() => {}
+                (_, Some(compact)) => compact.push((gpu.index, "-".to_string())),
+                (output::OutputFormat::Table, _) => grid.get_or_insert_with(Vec::new).push(display::grid::state_row(gpu, *state)),
+                (output::OutputFormat::Prometheus | output::OutputFormat::Statsd, _) => {}
+                (output::OutputFormat::Msgpack, _) => writer.bytes(&msgpack::encode_state(gpu, *state, output_context)),
+                _ if single_document => document.push(output::format_state(gpu, *state, output_context)),
+                _ => writer.line(&output::format_state(gpu, *state, output_context)),
+            }
+        }
+
+        // `--send-to` sends the tick's records together.
+        #[cfg(feature = "network")]
+        if let Some(udp) = &mut udp {
+            udp.send(&udp_records);
+        }
+
+        #[cfg(feature = "lua")]
+        if let Some(running) = &mut script {
+            match running.on_snapshot(&script_records) {
+                Ok(actions) => {
+                    let mut exit = None;
+                    for action in actions {
+                        match action {
+                            script::Action::Print(text) => status(&mut writer, &console, output_context, &text),
+                            // An alert is an event, so `--quiet` does not silence it and `--notify`
+                            // sends it.
+                            script::Action::Alert { message, gpu } => {
+                                let mut monitored = gpus.iter().chain(custom_devices.iter().flat_map(|(_, devices)| devices));
+                                let about = match gpu {
+                                    Some(index) => monitored.find(|g| g.index == index),
+                                    None => monitored.next(),
+                                };
+                                let Some(about) = about else {
+                                    console.error(&format!("Error: --script alert about GPU {}, which is not monitored: {}", gpu.unwrap_or_default(), message));
+                                    continue;
+                                };
+                                let event = event::Event::script_alert(about, &message);
+                                if let Some(notifier) = &mut notifier {
+                                    notifier.notify_script(&event);
+                                }
+                                let records = json_format.then(|| single_document.then_some(&mut document));
+                                let params = event.syslog_params(&output_context.labels);
+                                report_event(&event, &mut writer, &console, output_context, records, syslog.as_mut(), params, &mut event_counts);
+                            }
+                            script::Action::Exit(code) => exit = Some(code),
+                        }
+                    }
+                    if let Some(code) = exit {
+                        break code;
+                    }
+                }
+                Err(err) => {
+                    console.warning(&format!("Warning: --script failed ({}), it is not run any more", err));
+                    script = None;
+                }
+            }
+        }
+
+        if let Some(compact) = &mut compact {
+            if !compact.is_empty() {
+                compact.sort_by_key(|(index, _)| *index);
+                let line: String = compact.iter().map(|(_, block)| block.as_str()).collect();
+                writer.line(&output::prefix_text(&line, output_context));
+            }
+        }
+
+        if let Some(grid) = grid.as_mut().filter(|grid| !grid.is_empty()) {
+            let lines = display::grid::format_grid(grid, box_drawing);
+            if redraw {
+                writer.block(lines);
+            } else {
+                for line in &lines {
+                    writer.line(line);
+                }
+            }
+        }
+
+        match &mut aggregated {
+            Some(aggregated) if aggregated.is_empty() => {}
+            Some(aggregated) if json_format => writer.line(&aggregate::format_json(aggregated, output_context)),
+            Some(aggregated) => {
+                for line in aggregate::format_table(aggregated, highlight) {
+                    writer.line(&output::prefix_text(&line, output_context));
+                }
+            }
+            None => {}
+        }
+
+        if args.by_user && golden.is_none() {
+            let exclude = args.exclude_desktop.then_some(desktop);
+            let usage = users::aggregate(&processes, |pid| Some(user_names.name(users::read_uid(pid)?)), exclude);
+            gpu_hours.record(&usage, display_interval);
+
+            if !json_format {
+                for line in users::format_table(&usage) {
+                    writer.line(&output::prefix_text(&line, output_context));
+                }
+            } else if single_document {
+                document.push(users::format_json(&usage, output_context));
+            } else {
+                writer.line(&users::format_json(&usage, output_context));
+            }
+        }
+
+        if all_unchanged && output_context.format == output::OutputFormat::Text && golden.is_none() {
+            writer.line(&output::prefix_text("[unchanged]", output_context));
+        }
+
+        if let Some(sampled) = sampled.filter(|sampled| !sampled.is_empty()) {
+            if bell.as_mut().is_some_and(|bell| bell.update(&sampled, Instant::now())) {
+                writer.terminal(terminal::BELL);
+            }
+            if let Some(title) = &title {
+                writer.terminal(&title.title(&terminal::format_title(&sampled)));
+            }
+        }
+
+        if diverged {
+            break 1;
+        }
+
+        if gpus.is_empty() && custom_devices.iter().all(|(_, devices)| devices.is_empty()) {
+            console.error("Error: No GPUs left to monitor");
+            break 1;
+        }
+
+        for &pid in &args.pid_filter {
+            if !exited_pids.contains(&pid) && !process::is_process_alive(pid) {
+                if console.shows_info() {
+                    status(&mut writer, &console, output_context, &format!("[PID {} exited]", pid));
+                }
+                exited_pids.push(pid);
+            }
+        }
+
+        if args.exit_on_pid_exit && !args.pid_filter.is_empty() && exited_pids.len() == args.pid_filter.len() {
+            break 0;
+        }
+
+        if let Some(pid) = args.follow_pid {
+            if let process::ProcessState::Exited(code) = process::process_state(pid) {
+                if console.shows_info() {
+                    status(&mut writer, &console, output_context, &format!("[PID {} exited]", pid));
+                }
+                break code.unwrap_or(0);
+            }
+        }
+
+        ticks += 1;
+        if args.count.is_some_and(|count| ticks >= count) {
+            break 0;
+        }
+
+        // `d` shows the GPU drivers' kernel messages below the samples.
+        if pause::kernel_log_shown() {
+            if !kernel_log.is_open() {
+                status(&mut writer, &console, output_context, "[KERNEL LOG] GPU driver errors and warnings, d and Enter to hide");
+            }
+            if kernel_log.due(Instant::now()) {
+                match dmesg::query(runner) {
+                    Ok(messages) => {
+                        for message in kernel_log.update(messages) {
+                            status(&mut writer, &console, output_context, &dmesg::format_message(&message, dmesg::terminal_width(), highlight));
+                        }
+                    }
+                    Err(err) => {
+                        status(&mut writer, &console, output_context, &format!("[KERNEL LOG] Cannot read the kernel log: {}", err));
+                        kernel_log.fail();
+                    }
+                }
+            }
+        } else if kernel_log.is_open() {
+            kernel_log.close();
+            status(&mut writer, &console, output_context, "[KERNEL LOG OFF]");
+        }
+
+        // While paused nothing is polled or printed; the ticks due meanwhile are skipped like
+        // those collection fell behind on.
+        if pause::is_paused() {
+            status(&mut writer, &console, output_context, "[PAUSED] r to resume");
+            if pause::wait_until_resumed(stop) {
+                break 0;
+            }
+            status(&mut writer, &console, output_context, "[RESUMED]");
+        }
+
+        // A command that outlived the budget signal's grace period is killed.
+        match enforcer.as_mut().map(|enforcer| enforcer.tick(runner, Instant::now())) {
+            Some(Ok(true)) => status(&mut writer, &console, output_context, "The command outlived the grace period: SIGKILL to its process group"),
+            Some(Err(err)) => console.error(&format!("Error: {}", err)),
+            _ => {}
+        }
+
+        // Ticks are due at fixed times, so the time spent collecting does not add up to drift.
+        // In high-frequency mode the sampling window already ran until the next tick was due.
+        let deadline = schedule.advance(Instant::now());
+        let deadline = match &mut jitter {
+            Some(jitter) if !high_frequency => deadline + jitter.apply(display_interval) - display_interval,
+            _ => deadline,
+        };
+        if sleep_until_unless_stopped(end.map_or(deadline, |end| deadline.min(end)), stop) {
+            break 0;
+        }
+        if end.is_some_and(|end| Instant::now() >= end) {
+            if console.shows_info() {
+                status(&mut writer, &console, output_context, "[--duration elapsed]");
+            }
+            break 0;
+        }
+    };
+    // The last table stays on screen, and the summary follows it.
+    live::release();
+
+    if let Some(path) = &args.persist_history {
+        if let Err(err) = history.save(Path::new(path)) {
+            console.warning(&format!("Warning: Failed to save the history to {}: {}", path, err));
+        }
+    }
+
+    // With exec or --launch, the command ending first stops monitoring before the duration.
+    if child.is_some() && end.is_some_and(|end| Instant::now() < end) && stop.load(Ordering::Relaxed) && console.shows_info() {
+        status(&mut writer, &console, output_context, "[The command exited before the --duration elapsed]");
+    }
+
+    if let Some(title) = &title {
+        writer.terminal(&title.restore());
+    }
+
+    // The windows open at the end cover only part of their time.
+    for open in rollups.as_mut().map(rollup::RollupTracker::finish).unwrap_or_default() {
+        report_rollup(&open, &mut writer, &console, output_context, single_document.then_some(&mut document), &mut rollup_csv);
+    }
+
+    match document.as_slice() {
+        [] => {}
+        [object] => writer.write(object),
+        objects => writer.write(&format!("[{}]", objects.join(","))),
+    }
+
+    if output_context.format == output::OutputFormat::Prometheus && !page.is_empty() {
+        let page = match &self_stats {
+            Some(self_stats) => prometheus::format_page_with_self_cpu(&page, output_context, self_stats.cpu_seconds()),
+            None => prometheus::format_page(&page, output_context),
+        };
+        match &args.prometheus_file {
+            Some(path) => {
+                if let Err(err) = prometheus::write_page(Path::new(path), &page) {
+                    console.error(&format!("Error: Failed to write {}: {}", path, err));
+                    exit_code = 1;
+                }
+            }
+            None => writer.write(&page),
+        }
+    }
+
+    if enforcer.as_ref().is_some_and(budget::Enforcer::stopped) {
+        exit_code = budget::EXIT_CODE;
+    } else if exit_code == 0 && args.critical_exit_code != 0 && alerts.critical_fired() {
+        exit_code = args.critical_exit_code;
+    }
+
+    if output_context.format == output::OutputFormat::Text && console.shows_info() {
+        for line in statistics.format_summary(output_context.precision) {
+            writer.line(&output::prefix_text(&line, output_context));
+        }
+        for line in alert::format_history(&alerts.history(Instant::now())) {
+            writer.line(&output::prefix_text(&line, output_context));
+        }
+        for line in event_counts.format_summary() {
+            writer.line(&output::prefix_text(&line, output_context));
+        }
+        if let Some(budgets) = &budgets {
+            for line in budgets.format_summary() {
+                writer.line(&output::prefix_text(&line, output_context));
+            }
+        }
+        if args.by_user {
+            for line in gpu_hours.format_summary() {
+                writer.line(&output::prefix_text(&line, output_context));
+            }
+        }
+        if let Some(self_stats) = &self_stats {
+            for line in self_stats.format_summary(started.elapsed()) {
+                writer.line(&output::prefix_text(&line, output_context));
+            }
+        }
+    }
+
+    if let (Some(raw_samples), Some(path)) = (&raw_samples, &args.dump_raw) {
+        if raw_samples.dropped() > 0 {
+            console.warning(&format!("Warning: Raw sample buffer was full, the oldest {} samples were dropped", raw_samples.dropped()));
+        }
+        if let Err(err) = raw_samples.write_csv(path) {
+            console.error(&format!("Error: Failed to write raw samples {}: {}", path, err));
+        }
+    }
+
+    if let (Some(report), Some(path)) = (&html_report, &args.export_html) {
+        match report.write(path, &statistics) {
+            Ok(()) => console.info(&format!("HTML report written to {}", path)),
+            Err(err) => console.error(&format!("Error: Failed to write HTML report {}: {}", path, err)),
+        }
+    }
+
+    Ok(exit_code)
+}
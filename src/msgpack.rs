@@ -0,0 +1,314 @@
+//! `--format msgpack`: each sample is one MessagePack map with the same keys as the JSON
+//! output, preceded by its length as a 4-byte big-endian integer so a reader can split the
+//! stream without parsing it.
+
+use std::fmt;
+use std::io::{self, Cursor, Read};
+
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::efficiency::Efficiency;
+use crate::json::{self, Value};
+use crate::output::{aperture_fields, OutputContext, Timestamp};
+use crate::power::DeviceState;
+use crate::schema::SCHEMA_VERSION;
+use crate::{GpuInfo, GpuSnapshot};
+
+/// Largest frame `read_frame` accepts; a sample is a few hundred bytes, so anything bigger
+/// means the stream is not gpuatop MessagePack output.
+pub const MAX_FRAME_LEN: usize = 1 << 20;
+
+/// Key-value pairs serialized as a map, in their order.
+struct Entries<K, V>(Vec<(K, V)>);
+
+impl<K: Serialize, V: Serialize> Serialize for Entries<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+/// A string for ISO 8601, a number otherwise, as in JSON.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Time<'a> {
+    Iso8601(&'a str),
+    Unix(u64),
+    Relative(f64),
+}
+
+impl<'a> From<&'a Timestamp> for Time<'a> {
+    fn from(timestamp: &'a Timestamp) -> Self {
+        match timestamp {
+            Timestamp::Iso8601(time) => Time::Iso8601(time),
+            Timestamp::Unix(value) => Time::Unix(*value),
+            Timestamp::Relative(elapsed) => Time::Relative(elapsed.as_secs_f64()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Engine {
+    ns_total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    busy_pct: Option<f64>,
+}
+
+/// A sample, with the keys and in the order of [`crate::output::format_snapshot`].
+#[derive(Serialize)]
+struct SnapshotRecord<'a> {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<&'a str>,
+    gpu: u32,
+    name: &'a str,
+    utilization: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    utilization_max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_used_mib: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_total_mib: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature_c: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperatures: Option<Entries<String, f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power_w: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clock_mhz: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle_reasons: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<Entries<&'a String, &'a String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nvlink_tx_kib_per_s: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nvlink_rx_kib_per_s: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nvlink_replay_errors: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nvlink_crc_errors: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    desktop_utilization: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    apps_utilization: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_bandwidth_utilization: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_read_gbps: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_write_gbps: Option<f32>,
+    #[serde(flatten)]
+    aperture: Entries<&'static str, u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    efficiency_tflops_per_w: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engines: Option<Entries<&'a str, Engine>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tick_seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<Time<'a>>,
+}
+
+/// A GPU that was not sampled, as [`crate::output::format_state`].
+#[derive(Serialize)]
+struct StateRecord<'a> {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<&'a str>,
+    gpu: u32,
+    name: &'a str,
+    state: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<Entries<&'a String, &'a String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tick_seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<Time<'a>>,
+}
+
+fn labels(context: &OutputContext) -> Option<Entries<&String, &String>> {
+    (!context.labels.is_empty()).then(|| Entries(context.labels.sorted()))
+}
+
+/// Encodes one sample as a length-prefixed MessagePack map.
+pub fn encode_snapshot(snapshot: &GpuSnapshot, context: &OutputContext) -> Vec<u8> {
+    let bandwidth = snapshot.memory_bandwidth.as_ref();
+    frame(&SnapshotRecord {
+        schema_version: SCHEMA_VERSION,
+        hostname: context.hostname.as_deref(),
+        gpu: snapshot.gpu.index,
+        name: &snapshot.gpu.name,
+        utilization: snapshot.utilization,
+        utilization_max: snapshot.utilization_max,
+        memory_used_mib: snapshot.memory_used_mib,
+        memory_total_mib: snapshot.memory_total_mib,
+        temperature_c: snapshot.temperature_c,
+        temperatures: snapshot.temperatures.as_ref().map(|temperatures| Entries(temperatures.iter().map(|(sensor, value)| (sensor.to_string(), *value)).collect())),
+        power_w: snapshot.power_w,
+        clock_mhz: snapshot.clock_mhz,
+        throttle_reasons: snapshot.throttle_reasons.as_deref(),
+        labels: labels(context),
+        nvlink_tx_kib_per_s: snapshot.nvlink.as_ref().map(|nvlink| nvlink.tx_kib_per_s),
+        nvlink_rx_kib_per_s: snapshot.nvlink.as_ref().map(|nvlink| nvlink.rx_kib_per_s),
+        nvlink_replay_errors: snapshot.nvlink.as_ref().map(|nvlink| nvlink.replay_errors),
+        nvlink_crc_errors: snapshot.nvlink.as_ref().map(|nvlink| nvlink.crc_errors),
+        desktop_utilization: snapshot.usage_split.as_ref().map(|split| split.desktop),
+        apps_utilization: snapshot.usage_split.as_ref().map(|split| split.apps),
+        memory_bandwidth_utilization: bandwidth.and_then(|bandwidth| bandwidth.utilization_pct),
+        memory_read_gbps: bandwidth.and_then(|bandwidth| bandwidth.read_gbps),
+        memory_write_gbps: bandwidth.and_then(|bandwidth| bandwidth.write_gbps),
+        aperture: Entries(snapshot.aperture.as_ref().map(aperture_fields).unwrap_or_default()),
+        idle_seconds: snapshot.activity.map(|activity| activity.idle_seconds()),
+        efficiency_tflops_per_w: snapshot.efficiency.and_then(Efficiency::value),
+        engines: snapshot.engines.as_ref().map(|engines| Entries(engines.iter().map(|engine| (engine.engine.as_str(), Engine { ns_total: engine.ns_total, busy_pct: engine.busy_pct })).collect())),
+        source: snapshot.source.as_deref(),
+        tick_seq: context.tick_seq,
+        ts: context.timestamp.as_ref().map(Time::from),
+    })
+}
+
+/// Encodes the record of a GPU that was not sampled, as [`crate::output::format_state`].
+pub fn encode_state(gpu: &GpuInfo, state: DeviceState, context: &OutputContext) -> Vec<u8> {
+    frame(&StateRecord {
+        schema_version: SCHEMA_VERSION,
+        hostname: context.hostname.as_deref(),
+        gpu: gpu.index,
+        name: &gpu.name,
+        state: state.as_str(),
+        labels: labels(context),
+        tick_seq: context.tick_seq,
+        ts: context.timestamp.as_ref().map(Time::from),
+    })
+}
+
+/// `record` as a MessagePack map, prefixed with its length.
+fn frame(record: &impl Serialize) -> Vec<u8> {
+    let mut frame = vec![0; 4];
+    // Writing to a Vec cannot fail, and records only hold what MessagePack can express.
+    rmp_serde::encode::write_named(&mut frame, record).expect("records encode to MessagePack");
+    let len = (frame.len() - 4) as u32;
+    frame[..4].copy_from_slice(&len.to_be_bytes());
+    frame
+}
+
+/// Reads the next frame's payload. Returns `None` at a clean end of stream, between frames.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut prefix = [0; 4];
+    let mut filled = 0;
+
+    while filled < prefix.len() {
+        match reader.read(&mut prefix[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated frame length")),
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Frame of {} bytes is too large", len)));
+    }
+
+    let mut payload = vec![0; len];
+    reader
+        .read_exact(&mut payload)
+        .map_err(|err| io::Error::new(err.kind(), format!("Truncated frame: {}", err)))?;
+    Ok(Some(payload))
+}
+
+/// A JSON value deserialized with serde, so MessagePack decodes to what JSON output would be.
+struct JsonValue(Value);
+
+impl<'de> Deserialize<'de> for JsonValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(JsonVisitor).map(JsonValue)
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Value;
+
+    // bin and ext have no JSON counterpart and gpuatop never writes them.
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a value with a JSON counterpart")
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Number(value as f64))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Number(value as f64))
+    }
+
+    // Via the shortest decimal form, so 0.1f32 prints as 0.1 rather than 0.10000000149011612.
+    fn visit_f32<E>(self, value: f32) -> Result<Value, E> {
+        Ok(Value::Number(value.to_string().parse().unwrap_or(f64::NAN)))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(Value::Number(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(JsonValue(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut members = Vec::new();
+        while let Some((JsonValue(key), JsonValue(value))) = map.next_entry()? {
+            let key = match key {
+                Value::String(key) => key,
+                // JSON objects only have string keys.
+                other => other.to_json(),
+            };
+            members.push((key, value));
+        }
+        Ok(Value::Object(members))
+    }
+}
+
+/// Decodes one MessagePack value, e.g. a frame payload from [`read_frame`]. Arrays and maps
+/// may nest [`json::MAX_DEPTH`] levels deep, as in JSON.
+pub fn decode(input: &[u8]) -> Result<Value, String> {
+    let mut deserializer = rmp_serde::Deserializer::new(Cursor::new(input));
+    // rmp-serde refuses to reach the limit itself.
+    deserializer.set_max_depth(json::MAX_DEPTH + 1);
+    let JsonValue(value) = JsonValue::deserialize(&mut deserializer).map_err(|err| err.to_string())?;
+
+    if deserializer.position() != input.len() as u64 {
+        return Err(format!("Trailing bytes at byte {}", deserializer.position()));
+    }
+
+    Ok(value)
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::alert::{Alert, AlertKind, Severity};
+use crate::event::Event;
+use crate::live;
+use crate::process::effective_uid;
+
+/// Minimum time between two desktop notifications for the same alert kind.
+const RATE_LIMIT: Duration = Duration::from_secs(60);
+
+fn urgency(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "low",
+        Severity::Warning => "normal",
+        Severity::Critical => "critical",
+    }
+}
+
+fn session_bus_available() -> bool {
+    if env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some() {
+        return true;
+    }
+
+    effective_uid().is_some_and(|uid| Path::new(&format!("/run/user/{}/bus", uid)).exists())
+}
+
+/// What notifications are rate limited by: the kind of an alert, or the `--script` for all of its
+/// alerts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Source {
+    Alert(AlertKind),
+    Script,
+}
+
+/// What becomes of an alert that is not rate limited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// A desktop notification: `notify-send` run with these arguments.
+    Desktop(Vec<String>),
+    /// A line on stderr, without a session bus.
+    Stderr(String),
+}
+
+/// Sends freedesktop notifications for alerts through `notify-send`. Notifications are sent
+/// from a background thread so a slow notification daemon never delays sampling; without a
+/// session bus (headless, ssh) alerts are written to stderr instead.
+#[derive(Debug)]
+pub struct Notifier {
+    bus_available: bool,
+    last_sent: HashMap<Source, Instant>,
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self::with_bus(session_bus_available())
+    }
+
+    /// A notifier that assumes a session bus is or is not reachable.
+    pub fn with_bus(bus_available: bool) -> Self {
+        Notifier { bus_available, last_sent: HashMap::new() }
+    }
+
+    /// Decides how `alert`, fired at `now`, is delivered: `None` when an alert of the same kind
+    /// was delivered less than a minute before. Suppressed alerts do not extend the minute.
+    pub fn delivery(&mut self, alert: &Alert, now: Instant) -> Option<Delivery> {
+        self.deliver(Source::Alert(alert.kind), alert.severity, &alert.message(), now)
+    }
+
+    /// Decides how the `script_alert` event `event` is delivered. The script's alerts share one
+    /// rate limit, as if they were one more alert kind.
+    pub fn script_delivery(&mut self, event: &Event, now: Instant) -> Option<Delivery> {
+        self.deliver(Source::Script, event.severity, &event.message, now)
+    }
+
+    fn deliver(&mut self, source: Source, severity: Severity, message: &str, now: Instant) -> Option<Delivery> {
+        if self.last_sent.get(&source).is_some_and(|sent| now.saturating_duration_since(*sent) < RATE_LIMIT) {
+            return None;
+        }
+        self.last_sent.insert(source, now);
+
+        if !self.bus_available {
+            return Some(Delivery::Stderr(format!("gpuatop {}: {}", severity, message)));
+        }
+
+        let args = ["-a", "gpuatop", "-u", urgency(severity), "GPU alert", message];
+        Some(Delivery::Desktop(args.iter().map(|arg| arg.to_string()).collect()))
+    }
+
+    pub fn notify(&mut self, alert: &Alert) {
+        let delivery = self.delivery(alert, Instant::now());
+        send(delivery, alert.severity, &alert.message());
+    }
+
+    pub fn notify_script(&mut self, event: &Event) {
+        let delivery = self.script_delivery(event, Instant::now());
+        send(delivery, event.severity, &event.message);
+    }
+}
+
+/// Carries out `delivery`, falling back to stderr when `notify-send` fails.
+fn send(delivery: Option<Delivery>, severity: Severity, message: &str) {
+    match delivery {
+        Some(Delivery::Desktop(args)) => {
+            let fallback = format!("gpuatop {}: {}", urgency(severity), message);
+            thread::spawn(move || {
+                let sent = Command::new("notify-send")
+                    .args(&args)
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .is_ok_and(|status| status.success());
+
+                if !sent {
+                    live::eprintln(&fallback);
+                }
+            });
+        }
+        Some(Delivery::Stderr(line)) => live::eprintln(&line),
+        None => {}
+    }
+}
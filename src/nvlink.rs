@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::rate::{Moment, RateTracker};
+use crate::runner::CommandRunner;
+
+/// Cumulative NVLink counters for one GPU, summed over all of its links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NvLinkCounters {
+    pub tx_kib: u64,
+    pub rx_kib: u64,
+    pub replay_errors: u64,
+    pub crc_errors: u64,
+}
+
+/// NVLink activity over the last interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NvLinkMetrics {
+    pub tx_kib_per_s: f64,
+    pub rx_kib_per_s: f64,
+    pub replay_errors: u64,
+    pub crc_errors: u64,
+}
+
+/// Parses `Link N: <counter>: <value>` lines grouped under `GPU N: ...` headers, adding each
+/// line's value to the counter selected by `apply`. GPUs without any link lines are omitted.
+fn parse_link_counters(output: &str, counters: &mut HashMap<u32, NvLinkCounters>, apply: fn(&mut NvLinkCounters, &str, u64)) {
+    let mut gpu = None;
+
+    for line in output.lines() {
+        let line = line.trim();
+
+        if let Some(rest) = line.strip_prefix("GPU ") {
+            gpu = rest.split(':').next().and_then(|index| index.trim().parse().ok());
+        } else if let (Some(gpu), Some(rest)) = (gpu, line.strip_prefix("Link ")) {
+            let mut parts = rest.splitn(3, ':').map(str::trim);
+            let (Some(_link), Some(counter), Some(value)) = (parts.next(), parts.next(), parts.next()) else { continue };
+            let Some(value) = value.split_whitespace().next().and_then(|value| value.parse().ok()) else { continue };
+
+            apply(counters.entry(gpu).or_default(), counter, value);
+        }
+    }
+}
+
+pub fn parse_nvlink_throughput(output: &str, counters: &mut HashMap<u32, NvLinkCounters>) {
+    parse_link_counters(output, counters, |counters, counter, value| match counter {
+        "Data Tx" => counters.tx_kib += value,
+        "Data Rx" => counters.rx_kib += value,
+        _ => {}
+    });
+}
+
+pub fn parse_nvlink_errors(output: &str, counters: &mut HashMap<u32, NvLinkCounters>) {
+    parse_link_counters(output, counters, |counters, counter, value| match counter {
+        "Replay Errors" => counters.replay_errors += value,
+        "CRC Errors" | "CRC Data Errors" | "CRC Flit Errors" => counters.crc_errors += value,
+        _ => {}
+    });
+}
+
+fn run_nvidia_smi(runner: &dyn CommandRunner, args: &[&str]) -> Option<String> {
+    let output = runner.run("nvidia-smi", args).ok()?;
+    output.success.then_some(output.stdout)
+}
+
+pub fn query_nvlink_counters(runner: &dyn CommandRunner) -> HashMap<u32, NvLinkCounters> {
+    let mut counters = HashMap::new();
+
+    if let Some(output) = run_nvidia_smi(runner, &["nvlink", "-gt", "d"]) {
+        parse_nvlink_throughput(&output, &mut counters);
+    }
+    if let Some(output) = run_nvidia_smi(runner, &["nvlink", "-e"]) {
+        parse_nvlink_errors(&output, &mut counters);
+    }
+
+    counters
+}
+
+/// Turns cumulative counters into per-interval deltas.
+#[derive(Debug, Default)]
+pub struct NvLinkTracker {
+    counters: RateTracker<(u32, &'static str)>,
+}
+
+impl NvLinkTracker {
+    /// Records the latest counters and returns the activity since the previous update. The
+    /// first update only establishes a baseline and returns no metrics.
+    pub fn update(&mut self, counters: HashMap<u32, NvLinkCounters>) -> HashMap<u32, NvLinkMetrics> {
+        self.update_at(counters, Moment::now())
+    }
+
+    /// [`NvLinkTracker::update`] with the counters read `at`. A GPU whose counters were reset
+    /// since the previous update gets no metrics, and neither does any across a suspend.
+    pub fn update_at(&mut self, counters: HashMap<u32, NvLinkCounters>, at: Moment) -> HashMap<u32, NvLinkMetrics> {
+        self.counters.tick(at);
+
+        let mut metrics = HashMap::new();
+        for (gpu, current) in counters {
+            let tx = self.counters.update((gpu, "tx_kib"), current.tx_kib);
+            let rx = self.counters.update((gpu, "rx_kib"), current.rx_kib);
+            let replay_errors = self.counters.update((gpu, "replay_errors"), current.replay_errors);
+            let crc_errors = self.counters.update((gpu, "crc_errors"), current.crc_errors);
+            let (Some(tx), Some(rx), Some(replay_errors), Some(crc_errors)) = (tx, rx, replay_errors, crc_errors) else { continue };
+
+            metrics.insert(gpu, NvLinkMetrics {
+                tx_kib_per_s: tx.per_second(),
+                rx_kib_per_s: rx.per_second(),
+                replay_errors: replay_errors.increase,
+                crc_errors: crc_errors.increase,
+            });
+        }
+        metrics
+    }
+}
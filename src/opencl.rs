@@ -0,0 +1,105 @@
+//! OpenCL device enumeration, the last resort for identifying GPUs when `lspci` is missing.
+//! Calls the system's OpenCL ICD loader (`libOpenCL.so`) directly, so no commands are run.
+
+use std::ffi::c_void;
+use std::ptr;
+
+use crate::{GpuInfo, GpuType};
+
+type ClPlatformId = *mut c_void;
+type ClDeviceId = *mut c_void;
+
+const CL_SUCCESS: i32 = 0;
+const CL_DEVICE_NOT_FOUND: i32 = -1;
+const CL_DEVICE_TYPE_GPU: u64 = 1 << 2;
+const CL_DEVICE_NAME: u32 = 0x102B;
+const CL_DEVICE_VENDOR: u32 = 0x102C;
+
+#[link(name = "OpenCL")]
+extern "C" {
+    fn clGetPlatformIDs(num_entries: u32, platforms: *mut ClPlatformId, num_platforms: *mut u32) -> i32;
+    fn clGetDeviceIDs(platform: ClPlatformId, device_type: u64, num_entries: u32, devices: *mut ClDeviceId, num_devices: *mut u32) -> i32;
+    fn clGetDeviceInfo(device: ClDeviceId, param_name: u32, param_value_size: usize, param_value: *mut c_void, param_value_size_ret: *mut usize) -> i32;
+}
+
+fn check(call: &str, status: i32) -> Result<(), String> {
+    if status == CL_SUCCESS {
+        Ok(())
+    } else {
+        Err(format!("{} failed with OpenCL error {}", call, status))
+    }
+}
+
+fn platforms() -> Result<Vec<ClPlatformId>, String> {
+    let mut count = 0;
+    check("clGetPlatformIDs", unsafe { clGetPlatformIDs(0, ptr::null_mut(), &mut count) })?;
+
+    let mut platforms = vec![ptr::null_mut(); count as usize];
+    check("clGetPlatformIDs", unsafe { clGetPlatformIDs(count, platforms.as_mut_ptr(), ptr::null_mut()) })?;
+    Ok(platforms)
+}
+
+fn gpu_devices(platform: ClPlatformId) -> Result<Vec<ClDeviceId>, String> {
+    let mut count = 0;
+    match unsafe { clGetDeviceIDs(platform, CL_DEVICE_TYPE_GPU, 0, ptr::null_mut(), &mut count) } {
+        // A platform without GPUs (e.g. a CPU-only runtime) is not an error.
+        CL_DEVICE_NOT_FOUND => return Ok(Vec::new()),
+        status => check("clGetDeviceIDs", status)?,
+    }
+
+    let mut devices = vec![ptr::null_mut(); count as usize];
+    check("clGetDeviceIDs", unsafe { clGetDeviceIDs(platform, CL_DEVICE_TYPE_GPU, count, devices.as_mut_ptr(), ptr::null_mut()) })?;
+    Ok(devices)
+}
+
+fn device_string(device: ClDeviceId, param: u32) -> Result<String, String> {
+    let mut size = 0;
+    check("clGetDeviceInfo", unsafe { clGetDeviceInfo(device, param, 0, ptr::null_mut(), &mut size) })?;
+
+    let mut value = vec![0u8; size];
+    check("clGetDeviceInfo", unsafe { clGetDeviceInfo(device, param, size, value.as_mut_ptr().cast(), ptr::null_mut()) })?;
+    Ok(String::from_utf8_lossy(&value).trim_end_matches('\0').trim().to_string())
+}
+
+/// Name and vendor string of every OpenCL GPU, across all platforms.
+fn devices() -> Result<Vec<(String, String)>, String> {
+    let mut devices = Vec::new();
+
+    for platform in platforms()? {
+        for device in gpu_devices(platform)? {
+            devices.push((device_string(device, CL_DEVICE_NAME)?, device_string(device, CL_DEVICE_VENDOR)?));
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Maps an OpenCL vendor string such as `Advanced Micro Devices, Inc.` to a GPU type.
+pub fn gpu_type_for_vendor(vendor: &str) -> Option<GpuType> {
+    let vendor = vendor.to_ascii_lowercase();
+
+    if vendor.contains("nvidia") {
+        Some(GpuType::Nvidia)
+    } else if vendor.contains("advanced micro devices") || vendor.contains("amd") {
+        Some(GpuType::Amd)
+    } else if vendor.contains("intel") {
+        Some(GpuType::Intel)
+    } else {
+        None
+    }
+}
+
+/// Enumerates OpenCL-capable GPUs. OpenCL knows nothing about PCI addresses, so `bus_id` is
+/// always empty.
+pub fn identify_gpu_opencl() -> Result<Vec<GpuInfo>, String> {
+    Ok(devices()?
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, _))| GpuInfo { index: index as u32, name, bus_id: None, render_offload: None })
+        .collect())
+}
+
+/// The type of the first OpenCL GPU from a vendor with a supported top tool.
+pub fn identify_gpu_type() -> Option<GpuType> {
+    devices().ok()?.iter().find_map(|(_, vendor)| gpu_type_for_vendor(vendor))
+}
@@ -0,0 +1,871 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::aperture::ApertureMetrics;
+use crate::efficiency::Efficiency;
+use crate::idle::{self, Activity};
+use crate::live;
+use crate::metadata::Labels;
+use crate::power::DeviceState;
+use crate::prime::RenderOffloadMode;
+use crate::schema::SCHEMA_VERSION;
+use crate::temperature::{self, Temperatures};
+use crate::{GpuInfo, GpuSnapshot};
+
+pub use crate::json::json_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    /// One JSON object per sample and line (`application/x-ndjson`).
+    Ndjson,
+    /// Same as [`OutputFormat::Ndjson`], except that a single sample (`--count 1`) is emitted
+    /// as one plain JSON document (an array when several GPUs are sampled) without a trailing
+    /// newline.
+    Json,
+    Influx,
+    /// Length-prefixed MessagePack maps with the JSON keys, written by
+    /// [`crate::msgpack::encode_snapshot`].
+    Msgpack,
+    /// One Prometheus text exposition page with every GPU, written by
+    /// [`crate::prometheus::format_page`]; gpuatop exits after the first tick.
+    Prometheus,
+    /// StatsD gauges, one line per metric, written by [`crate::statsd::format_metrics`].
+    Statsd,
+    /// A bordered table with one row per GPU, written by [`crate::display::grid::format_grid`]
+    /// at the end of every tick; on a terminal each table replaces the previous one.
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "text" => OutputFormat::Text,
+            "ndjson" => OutputFormat::Ndjson,
+            "json" => OutputFormat::Json,
+            "influx" => OutputFormat::Influx,
+            "msgpack" => OutputFormat::Msgpack,
+            "prometheus" => OutputFormat::Prometheus,
+            "statsd" => OutputFormat::Statsd,
+            "table" => OutputFormat::Table,
+            _ => return Err(format!("Unknown output format: {}", s)),
+        })
+    }
+}
+
+/// Optional metric groups that are only collected when requested with `--fields`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    NvLink,
+    /// Utilization split into desktop (compositor) and application usage.
+    Split,
+    /// NVIDIA BAR1 usage and reserved memory. Tells "VRAM full" apart from "visible aperture
+    /// full", which makes allocations fail on systems without resizable BAR.
+    Bar1,
+    /// amdgpu CPU-visible VRAM usage, for the same reason as [`Field::Bar1`].
+    VisVram,
+    /// Every temperature sensor: junction (hotspot) and memory besides the edge or core one.
+    Temps,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "nvlink" => Field::NvLink,
+            "split" => Field::Split,
+            "bar1" => Field::Bar1,
+            "vis_vram" => Field::VisVram,
+            "temps" => Field::Temps,
+            _ => return Err(format!("Unknown field: {}", s)),
+        })
+    }
+}
+
+pub fn parse_fields(value: &str) -> Result<Vec<Field>, String> {
+    value.split(',').map(str::trim).filter(|field| !field.is_empty()).map(str::parse).collect()
+}
+
+/// The metrics printed, chosen with `--output-fields`, one bit per field. The GPU's index,
+/// name and utilization are always printed: they are what every record is keyed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSet(u16);
+
+impl FieldSet {
+    /// `utilization_max`, the busiest sample of a high-frequency window.
+    pub const UTIL: FieldSet = FieldSet(1 << 0);
+    pub const MEM: FieldSet = FieldSet(1 << 1);
+    pub const TEMP: FieldSet = FieldSet(1 << 2);
+    pub const POWER: FieldSet = FieldSet(1 << 3);
+    pub const NVLINK: FieldSet = FieldSet(1 << 4);
+    pub const SPLIT: FieldSet = FieldSet(1 << 5);
+    pub const MEMBW: FieldSet = FieldSet(1 << 6);
+    /// BAR1 usage and reserved memory.
+    pub const BAR1: FieldSet = FieldSet(1 << 7);
+    pub const VIS_VRAM: FieldSet = FieldSet(1 << 8);
+    pub const IDLE: FieldSet = FieldSet(1 << 9);
+    pub const ALL: FieldSet = FieldSet((1 << 10) - 1);
+    pub const NONE: FieldSet = FieldSet(0);
+
+    /// The `--output-fields` names, in output order, with what `--help` says of them.
+    pub const NAMES: [(&'static str, FieldSet, &'static str); 10] = [
+        ("util", FieldSet::UTIL, "peak utilization of a --display-interval window (utilization_max)"),
+        ("mem", FieldSet::MEM, "memory used and total"),
+        ("temp", FieldSet::TEMP, "temperature, and every sensor with --fields temps"),
+        ("power", FieldSet::POWER, "power draw"),
+        ("nvlink", FieldSet::NVLINK, "NVLink throughput, with --fields nvlink"),
+        ("split", FieldSet::SPLIT, "desktop and application utilization, with --fields split"),
+        ("membw", FieldSet::MEMBW, "memory bandwidth utilization"),
+        ("bar1", FieldSet::BAR1, "NVIDIA BAR1 aperture and reserved memory, with --fields bar1"),
+        ("vis_vram", FieldSet::VIS_VRAM, "amdgpu CPU-visible VRAM aperture, with --fields vis_vram"),
+        ("idle", FieldSet::IDLE, "time spent idle, with --idle-threshold"),
+    ];
+
+    pub fn contains(self, other: FieldSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// A copy of `snapshot` without the metrics outside the set, for the formatters, which
+    /// leave out whatever a snapshot lacks.
+    pub fn apply(self, snapshot: &GpuSnapshot) -> GpuSnapshot {
+        let mut snapshot = snapshot.clone();
+
+        if !self.contains(FieldSet::UTIL) {
+            snapshot.utilization_max = None;
+        }
+        if !self.contains(FieldSet::MEM) {
+            snapshot.memory_used_mib = None;
+            snapshot.memory_total_mib = None;
+        }
+        if !self.contains(FieldSet::TEMP) {
+            snapshot.temperature_c = None;
+            snapshot.temperatures = None;
+        }
+        if !self.contains(FieldSet::POWER) {
+            snapshot.power_w = None;
+        }
+        if !self.contains(FieldSet::NVLINK) {
+            snapshot.nvlink = None;
+        }
+        if !self.contains(FieldSet::SPLIT) {
+            snapshot.usage_split = None;
+        }
+        if !self.contains(FieldSet::MEMBW) {
+            snapshot.memory_bandwidth = None;
+        }
+        if let Some(aperture) = &mut snapshot.aperture {
+            if !self.contains(FieldSet::BAR1) {
+                aperture.reserved_mib = None;
+                aperture.bar1_used_mib = None;
+                aperture.bar1_total_mib = None;
+            }
+            if !self.contains(FieldSet::VIS_VRAM) {
+                aperture.vis_vram_used_mib = None;
+                aperture.vis_vram_total_mib = None;
+            }
+        }
+        if !self.contains(FieldSet::IDLE) {
+            snapshot.activity = None;
+        }
+
+        snapshot
+    }
+}
+
+impl Default for FieldSet {
+    fn default() -> Self {
+        FieldSet::ALL
+    }
+}
+
+impl std::ops::BitOr for FieldSet {
+    type Output = FieldSet;
+
+    fn bitor(self, other: FieldSet) -> FieldSet {
+        FieldSet(self.0 | other.0)
+    }
+}
+
+impl FromStr for FieldSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim() == "all" {
+            return Ok(FieldSet::ALL);
+        }
+
+        s.split(',').map(str::trim).filter(|name| !name.is_empty()).try_fold(FieldSet::NONE, |set, name| {
+            match FieldSet::NAMES.iter().find(|(known, _, _)| *known == name) {
+                Some((_, field, _)) => Ok(set | *field),
+                None => {
+                    let names: Vec<&str> = FieldSet::NAMES.iter().map(|(known, _, _)| *known).collect();
+                    Err(format!("Unknown output field: {} (expected all or {})", name, names.join(", ")))
+                }
+            }
+        })
+    }
+}
+
+/// `--timestamp-format`: how records are stamped with the time of their tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC 3339 UTC to the second, `2023-10-31T12:23:52Z`.
+    #[default]
+    Iso8601,
+    /// Whole seconds since the Unix epoch.
+    Unix,
+    /// Milliseconds since the Unix epoch.
+    UnixMs,
+    /// Seconds since gpuatop started, to the millisecond.
+    Relative,
+    None,
+}
+
+impl FromStr for TimestampFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "iso8601" => TimestampFormat::Iso8601,
+            "unix" => TimestampFormat::Unix,
+            "unix-ms" => TimestampFormat::UnixMs,
+            "relative" => TimestampFormat::Relative,
+            "none" => TimestampFormat::None,
+            _ => return Err(format!("Unknown timestamp format: {} (expected iso8601, unix, unix-ms, relative or none)", s)),
+        })
+    }
+}
+
+impl TimestampFormat {
+    /// The timestamp of a tick taken at `now`, `since_start` after gpuatop started.
+    pub fn stamp(self, now: SystemTime, since_start: Duration) -> Option<Timestamp> {
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        match self {
+            TimestampFormat::Iso8601 => Some(Timestamp::Iso8601(format_iso8601(now))),
+            TimestampFormat::Unix => Some(Timestamp::Unix(since_epoch.as_secs())),
+            TimestampFormat::UnixMs => Some(Timestamp::Unix(since_epoch.as_millis() as u64)),
+            TimestampFormat::Relative => Some(Timestamp::Relative(since_start)),
+            TimestampFormat::None => None,
+        }
+    }
+}
+
+/// A tick's time in its [`TimestampFormat`]. Prefixes text lines and is the `ts` key of
+/// JSON and MessagePack records: a string for ISO 8601, a number otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Timestamp {
+    Iso8601(String),
+    /// Seconds or milliseconds since the Unix epoch.
+    Unix(u64),
+    Relative(Duration),
+}
+
+impl Timestamp {
+    pub fn to_json(&self) -> String {
+        match self {
+            Timestamp::Iso8601(time) => json_string(time),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timestamp::Iso8601(time) => f.write_str(time),
+            Timestamp::Unix(value) => write!(f, "{}", value),
+            Timestamp::Relative(elapsed) => write!(f, "{:.3}", elapsed.as_secs_f64()),
+        }
+    }
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp to the second.
+pub fn format_iso8601(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, second_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day % 3600 / 60,
+        second_of_day % 60
+    )
+}
+
+/// Everything the formatters need besides the snapshot itself.
+#[derive(Debug, Clone)]
+pub struct OutputContext {
+    pub format: OutputFormat,
+    pub hostname: Option<String>,
+    pub labels: Labels,
+    /// Sequence number of the tick the sample belongs to, added to JSON and MessagePack
+    /// records. Skipped ticks leave a gap.
+    pub tick_seq: Option<u64>,
+    /// `--timestamp-format`: the time of the tick, prefixed to text lines and added to JSON
+    /// and MessagePack records as `ts`.
+    pub timestamp: Option<Timestamp>,
+    /// `--precision`: decimals of the percentages in text lines. Other formats keep every
+    /// digit.
+    pub precision: usize,
+}
+
+/// Decimals of the percentages in text lines without `--precision`.
+pub const DEFAULT_PRECISION: usize = 1;
+
+/// `--precision`: 0 to 6 decimals.
+pub fn parse_precision(value: &str) -> Result<usize, String> {
+    value.parse().ok().filter(|precision| *precision <= 6).ok_or_else(|| format!("Invalid --precision value: {} (expected 0 to 6)", value))
+}
+
+/// Reads the system hostname once; the result is meant to be stored in [`OutputContext`].
+pub fn read_hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: gethostname writes at most `buffer.len()` bytes into the buffer.
+    let hostname = (unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } == 0).then(|| {
+        // A name as long as the buffer may be cut off without its terminating NUL.
+        let end = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+        String::from_utf8_lossy(&buffer[..end]).into_owned()
+    });
+    hostname
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+
+/// Escapes commas, spaces, and equals signs in InfluxDB line protocol tag keys and values.
+pub fn influx_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(',', "\\,").replace(' ', "\\ ").replace('=', "\\=")
+}
+
+/// The aperture figures a GPU reports, under their JSON keys.
+pub fn aperture_fields(aperture: &ApertureMetrics) -> Vec<(&'static str, u64)> {
+    [
+        ("memory_reserved_mib", aperture.reserved_mib),
+        ("bar1_used_mib", aperture.bar1_used_mib),
+        ("bar1_total_mib", aperture.bar1_total_mib),
+        ("vis_vram_used_mib", aperture.vis_vram_used_mib),
+        ("vis_vram_total_mib", aperture.vis_vram_total_mib),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value?)))
+    .collect()
+}
+
+/// `edge 60°C, junction 75°C, mem 82°C`.
+pub fn format_temperatures(temperatures: &Temperatures) -> String {
+    temperatures.iter().map(|(sensor, value)| format!("{} {}°C", sensor, value)).collect::<Vec<_>>().join(", ")
+}
+
+/// The `normal` text layout, see [`crate::display::layout`], with `precision` decimals for
+/// percentages.
+pub fn format_text(snapshot: &GpuSnapshot, precision: usize) -> String {
+    let mut line = match snapshot.utilization_max {
+        Some(max) => format!("GPU {} ({}) Utilization (percent): {:.*} (max {:.*})", snapshot.gpu.index, snapshot.gpu.name, precision, snapshot.utilization, precision, max),
+        None => format!("GPU {} ({}) Utilization (percent): {:.*}", snapshot.gpu.index, snapshot.gpu.name, precision, snapshot.utilization),
+    };
+
+    if let (Some(used), Some(total)) = (snapshot.memory_used_mib, snapshot.memory_total_mib) {
+        line.push_str(&format!(", Memory: {}/{} MiB", used, total));
+    } else if let Some(used) = snapshot.memory_used_mib {
+        line.push_str(&format!(", Memory: {} MiB", used));
+    }
+    let hottest = snapshot.temperatures.as_ref().and_then(temperature::hottest);
+    if let Some(temperature) = hottest.into_iter().chain(snapshot.temperature_c).reduce(f32::max) {
+        line.push_str(&format!(", Temperature: {}°C", temperature));
+    }
+    if let Some(temperatures) = snapshot.temperatures.as_ref().filter(|temperatures| !temperatures.is_empty()) {
+        line.push_str(&format!(" ({})", format_temperatures(temperatures)));
+    }
+    if let Some(power) = snapshot.power_w {
+        line.push_str(&format!(", Power: {} W", power));
+    }
+    if let Some(clock) = snapshot.clock_mhz {
+        line.push_str(&format!(", Clock: {} MHz", clock));
+    }
+    if let Some(reasons) = snapshot.throttle_reasons.as_ref().filter(|reasons| !reasons.is_empty()) {
+        line.push_str(&format!(", Throttled: {}", reasons.join(", ")));
+    }
+    if let Some(nvlink) = &snapshot.nvlink {
+        line.push_str(&format!(
+            ", NVLink TX: {:.0} KiB/s, RX: {:.0} KiB/s, Replay errors: {}, CRC errors: {}",
+            nvlink.tx_kib_per_s, nvlink.rx_kib_per_s, nvlink.replay_errors, nvlink.crc_errors
+        ));
+    }
+    if let Some(split) = &snapshot.usage_split {
+        line.push_str(&format!(", Desktop: {:.*}%, Apps: {:.*}%", precision, split.desktop, precision, split.apps));
+    }
+    if let Some(bandwidth) = &snapshot.memory_bandwidth {
+        if let Some(utilization) = bandwidth.utilization_pct {
+            line.push_str(&format!(", membw: {:.*}%", precision, utilization));
+        }
+        if let (Some(read), Some(write)) = (bandwidth.read_gbps, bandwidth.write_gbps) {
+            line.push_str(&format!(", membw: {:.2} GB/s read, {:.2} GB/s write", read, write));
+        }
+    }
+    if let Some(aperture) = &snapshot.aperture {
+        if let Some(reserved) = aperture.reserved_mib {
+            line.push_str(&format!(", Reserved: {} MiB", reserved));
+        }
+        if let (Some(used), Some(total)) = (aperture.bar1_used_mib, aperture.bar1_total_mib) {
+            line.push_str(&format!(", BAR1: {}/{} MiB", used, total));
+        }
+        if let (Some(used), Some(total)) = (aperture.vis_vram_used_mib, aperture.vis_vram_total_mib) {
+            line.push_str(&format!(", Visible VRAM: {}/{} MiB", used, total));
+        }
+    }
+    match snapshot.activity {
+        Some(Activity::Active) => line.push_str(", active"),
+        Some(Activity::Idle(idle)) => line.push_str(&format!(", Idle for {}", idle::format_duration(idle))),
+        None => {}
+    }
+    match snapshot.efficiency {
+        Some(Efficiency::TflopsPerWatt(efficiency)) => line.push_str(&format!(", Efficiency: {:.2} TFLOPS/W", efficiency)),
+        Some(Efficiency::Unavailable) => line.push_str(", Efficiency: N/A"),
+        None => {}
+    }
+    if snapshot.gpu.render_offload == Some(RenderOffloadMode::OffloadGpu) {
+        line.push_str(" [PRIME offload]");
+    }
+
+    line
+}
+
+fn format_json(snapshot: &GpuSnapshot, context: &OutputContext) -> String {
+    let mut fields = vec![format!("\"schema_version\":{}", SCHEMA_VERSION)];
+
+    if let Some(hostname) = &context.hostname {
+        fields.push(format!("\"hostname\":{}", json_string(hostname)));
+    }
+    fields.push(format!("\"gpu\":{}", snapshot.gpu.index));
+    fields.push(format!("\"name\":{}", json_string(&snapshot.gpu.name)));
+    fields.push(format!("\"utilization\":{}", snapshot.utilization));
+    if let Some(max) = snapshot.utilization_max {
+        fields.push(format!("\"utilization_max\":{}", max));
+    }
+
+    if let Some(used) = snapshot.memory_used_mib {
+        fields.push(format!("\"memory_used_mib\":{}", used));
+    }
+    if let Some(total) = snapshot.memory_total_mib {
+        fields.push(format!("\"memory_total_mib\":{}", total));
+    }
+    if let Some(temperature) = snapshot.temperature_c {
+        fields.push(format!("\"temperature_c\":{}", temperature));
+    }
+    if let Some(temperatures) = &snapshot.temperatures {
+        let sensors: Vec<String> = temperatures.iter().map(|(sensor, value)| format!("\"{}\":{}", sensor, value)).collect();
+        fields.push(format!("\"temperatures\":{{{}}}", sensors.join(",")));
+    }
+    if let Some(power) = snapshot.power_w {
+        fields.push(format!("\"power_w\":{}", power));
+    }
+    if let Some(clock) = snapshot.clock_mhz {
+        fields.push(format!("\"clock_mhz\":{}", clock));
+    }
+    if let Some(reasons) = &snapshot.throttle_reasons {
+        let reasons: Vec<String> = reasons.iter().map(|reason| json_string(reason)).collect();
+        fields.push(format!("\"throttle_reasons\":[{}]", reasons.join(",")));
+    }
+    if !context.labels.is_empty() {
+        fields.push(format!("\"labels\":{}", context.labels.to_json()));
+    }
+    if let Some(nvlink) = &snapshot.nvlink {
+        fields.push(format!("\"nvlink_tx_kib_per_s\":{:.1}", nvlink.tx_kib_per_s));
+        fields.push(format!("\"nvlink_rx_kib_per_s\":{:.1}", nvlink.rx_kib_per_s));
+        fields.push(format!("\"nvlink_replay_errors\":{}", nvlink.replay_errors));
+        fields.push(format!("\"nvlink_crc_errors\":{}", nvlink.crc_errors));
+    }
+    if let Some(split) = &snapshot.usage_split {
+        fields.push(format!("\"desktop_utilization\":{}", split.desktop));
+        fields.push(format!("\"apps_utilization\":{}", split.apps));
+    }
+    if let Some(bandwidth) = &snapshot.memory_bandwidth {
+        if let Some(utilization) = bandwidth.utilization_pct {
+            fields.push(format!("\"memory_bandwidth_utilization\":{}", utilization));
+        }
+        if let Some(read) = bandwidth.read_gbps {
+            fields.push(format!("\"memory_read_gbps\":{}", read));
+        }
+        if let Some(write) = bandwidth.write_gbps {
+            fields.push(format!("\"memory_write_gbps\":{}", write));
+        }
+    }
+    if let Some(aperture) = &snapshot.aperture {
+        for (key, value) in aperture_fields(aperture) {
+            fields.push(format!("\"{}\":{}", key, value));
+        }
+    }
+    if let Some(activity) = snapshot.activity {
+        fields.push(format!("\"idle_seconds\":{}", activity.idle_seconds()));
+    }
+    if let Some(efficiency) = snapshot.efficiency.and_then(Efficiency::value) {
+        fields.push(format!("\"efficiency_tflops_per_w\":{}", efficiency));
+    }
+    if let Some(engines) = &snapshot.engines {
+        let engines: Vec<String> = engines
+            .iter()
+            .map(|engine| match engine.busy_pct {
+                Some(busy) => format!("{}:{{\"ns_total\":{},\"busy_pct\":{}}}", json_string(&engine.engine), engine.ns_total, busy),
+                None => format!("{}:{{\"ns_total\":{}}}", json_string(&engine.engine), engine.ns_total),
+            })
+            .collect();
+        fields.push(format!("\"engines\":{{{}}}", engines.join(",")));
+    }
+    if let Some(source) = &snapshot.source {
+        fields.push(format!("\"source\":{}", json_string(source)));
+    }
+    if let Some(tick_seq) = context.tick_seq {
+        fields.push(format!("\"tick_seq\":{}", tick_seq));
+    }
+    if let Some(timestamp) = &context.timestamp {
+        fields.push(format!("\"ts\":{}", timestamp.to_json()));
+    }
+
+    format!("{{{}}}", fields.join(","))
+}
+
+fn format_influx(snapshot: &GpuSnapshot, context: &OutputContext) -> String {
+    let mut tags = format!("gpu,gpu={},name={}", snapshot.gpu.index, influx_escape(&snapshot.gpu.name));
+
+    if let Some(hostname) = &context.hostname {
+        tags.push_str(&format!(",hostname={}", influx_escape(hostname)));
+    }
+    tags.push_str(&context.labels.to_influx_tags());
+
+    let mut fields = vec![format!("utilization={}", snapshot.utilization)];
+    if let Some(max) = snapshot.utilization_max {
+        fields.push(format!("utilization_max={}", max));
+    }
+
+    if let Some(used) = snapshot.memory_used_mib {
+        fields.push(format!("memory_used_mib={}i", used));
+    }
+    if let Some(total) = snapshot.memory_total_mib {
+        fields.push(format!("memory_total_mib={}i", total));
+    }
+    if let Some(temperature) = snapshot.temperature_c {
+        fields.push(format!("temperature_c={}", temperature));
+    }
+    for (sensor, value) in snapshot.temperatures.iter().flatten() {
+        fields.push(format!("temperature_{}_c={}", sensor, value));
+    }
+    if let Some(power) = snapshot.power_w {
+        fields.push(format!("power_w={}", power));
+    }
+    if let Some(clock) = snapshot.clock_mhz {
+        fields.push(format!("clock_mhz={}i", clock));
+    }
+    if let Some(nvlink) = &snapshot.nvlink {
+        fields.push(format!("nvlink_tx_kib_per_s={:.1}", nvlink.tx_kib_per_s));
+        fields.push(format!("nvlink_rx_kib_per_s={:.1}", nvlink.rx_kib_per_s));
+        fields.push(format!("nvlink_replay_errors={}i", nvlink.replay_errors));
+        fields.push(format!("nvlink_crc_errors={}i", nvlink.crc_errors));
+    }
+    if let Some(split) = &snapshot.usage_split {
+        fields.push(format!("desktop_utilization={}", split.desktop));
+        fields.push(format!("apps_utilization={}", split.apps));
+    }
+    if let Some(bandwidth) = &snapshot.memory_bandwidth {
+        if let Some(utilization) = bandwidth.utilization_pct {
+            fields.push(format!("memory_bandwidth_utilization={}", utilization));
+        }
+        if let Some(read) = bandwidth.read_gbps {
+            fields.push(format!("memory_read_gbps={}", read));
+        }
+        if let Some(write) = bandwidth.write_gbps {
+            fields.push(format!("memory_write_gbps={}", write));
+        }
+    }
+    if let Some(aperture) = &snapshot.aperture {
+        for (key, value) in aperture_fields(aperture) {
+            fields.push(format!("{}={}i", key, value));
+        }
+    }
+    if let Some(activity) = snapshot.activity {
+        fields.push(format!("idle_seconds={}i", activity.idle_seconds()));
+    }
+    if let Some(efficiency) = snapshot.efficiency.and_then(Efficiency::value) {
+        fields.push(format!("efficiency_tflops_per_w={}", efficiency));
+    }
+    for engine in snapshot.engines.iter().flatten() {
+        fields.push(format!("engine_{}_ns_total={}i", influx_escape(&engine.engine), engine.ns_total));
+        if let Some(busy) = engine.busy_pct {
+            fields.push(format!("engine_{}_busy_pct={}", influx_escape(&engine.engine), busy));
+        }
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+
+    format!("{} {} {}", tags, fields.join(","), timestamp)
+}
+
+/// The percentages of a sample, which [`crate::clamp_percent`] keeps within 0 to 100.
+fn percentages(snapshot: &GpuSnapshot) -> impl Iterator<Item = f64> + '_ {
+    let split = snapshot.usage_split.map(|split| [split.desktop, split.apps]);
+    [snapshot.utilization]
+        .into_iter()
+        .chain(snapshot.utilization_max)
+        .chain(split.into_iter().flatten())
+        .chain(snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct))
+        .chain(snapshot.engines.iter().flatten().filter_map(|engine| engine.busy_pct))
+}
+
+/// Formats a sample as a line of text. MessagePack is binary, so for
+/// [`OutputFormat::Msgpack`] this is the equivalent JSON.
+pub fn format_snapshot(snapshot: &GpuSnapshot, context: &OutputContext) -> String {
+    debug_assert!(percentages(snapshot).all(|percent| (0.0..=100.0).contains(&percent)), "a source skipped clamp_percent: {:?}", snapshot);
+    match context.format {
+        OutputFormat::Text | OutputFormat::Table => prefix_text(&format_text(snapshot, context.precision), context),
+        OutputFormat::Ndjson | OutputFormat::Json | OutputFormat::Msgpack => format_json(snapshot, context),
+        OutputFormat::Influx => format_influx(snapshot, context),
+        OutputFormat::Prometheus => {
+            crate::prometheus::format_page(std::slice::from_ref(snapshot), context).trim_end().to_string()
+        }
+        OutputFormat::Statsd => crate::statsd::format_metrics(snapshot, &crate::statsd::StatsdOptions::default(), context),
+    }
+}
+
+/// The record of a GPU that was not sampled this tick because it is asleep or lost: the GPU
+/// and its `state`, without metrics, so a consumer cannot mistake it for an idle GPU.
+/// Prometheus and StatsD have no place for a state; for them this is the JSON record.
+pub fn format_state(gpu: &GpuInfo, state: DeviceState, context: &OutputContext) -> String {
+    match context.format {
+        OutputFormat::Text | OutputFormat::Table => prefix_text(&format!("GPU {} ({}): {}", gpu.index, gpu.name, state.as_str()), context),
+        OutputFormat::Influx => {
+            let mut tags = format!("gpu,gpu={},name={}", gpu.index, influx_escape(&gpu.name));
+            if let Some(hostname) = &context.hostname {
+                tags.push_str(&format!(",hostname={}", influx_escape(hostname)));
+            }
+            tags.push_str(&context.labels.to_influx_tags());
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+
+            format!("{} state=\"{}\" {}", tags, state.as_str(), timestamp)
+        }
+        _ => {
+            let mut fields = vec![format!("\"schema_version\":{}", SCHEMA_VERSION)];
+            if let Some(hostname) = &context.hostname {
+                fields.push(format!("\"hostname\":{}", json_string(hostname)));
+            }
+            fields.push(format!("\"gpu\":{}", gpu.index));
+            fields.push(format!("\"name\":{}", json_string(&gpu.name)));
+            fields.push(format!("\"state\":\"{}\"", state.as_str()));
+            if !context.labels.is_empty() {
+                fields.push(format!("\"labels\":{}", context.labels.to_json()));
+            }
+            if let Some(tick_seq) = context.tick_seq {
+                fields.push(format!("\"tick_seq\":{}", tick_seq));
+            }
+            if let Some(timestamp) = &context.timestamp {
+                fields.push(format!("\"ts\":{}", timestamp.to_json()));
+            }
+
+            format!("{{{}}}", fields.join(","))
+        }
+    }
+}
+
+/// Prefixes a text line with the tick's timestamp and, when `--machine-hostname` is active,
+/// `[hostname]`.
+pub fn prefix_text(line: &str, context: &OutputContext) -> String {
+    let line = match &context.hostname {
+        Some(hostname) => format!("[{}] {}", hostname, line),
+        None => line.to_string(),
+    };
+    match &context.timestamp {
+        Some(timestamp) => format!("{} {}", timestamp, line),
+        None => line,
+    }
+}
+
+/// gpuatop's own messages: the startup banner, warnings and errors. With a machine-readable
+/// format they go to stderr, so stdout carries nothing but the data from its first byte. Each
+/// `--quiet` (`-q`) silences one more level: the banner and summary first, then warnings.
+/// Errors are never silenced.
+#[derive(Debug, Clone, Copy)]
+pub struct Console {
+    machine: bool,
+    quiet: u8,
+}
+
+impl Console {
+    pub fn new(format: OutputFormat, quiet: u8) -> Self {
+        Console { machine: format != OutputFormat::Text, quiet }
+    }
+
+    pub fn shows_info(&self) -> bool {
+        self.quiet == 0
+    }
+
+    pub fn shows_warnings(&self) -> bool {
+        self.quiet < 2
+    }
+
+    /// Whether stdout is reserved for a machine-readable format.
+    pub fn is_machine(&self) -> bool {
+        self.machine
+    }
+
+    /// Whether the messages go to a terminal, where a line can be rewritten in place.
+    pub fn is_terminal(&self) -> bool {
+        if self.machine {
+            std::io::stderr().is_terminal()
+        } else {
+            std::io::stdout().is_terminal()
+        }
+    }
+
+    /// Prints `message` regardless of `--quiet`.
+    pub fn emit(&self, message: &str) {
+        if self.machine {
+            live::eprintln(message);
+        } else {
+            // A closed stdout is the `Writer`'s to notice; messages are not output.
+            let _ = live::println(message);
+        }
+    }
+
+    /// Prints `prompt` without a newline and flushes it, for a question answered on stdin.
+    pub fn prompt(&self, prompt: &str) {
+        if self.machine {
+            eprint!("{}", prompt);
+            let _ = std::io::stderr().flush();
+        } else {
+            print!("{}", prompt);
+            let _ = std::io::stdout().flush();
+        }
+    }
+
+    pub fn info(&self, message: &str) {
+        if self.shows_info() {
+            self.emit(message);
+        }
+    }
+
+    pub fn warning(&self, message: &str) {
+        if self.shows_warnings() {
+            self.emit(message);
+        }
+    }
+
+    pub fn error(&self, message: &str) {
+        self.emit(message);
+    }
+}
+
+/// Writes output lines to stdout and, with `--log-file`, appends them to the log file.
+#[derive(Debug, Default)]
+pub struct Writer {
+    log_file: Option<fs::File>,
+    /// Prefixes the lines in the log file with the current ISO 8601 time, for text output
+    /// whose lines carry no `--timestamp-format` timestamp of their own.
+    stamp_log: bool,
+    /// The reader of stdout went away; nothing more is written there.
+    closed: bool,
+}
+
+impl Writer {
+    pub fn new(log_file: Option<&str>, stamp_log: bool) -> std::io::Result<Self> {
+        let log_file = match log_file {
+            Some(path) => Some(fs::OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+
+        Ok(Writer { log_file, stamp_log, closed: false })
+    }
+
+    /// Whether stdout was closed by its reader, as by `gpuatop | head -1`. Monitoring stops
+    /// then, as a command writing to a closed pipe does.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Notes a failed write to stdout. Only a closed pipe stops the output; other errors are
+    /// left to the next write.
+    fn stdout(&mut self, result: io::Result<()>) {
+        if result.is_err_and(|err| err.kind() == io::ErrorKind::BrokenPipe) {
+            self.closed = true;
+        }
+    }
+
+    pub fn line(&mut self, line: &str) {
+        if !self.stamp_log {
+            return self.write(&format!("{}\n", line));
+        }
+        if !self.closed {
+            let result = live::println(line);
+            self.stdout(result);
+        }
+
+        self.log(format!("{} {}\n", format_iso8601(SystemTime::now()), line).as_bytes());
+    }
+
+    /// Writes `text` as is, without appending a newline.
+    pub fn write(&mut self, text: &str) {
+        if !self.closed {
+            let result = live::print(text);
+            self.stdout(result);
+        }
+
+        self.log(text.as_bytes());
+    }
+
+    /// Draws `lines` over the ones drawn last, see [`live`]; the log file gets them as lines.
+    pub fn block(&mut self, lines: Vec<String>) {
+        let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        if !self.closed {
+            let result = live::draw(lines);
+            self.stdout(result);
+        }
+
+        self.log(text.as_bytes());
+    }
+
+    /// Writes terminal control sequences, such as the window title, to stdout only: they mean
+    /// nothing in the log file.
+    pub fn terminal(&mut self, sequence: &str) {
+        print!("{}", sequence);
+        let _ = std::io::stdout().flush();
+    }
+
+    /// Writes binary output such as MessagePack frames.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        if !self.closed {
+            let mut stdout = std::io::stdout().lock();
+            let result = stdout.write_all(bytes).and_then(|_| stdout.flush());
+            drop(stdout);
+            self.stdout(result);
+        }
+
+        self.log(bytes);
+    }
+
+    fn log(&mut self, bytes: &[u8]) {
+        if let Some(file) = &mut self.log_file {
+            if let Err(err) = file.write_all(bytes) {
+                eprintln!("Error: Failed to write log file: {}", err);
+                self.log_file = None;
+            }
+        }
+    }
+}
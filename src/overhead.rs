@@ -0,0 +1,91 @@
+use std::mem::MaybeUninit;
+use std::time::Duration;
+
+/// CPU time consumed by this process and by the child processes it has waited for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuTimes {
+    pub own_seconds: f64,
+    pub children_seconds: f64,
+}
+
+/// Reads the CPU times with `getrusage`: two system calls, cheap enough to do every tick.
+pub fn read_cpu_times() -> Option<CpuTimes> {
+    Some(CpuTimes { own_seconds: rusage_seconds(libc::RUSAGE_SELF)?, children_seconds: rusage_seconds(libc::RUSAGE_CHILDREN)? })
+}
+
+/// User plus system CPU time of `who`, in seconds.
+fn rusage_seconds(who: libc::c_int) -> Option<f64> {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let seconds = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1_000_000.0;
+    Some(seconds(usage.ru_utime) + seconds(usage.ru_stime))
+}
+
+/// gpuatop's own cost over a monitoring session, reported with `--self-stats`.
+#[derive(Debug)]
+pub struct SelfStats {
+    start: CpuTimes,
+    ticks: u64,
+    collect_total: Duration,
+    collect_max: Duration,
+}
+
+impl Default for SelfStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelfStats {
+    pub fn new() -> Self {
+        Self::starting_at(read_cpu_times().unwrap_or_default())
+    }
+
+    /// Stats measured from CPU times `start` rather than the current ones.
+    pub fn starting_at(start: CpuTimes) -> Self {
+        SelfStats { start, ticks: 0, collect_total: Duration::ZERO, collect_max: Duration::ZERO }
+    }
+
+    pub fn record_tick(&mut self, collect_time: Duration) {
+        self.ticks += 1;
+        self.collect_total += collect_time;
+        self.collect_max = self.collect_max.max(collect_time);
+    }
+
+    /// CPU time gpuatop and the child processes it waited for used since the session started,
+    /// for `gpuatop_self_cpu_seconds`.
+    pub fn cpu_seconds(&self) -> f64 {
+        self.cpu_seconds_at(read_cpu_times().unwrap_or(self.start))
+    }
+
+    /// [`SelfStats::cpu_seconds`] with the CPU times `now`.
+    pub fn cpu_seconds_at(&self, now: CpuTimes) -> f64 {
+        (now.own_seconds - self.start.own_seconds) + (now.children_seconds - self.start.children_seconds)
+    }
+
+    pub fn format_summary(&self, elapsed: Duration) -> Vec<String> {
+        self.format_summary_at(read_cpu_times().unwrap_or_default(), elapsed)
+    }
+
+    /// [`SelfStats::format_summary`] with the CPU times `now` at the end of the session.
+    pub fn format_summary_at(&self, now: CpuTimes, elapsed: Duration) -> Vec<String> {
+        let own = now.own_seconds - self.start.own_seconds;
+        let children = now.children_seconds - self.start.children_seconds;
+        let elapsed = elapsed.as_secs_f64().max(f64::EPSILON);
+        let average_ms = if self.ticks == 0 { 0.0 } else { self.collect_total.as_secs_f64() * 1000.0 / self.ticks as f64 };
+
+        vec![
+            format!(
+                "  gpuatop: CPU {:.2}s own + {:.2}s child processes ({:.2}% of a core), {} ticks",
+                own,
+                children,
+                (own + children) * 100.0 / elapsed,
+                self.ticks
+            ),
+            format!("  gpuatop: collecting took {:.1} ms per tick on average, {:.1} ms at most", average_ms, self.collect_max.as_secs_f64() * 1000.0),
+        ]
+    }
+}
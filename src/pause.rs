@@ -0,0 +1,206 @@
+//! Pausing the monitor from the keyboard, to read a moment of the output before it scrolls
+//! away, and zooming into one GPU. While listening, the terminal is switched out of line mode and
+//! stops echoing, so each key takes effect as it is pressed: `p` pauses (or resumes), `r` resumes,
+//! a GPU index shows that GPU in detail, `o` or Esc returns to the overview and `d` shows (or
+//! hides) the GPU drivers' kernel messages. The terminal's settings are put back when gpuatop
+//! exits, is interrupted or is suspended.
+
+use std::fs;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::display::detail::View;
+
+/// Shared between the monitor loop and the thread reading the keys.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the kernel messages are shown.
+static KERNEL_LOG: AtomicBool = AtomicBool::new(false);
+
+/// The GPU shown in detail, or [`OVERVIEW`].
+static DETAIL: AtomicU64 = AtomicU64::new(OVERVIEW);
+const OVERVIEW: u64 = u64::MAX;
+
+/// Digits pressed within this time of each other make up one GPU index, such as `12`.
+pub const INDEX_KEY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The terminal's settings before [`listen`] changed them.
+static ORIGINAL_TERMINAL: OnceLock<libc::termios> = OnceLock::new();
+
+/// Signals that end or suspend gpuatop, after which the terminal must be usable again.
+const RESTORING_SIGNALS: [libc::c_int; 5] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT, libc::SIGTSTP];
+
+/// The paused state after `key` was pressed while `paused`.
+pub fn apply_key(paused: bool, key: &str) -> bool {
+    match key.trim() {
+        "p" | "P" => !paused,
+        "r" | "R" => false,
+        _ => paused,
+    }
+}
+
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+pub fn kernel_log_shown() -> bool {
+    KERNEL_LOG.load(Ordering::Relaxed)
+}
+
+/// Whether the kernel messages are shown after `key` was pressed while `shown`.
+pub fn apply_kernel_log_key(shown: bool, key: &str) -> bool {
+    match key.trim() {
+        "d" | "D" => !shown,
+        _ => shown,
+    }
+}
+
+/// The view after `key`, or the digits of a GPU index, were pressed in `view`.
+pub fn apply_view_key(view: View, key: &str) -> View {
+    match key.trim() {
+        "o" | "O" | "\u{1b}" => View::Overview,
+        key => key.parse().map(View::Detail).unwrap_or(view),
+    }
+}
+
+pub fn view() -> View {
+    match DETAIL.load(Ordering::Relaxed) {
+        OVERVIEW => View::Overview,
+        index => View::Detail(index as u32),
+    }
+}
+
+fn set_view(view: View) {
+    DETAIL.store(
+        match view {
+            View::Overview => OVERVIEW,
+            View::Detail(index) => index.into(),
+        },
+        Ordering::Relaxed,
+    );
+}
+
+/// The GPU index typed so far after `key` was pressed `elapsed` after the previous key: digits
+/// pressed in quick succession add up to one index, and any other key starts over.
+pub fn typed_index(typed: &str, key: char, elapsed: Duration) -> String {
+    match key {
+        '0'..='9' if !typed.is_empty() && elapsed < INDEX_KEY_INTERVAL => format!("{}{}", typed, key),
+        '0'..='9' => key.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Reads the keys from stdin on a background thread for the rest of the process, with the
+/// terminal out of line mode.
+pub fn listen() {
+    enter_key_mode();
+    thread::spawn(|| {
+        let (mut typed, mut pressed) = (String::new(), Instant::now());
+        for byte in io::stdin().lock().bytes().map_while(Result::ok) {
+            let key = char::from(byte);
+            typed = typed_index(&typed, key, pressed.elapsed());
+            pressed = Instant::now();
+
+            let key = if typed.is_empty() { key.to_string() } else { typed.clone() };
+            PAUSED.store(apply_key(is_paused(), &key), Ordering::Relaxed);
+            set_view(apply_view_key(view(), &key));
+            KERNEL_LOG.store(apply_kernel_log_key(kernel_log_shown(), &key), Ordering::Relaxed);
+        }
+    });
+}
+
+/// Turns off line buffering and echo on the terminal, and arranges for its settings to be
+/// restored at exit and on the signals in [`RESTORING_SIGNALS`].
+fn enter_key_mode() {
+    // SAFETY: termios is plain data, filled in by tcgetattr before it is used.
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 || ORIGINAL_TERMINAL.set(original).is_err() {
+        return;
+    }
+    unsafe {
+        libc::atexit(restore_at_exit);
+        for signal in RESTORING_SIGNALS {
+            libc::signal(signal, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+    }
+    set_key_mode();
+}
+
+fn set_key_mode() {
+    if let Some(original) = ORIGINAL_TERMINAL.get() {
+        let mut keys = *original;
+        keys.c_lflag &= !(libc::ICANON | libc::ECHO);
+        keys.c_cc[libc::VMIN] = 1;
+        keys.c_cc[libc::VTIME] = 0;
+        // SAFETY: tcsetattr only reads the settings passed to it.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &keys) };
+    }
+}
+
+/// Puts the terminal back as [`listen`] found it. Only makes async-signal-safe calls. In the
+/// background, where the terminal was already restored on suspension, it does nothing: changing
+/// the settings there would stop gpuatop with SIGTTOU.
+pub fn restore_terminal() {
+    if let (Some(original), true) = (ORIGINAL_TERMINAL.get(), in_terminal_foreground()) {
+        // SAFETY: tcsetattr only reads the settings passed to it.
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original) };
+    }
+}
+
+/// [`in_foreground`] for signal handlers, which must not read files.
+fn in_terminal_foreground() -> bool {
+    // SAFETY: tcgetpgrp and getpgrp are async-signal-safe and take no pointers.
+    unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp() }
+}
+
+extern "C" fn restore_at_exit() {
+    restore_terminal();
+}
+
+/// Restores the terminal, then lets the signal take its default action. After a suspension
+/// (Ctrl-Z) is continued, the keys are read one by one again if gpuatop is back in the foreground.
+extern "C" fn on_signal(signal: libc::c_int) {
+    restore_terminal();
+    // SAFETY: signal, sigemptyset, sigaddset, pthread_sigmask and raise are async-signal-safe.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        // The signal is blocked while its handler runs: unblock it so that it acts right away.
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, signal);
+        libc::pthread_sigmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+        libc::raise(signal);
+        // Only a stop returns here, once the process is continued.
+        libc::signal(signal, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+    if in_terminal_foreground() {
+        set_key_mode();
+    }
+}
+
+/// Sleeps while paused, waking early when `stop` is set. Returns whether it was stopped.
+pub fn wait_until_resumed(stop: &AtomicBool) -> bool {
+    while is_paused() {
+        if stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    stop.load(Ordering::Relaxed)
+}
+
+/// Whether gpuatop is the terminal's foreground job. A background job reading the terminal
+/// would be stopped by SIGTTIN, so it must not listen for keys.
+pub fn in_foreground() -> bool {
+    let Ok(stat) = fs::read_to_string("/proc/self/stat") else {
+        return false;
+    };
+    // The command name may contain spaces; fields are counted after its closing parenthesis.
+    let fields: Vec<&str> = stat.rsplit_once(')').map(|(_, rest)| rest.split_whitespace().collect()).unwrap_or_default();
+
+    // pgrp and tpgid are fields 5 and 8, i.e. 2 and 5 after the name.
+    matches!((fields.get(2), fields.get(5)), (Some(pgrp), Some(tpgid)) if pgrp == tpgid)
+}
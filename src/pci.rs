@@ -0,0 +1,220 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const SYSFS_PCI_DEVICES: &str = "/sys/bus/pci/devices";
+
+/// PCI classes, base class and subclass, that GPUs come as: VGA compatible, 3D and other
+/// display controllers. The rest of base class 0x03 (XGA) is legacy, and NVSwitch fabric
+/// bridges (0x0680) carry the GPU vendor's ID without being GPUs.
+pub const GPU_CLASSES: [u16; 3] = [0x0300, 0x0302, 0x0380];
+
+/// Vendors whose display controllers on servers are the BMC's remote console, not GPUs:
+/// ASPEED, and Matrox, whose G200 HPE iLO and Dell iDRAC expose.
+const BMC_VENDORS: [u16; 2] = [0x1a03, 0x102b];
+
+/// Whether a display controller of `vendor_id` is a BMC console rather than a GPU.
+pub fn is_bmc_vendor(vendor_id: u16) -> bool {
+    BMC_VENDORS.contains(&vendor_id)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PciDevice {
+    pub address: String,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u32,
+    /// Address of the physical function when this device is an SR-IOV virtual function.
+    pub physfn: Option<String>,
+}
+
+/// A physical function together with the SR-IOV virtual functions it exposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PciDeviceGroup {
+    pub physical: PciDevice,
+    pub virtual_functions: Vec<PciDevice>,
+}
+
+pub fn vendor_name(vendor_id: u16) -> &'static str {
+    match vendor_id {
+        0x10de => "NVIDIA",
+        0x1002 => "AMD",
+        0x8086 => "Intel",
+        0x1ed5 => "Moore Threads",
+        0x13b5 => "ARM",
+        0x5143 => "Qualcomm",
+        0x1010 => "Imagination Technologies",
+        0x1a03 => "ASPEED",
+        0x102b => "Matrox",
+        0x15ad => "VMware",
+        0x1af4 => "Red Hat (virtio)",
+        0x1234 => "QEMU",
+        _ => "Unknown",
+    }
+}
+
+fn read_hex(path: &Path) -> Option<u32> {
+    let value = fs::read_to_string(path).ok()?;
+    u32::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok()
+}
+
+fn read_device(path: &Path) -> Option<PciDevice> {
+    let physfn = fs::read_link(path.join("physfn"))
+        .ok()
+        .and_then(|link| Some(link.file_name()?.to_string_lossy().into_owned()));
+
+    Some(PciDevice {
+        address: path.file_name()?.to_string_lossy().into_owned(),
+        vendor_id: read_hex(&path.join("vendor"))? as u16,
+        device_id: read_hex(&path.join("device"))? as u16,
+        class: read_hex(&path.join("class"))?,
+        physfn,
+    })
+}
+
+/// Lists the PCI devices in sysfs that are GPUs, virtual functions included: those of the
+/// [`GPU_CLASSES`] that are not BMC consoles.
+pub fn list_display_devices() -> io::Result<Vec<PciDevice>> {
+    list_display_devices_in(Path::new(SYSFS_PCI_DEVICES))
+}
+
+/// `list_display_devices` over another directory laid out like `/sys/bus/pci/devices`.
+pub fn list_display_devices_in(devices_dir: &Path) -> io::Result<Vec<PciDevice>> {
+    let mut devices: Vec<PciDevice> = fs::read_dir(devices_dir)?
+        .filter_map(|entry| read_device(&entry.ok()?.path()))
+        .filter(|device| GPU_CLASSES.contains(&((device.class >> 8) as u16)) && !is_bmc_vendor(device.vendor_id))
+        .collect();
+
+    devices.sort_by(|a, b| a.address.cmp(&b.address));
+    Ok(devices)
+}
+
+/// Groups SR-IOV virtual functions under their physical function. Virtual functions whose
+/// physical function is not in `devices` (e.g. a VF passed through to a guest) are kept as
+/// standalone devices.
+pub fn group_virtual_functions(devices: &[PciDevice]) -> Vec<PciDeviceGroup> {
+    let mut groups: Vec<PciDeviceGroup> = devices
+        .iter()
+        .filter(|device| match &device.physfn {
+            Some(physfn) => !devices.iter().any(|other| &other.address == physfn),
+            None => true,
+        })
+        .map(|device| PciDeviceGroup { physical: device.clone(), virtual_functions: Vec::new() })
+        .collect();
+
+    for device in devices {
+        if let Some(physfn) = &device.physfn {
+            if let Some(group) = groups.iter_mut().find(|group| &group.physical.address == physfn) {
+                group.virtual_functions.push(device.clone());
+            }
+        }
+    }
+
+    groups
+}
+
+pub fn format_device_group(group: &PciDeviceGroup) -> String {
+    let device = &group.physical;
+    let mut line = format!(
+        "{} {} [{:04x}:{:04x}]",
+        device.address,
+        vendor_name(device.vendor_id),
+        device.vendor_id,
+        device.device_id
+    );
+
+    if !group.virtual_functions.is_empty() {
+        line.push_str(&format!(" ({} virtual functions)", group.virtual_functions.len()));
+    }
+
+    line
+}
+
+/// Normalizes a PCI address to sysfs form: nvidia-smi reports an 8-digit domain
+/// (`00000000:3B:00.0`) where sysfs uses 4 lowercase digits (`0000:3b:00.0`).
+pub fn normalize_bus_id(bus_id: &str) -> String {
+    let bus_id = bus_id.trim().to_lowercase();
+
+    match bus_id.split_once(':') {
+        Some((domain, rest)) if rest.contains(':') => match u32::from_str_radix(domain, 16) {
+            Ok(domain) => format!("{:04x}:{}", domain, rest),
+            Err(_) => bus_id,
+        },
+        _ => format!("0000:{}", bus_id),
+    }
+}
+
+/// A device line of `lspci -Dnn` output, or of plain `lspci`, which lacks the numeric IDs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspciDevice<'a> {
+    pub address: &'a str,
+    /// Base class and subclass, e.g. `0x0302`; `None` for a class name gpuatop does not know
+    /// and no code to go by.
+    pub class: Option<u16>,
+    /// `None` without `-n` for a vendor name gpuatop does not know.
+    pub vendor_id: Option<u16>,
+    /// Vendor and device name, without the IDs and revision.
+    pub description: &'a str,
+}
+
+impl LspciDevice<'_> {
+    /// A display controller of one of the [`GPU_CLASSES`] that is not a BMC console.
+    pub fn is_gpu(&self) -> bool {
+        self.class.is_some_and(|class| GPU_CLASSES.contains(&class)) && !self.vendor_id.is_some_and(is_bmc_vendor)
+    }
+}
+
+/// The classes of [`GPU_CLASSES`] as `lspci` names them without `-n`.
+const LSPCI_CLASS_NAMES: [(&str, u16); 3] = [("VGA compatible controller", 0x0300), ("3D controller", 0x0302), ("Display controller", 0x0380)];
+
+/// Vendor names as `lspci` prints them, for output without `-n`.
+const LSPCI_VENDOR_NAMES: [(&str, u16); 6] = [
+    ("NVIDIA Corporation", 0x10de),
+    ("Advanced Micro Devices", 0x1002),
+    ("Intel Corporation", 0x8086),
+    ("ASPEED Technology", 0x1a03),
+    ("Matrox Electronics", 0x102b),
+    ("Moore Threads", 0x1ed5),
+];
+
+/// `[xxxx]`, `[xxxx:yyyy]` and the like: hexadecimal fields in brackets at the end of `text`.
+fn trailing_ids(text: &str) -> Option<(&str, Vec<u16>)> {
+    let (rest, ids) = text.strip_suffix(']')?.rsplit_once('[')?;
+    let ids = ids.split(':').map(|id| if id.len() == 4 { u16::from_str_radix(id, 16).ok() } else { None }).collect::<Option<Vec<u16>>>()?;
+    Some((rest.trim_end(), ids))
+}
+
+fn parse_lspci_line(line: &str) -> Option<LspciDevice<'_>> {
+    let (address, rest) = line.split_once(' ')?;
+    let (class_text, description) = rest.split_once(": ")?;
+    if !address.contains(':') {
+        return None;
+    }
+
+    let class = match trailing_ids(class_text) {
+        Some((_, ids)) if ids.len() == 1 => Some(ids[0]),
+        _ => LSPCI_CLASS_NAMES.iter().find(|(name, _)| *name == class_text).map(|(_, class)| *class),
+    };
+
+    let description = description.trim_end();
+    let description = match description.rsplit_once(" (rev ") {
+        Some((before, revision)) if revision.ends_with(')') => before,
+        _ => description,
+    };
+    let (description, vendor_id) = match trailing_ids(description) {
+        Some((rest, ids)) if ids.len() == 2 => (rest, Some(ids[0])),
+        _ => (description, LSPCI_VENDOR_NAMES.iter().find(|(name, _)| description.starts_with(name)).map(|(_, vendor)| *vendor)),
+    };
+
+    Some(LspciDevice { address, class, vendor_id, description })
+}
+
+/// Every device of `lspci` output; the indented detail lines of `-v` are skipped.
+pub fn parse_lspci(output: &str) -> Vec<LspciDevice<'_>> {
+    output.lines().filter(|line| !line.starts_with(char::is_whitespace)).filter_map(parse_lspci_line).collect()
+}
+
+/// The GPUs of `lspci` output, see [`LspciDevice::is_gpu`].
+pub fn lspci_gpus(output: &str) -> Vec<LspciDevice<'_>> {
+    parse_lspci(output).into_iter().filter(LspciDevice::is_gpu).collect()
+}
@@ -0,0 +1,58 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+use crate::csv;
+use crate::runner::CommandRunner;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuModes {
+    pub index: u32,
+    pub persistence_mode: String,
+    pub compute_mode: String,
+}
+
+impl GpuModes {
+    pub fn persistence_enabled(&self) -> bool {
+        self.persistence_mode.eq_ignore_ascii_case("Enabled")
+    }
+}
+
+/// Parses `nvidia-smi --query-gpu=index,persistence_mode,compute_mode --format=csv,noheader`.
+pub fn parse_modes(output: &str) -> Vec<GpuModes> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = csv::parse_line(line).into_iter();
+            Some(GpuModes {
+                index: csv::number(&fields.next()?)?,
+                persistence_mode: fields.next()?,
+                compute_mode: fields.next()?,
+            })
+        })
+        .collect()
+}
+
+pub fn query_modes(runner: &dyn CommandRunner) -> Vec<GpuModes> {
+    match runner.run("nvidia-smi", &["--query-gpu=index,persistence_mode,compute_mode", "--format=csv,noheader"]) {
+        Ok(output) if output.success => parse_modes(&output.stdout),
+        _ => Vec::new(),
+    }
+}
+
+/// A machine is considered headless when no display server is reachable from this session
+/// and no X server socket exists.
+pub fn is_headless() -> bool {
+    let has_display = ["DISPLAY", "WAYLAND_DISPLAY"].iter().any(|var| env::var_os(var).is_some_and(|value| !value.is_empty()));
+    let has_x_server = fs::read_dir("/tmp/.X11-unix").map(|mut entries| entries.next().is_some()).unwrap_or(false);
+
+    !has_display && !has_x_server
+}
+
+pub const PERSISTENCE_WARNING: &str = "Warning: Persistence mode is disabled on this headless machine, so the driver \
+unloads when idle and every query has to reinitialize it. Enable it with `sudo nvidia-smi -pm 1` \
+(or run nvidia-persistenced), or run `gpuatop fix-persistence`.";
+
+pub fn enable_persistence() -> std::io::Result<bool> {
+    Ok(Command::new("nvidia-smi").args(["-pm", "1"]).status()?.success())
+}
@@ -0,0 +1,101 @@
+//! Other processes polling the GPUs. Every poller adds load that skews the measurements, and on
+//! some AMD cards concurrent readers of `gpu_metrics` read garbage. Well-known monitors are
+//! only reported; gpuatop instances take an advisory lock per GPU so that a second one does not
+//! start against the same GPU.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::GpuInfo;
+
+/// Process names (`/proc/<pid>/comm`) of monitors that poll the GPUs continuously.
+pub const KNOWN_MONITORS: [&str; 2] = ["nv-hostengine", "dcgm-exporter"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Monitor {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// The [`KNOWN_MONITORS`] running under `proc_root`, by PID.
+pub fn find_monitors_in(proc_root: &Path) -> Vec<Monitor> {
+    let Ok(entries) = fs::read_dir(proc_root) else { return Vec::new() };
+
+    let mut monitors: Vec<Monitor> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            let name = comm.trim_end();
+            KNOWN_MONITORS.contains(&name).then(|| Monitor { pid, name: name.to_string() })
+        })
+        .collect();
+    monitors.sort_by_key(|monitor| monitor.pid);
+    monitors
+}
+
+pub fn find_monitors() -> Vec<Monitor> {
+    find_monitors_in(Path::new("/proc"))
+}
+
+/// Where the lock files live: `$XDG_RUNTIME_DIR/gpuatop`, or a per-user directory under the
+/// temporary directory.
+pub fn lock_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("gpuatop"),
+        None => std::env::temp_dir().join(format!("gpuatop-{}", crate::process::effective_uid().unwrap_or(0))),
+    }
+}
+
+/// One lock file per GPU, named after its PCI bus ID where known so that the lock follows the
+/// device rather than its index.
+fn lock_file(dir: &Path, gpu: &GpuInfo) -> PathBuf {
+    match &gpu.bus_id {
+        Some(bus_id) => dir.join(format!("{}.lock", bus_id)),
+        None => dir.join(format!("gpu{}.lock", gpu.index)),
+    }
+}
+
+/// A GPU another gpuatop already monitors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Busy {
+    pub gpu: u32,
+    /// The PID the other instance wrote into the lock file.
+    pub pid: Option<u32>,
+}
+
+/// The locks held on the monitored GPUs. They are `flock` locks, which the kernel releases
+/// when the process exits, crash included, so a lock file left behind never blocks anyone.
+#[derive(Debug)]
+pub struct DeviceLocks {
+    _files: Vec<File>,
+}
+
+impl DeviceLocks {
+    /// Locks every GPU in `gpus` that no other gpuatop holds, and reports those that one does.
+    pub fn acquire(dir: &Path, gpus: &[GpuInfo]) -> io::Result<(DeviceLocks, Vec<Busy>)> {
+        fs::create_dir_all(dir)?;
+        let mut files = Vec::new();
+        let mut busy = Vec::new();
+
+        for gpu in gpus {
+            let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(lock_file(dir, gpu))?;
+            match file.try_lock() {
+                Ok(()) => {
+                    file.set_len(0)?;
+                    write!(file, "{}", std::process::id())?;
+                    files.push(file);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    let mut pid = String::new();
+                    file.read_to_string(&mut pid)?;
+                    busy.push(Busy { gpu: gpu.index, pid: pid.trim().parse().ok() });
+                }
+                Err(TryLockError::Error(err)) => return Err(err),
+            }
+        }
+
+        Ok((DeviceLocks { _files: files }, busy))
+    }
+}
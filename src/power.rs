@@ -0,0 +1,76 @@
+//! GPUs that stop answering mid-run. The kernel runtime-suspends an idle discrete GPU (Optimus,
+//! PRIME offload), and querying it through the vendor tool wakes it up again, so a suspended GPU
+//! is reported `asleep` instead of being sampled. A GPU whose driver is unloaded (`rmmod`) or
+//! whose device is removed is `lost`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::GpuInfo;
+
+const SYSFS_PCI_DEVICES: &str = "/sys/bus/pci/devices";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceState {
+    Active,
+    /// Runtime-suspended; not sampled, so it stays asleep.
+    Asleep,
+    /// The driver was unbound or the device removed.
+    Lost,
+}
+
+impl DeviceState {
+    /// The `state` value of the JSON records.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceState::Active => "active",
+            DeviceState::Asleep => "asleep",
+            DeviceState::Lost => "lost",
+        }
+    }
+}
+
+/// Whether a `power/runtime_status` value means the device is powered down. `suspending` is
+/// left alone: the device is still up and the next tick sees it suspended.
+pub fn is_suspended(runtime_status: &str) -> bool {
+    runtime_status.trim() == "suspended"
+}
+
+/// The PCI devices of the GPUs that had a driver bound at startup. Only those are watched, so
+/// a GPU whose bus ID has no sysfs device (containers, a tool reporting another domain) is
+/// never mistaken for a lost one.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceWatch {
+    devices: Vec<(u32, PathBuf)>,
+}
+
+impl DeviceWatch {
+    pub fn new(gpus: &[GpuInfo]) -> Self {
+        DeviceWatch::with_root(Path::new(SYSFS_PCI_DEVICES), gpus)
+    }
+
+    /// Same as [`DeviceWatch::new`], with `root` in place of `/sys/bus/pci/devices`.
+    pub fn with_root(root: &Path, gpus: &[GpuInfo]) -> Self {
+        let devices = gpus
+            .iter()
+            .filter_map(|gpu| Some((gpu.index, root.join(gpu.bus_id.as_ref()?))))
+            .filter(|(_, device)| device.join("driver").exists())
+            .collect();
+
+        DeviceWatch { devices }
+    }
+
+    pub fn state(&self, gpu: &GpuInfo) -> DeviceState {
+        let Some((_, device)) = self.devices.iter().find(|(index, _)| *index == gpu.index) else {
+            return DeviceState::Active;
+        };
+
+        if !device.join("driver").exists() {
+            return DeviceState::Lost;
+        }
+        match fs::read_to_string(device.join("power").join("runtime_status")) {
+            Ok(status) if is_suspended(&status) => DeviceState::Asleep,
+            _ => DeviceState::Active,
+        }
+    }
+}
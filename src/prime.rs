@@ -0,0 +1,116 @@
+//! Hybrid graphics laptops (NVIDIA Optimus, PRIME): whether a GPU drives the display or only
+//! renders the applications offloaded to it. A discrete GPU in offload mode reads 0% while
+//! the desktop runs on the integrated GPU, which is expected rather than a broken sample.
+
+use std::fs;
+use std::path::Path;
+
+use crate::pci::{self, PciDevice};
+use crate::runner::CommandRunner;
+use crate::{GpuInfo, GpuType};
+
+const SYSFS_PCI_DEVICES: &str = "/sys/bus/pci/devices";
+
+const VENDOR_NVIDIA: u16 = 0x10de;
+const VENDOR_AMD: u16 = 0x1002;
+const VENDOR_INTEL: u16 = 0x8086;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderOffloadMode {
+    /// The GPU renders the desktop.
+    PrimaryGpu,
+    /// The desktop runs on the other GPU; this one only renders offloaded applications.
+    OffloadGpu,
+    Unknown,
+}
+
+/// The profile reported by `prime-select query` (nvidia-prime on Ubuntu and derivatives).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimeProfile {
+    Nvidia,
+    Intel,
+    OnDemand,
+}
+
+pub fn parse_prime_select(output: &str) -> Option<PrimeProfile> {
+    match output.trim() {
+        "nvidia" => Some(PrimeProfile::Nvidia),
+        "intel" => Some(PrimeProfile::Intel),
+        "on-demand" => Some(PrimeProfile::OnDemand),
+        _ => None,
+    }
+}
+
+/// Whether `devices` pair an Intel GPU with a GPU of another vendor, as on Optimus laptops.
+pub fn is_hybrid(devices: &[PciDevice]) -> bool {
+    let physical = || devices.iter().filter(|device| device.physfn.is_none());
+
+    physical().any(|device| device.vendor_id == VENDOR_INTEL) && physical().any(|device| device.vendor_id != VENDOR_INTEL)
+}
+
+/// The mode of a GPU of `vendor_id`. The PRIME profile names the GPU the desktop runs on;
+/// without one, the firmware's boot VGA device is taken to be the primary GPU.
+pub fn render_offload_mode(vendor_id: u16, profile: Option<PrimeProfile>, boot_vga: Option<bool>) -> RenderOffloadMode {
+    match (vendor_id, profile) {
+        (VENDOR_NVIDIA, Some(PrimeProfile::Nvidia)) | (VENDOR_INTEL, Some(PrimeProfile::Intel | PrimeProfile::OnDemand)) => RenderOffloadMode::PrimaryGpu,
+        (VENDOR_NVIDIA, Some(PrimeProfile::Intel | PrimeProfile::OnDemand)) | (VENDOR_INTEL, Some(PrimeProfile::Nvidia)) => RenderOffloadMode::OffloadGpu,
+        _ => match boot_vga {
+            Some(true) => RenderOffloadMode::PrimaryGpu,
+            Some(false) => RenderOffloadMode::OffloadGpu,
+            None => RenderOffloadMode::Unknown,
+        },
+    }
+}
+
+fn vendor_id(gpu_type: &GpuType) -> Option<u16> {
+    match gpu_type {
+        GpuType::Nvidia => Some(VENDOR_NVIDIA),
+        GpuType::Amd | GpuType::AmdApu => Some(VENDOR_AMD),
+        GpuType::Intel | GpuType::IntelArc(_) => Some(VENDOR_INTEL),
+        GpuType::JetsonGpu | GpuType::Broadcom(_) | GpuType::Unknown(_) => None,
+    }
+}
+
+/// The PCI device of `gpu`: by bus ID when the vendor tool reports one, otherwise the only
+/// device of the vendor.
+fn find_device<'a>(devices: &'a [PciDevice], gpu: &GpuInfo, gpu_type: &GpuType) -> Option<&'a PciDevice> {
+    if let Some(bus_id) = &gpu.bus_id {
+        return devices.iter().find(|device| &device.address == bus_id);
+    }
+
+    let vendor_id = vendor_id(gpu_type)?;
+    let mut candidates = devices.iter().filter(|device| device.vendor_id == vendor_id && device.physfn.is_none());
+    match (candidates.next(), candidates.next()) {
+        (Some(device), None) => Some(device),
+        _ => None,
+    }
+}
+
+fn read_boot_vga(address: &str) -> Option<bool> {
+    let value = fs::read_to_string(Path::new(SYSFS_PCI_DEVICES).join(address).join("boot_vga")).ok()?;
+    Some(value.trim() == "1")
+}
+
+/// Sets `render_offload` on each GPU when the machine has hybrid graphics; elsewhere it stays
+/// `None`.
+pub fn annotate(runner: &dyn CommandRunner, gpu_type: &GpuType, gpus: &mut [GpuInfo]) {
+    let Ok(devices) = pci::list_display_devices() else {
+        return;
+    };
+    if !is_hybrid(&devices) {
+        return;
+    }
+
+    let profile = runner
+        .run("prime-select", &["query"])
+        .ok()
+        .filter(|output| output.success)
+        .and_then(|output| parse_prime_select(&output.stdout));
+
+    for gpu in gpus {
+        gpu.render_offload = Some(match find_device(&devices, gpu, gpu_type) {
+            Some(device) => render_offload_mode(device.vendor_id, profile, read_boot_vga(&device.address)),
+            None => RenderOffloadMode::Unknown,
+        });
+    }
+}
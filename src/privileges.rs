@@ -0,0 +1,292 @@
+//! Whether the vendor tool can read the GPU without root. `intel_gpu_top` reads the i915 PMU
+//! through perf, which unprivileged users may only do with `kernel.perf_event_paranoid` at 0 or
+//! below; `radeontop` needs read-write access to a `/dev/dri/card*` node. Run without it, both
+//! hang or print zeros instead of failing.
+//!
+//! Most often what is missing is membership in the group owning `/dev/dri`, `render` or
+//! `video`. The preflight ([`dri_preflight`]) tells from the nodes' ownership and the user's
+//! groups before the first sample, and names the `usermod` command that fixes it; under `sudo`
+//! it tells the user who would not need root with the group.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::GpuType;
+
+const PERF_EVENT_PARANOID: &str = "/proc/sys/kernel/perf_event_paranoid";
+const DEV_DRI: &str = "/dev/dri";
+const GROUP_PATH: &str = "/etc/group";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivilegeCheck {
+    RequiresRoot,
+    SufficientAsUser,
+    /// There is nothing to tell from, e.g. no perf support or no DRM device.
+    Unknown,
+}
+
+pub fn parse_perf_event_paranoid(value: &str) -> Option<i32> {
+    value.trim().parse().ok()
+}
+
+/// The decision behind [`check_privileges`], from the perf paranoia level and whether a DRM
+/// card node can be opened read-write.
+pub fn requirement(gpu_type: &GpuType, perf_event_paranoid: Option<i32>, dri_access: Option<bool>) -> PrivilegeCheck {
+    match (gpu_type, perf_event_paranoid, dri_access) {
+        (GpuType::Nvidia | GpuType::JetsonGpu, _, _) => PrivilegeCheck::SufficientAsUser,
+        (GpuType::Intel, Some(level), _) if level <= 0 => PrivilegeCheck::SufficientAsUser,
+        (GpuType::Intel, Some(_), _) => PrivilegeCheck::RequiresRoot,
+        (GpuType::Amd, _, Some(true)) => PrivilegeCheck::SufficientAsUser,
+        (GpuType::Amd, _, Some(false)) => PrivilegeCheck::RequiresRoot,
+        _ => PrivilegeCheck::Unknown,
+    }
+}
+
+/// Who the process runs as, which decides the access to a device node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub uid: u32,
+    pub gid: u32,
+    /// The supplementary groups of the login session, which a `usermod` only changes for the
+    /// sessions started after it.
+    pub groups: Vec<u32>,
+}
+
+impl Identity {
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/// The effective user and group and the supplementary groups in `/proc/self/status`.
+pub fn parse_identity(status: &str) -> Option<Identity> {
+    let field = |name: &str| status.lines().find_map(|line| line.strip_prefix(name));
+    let effective = |name: &str| field(name)?.split_whitespace().nth(1)?.parse().ok();
+    let groups = field("Groups:")?.split_whitespace().filter_map(|gid| gid.parse().ok()).collect();
+
+    Some(Identity { uid: effective("Uid:")?, gid: effective("Gid:")?, groups })
+}
+
+/// A DRM device node with the owner and permissions that decide who opens it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceNode {
+    pub path: PathBuf,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+}
+
+impl DeviceNode {
+    /// Whether `identity` may open the node read-write, as the kernel decides it from the
+    /// permission bits: the owner's, else the group's, else everyone else's.
+    pub fn opens_for(&self, identity: &Identity) -> bool {
+        let bits = if identity.uid == 0 {
+            return true;
+        } else if identity.uid == self.uid {
+            self.mode >> 6
+        } else if identity.in_group(self.gid) {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        bits & 0o6 == 0o6
+    }
+
+    fn is_render_node(&self) -> bool {
+        self.path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("renderD"))
+    }
+}
+
+/// The card and render nodes in `/dev/dri`.
+pub fn dri_nodes() -> Vec<DeviceNode> {
+    let Ok(entries) = fs::read_dir(DEV_DRI) else {
+        return Vec::new();
+    };
+
+    let mut nodes: Vec<DeviceNode> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with("card") || name.starts_with("renderD")))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(DeviceNode { path: entry.path(), uid: metadata.uid(), gid: metadata.gid(), mode: metadata.mode() })
+        })
+        .collect();
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    nodes
+}
+
+/// The first node `identity` cannot open among a kind, card or render, of which it opens none.
+/// `None` when it opens one of each kind there is, or there are no nodes.
+pub fn denied_node<'a>(nodes: &'a [DeviceNode], identity: &Identity) -> Option<&'a DeviceNode> {
+    [false, true].into_iter().find_map(|render| {
+        let kind: Vec<&DeviceNode> = nodes.iter().filter(|node| node.is_render_node() == render).collect();
+        if kind.iter().any(|node| node.opens_for(identity)) {
+            None
+        } else {
+            kind.first().copied()
+        }
+    })
+}
+
+/// The groups `/etc/group` lists `user` as a member of. A group added with `usermod` shows up
+/// here at once, and in the session's groups only after logging in again.
+pub fn user_groups(contents: &str, user: &str) -> Vec<u32> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let gid = fields.get(2)?.parse().ok()?;
+            fields.get(3)?.split(',').any(|member| member == user).then_some(gid)
+        })
+        .collect()
+}
+
+/// What a user who cannot open `node` is told: the command that adds them to the `group`
+/// owning it, or, when `/etc/group` lists them in it already (`listed`), that the session
+/// predates the change.
+pub fn dri_remedy(tool: &str, node: &Path, group: &str, listed: bool) -> String {
+    if listed {
+        format!(
+            "{} cannot open {}: you were added to the {} group after this session started; log out and back in, or run `newgrp {}`",
+            tool,
+            node.display(),
+            group,
+            group
+        )
+    } else {
+        format!(
+            "{} cannot open {}, which needs the {} group; add yourself with `sudo usermod -aG {} $USER`, then log out and back in",
+            tool,
+            node.display(),
+            group,
+            group
+        )
+    }
+}
+
+/// What a user running gpuatop through `sudo` only for the `group` is told.
+pub fn sudo_remedy(tool: &str, user: &str, group: &str) -> String {
+    format!(
+        "{} needs root here only because {} is not in the {} group; after `sudo usermod -aG {} {}` and logging in again, gpuatop runs without sudo",
+        tool, user, group, group, user
+    )
+}
+
+/// The tool that opens `/dev/dri` for `gpu_type`.
+fn dri_reader(gpu_type: &GpuType) -> Option<&'static str> {
+    match gpu_type {
+        GpuType::Amd => Some("radeontop"),
+        GpuType::Intel => Some("intel_gpu_top"),
+        GpuType::Nvidia | GpuType::AmdApu | GpuType::IntelArc(_) | GpuType::JetsonGpu | GpuType::Broadcom(_) | GpuType::Unknown(_) => None,
+    }
+}
+
+/// The user `sudo` ran gpuatop for, from the variables it sets: name, uid and gid.
+fn sudo_user() -> Option<(String, u32, u32)> {
+    let variable = |name: &str| env::var(name).ok();
+    Some((variable("SUDO_USER")?, variable("SUDO_UID")?.parse().ok()?, variable("SUDO_GID")?.parse().ok()?))
+}
+
+/// The `/dev/dri` preflight: a remedy when the user cannot open the GPU's DRM nodes, or when
+/// gpuatop runs through `sudo` for a user who only lacks the group. `None` when access is
+/// fine, the GPU needs no DRM node, or there is nothing to tell from.
+pub fn dri_preflight(gpu_type: &GpuType) -> Option<String> {
+    let tool = dri_reader(gpu_type)?;
+    let nodes = dri_nodes();
+    let groups = fs::read_to_string(GROUP_PATH).unwrap_or_default();
+    let identity = parse_identity(&fs::read_to_string("/proc/self/status").ok()?)?;
+
+    if identity.uid == 0 {
+        // intel_gpu_top needs root for perf as well, unless users may read the counters.
+        if *gpu_type == GpuType::Intel && check_privileges(gpu_type) == PrivilegeCheck::RequiresRoot {
+            return None;
+        }
+        let (user, uid, gid) = sudo_user()?;
+        let invoker = Identity { uid, gid, groups: user_groups(&groups, &user) };
+        let node = denied_node(&nodes, &invoker)?;
+        return Some(sudo_remedy(tool, &user, &group_name(&groups, node.gid)?));
+    }
+
+    let node = denied_node(&nodes, &identity)?;
+    let group = group_name(&groups, node.gid)?;
+    let listed = env::var("USER").is_ok_and(|user| user_groups(&groups, &user).contains(&node.gid));
+    Some(dri_remedy(tool, &node.path, &group, listed))
+}
+
+fn card_nodes() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(DEV_DRI) else {
+        return Vec::new();
+    };
+
+    let mut cards: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with("card")))
+        .map(|entry| entry.path())
+        .collect();
+    cards.sort();
+    cards
+}
+
+/// Whether any DRM card node opens read-write; `None` without DRM devices.
+fn dri_access() -> Option<bool> {
+    let cards = card_nodes();
+    if cards.is_empty() {
+        return None;
+    }
+
+    Some(cards.iter().any(|card| OpenOptions::new().read(true).write(true).open(card).is_ok()))
+}
+
+/// Whether `gpu_type`'s tool needs root on this machine. Running as root is not considered.
+pub fn check_privileges(gpu_type: &GpuType) -> PrivilegeCheck {
+    let perf_event_paranoid = match gpu_type {
+        GpuType::Intel => fs::read_to_string(PERF_EVENT_PARANOID).ok().and_then(|value| parse_perf_event_paranoid(&value)),
+        _ => None,
+    };
+    let dri_access = match gpu_type {
+        GpuType::Amd => dri_access(),
+        _ => None,
+    };
+
+    requirement(gpu_type, perf_event_paranoid, dri_access)
+}
+
+/// Name of group `gid` in the contents of `/etc/group`.
+pub fn group_name(contents: &str, gid: u32) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        (fields.nth(1)?.parse::<u32>().ok()? == gid).then(|| name.to_string())
+    })
+}
+
+/// The group owning the first DRM card node, `video` or `render` depending on the distribution.
+fn dri_group() -> Option<String> {
+    let gid = fs::metadata(card_nodes().first()?).ok()?.gid();
+    group_name(&fs::read_to_string(Path::new(GROUP_PATH)).ok()?, gid)
+}
+
+/// What to tell a user without root, with the way to get by without it where there is one.
+pub fn root_message(gpu_type: &GpuType, dri_group: Option<&str>) -> Option<String> {
+    match gpu_type {
+        GpuType::Intel => Some(
+            "intel_gpu_top requires root; re-run with sudo, or let it read the GPU counters as a user with `sudo sysctl kernel.perf_event_paranoid=0`"
+                .to_string(),
+        ),
+        GpuType::Amd => {
+            let group = dri_group.unwrap_or("render");
+            Some(format!(
+                "radeontop requires root or access to /dev/dri; re-run with sudo or add yourself to the {} group (`sudo usermod -aG {} $USER`, then log in again)",
+                group, group
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// [`root_message`] with the group read from the DRM device nodes.
+pub fn message(gpu_type: &GpuType) -> Option<String> {
+    root_message(gpu_type, dri_group().as_deref())
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::backend::{read_process_drm_clients, DrmClient};
+use crate::runner::CommandRunner;
+use crate::{csv, GpuType};
+
+/// The kind of GPU context a process holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextKind {
+    Compute,
+    Graphics,
+    /// Both, `C+G` in `nvidia-smi pmon`: counted as compute and as graphics.
+    Mixed,
+}
+
+impl ContextKind {
+    /// The `type` column of `nvidia-smi pmon`: `C`, `G` or `C+G`.
+    pub fn from_pmon(value: &str) -> Option<Self> {
+        match value {
+            "C" => Some(ContextKind::Compute),
+            "G" => Some(ContextKind::Graphics),
+            "C+G" => Some(ContextKind::Mixed),
+            _ => None,
+        }
+    }
+
+    /// An approximation from the engines the process's DRM clients have used: the `compute`
+    /// engine for compute, `gfx` (amdgpu) or `render` (i915, xe) for graphics. A process that
+    /// only used the copy or video engines is neither.
+    pub fn from_drm_clients(clients: &[DrmClient]) -> Option<Self> {
+        let used = |names: &[&str]| clients.iter().flat_map(|client| &client.engines).any(|(name, busy)| *busy > 0 && names.contains(&name.as_str()));
+        match (used(&["compute"]), used(&["gfx", "render"])) {
+            (true, true) => Some(ContextKind::Mixed),
+            (true, false) => Some(ContextKind::Compute),
+            (false, true) => Some(ContextKind::Graphics),
+            (false, false) => None,
+        }
+    }
+
+    pub fn is_compute(self) -> bool {
+        matches!(self, ContextKind::Compute | ContextKind::Mixed)
+    }
+
+    pub fn is_graphics(self) -> bool {
+        matches!(self, ContextKind::Graphics | ContextKind::Mixed)
+    }
+}
+
+/// GPU usage of a single process as reported by the vendor tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuProcess {
+    pub gpu_index: u32,
+    pub pid: u32,
+    pub name: String,
+    /// Share of the GPU's compute engines used by the process, in percent.
+    pub utilization: Option<f32>,
+    pub memory_used_mib: Option<u64>,
+    /// `None` where the source cannot tell.
+    pub kind: Option<ContextKind>,
+}
+
+/// The number of compute processes and graphics clients among `processes`; a process with
+/// both kinds of contexts counts once in each.
+pub fn count_contexts(processes: &[&GpuProcess]) -> (usize, usize) {
+    let kinds = || processes.iter().filter_map(|process| process.kind);
+    (kinds().filter(|kind| kind.is_compute()).count(), kinds().filter(|kind| kind.is_graphics()).count())
+}
+
+/// Parses `nvidia-smi pmon -c 1`. Columns are located through the header because newer
+/// drivers add columns (`jpg`, `ofa`, `fb`, ...); `-` marks values that are not available.
+pub fn parse_nvidia_pmon(output: &str) -> Vec<GpuProcess> {
+    let mut lines = output.lines();
+    let Some(header) = lines.next() else { return Vec::new() };
+    let columns: Vec<&str> = header.trim_start_matches('#').split_whitespace().collect();
+    let column = |name: &str| columns.iter().position(|column| *column == name);
+
+    let (Some(gpu), Some(pid)) = (column("gpu"), column("pid")) else { return Vec::new() };
+    let sm = column("sm");
+    let fb = column("fb");
+    let command = column("command");
+    let kind = column("type");
+
+    lines
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            let value = |index: Option<usize>| index.and_then(|index| values.get(index)).filter(|value| **value != "-");
+
+            Some(GpuProcess {
+                gpu_index: value(Some(gpu))?.parse().ok()?,
+                pid: value(Some(pid))?.parse().ok()?,
+                name: value(command).map(|name| name.to_string()).unwrap_or_default(),
+                utilization: value(sm).and_then(|sm| sm.parse().ok()),
+                memory_used_mib: value(fb).and_then(|fb| fb.parse().ok()),
+                kind: value(kind).and_then(|kind| ContextKind::from_pmon(kind)),
+            })
+        })
+        .collect()
+}
+
+/// Parses the KFD process table from `rocm-smi --showpids`. rocm-smi reports no per-process
+/// utilization, only VRAM (in bytes), and no per-GPU split, so processes are attributed to GPU 0.
+pub fn parse_rocm_smi_pids(output: &str) -> Vec<GpuProcess> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("PROCESS NAME"))
+        .skip(1)
+        .take_while(|line| !line.starts_with('='))
+        .filter_map(|line| {
+            let values: Vec<&str> = line.split_whitespace().collect();
+
+            Some(GpuProcess {
+                gpu_index: 0,
+                pid: values.get(1)?.parse().ok()?,
+                name: values.first()?.to_string(),
+                utilization: None,
+                memory_used_mib: values.get(3).and_then(|bytes| bytes.parse::<u64>().ok()).map(|bytes| bytes / (1024 * 1024)),
+                kind: None,
+            })
+        })
+        .collect()
+}
+
+/// Parses `nvidia-smi --query-compute-apps=pid,used_memory --format=csv,noheader,nounits`
+/// into PIDs and their memory in MiB, for drivers whose `pmon` has no `fb` column. A process
+/// on several GPUs has one line per GPU.
+pub fn parse_compute_apps(output: &str) -> Vec<(u32, u64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields = csv::parse_line(line);
+            Some((fields.first()?.parse().ok()?, csv::number(fields.get(1)?)?))
+        })
+        .collect()
+}
+
+pub fn query_compute_apps(runner: &dyn CommandRunner) -> io::Result<Vec<(u32, u64)>> {
+    let output = runner.run("nvidia-smi", &["--query-compute-apps=pid,used_memory", "--format=csv,noheader,nounits"])?;
+    Ok(parse_compute_apps(&output.stdout))
+}
+
+/// Whether the vendor tool reports how busy each process keeps the GPU. rocm-smi only reports
+/// their VRAM.
+pub fn reports_utilization(gpu_type: &GpuType) -> bool {
+    *gpu_type == GpuType::Nvidia
+}
+
+pub fn query_processes(runner: &dyn CommandRunner, gpu_type: &GpuType) -> io::Result<Vec<GpuProcess>> {
+    match gpu_type {
+        GpuType::Nvidia => {
+            let output = runner.run("nvidia-smi", &["pmon", "-c", "1"])?;
+            Ok(parse_nvidia_pmon(&output.stdout))
+        }
+        GpuType::Amd => {
+            let output = runner.run("rocm-smi", &["--showpids"])?;
+            let mut processes = parse_rocm_smi_pids(&output.stdout);
+            // rocm-smi has no context types; the process's own DRM files tell.
+            for process in &mut processes {
+                process.kind = ContextKind::from_drm_clients(&read_process_drm_clients(process.pid));
+            }
+            Ok(processes)
+        }
+        GpuType::AmdApu => Err(io::Error::new(io::ErrorKind::Unsupported, "Per-process metrics are not supported for AMD APUs")),
+        GpuType::Intel | GpuType::IntelArc(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "Per-process metrics are not supported for Intel GPUs")),
+        GpuType::JetsonGpu => Err(io::Error::new(io::ErrorKind::Unsupported, "Per-process metrics are not supported for Jetson GPUs")),
+        GpuType::Broadcom(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "Per-process metrics are not supported for Raspberry Pi GPUs")),
+        GpuType::Unknown(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "Per-process metrics are not supported for this GPU")),
+    }
+}
+
+pub fn is_process_alive(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+pub fn parse_pid_list(value: &str) -> Result<Vec<u32>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pid| !pid.is_empty())
+        .map(|pid| pid.parse().map_err(|_| format!("Invalid PID: {}", pid)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running,
+    /// The process exited; the exit code is only known while it is still a zombie.
+    Exited(Option<i32>),
+}
+
+/// Reads a process's state from `/proc/<pid>/stat`. The `exit_code` field (52) holds the wait
+/// status of a zombie process, which is as close as a non-parent can get to its exit code.
+pub fn process_state(pid: u32) -> ProcessState {
+    let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return ProcessState::Exited(None);
+    };
+
+    // The command name may contain spaces and parentheses, so fields are counted from the last ')'.
+    let fields: Vec<&str> = stat.rsplit_once(')').map(|(_, rest)| rest.split_whitespace().collect()).unwrap_or_default();
+
+    match fields.first() {
+        Some(&"Z") | Some(&"X") => {
+            let status: Option<i32> = fields.get(49).and_then(|status| status.parse().ok());
+            ProcessState::Exited(status.map(|status| (status >> 8) & 0xff))
+        }
+        _ => ProcessState::Running,
+    }
+}
+
+/// The `starttime` field (22) of `/proc/<pid>/stat`: when the process started, in clock ticks
+/// since boot. A PID reused by a new process comes back with a different start time.
+pub fn parse_start_time(stat: &str) -> Option<u64> {
+    // Fields are counted from the last ')', which ends field 2, the command name.
+    stat.rsplit_once(')')?.1.split_whitespace().nth(19)?.parse().ok()
+}
+
+pub fn start_time(pid: u32) -> Option<u64> {
+    parse_start_time(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+/// The command name of a process, from `/proc/<pid>/comm`.
+pub fn comm(pid: u32) -> Option<String> {
+    Some(fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?.trim_end().to_string())
+}
+
+/// Effective user ID of the current process, read from `/proc/self/status`.
+pub fn effective_uid() -> Option<u32> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let uids = status.lines().find_map(|line| line.strip_prefix("Uid:"))?;
+    uids.split_whitespace().nth(1)?.parse().ok()
+}
@@ -0,0 +1,258 @@
+//! `--format prometheus`: one page in the Prometheus text exposition format (version 0.0.4)
+//! with every GPU of a single tick, for cron jobs and node_exporter's textfile collector.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::efficiency::Efficiency;
+use crate::metadata::label_name;
+use crate::output::OutputContext;
+use crate::GpuSnapshot;
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    value: fn(&GpuSnapshot) -> Option<f64>,
+}
+
+const FAMILIES: &[Family] = &[
+    Family {
+        name: "gpuatop_utilization_percent",
+        help: "GPU utilization.",
+        kind: "gauge",
+        value: |snapshot| Some(snapshot.utilization),
+    },
+    Family {
+        name: "gpuatop_utilization_max_percent",
+        help: "Peak GPU utilization within the display interval.",
+        kind: "gauge",
+        value: |snapshot| snapshot.utilization_max,
+    },
+    Family {
+        name: "gpuatop_memory_used_bytes",
+        help: "Video memory in use.",
+        kind: "gauge",
+        value: |snapshot| snapshot.memory_used_mib.map(|used| used as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_memory_total_bytes",
+        help: "Total video memory.",
+        kind: "gauge",
+        value: |snapshot| snapshot.memory_total_mib.map(|total| total as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_temperature_celsius",
+        help: "GPU temperature.",
+        kind: "gauge",
+        value: |snapshot| snapshot.temperature_c.map(f64::from),
+    },
+    Family {
+        name: "gpuatop_power_watts",
+        help: "GPU power draw.",
+        kind: "gauge",
+        value: |snapshot| snapshot.power_w.map(f64::from),
+    },
+    Family {
+        name: "gpuatop_nvlink_transmit_bytes_per_second",
+        help: "NVLink transmit throughput over all links.",
+        kind: "gauge",
+        value: |snapshot| snapshot.nvlink.as_ref().map(|nvlink| nvlink.tx_kib_per_s * 1024.0),
+    },
+    Family {
+        name: "gpuatop_nvlink_receive_bytes_per_second",
+        help: "NVLink receive throughput over all links.",
+        kind: "gauge",
+        value: |snapshot| snapshot.nvlink.as_ref().map(|nvlink| nvlink.rx_kib_per_s * 1024.0),
+    },
+    Family {
+        name: "gpuatop_nvlink_replay_errors_total",
+        help: "NVLink replay errors over all links.",
+        kind: "counter",
+        value: |snapshot| snapshot.nvlink.as_ref().map(|nvlink| nvlink.replay_errors as f64),
+    },
+    Family {
+        name: "gpuatop_nvlink_crc_errors_total",
+        help: "NVLink CRC errors over all links.",
+        kind: "counter",
+        value: |snapshot| snapshot.nvlink.as_ref().map(|nvlink| nvlink.crc_errors as f64),
+    },
+    Family {
+        name: "gpuatop_desktop_utilization_percent",
+        help: "GPU utilization of the desktop compositor.",
+        kind: "gauge",
+        value: |snapshot| snapshot.usage_split.as_ref().map(|split| split.desktop),
+    },
+    Family {
+        name: "gpuatop_apps_utilization_percent",
+        help: "GPU utilization of applications.",
+        kind: "gauge",
+        value: |snapshot| snapshot.usage_split.as_ref().map(|split| split.apps),
+    },
+    Family {
+        name: "gpuatop_memory_bandwidth_utilization_percent",
+        help: "Memory bandwidth utilization.",
+        kind: "gauge",
+        value: |snapshot| snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct),
+    },
+    Family {
+        name: "gpuatop_memory_read_gbps",
+        help: "Memory read bandwidth in GB/s.",
+        kind: "gauge",
+        value: |snapshot| snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.read_gbps).map(f64::from),
+    },
+    Family {
+        name: "gpuatop_memory_write_gbps",
+        help: "Memory write bandwidth in GB/s.",
+        kind: "gauge",
+        value: |snapshot| snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.write_gbps).map(f64::from),
+    },
+    Family {
+        name: "gpuatop_memory_reserved_bytes",
+        help: "Video memory reserved by the driver.",
+        kind: "gauge",
+        value: |snapshot| snapshot.aperture.and_then(|aperture| aperture.reserved_mib).map(|reserved| reserved as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_bar1_used_bytes",
+        help: "BAR1 aperture in use.",
+        kind: "gauge",
+        value: |snapshot| snapshot.aperture.and_then(|aperture| aperture.bar1_used_mib).map(|used| used as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_bar1_total_bytes",
+        help: "Size of the BAR1 aperture.",
+        kind: "gauge",
+        value: |snapshot| snapshot.aperture.and_then(|aperture| aperture.bar1_total_mib).map(|total| total as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_visible_vram_used_bytes",
+        help: "CPU-visible video memory in use.",
+        kind: "gauge",
+        value: |snapshot| snapshot.aperture.and_then(|aperture| aperture.vis_vram_used_mib).map(|used| used as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_visible_vram_total_bytes",
+        help: "Total CPU-visible video memory.",
+        kind: "gauge",
+        value: |snapshot| snapshot.aperture.and_then(|aperture| aperture.vis_vram_total_mib).map(|total| total as f64 * MIB),
+    },
+    Family {
+        name: "gpuatop_idle_seconds",
+        help: "Time since utilization was last above the idle threshold, 0 while above.",
+        kind: "gauge",
+        value: |snapshot| snapshot.activity.map(|activity| activity.idle_seconds() as f64),
+    },
+    Family {
+        name: "gpuatop_efficiency_tflops_per_watt",
+        help: "Peak Tensor Core TFLOPS scaled by utilization, per watt drawn.",
+        kind: "gauge",
+        value: |snapshot| snapshot.efficiency.and_then(Efficiency::value),
+    },
+];
+
+/// Escapes a label value: backslash, double quote and line feed.
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    match value {
+        value if value.is_nan() => "NaN".to_string(),
+        value if value == f64::INFINITY => "+Inf".to_string(),
+        value if value == f64::NEG_INFINITY => "-Inf".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn labels(snapshot: &GpuSnapshot, context: &OutputContext) -> String {
+    let mut labels = vec![
+        format!("gpu=\"{}\"", snapshot.gpu.index),
+        format!("name=\"{}\"", escape_label_value(&snapshot.gpu.name)),
+    ];
+    labels.extend(host_labels(context));
+
+    labels.join(",")
+}
+
+/// The labels of the machine rather than of a GPU: its hostname and the `--label`s.
+fn host_labels(context: &OutputContext) -> Vec<String> {
+    let mut labels = Vec::new();
+    if let Some(hostname) = &context.hostname {
+        labels.push(format!("hostname=\"{}\"", escape_label_value(hostname)));
+    }
+    for (key, value) in context.labels.sorted() {
+        labels.push(format!("{}=\"{}\"", label_name(key), escape_label_value(value)));
+    }
+    labels
+}
+
+/// Formats the snapshots as one page: each metric family's `# HELP` and `# TYPE` lines followed
+/// by a sample per GPU that reports it, ending with `# EOF`. Families no GPU reports are left
+/// out.
+pub fn format_page(snapshots: &[GpuSnapshot], context: &OutputContext) -> String {
+    page(snapshots.iter().map(|snapshot| (snapshot, labels(snapshot, context))).collect(), "")
+}
+
+/// Like [`format_page`], ending with gpuatop's own CPU time for `--self-stats`: the
+/// `gpuatop_self_cpu_seconds` counter, which carries the hostname and `--label`s but no GPU.
+pub fn format_page_with_self_cpu(snapshots: &[GpuSnapshot], context: &OutputContext, cpu_seconds: f64) -> String {
+    let family = format!(
+        "# HELP gpuatop_self_cpu_seconds CPU time used by gpuatop and the tools it ran since monitoring started.\n# TYPE gpuatop_self_cpu_seconds counter\ngpuatop_self_cpu_seconds{{{}}} {}\n",
+        host_labels(context).join(","),
+        format_value(cpu_seconds)
+    );
+    page(snapshots.iter().map(|snapshot| (snapshot, labels(snapshot, context))).collect(), &family)
+}
+
+/// Like [`format_page`], for the GPUs of several machines: each host's samples carry its name
+/// as the `hostname` label.
+pub fn format_hosts_page(hosts: &[(String, Vec<GpuSnapshot>)], context: &OutputContext) -> String {
+    page(
+        hosts
+            .iter()
+            .flat_map(|(hostname, snapshots)| {
+                let context = OutputContext { hostname: Some(hostname.clone()), ..context.clone() };
+                snapshots.iter().map(move |snapshot| (snapshot, labels(snapshot, &context)))
+            })
+            .collect(),
+        "",
+    )
+}
+
+/// The page of `samples`, with the families `trailer` last.
+fn page(samples: Vec<(&GpuSnapshot, String)>, trailer: &str) -> String {
+    let mut page = String::new();
+
+    for family in FAMILIES {
+        let samples: Vec<String> = samples
+            .iter()
+            .filter_map(|(snapshot, labels)| {
+                (family.value)(snapshot).map(|value| format!("{}{{{}}} {}\n", family.name, labels, format_value(value)))
+            })
+            .collect();
+        if samples.is_empty() {
+            continue;
+        }
+
+        page.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", family.name, family.help, family.name, family.kind));
+        page.extend(samples);
+    }
+
+    page.push_str(trailer);
+    page.push_str("# EOF\n");
+    page
+}
+
+/// Replaces the file at `path` with `page` through a temporary file in the same directory, so
+/// a textfile collector never reads a half-written page.
+pub fn write_page(path: &Path, page: &str) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    fs::write(&temporary, page)?;
+    fs::rename(&temporary, path)
+}
@@ -0,0 +1,111 @@
+//! Rates from monotonically increasing counters: DRM engine busy nanoseconds, NVLink bytes. Each
+//! tick the counters are read, and the increase since the previous tick divided by the time in
+//! between is the rate. Three cases yield no rate for a tick rather than a wrong one: the first
+//! reading of a counter, a counter that went down (the driver reset it, or it wrapped around),
+//! and a tick across a suspend.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How much more wall-clock than monotonic time may pass between two ticks before the system
+/// is taken to have been suspended in between. The monotonic clock stops during a suspend and
+/// the wall clock does not; NTP adjustments stay well below this.
+pub const SUSPEND_TOLERANCE: Duration = Duration::from_secs(1);
+
+/// When a tick was taken, on both clocks: the monotonic one to measure rates, the wall clock
+/// to notice a suspend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Moment {
+    pub monotonic: Instant,
+    pub wall: SystemTime,
+}
+
+impl Moment {
+    pub fn now() -> Self {
+        Moment { monotonic: Instant::now(), wall: SystemTime::now() }
+    }
+
+    /// `elapsed` later on both clocks, as if the system ran all along.
+    pub fn after(self, elapsed: Duration) -> Self {
+        Moment { monotonic: self.monotonic + elapsed, wall: self.wall + elapsed }
+    }
+
+    /// The monotonic time since `earlier`; `None` when none passed, when the wall clock went
+    /// back, or when it went on by more than [`SUSPEND_TOLERANCE`] during a suspend.
+    pub fn since(self, earlier: Moment) -> Option<Duration> {
+        let monotonic = self.monotonic.checked_duration_since(earlier.monotonic).filter(|elapsed| !elapsed.is_zero())?;
+        let wall = self.wall.duration_since(earlier.wall).ok()?;
+        (wall <= monotonic + SUSPEND_TOLERANCE).then_some(monotonic)
+    }
+}
+
+/// The increase of a counter over the time between two ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub increase: u64,
+    pub elapsed: Duration,
+}
+
+impl Rate {
+    pub fn per_second(self) -> f64 {
+        self.increase as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// A nanosecond counter's share of the elapsed time, in percent: how busy an engine was.
+    pub fn percent_busy(self) -> f64 {
+        self.increase as f64 / self.elapsed.as_nanos() as f64 * 100.0
+    }
+}
+
+/// The counters read in the last two ticks, keyed by what they count, typically the GPU and the
+/// metric. A counter not read in a tick is forgotten, so one that comes back starts over.
+#[derive(Debug)]
+pub struct RateTracker<K> {
+    previous: HashMap<K, u64>,
+    current: HashMap<K, u64>,
+    last_tick: Option<Moment>,
+    /// The time since the previous tick, `None` on the first one and across a suspend.
+    elapsed: Option<Duration>,
+}
+
+impl<K> Default for RateTracker<K> {
+    fn default() -> Self {
+        RateTracker { previous: HashMap::new(), current: HashMap::new(), last_tick: None, elapsed: None }
+    }
+}
+
+impl<K: Eq + Hash> RateTracker<K> {
+    /// Starts a tick taken `at`, returning the time since the previous one: `None` for the
+    /// first tick and one across a suspend, which yield no rates.
+    pub fn tick(&mut self, at: Moment) -> Option<Duration> {
+        self.previous = std::mem::take(&mut self.current);
+        self.elapsed = self.last_tick.and_then(|last_tick| at.since(last_tick));
+        self.last_tick = Some(at);
+        self.elapsed
+    }
+
+    /// Records the `value` of the counter `key` in this tick, returning its increase since the
+    /// previous tick; `None` when it was not read then, or is lower now.
+    pub fn update(&mut self, key: K, value: u64) -> Option<Rate> {
+        let before = self.previous.get(&key).copied();
+        self.current.insert(key, value);
+        self.rate(before?, value)
+    }
+
+    /// Like [`RateTracker::update`], for counters that start at 0 when they appear, such as a
+    /// DRM client's busy time: one not read in the previous tick started since, and all of its
+    /// value is the increase.
+    pub fn update_from_zero(&mut self, key: K, value: u64) -> Option<Rate> {
+        let before = self.previous.get(&key).copied();
+        self.current.insert(key, value);
+        self.rate(before.unwrap_or(0), value)
+    }
+
+    fn rate(&self, before: u64, value: u64) -> Option<Rate> {
+        let elapsed = self.elapsed?;
+        // A counter that went down was reset or wrapped around; its new value is a baseline.
+        let increase = value.checked_sub(before)?;
+        Some(Rate { increase, elapsed })
+    }
+}
@@ -0,0 +1,501 @@
+/// A small regular expression engine, enough for user-supplied parse patterns
+/// of custom backends: literals, `.`, classes (`[0-9.]`, `\d`, `\w`, `\s` and negations),
+/// groups (capturing, `(?:...)`, named `(?P<name>...)` / `(?<name>...)`), alternation,
+/// greedy and lazy quantifiers (`*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`), and `^`/`$`, which
+/// match at line boundaries.
+///
+/// Patterns compile to a program for a Pike VM, which runs every candidate match in lockstep
+/// over the text instead of backtracking: matching takes time linear in the length of the text
+/// and constant stack, whatever the pattern.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    group_names: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    LineStart,
+    LineEnd,
+    Group { index: Option<usize>, alternatives: Vec<Vec<Node>> },
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+/// Byte offsets of a match and of its capture groups (group 0 is the whole match).
+#[derive(Debug, Clone)]
+pub struct Captures<'t> {
+    text: &'t str,
+    groups: Vec<Option<(usize, usize)>>,
+    names: &'t [Option<String>],
+}
+
+impl<'t> Captures<'t> {
+    pub fn name(&self, name: &str) -> Option<&'t str> {
+        let index = self.names.iter().position(|group| group.as_deref() == Some(name))?;
+        let (start, end) = self.groups.get(index + 1).copied().flatten()?;
+        Some(&self.text[start..end])
+    }
+
+    pub fn end(&self) -> usize {
+        self.groups[0].map(|(_, end)| end).unwrap_or(0)
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    group_names: &'a mut Vec<Option<String>>,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+
+        Ok(nodes)
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect::<String>().parse().ok()
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.position += 1;
+                let min = self.parse_number().ok_or_else(|| self.error("Expected repetition count"))?;
+                let max = if self.eat(',') { self.parse_number() } else { Some(min) };
+                if self.peek() != Some('}') {
+                    return Err(self.error("Expected '}'"));
+                }
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        self.position += 1;
+
+        if matches!(atom, Node::LineStart | Node::LineEnd) {
+            return Err(self.error("Nothing to repeat"));
+        }
+
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or_else(|| self.error("Trailing backslash"))?;
+
+        Ok(match c {
+            'd' | 'D' => Node::Class { ranges: vec![('0', '9')], negated: c == 'D' },
+            'w' | 'W' => Node::Class { ranges: vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')], negated: c == 'W' },
+            's' | 'S' => Node::Class { ranges: vec![(' ', ' '), ('\t', '\r')], negated: c == 'S' },
+            'n' => Node::Char('\n'),
+            't' => Node::Char('\t'),
+            c => Node::Char(c),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = self.next().ok_or_else(|| self.error("Unterminated character class"))?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let start = if c == '\\' {
+                match self.parse_escape()? {
+                    Node::Char(c) => c,
+                    Node::Class { ranges: escaped, negated: false } => {
+                        ranges.extend(escaped);
+                        continue;
+                    }
+                    _ => return Err(self.error("Negated escapes are not supported in classes")),
+                }
+            } else {
+                c
+            };
+
+            if self.peek() == Some('-') && self.chars.get(self.position + 1).is_some_and(|c| *c != ']') {
+                self.position += 1;
+                let end = self.next().ok_or_else(|| self.error("Unterminated range"))?;
+                ranges.push((start, end));
+            } else {
+                ranges.push((start, start));
+            }
+        }
+
+        Ok(Node::Class { ranges, negated })
+    }
+
+    fn parse_group(&mut self) -> Result<Node, String> {
+        let index = if self.eat('?') {
+            if self.eat(':') {
+                None
+            } else {
+                self.eat('P');
+                if !self.eat('<') {
+                    return Err(self.error("Unsupported group syntax"));
+                }
+                let start = self.position;
+                while self.peek().is_some_and(|c| c != '>') {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                if !self.eat('>') || name.is_empty() {
+                    return Err(self.error("Invalid group name"));
+                }
+                self.group_names.push(Some(name));
+                Some(self.group_names.len())
+            }
+        } else {
+            self.group_names.push(None);
+            Some(self.group_names.len())
+        };
+
+        let alternatives = self.parse_alternatives()?;
+        if !self.eat(')') {
+            return Err(self.error("Unclosed group"));
+        }
+
+        Ok(Node::Group { index, alternatives })
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let c = self.next().ok_or_else(|| self.error("Unexpected end of pattern"))?;
+
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::LineStart),
+            '$' => Ok(Node::LineEnd),
+            '\\' => self.parse_escape(),
+            '[' => self.parse_class(),
+            '(' => self.parse_group(),
+            '*' | '+' | '?' | '{' => Err(self.error("Nothing to repeat")),
+            c => Ok(Node::Char(c)),
+        }
+    }
+}
+
+/// Instructions of the compiled pattern, run by the Pike VM in [`Regex::captures_at`].
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class { ranges: Vec<(char, char)>, negated: bool },
+    LineStart,
+    LineEnd,
+    /// Continues at both targets, preferring the first.
+    Split(usize, usize),
+    Jump(usize),
+    /// Records the current offset in a capture slot: `2 * group` for its start, `+ 1` for its end.
+    Save(usize),
+    Match,
+}
+
+/// Patterns compiling to more instructions are rejected, which bounds both memory and the work
+/// per input character; counted repetitions such as `(...){1000}` are unrolled.
+const MAX_PROGRAM: usize = 10_000;
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err("Pattern is too large".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        match &mut self.program[at] {
+            Inst::Split(_, second) => *second = target,
+            Inst::Jump(to) => *to = target,
+            _ => unreachable!("only splits and jumps are patched"),
+        }
+    }
+
+    /// A split whose preferred branch is the next instruction and whose other branch is patched
+    /// later; lazy repetitions prefer the patched branch instead.
+    fn split(&mut self, greedy: bool) -> Result<usize, String> {
+        let next = self.program.len() + 1;
+        self.push(if greedy { Inst::Split(next, usize::MAX) } else { Inst::Split(usize::MAX, next) })
+    }
+
+    fn patch_split(&mut self, at: usize, target: usize, greedy: bool) {
+        if greedy {
+            self.patch(at, target);
+        } else if let Inst::Split(first, _) = &mut self.program[at] {
+            *first = target;
+        }
+    }
+
+    fn alternatives(&mut self, alternatives: &[Vec<Node>]) -> Result<(), String> {
+        let mut jumps = Vec::new();
+        for (position, alternative) in alternatives.iter().enumerate() {
+            let split = if position + 1 < alternatives.len() { Some(self.split(true)?) } else { None };
+            for node in alternative {
+                self.node(node)?;
+            }
+            if let Some(split) = split {
+                jumps.push(self.push(Inst::Jump(usize::MAX))?);
+                let next = self.program.len();
+                self.patch(split, next);
+            }
+        }
+        let end = self.program.len();
+        for jump in jumps {
+            self.patch(jump, end);
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(c) => self.push(Inst::Char(*c)).map(drop),
+            Node::Any => self.push(Inst::Any).map(drop),
+            Node::Class { ranges, negated } => self.push(Inst::Class { ranges: ranges.clone(), negated: *negated }).map(drop),
+            Node::LineStart => self.push(Inst::LineStart).map(drop),
+            Node::LineEnd => self.push(Inst::LineEnd).map(drop),
+            Node::Group { index, alternatives } => {
+                if let Some(index) = index {
+                    self.push(Inst::Save(2 * index))?;
+                }
+                self.alternatives(alternatives)?;
+                if let Some(index) = index {
+                    self.push(Inst::Save(2 * index + 1))?;
+                }
+                Ok(())
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.node(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.split(*greedy)?;
+                        self.node(node)?;
+                        self.push(Inst::Jump(split))?;
+                        let end = self.program.len();
+                        self.patch_split(split, end, *greedy);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.split(*greedy)?);
+                            self.node(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.patch_split(split, end, *greedy);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The threads alive at one input offset, in priority order, each program counter once.
+struct Threads {
+    dense: Vec<(usize, Vec<Option<usize>>)>,
+    /// For each program counter, whether a thread reached it at this offset already.
+    seen: Vec<bool>,
+    /// The program counters set in `seen`, to clear them.
+    visited: Vec<usize>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Self {
+        Threads { dense: Vec::new(), seen: vec![false; size], visited: Vec::new() }
+    }
+
+    fn clear(&mut self) {
+        for pc in self.visited.drain(..) {
+            self.seen[pc] = false;
+        }
+        self.dense.clear();
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut group_names = Vec::new();
+        let mut parser = Parser { chars: pattern.chars().collect(), position: 0, group_names: &mut group_names };
+        let alternatives = parser.parse_alternatives()?;
+
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("Unmatched ')'"));
+        }
+
+        let mut compiler = Compiler { program: Vec::new() };
+        compiler.node(&Node::Group { index: Some(0), alternatives })?;
+        compiler.push(Inst::Match)?;
+
+        Ok(Regex { program: compiler.program, group_names })
+    }
+
+    pub fn group_names(&self) -> impl Iterator<Item = &str> {
+        self.group_names.iter().filter_map(|name| name.as_deref())
+    }
+
+    /// Follows the jumps, splits, saves and assertions from `pc` at byte offset `at`, adding the
+    /// threads that wait for a character (or match) to `threads` in priority order. Iterative,
+    /// so the stack stays flat whatever the pattern.
+    fn add_thread(&self, threads: &mut Threads, pc: usize, slots: Vec<Option<usize>>, text: &str, at: usize) {
+        let mut stack = vec![(pc, slots)];
+
+        while let Some((pc, mut slots)) = stack.pop() {
+            if threads.seen[pc] {
+                continue;
+            }
+            threads.seen[pc] = true;
+            threads.visited.push(pc);
+
+            match &self.program[pc] {
+                Inst::Jump(to) => stack.push((*to, slots)),
+                Inst::Split(first, second) => {
+                    stack.push((*second, slots.clone()));
+                    stack.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(at);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::LineStart => {
+                    if at == 0 || text.as_bytes()[at - 1] == b'\n' {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::LineEnd => {
+                    if at == text.len() || text.as_bytes()[at] == b'\n' {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                Inst::Char(_) | Inst::Any | Inst::Class { .. } | Inst::Match => threads.dense.push((pc, slots)),
+            }
+        }
+    }
+
+    /// Finds the first match starting at or after byte offset `start`, preferring what a
+    /// backtracking engine would: the leftmost start, then the alternatives and repetitions in
+    /// pattern order. Runs in time linear in the length of the text.
+    pub fn captures_at<'t>(&'t self, text: &'t str, start: usize) -> Option<Captures<'t>> {
+        let mut at = (start..=text.len()).find(|at| text.is_char_boundary(*at))?;
+        let slot_count = 2 * (self.group_names.len() + 1);
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched: Option<Vec<Option<usize>>> = None;
+
+        loop {
+            // A new attempt at every offset until one matches, behind the older threads.
+            if matched.is_none() {
+                self.add_thread(&mut current, 0, vec![None; slot_count], text, at);
+            }
+            if current.dense.is_empty() && matched.is_some() {
+                break;
+            }
+
+            let c = text[at..].chars().next();
+            let after = at + c.map_or(0, char::len_utf8);
+            let threads = std::mem::take(&mut current.dense);
+            current.clear();
+            for (pc, slots) in threads {
+                let step = match &self.program[pc] {
+                    Inst::Char(expected) => c == Some(*expected),
+                    Inst::Any => c.is_some_and(|c| c != '\n'),
+                    Inst::Class { ranges, negated } => c.is_some_and(|c| ranges.iter().any(|(start, end)| (*start..=*end).contains(&c)) != *negated),
+                    Inst::Match => {
+                        // Threads behind this one have lower priority.
+                        matched = Some(slots);
+                        break;
+                    }
+                    _ => unreachable!("add_thread only keeps consuming instructions"),
+                };
+                if step {
+                    self.add_thread(&mut next, pc + 1, slots, text, after);
+                }
+            }
+            if c.is_none() {
+                break;
+            }
+            std::mem::swap(&mut current, &mut next);
+            at = after;
+        }
+
+        let slots = matched?;
+        let groups = slots.chunks(2).map(|pair| Some((pair[0]?, pair[1]?))).collect();
+        Some(Captures { text, groups, names: &self.group_names })
+    }
+
+    /// Iterates over successive non-overlapping matches.
+    pub fn captures_iter<'t>(&'t self, text: &'t str) -> impl Iterator<Item = Captures<'t>> + 't {
+        let mut start = 0;
+
+        std::iter::from_fn(move || {
+            if start > text.len() {
+                return None;
+            }
+            let captures = self.captures_at(text, start)?;
+            let end = captures.end();
+            start = if end > start { end } else { start + text[start..].chars().next().map_or(1, char::len_utf8) };
+            Some(captures)
+        })
+    }
+}
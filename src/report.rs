@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::output::json_string;
+use crate::stats::Statistics;
+use crate::GpuSnapshot;
+
+const TEMPLATE: &str = include_str!("templates/report.html");
+
+fn html_escape(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn unix_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0)
+}
+
+/// Utilization history collected during a run for `--export-html`.
+#[derive(Debug, Default)]
+pub struct HtmlReport {
+    /// Per GPU index: name and `(unix milliseconds, utilization)` samples.
+    gpus: BTreeMap<u32, (String, Vec<(u128, f64)>)>,
+}
+
+impl HtmlReport {
+    pub fn record(&mut self, snapshot: &GpuSnapshot) {
+        self.gpus
+            .entry(snapshot.gpu.index)
+            .or_insert_with(|| (snapshot.gpu.name.clone(), Vec::new()))
+            .1
+            .push((unix_millis(), snapshot.utilization));
+    }
+
+    fn data_json(&self) -> String {
+        let gpus: Vec<String> = self
+            .gpus
+            .iter()
+            .map(|(index, (name, samples))| {
+                let samples: Vec<String> = samples.iter().map(|(timestamp, utilization)| format!("[{},{}]", timestamp, utilization)).collect();
+                format!("{{\"index\":{},\"name\":{},\"samples\":[{}]}}", index, json_string(name), samples.join(","))
+            })
+            .collect();
+
+        // "</" would end the enclosing <script> element early.
+        format!("{{\"gpus\":[{}]}}", gpus.join(",")).replace("</", "<\\/")
+    }
+
+    pub fn render(&self, statistics: &Statistics) -> String {
+        let summary: Vec<String> = statistics
+            .gpus()
+            .map(|stats| {
+                format!(
+                    "  <tr><td>GPU {} ({})</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    stats.gpu.index,
+                    html_escape(&stats.gpu.name),
+                    stats.samples,
+                    stats.utilization_min,
+                    stats.utilization_avg(),
+                    stats.utilization_max,
+                    stats.memory_peak_mib.map(|memory| memory.to_string()).unwrap_or_default(),
+                    stats.temperature_max_c.map(|temperature| temperature.to_string()).unwrap_or_default()
+                )
+            })
+            .collect();
+
+        TEMPLATE
+            .replace("{{GENERATED}}", &format!("at unix time {}", unix_millis() / 1000))
+            .replace("{{SUMMARY}}", &summary.join("\n"))
+            .replace("{{DATA}}", &self.data_json())
+    }
+
+    pub fn write(&self, path: &str, statistics: &Statistics) -> io::Result<()> {
+        fs::write(path, self.render(statistics))
+    }
+}
@@ -0,0 +1,143 @@
+//! `--require-gpu <vendor>[:count=N][,vram=MiB]`: fails fast on a node without the GPUs a job
+//! was scheduled for, before any vendor tool is installed or run.
+
+use std::str::FromStr;
+
+use crate::pci;
+use crate::runner::CommandRunner;
+use crate::{poll_gpus, try_identify_gpu_card, GpuInfo, GpuSnapshot, GpuType, PollResult};
+
+/// The GPUs a job needs: at least `min_count` of `vendor`, each with `min_vram_mib` of VRAM
+/// where that is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuRequirement {
+    pub vendor: GpuType,
+    pub min_count: usize,
+    pub min_vram_mib: Option<u64>,
+}
+
+impl FromStr for GpuRequirement {
+    type Err = String;
+
+    /// `nvidia`, `amd:count=2`, `nvidia:count=4,vram=40960`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (vendor, options) = s.split_once(':').unwrap_or((s, ""));
+        let vendor = match vendor {
+            "nvidia" => GpuType::Nvidia,
+            "amd" => GpuType::Amd,
+            "intel" => GpuType::Intel,
+            "jetson" => GpuType::JetsonGpu,
+            "broadcom" => GpuType::Broadcom(String::new()),
+            _ => return Err(format!("Invalid --require-gpu vendor: {} (expected nvidia, amd, intel, jetson or broadcom)", vendor)),
+        };
+        let mut requirement = GpuRequirement { vendor, min_count: 1, min_vram_mib: None };
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let invalid = || format!("Invalid --require-gpu option: {} (expected count=N or vram=MiB)", option);
+            match option.split_once('=').ok_or_else(invalid)? {
+                ("count", count) => requirement.min_count = count.parse().ok().filter(|count| *count > 0).ok_or_else(invalid)?,
+                ("vram", mib) => requirement.min_vram_mib = Some(mib.parse().map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(requirement)
+    }
+}
+
+/// The vendor as `--require-gpu` and its error messages name it.
+fn vendor_key(vendor: &GpuType) -> &str {
+    match vendor {
+        GpuType::Nvidia => "nvidia",
+        GpuType::Amd | GpuType::AmdApu => "amd",
+        GpuType::Intel | GpuType::IntelArc(_) => "intel",
+        GpuType::JetsonGpu => "jetson",
+        GpuType::Broadcom(_) => "broadcom",
+        GpuType::Unknown(description) => description,
+    }
+}
+
+fn vendor_name(vendor: &GpuType) -> &str {
+    match vendor {
+        GpuType::Nvidia => "NVIDIA",
+        GpuType::Amd => "AMD",
+        GpuType::AmdApu => "AMD APU",
+        GpuType::Intel => "Intel",
+        GpuType::IntelArc(_) => "Intel Arc",
+        GpuType::JetsonGpu => "Jetson",
+        GpuType::Broadcom(_) => "Raspberry Pi",
+        GpuType::Unknown(description) => description,
+    }
+}
+
+/// The vendor of every GPU `lspci` lists. Where it lists none, as on a Jetson or a Raspberry Pi, whose GPU is
+/// not a PCI device, the vendor [`try_identify_gpu_card`] finds stands for one GPU.
+pub fn detect_vendors(runner: &dyn CommandRunner) -> Vec<GpuType> {
+    let output = runner.run("lspci", &["-Dnn"]).map(|output| output.stdout).unwrap_or_default();
+    let vendors: Vec<GpuType> = pci::lspci_gpus(&output)
+        .iter()
+        .map(|gpu| match gpu.vendor_id {
+            Some(vendor_id) => GpuType::from_pci_vendor(vendor_id).unwrap_or_else(|| GpuType::Unknown(pci::vendor_name(vendor_id).to_string())),
+            None => GpuType::Unknown(gpu.description.to_string()),
+        })
+        .collect();
+
+    if vendors.is_empty() {
+        return try_identify_gpu_card(runner).into_iter().collect();
+    }
+    vendors
+}
+
+/// `Intel`, `NVIDIA x4, Intel`, or `none`.
+pub fn format_detected(vendors: &[GpuType]) -> String {
+    let mut counts: Vec<(&GpuType, usize)> = Vec::new();
+    for vendor in vendors {
+        match counts.iter_mut().find(|(counted, _)| *counted == vendor) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((vendor, 1)),
+        }
+    }
+    if counts.is_empty() {
+        return "none".to_string();
+    }
+
+    let names: Vec<String> = counts
+        .iter()
+        .map(|(vendor, count)| if *count > 1 { format!("{} x{}", vendor_name(vendor), count) } else { vendor_name(vendor).to_string() })
+        .collect();
+    names.join(", ")
+}
+
+/// One sample of each GPU from the vendor tool, for their memory totals; failed ones are left out.
+pub fn sample_gpus(runner: &dyn CommandRunner, gpu_type: &GpuType, gpus: &[GpuInfo]) -> Vec<GpuSnapshot> {
+    poll_gpus(runner, gpu_type, gpus)
+        .into_iter()
+        .filter_map(|result| if let PollResult::Ok { snapshot, .. } = result { Some(snapshot) } else { None })
+        .collect()
+}
+
+impl GpuRequirement {
+    /// Checks the vendor and the count against the `detected` vendors, one per GPU.
+    pub fn check(&self, detected: &[GpuType]) -> Result<(), String> {
+        // An APU is an AMD GPU to `--require-gpu amd`.
+        let found = detected.iter().filter(|vendor| vendor_key(vendor) == vendor_key(&self.vendor)).count();
+        if found == 0 {
+            return Err(format!("Required GPU vendor '{}' not found. Detected: {}", vendor_key(&self.vendor), format_detected(detected)));
+        }
+        if found < self.min_count {
+            return Err(format!("Required {} GPUs of vendor '{}', found {}. Detected: {}", self.min_count, vendor_key(&self.vendor), found, format_detected(detected)));
+        }
+        Ok(())
+    }
+
+    /// Checks the VRAM of the GPUs against `vram=`: at least `min_count` of them must have it.
+    /// A GPU that reports no memory total counts as lacking it.
+    pub fn check_vram(&self, samples: &[GpuSnapshot]) -> Result<(), String> {
+        let Some(min_vram_mib) = self.min_vram_mib else { return Ok(()) };
+        let found = samples.iter().filter(|sample| sample.memory_total_mib.is_some_and(|total| total >= min_vram_mib)).count();
+        if found < self.min_count {
+            let gpus = if self.min_count == 1 { "1 GPU".to_string() } else { format!("{} GPUs", self.min_count) };
+            return Err(format!("Required {} of vendor '{}' with at least {} MiB of VRAM, found {}", gpus, vendor_key(&self.vendor), min_vram_mib, found));
+        }
+        Ok(())
+    }
+}
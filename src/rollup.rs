@@ -0,0 +1,317 @@
+//! `--rollup <window>`: one aggregate record per GPU and window of wall-clock time, besides the
+//! samples of every tick or, with `--rollup-only`, instead of them. Windows start on multiples
+//! of their length since the Unix epoch, so `--rollup 1m` windows start on the minute whenever
+//! gpuatop was started.
+//!
+//! Each window is folded as its samples arrive; none is kept. A window is complete once the first
+//! sample of a later one arrives, and the windows still open are written when monitoring ends.
+//! The first window of a GPU, unless its first sample falls on the boundary, and the windows
+//! written at the end are flagged `partial`: they do not cover the whole window.
+//!
+//! Energy is the integral of the power draw by the trapezoidal rule, as for `--max-energy`. The
+//! stretch between the last sample of a window and the first of the next is split at the boundary,
+//! with the power interpolated there, and a gap longer than the maximum adds nothing.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::output::{format_iso8601, json_string, OutputContext};
+use crate::sampling::parse_duration;
+use crate::schema::SCHEMA_VERSION;
+use crate::GpuSnapshot;
+
+/// Parses `--rollup`: a whole number of seconds, such as `1m` or `300`.
+pub fn parse_window(value: &str) -> Result<Duration, String> {
+    let window = parse_duration(value)?;
+    if window.is_zero() || window.subsec_nanos() != 0 {
+        return Err(format!("Invalid --rollup window: {} (expected a whole number of seconds, e.g. 1m)", value));
+    }
+    Ok(window)
+}
+
+/// The roll-up CSV written next to the `--dump-raw` file: `samples.csv` rolls up into
+/// `samples.rollup.csv`.
+pub fn csv_path(path: &str) -> String {
+    format!("{}.rollup.csv", path.strip_suffix(".csv").unwrap_or(path))
+}
+
+/// One GPU over one window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollup {
+    pub gpu: u32,
+    pub name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub partial: bool,
+    pub samples: u32,
+    pub utilization_mean: f64,
+    pub utilization_min: f64,
+    pub utilization_max: f64,
+    pub temperature_mean: Option<f64>,
+    pub temperature_max: Option<f32>,
+    pub memory_used_max_mib: Option<u64>,
+    /// `None` unless two samples in a row reported the power draw.
+    pub energy_wh: Option<f64>,
+}
+
+impl Rollup {
+    pub fn format_text(&self, precision: usize) -> String {
+        let mut line = format!(
+            "GPU {} ({}) {} to {}{}: Utilization (percent): mean {:.*}, min {:.*}, max {:.*}",
+            self.gpu,
+            self.name,
+            format_iso8601(self.start),
+            format_iso8601(self.end),
+            if self.partial { " (partial)" } else { "" },
+            precision,
+            self.utilization_mean,
+            precision,
+            self.utilization_min,
+            precision,
+            self.utilization_max
+        );
+
+        if let (Some(mean), Some(max)) = (self.temperature_mean, self.temperature_max) {
+            line.push_str(&format!(", Temperature: mean {:.1}°C, max {}°C", mean, max));
+        }
+        if let Some(used) = self.memory_used_max_mib {
+            line.push_str(&format!(", Memory: max {} MiB", used));
+        }
+        if let Some(energy) = self.energy_wh {
+            line.push_str(&format!(", Energy: {:.3} Wh", energy));
+        }
+        line
+    }
+
+    /// The `"type":"rollup"` record in JSON output.
+    pub fn to_json(&self, context: &OutputContext) -> String {
+        let mut fields = vec![format!("\"schema_version\":{}", SCHEMA_VERSION)];
+
+        if let Some(hostname) = &context.hostname {
+            fields.push(format!("\"hostname\":{}", json_string(hostname)));
+        }
+        fields.push("\"type\":\"rollup\"".to_string());
+        fields.push(format!("\"gpu\":{}", self.gpu));
+        fields.push(format!("\"name\":{}", json_string(&self.name)));
+        fields.push(format!("\"window_start\":{}", json_string(&format_iso8601(self.start))));
+        fields.push(format!("\"window_end\":{}", json_string(&format_iso8601(self.end))));
+        fields.push(format!("\"partial\":{}", self.partial));
+        fields.push(format!("\"samples\":{}", self.samples));
+        fields.push(format!("\"utilization_mean\":{}", self.utilization_mean));
+        fields.push(format!("\"utilization_min\":{}", self.utilization_min));
+        fields.push(format!("\"utilization_max\":{}", self.utilization_max));
+        if let Some(mean) = self.temperature_mean {
+            fields.push(format!("\"temperature_mean\":{}", mean));
+        }
+        if let Some(max) = self.temperature_max {
+            fields.push(format!("\"temperature_max\":{}", max));
+        }
+        if let Some(used) = self.memory_used_max_mib {
+            fields.push(format!("\"memory_used_max_mib\":{}", used));
+        }
+        if let Some(energy) = self.energy_wh {
+            fields.push(format!("\"energy_wh\":{}", energy));
+        }
+        if !context.labels.is_empty() {
+            fields.push(format!("\"labels\":{}", context.labels.to_json()));
+        }
+
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+/// The window a GPU's samples are being folded into.
+#[derive(Debug, Clone)]
+struct Window {
+    /// Seconds since the Unix epoch.
+    start: u64,
+    name: String,
+    partial: bool,
+    samples: u32,
+    utilization_sum: f64,
+    utilization_min: f64,
+    utilization_max: f64,
+    temperature_sum: f64,
+    temperatures: u32,
+    temperature_max: Option<f32>,
+    memory_used_max_mib: Option<u64>,
+    energy_wh: Option<f64>,
+}
+
+impl Window {
+    fn new(start: u64, name: &str, partial: bool) -> Self {
+        Window {
+            start,
+            name: name.to_string(),
+            partial,
+            samples: 0,
+            utilization_sum: 0.0,
+            utilization_min: f64::INFINITY,
+            utilization_max: f64::NEG_INFINITY,
+            temperature_sum: 0.0,
+            temperatures: 0,
+            temperature_max: None,
+            memory_used_max_mib: None,
+            energy_wh: None,
+        }
+    }
+
+    fn add(&mut self, snapshot: &GpuSnapshot) {
+        self.samples += 1;
+        self.utilization_sum += snapshot.utilization;
+        self.utilization_min = self.utilization_min.min(snapshot.utilization);
+        self.utilization_max = self.utilization_max.max(snapshot.utilization);
+        if let Some(temperature) = snapshot.temperature_c {
+            self.temperature_sum += f64::from(temperature);
+            self.temperatures += 1;
+            self.temperature_max = Some(self.temperature_max.map_or(temperature, |max| max.max(temperature)));
+        }
+        if let Some(used) = snapshot.memory_used_mib {
+            self.memory_used_max_mib = Some(self.memory_used_max_mib.map_or(used, |max| max.max(used)));
+        }
+    }
+
+    fn add_energy(&mut self, energy_wh: f64) {
+        *self.energy_wh.get_or_insert(0.0) += energy_wh;
+    }
+
+    fn rollup(self, gpu: u32, length: u64) -> Rollup {
+        Rollup {
+            gpu,
+            name: self.name,
+            start: UNIX_EPOCH + Duration::from_secs(self.start),
+            end: UNIX_EPOCH + Duration::from_secs(self.start + length),
+            partial: self.partial,
+            samples: self.samples,
+            utilization_mean: self.utilization_sum / f64::from(self.samples.max(1)),
+            utilization_min: self.utilization_min,
+            utilization_max: self.utilization_max,
+            temperature_mean: (self.temperatures > 0).then(|| self.temperature_sum / f64::from(self.temperatures)),
+            temperature_max: self.temperature_max,
+            memory_used_max_mib: self.memory_used_max_mib,
+            energy_wh: self.energy_wh,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LastSample {
+    at: SystemTime,
+    power_w: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RollupTracker {
+    /// The window length in seconds.
+    length: u64,
+    /// The longest gap between two samples of a GPU that adds to its energy.
+    max_gap: Duration,
+    windows: BTreeMap<u32, Window>,
+    last: BTreeMap<u32, LastSample>,
+}
+
+impl RollupTracker {
+    pub fn new(window: Duration, max_gap: Duration) -> Self {
+        RollupTracker { length: window.as_secs().max(1), max_gap, windows: BTreeMap::new(), last: BTreeMap::new() }
+    }
+
+    /// Folds a sample taken `at` into its GPU's window. Returns the previous window when the
+    /// sample is the first of a later one.
+    pub fn record(&mut self, snapshot: &GpuSnapshot, at: SystemTime) -> Option<Rollup> {
+        let index = snapshot.gpu.index;
+        let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let start = since_epoch.as_secs() / self.length * self.length;
+        let sample = LastSample { at, power_w: snapshot.power_w.map(f64::from) };
+
+        // The energy since the previous sample, up to the end of its window and from the start
+        // of this one.
+        let (mut earlier_wh, mut later_wh) = (None, None);
+        if let Some(last) = self.last.insert(index, sample) {
+            let gap = at.duration_since(last.at).unwrap_or_default();
+            if let (Some(before), Some(after), false) = (last.power_w, sample.power_w, gap.is_zero() || gap > self.max_gap) {
+                let gap_s = gap.as_secs_f64();
+                let power = |offset: f64| before + (after - before) * offset / gap_s;
+                let energy = |from: f64, to: f64| (power(from) + power(to)) / 2.0 * (to - from) / 3600.0;
+                let offset = |seconds: u64| (UNIX_EPOCH + Duration::from_secs(seconds)).duration_since(last.at).map_or(0.0, |offset| offset.as_secs_f64().min(gap_s));
+
+                match self.windows.get(&index) {
+                    Some(window) if window.start < start => {
+                        earlier_wh = Some(energy(0.0, offset(window.start + self.length)));
+                        later_wh = Some(energy(offset(start), gap_s));
+                    }
+                    _ => later_wh = Some(energy(0.0, gap_s)),
+                }
+            }
+        }
+
+        let mut closed = None;
+        if let Some(window) = self.windows.get_mut(&index).filter(|window| window.start < start) {
+            if let Some(energy) = earlier_wh {
+                window.add_energy(energy);
+            }
+            closed = self.windows.remove(&index).map(|window| window.rollup(index, self.length));
+        }
+        let first = closed.is_none() && !self.windows.contains_key(&index);
+        let window = self.windows.entry(index).or_insert_with(|| Window::new(start, &snapshot.gpu.name, first && since_epoch > Duration::from_secs(start)));
+        window.add(snapshot);
+        if let Some(energy) = later_wh {
+            window.add_energy(energy);
+        }
+        closed
+    }
+
+    /// The windows still open, flagged partial, when monitoring ends.
+    pub fn finish(&mut self) -> Vec<Rollup> {
+        let length = self.length;
+        self.last.clear();
+        std::mem::take(&mut self.windows)
+            .into_iter()
+            .map(|(gpu, mut window)| {
+                window.partial = true;
+                window.rollup(gpu, length)
+            })
+            .collect()
+    }
+}
+
+/// The roll-up CSV, written a row at a time as windows close.
+pub struct RollupCsv {
+    file: io::BufWriter<fs::File>,
+}
+
+impl RollupCsv {
+    pub const HEADER: &'static str = "window_start,window_end,gpu,partial,samples,utilization_mean,utilization_min,utilization_max,temperature_mean,temperature_max,memory_used_max_mib,energy_wh";
+
+    /// Creates the file with the `# schema_version` line and the header, like `--dump-raw`.
+    pub fn create(path: &str) -> io::Result<Self> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "# schema_version={}", SCHEMA_VERSION)?;
+        writeln!(file, "{}", Self::HEADER)?;
+        file.flush()?;
+        Ok(RollupCsv { file })
+    }
+
+    pub fn write(&mut self, rollup: &Rollup) -> io::Result<()> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        writeln!(
+            self.file,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            format_iso8601(rollup.start),
+            format_iso8601(rollup.end),
+            rollup.gpu,
+            rollup.partial,
+            rollup.samples,
+            rollup.utilization_mean,
+            rollup.utilization_min,
+            rollup.utilization_max,
+            optional(rollup.temperature_mean.map(|value| value.to_string())),
+            optional(rollup.temperature_max.map(|value| value.to_string())),
+            optional(rollup.memory_used_max_mib.map(|value| value.to_string())),
+            optional(rollup.energy_wh.map(|value| value.to_string()))
+        )?;
+        // Flushed per row so that a run that is killed keeps the windows it completed.
+        self.file.flush()
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Captured result of a finished command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    /// A successful run that printed `stdout`.
+    pub fn ok(stdout: &str) -> Self {
+        CommandOutput { success: true, code: Some(0), stdout: stdout.to_string(), stderr: String::new() }
+    }
+
+    /// A failed run with exit code `code` that printed `stderr`.
+    pub fn failed(code: i32, stderr: &str) -> Self {
+        CommandOutput { success: false, code: Some(code), stdout: String::new(), stderr: stderr.to_string() }
+    }
+}
+
+/// Runs external commands to completion. Everything that shells out to a vendor tool goes
+/// through this, so it can be exercised without GPUs by substituting [`MockRunner`].
+pub trait CommandRunner: Sync {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput>;
+
+    /// Like [`CommandRunner::run`], but hands every stdout and stderr line to `on_line` as it
+    /// is printed, for long-running commands such as package installs. A command still running
+    /// after `timeout` is killed and fails with [`io::ErrorKind::TimedOut`]. The default
+    /// forwards the lines once the command has finished and ignores the timeout.
+    fn run_streaming(&self, program: &str, args: &[&str], _timeout: Duration, on_line: &mut dyn FnMut(&str)) -> io::Result<CommandOutput> {
+        let output = self.run(program, args)?;
+        output.stdout.lines().chain(output.stderr.lines()).for_each(&mut *on_line);
+        Ok(output)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealRunner;
+
+/// Sends the lines of `stream` tagged with `is_stdout` until it closes.
+fn forward_lines(stream: impl Read + Send + 'static, is_stdout: bool, sender: Sender<(bool, String)>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if sender.send((is_stdout, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+impl CommandRunner for RealRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        let output = Command::new(program).args(args).output()?;
+
+        Ok(CommandOutput {
+            success: output.status.success(),
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    fn run_streaming(&self, program: &str, args: &[&str], timeout: Duration, on_line: &mut dyn FnMut(&str)) -> io::Result<CommandOutput> {
+        let mut child = Command::new(program).args(args).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let (sender, lines) = mpsc::channel();
+        forward_lines(child.stdout.take().expect("stdout is piped"), true, sender.clone());
+        forward_lines(child.stderr.take().expect("stderr is piped"), false, sender);
+
+        let deadline = Instant::now() + timeout;
+        let mut output = CommandOutput::default();
+        loop {
+            let (is_stdout, line) = match lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, format!("{} did not finish within {}s and was stopped", program, timeout.as_secs())));
+                }
+            };
+            on_line(&line);
+            let captured = if is_stdout { &mut output.stdout } else { &mut output.stderr };
+            captured.push_str(&line);
+            captured.push('\n');
+        }
+
+        let status = child.wait()?;
+        output.success = status.success();
+        output.code = status.code();
+        Ok(output)
+    }
+}
+
+/// Returns pre-configured outputs; commands without a response fail as if not installed.
+#[derive(Debug, Clone, Default)]
+pub struct MockRunner {
+    responses: HashMap<(String, Vec<String>), CommandOutput>,
+}
+
+impl MockRunner {
+    pub fn new() -> Self {
+        MockRunner::default()
+    }
+
+    pub fn with(mut self, program: &str, args: &[&str], output: CommandOutput) -> Self {
+        self.responses.insert((program.to_string(), args.iter().map(|arg| arg.to_string()).collect()), output);
+        self
+    }
+}
+
+impl CommandRunner for MockRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        let key = (program.to_string(), args.iter().map(|arg| arg.to_string()).collect());
+
+        self.responses
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{}: command not found", program)))
+    }
+}
+
+/// How often a failed vendor tool run is attempted, see [`retry_command`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// Attempts in all, the first one included.
+    pub max_attempts: u32,
+    pub delay: Duration,
+}
+
+impl Retry {
+    /// A single attempt.
+    pub const ONCE: Retry = Retry { max_attempts: 1, delay: Duration::ZERO };
+
+    /// Calls `attempt` until `succeeded` accepts its result, at most `max_attempts` times and
+    /// `delay` apart, and returns the last result. A tool that is not installed is not tried
+    /// again.
+    pub fn run<T>(&self, mut attempt: impl FnMut() -> io::Result<T>, succeeded: impl Fn(&T) -> bool) -> io::Result<T> {
+        let mut result = attempt();
+        for _ in 1..self.max_attempts {
+            match &result {
+                Ok(output) if succeeded(output) => break,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+                _ => {}
+            }
+            thread::sleep(self.delay);
+            result = attempt();
+        }
+        result
+    }
+}
+
+impl Default for Retry {
+    /// A transient failure, such as `nvidia-smi` during a GPU reset, usually clears within a
+    /// few hundred milliseconds.
+    fn default() -> Self {
+        Retry { max_attempts: 3, delay: Duration::from_millis(100) }
+    }
+}
+
+/// Runs `program` until it exits successfully, at most `max_attempts` times with `delay`
+/// between attempts. Returns the last output, which the caller still checks for success.
+pub fn retry_command(runner: &dyn CommandRunner, program: &str, args: &[&str], max_attempts: u32, delay: Duration) -> io::Result<CommandOutput> {
+    Retry { max_attempts, delay }.run(|| runner.run(program, args), |output| output.success)
+}
@@ -0,0 +1,239 @@
+//! The library's stable entry points: [`detect`] the GPUs, build a [`Sampler`] with
+//! [`SamplerBuilder`], and take [`Sample`]s.
+
+use std::error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::backend::{self, Backend};
+use crate::runner::{RealRunner, Retry};
+use crate::{enumerate_gpus, pci, try_identify_gpu_card, GpuInfo, GpuSnapshot, GpuType, PollResult};
+
+static RUNNER: RealRunner = RealRunner;
+
+/// One sample of one GPU.
+pub type Sample = GpuSnapshot;
+
+/// A GPU found by [`detect`].
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub info: GpuInfo,
+    pub vendor: GpuType,
+}
+
+/// The vendor of the first display-class PCI device from a supported vendor, from sysfs.
+fn detect_vendor_sysfs() -> Option<GpuType> {
+    pci::list_display_devices().ok()?.iter().find_map(|device| GpuType::from_pci_vendor(device.vendor_id))
+}
+
+/// Finds the GPUs of the machine's GPU vendor. Returns an empty list, rather than failing,
+/// when there is no supported GPU.
+///
+/// ```
+/// for device in gpu_auto_top::detect() {
+///     println!("GPU {}: {} ({:?})", device.info.index, device.info.name, device.vendor);
+/// }
+/// ```
+pub fn detect() -> Vec<Device> {
+    let Some(vendor) = detect_vendor_sysfs().or_else(|| try_identify_gpu_card(&RUNNER)) else {
+        return Vec::new();
+    };
+
+    enumerate_gpus(&RUNNER, &vendor).into_iter().map(|info| Device { info, vendor: vendor.clone() }).collect()
+}
+
+/// Where a [`Sampler`] reads its metrics from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendPreference {
+    /// Run the vendor tool (`nvidia-smi`, `radeontop`, `intel_gpu_top`, `tegrastats`) once per
+    /// sample.
+    #[default]
+    PerSample,
+    /// The cheapest source available: amdgpu sysfs, or a vendor tool kept running that
+    /// reports every interval. Falls back to [`BackendPreference::PerSample`].
+    LowOverhead,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// [`detect`] found no supported GPU.
+    NoDevices,
+    /// A device passed to [`SamplerBuilder::devices`] was not detected.
+    UnknownDevice(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoDevices => write!(f, "No supported GPU found"),
+            Error::UnknownDevice(index) => write!(f, "No GPU with index {}", index),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// A failed sample of one GPU. Transient failures usually clear up on the next sample;
+/// permanent ones (e.g. the vendor tool is missing) do not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleError {
+    pub device: u32,
+    pub message: String,
+    pub permanent: bool,
+}
+
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GPU {}: {}", self.device, self.message)
+    }
+}
+
+impl error::Error for SampleError {}
+
+/// Configures a [`Sampler`].
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use gpu_auto_top::{BackendPreference, SamplerBuilder};
+///
+/// let sampler = SamplerBuilder::new()
+///     .interval(Duration::from_millis(500))
+///     .backend(BackendPreference::LowOverhead)
+///     .devices([0])
+///     .build();
+///
+/// match sampler {
+///     Ok(sampler) => println!("Sampling with {}", sampler.backend_name()),
+///     Err(err) => println!("{}", err),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SamplerBuilder {
+    interval: Duration,
+    backend: BackendPreference,
+    devices: Option<Vec<u32>>,
+}
+
+impl Default for SamplerBuilder {
+    fn default() -> Self {
+        SamplerBuilder { interval: Duration::from_secs(1), backend: BackendPreference::default(), devices: None }
+    }
+}
+
+impl SamplerBuilder {
+    pub fn new() -> Self {
+        SamplerBuilder::default()
+    }
+
+    /// How often the caller intends to call [`Sampler::sample`]; streaming sources report at
+    /// this rate. Defaults to one second.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn backend(mut self, backend: BackendPreference) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Samples only the GPUs with these indices instead of all detected GPUs.
+    pub fn devices(mut self, indices: impl IntoIterator<Item = u32>) -> Self {
+        self.devices = Some(indices.into_iter().collect());
+        self
+    }
+
+    /// Detects the GPUs and opens the metrics source.
+    pub fn build(self) -> Result<Sampler, Error> {
+        let detected = detect();
+        let vendor = detected.first().ok_or(Error::NoDevices)?.vendor.clone();
+
+        let devices = match &self.devices {
+            None => detected.into_iter().map(|device| device.info).collect(),
+            Some(indices) => indices
+                .iter()
+                .map(|&index| {
+                    detected.iter().find(|device| device.info.index == index).map(|device| device.info.clone()).ok_or(Error::UnknownDevice(index))
+                })
+                .collect::<Result<_, _>>()?,
+        };
+
+        let low_overhead = self.backend == BackendPreference::LowOverhead;
+        let backend = backend::select(&RUNNER, &vendor, low_overhead, self.interval, Retry::default());
+        Ok(Sampler { devices, vendor, interval: self.interval, backend })
+    }
+}
+
+/// Takes samples of a fixed set of GPUs.
+///
+/// ```
+/// use gpu_auto_top::Sampler;
+///
+/// if gpu_auto_top::detect().is_empty() {
+///     println!("No GPU, nothing to sample");
+///     return;
+/// }
+///
+/// let mut sampler = Sampler::builder().build().expect("a GPU was detected");
+/// for sample in sampler.sample() {
+///     match sample {
+///         Ok(sample) => println!("GPU {}: {}%", sample.gpu.index, sample.utilization),
+///         Err(err) => println!("{}", err),
+///     }
+/// }
+/// ```
+pub struct Sampler {
+    devices: Vec<GpuInfo>,
+    vendor: GpuType,
+    interval: Duration,
+    backend: Box<dyn Backend>,
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("devices", &self.devices)
+            .field("vendor", &self.vendor)
+            .field("interval", &self.interval)
+            .field("backend", &self.backend.name())
+            .finish()
+    }
+}
+
+impl Sampler {
+    pub fn builder() -> SamplerBuilder {
+        SamplerBuilder::new()
+    }
+
+    pub fn devices(&self) -> &[GpuInfo] {
+        &self.devices
+    }
+
+    pub fn vendor(&self) -> &GpuType {
+        &self.vendor
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Human-readable name of the metrics source, e.g. `amdgpu sysfs`.
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    /// Takes one sample of every device, in device order, each with its `source`. Does not
+    /// sleep; call it once per [`Sampler::interval`].
+    pub fn sample(&mut self) -> Vec<Result<Sample, SampleError>> {
+        self.backend
+            .poll(&self.devices)
+            .into_iter()
+            .map(|result| match result {
+                PollResult::Ok { snapshot, .. } => Ok(Sample { source: Some(self.backend.source().to_string()), ..snapshot }),
+                PollResult::TransientError { gpu, message, .. } => Err(SampleError { device: gpu.index, message, permanent: false }),
+                PollResult::PermanentError { gpu, message, .. } => Err(SampleError { device: gpu.index, message, permanent: true }),
+            })
+            .collect()
+    }
+}
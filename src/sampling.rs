@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::schema::SCHEMA_VERSION;
+use crate::GpuSnapshot;
+
+/// Default capacity of the raw sample ring buffer: one GPU at 50 ms for well over an hour.
+pub const DEFAULT_BUFFER_SAMPLES: usize = 100_000;
+
+/// The shortest `--interval` without `--allow-fast-poll`. Each poll of an NVIDIA GPU runs
+/// `nvidia-smi`, which the driver serves at the expense of the work on the GPU; below this the
+/// monitoring starts to slow down what it monitors.
+pub const MIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The interval to poll at: `interval`, raised to [`MIN_INTERVAL`] unless `allow_fast_poll`.
+pub fn clamp_interval(interval: Duration, allow_fast_poll: bool) -> Duration {
+    if allow_fast_poll {
+        interval
+    } else {
+        interval.max(MIN_INTERVAL)
+    }
+}
+
+/// Parses durations such as `50ms`, `1s`, `2m` or a bare number of seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", value);
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(position) => value.split_at(position),
+        None => (value, "s"),
+    };
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    if seconds <= 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// One full-resolution sample, kept small since the buffer may hold many of them.
+#[derive(Debug, Clone, Copy)]
+pub struct RawSample {
+    pub elapsed: Duration,
+    pub tick_seq: u64,
+    pub gpu: u32,
+    pub utilization: f64,
+    pub memory_used_mib: Option<u64>,
+    pub temperature_c: Option<f32>,
+    pub power_w: Option<f32>,
+}
+
+impl RawSample {
+    pub fn new(elapsed: Duration, tick_seq: u64, snapshot: &GpuSnapshot) -> Self {
+        RawSample {
+            elapsed,
+            tick_seq,
+            gpu: snapshot.gpu.index,
+            utilization: snapshot.utilization,
+            memory_used_mib: snapshot.memory_used_mib,
+            temperature_c: snapshot.temperature_c,
+            power_w: snapshot.power_w,
+        }
+    }
+}
+
+/// Bounded buffer of raw samples; once full, the oldest samples are overwritten.
+#[derive(Debug)]
+pub struct RingBuffer {
+    capacity: usize,
+    samples: VecDeque<RawSample>,
+    dropped: u64,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer { capacity, samples: VecDeque::with_capacity(capacity.min(DEFAULT_BUFFER_SAMPLES)), dropped: 0 }
+    }
+
+    pub fn push(&mut self, sample: RawSample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+            self.dropped += 1;
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Number of samples overwritten because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn write_csv(&self, path: &str) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        // Readers check the version comment before the header, like `schema_version` in JSON.
+        writeln!(file, "# schema_version={}", SCHEMA_VERSION)?;
+        writeln!(file, "time_s,tick_seq,gpu,utilization,memory_used_mib,temperature_c,power_w")?;
+
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        for sample in &self.samples {
+            writeln!(
+                file,
+                "{:.3},{},{},{},{},{},{}",
+                sample.elapsed.as_secs_f64(),
+                sample.tick_seq,
+                sample.gpu,
+                sample.utilization,
+                optional(sample.memory_used_mib.map(|value| value.to_string())),
+                optional(sample.temperature_c.map(|value| value.to_string())),
+                optional(sample.power_w.map(|value| value.to_string()))
+            )?;
+        }
+
+        file.flush()
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Folds the samples of one GPU over a display interval into a single snapshot: mean and
+/// peak utilization (so a short burst in an idle second stays visible), peak memory and
+/// temperature, and mean power. Counters such as NVLink come from the latest sample.
+pub fn aggregate(samples: &[GpuSnapshot]) -> Option<GpuSnapshot> {
+    let last = samples.last()?;
+
+    Some(GpuSnapshot {
+        gpu: last.gpu.clone(),
+        utilization: mean(samples.iter().map(|sample| sample.utilization))?,
+        utilization_max: samples.iter().map(|sample| sample.utilization).reduce(f64::max),
+        memory_used_mib: samples.iter().filter_map(|sample| sample.memory_used_mib).max(),
+        memory_total_mib: last.memory_total_mib,
+        temperature_c: samples.iter().filter_map(|sample| sample.temperature_c).reduce(f32::max),
+        power_w: mean(samples.iter().filter_map(|sample| sample.power_w).map(f64::from)).map(|power| power as f32),
+        nvlink: last.nvlink,
+        usage_split: last.usage_split,
+        memory_bandwidth: last.memory_bandwidth,
+        aperture: last.aperture,
+        temperatures: last.temperatures.clone(),
+        activity: last.activity,
+        efficiency: last.efficiency,
+        engines: None,
+        clock_mhz: None,
+        throttle_reasons: last.throttle_reasons.clone(),
+        source: last.source.clone(),
+    })
+}
@@ -0,0 +1,102 @@
+//! `--schema`: the JSON Schema of the records gpuatop writes, and the version every record
+//! carries as `schema_version`. The version is bumped when a field is removed, renamed or
+//! changes its type; new optional fields keep it.
+//!
+//! The schema is written by hand. [`validate`] checks a record against it, which the tests do
+//! for every record type so that the schema cannot fall behind the output.
+
+use crate::json::{self, Value};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The JSON Schema (draft 2020-12) document.
+pub const SCHEMA: &str = include_str!("schema.json");
+
+fn member<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match (name, value) {
+        ("null", Value::Null) | ("boolean", Value::Bool(_)) | ("number", Value::Number(_)) => true,
+        ("integer", Value::Number(number)) => number.fract() == 0.0,
+        ("string", Value::String(_)) | ("array", Value::Array(_)) | ("object", Value::Object(_)) => true,
+        _ => false,
+    }
+}
+
+/// Checks `value` against `schema`, supporting the keywords [`SCHEMA`] uses: `$ref` into
+/// `$defs`, `oneOf`, `type`, `const`, `enum`, `minimum`, `required`, `properties`,
+/// `additionalProperties` and `items`.
+fn check(root: &Value, schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    if let Some(Value::String(reference)) = member(schema, "$ref") {
+        let name = reference.strip_prefix("#/$defs/").ok_or_else(|| format!("Unsupported $ref {}", reference))?;
+        let definition = member(root, "$defs").and_then(|defs| member(defs, name)).ok_or_else(|| format!("Unknown $ref {}", reference))?;
+        check(root, definition, value, path)?;
+    }
+
+    if let Some(Value::Array(alternatives)) = member(schema, "oneOf") {
+        let matches = alternatives.iter().filter(|alternative| check(root, alternative, value, path).is_ok()).count();
+        if matches != 1 {
+            return Err(format!("{}: matches {} of the {} record types, expected exactly one", path, matches, alternatives.len()));
+        }
+    }
+
+    match member(schema, "type") {
+        Some(Value::String(name)) if !has_type(value, name) => return Err(format!("{}: expected {}, got {}", path, name, value.to_json())),
+        Some(Value::Array(names)) if !names.iter().any(|name| matches!(name, Value::String(name) if has_type(value, name))) => {
+            return Err(format!("{}: unexpected type of {}", path, value.to_json()));
+        }
+        _ => {}
+    }
+    if let Some(expected) = member(schema, "const") {
+        if value != expected {
+            return Err(format!("{}: expected {}, got {}", path, expected.to_json(), value.to_json()));
+        }
+    }
+    if let Some(Value::Array(allowed)) = member(schema, "enum") {
+        if !allowed.contains(value) {
+            return Err(format!("{}: {} is not one of {}", path, value.to_json(), Value::Array(allowed.clone()).to_json()));
+        }
+    }
+    if let (Some(Value::Number(minimum)), Value::Number(number)) = (member(schema, "minimum"), value) {
+        if number < minimum {
+            return Err(format!("{}: {} is below {}", path, number, minimum));
+        }
+    }
+
+    if let Value::Object(members) = value {
+        if let Some(Value::Array(required)) = member(schema, "required") {
+            for key in required.iter().filter_map(|key| if let Value::String(key) = key { Some(key) } else { None }) {
+                if member(value, key).is_none() {
+                    return Err(format!("{}: missing \"{}\"", path, key));
+                }
+            }
+        }
+        for (key, field) in members {
+            let path = format!("{}.{}", path, key);
+            match (member(schema, "properties").and_then(|properties| member(properties, key)), member(schema, "additionalProperties")) {
+                (Some(property), _) => check(root, property, field, &path)?,
+                (None, Some(Value::Bool(false))) => return Err(format!("{}: not in the schema", path)),
+                (None, Some(additional @ Value::Object(_))) => check(root, additional, field, &path)?,
+                (None, _) => {}
+            }
+        }
+    }
+    if let (Value::Array(items), Some(schema)) = (value, member(schema, "items")) {
+        for (index, item) in items.iter().enumerate() {
+            check(root, schema, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks one record, as parsed from the JSON output, against [`SCHEMA`].
+pub fn validate(record: &Value) -> Result<(), String> {
+    let schema = json::parse(SCHEMA).map_err(|err| format!("Invalid schema: {}", err))?;
+    check(&schema, &schema, record, "$")
+}
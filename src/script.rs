@@ -0,0 +1,138 @@
+//! `--script <file.lua>`: user-defined alerting and formatting logic in Lua 5.4, embedded in
+//! gpuatop. The script runs once at start-up and must define a global `on_snapshot(snapshots)`
+//! function, which is called every polling cycle with the cycle's samples: a list of tables with
+//! the fields of the `--format ndjson` record of each sample, such as `gpu`, `utilization` or
+//! `power_w`. A metric that was not sampled is `nil`.
+//!
+//! Besides the Lua standard libraries, the script has the functions of [`Action`]: `print`
+//! writes among the samples instead of straight to stdout.
+
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use mlua::{Function, Lua, MultiValue, Value as LuaValue};
+
+use crate::json::{self, Value};
+
+/// What the script asked for during a call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// `print(...)`: its arguments, separated by tabs as Lua's own `print` does.
+    Print(String),
+    /// `alert(message[, gpu])`: a `script_alert` event about GPU `gpu`, or the first GPU.
+    Alert { message: String, gpu: Option<u32> },
+    /// `exit(code)`: the script stops there and monitoring ends with the code.
+    Exit(i32),
+}
+
+/// A loaded `--script`.
+pub struct Script {
+    lua: Lua,
+    actions: Rc<RefCell<Vec<Action>>>,
+}
+
+impl Script {
+    /// Reads and runs the script at `path`.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|err| format!("Failed to read --script {}: {}", path, err))?;
+        Script::new(path, &source)
+    }
+
+    /// Runs `source`, which error messages call `name`.
+    pub fn new(name: &str, source: &str) -> Result<Self, String> {
+        let lua = Lua::new();
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        register(&lua, &actions).map_err(|err| err.to_string())?;
+        lua.load(source).set_name(name).exec().map_err(|err| format!("Failed to load --script {}: {}", name, first_line(&err)))?;
+        if !matches!(lua.globals().get("on_snapshot"), Ok(LuaValue::Function(_))) {
+            return Err(format!("--script {} does not define an on_snapshot function", name));
+        }
+        Ok(Script { lua, actions })
+    }
+
+    /// Calls `on_snapshot` with the cycle's NDJSON records. Returns what the script asked for
+    /// since the last call, or the error it raised.
+    pub fn on_snapshot(&mut self, records: &[String]) -> Result<Vec<Action>, String> {
+        let called = self.call(records);
+        let actions = self.actions.take();
+        match called {
+            // `exit` stops the script with an error of its own.
+            Err(_) if matches!(actions.last(), Some(Action::Exit(_))) => Ok(actions),
+            Err(err) => Err(err),
+            Ok(()) => Ok(actions),
+        }
+    }
+
+    fn call(&self, records: &[String]) -> Result<(), String> {
+        let snapshots = self.lua.create_table().map_err(|err| err.to_string())?;
+        for (index, record) in records.iter().enumerate() {
+            let snapshot = to_lua(&self.lua, &json::parse(record)?).map_err(|err| err.to_string())?;
+            snapshots.set(index + 1, snapshot).map_err(|err| err.to_string())?;
+        }
+        let on_snapshot: Function = self.lua.globals().get("on_snapshot").map_err(|err| err.to_string())?;
+        on_snapshot.call::<_, ()>(snapshots).map_err(|err| first_line(&err))
+    }
+}
+
+/// Defines `print`, `alert` and `exit`, which record their action in `actions`.
+fn register(lua: &Lua, actions: &Rc<RefCell<Vec<Action>>>) -> mlua::Result<()> {
+    let globals = lua.globals();
+
+    let recorded = Rc::clone(actions);
+    let print = lua.create_function(move |lua, values: MultiValue| {
+        let tostring: Function = lua.globals().get("tostring")?;
+        let parts = values.into_iter().map(|value| tostring.call::<_, String>(value)).collect::<mlua::Result<Vec<_>>>()?;
+        recorded.borrow_mut().push(Action::Print(parts.join("\t")));
+        Ok(())
+    })?;
+    globals.set("print", print)?;
+
+    let recorded = Rc::clone(actions);
+    let alert = lua.create_function(move |_, (message, gpu): (String, Option<u32>)| {
+        recorded.borrow_mut().push(Action::Alert { message, gpu });
+        Ok(())
+    })?;
+    globals.set("alert", alert)?;
+
+    let recorded = Rc::clone(actions);
+    let exit = lua.create_function(move |_, code: Option<i32>| -> mlua::Result<()> {
+        recorded.borrow_mut().push(Action::Exit(code.unwrap_or(0)));
+        Err(mlua::Error::runtime("exit"))
+    })?;
+    globals.set("exit", exit)
+}
+
+/// The Lua value of a JSON value: objects and arrays become tables, whole numbers integers.
+fn to_lua<'lua>(lua: &'lua Lua, value: &Value) -> mlua::Result<LuaValue<'lua>> {
+    Ok(match value {
+        Value::Null => LuaValue::Nil,
+        Value::Bool(value) => LuaValue::Boolean(*value),
+        Value::Number(number) if number.fract() == 0.0 && number.abs() < 9.0e15 => LuaValue::Integer(*number as i64),
+        Value::Number(number) => LuaValue::Number(*number),
+        Value::String(text) => LuaValue::String(lua.create_string(text)?),
+        Value::Array(items) => {
+            let table = lua.create_table()?;
+            for (index, item) in items.iter().enumerate() {
+                table.set(index + 1, to_lua(lua, item)?)?;
+            }
+            LuaValue::Table(table)
+        }
+        Value::Object(fields) => {
+            let table = lua.create_table()?;
+            for (key, field) in fields {
+                table.set(key.as_str(), to_lua(lua, field)?)?;
+            }
+            LuaValue::Table(table)
+        }
+    })
+}
+
+/// The message of a Lua error without its stack traceback.
+fn first_line(err: &mlua::Error) -> String {
+    let message = match err {
+        mlua::Error::CallbackError { cause, .. } => cause.to_string(),
+        err => err.to_string(),
+    };
+    message.lines().next().unwrap_or_default().to_string()
+}
@@ -0,0 +1,218 @@
+//! `gpuatop server`: collects the samples of remote `gpuatop --send-to-tcp` instances and shows
+//! every GPU grouped by machine, optionally exporting them all on one Prometheus endpoint.
+//!
+//! Each client connection must start with the [`HANDSHAKE`] line; what follows is read as
+//! NDJSON records, and records that are not GPU samples (GPU states, per-user tables) are
+//! skipped. Machines are named by the records' `hostname` (`--machine-hostname` on the
+//! client), or by the client's address without it.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::golden::snapshot_from_json;
+use crate::json::{self, Value};
+use crate::output::{self, OutputContext};
+use crate::prometheus;
+use crate::tcp::HANDSHAKE;
+use crate::GpuSnapshot;
+
+/// How long each remote GPU's samples are kept.
+pub const HISTORY: Duration = Duration::from_secs(60 * 60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest line a client may send; records are a few hundred bytes.
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// `--port` and `--prometheus-port`.
+pub fn parse_port(option: &str, value: &str) -> Result<u16, String> {
+    value.parse().ok().filter(|port| *port > 0).ok_or_else(|| format!("Invalid {} value: {}", option, value))
+}
+
+/// The machine's name and the sample in one NDJSON record; `None` for anything but a sample.
+pub fn parse_record(line: &str) -> Option<(Option<String>, GpuSnapshot)> {
+    let record = json::parse(line).ok()?;
+    let hostname = match &record {
+        Value::Object(members) => members.iter().find(|(key, _)| key == "hostname").and_then(|(_, value)| match value {
+            Value::String(hostname) => Some(hostname.clone()),
+            _ => None,
+        }),
+        _ => None,
+    };
+    Some((hostname, snapshot_from_json(&record).ok()?))
+}
+
+#[derive(Debug, Default)]
+struct Host {
+    connections: usize,
+    /// Each GPU's samples, oldest first.
+    gpus: BTreeMap<u32, VecDeque<(Instant, GpuSnapshot)>>,
+}
+
+/// The remote GPUs, by machine.
+#[derive(Debug, Default)]
+pub struct Fleet {
+    hosts: BTreeMap<String, Host>,
+}
+
+impl Fleet {
+    pub fn connect(&mut self, host: &str) {
+        self.hosts.entry(host.to_string()).or_default().connections += 1;
+    }
+
+    pub fn disconnect(&mut self, host: &str) {
+        if let Some(host) = self.hosts.get_mut(host) {
+            host.connections = host.connections.saturating_sub(1);
+        }
+    }
+
+    pub fn record(&mut self, host: &str, snapshot: GpuSnapshot, now: Instant) {
+        let host = self.hosts.entry(host.to_string()).or_default();
+        host.gpus.entry(snapshot.gpu.index).or_default().push_back((now, snapshot));
+    }
+
+    /// Drops the samples older than [`HISTORY`], and the machines left with none that are not
+    /// connected.
+    pub fn expire(&mut self, now: Instant) {
+        for host in self.hosts.values_mut() {
+            for samples in host.gpus.values_mut() {
+                while samples.front().is_some_and(|(at, _)| now.saturating_duration_since(*at) > HISTORY) {
+                    samples.pop_front();
+                }
+            }
+            host.gpus.retain(|_, samples| !samples.is_empty());
+        }
+        self.hosts.retain(|_, host| host.connections > 0 || !host.gpus.is_empty());
+    }
+
+    /// The latest sample of every GPU of the connected machines.
+    pub fn latest(&self) -> Vec<(String, Vec<GpuSnapshot>)> {
+        self.hosts
+            .iter()
+            .filter(|(_, host)| host.connections > 0)
+            .map(|(name, host)| (name.clone(), host.gpus.values().filter_map(|samples| samples.back()).map(|(_, snapshot)| snapshot.clone()).collect()))
+            .filter(|(_, snapshots): &(String, Vec<GpuSnapshot>)| !snapshots.is_empty())
+            .collect()
+    }
+
+    /// A header line per machine followed by a line per GPU: its latest sample, and the mean
+    /// and peak utilization over the kept history.
+    pub fn format(&self, now: Instant, precision: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (name, host) in &self.hosts {
+            let last_seen = host.gpus.values().filter_map(|samples| samples.back()).map(|(at, _)| *at).max();
+            let state = match (host.connections, last_seen) {
+                (0, _) => "disconnected".to_string(),
+                (_, None) => "connected, no samples yet".to_string(),
+                (_, Some(at)) => format!("last sample {}s ago", now.saturating_duration_since(at).as_secs()),
+            };
+            lines.push(format!("{} ({})", name, state));
+
+            for samples in host.gpus.values() {
+                let Some((first, latest)) = samples.front().zip(samples.back()) else { continue };
+                let utilizations = samples.iter().map(|(_, snapshot)| snapshot.utilization);
+                let mean = utilizations.clone().sum::<f64>() / samples.len() as f64;
+                let peak = utilizations.fold(0.0, f64::max);
+                let minutes = now.saturating_duration_since(first.0).as_secs().div_ceil(60).max(1);
+                lines.push(format!(
+                    "  {} | {} min: mean {:.*}%, peak {:.*}%",
+                    output::format_text(&latest.1, precision),
+                    minutes,
+                    precision,
+                    mean,
+                    precision,
+                    peak
+                ));
+            }
+        }
+        lines
+    }
+}
+
+/// The fleet as shared between the connection threads and the display.
+pub type SharedFleet = Arc<Mutex<Fleet>>;
+
+pub fn lock(fleet: &SharedFleet) -> MutexGuard<'_, Fleet> {
+    fleet.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Reads a line of at most [`MAX_LINE_LEN`] bytes; `None` at the end of the stream. A longer
+/// line is an error, as the client is not a gpuatop.
+pub fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.by_ref().take(MAX_LINE_LEN as u64 + 1).read_line(&mut line)?;
+    if read > MAX_LINE_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("line longer than {} bytes", MAX_LINE_LEN)));
+    }
+    Ok((read > 0).then_some(line))
+}
+
+fn handle_client(stream: TcpStream, peer: SocketAddr, fleet: &SharedFleet) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    if read_line(&mut reader)?.as_deref().map(str::trim_end) != Some(HANDSHAKE) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} did not start with {}", peer, HANDSHAKE)));
+    }
+
+    // The machine is only known from its first sample.
+    let mut host: Option<String> = None;
+    // A connection reset ends the stream like a clean close does, and an overlong line too:
+    // the connection is dropped.
+    while let Ok(Some(line)) = read_line(&mut reader) {
+        let Some((hostname, snapshot)) = parse_record(&line) else { continue };
+        let mut fleet = lock(fleet);
+        let name = host.get_or_insert_with(|| {
+            let name = hostname.unwrap_or_else(|| peer.ip().to_string());
+            fleet.connect(&name);
+            name
+        });
+        fleet.record(name, snapshot, Instant::now());
+    }
+
+    if let Some(host) = host {
+        lock(fleet).disconnect(&host);
+    }
+    Ok(())
+}
+
+/// Accepts `--send-to-tcp` clients in the background, a thread each.
+pub fn accept_clients(listener: TcpListener, fleet: SharedFleet) {
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let Ok(peer) = stream.peer_addr() else { continue };
+            let fleet = Arc::clone(&fleet);
+            thread::spawn(move || handle_client(stream, peer, &fleet));
+        }
+    });
+}
+
+fn handle_scrape(mut stream: TcpStream, fleet: &SharedFleet, context: &OutputContext) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(stream.try_clone()?).read_line(&mut request_line)?;
+
+    let (status, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", prometheus::format_hosts_page(&lock(fleet).latest(), context)),
+        _ => ("404 Not Found", "Not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Serves the latest sample of every connected machine's GPUs at `/metrics`, in the
+/// background.
+pub fn serve_metrics(listener: TcpListener, fleet: SharedFleet, context: OutputContext) {
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            let _ = handle_scrape(stream, &fleet, &context);
+        }
+    });
+}
@@ -0,0 +1,144 @@
+//! Local output sinks that stream NDJSON samples to other programs: a Unix socket any number
+//! of clients can connect to, and a FIFO.
+//!
+//! Writes to a peer that went away fail with `EPIPE` rather than killing the process, since
+//! the Rust runtime ignores `SIGPIPE`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::live;
+
+/// `O_NONBLOCK` as defined on Linux for all mainstream architectures.
+const O_NONBLOCK: i32 = 0o4000;
+
+/// `ENXIO`: opening a FIFO for writing without a reader on the other end.
+const ENXIO: i32 = 6;
+
+/// How long a write may block on a slow socket client before it is disconnected.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+fn debug(enabled: bool, message: &str) {
+    if enabled {
+        live::eprintln(&format!("debug: {}", message));
+    }
+}
+
+/// Unix stream socket that sends every sample to each connected client.
+#[derive(Debug)]
+pub struct SocketSink {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<(u64, UnixStream)>,
+    next_client: u64,
+    debug: bool,
+}
+
+impl SocketSink {
+    pub fn bind(path: &str, debug: bool) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+
+        // A socket file left behind by a crashed instance is removed, a live one is not.
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is in use by another process", path.display())));
+            }
+            fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(SocketSink { path, listener, clients: Vec::new(), next_client: 0, debug })
+    }
+
+    fn accept_pending(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(false).and_then(|()| stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))).is_err() {
+                        continue;
+                    }
+                    self.next_client += 1;
+                    debug(self.debug, &format!("socket client {} connected", self.next_client));
+                    self.clients.push((self.next_client, stream));
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    debug(self.debug, &format!("socket accept failed: {}", err));
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn send(&mut self, line: &str) {
+        self.accept_pending();
+
+        let debug_enabled = self.debug;
+        self.clients.retain_mut(|(id, stream)| match writeln!(stream, "{}", line) {
+            Ok(()) => true,
+            Err(err) => {
+                debug(debug_enabled, &format!("socket client {} disconnected: {}", id, err));
+                false
+            }
+        });
+    }
+}
+
+impl Drop for SocketSink {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Named pipe written to whenever a reader has it open. Without a reader, or when the reader
+/// is not keeping up, samples are skipped instead of blocking the monitor.
+#[derive(Debug)]
+pub struct FifoSink {
+    path: PathBuf,
+    file: Option<File>,
+    debug: bool,
+}
+
+impl FifoSink {
+    pub fn open(path: &str, debug: bool) -> io::Result<Self> {
+        let path = PathBuf::from(path);
+
+        if !fs::metadata(&path)?.file_type().is_fifo() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a FIFO (create it with mkfifo)", path.display())));
+        }
+
+        Ok(FifoSink { path, file: None, debug })
+    }
+
+    pub fn send(&mut self, line: &str) {
+        if self.file.is_none() {
+            match OpenOptions::new().write(true).custom_flags(O_NONBLOCK).open(&self.path) {
+                Ok(file) => {
+                    debug(self.debug, "FIFO reader connected");
+                    self.file = Some(file);
+                }
+                Err(err) if err.raw_os_error() == Some(ENXIO) => return,
+                Err(err) => {
+                    debug(self.debug, &format!("FIFO open failed: {}", err));
+                    return;
+                }
+            }
+        }
+
+        let Some(file) = &mut self.file else { return };
+        match writeln!(file, "{}", line) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => debug(self.debug, "FIFO full, sample skipped"),
+            Err(err) => {
+                debug(self.debug, &format!("FIFO reader disconnected: {}", err));
+                self.file = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::json::Value;
+use crate::runner::CommandRunner;
+use crate::xml::{self, Element};
+
+/// Converts an XML element into a JSON value mirroring its hierarchy. Leaf elements become
+/// strings; repeated child elements become arrays. Attributes keep their names and text next to
+/// children is `value`; a child element whose name is taken by one of those is kept under its
+/// name in angle brackets (`<value>`). Nothing is dropped, so elements added by newer drivers
+/// show up without code changes.
+pub fn element_to_value(element: &Element) -> Value {
+    if element.children.is_empty() && element.attributes.is_empty() {
+        return Value::String(element.text.clone());
+    }
+
+    let mut members: Vec<(String, Value)> = element
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+
+    if !element.text.is_empty() {
+        members.push(("value".to_string(), Value::String(element.text.clone())));
+    }
+
+    let mut grouped: Vec<&str> = Vec::new();
+    for child in &element.children {
+        if grouped.contains(&child.name.as_str()) {
+            continue;
+        }
+        grouped.push(&child.name);
+
+        let mut same_name = element.children_named(&child.name).map(element_to_value);
+        let value = match (same_name.next(), same_name.next()) {
+            (Some(first), None) => first,
+            (Some(first), Some(second)) => Value::Array([first, second].into_iter().chain(same_name).collect()),
+            _ => unreachable!("child is always among its own name group"),
+        };
+
+        let key = if members.iter().any(|(name, _)| name == &child.name) { format!("<{}>", child.name) } else { child.name.clone() };
+        members.push((key, value));
+    }
+
+    Value::Object(members)
+}
+
+pub fn query_snapshot_xml(runner: &dyn CommandRunner) -> io::Result<String> {
+    let output = runner.run("nvidia-smi", &["-q", "-x"])?;
+
+    if !output.success {
+        return Err(io::Error::other(output.stderr.trim().to_string()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Builds a snapshot of the `nvidia-smi -q -x` report: host-level fields (driver and CUDA
+/// versions, ...) plus a `gpus` array, limited to the GPU at position `gpu` when given.
+pub fn build_snapshot(xml: &str, gpu: Option<u32>) -> Result<Value, String> {
+    let root = xml::parse(xml)?;
+
+    if root.name != "nvidia_smi_log" {
+        return Err(format!("Unexpected root element <{}>", root.name));
+    }
+
+    let mut members: Vec<(String, Value)> = root
+        .children
+        .iter()
+        .filter(|child| child.name != "gpu")
+        .map(|child| (child.name.clone(), element_to_value(child)))
+        .collect();
+
+    let gpus: Vec<Value> = root
+        .children_named("gpu")
+        .enumerate()
+        .filter(|(index, _)| gpu.is_none_or(|gpu| gpu as usize == *index))
+        .map(|(_, element)| element_to_value(element))
+        .collect();
+
+    if let (Some(gpu), true) = (gpu, gpus.is_empty()) {
+        return Err(format!("GPU {} not found", gpu));
+    }
+
+    members.push(("gpus".to_string(), Value::Array(gpus)));
+    Ok(Value::Object(members))
+}
+
+/// Renders a snapshot as indented `key: value` lines.
+pub fn format_text(value: &Value) -> String {
+    let mut lines = Vec::new();
+    push_text_lines(&mut lines, None, value, 0);
+    lines.join("\n")
+}
+
+fn push_text_lines(lines: &mut Vec<String>, key: Option<&str>, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+
+    match value {
+        Value::Object(members) => {
+            let child_depth = match key {
+                Some(key) => {
+                    lines.push(format!("{}{}", indent, key));
+                    depth + 1
+                }
+                None => depth,
+            };
+            for (name, member) in members {
+                push_text_lines(lines, Some(name), member, child_depth);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let key = format!("{}[{}]", key.unwrap_or_default(), index);
+                push_text_lines(lines, Some(&key), item, depth);
+            }
+        }
+        scalar => lines.push(format!("{}{}: {}", indent, key.unwrap_or_default(), scalar.to_display())),
+    }
+}
+
+/// Fields that change from one moment to the next and are not part of a device's
+/// configuration. They are skipped by [`diff_snapshots`] unless `--all` is given.
+const VOLATILE_FIELDS: [&str; 16] = [
+    "timestamp",
+    "temperature",
+    "utilization",
+    "fan_speed",
+    "performance_state",
+    "clocks",
+    "clocks_event_reasons",
+    "clocks_throttle_reasons",
+    "fb_memory_usage",
+    "bar1_memory_usage",
+    "power_draw",
+    "instant_power_draw",
+    "average_power_draw",
+    "processes",
+    "voltage",
+    "total_energy_consumption",
+];
+
+/// A field that differs between two snapshots; `None` marks a field missing on one side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub path: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub volatile: bool,
+}
+
+fn flatten(value: &Value, path: String, fields: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(members) => {
+            for (key, member) in members {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                flatten(member, path, fields);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(item, format!("{}[{}]", path, index), fields);
+            }
+        }
+        scalar => fields.push((path, scalar.to_display())),
+    }
+}
+
+fn is_volatile(path: &str) -> bool {
+    path.split('.')
+        .map(|segment| segment.split('[').next().unwrap_or(segment))
+        .any(|segment| VOLATILE_FIELDS.contains(&segment))
+}
+
+/// Compares two snapshots field by field. Volatile fields are only included when
+/// `include_volatile` is set.
+pub fn diff_snapshots(old: &Value, new: &Value, include_volatile: bool) -> Vec<FieldChange> {
+    let mut old_fields = Vec::new();
+    let mut new_fields = Vec::new();
+    flatten(old, String::new(), &mut old_fields);
+    flatten(new, String::new(), &mut new_fields);
+
+    let old_values: HashMap<&str, &str> = old_fields.iter().map(|(path, value)| (path.as_str(), value.as_str())).collect();
+    let new_values: HashMap<&str, &str> = new_fields.iter().map(|(path, value)| (path.as_str(), value.as_str())).collect();
+
+    // Fields of the old snapshot in its order, then those only the new one has.
+    let paths = old_fields.iter().map(|(path, _)| path).chain(new_fields.iter().map(|(path, _)| path).filter(|path| !old_values.contains_key(path.as_str())));
+
+    paths
+        .filter_map(|path| {
+            let old = old_values.get(path.as_str()).map(|value| value.to_string());
+            let new = new_values.get(path.as_str()).map(|value| value.to_string());
+            let volatile = is_volatile(path);
+
+            (old != new && (include_volatile || !volatile)).then(|| FieldChange { path: path.clone(), old, new, volatile })
+        })
+        .collect()
+}
+
+pub fn format_change(change: &FieldChange) -> String {
+    format!(
+        "{}: {} → {}",
+        change.path,
+        change.old.as_deref().unwrap_or("(missing)"),
+        change.new.as_deref().unwrap_or("(missing)")
+    )
+}
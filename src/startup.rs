@@ -0,0 +1,52 @@
+//! The wait for the first sample. With persistence mode off, the first vendor tool call loads
+//! and initializes the driver, which takes 3 to 8 seconds; gpuatop says so instead of
+//! looking hung, and `--max-startup-wait` lets a status bar give up on it.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the first query may take before gpuatop prints a notice and reports the wake-up.
+pub const NOTICE_AFTER: Duration = Duration::from_millis(500);
+
+/// Whether the monitor has its first sample; held by `--max-startup-wait` while it gives up.
+static FIRST_SAMPLE: Mutex<bool> = Mutex::new(false);
+
+/// Runs `query`, calling `notice` from another thread if it is still running after `after`.
+/// Returns its result and how long it took.
+pub fn timed<T>(after: Duration, notice: impl FnOnce() + Send, query: impl FnOnce() -> T) -> (T, Duration) {
+    let started = Instant::now();
+    let (done, finished) = mpsc::channel::<()>();
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            if finished.recv_timeout(after) == Err(RecvTimeoutError::Timeout) {
+                notice();
+            }
+        });
+        let result = query();
+        drop(done);
+        (result, started.elapsed())
+    })
+}
+
+/// `--max-startup-wait`: unless the monitor claims its first sample within `wait`, prints
+/// `line` and exits with code 0, so that a status bar renders without waiting for the driver.
+pub fn exit_unless_sampled_within(wait: Duration, line: String) {
+    thread::spawn(move || {
+        thread::sleep(wait);
+        let sampled = FIRST_SAMPLE.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*sampled {
+            println!("{}", line);
+            // Exits with the lock held: a sample arriving now is never printed.
+            std::process::exit(0);
+        }
+    });
+}
+
+/// Called by the monitor before it prints a tick. Blocks, never to return, if
+/// `--max-startup-wait` is giving up at that moment.
+pub fn claim_first_sample() {
+    *FIRST_SAMPLE.lock().unwrap_or_else(PoisonError::into_inner) = true;
+}
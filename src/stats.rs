@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use crate::display::table::{Align, Column, Table};
+use crate::{GpuInfo, GpuSnapshot};
+
+/// Running statistics for one GPU, accumulated without keeping individual samples.
+#[derive(Debug, Clone)]
+pub struct GpuStats {
+    pub gpu: GpuInfo,
+    pub samples: u64,
+    pub utilization_sum: f64,
+    pub utilization_min: f64,
+    pub utilization_max: f64,
+    pub memory_peak_mib: Option<u64>,
+    pub temperature_max_c: Option<f32>,
+    pub power_max_w: Option<f32>,
+}
+
+impl GpuStats {
+    fn new(gpu: GpuInfo) -> Self {
+        GpuStats {
+            gpu,
+            samples: 0,
+            utilization_sum: 0.0,
+            utilization_min: f64::MAX,
+            utilization_max: f64::MIN,
+            memory_peak_mib: None,
+            temperature_max_c: None,
+            power_max_w: None,
+        }
+    }
+
+    pub fn utilization_avg(&self) -> f64 {
+        if self.samples == 0 { 0.0 } else { self.utilization_sum / self.samples as f64 }
+    }
+
+    fn record(&mut self, snapshot: &GpuSnapshot) {
+        self.samples += 1;
+        self.utilization_sum += snapshot.utilization;
+        self.utilization_min = self.utilization_min.min(snapshot.utilization);
+        self.utilization_max = self.utilization_max.max(snapshot.utilization);
+        self.memory_peak_mib = self.memory_peak_mib.max(snapshot.memory_used_mib);
+        self.temperature_max_c = max_option(self.temperature_max_c, snapshot.temperature_c);
+        self.power_max_w = max_option(self.power_max_w, snapshot.power_w);
+    }
+}
+
+fn max_option(current: Option<f32>, value: Option<f32>) -> Option<f32> {
+    match (current, value) {
+        (Some(current), Some(value)) => Some(current.max(value)),
+        (current, value) => current.or(value),
+    }
+}
+
+/// Per-GPU statistics over a whole run, reported in the exit summary.
+#[derive(Debug, Default)]
+pub struct Statistics {
+    gpus: BTreeMap<u32, GpuStats>,
+}
+
+impl Statistics {
+    pub fn record(&mut self, snapshot: &GpuSnapshot) {
+        self.gpus
+            .entry(snapshot.gpu.index)
+            .or_insert_with(|| GpuStats::new(snapshot.gpu.clone()))
+            .record(snapshot);
+    }
+
+    pub fn gpus(&self) -> impl Iterator<Item = &GpuStats> {
+        self.gpus.values()
+    }
+
+    /// The summary table, with the utilization to `precision` decimals as in the text lines.
+    pub fn format_summary(&self, precision: usize) -> Vec<String> {
+        let columns = vec![
+            Column::new("GPU"),
+            Column::new("Name"),
+            Column::new("Samples").align(Align::Right),
+            Column::new("Min util").align(Align::Right),
+            Column::new("Avg util").align(Align::Right),
+            Column::new("Max util").align(Align::Right),
+            Column::new("Peak memory").align(Align::Right),
+            Column::new("Max temp").align(Align::Right),
+            Column::new("Max power").align(Align::Right),
+        ];
+        let mut table = Table::new(columns).indent(2);
+
+        for stats in self.gpus() {
+            table.row(vec![
+                stats.gpu.index.to_string(),
+                stats.gpu.name.clone(),
+                stats.samples.to_string(),
+                format!("{:.*}%", precision, stats.utilization_min),
+                format!("{:.*}%", precision, stats.utilization_avg()),
+                format!("{:.*}%", precision, stats.utilization_max),
+                stats.memory_peak_mib.map_or("-".to_string(), |memory| format!("{} MiB", memory)),
+                stats.temperature_max_c.map_or("-".to_string(), |temperature| format!("{}°C", temperature)),
+                stats.power_max_w.map_or("-".to_string(), |power| format!("{} W", power)),
+            ]);
+        }
+
+        let mut lines = vec!["Summary:".to_string()];
+        lines.extend(table.render());
+        lines
+    }
+}
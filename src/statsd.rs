@@ -0,0 +1,107 @@
+//! `--format statsd`: every metric of a sample as a StatsD gauge,
+//! `gpuatop.gpu0.utilization:45|g`, ready to be piped to `nc -u localhost 8125`.
+//!
+//! With tags (`--statsd-tags`, `--label` or `--machine-hostname`) the lines carry them in the
+//! DogStatsD form, `|#key:value,...`, along with the GPU name; plain StatsD servers do not
+//! understand tags, so none are written otherwise.
+
+use crate::metadata::Labels;
+use crate::output::{aperture_fields, OutputContext};
+use crate::GpuSnapshot;
+
+pub const DEFAULT_PREFIX: &str = "gpuatop";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsdOptions {
+    pub prefix: String,
+    pub tags: Labels,
+}
+
+impl Default for StatsdOptions {
+    fn default() -> Self {
+        StatsdOptions { prefix: DEFAULT_PREFIX.to_string(), tags: Labels::default() }
+    }
+}
+
+/// Lowercases `name` and turns what StatsD would misread into underscores: whitespace and the
+/// `:`, `|`, `@`, `#` and `,` delimiters.
+pub fn sanitize(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_whitespace() || matches!(c, ':' | '|' | '@' | '#' | ',') { '_' } else { c })
+        .collect()
+}
+
+/// `--statsd-prefix`: dot-separated parts, each sanitized.
+pub fn parse_prefix(value: &str) -> Result<String, String> {
+    let prefix = value.split('.').map(sanitize).collect::<Vec<_>>().join(".");
+
+    if prefix.split('.').any(str::is_empty) {
+        return Err(format!("Invalid --statsd-prefix value: {}", value));
+    }
+    Ok(prefix)
+}
+
+/// The sample's metrics under their JSON keys, formatted as their own type would print them,
+/// so `45.3` stays `45.3` rather than its nearest `f64`.
+fn metrics(snapshot: &GpuSnapshot) -> Vec<(&'static str, String)> {
+    let text = |value: Option<f32>| value.map(|value| value.to_string());
+    let percent = |value: Option<f64>| value.map(|value| value.to_string());
+    let split = snapshot.usage_split.as_ref();
+    let bandwidth = snapshot.memory_bandwidth.as_ref();
+
+    let mut metrics = vec![("utilization", snapshot.utilization.to_string())];
+    let optional = [
+        ("utilization_max", percent(snapshot.utilization_max)),
+        ("memory_used_mib", snapshot.memory_used_mib.map(|used| used.to_string())),
+        ("memory_total_mib", snapshot.memory_total_mib.map(|total| total.to_string())),
+        ("temperature_c", text(snapshot.temperature_c)),
+        ("power_w", text(snapshot.power_w)),
+        ("nvlink_tx_kib_per_s", snapshot.nvlink.map(|nvlink| format!("{:.1}", nvlink.tx_kib_per_s))),
+        ("nvlink_rx_kib_per_s", snapshot.nvlink.map(|nvlink| format!("{:.1}", nvlink.rx_kib_per_s))),
+        ("nvlink_replay_errors", snapshot.nvlink.map(|nvlink| nvlink.replay_errors.to_string())),
+        ("nvlink_crc_errors", snapshot.nvlink.map(|nvlink| nvlink.crc_errors.to_string())),
+        ("desktop_utilization", percent(split.map(|split| split.desktop))),
+        ("apps_utilization", percent(split.map(|split| split.apps))),
+        ("memory_bandwidth_utilization", percent(bandwidth.and_then(|bandwidth| bandwidth.utilization_pct))),
+        ("memory_read_gbps", text(bandwidth.and_then(|bandwidth| bandwidth.read_gbps))),
+        ("memory_write_gbps", text(bandwidth.and_then(|bandwidth| bandwidth.write_gbps))),
+    ];
+    metrics.extend(optional.into_iter().filter_map(|(name, value)| Some((name, value?))));
+    if let Some(aperture) = &snapshot.aperture {
+        metrics.extend(aperture_fields(aperture).into_iter().map(|(name, value)| (name, value.to_string())));
+    }
+    if let Some(activity) = snapshot.activity {
+        metrics.push(("idle_seconds", activity.idle_seconds().to_string()));
+    }
+
+    metrics
+}
+
+/// The DogStatsD tag suffix, `|#host:node1,rack:a1,gpu_name:nvidia_a100`, or nothing without
+/// tags. `--statsd-tags` win over `--label` keys of the same name.
+fn tag_suffix(snapshot: &GpuSnapshot, options: &StatsdOptions, context: &OutputContext) -> String {
+    let mut labels = context.labels.clone();
+    labels.extend(options.tags.clone());
+
+    let mut tags: Vec<String> = context.hostname.iter().map(|hostname| format!("host:{}", sanitize(hostname))).collect();
+    tags.extend(labels.sorted().into_iter().map(|(key, value)| format!("{}:{}", sanitize(key), sanitize(value))));
+    if tags.is_empty() {
+        return String::new();
+    }
+
+    tags.push(format!("gpu_name:{}", sanitize(&snapshot.gpu.name)));
+    format!("|#{}", tags.join(","))
+}
+
+/// One gauge line per metric the sample has, without a trailing newline.
+pub fn format_metrics(snapshot: &GpuSnapshot, options: &StatsdOptions, context: &OutputContext) -> String {
+    let tags = tag_suffix(snapshot, options, context);
+
+    metrics(snapshot)
+        .into_iter()
+        .map(|(name, value)| format!("{}.gpu{}.{}:{}|g{}", options.prefix, snapshot.gpu.index, name, value, tags))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,216 @@
+//! Syslog output sink (`--output syslog[:facility]`): one RFC 5424 message with structured
+//! data per sample, sent to the local `/dev/log` socket or over UDP to `--syslog-server`.
+
+use std::io;
+use std::net::UdpSocket;
+use std::os::unix::net::UnixDatagram;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metadata::Labels;
+use crate::output;
+use crate::GpuSnapshot;
+
+const LOCAL_SOCKET: &str = "/dev/log";
+const APP_NAME: &str = "gpuatop";
+
+/// SD-ID of the structured data element, under the documentation enterprise number of
+/// RFC 5612.
+const SD_ID: &str = "gpuatop@32473";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Facility(u8);
+
+impl Default for Facility {
+    fn default() -> Self {
+        Facility(3)
+    }
+}
+
+impl FromStr for Facility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = match s {
+            "kern" => 0,
+            "user" => 1,
+            "mail" => 2,
+            "daemon" => 3,
+            "auth" => 4,
+            "syslog" => 5,
+            "lpr" => 6,
+            "news" => 7,
+            "uucp" => 8,
+            "cron" => 9,
+            "authpriv" => 10,
+            "ftp" => 11,
+            _ => match s.strip_prefix("local").and_then(|n| n.parse::<u8>().ok()) {
+                Some(n) if n <= 7 => 16 + n,
+                _ => return Err(format!("Unknown syslog facility: {}", s)),
+            },
+        };
+        Ok(Facility(code))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning = 4,
+    Info = 6,
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with millisecond precision.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let millis = time.duration_since(UNIX_EPOCH).unwrap_or_default().subsec_millis();
+    format!("{}.{:03}Z", output::format_iso8601(time).trim_end_matches('Z'), millis)
+}
+
+/// Header fields are printable ASCII without spaces, or `-` when empty.
+fn header_field(value: &str, max_len: usize) -> String {
+    let value: String = value.chars().filter(|c| c.is_ascii_graphic()).take(max_len).collect();
+    if value.is_empty() {
+        "-".to_string()
+    } else {
+        value
+    }
+}
+
+fn escape_param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// PARAM-NAMEs are 1 to 32 printable ASCII characters except `=`, space, `]` and `"`.
+fn param_name(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"')).take(32).collect()
+}
+
+/// One RFC 5424 message.
+#[derive(Debug, Clone)]
+pub struct Message<'a> {
+    pub facility: Facility,
+    pub severity: Severity,
+    pub timestamp: SystemTime,
+    pub hostname: Option<&'a str>,
+    pub procid: u32,
+    pub msgid: &'a str,
+    pub params: Vec<(String, String)>,
+    pub text: &'a str,
+}
+
+impl Message<'_> {
+    pub fn format(&self) -> String {
+        let structured_data = if self.params.is_empty() {
+            "-".to_string()
+        } else {
+            let params: String = self.params.iter().map(|(name, value)| format!(" {}=\"{}\"", param_name(name), escape_param_value(value))).collect();
+            format!("[{}{}]", SD_ID, params)
+        };
+
+        format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            self.facility.0 as u32 * 8 + self.severity as u32,
+            format_timestamp(self.timestamp),
+            header_field(self.hostname.unwrap_or(""), 255),
+            APP_NAME,
+            self.procid,
+            header_field(self.msgid, 32),
+            structured_data,
+            self.text
+        )
+    }
+}
+
+/// Structured data parameters describing a sample.
+pub fn sample_params(snapshot: &GpuSnapshot, labels: &Labels) -> Vec<(String, String)> {
+    let mut params = vec![
+        ("gpu".to_string(), snapshot.gpu.index.to_string()),
+        ("name".to_string(), snapshot.gpu.name.clone()),
+        ("utilization".to_string(), snapshot.utilization.to_string()),
+    ];
+
+    let optional = [
+        ("memory_used_mib", snapshot.memory_used_mib.map(|value| value.to_string())),
+        ("memory_total_mib", snapshot.memory_total_mib.map(|value| value.to_string())),
+        ("temperature_c", snapshot.temperature_c.map(|value| value.to_string())),
+        ("power_w", snapshot.power_w.map(|value| value.to_string())),
+    ];
+    params.extend(optional.into_iter().filter_map(|(name, value)| Some((name.to_string(), value?))));
+    params.extend(labels.sorted().into_iter().map(|(key, value)| (key.to_string(), value.to_string())));
+
+    params
+}
+
+#[derive(Debug)]
+enum Transport {
+    Local(Option<UnixDatagram>),
+    Udp { socket: UdpSocket, server: String },
+}
+
+/// Sends messages without ever blocking: sockets are non-blocking, and a message that cannot
+/// be sent is dropped. The local socket is reopened on the next message after a failure, so
+/// a restarted syslog daemon is picked up again.
+#[derive(Debug)]
+pub struct SyslogSink {
+    transport: Transport,
+    facility: Facility,
+    hostname: Option<String>,
+}
+
+fn connect_local() -> Option<UnixDatagram> {
+    let socket = UnixDatagram::unbound().ok()?;
+    socket.connect(LOCAL_SOCKET).ok()?;
+    socket.set_nonblocking(true).ok()?;
+    Some(socket)
+}
+
+impl SyslogSink {
+    pub fn new(facility: Facility, server: Option<&str>, hostname: Option<String>) -> io::Result<Self> {
+        let transport = match server {
+            Some(server) => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.set_nonblocking(true)?;
+                Transport::Udp { socket, server: server.to_string() }
+            }
+            None => Transport::Local(connect_local()),
+        };
+
+        Ok(SyslogSink { transport, facility, hostname })
+    }
+
+    pub fn send(&mut self, severity: Severity, msgid: &str, params: Vec<(String, String)>, text: &str) {
+        let message = Message {
+            facility: self.facility,
+            severity,
+            timestamp: SystemTime::now(),
+            hostname: self.hostname.as_deref(),
+            procid: std::process::id(),
+            msgid,
+            params,
+            text,
+        }
+        .format();
+
+        match &mut self.transport {
+            Transport::Local(socket) => {
+                if socket.is_none() {
+                    *socket = connect_local();
+                }
+                if let Some(connected) = socket {
+                    if connected.send(message.as_bytes()).is_err() {
+                        *socket = None;
+                    }
+                }
+            }
+            Transport::Udp { socket, server } => {
+                let _ = socket.send_to(message.as_bytes(), server.as_str());
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! `--send-to-tcp host:port` streams NDJSON records to a TCP server, for collectors that must
+//! not miss samples. The stream starts with a [`HANDSHAKE`] line, then one record per line.
+//!
+//! A background thread owns the connection, so a slow or unreachable server never stalls
+//! sampling. While it is away, records wait in a queue of `--tcp-buffer-size` entries, the
+//! oldest dropped first once it is full, and the thread reconnects after 1, 2, 4, ... seconds,
+//! at most [`MAX_BACKOFF`] apart. The queue is sent, oldest first, on the new connection.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::live;
+
+/// The first line of every connection.
+pub const HANDSHAKE: &str = "GPUATOP/1.0";
+
+/// Records kept while the server is unreachable, unless `--tcp-buffer-size` says otherwise.
+pub const DEFAULT_BUFFER_SIZE: usize = 1000;
+
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a write may block on a server that stopped reading before it counts as gone.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn debug(enabled: bool, message: &str) {
+    if enabled {
+        live::eprintln(&format!("debug: {}", message));
+    }
+}
+
+/// `--send-to-tcp`: `host:port`, with an IPv6 host in brackets.
+pub fn parse_target(value: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid --send-to-tcp address: {} (expected host:port)", value);
+    let (host, port) = value.rsplit_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || port.parse::<u16>().map_or(true, |port| port == 0) {
+        return Err(invalid());
+    }
+    Ok(value.to_string())
+}
+
+/// `--tcp-buffer-size`: a positive number of records.
+pub fn parse_buffer_size(value: &str) -> Result<usize, String> {
+    value.parse().ok().filter(|size| *size > 0).ok_or_else(|| format!("Invalid --tcp-buffer-size value: {}", value))
+}
+
+/// The delay before each reconnection attempt: doubling from [`INITIAL_BACKOFF`] up to
+/// [`MAX_BACKOFF`], and back to the start once a connection succeeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    next: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff { next: INITIAL_BACKOFF }
+    }
+}
+
+impl Backoff {
+    /// The delay after another failed attempt.
+    pub fn fail(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(MAX_BACKOFF);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        *self = Backoff::default();
+    }
+}
+
+/// The records waiting to be sent, at most `capacity` of them.
+#[derive(Debug)]
+pub struct Outbox {
+    records: VecDeque<String>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl Outbox {
+    pub fn new(capacity: usize) -> Self {
+        Outbox { records: VecDeque::with_capacity(capacity.min(DEFAULT_BUFFER_SIZE)), capacity, dropped: 0 }
+    }
+
+    /// Queues a record, dropping the oldest one when the queue is full.
+    pub fn push(&mut self, record: String) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+            self.dropped += 1;
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn pop(&mut self) -> Option<String> {
+        self.records.pop_front()
+    }
+
+    /// Puts back a record that could not be sent, unless newer ones filled the queue meanwhile.
+    pub fn requeue(&mut self, record: String) {
+        if self.records.len() < self.capacity {
+            self.records.push_front(record);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Records dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    outbox: Outbox,
+    closing: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hands records to the background thread that sends them. Dropping it makes one last attempt
+/// to send what is queued, and stops the thread.
+#[derive(Debug)]
+pub struct TcpSender {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TcpSender {
+    pub fn new(target: &str, buffer_size: usize, debug: bool) -> io::Result<Self> {
+        // Resolving up front reports a misspelt host at startup; later attempts resolve again.
+        target.to_socket_addrs()?;
+
+        let shared = Arc::new(Shared { state: Mutex::new(State { outbox: Outbox::new(buffer_size), closing: false }), wake: Condvar::new() });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            let target = target.to_string();
+            thread::spawn(move || run(&target, &shared, debug))
+        };
+
+        Ok(TcpSender { shared, thread: Some(thread) })
+    }
+
+    pub fn send(&self, record: &str) {
+        self.shared.lock().outbox.push(record.to_string());
+        self.shared.wake.notify_one();
+    }
+}
+
+impl Drop for TcpSender {
+    fn drop(&mut self) {
+        self.shared.lock().closing = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn connect(target: &str) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", target));
+    for address in target.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(mut stream) => {
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                writeln!(stream, "{}", HANDSHAKE)?;
+                return Ok(stream);
+            }
+            Err(err) => last_error = err,
+        }
+    }
+    Err(last_error)
+}
+
+fn run(target: &str, shared: &Shared, debug_enabled: bool) {
+    let mut backoff = Backoff::default();
+    let mut stream: Option<TcpStream> = None;
+    let mut reported_drops = 0;
+
+    loop {
+        if stream.is_none() {
+            let (closing, idle) = {
+                let state = shared.lock();
+                (state.closing, state.outbox.is_empty())
+            };
+            if closing && idle {
+                return;
+            }
+            match connect(target) {
+                Ok(connected) => {
+                    debug(debug_enabled, &format!("connected to {}", target));
+                    backoff.reset();
+                    stream = Some(connected);
+                }
+                Err(_) if closing => return,
+                Err(err) => {
+                    let delay = backoff.fail();
+                    debug(debug_enabled, &format!("connecting to {} failed: {}, retrying in {}s", target, err, delay.as_secs()));
+                    let state = shared.lock();
+                    // Waking early only to stop: queued records wait for the next attempt.
+                    let _ = shared.wake.wait_timeout_while(state, delay, |state| !state.closing);
+                    continue;
+                }
+            }
+        }
+
+        let record = {
+            let mut state = shared.lock();
+            loop {
+                if state.outbox.dropped() > reported_drops {
+                    reported_drops = state.outbox.dropped();
+                    debug(debug_enabled, &format!("TCP buffer full, {} records dropped so far", reported_drops));
+                }
+                match state.outbox.pop() {
+                    Some(record) => break record,
+                    None if state.closing => return,
+                    None => state = shared.wake.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner()),
+                }
+            }
+        };
+
+        let Some(connected) = &mut stream else { continue };
+        if let Err(err) = writeln!(connected, "{}", record) {
+            debug(debug_enabled, &format!("connection to {} lost: {}", target, err));
+            shared.lock().outbox.requeue(record);
+            stream = None;
+        }
+    }
+}
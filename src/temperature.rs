@@ -0,0 +1,151 @@
+//! `--fields temps`: every temperature sensor of a GPU, not only the one `temperature_c`
+//! reports. The hotspot (junction) and memory run well above the edge sensor and throttle
+//! first, so a card at 70°C edge can be throttling at 110°C junction.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::csv;
+use crate::runner::CommandRunner;
+
+const SYSFS_DRM: &str = "/sys/class/drm";
+
+/// The extra query. `temperature.memory` is only known to recent nvidia-smi, and an unknown
+/// field fails the whole query, so it is not part of the main one.
+pub const NVIDIA_QUERY: &str = "--query-gpu=index,temperature.gpu,temperature.memory";
+
+/// A temperature sensor, named as the driver labels it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Sensor {
+    /// NVIDIA's core sensor (`temperature.gpu`), the one `temperature_c` reports.
+    Gpu,
+    /// amdgpu's edge sensor, the one `temperature_c` reports.
+    Edge,
+    /// amdgpu's hotspot: the hottest point of the die.
+    Junction,
+    /// Memory: GDDR or HBM.
+    Mem,
+}
+
+impl Sensor {
+    /// The key in `--alert-severity` and in alert event records.
+    pub fn alert_name(self) -> &'static str {
+        match self {
+            Sensor::Gpu => "temp.gpu",
+            Sensor::Edge => "temp.edge",
+            Sensor::Junction => "temp.junction",
+            Sensor::Mem => "temp.mem",
+        }
+    }
+}
+
+impl fmt::Display for Sensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Sensor::Gpu => "gpu",
+            Sensor::Edge => "edge",
+            Sensor::Junction => "junction",
+            Sensor::Mem => "mem",
+        })
+    }
+}
+
+/// The names amdgpu's `tempN_label` files use, plus `gpu`.
+impl FromStr for Sensor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "gpu" => Sensor::Gpu,
+            "edge" => Sensor::Edge,
+            "junction" => Sensor::Junction,
+            "mem" => Sensor::Mem,
+            _ => return Err(format!("Unknown temperature sensor: {} (expected gpu, edge, junction or mem)", s)),
+        })
+    }
+}
+
+/// The readings of one GPU in °C, in sensor order.
+pub type Temperatures = BTreeMap<Sensor, f32>;
+
+/// The hottest reading.
+pub fn hottest(temperatures: &Temperatures) -> Option<f32> {
+    temperatures.values().copied().reduce(f32::max)
+}
+
+/// Parses the output of [`NVIDIA_QUERY`]. A GPU without a memory sensor reports `[N/A]`,
+/// which is left out.
+pub fn parse_nvidia(output: &str) -> HashMap<u32, Temperatures> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields = csv::parse_line(line);
+            let index = csv::number(fields.first()?)?;
+            let temperatures: Temperatures = [(Sensor::Gpu, fields.get(1)), (Sensor::Mem, fields.get(2))]
+                .into_iter()
+                .filter_map(|(sensor, field)| Some((sensor, csv::number(field?)?)))
+                .collect();
+            (!temperatures.is_empty()).then_some((index, temperatures))
+        })
+        .collect()
+}
+
+pub fn query_nvidia(runner: &dyn CommandRunner) -> HashMap<u32, Temperatures> {
+    match runner.run("nvidia-smi", &[NVIDIA_QUERY, "--format=csv,noheader,nounits"]) {
+        Ok(output) if output.success => parse_nvidia(&output.stdout),
+        _ => HashMap::new(),
+    }
+}
+
+/// Reads the `tempN_input` sensors of a hwmon directory, named by their `tempN_label`.
+/// Sensors without a label or with a label gpuatop does not know are left out.
+pub fn read_hwmon(hwmon: &Path) -> Temperatures {
+    let Ok(entries) = fs::read_dir(hwmon) else {
+        return Temperatures::new();
+    };
+
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let channel = name.strip_prefix("temp")?.strip_suffix("_input")?;
+            let sensor = fs::read_to_string(hwmon.join(format!("temp{}_label", channel))).ok()?.trim().parse().ok()?;
+            let millidegrees: i64 = fs::read_to_string(hwmon.join(&name)).ok()?.trim().parse().ok()?;
+            Some((sensor, millidegrees as f32 / 1000.0))
+        })
+        .collect()
+}
+
+/// The sensors of an amdgpu `device` directory, from the first of its hwmon directories that
+/// has any.
+pub fn read_device(device: &Path) -> Temperatures {
+    fs::read_dir(device.join("hwmon"))
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| read_hwmon(&entry.path()))
+        .find(|temperatures| !temperatures.is_empty())
+        .unwrap_or_default()
+}
+
+/// The sensors of every amdgpu card, keyed by the card's position among the cards that
+/// report utilization, as the sysfs backend numbers them.
+pub fn query_amdgpu() -> HashMap<u32, Temperatures> {
+    let Ok(entries) = fs::read_dir(SYSFS_DRM) else {
+        return HashMap::new();
+    };
+
+    let mut cards: Vec<(u32, Temperatures)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let number = entry.file_name().to_str()?.strip_prefix("card")?.parse().ok()?;
+            let device = entry.path().join("device");
+            device.join("gpu_busy_percent").exists().then(|| (number, read_device(&device)))
+        })
+        .collect();
+    cards.sort_by_key(|(number, _)| *number);
+
+    cards.into_iter().enumerate().filter(|(_, (_, temperatures))| !temperatures.is_empty()).map(|(index, (_, temperatures))| (index as u32, temperatures)).collect()
+}
@@ -0,0 +1,300 @@
+//! `--format "<template>"`: the text line built from a user template such as
+//! `gpu{index}: {util:>3}% {mem_used}/{mem_total}MiB {temp|--}°C`.
+//!
+//! A placeholder is `{field|default:spec}`, where the default and the spec are optional. The
+//! default replaces `n/a` when the sample lacks the field; the spec is
+//! `[[fill]align][width][.precision]` with `<`, `>` or `^` as alignment, as in `format!`.
+//! `{{` and `}}` are literal braces.
+
+use crate::efficiency::Efficiency;
+use crate::GpuSnapshot;
+
+/// Shown for a field the sample does not have, unless the placeholder has a default.
+pub const MISSING: &str = "n/a";
+
+/// The fields a template can refer to: the sample's field names, plus short aliases.
+pub const FIELDS: [(&str, &str); 25] = [
+    ("index", "index"),
+    ("name", "name"),
+    ("bus_id", "bus_id"),
+    ("utilization", "util"),
+    ("utilization_max", "util_max"),
+    ("memory_used_mib", "mem_used"),
+    ("memory_total_mib", "mem_total"),
+    ("temperature_c", "temp"),
+    ("power_w", "power"),
+    ("clock_mhz", "clock"),
+    ("nvlink_tx_kib_per_s", "nvlink_tx"),
+    ("nvlink_rx_kib_per_s", "nvlink_rx"),
+    ("desktop_utilization", "desktop"),
+    ("apps_utilization", "apps"),
+    ("memory_bandwidth_utilization", "membw"),
+    ("memory_read_gbps", "mem_read"),
+    ("memory_write_gbps", "mem_write"),
+    ("memory_reserved_mib", "mem_reserved"),
+    ("bar1_used_mib", "bar1_used"),
+    ("bar1_total_mib", "bar1_total"),
+    ("vis_vram_used_mib", "vis_vram_used"),
+    ("vis_vram_total_mib", "vis_vram_total"),
+    ("idle_seconds", "idle"),
+    ("efficiency_tflops_per_w", "efficiency"),
+    ("source", "source"),
+];
+
+/// A template that failed to parse, with the character offset of the offending token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl TemplateError {
+    /// The error message, then the template with a caret under the offending token.
+    pub fn with_caret(&self, template: &str) -> String {
+        format!("{}\n  {}\n  {}^", self.message, template, " ".repeat(self.position))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// `[[fill]align][width][.precision]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spec {
+    pub fill: char,
+    pub align: Option<Align>,
+    pub width: usize,
+    pub precision: Option<usize>,
+}
+
+impl Default for Spec {
+    fn default() -> Self {
+        Spec { fill: ' ', align: None, width: 0, precision: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field { field: usize, default: Option<String>, spec: Spec },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+/// Whether `value` should be taken as a template rather than a format name.
+pub fn is_template(value: &str) -> bool {
+    value.contains(['{', '}'])
+}
+
+fn align(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+fn parse_number(chars: &[(usize, char)], index: &mut usize) -> Option<usize> {
+    let start = *index;
+    while chars.get(*index).is_some_and(|(_, c)| c.is_ascii_digit()) {
+        *index += 1;
+    }
+    let digits: String = chars[start..*index].iter().map(|(_, c)| c).collect();
+    digits.parse().ok()
+}
+
+/// Parses a spec; `chars` holds its characters with their offsets in the template.
+fn parse_spec(chars: &[(usize, char)]) -> Result<Spec, TemplateError> {
+    let mut spec = Spec::default();
+    let mut index = 0;
+
+    match (chars.first(), chars.get(1)) {
+        (Some(&(_, fill)), Some(&(_, c))) if align(c).is_some() => {
+            spec.fill = fill;
+            spec.align = align(c);
+            index = 2;
+        }
+        (Some(&(_, c)), _) if align(c).is_some() => {
+            spec.align = align(c);
+            index = 1;
+        }
+        _ => {}
+    }
+
+    if let Some(width) = parse_number(chars, &mut index) {
+        spec.width = width;
+    }
+    if chars.get(index).is_some_and(|(_, c)| *c == '.') {
+        index += 1;
+        let position = chars.get(index).map_or(chars[index - 1].0 + 1, |(position, _)| *position);
+        spec.precision = Some(parse_number(chars, &mut index).ok_or_else(|| TemplateError { message: "Expected a precision after '.'".to_string(), position })?);
+    }
+
+    match chars.get(index) {
+        None => Ok(spec),
+        Some(&(position, c)) => Err(TemplateError { message: format!("Unexpected '{}' in format spec", c), position }),
+    }
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let chars: Vec<(usize, char)> = template.chars().enumerate().collect();
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut index = 0;
+
+        while let Some(&(position, c)) = chars.get(index) {
+            match (c, chars.get(index + 1).map(|(_, next)| *next)) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    literal.push(c);
+                    index += 2;
+                }
+                ('}', _) => return Err(TemplateError { message: "Unmatched '}', write '}}' for a literal brace".to_string(), position }),
+                ('{', _) => {
+                    let end = chars[index..]
+                        .iter()
+                        .position(|(_, c)| *c == '}')
+                        .map(|offset| index + offset)
+                        .ok_or_else(|| TemplateError { message: "Unclosed '{', write '{{' for a literal brace".to_string(), position })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Self::parse_placeholder(&chars[index + 1..end], position)?);
+                    index = end + 1;
+                }
+                _ => {
+                    literal.push(c);
+                    index += 1;
+                }
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template { segments })
+    }
+
+    /// Parses the inside of `{...}`; `open` is the offset of the `{`.
+    fn parse_placeholder(chars: &[(usize, char)], open: usize) -> Result<Segment, TemplateError> {
+        let (body, spec) = match chars.iter().position(|(_, c)| *c == ':') {
+            Some(colon) => (&chars[..colon], parse_spec(&chars[colon + 1..])?),
+            None => (chars, Spec::default()),
+        };
+        let (name, default) = match body.iter().position(|(_, c)| *c == '|') {
+            Some(bar) => (&body[..bar], Some(body[bar + 1..].iter().map(|(_, c)| c).collect())),
+            None => (body, None),
+        };
+
+        let position = name.first().map_or(open + 1, |(position, _)| *position);
+        let name: String = name.iter().map(|(_, c)| c).collect();
+        if name.is_empty() {
+            return Err(TemplateError { message: "Expected a field name".to_string(), position });
+        }
+        let field = FIELDS
+            .iter()
+            .position(|(field, alias)| name == *field || name == *alias)
+            .ok_or_else(|| TemplateError { message: format!("Unknown field '{}'", name), position })?;
+
+        Ok(Segment::Field { field, default, spec })
+    }
+
+    pub fn render(&self, snapshot: &GpuSnapshot) -> String {
+        let mut line = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => line.push_str(text),
+                Segment::Field { field, default, spec } => match value(snapshot, FIELDS[*field].0) {
+                    Some(value) => line.push_str(&pad(&value.format(spec.precision), spec, value.is_number())),
+                    None => line.push_str(&pad(default.as_deref().unwrap_or(MISSING), spec, false)),
+                },
+            }
+        }
+
+        line
+    }
+}
+
+enum Value {
+    Int(u64),
+    Float(f32),
+    /// Utilization and other percentages, kept as `f64`.
+    Percent(f64),
+    Text(String),
+}
+
+impl Value {
+    fn is_number(&self) -> bool {
+        !matches!(self, Value::Text(_))
+    }
+
+    fn format(&self, precision: Option<usize>) -> String {
+        match (self, precision) {
+            (Value::Float(value), Some(precision)) => format!("{:.*}", precision, value),
+            (Value::Float(value), None) => value.to_string(),
+            (Value::Percent(value), Some(precision)) => format!("{:.*}", precision, value),
+            (Value::Percent(value), None) => value.to_string(),
+            (Value::Int(value), _) => value.to_string(),
+            // A precision truncates text, as in `format!`.
+            (Value::Text(text), Some(precision)) => text.chars().take(precision).collect(),
+            (Value::Text(text), None) => text.clone(),
+        }
+    }
+}
+
+fn value(snapshot: &GpuSnapshot, field: &str) -> Option<Value> {
+    let split = snapshot.usage_split.as_ref();
+    let bandwidth = snapshot.memory_bandwidth.as_ref();
+    let aperture = snapshot.aperture.as_ref();
+
+    match field {
+        "index" => Some(Value::Int(snapshot.gpu.index.into())),
+        "name" => Some(Value::Text(snapshot.gpu.name.clone())),
+        "bus_id" => snapshot.gpu.bus_id.clone().map(Value::Text),
+        "utilization" => Some(Value::Percent(snapshot.utilization)),
+        "utilization_max" => snapshot.utilization_max.map(Value::Percent),
+        "memory_used_mib" => snapshot.memory_used_mib.map(Value::Int),
+        "memory_total_mib" => snapshot.memory_total_mib.map(Value::Int),
+        "temperature_c" => snapshot.temperature_c.map(Value::Float),
+        "power_w" => snapshot.power_w.map(Value::Float),
+        "clock_mhz" => snapshot.clock_mhz.map(|clock| Value::Int(clock.into())),
+        "nvlink_tx_kib_per_s" => snapshot.nvlink.as_ref().map(|nvlink| Value::Float(nvlink.tx_kib_per_s as f32)),
+        "nvlink_rx_kib_per_s" => snapshot.nvlink.as_ref().map(|nvlink| Value::Float(nvlink.rx_kib_per_s as f32)),
+        "desktop_utilization" => split.map(|split| Value::Percent(split.desktop)),
+        "apps_utilization" => split.map(|split| Value::Percent(split.apps)),
+        "memory_bandwidth_utilization" => bandwidth.and_then(|bandwidth| bandwidth.utilization_pct).map(Value::Percent),
+        "memory_read_gbps" => bandwidth.and_then(|bandwidth| bandwidth.read_gbps).map(Value::Float),
+        "memory_write_gbps" => bandwidth.and_then(|bandwidth| bandwidth.write_gbps).map(Value::Float),
+        "memory_reserved_mib" => aperture.and_then(|aperture| aperture.reserved_mib).map(Value::Int),
+        "bar1_used_mib" => aperture.and_then(|aperture| aperture.bar1_used_mib).map(Value::Int),
+        "bar1_total_mib" => aperture.and_then(|aperture| aperture.bar1_total_mib).map(Value::Int),
+        "vis_vram_used_mib" => aperture.and_then(|aperture| aperture.vis_vram_used_mib).map(Value::Int),
+        "vis_vram_total_mib" => aperture.and_then(|aperture| aperture.vis_vram_total_mib).map(Value::Int),
+        "idle_seconds" => snapshot.activity.map(|activity| Value::Int(activity.idle_seconds())),
+        "efficiency_tflops_per_w" => snapshot.efficiency.and_then(Efficiency::value).map(|efficiency| Value::Float(efficiency as f32)),
+        "source" => snapshot.source.clone().map(Value::Text),
+        _ => None,
+    }
+}
+
+/// Pads `text` to the spec's width. Without an alignment numbers go right and text left,
+/// as in `format!`.
+fn pad(text: &str, spec: &Spec, number: bool) -> String {
+    let padding = spec.width.saturating_sub(text.chars().count());
+    let fill = |count: usize| spec.fill.to_string().repeat(count);
+
+    match spec.align.unwrap_or(if number { Align::Right } else { Align::Left }) {
+        Align::Left => format!("{}{}", text, fill(padding)),
+        Align::Right => format!("{}{}", fill(padding), text),
+        Align::Center => format!("{}{}{}", fill(padding / 2), text, fill(padding - padding / 2)),
+    }
+}
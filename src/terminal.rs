@@ -0,0 +1,103 @@
+//! At-a-glance status for a gpuatop left running in a background tab or tmux pane:
+//! `--bell-on <expression>` rings the terminal bell when the expression starts to hold, and
+//! `--set-title` shows every GPU's utilization and temperature in the window title.
+//!
+//! Both only ever write to a terminal, and their sequences are zero-width: they move no cursor
+//! and leave the lines around them intact.
+
+use std::time::{Duration, Instant};
+
+use crate::check::Expression;
+use crate::GpuSnapshot;
+
+pub const BELL: &str = "\x07";
+
+/// The least time between two bells, however often the expression trips.
+pub const BELL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// XTWINOPS: push the window title on the terminal's stack, and pop it back at exit.
+const SAVE_TITLE: &str = "\x1b[22;0t";
+const RESTORE_TITLE: &str = "\x1b[23;0t";
+
+/// Rings when `--bell-on` starts to hold on any GPU, at most every [`BELL_INTERVAL`].
+#[derive(Debug, Clone)]
+pub struct Bell {
+    expression: Expression,
+    holding: bool,
+    rung: Option<Instant>,
+}
+
+impl Bell {
+    pub fn new(expression: Expression) -> Self {
+        Bell { expression, holding: false, rung: None }
+    }
+
+    /// Whether to ring after the tick's samples: the expression tripped, and the last bell is
+    /// long enough ago. A condition that keeps holding rings once.
+    pub fn update(&mut self, snapshots: &[GpuSnapshot], now: Instant) -> bool {
+        let holding = snapshots.iter().any(|snapshot| self.expression.matches(snapshot));
+        let tripped = holding && !self.holding;
+        self.holding = holding;
+
+        let quiet = self.rung.is_none_or(|rung| now.saturating_duration_since(rung) >= BELL_INTERVAL);
+        if tripped && quiet {
+            self.rung = Some(now);
+        }
+        tripped && quiet
+    }
+}
+
+/// The title for a tick: `gpu0 42% 61°C | gpu1 3% 40°C`.
+pub fn format_title(snapshots: &[GpuSnapshot]) -> String {
+    snapshots
+        .iter()
+        .map(|snapshot| {
+            let temperature = snapshot.temperature_c.map(|temperature| format!(" {:.0}°C", temperature)).unwrap_or_default();
+            format!("gpu{} {:.0}%{}", snapshot.gpu.index, snapshot.utilization, temperature)
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Wraps a sequence for tmux to pass through to the terminal outside it: a DCS with every
+/// escape doubled. tmux 3.3 and later need `set -g allow-passthrough on` for it.
+pub fn tmux_passthrough(sequence: &str) -> String {
+    format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+}
+
+/// Writes the window title of the terminal gpuatop runs in, saving the previous one first and
+/// giving it back on [`TitleSetter::restore`].
+#[derive(Debug, Clone, Copy)]
+pub struct TitleSetter {
+    /// Inside tmux, from `$TMUX`: the sequences go to the outer terminal.
+    tmux: bool,
+}
+
+impl TitleSetter {
+    pub fn new(tmux: bool) -> Self {
+        TitleSetter { tmux }
+    }
+
+    fn wrap(&self, sequence: &str) -> String {
+        if self.tmux {
+            tmux_passthrough(sequence)
+        } else {
+            sequence.to_string()
+        }
+    }
+
+    pub fn save(&self) -> String {
+        self.wrap(SAVE_TITLE)
+    }
+
+    /// OSC 0, which sets the window and icon title; control characters, which would end the
+    /// sequence early, are left out.
+    pub fn title(&self, title: &str) -> String {
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+        self.wrap(&format!("\x1b]0;{}\x07", title))
+    }
+
+    pub fn restore(&self) -> String {
+        self.wrap(RESTORE_TITLE)
+    }
+}
@@ -0,0 +1,299 @@
+use crate::display::table::{Column, Table};
+use crate::json::Value;
+use crate::runner::CommandRunner;
+use crate::{GpuInfo, GpuType};
+
+/// How two GPUs are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkType {
+    /// The diagonal: a GPU and itself.
+    Same,
+    /// NVLink, bonded over this many links (`NV4` in `nvidia-smi topo -m`).
+    NvLink(u32),
+    /// AMD's GPU-to-GPU interconnect.
+    Xgmi,
+    /// Through PCIe switches or the host bridge of one CPU (`PIX`, `PXB`, `PHB`).
+    Pcie,
+    /// Across the CPU interconnect or NUMA nodes, through system memory (`NODE`, `SYS`).
+    SysMem,
+    NotConnected,
+}
+
+impl LinkType {
+    fn from_nvidia_code(code: &str) -> Self {
+        match code {
+            "X" => LinkType::Same,
+            "PIX" | "PXB" | "PHB" => LinkType::Pcie,
+            "NODE" | "SYS" => LinkType::SysMem,
+            _ => match code.strip_prefix("NV").and_then(|links| links.parse().ok()) {
+                Some(links) => LinkType::NvLink(links),
+                None => LinkType::NotConnected,
+            },
+        }
+    }
+
+    fn from_rocm_smi(value: &str) -> Self {
+        match value.to_uppercase().as_str() {
+            "0" => LinkType::Same,
+            "XGMI" => LinkType::Xgmi,
+            "PCIE" => LinkType::Pcie,
+            _ => LinkType::NotConnected,
+        }
+    }
+
+    /// Short form for the table.
+    pub fn code(&self) -> String {
+        match self {
+            LinkType::Same => "X".to_string(),
+            LinkType::NvLink(links) => format!("NV{}", links),
+            LinkType::Xgmi => "XGMI".to_string(),
+            LinkType::Pcie => "PCIe".to_string(),
+            LinkType::SysMem => "SYS".to_string(),
+            LinkType::NotConnected => "-".to_string(),
+        }
+    }
+
+    fn to_value(self) -> Value {
+        let kind = match self {
+            LinkType::Same => "self",
+            LinkType::NvLink(_) => "nvlink",
+            LinkType::Xgmi => "xgmi",
+            LinkType::Pcie => "pcie",
+            LinkType::SysMem => "sysmem",
+            LinkType::NotConnected => "none",
+        };
+
+        let mut members = vec![("type".to_string(), Value::String(kind.to_string()))];
+        if let LinkType::NvLink(links) = self {
+            members.push(("links".to_string(), Value::Number(links.into())));
+        }
+        Value::Object(members)
+    }
+}
+
+const LEGEND: [(&str, &str); 6] = [
+    ("X", "Self"),
+    ("NV#", "NVLink, bonded over # links"),
+    ("XGMI", "AMD xGMI"),
+    ("PCIe", "PCIe switches or host bridge"),
+    ("SYS", "Across NUMA nodes or the CPU interconnect"),
+    ("-", "Not connected"),
+];
+
+/// Interconnect matrix between the GPUs of the machine.
+#[derive(Debug, Clone)]
+pub struct TopologyMatrix {
+    pub gpus: Vec<GpuInfo>,
+    /// `links[row][column]` connects `gpus[row]` and `gpus[column]`.
+    pub links: Vec<Vec<LinkType>>,
+    pub cpu_affinity: Vec<Option<String>>,
+    pub numa_affinity: Vec<Option<String>>,
+}
+
+/// Removes ANSI escape sequences; newer drivers underline the header row.
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+
+    stripped
+}
+
+/// `GPU3` → 3.
+fn gpu_index(name: &str) -> Option<u32> {
+    name.strip_prefix("GPU")?.parse().ok()
+}
+
+fn gpu(index: u32) -> GpuInfo {
+    GpuInfo { index, name: format!("GPU{}", index), bus_id: None, render_offload: None }
+}
+
+/// Parses `nvidia-smi topo -m`. NICs in the matrix are left out.
+pub fn parse_topology(output: &str) -> Result<TopologyMatrix, String> {
+    let lines: Vec<String> = output.lines().map(strip_ansi).collect();
+    let mut lines = lines.iter().map(String::as_str).skip_while(|line| line.trim().is_empty());
+
+    let header = lines.next().ok_or("Empty topology output")?;
+    let devices: Vec<&str> = header
+        .split_whitespace()
+        .take_while(|column| !column.starts_with("CPU") && !column.starts_with("NUMA"))
+        .collect();
+    let gpu_columns: Vec<usize> = (0..devices.len()).filter(|&column| gpu_index(devices[column]).is_some()).collect();
+
+    if gpu_columns.is_empty() {
+        return Err(format!("Unexpected topology header: {}", header.trim()));
+    }
+
+    let mut matrix = TopologyMatrix { gpus: Vec::new(), links: Vec::new(), cpu_affinity: Vec::new(), numa_affinity: Vec::new() };
+
+    for line in lines {
+        if line.trim().is_empty() || line.trim_start().starts_with("Legend") {
+            break;
+        }
+
+        let mut columns = line.split_whitespace();
+        let Some(index) = columns.next().and_then(gpu_index) else {
+            continue;
+        };
+
+        let links: Vec<&str> = columns.by_ref().take(devices.len()).collect();
+        if links.len() != devices.len() {
+            return Err(format!("Topology row for GPU{} has {} columns, expected {}", index, links.len(), devices.len()));
+        }
+
+        let affinity: Vec<&str> = columns.collect();
+        matrix.gpus.push(gpu(index));
+        matrix.links.push(gpu_columns.iter().map(|&column| LinkType::from_nvidia_code(links[column])).collect());
+        matrix.cpu_affinity.push(affinity.first().map(|value| value.to_string()));
+        matrix.numa_affinity.push(affinity.get(1).map(|value| value.to_string()));
+    }
+
+    if matrix.gpus.len() != gpu_columns.len() {
+        return Err(format!("Topology has {} GPU rows, expected {}", matrix.gpus.len(), gpu_columns.len()));
+    }
+
+    Ok(matrix)
+}
+
+/// Parses `rocm-smi --showtopo`: the "Link Type between two GPUs" section, plus the NUMA
+/// affinity of each GPU.
+pub fn parse_rocm_topology(output: &str) -> Result<TopologyMatrix, String> {
+    let mut lines = output.lines().skip_while(|line| !line.contains("Link Type between two GPUs")).skip(1);
+
+    let header = lines.next().ok_or("No link type section in rocm-smi output")?;
+    let indices: Vec<u32> = header.split_whitespace().map(gpu_index).collect::<Option<_>>().ok_or_else(|| format!("Unexpected topology header: {}", header.trim()))?;
+    if indices.is_empty() {
+        return Err(format!("Unexpected topology header: {}", header.trim()));
+    }
+
+    let mut matrix = TopologyMatrix { gpus: Vec::new(), links: Vec::new(), cpu_affinity: Vec::new(), numa_affinity: Vec::new() };
+
+    for line in lines.take_while(|line| !line.trim().is_empty() && !line.starts_with('=')) {
+        let mut columns = line.split_whitespace();
+        let Some(index) = columns.next().and_then(gpu_index) else {
+            continue;
+        };
+
+        let links: Vec<LinkType> = columns.map(LinkType::from_rocm_smi).collect();
+        if links.len() != indices.len() {
+            return Err(format!("Topology row for GPU{} has {} columns, expected {}", index, links.len(), indices.len()));
+        }
+
+        matrix.gpus.push(gpu(index));
+        matrix.links.push(links);
+        matrix.cpu_affinity.push(None);
+        matrix.numa_affinity.push(None);
+    }
+
+    if matrix.gpus.len() != indices.len() {
+        return Err(format!("Topology has {} GPU rows, expected {}", matrix.gpus.len(), indices.len()));
+    }
+
+    // GPU[0]		: (Topology) Numa Affinity: 0
+    for line in output.lines() {
+        let Some((device, value)) = line.split_once("Numa Affinity:") else {
+            continue;
+        };
+        let index = device.trim().strip_prefix("GPU[").and_then(|rest| rest.split(']').next()).and_then(|index| index.parse::<u32>().ok());
+        if let Some(row) = matrix.gpus.iter().position(|gpu| Some(gpu.index) == index) {
+            matrix.numa_affinity[row] = Some(value.trim().to_string());
+        }
+    }
+
+    Ok(matrix)
+}
+
+/// Runs the vendor's topology query. GPU names are taken from `gpus` where the index matches.
+pub fn query_topology(runner: &dyn CommandRunner, gpu_type: &GpuType, gpus: &[GpuInfo]) -> Result<TopologyMatrix, String> {
+    let (program, args): (_, &[&str]) = match gpu_type {
+        GpuType::Nvidia => ("nvidia-smi", &["topo", "-m"]),
+        GpuType::Amd => ("rocm-smi", &["--showtopo"]),
+        _ => return Err("Topology is only available for NVIDIA (nvidia-smi) and AMD (rocm-smi) GPUs".to_string()),
+    };
+
+    let output = runner.run(program, args).map_err(|err| format!("{}: {}", program, err))?;
+    if !output.success {
+        let message = if output.stderr.trim().is_empty() { &output.stdout } else { &output.stderr };
+        return Err(format!("{} failed: {}", program, message.trim()));
+    }
+
+    let mut matrix = match gpu_type {
+        GpuType::Amd => parse_rocm_topology(&output.stdout)?,
+        _ => parse_topology(&output.stdout)?,
+    };
+    for gpu in &mut matrix.gpus {
+        if let Some(known) = gpus.iter().find(|known| known.index == gpu.index) {
+            *gpu = known.clone();
+        }
+    }
+    Ok(matrix)
+}
+
+pub fn format_topology(matrix: &TopologyMatrix) -> String {
+    let labels: Vec<String> = matrix.gpus.iter().map(|gpu| format!("GPU{}", gpu.index)).collect();
+    let mut columns = vec![Column::new("")];
+    columns.extend(labels.iter().map(|label| Column::new(label)));
+    columns.push(Column::new("CPU Affinity"));
+    columns.push(Column::new("NUMA Affinity"));
+
+    let mut matrix_table = Table::new(columns);
+    for (i, label) in labels.iter().enumerate() {
+        let mut row = vec![label.clone()];
+        row.extend(matrix.links[i].iter().map(LinkType::code));
+        row.push(matrix.cpu_affinity[i].clone().unwrap_or_default());
+        row.push(matrix.numa_affinity[i].clone().unwrap_or_default());
+        matrix_table.row(row);
+    }
+
+    let mut table = matrix_table.render();
+
+    table.push(String::new());
+    for (label, gpu) in labels.iter().zip(&matrix.gpus) {
+        if &gpu.name != label {
+            table.push(format!("{}: {}", label, gpu.name));
+        }
+    }
+    table.push("Legend:".to_string());
+    let code_width = LEGEND.iter().map(|(code, _)| code.len()).max().unwrap_or(0);
+    for (code, description) in LEGEND {
+        table.push(format!("  {:<width$} = {}", code, description, width = code_width));
+    }
+
+    table.join("\n")
+}
+
+fn optional_string(value: &Option<String>) -> Value {
+    value.clone().map_or(Value::Null, Value::String)
+}
+
+/// `{"gpus": [{"index", "name", "bus_id", "cpu_affinity", "numa_affinity"}], "links": [[{"type", "links"?}]]}`.
+pub fn to_json(matrix: &TopologyMatrix) -> String {
+    let gpus = matrix
+        .gpus
+        .iter()
+        .enumerate()
+        .map(|(i, gpu)| {
+            Value::Object(vec![
+                ("index".to_string(), Value::Number(gpu.index.into())),
+                ("name".to_string(), Value::String(gpu.name.clone())),
+                ("bus_id".to_string(), optional_string(&gpu.bus_id)),
+                ("cpu_affinity".to_string(), optional_string(&matrix.cpu_affinity[i])),
+                ("numa_affinity".to_string(), optional_string(&matrix.numa_affinity[i])),
+            ])
+        })
+        .collect();
+    let links = matrix.links.iter().map(|row| Value::Array(row.iter().map(|link| link.to_value()).collect())).collect();
+
+    Value::Object(vec![("gpus".to_string(), Value::Array(gpus)), ("links".to_string(), Value::Array(links))]).to_json()
+}
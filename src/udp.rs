@@ -0,0 +1,192 @@
+//! `--send-to host:port` streams each tick's NDJSON records to a remote receiver over UDP, and
+//! `--receive <port>` prints what arrives, for monitoring a few machines without a metrics stack.
+//!
+//! A tick's records travel as one message, the records joined by newlines. A message of up to
+//! [`FRAGMENT_SIZE`] bytes is sent as it is, so `nc -ul <port>` reads it too; a longer one is
+//! split into fragments that each start with a `#gpuatop <message> <part>/<parts>` line, and
+//! put back together by the receiver. Staying under the usual MTU keeps IP from fragmenting
+//! datagrams itself, which loses the whole datagram when any piece is dropped. A tick with more
+//! than [`MAX_MESSAGE`] bytes of records is sent as several messages.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// The largest payload of one datagram, leaving room for the IP and UDP headers within a
+/// 1500-byte Ethernet MTU.
+pub const FRAGMENT_SIZE: usize = 1400;
+
+/// The largest UDP payload over IPv4, and so the largest datagram the receiver reads.
+pub const MAX_DATAGRAM: usize = 65507;
+
+/// The most fragments a message is split into, and so the most the receiver accepts.
+pub const MAX_PARTS: usize = MAX_DATAGRAM.div_ceil(FRAGMENT_SIZE);
+
+/// The longest message: [`MAX_PARTS`] full fragments.
+pub const MAX_MESSAGE: usize = MAX_PARTS * FRAGMENT_SIZE;
+
+/// How many incomplete messages of one sender are kept; a new one drops the oldest.
+pub const MAX_PENDING: usize = 16;
+
+/// How long the fragments of a message are kept waiting for the missing ones.
+pub const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+const FRAGMENT_MARKER: &str = "#gpuatop";
+
+/// `--send-to`: `host:port`, with an IPv6 host in brackets.
+pub fn parse_target(value: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid --send-to address: {} (expected host:port)", value);
+    let (host, port) = value.rsplit_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || port.parse::<u16>().map_or(true, |port| port == 0) {
+        return Err(invalid());
+    }
+    Ok(value.to_string())
+}
+
+/// `--receive`: the port to listen on.
+pub fn parse_port(value: &str) -> Result<u16, String> {
+    value.parse().ok().filter(|port| *port > 0).ok_or_else(|| format!("Invalid --receive port: {}", value))
+}
+
+/// The datagrams that carry message number `message`.
+pub fn datagrams(message: u32, payload: &[u8]) -> Vec<Vec<u8>> {
+    if payload.len() <= FRAGMENT_SIZE {
+        return vec![payload.to_vec()];
+    }
+
+    let chunks: Vec<&[u8]> = payload.chunks(FRAGMENT_SIZE).collect();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(part, chunk)| {
+            let mut datagram = format!("{} {} {}/{}\n", FRAGMENT_MARKER, message, part + 1, chunks.len()).into_bytes();
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect()
+}
+
+/// The message, part and part count of a fragment, and its payload.
+fn parse_fragment(datagram: &[u8]) -> Option<(u32, usize, usize, &[u8])> {
+    let newline = datagram.iter().position(|byte| *byte == b'\n')?;
+    let header = std::str::from_utf8(&datagram[..newline]).ok()?;
+    let mut fields = header.split(' ');
+    if fields.next()? != FRAGMENT_MARKER {
+        return None;
+    }
+    let message = fields.next()?.parse().ok()?;
+    let (part, parts) = fields.next()?.split_once('/')?;
+    let (part, parts): (usize, usize) = (part.parse().ok()?, parts.parse().ok()?);
+    (parts <= MAX_PARTS && (1..=parts).contains(&part)).then_some((message, part, parts, &datagram[newline + 1..]))
+}
+
+/// Sends without ever blocking the monitor: a datagram that cannot be sent is dropped.
+#[derive(Debug)]
+pub struct UdpSender {
+    socket: UdpSocket,
+    target: SocketAddr,
+    next_message: u32,
+}
+
+impl UdpSender {
+    pub fn new(target: &str) -> io::Result<Self> {
+        let target = target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", target)))?;
+        let socket = UdpSocket::bind(if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+        socket.set_nonblocking(true)?;
+
+        Ok(UdpSender { socket, target, next_message: 0 })
+    }
+
+    /// Sends one tick's records, as one message unless they are longer than [`MAX_MESSAGE`].
+    pub fn send(&mut self, records: &[String]) {
+        for message in messages(records) {
+            for datagram in datagrams(self.next_message, message.as_bytes()) {
+                let _ = self.socket.send_to(&datagram, self.target);
+            }
+            self.next_message = self.next_message.wrapping_add(1);
+        }
+    }
+}
+
+/// `records` joined by newlines into messages of at most [`MAX_MESSAGE`] bytes, without
+/// splitting a record.
+pub fn messages(records: &[String]) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+    for record in records {
+        match messages.last_mut() {
+            Some(message) if message.len() + 1 + record.len() <= MAX_MESSAGE => {
+                message.push('\n');
+                message.push_str(record);
+            }
+            _ => messages.push(record.clone()),
+        }
+    }
+    messages
+}
+
+#[derive(Debug)]
+struct Partial {
+    parts: Vec<Option<Vec<u8>>>,
+    started: Instant,
+}
+
+/// Puts fragmented messages back together, per sender. A message missing a fragment for
+/// [`REASSEMBLY_TIMEOUT`] is dropped, and so is a sender's oldest one past [`MAX_PENDING`].
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    partial: HashMap<(SocketAddr, u32), Partial>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Reassembler::default()
+    }
+
+    /// The message `datagram` completes, if any: the datagram itself when it is not a
+    /// fragment.
+    pub fn push(&mut self, source: SocketAddr, datagram: &[u8], now: Instant) -> Option<Vec<u8>> {
+        self.partial.retain(|_, partial| now.saturating_duration_since(partial.started) < REASSEMBLY_TIMEOUT);
+
+        let Some((message, part, parts, payload)) = parse_fragment(datagram) else {
+            return Some(datagram.to_vec());
+        };
+        if !self.partial.contains_key(&(source, message)) {
+            let pending: Vec<_> = self.partial.iter().filter(|((from, _), _)| *from == source).map(|(key, partial)| (partial.started, *key)).collect();
+            if pending.len() >= MAX_PENDING {
+                let oldest = pending.iter().min().map(|(_, key)| *key)?;
+                self.partial.remove(&oldest);
+            }
+        }
+        let partial = self.partial.entry((source, message)).or_insert_with(|| Partial { parts: vec![None; parts], started: now });
+        if partial.parts.len() != parts {
+            return None;
+        }
+        partial.parts[part - 1] = Some(payload.to_vec());
+        if partial.parts.iter().any(Option::is_none) {
+            return None;
+        }
+
+        let partial = self.partial.remove(&(source, message))?;
+        Some(partial.parts.into_iter().flatten().flatten().collect())
+    }
+}
+
+/// Listens on `port` and hands every record received to `on_record`, until reading fails.
+pub fn receive(port: u16, mut on_record: impl FnMut(&str)) -> io::Result<()> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    let mut reassembler = Reassembler::new();
+    let mut buffer = vec![0; MAX_DATAGRAM];
+
+    loop {
+        let (length, source) = socket.recv_from(&mut buffer)?;
+        if let Some(message) = reassembler.push(source, &buffer[..length], Instant::now()) {
+            for record in String::from_utf8_lossy(&message).lines().filter(|line| !line.trim().is_empty()) {
+                on_record(record);
+            }
+        }
+    }
+}
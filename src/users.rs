@@ -0,0 +1,173 @@
+//! `--by-user`: GPU memory and busy time summed per user, from the per-process metrics of the
+//! vendor tool, with each process's owner read from `/proc/<pid>/status`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::time::Duration;
+
+use crate::desktop::DesktopClassifier;
+use crate::display::table::{Column, Table};
+use crate::output::{json_string, OutputContext};
+use crate::process::GpuProcess;
+use crate::schema::SCHEMA_VERSION;
+
+const PASSWD_PATH: &str = "/etc/passwd";
+
+/// Name of the row for processes whose owner cannot be read, e.g. PIDs of another PID
+/// namespace.
+pub const UNKNOWN_USER: &str = "(unknown)";
+
+/// The real UID from the `Uid:` line of `/proc/<pid>/status`.
+pub fn parse_status_uid(status: &str) -> Option<u32> {
+    let uids = status.lines().find_map(|line| line.strip_prefix("Uid:"))?;
+    uids.split_whitespace().next()?.parse().ok()
+}
+
+/// UID → name from the contents of `/etc/passwd`.
+pub fn parse_passwd(contents: &str) -> HashMap<u32, String> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+/// Caches UID → name lookups. `/etc/passwd` is read again only for a UID not seen before; a
+/// UID it does not list is shown as the number.
+#[derive(Debug, Default)]
+pub struct UserNames {
+    names: HashMap<u32, String>,
+}
+
+impl UserNames {
+    pub fn name(&mut self, uid: u32) -> String {
+        if !self.names.contains_key(&uid) {
+            let passwd = fs::read_to_string(PASSWD_PATH).map(|contents| parse_passwd(&contents)).unwrap_or_default();
+            self.names.extend(passwd);
+            self.names.entry(uid).or_insert_with(|| uid.to_string());
+        }
+
+        self.names[&uid].clone()
+    }
+}
+
+pub fn read_uid(pid: u32) -> Option<u32> {
+    parse_status_uid(&fs::read_to_string(format!("/proc/{}/status", pid)).ok()?)
+}
+
+/// GPU usage of one user in a tick, over all GPUs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserUsage {
+    pub user: String,
+    pub processes: usize,
+    /// Summed busy % of the user's processes; a process busy on two GPUs counts twice. `None`
+    /// when the vendor tool reports no per-process utilization.
+    pub utilization: Option<f32>,
+    pub memory_used_mib: u64,
+}
+
+/// Sums the processes by owner, heaviest memory user first. With `exclude`, processes the
+/// desktop classifier recognizes (Xorg and the compositors) are left out.
+pub fn aggregate(processes: &[GpuProcess], mut owner: impl FnMut(u32) -> Option<String>, exclude: Option<&DesktopClassifier>) -> Vec<UserUsage> {
+    let mut users: BTreeMap<String, UserUsage> = BTreeMap::new();
+
+    for process in processes {
+        if exclude.is_some_and(|desktop| desktop.is_desktop(&process.name)) {
+            continue;
+        }
+
+        let user = owner(process.pid).unwrap_or_else(|| UNKNOWN_USER.to_string());
+        let usage = users.entry(user.clone()).or_insert(UserUsage { user, processes: 0, utilization: None, memory_used_mib: 0 });
+        usage.processes += 1;
+        if let Some(utilization) = process.utilization {
+            *usage.utilization.get_or_insert(0.0) += utilization;
+        }
+        usage.memory_used_mib += process.memory_used_mib.unwrap_or(0);
+    }
+
+    let mut users: Vec<UserUsage> = users.into_values().collect();
+    users.sort_by(|a, b| b.memory_used_mib.cmp(&a.memory_used_mib).then_with(|| a.user.cmp(&b.user)));
+    users
+}
+
+pub fn format_table(users: &[UserUsage]) -> Vec<String> {
+    let mut table = Table::new(["User", "Processes", "Busy", "Memory"].into_iter().map(Column::new).collect());
+    for usage in users {
+        table.row(vec![
+            usage.user.clone(),
+            usage.processes.to_string(),
+            usage.utilization.map_or("-".to_string(), |utilization| format!("{:.1}%", utilization)),
+            format!("{} MiB", usage.memory_used_mib),
+        ]);
+    }
+
+    table.render()
+}
+
+/// The tick's users as one record, `{"users": [{"user", "processes", "utilization"?,
+/// "memory_used_mib"}]}`.
+pub fn format_json(users: &[UserUsage], context: &OutputContext) -> String {
+    let entries: Vec<String> = users
+        .iter()
+        .map(|usage| {
+            let mut fields = vec![format!("\"user\":{}", json_string(&usage.user)), format!("\"processes\":{}", usage.processes)];
+            if let Some(utilization) = usage.utilization {
+                fields.push(format!("\"utilization\":{}", utilization));
+            }
+            fields.push(format!("\"memory_used_mib\":{}", usage.memory_used_mib));
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    let mut fields = vec![format!("\"schema_version\":{}", SCHEMA_VERSION)];
+    if let Some(hostname) = &context.hostname {
+        fields.push(format!("\"hostname\":{}", json_string(hostname)));
+    }
+    fields.push(format!("\"users\":[{}]", entries.join(",")));
+    if !context.labels.is_empty() {
+        fields.push(format!("\"labels\":{}", context.labels.to_json()));
+    }
+    if let Some(tick_seq) = context.tick_seq {
+        fields.push(format!("\"tick_seq\":{}", tick_seq));
+    }
+    if let Some(timestamp) = &context.timestamp {
+        fields.push(format!("\"ts\":{}", timestamp.to_json()));
+    }
+
+    format!("{{{}}}", fields.join(","))
+}
+
+/// GPU-hours per user over the run: the busy % of each tick times the tick's length, so a
+/// process keeping one GPU fully busy for an hour accounts for one GPU-hour.
+#[derive(Debug, Default)]
+pub struct GpuHours {
+    users: BTreeMap<String, f64>,
+}
+
+impl GpuHours {
+    pub fn record(&mut self, users: &[UserUsage], period: Duration) {
+        for usage in users {
+            let Some(utilization) = usage.utilization else { continue };
+            *self.users.entry(usage.user.clone()).or_default() += utilization as f64 / 100.0 * period.as_secs_f64() / 3600.0;
+        }
+    }
+
+    pub fn get(&self, user: &str) -> Option<f64> {
+        self.users.get(user).copied()
+    }
+
+    /// Exit summary lines, the heaviest user first.
+    pub fn format_summary(&self) -> Vec<String> {
+        let mut users: Vec<(&String, &f64)> = self.users.iter().collect();
+        users.sort_by(|a, b| b.1.total_cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+        let mut lines = vec!["GPU-hours by user:".to_string()];
+        lines.extend(users.into_iter().map(|(user, hours)| format!("  {}: {:.3}", user, hours)));
+        lines
+    }
+}
@@ -0,0 +1,144 @@
+use crate::pci::normalize_bus_id;
+use crate::runner::CommandRunner;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualizationMode {
+    None,
+    PassThrough,
+    /// Host running the NVIDIA vGPU manager; vGPUs are sampled under their parent GPU.
+    Host,
+    /// Guest that received a vGPU.
+    Guest,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualizationInfo {
+    pub mode: VirtualizationMode,
+    pub license_status: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VgpuSnapshot {
+    pub parent_bus_id: String,
+    pub id: String,
+    pub name: Option<String>,
+    pub utilization: Option<f32>,
+    pub fps: Option<f32>,
+}
+
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(" : ")?;
+    Some((key.trim(), value.trim()))
+}
+
+fn parse_number(value: &str) -> Option<f32> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// Parses the virtualization mode and license state from `nvidia-smi -q`. The license
+/// status is only reported inside guests running licensed vGPU software.
+pub fn parse_virtualization_info(output: &str) -> VirtualizationInfo {
+    let mut info = VirtualizationInfo { mode: VirtualizationMode::None, license_status: None };
+
+    for (key, value) in output.lines().filter_map(key_value) {
+        match key {
+            "Virtualization Mode" => {
+                info.mode = match value {
+                    "Host VGPU" => VirtualizationMode::Host,
+                    "VGPU" => VirtualizationMode::Guest,
+                    "Pass-Through" => VirtualizationMode::PassThrough,
+                    _ => VirtualizationMode::None,
+                }
+            }
+            "License Status" => info.license_status = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    info
+}
+
+pub fn query_virtualization_info(runner: &dyn CommandRunner) -> Option<VirtualizationInfo> {
+    let output = runner.run("nvidia-smi", &["-q"]).ok()?;
+
+    if !output.success {
+        return None;
+    }
+
+    Some(parse_virtualization_info(&output.stdout))
+}
+
+/// Parses `nvidia-smi vgpu -q` into one snapshot per active vGPU, keyed by parent GPU.
+pub fn parse_vgpu_query(output: &str) -> Vec<VgpuSnapshot> {
+    let mut vgpus: Vec<VgpuSnapshot> = Vec::new();
+    let mut parent_bus_id: Option<String> = None;
+    let mut section = "";
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if let Some((key, value)) = key_value(line) {
+            match (section, key) {
+                (_, "vGPU ID") => {
+                    if let Some(parent_bus_id) = &parent_bus_id {
+                        vgpus.push(VgpuSnapshot {
+                            parent_bus_id: normalize_bus_id(parent_bus_id),
+                            id: value.to_string(),
+                            name: None,
+                            utilization: None,
+                            fps: None,
+                        });
+                    }
+                    section = "";
+                }
+                (_, "vGPU Name") => {
+                    if let Some(vgpu) = vgpus.last_mut() {
+                        vgpu.name = Some(value.to_string());
+                    }
+                }
+                ("Utilization", "Gpu") => {
+                    if let Some(vgpu) = vgpus.last_mut() {
+                        vgpu.utilization = parse_number(value);
+                    }
+                }
+                ("FBC Stats", "Average FPS") => {
+                    if let Some(vgpu) = vgpus.last_mut() {
+                        vgpu.fps = parse_number(value);
+                    }
+                }
+                _ => {}
+            }
+        } else if let Some(bus_id) = trimmed.strip_prefix("GPU ").filter(|_| !line.starts_with(' ')) {
+            parent_bus_id = Some(bus_id.to_string());
+        } else if !trimmed.is_empty() {
+            section = trimmed;
+        }
+    }
+
+    vgpus
+}
+
+/// Samples all vGPUs on a vGPU host. Returns an empty list when the host tools do not
+/// support vGPU queries.
+pub fn query_vgpus(runner: &dyn CommandRunner) -> Vec<VgpuSnapshot> {
+    match runner.run("nvidia-smi", &["vgpu", "-q"]) {
+        Ok(output) if output.success => parse_vgpu_query(&output.stdout),
+        _ => Vec::new(),
+    }
+}
+
+pub fn format_vgpu(vgpu: &VgpuSnapshot) -> String {
+    let mut line = format!("  vGPU {}", vgpu.id);
+
+    if let Some(name) = &vgpu.name {
+        line.push_str(&format!(" ({})", name));
+    }
+    if let Some(utilization) = vgpu.utilization {
+        line.push_str(&format!(" Utilization (percent): {}", utilization));
+    }
+    if let Some(fps) = vgpu.fps {
+        line.push_str(&format!(", FPS: {}", fps));
+    }
+
+    line
+}
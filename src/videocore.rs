@@ -0,0 +1,189 @@
+//! The VideoCore GPU of a Raspberry Pi, read through `vcgencmd`, which Raspberry Pi OS ships
+//! with the firmware: `measure_clock v3d` for the 3D block's clock, `measure_temp` for the SoC
+//! temperature and `get_throttled` for the firmware's under-voltage and thermal flags.
+//!
+//! `vcgencmd` reports no load, so utilization comes from the busy times the v3d driver reports
+//! per client in fdinfo, as for [`crate::backend::FdinfoBackend`]. Kernels before 6.7 report
+//! none, and the GPU then reads as idle.
+
+use std::io;
+
+use crate::backend::{read_drm_clients, Backend, Cost, DrmBusyTracker, DrmClient, RawOutput};
+use crate::rate::Moment;
+use crate::runner::{retry_command, CommandRunner, Retry};
+use crate::{GpuInfo, GpuSnapshot, PollResult};
+
+pub const CLOCK_ARGS: [&str; 2] = ["measure_clock", "v3d"];
+pub const TEMPERATURE_ARGS: [&str; 1] = ["measure_temp"];
+pub const THROTTLED_ARGS: [&str; 1] = ["get_throttled"];
+
+/// The board, as the device tree names it (`Raspberry Pi 5 Model B Rev 1.0`) with its NUL
+/// terminator; `None` for other boards.
+#[doc(hidden)]
+pub fn raspberry_pi_model(model: &str) -> Option<String> {
+    let model = model.trim_end_matches('\0').trim();
+    model.starts_with("Raspberry Pi").then(|| model.to_string())
+}
+
+/// The flags of `vcgencmd get_throttled`. The low bits hold while the condition lasts, the
+/// high ones stay set from its first occurrence until the next boot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleStatus {
+    pub under_voltage: bool,
+    pub arm_frequency_capped: bool,
+    pub throttled: bool,
+    pub soft_temp_limit: bool,
+    pub under_voltage_occurred: bool,
+    pub arm_frequency_capped_occurred: bool,
+    pub throttled_occurred: bool,
+    pub soft_temp_limit_occurred: bool,
+}
+
+impl ThrottleStatus {
+    /// The conditions that hold right now, as the `throttle_reasons` of a sample.
+    pub fn reasons(&self) -> Vec<String> {
+        [
+            (self.under_voltage, "under_voltage"),
+            (self.arm_frequency_capped, "arm_frequency_capped"),
+            (self.throttled, "throttled"),
+            (self.soft_temp_limit, "soft_temp_limit"),
+        ]
+        .into_iter()
+        .filter(|(active, _)| *active)
+        .map(|(_, reason)| reason.to_string())
+        .collect()
+    }
+}
+
+/// The throttle bits, with or without the `throttled=` prefix: `throttled=0x50005`, `0x0`. A
+/// value that is not hexadecimal has no flags set.
+pub fn parse_vcgencmd_throttled(hex: &str) -> ThrottleStatus {
+    let hex = hex.trim();
+    let hex = hex.strip_prefix("throttled=").unwrap_or(hex);
+    let bits = u32::from_str_radix(hex.strip_prefix("0x").unwrap_or(hex), 16).unwrap_or(0);
+    let bit = |position: u32| bits & (1 << position) != 0;
+
+    ThrottleStatus {
+        under_voltage: bit(0),
+        arm_frequency_capped: bit(1),
+        throttled: bit(2),
+        soft_temp_limit: bit(3),
+        under_voltage_occurred: bit(16),
+        arm_frequency_capped_occurred: bit(17),
+        throttled_occurred: bit(18),
+        soft_temp_limit_occurred: bit(19),
+    }
+}
+
+/// The clock of `vcgencmd measure_clock v3d` (`frequency(46)=500000000`), in MHz.
+pub fn parse_vcgencmd_clock(output: &str) -> Option<u32> {
+    let (_, hertz) = output.trim().split_once('=')?;
+    Some((hertz.parse::<u64>().ok()? / 1_000_000) as u32)
+}
+
+/// The temperature of `vcgencmd measure_temp` (`temp=48.3'C`), in °C.
+pub fn parse_vcgencmd_temperature(output: &str) -> Option<f32> {
+    output.trim().strip_prefix("temp=")?.strip_suffix("'C")?.parse().ok()
+}
+
+/// Runs `vcgencmd` three times a tick for the clock, temperature and throttle flags, and
+/// takes the utilization from the v3d clients in fdinfo.
+pub struct VideoCoreBackend<'r> {
+    runner: &'r dyn CommandRunner,
+    busy: DrmBusyTracker,
+    retry: Retry,
+    last_output: Option<RawOutput>,
+}
+
+impl<'r> VideoCoreBackend<'r> {
+    /// Fails where `vcgencmd` is missing or cannot reach the firmware (`/dev/vcio`).
+    pub fn open(runner: &'r dyn CommandRunner) -> io::Result<Self> {
+        Self::open_with(runner, &read_drm_clients(), Moment::now())
+    }
+
+    /// [`VideoCoreBackend::open`] with the DRM clients read `at`.
+    pub fn open_with(runner: &'r dyn CommandRunner, clients: &[DrmClient], at: Moment) -> io::Result<Self> {
+        let output = runner.run("vcgencmd", &THROTTLED_ARGS)?;
+        if !output.success || !output.stdout.trim().starts_with("throttled=") {
+            return Err(io::Error::other(format!("vcgencmd get_throttled failed: {}", RawOutput::from(&output).text)));
+        }
+
+        let mut busy = DrmBusyTracker::default();
+        busy.update(clients, at);
+        Ok(VideoCoreBackend { runner, busy, retry: Retry::default(), last_output: None })
+    }
+
+    /// How often a failed `vcgencmd` run is attempted within one poll.
+    pub fn with_retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn vcgencmd(&mut self, args: &[&str]) -> Result<String, String> {
+        let output = retry_command(self.runner, "vcgencmd", args, self.retry.max_attempts, self.retry.delay).map_err(|err| err.to_string())?;
+        let raw = RawOutput::from(&output);
+        self.last_output = Some(match self.last_output.take() {
+            Some(last) => RawOutput { text: format!("{}\n{}", last.text, raw.text), code: raw.code },
+            None => raw,
+        });
+        if !output.success {
+            return Err(format!("vcgencmd {} failed: {}", args.join(" "), output.stderr.trim()));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Polls the GPU with the DRM clients read `at`.
+    pub fn poll_with(&mut self, gpus: &[GpuInfo], clients: &[DrmClient], at: Moment) -> Vec<PollResult> {
+        self.busy.update(clients, at);
+        self.last_output = None;
+
+        let readings = self.vcgencmd(&THROTTLED_ARGS).and_then(|throttled| {
+            let clock = self.vcgencmd(&CLOCK_ARGS)?;
+            let temperature = self.vcgencmd(&TEMPERATURE_ARGS)?;
+            Ok((parse_vcgencmd_throttled(&throttled), parse_vcgencmd_clock(&clock), parse_vcgencmd_temperature(&temperature)))
+        });
+
+        gpus.iter()
+            .map(|gpu| {
+                let (throttle, clock_mhz, temperature_c) = match &readings {
+                    Ok(readings) => *readings,
+                    Err(message) => return PollResult::TransientError { gpu: gpu.clone(), message: message.clone(), retries: 0 },
+                };
+                let Some(utilization) = self.busy.utilization(None) else {
+                    // The next reading, taken right away on a retry, has a rate again.
+                    return PollResult::TransientError { gpu: gpu.clone(), message: "No busy times across a suspend".to_string(), retries: 0 };
+                };
+
+                PollResult::Ok { snapshot: GpuSnapshot {
+                    temperature_c,
+                    engines: Some(self.busy.engines(None)),
+                    clock_mhz,
+                    throttle_reasons: Some(throttle.reasons()),
+                    ..GpuSnapshot::new(gpu.clone(), utilization)
+                }, retries: 0 }
+            })
+            .collect()
+    }
+}
+
+impl Backend for VideoCoreBackend<'_> {
+    fn name(&self) -> &'static str {
+        "vcgencmd (per tick)"
+    }
+
+    fn source(&self) -> &'static str {
+        "vcgencmd"
+    }
+
+    fn cost(&self) -> Cost {
+        Cost::SpawnPerTick
+    }
+
+    fn poll(&mut self, gpus: &[GpuInfo]) -> Vec<PollResult> {
+        self.poll_with(gpus, &read_drm_clients(), Moment::now())
+    }
+
+    fn last_output(&self) -> Option<RawOutput> {
+        self.last_output.clone()
+    }
+}
@@ -0,0 +1,82 @@
+//! Vulkan physical device enumeration, a GPU identification fallback for systems that have a
+//! Vulkan driver but no OpenCL runtime (common on ARM SoCs). The system's Vulkan loader
+//! (`libvulkan.so.1`) is opened at runtime through `ash`, so a system without one simply has no
+//! Vulkan devices.
+
+use ash::vk;
+
+use crate::{GpuInfo, GpuType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    Other,
+    Integrated,
+    Discrete,
+    Virtual,
+    Cpu,
+}
+
+impl DeviceType {
+    fn from_vk(device_type: vk::PhysicalDeviceType) -> Self {
+        match device_type {
+            vk::PhysicalDeviceType::INTEGRATED_GPU => DeviceType::Integrated,
+            vk::PhysicalDeviceType::DISCRETE_GPU => DeviceType::Discrete,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => DeviceType::Virtual,
+            vk::PhysicalDeviceType::CPU => DeviceType::Cpu,
+            _ => DeviceType::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VulkanDevice {
+    pub info: GpuInfo,
+    pub vendor_id: u32,
+    pub device_type: DeviceType,
+}
+
+/// Maps a PCI vendor ID as reported in `VkPhysicalDeviceProperties::vendorID` to a GPU type.
+/// Vendors without a supported top tool (ARM, Qualcomm, Imagination, ...) map to `None`.
+pub fn gpu_type_for_vendor_id(vendor_id: u32) -> Option<GpuType> {
+    u16::try_from(vendor_id).ok().and_then(GpuType::from_pci_vendor)
+}
+
+/// Enumerates Vulkan physical devices, skipping software renderers such as llvmpipe.
+pub fn enumerate_vulkan_devices() -> Result<Vec<VulkanDevice>, String> {
+    let entry = unsafe { ash::Entry::load() }.map_err(|err| format!("Failed to load the Vulkan loader: {}", err))?;
+    let instance = unsafe { entry.create_instance(&vk::InstanceCreateInfo::default(), None) }.map_err(|err| format!("vkCreateInstance failed: {}", err))?;
+
+    let devices = physical_devices(&instance);
+    unsafe { instance.destroy_instance(None) };
+    devices
+}
+
+fn physical_devices(instance: &ash::Instance) -> Result<Vec<VulkanDevice>, String> {
+    let handles = unsafe { instance.enumerate_physical_devices() }.map_err(|err| format!("vkEnumeratePhysicalDevices failed: {}", err))?;
+
+    let mut devices = Vec::new();
+    for handle in handles {
+        let properties = unsafe { instance.get_physical_device_properties(handle) };
+        let device_type = DeviceType::from_vk(properties.device_type);
+        if device_type == DeviceType::Cpu {
+            continue;
+        }
+
+        let name = properties.device_name_as_c_str().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        devices.push(VulkanDevice {
+            info: GpuInfo { index: devices.len() as u32, name, bus_id: None, render_offload: None },
+            vendor_id: properties.vendor_id,
+            device_type,
+        });
+    }
+
+    Ok(devices)
+}
+
+/// The type of the first Vulkan device from a vendor with a supported top tool, preferring
+/// discrete GPUs over integrated ones.
+pub fn identify_gpu_type() -> Option<GpuType> {
+    let mut devices = enumerate_vulkan_devices().ok()?;
+    devices.sort_by_key(|device| device.device_type != DeviceType::Discrete);
+    devices.iter().find_map(|device| gpu_type_for_vendor_id(device.vendor_id))
+}
@@ -0,0 +1,215 @@
+//! `gpuatop watch-pid`: one process's GPU memory and busy share every interval until it exits,
+//! then a summary of its time on the GPU. NVIDIA processes are read from `nvidia-smi pmon`,
+//! others from the DRM fdinfo of the process.
+//!
+//! The process is identified by its PID and start time, so a PID the kernel hands to a new
+//! process after the watched one exited is not mistaken for it.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::backend::{read_process_drm_clients, DrmBusyTracker, DrmClient};
+use crate::idle::format_duration;
+use crate::process::{self, GpuProcess, ProcessState};
+use crate::rate::Moment;
+use crate::runner::CommandRunner;
+use crate::{widen, GpuType};
+
+pub const CSV_HEADER: &str = "elapsed_s,pid,vram_mib,busy_percent";
+
+/// The watched process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub pid: u32,
+    pub name: String,
+    start_time: u64,
+}
+
+impl Target {
+    pub fn new(pid: u32) -> Result<Self, String> {
+        let start_time = process::start_time(pid).ok_or_else(|| format!("No process with PID {}", pid))?;
+        Ok(Target { pid, name: process::comm(pid).unwrap_or_default(), start_time })
+    }
+
+    /// The most recently started process named `name`.
+    pub fn newest_named(name: &str) -> Result<Self, String> {
+        let pid = newest_named_in(Path::new("/proc"), name).ok_or_else(|| format!("No process named {}", name))?;
+        Target::new(pid)
+    }
+
+    /// Whether the process is still running: not a zombie, and its PID not reused.
+    pub fn is_running(&self) -> bool {
+        process::start_time(self.pid) == Some(self.start_time) && process::process_state(self.pid) == ProcessState::Running
+    }
+}
+
+/// The PID of the most recently started process under `proc_root` whose command name is
+/// `name`.
+pub fn newest_named_in(proc_root: &Path, name: &str) -> Option<u32> {
+    let entries = fs::read_dir(proc_root).ok()?;
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            if comm.trim_end() != name {
+                return None;
+            }
+            let start_time = process::parse_start_time(&fs::read_to_string(entry.path().join("stat")).ok()?)?;
+            Some((start_time, pid))
+        })
+        .max()
+        .map(|(_, pid)| pid)
+}
+
+/// What the process uses, summed over the GPUs it is on. Both are `None` while it is on none.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub memory_mib: Option<u64>,
+    /// Busy share of the GPU, in percent; above 100 for a process busy on several GPUs.
+    pub busy: Option<f64>,
+}
+
+impl Usage {
+    pub fn on_gpu(&self) -> bool {
+        self.memory_mib.is_some() || self.busy.is_some()
+    }
+}
+
+fn sum<T: std::iter::Sum<T>>(values: impl Iterator<Item = Option<T>>) -> Option<T> {
+    let values: Vec<T> = values.flatten().collect();
+    (!values.is_empty()).then(|| values.into_iter().sum())
+}
+
+/// The usage of `pid` from the `pmon` rows, with the memory from `--query-compute-apps` when
+/// `pmon` has no `fb` column.
+pub fn nvidia_usage(pid: u32, processes: &[GpuProcess], compute_apps: &[(u32, u64)]) -> Usage {
+    let rows: Vec<&GpuProcess> = processes.iter().filter(|process| process.pid == pid).collect();
+    let memory = sum(rows.iter().map(|process| process.memory_used_mib)).or_else(|| sum(compute_apps.iter().filter(|(app, _)| *app == pid).map(|(_, mib)| Some(*mib))));
+
+    Usage { memory_mib: memory, busy: sum(rows.iter().map(|process| process.utilization.map(widen))) }
+}
+
+/// The usage of a process from two readings of its DRM clients taken `elapsed` apart: the
+/// busiest engine's share on each GPU, and the resident VRAM.
+pub fn drm_usage(previous: &[DrmClient], current: &[DrmClient], elapsed: Duration) -> Usage {
+    let mut busy = DrmBusyTracker::default();
+    let at = Moment::now();
+    busy.update(previous, at);
+    busy.update(current, at.after(elapsed));
+    tracked_usage(&busy, current)
+}
+
+/// The usage of a process whose `current` DRM clients `busy` just read. The busy share is
+/// missing for a reading across a suspend.
+fn tracked_usage(busy: &DrmBusyTracker, current: &[DrmClient]) -> Usage {
+    if current.is_empty() {
+        return Usage::default();
+    }
+
+    let devices: BTreeSet<Option<&str>> = current.iter().map(|client| client.pdev.as_deref()).collect();
+    let busy = devices.into_iter().map(|device| busy.device_utilization(device)).sum();
+    let memory = sum(current.iter().map(|client| client.vram_bytes)).map(|bytes: u64| bytes / (1024 * 1024));
+
+    Usage { memory_mib: memory, busy }
+}
+
+/// Reads the usage of one process each tick.
+#[derive(Debug)]
+pub enum UsageReader {
+    Nvidia,
+    /// The busy share is the difference between two readings, so the last one is kept.
+    Drm(DrmBusyTracker),
+}
+
+impl UsageReader {
+    pub fn new(gpu_type: &GpuType, pid: u32) -> Self {
+        match gpu_type {
+            GpuType::Nvidia => UsageReader::Nvidia,
+            _ => {
+                let mut busy = DrmBusyTracker::default();
+                busy.update(&read_process_drm_clients(pid), Moment::now());
+                UsageReader::Drm(busy)
+            }
+        }
+    }
+
+    pub fn read(&mut self, runner: &dyn CommandRunner, pid: u32) -> Usage {
+        match self {
+            UsageReader::Nvidia => {
+                let processes = process::query_processes(runner, &GpuType::Nvidia).unwrap_or_default();
+                let on_gpu = processes.iter().any(|process| process.pid == pid);
+                let missing_memory = on_gpu && processes.iter().all(|process| process.memory_used_mib.is_none());
+                let compute_apps = if missing_memory { process::query_compute_apps(runner).unwrap_or_default() } else { Vec::new() };
+                nvidia_usage(pid, &processes, &compute_apps)
+            }
+            UsageReader::Drm(busy) => {
+                let current = read_process_drm_clients(pid);
+                busy.update(&current, Moment::now());
+                tracked_usage(busy, &current)
+            }
+        }
+    }
+}
+
+/// Totals over the process's lifetime.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub peak_memory_mib: Option<u64>,
+    busy_total: f64,
+    busy_samples: u32,
+    /// GPU time used: the busy share times the time between samples, summed.
+    pub gpu_seconds: f64,
+}
+
+impl Summary {
+    /// Counts a sample taken `elapsed` after the previous one.
+    pub fn record(&mut self, usage: &Usage, elapsed: Duration) {
+        if let Some(memory) = usage.memory_mib {
+            self.peak_memory_mib = Some(self.peak_memory_mib.map_or(memory, |peak| peak.max(memory)));
+        }
+        if let Some(busy) = usage.busy {
+            self.busy_total += busy;
+            self.busy_samples += 1;
+            self.gpu_seconds += busy / 100.0 * elapsed.as_secs_f64();
+        }
+    }
+
+    /// The mean busy share over the samples that had one, i.e. while the process was on a GPU.
+    pub fn mean_busy(&self) -> Option<f64> {
+        (self.busy_samples > 0).then(|| self.busy_total / f64::from(self.busy_samples))
+    }
+
+    pub fn format(&self, target: &Target, duration: Duration) -> Vec<String> {
+        let or_na = |value: Option<String>| value.unwrap_or_else(|| "n/a".to_string());
+        vec![
+            format!("{} exited after {}", describe(target), format_duration(duration)),
+            format!("  Peak VRAM:   {}", or_na(self.peak_memory_mib.map(|mib| format!("{} MiB", mib)))),
+            format!("  Mean busy:   {}", or_na(self.mean_busy().map(|busy| format!("{:.1}%", busy)))),
+            format!("  GPU-seconds: {:.1}", self.gpu_seconds),
+        ]
+    }
+}
+
+fn describe(target: &Target) -> String {
+    format!("PID {} ({})", target.pid, target.name)
+}
+
+pub fn format_line(target: &Target, usage: &Usage) -> String {
+    if !usage.on_gpu() {
+        return format!("{} not using the GPU", describe(target));
+    }
+    let memory = usage.memory_mib.map_or("n/a".to_string(), |mib| format!("{} MiB", mib));
+    let busy = usage.busy.map_or("n/a".to_string(), |busy| format!("{:.1}%", busy));
+    format!("{} VRAM: {}, Busy: {}", describe(target), memory, busy)
+}
+
+/// A [`CSV_HEADER`] row; values the process does not have are left empty.
+pub fn format_csv(target: &Target, usage: &Usage, elapsed: Duration) -> String {
+    let memory = usage.memory_mib.map(|mib| mib.to_string()).unwrap_or_default();
+    let busy = usage.busy.map(|busy| busy.to_string()).unwrap_or_default();
+    format!("{:.1},{},{},{}", elapsed.as_secs_f64(), target.pid, memory, busy)
+}
@@ -0,0 +1,196 @@
+//! `gpuatop web`: a small HTTP server with an embedded live dashboard at `/` and the raw
+//! NDJSON sample stream as WebSocket text messages at `/ws`. Request headers are limited to
+//! 8 KiB, at most 32 connections are served at once, and `/ws` only
+//! accepts pages served from the same host.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::handshake::server::{self as handshake, Request};
+use tungstenite::http::Version;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+const DASHBOARD: &[u8] = include_bytes!("templates/dashboard.html");
+
+/// Samples queued per client; a client that falls further behind is dropped.
+const CLIENT_QUEUE: usize = 64;
+
+/// Longest request line and headers accepted; browsers send well under 2 KiB.
+const MAX_REQUEST_HEAD: u64 = 8 * 1024;
+
+/// Connections served at once, page loads and WebSocket clients together.
+const MAX_CONNECTIONS: usize = 32;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Normalizes `--listen` values: `:8080` listens on all interfaces, a bare port on localhost.
+pub fn listen_address(value: &str) -> String {
+    if let Some(port) = value.strip_prefix(':') {
+        format!("0.0.0.0:{}", port)
+    } else if value.chars().all(|c| c.is_ascii_digit()) {
+        format!("127.0.0.1:{}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+type Clients = Arc<Mutex<Vec<SyncSender<String>>>>;
+
+/// The dashboard server. Samples passed to [`Server::publish`] are queued to every WebSocket
+/// client without blocking; clients whose queue is full are disconnected.
+#[derive(Debug)]
+pub struct Server {
+    clients: Clients,
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, content_type, body.len())?;
+    stream.write_all(body)
+}
+
+/// Whether a WebSocket connection may stream the samples. Browsers send the `Origin` of the
+/// page opening it, which must be this server's, so that another site open in the browser
+/// cannot read them; other programs send no `Origin`.
+pub fn origin_allowed(origin: Option<&str>, host: Option<&str>) -> bool {
+    match (origin, host) {
+        (None, _) => true,
+        (Some(origin), Some(host)) => origin.split_once("://").is_some_and(|(_, authority)| authority.eq_ignore_ascii_case(host)),
+        (Some(_), None) => false,
+    }
+}
+
+/// Reads the request line and headers, trimmed. `None` when they are longer than
+/// [`MAX_REQUEST_HEAD`].
+fn read_head(stream: &TcpStream) -> io::Result<Option<Vec<String>>> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_HEAD));
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            if reader.get_ref().limit() == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if line.trim().is_empty() {
+            return Ok(Some(lines));
+        }
+        lines.push(line.trim_end().to_string());
+    }
+}
+
+fn parse_request(head: &[String]) -> Option<Request> {
+    let (request_line, headers) = head.split_first()?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next()?, parts.next()?);
+    let version = if parts.next() == Some("HTTP/1.0") { Version::HTTP_10 } else { Version::HTTP_11 };
+
+    let mut request = Request::builder().method(method).uri(path).version(version);
+    for line in headers {
+        let (name, value) = line.split_once(':')?;
+        request = request.header(name.trim(), value.trim());
+    }
+    request.body(()).ok()
+}
+
+fn stream_samples(mut socket: WebSocket<TcpStream>, samples: Receiver<String>) {
+    for sample in samples {
+        if socket.send(Message::text(sample)).is_err() {
+            return;
+        }
+    }
+    // The sender was dropped because the client fell behind: close the connection.
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+fn handle_connection(mut stream: TcpStream, clients: Clients) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))?;
+
+    let Some(head) = read_head(&stream)? else {
+        return write_response(&mut stream, "431 Request Header Fields Too Large", "text/plain", b"Request header too large\n");
+    };
+    let Some(request) = parse_request(&head) else {
+        return write_response(&mut stream, "400 Bad Request", "text/plain", b"Bad request\n");
+    };
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+
+    match (request.method().as_str(), request.uri().path()) {
+        ("GET", "/ws") if header("upgrade").is_none() => write_response(&mut stream, "426 Upgrade Required", "text/plain", b"WebSocket upgrade required\n"),
+        ("GET", "/ws") if !origin_allowed(header("origin"), header("host")) => {
+            write_response(&mut stream, "403 Forbidden", "text/plain", b"Cross-origin WebSocket connections are not allowed\n")
+        }
+        ("GET", "/ws") => match handshake::create_response(&request) {
+            Ok(response) => {
+                handshake::write_response(&mut stream, &response).map_err(io::Error::other)?;
+
+                let (sender, samples) = mpsc::sync_channel(CLIENT_QUEUE);
+                clients.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(sender);
+                stream_samples(WebSocket::from_raw_socket(stream, Role::Server, None), samples);
+                Ok(())
+            }
+            Err(err) => write_response(&mut stream, "400 Bad Request", "text/plain", format!("{}\n", err).as_bytes()),
+        },
+        ("GET", "/" | "/index.html") => write_response(&mut stream, "200 OK", "text/html; charset=utf-8", DASHBOARD),
+        ("GET", _) => write_response(&mut stream, "404 Not Found", "text/plain", b"Not found\n"),
+        _ => write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed\n"),
+    }
+}
+
+/// Holds one of the [`MAX_CONNECTIONS`] while a connection is served.
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| (count < MAX_CONNECTIONS).then_some(count + 1)).ok()?;
+        Some(Slot(Arc::clone(connections)))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Server {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(listen_address(address))?;
+        let clients: Clients = Arc::default();
+
+        let accept_clients = Arc::clone(&clients);
+        thread::spawn(move || {
+            let connections = Arc::new(AtomicUsize::new(0));
+            for mut stream in listener.incoming().map_while(Result::ok) {
+                let Some(slot) = Slot::take(&connections) else {
+                    let _ = stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT));
+                    let _ = write_response(&mut stream, "503 Service Unavailable", "text/plain", b"Too many connections\n");
+                    continue;
+                };
+                let clients = Arc::clone(&accept_clients);
+                thread::spawn(move || {
+                    let _slot = slot;
+                    handle_connection(stream, clients)
+                });
+            }
+        });
+
+        Ok(Server { clients })
+    }
+
+    pub fn publish(&self, sample: &str) {
+        let mut clients = self.clients.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        clients.retain(|client| match client.try_send(sample.to_string()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
@@ -0,0 +1,144 @@
+/// A parsed XML element. Only what is needed to read tool output: elements, attributes,
+/// and text; comments, processing instructions, and DOCTYPE declarations are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// The character an entity between `&` and `;` stands for: one of the five predefined
+/// entities, or a numeric character reference, decimal (`#233`) or hexadecimal (`#xE9`).
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "amp" => Some('&'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Replaces entities in a single pass, so that `&amp;lt;` stays `&lt;`. Anything that is not
+/// a known entity is kept as written.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // The longest entity, `#x10FFFF`, is eight characters; a `;` further on is not its end.
+        match rest.bytes().take(10).position(|byte| byte == b';').and_then(|end| Some((end, entity(&rest[1..end])?))) {
+            Some((end, character)) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn parse_tag(tag: &str) -> Result<(String, Vec<(String, String)>), String> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = tag[..name_end].to_string();
+    let mut rest = tag[name_end..].trim_start();
+    let mut attributes = Vec::new();
+
+    while !rest.is_empty() {
+        let (key, after_key) = rest.split_once('=').ok_or_else(|| format!("Malformed attribute in <{}>", name))?;
+        let after_key = after_key.trim_start();
+        let quote = after_key.chars().next().filter(|c| *c == '"' || *c == '\'').ok_or_else(|| format!("Unquoted attribute in <{}>", name))?;
+        let value_end = after_key[1..].find(quote).ok_or_else(|| format!("Unterminated attribute in <{}>", name))?;
+
+        attributes.push((key.trim().to_string(), unescape(&after_key[1..1 + value_end])));
+        rest = after_key[value_end + 2..].trim_start();
+    }
+
+    Ok((name, attributes))
+}
+
+/// Parses a document and returns its root element.
+pub fn parse(input: &str) -> Result<Element, String> {
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    let mut rest = input;
+
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        if let Some(current) = stack.last_mut() {
+            current.text.push_str(&unescape(text.trim()));
+        }
+        rest = &rest[start..];
+
+        let terminator = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<?") {
+            "?>"
+        } else {
+            ">"
+        };
+
+        let end = rest.find(terminator).ok_or("Unterminated markup")?;
+        let markup = &rest[1..end];
+        rest = &rest[end + terminator.len()..];
+
+        // Comments, processing instructions, and DOCTYPE declarations carry no data.
+        if markup.starts_with('!') || markup.starts_with('?') {
+            continue;
+        }
+
+        if let Some(name) = markup.strip_prefix('/') {
+            let element = stack.pop().ok_or_else(|| format!("Unexpected closing tag </{}>", name))?;
+            if element.name != name.trim() {
+                return Err(format!("Mismatched closing tag </{}> for <{}>", name.trim(), element.name));
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => root = Some(element),
+            }
+        } else {
+            let self_closing = markup.ends_with('/');
+            let (name, attributes) = parse_tag(markup.trim_end_matches('/'))?;
+            let element = Element { name, attributes, text: String::new(), children: Vec::new() };
+
+            if self_closing {
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => root = Some(element),
+                }
+            } else {
+                stack.push(element);
+            }
+        }
+    }
+
+    if let Some(element) = stack.pop() {
+        return Err(format!("Unclosed element <{}>", element.name));
+    }
+
+    root.ok_or_else(|| "No root element".to_string())
+}
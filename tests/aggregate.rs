@@ -0,0 +1,78 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::aggregate::{format_json, format_table, totals, Totals};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, utilization: f64, memory_used_mib: Option<u64>, power_w: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot {
+        memory_used_mib,
+        memory_total_mib: memory_used_mib.map(|_| 8192),
+        temperature_c: Some(60.0),
+        power_w,
+        ..common::snapshot(index, &format!("GPU {}", index), utilization)
+    }
+}
+
+fn context() -> OutputContext {
+    OutputContext { format: OutputFormat::Json, hostname: None, labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn totals_average_utilization_and_sum_memory_and_power() {
+    let snapshots = [snapshot(0, 40.0, Some(1000), Some(100.0)), snapshot(1, 80.0, Some(3000), Some(150.5))];
+
+    assert_eq!(
+        totals(&snapshots),
+        Totals { gpus: 2, mean_utilization: 60.0, memory_used_mib: Some(4000), memory_total_mib: Some(16384), power_w: Some(250.5) }
+    );
+}
+
+#[test]
+fn totals_skip_gpus_without_a_metric() {
+    let totals = totals(&[snapshot(0, 40.0, None, None), snapshot(1, 80.0, Some(3000), None)]);
+
+    assert_eq!(totals.memory_used_mib, Some(3000));
+    assert_eq!(totals.power_w, None);
+}
+
+#[test]
+fn table_is_sorted_by_index_with_the_total_row_last() {
+    let mut snapshots = vec![snapshot(1, 80.0, Some(3000), Some(150.5)), snapshot(0, 40.0, Some(1000), Some(100.0))];
+
+    let table = format_table(&mut snapshots, false);
+
+    assert_eq!(table.len(), 4);
+    assert!(table[0].starts_with("GPU"));
+    assert!(table[1].starts_with("0 "));
+    assert!(table[2].starts_with("1 "));
+    assert!(table[3].starts_with("Total"));
+    assert!(table[3].contains("60.0% mean"));
+    assert!(table[3].contains("4000/16384 MiB"));
+    assert!(table[3].ends_with("250.5 W"));
+}
+
+#[test]
+fn highlight_only_marks_the_total_row() {
+    let table = format_table(&mut [snapshot(0, 40.0, Some(1000), Some(100.0))], true);
+
+    assert!(!table[1].contains('\x1b'));
+    assert!(table[2].starts_with("\x1b[1;7mTotal"));
+    assert!(table[2].ends_with("\x1b[0m"));
+}
+
+#[test]
+fn json_wraps_the_records_with_totals() {
+    let mut snapshots = vec![snapshot(1, 80.0, Some(3000), None), snapshot(0, 40.0, Some(1000), None)];
+
+    let document = format_json(&mut snapshots, &context());
+
+    assert!(document.starts_with("{\"schema_version\":1,\"gpus\":[{\"schema_version\":1,\"gpu\":0,"));
+    assert!(document.contains("},{\"schema_version\":1,\"gpu\":1,"));
+    assert!(document.ends_with(
+        "],\"totals\":{\"gpus\":2,\"mean_utilization\":60,\"memory_used_mib\":4000,\"memory_total_mib\":16384}}"
+    ));
+}
@@ -0,0 +1,236 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use gpu_auto_top::alert::{format_history, parse_severities, rules, AlertKind, AlertTracker, Severity, VramTrend};
+use gpu_auto_top::event::Event;
+use gpu_auto_top::json;
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::schema::validate;
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(temperature_c: f32) -> GpuSnapshot {
+    GpuSnapshot { temperature_c: Some(temperature_c), ..common::snapshot(0, "NVIDIA A100-SXM4-80GB", 100.0) }
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn severities_can_be_overridden_per_alert() {
+    let severities = parse_severities("temp=warning, vram=critical").unwrap();
+    let rules = rules(Some(85.0), &[], Some(90.0), None, &severities);
+
+    let severity = |kind| rules.iter().find(|rule| rule.kind == kind).unwrap().severity;
+    assert_eq!(severity(AlertKind::Temperature), Severity::Warning);
+    assert_eq!(severity(AlertKind::VramNearlyFull), Severity::Critical);
+    assert_eq!(severity(AlertKind::Utilization), Severity::Info);
+
+    assert!(parse_severities("fan=critical").is_err());
+    assert!(parse_severities("temp=fatal").is_err());
+    assert!(parse_severities("temp").is_err());
+}
+
+#[test]
+fn a_ten_minute_thermal_event_is_one_event() {
+    let mut tracker = AlertTracker::new(rules(Some(85.0), &[], None, None, &[]));
+    let started = Instant::now();
+    let wall_clock = UNIX_EPOCH + Duration::from_secs(1_760_623_392);
+    let mut fired = 0;
+    let mut resolved = Vec::new();
+
+    for second in 0..=600 {
+        let temperature = if second == 600 { 80.0 } else { 86.0 + (second % 6) as f32 };
+        let update = tracker.update_at(&snapshot(temperature), started + Duration::from_secs(second), wall_clock + Duration::from_secs(second));
+        fired += update.fired.len();
+        resolved.extend(update.resolved);
+    }
+
+    assert_eq!(fired, 1);
+    assert_eq!(resolved.len(), 1);
+    let (alert, event) = &resolved[0];
+    assert_eq!((alert.value, event.peak, event.duration, event.ongoing), (86.0, 91.0, Duration::from_secs(600), false));
+    assert_eq!(event.format_summary(), "2025-10-16T14:03:12.000Z critical: GPU 0 temperature above 85°C for 00:10:00, peak 91°C");
+    assert!(tracker.critical_fired());
+}
+
+#[test]
+fn history_lists_alerts_still_active_at_exit() {
+    let mut tracker = AlertTracker::new(rules(Some(85.0), &[], None, None, &[(AlertKind::Temperature, Severity::Warning)]));
+    let started = Instant::now();
+    assert!(format_history(&tracker.history(started)).is_empty());
+
+    tracker.update_at(&snapshot(90.0), started, SystemTime::now());
+    let history = tracker.history(started + Duration::from_secs(42));
+
+    assert_eq!(history.len(), 1);
+    assert_eq!((history[0].duration, history[0].ongoing), (Duration::from_secs(42), true));
+    let lines = format_history(&history);
+    assert_eq!(lines[0], "Alerts:");
+    assert!(lines[1].ends_with(" warning: GPU 0 temperature above 85°C for 00:00:42, peak 90°C (still active at exit)"), "{}", lines[1]);
+    assert!(!tracker.critical_fired());
+}
+
+#[test]
+fn event_records_match_the_schema() {
+    let context = OutputContext { format: OutputFormat::Ndjson, hostname: Some("node1".to_string()), labels: "rack=a1".parse::<Labels>().unwrap(), tick_seq: Some(9), timestamp: None, precision: 1 };
+    let mut tracker = AlertTracker::new(rules(Some(85.0), &[], None, None, &[]));
+    let started = Instant::now();
+
+    let fired = tracker.update_at(&snapshot(90.0), started, SystemTime::now()).fired;
+    let resolved = tracker.update_at(&snapshot(70.0), started + Duration::from_millis(2500), SystemTime::now()).resolved;
+
+    let firing = Event::alert_firing(&fired[0]).to_json(&context);
+    let (alert, event) = &resolved[0];
+    let resolved = Event::alert_resolved(alert, event).to_json(&context);
+    assert!(firing.contains("\"type\":\"event\",\"event\":\"alert\",\"state\":\"firing\",\"severity\":\"critical\",\"gpu\":0,"), "{}", firing);
+    assert!(firing.contains("\"metric\":\"temperature_c\",\"threshold\":85,\"value\":90,"), "{}", firing);
+    assert!(resolved.contains("\"peak\":90,\"duration_s\":2.5,"), "{}", resolved);
+    for record in [firing, resolved] {
+        validate(&json::parse(&record).unwrap()).unwrap_or_else(|err| panic!("{}\n{}", err, record));
+    }
+}
+
+#[test]
+fn critical_alerts_change_the_exit_code() {
+    let critical = run("alert-critical", &["--count", "2", "--alert-temp", "50"]);
+    let stdout = String::from_utf8_lossy(&critical.stdout);
+    assert_eq!(critical.status.code(), Some(3));
+    assert!(stdout.contains("[ALERT critical] GPU 0 (NVIDIA GeForce RTX 3090) temperature 60°C exceeds 50°C"), "{}", stdout);
+    assert!(stdout.contains("\nAlerts:\n"), "{}", stdout);
+    assert!(stdout.contains("\nEvents: 1 (1 alert)\n"), "{}", stdout);
+    assert!(stdout.contains(" critical: GPU 0 temperature above 50°C for 00:00:0"), "{}", stdout);
+
+    let configured = run("alert-exit-code", &["--count", "1", "--alert-temp", "50", "--critical-exit-code", "42"]);
+    assert_eq!(configured.status.code(), Some(42));
+    let disabled = run("alert-no-exit-code", &["--count", "1", "--alert-temp", "50", "--critical-exit-code", "0"]);
+    assert_eq!(disabled.status.code(), Some(0));
+    let warning = run("alert-warning", &["--count", "1", "--alert-temp", "50", "--alert-severity", "temp=warning"]);
+    assert_eq!(warning.status.code(), Some(0));
+}
+
+#[test]
+fn ndjson_carries_alert_events() {
+    let output = run("alert-ndjson", &["--format", "ndjson", "--count", "1", "--alert-temp", "50"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let events: Vec<&str> = stdout.lines().filter(|line| line.contains("\"event\":\"alert\"")).collect();
+    assert_eq!(events.len(), 1, "{}", stdout);
+    assert!(events[0].contains("\"state\":\"firing\",\"severity\":\"critical\""), "{}", events[0]);
+}
+
+const TOTAL_MIB: u64 = 81920;
+
+/// Records `used` MiB at one sample every two seconds.
+fn trend_of(used: &[u64]) -> VramTrend {
+    let mut trend = VramTrend::default();
+    for (tick, &used) in used.iter().enumerate() {
+        trend.record(tick as f64 * 2.0, used, TOTAL_MIB);
+    }
+    trend
+}
+
+#[test]
+fn steady_vram_growth_estimates_time_to_full() {
+    // 60 MiB every 2 s: 1800 MiB/min, 1680 MiB left after the last sample.
+    let trend = trend_of(&[80000, 80060, 80120, 80180, 80240]);
+
+    assert_eq!(trend.growth_mib_per_min(), Some(1800.0));
+    let (rate, eta) = trend.time_to_full(TOTAL_MIB).unwrap();
+    assert_eq!(rate, 1800.0);
+    assert_eq!(eta.as_secs(), 56);
+}
+
+#[test]
+fn noisy_flat_vram_usage_has_no_trend() {
+    let noisy = [40000, 40210, 39950, 40180, 39990, 40230, 40010, 39970, 40200, 40050];
+    let mut trend = VramTrend::default();
+
+    for (tick, &used) in noisy.iter().enumerate() {
+        trend.record(tick as f64 * 2.0, used, TOTAL_MIB);
+        assert_eq!(trend.growth_mib_per_min(), None, "after {} samples", tick + 1);
+    }
+}
+
+#[test]
+fn vram_growth_within_noise_has_no_trend() {
+    let trend = trend_of(&[40000, 40010, 40020, 40030, 40040]);
+    assert_eq!(trend.growth_mib_per_min(), None);
+}
+
+#[test]
+fn vram_trend_needs_a_full_window() {
+    let trend = trend_of(&[10000, 20000, 30000, 40000]);
+    assert_eq!(trend.growth_mib_per_min(), None);
+}
+
+#[test]
+fn a_large_vram_drop_resets_the_trend() {
+    let mut trend = trend_of(&[60000, 62000, 64000, 66000, 68000]);
+    assert!(trend.growth_mib_per_min().is_some());
+
+    // A job exits and frees 20 GiB, more than a tenth of the total.
+    trend.record(10.0, 47520, TOTAL_MIB);
+    assert_eq!(trend.growth_mib_per_min(), None);
+
+    // Growth from there only counts once a new window is full.
+    for (tick, used) in [(12.0, 49520), (14.0, 51520), (16.0, 53520)] {
+        trend.record(tick, used, TOTAL_MIB);
+        assert_eq!(trend.growth_mib_per_min(), None);
+    }
+    trend.record(18.0, 55520, TOTAL_MIB);
+    assert_eq!(trend.growth_mib_per_min(), Some(60000.0));
+}
+
+#[test]
+fn a_small_vram_drop_breaks_the_trend_without_resetting_it() {
+    let mut trend = trend_of(&[60000, 62000, 64000, 66000, 68000]);
+
+    trend.record(10.0, 67000, TOTAL_MIB);
+    assert_eq!(trend.growth_mib_per_min(), None);
+
+    // The dip stays in the window until it is its oldest sample.
+    for tick in 6..9 {
+        trend.record(tick as f64 * 2.0, 67000 + (tick - 5) * 1000, TOTAL_MIB);
+        assert_eq!(trend.growth_mib_per_min(), None);
+    }
+    trend.record(18.0, 71000, TOTAL_MIB);
+    assert_eq!(trend.growth_mib_per_min(), Some(30000.0));
+}
+
+#[test]
+fn vram_alert_mentions_the_trend_only_when_usage_rises() {
+    let vram = |used: u64| GpuSnapshot { memory_used_mib: Some(used), memory_total_mib: Some(TOTAL_MIB), ..snapshot(60.0) };
+    let start = Instant::now();
+    let at = |seconds: u64| start + Duration::from_secs(seconds);
+
+    let mut rising = AlertTracker::new(rules(None, &[], None, None, &[]));
+    let mut fired = Vec::new();
+    for (tick, used) in [74000, 75000, 76000, 77000, 78500].into_iter().enumerate() {
+        fired.extend(rising.update_at(&vram(used), at(tick as u64 * 60), SystemTime::now()).fired);
+    }
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].message(), "GPU 0 (NVIDIA A100-SXM4-80GB) VRAM 96% and rising ~1.1 GiB/min — full in ~3m");
+
+    let mut noisy = AlertTracker::new(rules(None, &[], None, None, &[]));
+    let mut fired = Vec::new();
+    for (tick, used) in [79000, 78900, 79100, 78950, 79050].into_iter().enumerate() {
+        fired.extend(noisy.update_at(&vram(used), at(tick as u64 * 2), SystemTime::now()).fired);
+    }
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].message(), "GPU 0 (NVIDIA A100-SXM4-80GB) VRAM 96.4% full");
+}
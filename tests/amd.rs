@@ -0,0 +1,84 @@
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::{enumerate_gpus, parse_radeontop_output, poll_gpus, GpuInfo, GpuType, PollResult};
+
+const RADEONTOP_ARGS: [&str; 4] = ["-d", "-", "-l", "1"];
+
+const RADEONTOP: &str = "Dumping to -, line limit 1.\n\
+1700000000.123456: bus 03, gpu 12.50%, ee 0.00%, vgt 0.00%, ta 4.17%, sx 4.17%, sh 0.00%, spi 8.33%, sc 4.17%, pa 0.00%, \
+db 4.17%, cb 4.17%, vram 10.23% 835.12mb, gtt 0.50% 40.00mb, mclk 100.00% 1.000ghz, sclk 30.00% 0.600ghz\n";
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "Amd GPU".to_string(), bus_id: None, render_offload: None }
+}
+
+#[test]
+fn parses_radeontop_dump() {
+    let snapshot = parse_radeontop_output(RADEONTOP, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 12.5);
+    assert_eq!(snapshot.memory_used_mib, Some(835));
+    assert_eq!(snapshot.memory_total_mib, None);
+    assert_eq!(snapshot.temperature_c, None);
+}
+
+#[test]
+fn uses_the_last_sample_line() {
+    let output = format!("{}1700000001.123456: bus 03, gpu 99.00%, vram 50.00% 4096.00mb\n", RADEONTOP);
+
+    let snapshot = parse_radeontop_output(&output, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 99.0);
+    assert_eq!(snapshot.memory_used_mib, Some(4096));
+}
+
+#[test]
+fn rejects_output_without_samples() {
+    assert!(parse_radeontop_output("Dumping to -, line limit 1.\n", &gpu()).is_err());
+    assert!(parse_radeontop_output("", &gpu()).is_err());
+}
+
+#[test]
+fn polls_through_radeontop() {
+    let runner = MockRunner::new().with("radeontop", &RADEONTOP_ARGS, CommandOutput::ok(RADEONTOP));
+
+    let results = poll_gpus(&runner, &GpuType::Amd, &[gpu()]);
+
+    match &results[0] {
+        PollResult::Ok { snapshot, .. } => assert_eq!(snapshot.utilization, 12.5),
+        other => panic!("expected a snapshot, got {:?}", other),
+    }
+}
+
+#[test]
+fn unexpected_output_is_a_transient_error() {
+    let runner = MockRunner::new().with("radeontop", &RADEONTOP_ARGS, CommandOutput::ok("Cannot access GPU registers, are you root?\n"));
+
+    let results = poll_gpus(&runner, &GpuType::Amd, &[gpu()]);
+
+    match &results[0] {
+        PollResult::TransientError { message, .. } => assert!(message.contains("are you root?")),
+        other => panic!("expected a transient error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_radeontop_is_a_permanent_error() {
+    let results = poll_gpus(&MockRunner::new(), &GpuType::Amd, &[gpu()]);
+
+    assert!(matches!(results[0], PollResult::PermanentError { .. }));
+}
+
+#[test]
+fn enumerates_a_single_device() {
+    let gpus = enumerate_gpus(&MockRunner::new(), &GpuType::Amd);
+
+    assert_eq!(gpus.len(), 1);
+    assert_eq!(gpus[0].name, "Amd GPU");
+}
+
+#[test]
+fn reads_a_decimal_comma() {
+    let snapshot = parse_radeontop_output("1700000000,123456: bus 03, gpu 12,50%, vram 10,23% 835,12mb\n", &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 12.5);
+}
@@ -0,0 +1,83 @@
+use gpu_auto_top::backend::fits;
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::{is_amd_apu, parse_amd_pm_info, try_identify_gpu_card, GpuInfo, GpuType};
+
+/// `amdgpu_pm_info` of a Ryzen 7 5800U (Cezanne).
+const PM_INFO: &str = "Clock Gating Flags Mask: 0x3fbcf
+\tGraphics Medium Grain Clock Gating: On
+\tGraphics Coarse Grain Clock Gating: On
+
+GFX Clocks and Power:
+\t1600 MHz (MCLK)
+\t400 MHz (SCLK)
+\t1200 MHz (PSTATE_SCLK)
+\t1600 MHz (PSTATE_MCLK)
+\t831 mV (VDDGFX)
+\t3.25 W (average SoC)
+
+GPU Temperature: 44 C
+GPU Load: 7 %
+VCN: Disabled
+";
+
+const APU: &str = "0000:04:00.0 VGA compatible controller [0300]: Advanced Micro Devices, Inc. [AMD/ATI] Cezanne [Radeon Vega Series / Radeon Vega Mobile Series] [1002:1638] (rev c1)\n";
+const NAVI: &str = "0000:03:00.0 VGA compatible controller [0300]: Advanced Micro Devices, Inc. [AMD/ATI] Navi 23 [Radeon RX 6600/6600 XT/6600M] [1002:73ff] (rev c1)\n";
+const NVIDIA: &str = "0000:01:00.0 VGA compatible controller [0300]: NVIDIA Corporation GA107M [GeForce RTX 3050 Mobile] [10de:25a2] (rev a1)\n";
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "AMD Radeon Graphics".to_string(), bus_id: None, render_offload: None }
+}
+
+fn identify(lspci: &str) -> Option<GpuType> {
+    try_identify_gpu_card(&MockRunner::new().with("lspci", &["-Dnn"], CommandOutput::ok(lspci)))
+}
+
+#[test]
+fn parses_the_load_temperature_and_soc_power() {
+    let snapshot = parse_amd_pm_info(PM_INFO, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 7.0);
+    assert_eq!(snapshot.temperature_c, Some(44.0));
+    assert_eq!(snapshot.power_w, Some(3.25));
+    assert_eq!(snapshot.memory_used_mib, None);
+    assert_eq!(snapshot.memory_bandwidth, None);
+}
+
+#[test]
+fn reads_the_memory_load_where_there_is_one() {
+    let output = format!("{}MEM Load: 12 %\n", PM_INFO);
+
+    let snapshot = parse_amd_pm_info(&output, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct), Some(12.0));
+}
+
+#[test]
+fn rejects_pm_info_without_a_load() {
+    assert!(parse_amd_pm_info("GFX Clocks and Power:\n\t400 MHz (SCLK)\n", &gpu()).is_err());
+    assert!(parse_amd_pm_info("", &gpu()).is_err());
+}
+
+#[test]
+fn recognizes_the_apus_by_code_name() {
+    assert!(is_amd_apu("Advanced Micro Devices, Inc. [AMD/ATI] Renoir"));
+    assert!(is_amd_apu("Advanced Micro Devices, Inc. [AMD/ATI] Rembrandt [Radeon 680M]"));
+    assert!(is_amd_apu("Advanced Micro Devices, Inc. [AMD/ATI] Phoenix1"));
+    assert!(!is_amd_apu("Advanced Micro Devices, Inc. [AMD/ATI] Navi 31 [Radeon RX 7900 XT/7900 XTX]"));
+}
+
+#[test]
+fn an_apu_only_counts_without_a_discrete_gpu() {
+    assert_eq!(identify(APU), Some(GpuType::AmdApu));
+    assert_eq!(identify(&format!("{}{}", NAVI, APU)), Some(GpuType::Amd));
+    assert_eq!(identify(&format!("{}{}", NVIDIA, APU)), Some(GpuType::Nvidia));
+}
+
+#[test]
+fn only_apus_are_read_from_pm_info() {
+    assert!(fits(&GpuType::AmdApu, "amdgpu_pm_info"));
+    assert!(fits(&GpuType::AmdApu, "sysfs:gpu_busy_percent"));
+    assert!(!fits(&GpuType::Amd, "amdgpu_pm_info"));
+    assert!(!fits(&GpuType::AmdApu, "radeontop"));
+    assert_eq!(GpuType::AmdApu.top_tool(), None);
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use gpu_auto_top::amd_smi::{parse_list, parse_metrics, Device, LIST_ARGS, METRIC_ARGS};
+use gpu_auto_top::backend;
+use gpu_auto_top::runner::{CommandOutput, MockRunner, Retry};
+use gpu_auto_top::temperature::Sensor;
+use gpu_auto_top::{check_top_exists_local, enumerate_gpus, GpuInfo, GpuType, PollResult};
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/amd-smi/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn gpus_at(bus_ids: &[&str]) -> Vec<GpuInfo> {
+    bus_ids
+        .iter()
+        .enumerate()
+        .map(|(index, bus_id)| GpuInfo { index: index as u32, name: format!("GPU {}", index), bus_id: Some(bus_id.to_string()), render_offload: None })
+        .collect()
+}
+
+fn runner() -> MockRunner {
+    MockRunner::new()
+        .with("amd-smi", &LIST_ARGS, CommandOutput::ok(&fixture("list-24.6.json")))
+        .with("amd-smi", &METRIC_ARGS, CommandOutput::ok(&fixture("metric-24.6.json")))
+}
+
+#[test]
+fn list_reads_indices_and_bus_ids() {
+    let devices = parse_list(&fixture("list-24.6.json")).unwrap();
+
+    assert_eq!(
+        devices,
+        [Device { index: 0, bus_id: "0000:c1:00.0".to_string() }, Device { index: 1, bus_id: "0000:03:00.0".to_string() }]
+    );
+}
+
+/// amd-smi's 24.x layout: every reading is a `{"value", "unit"}` object, and an Instinct
+/// accelerator has no edge sensor.
+#[test]
+fn metrics_are_matched_to_gpus_by_bus_id() {
+    let devices = parse_list(&fixture("list-24.6.json")).unwrap();
+    // Detected in PCI order, the reverse of amd-smi's.
+    let gpus = gpus_at(&["0000:03:00.0", "0000:C1:00.0"]);
+
+    let snapshots = parse_metrics(&fixture("metric-24.6.json"), &devices, &gpus).unwrap();
+
+    let radeon = &snapshots[&0];
+    assert_eq!((radeon.utilization, radeon.memory_used_mib, radeon.memory_total_mib), (3.0, Some(1178), Some(24560)));
+    assert_eq!((radeon.temperature_c, radeon.power_w), (Some(38.0), Some(21.0)));
+    let temperatures = radeon.temperatures.as_ref().unwrap();
+    assert_eq!((temperatures[&Sensor::Edge], temperatures[&Sensor::Junction], temperatures[&Sensor::Mem]), (38.0, 40.0, 46.0));
+
+    let instinct = &snapshots[&1];
+    assert_eq!((instinct.utilization, instinct.memory_total_mib, instinct.power_w), (87.0, Some(196592), Some(412.0)));
+    assert_eq!(instinct.temperature_c, Some(71.0));
+    assert!(!instinct.temperatures.as_ref().unwrap().contains_key(&Sensor::Edge));
+    assert_eq!(instinct.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct), Some(41.0));
+}
+
+#[test]
+fn bare_readings_of_earlier_releases_parse() {
+    let output = r#"[{"gpu": 0, "usage": {"gfx_activity": 12, "umc_activity": "N/A"}, "power": {"socket_power": "35 W"},
+        "temperature": {"edge": 44, "hotspot": 47, "mem": "N/A"}, "mem_usage": {"total_vram": 16368, "used_vram": 512}}]"#;
+    let gpus = [GpuInfo { index: 0, name: "Amd GPU".to_string(), bus_id: None, render_offload: None }];
+
+    let snapshot = &parse_metrics(output, &[], &gpus).unwrap()[&0];
+
+    assert_eq!((snapshot.utilization, snapshot.memory_used_mib, snapshot.memory_total_mib), (12.0, Some(512), Some(16368)));
+    assert_eq!((snapshot.temperature_c, snapshot.power_w), (Some(44.0), Some(35.0)));
+    assert!(snapshot.memory_bandwidth.is_none());
+}
+
+#[test]
+fn rejects_records_without_utilization() {
+    let gpus = gpus_at(&["0000:03:00.0"]);
+    let devices = [Device { index: 0, bus_id: "0000:03:00.0".to_string() }];
+
+    assert!(parse_metrics(r#"[{"gpu": 0, "usage": {"gfx_activity": "N/A"}}]"#, &devices, &gpus).is_err());
+    assert!(parse_metrics("amd-smi: command failed", &devices, &gpus).is_err());
+}
+
+#[test]
+fn enumeration_lists_amd_smi_devices() {
+    let gpus = enumerate_gpus(&runner(), &GpuType::Amd);
+
+    assert_eq!(gpus.len(), 2);
+    assert_eq!(gpus[1].bus_id.as_deref(), Some("0000:03:00.0"));
+}
+
+#[test]
+fn amd_smi_is_preferred_over_radeontop() {
+    let runner = runner();
+    let mut backend = backend::select(&runner, &GpuType::Amd, false, Duration::from_secs(1), Retry::default());
+    assert_eq!(backend.name(), "amd-smi (per tick)");
+
+    let gpus = enumerate_gpus(&runner, &GpuType::Amd);
+    match &backend.poll(&gpus)[0] {
+        PollResult::Ok { snapshot, .. } => assert_eq!(snapshot.utilization, 87.0),
+        other => panic!("expected a snapshot, got {:?}", other),
+    }
+}
+
+#[test]
+fn radeontop_remains_without_amd_smi() {
+    let runner = MockRunner::new();
+
+    assert_eq!(backend::select(&runner, &GpuType::Amd, false, Duration::from_secs(1), Retry::default()).name(), "radeontop (per tick)");
+}
+
+#[test]
+fn amd_smi_counts_as_the_vendor_tool() {
+    let missing = CommandOutput::failed(1, "");
+    let runner = MockRunner::new().with("which", &["radeontop"], missing.clone()).with("which", &["amd-smi"], CommandOutput::ok("/opt/rocm/bin/amd-smi\n"));
+    assert!(check_top_exists_local(&runner, &GpuType::Amd).unwrap());
+
+    let runner = MockRunner::new().with("which", &["radeontop"], missing);
+    assert!(!check_top_exists_local(&runner, &GpuType::Amd).unwrap());
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+
+use gpu_auto_top::aperture::{parse_nvidia_memory, query_nvidia, read_vis_vram, ApertureMetrics};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{format_snapshot, parse_fields, Field, OutputContext, OutputFormat};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::snapshot::build_snapshot;
+use gpu_auto_top::GpuSnapshot;
+
+const NVIDIA_MEMORY: &str = "
+==============NVSMI LOG==============
+
+Timestamp                                 : Tue Mar  4 10:12:45 2025
+Driver Version                            : 550.54.14
+CUDA Version                              : 12.4
+
+Attached GPUs                             : 2
+GPU 00000000:3B:00.0
+    FB Memory Usage
+        Total                             : 24564 MiB
+        Reserved                          : 346 MiB
+        Used                              : 20480 MiB
+        Free                              : 3738 MiB
+    BAR1 Memory Usage
+        Total                             : 256 MiB
+        Used                              : 254 MiB
+        Free                              : 2 MiB
+    Conf Compute Protected Memory Usage
+        Total                             : 0 MiB
+        Used                              : 0 MiB
+        Free                              : 0 MiB
+
+GPU 00000000:AF:00.0
+    FB Memory Usage
+        Total                             : 24564 MiB
+        Reserved                          : 346 MiB
+        Used                              : 2 MiB
+        Free                              : 24216 MiB
+    BAR1 Memory Usage
+        Total                             : 32768 MiB
+        Used                              : 1 MiB
+        Free                              : 32767 MiB
+";
+
+fn snapshot(aperture: Option<ApertureMetrics>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(20480), memory_total_mib: Some(24564), aperture, ..common::snapshot(0, "RTX 3090", 45.0) }
+}
+
+fn context(format: OutputFormat) -> OutputContext {
+    OutputContext { format, hostname: None, labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn parses_reserved_memory_and_bar1_per_gpu() {
+    let metrics = parse_nvidia_memory(NVIDIA_MEMORY);
+
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[&0], ApertureMetrics { reserved_mib: Some(346), bar1_used_mib: Some(254), bar1_total_mib: Some(256), ..Default::default() });
+    assert_eq!(metrics[&1].bar1_total_mib, Some(32768));
+}
+
+#[test]
+fn drivers_without_a_reserved_line_still_report_bar1() {
+    let output = "GPU 00000000:01:00.0\n    FB Memory Usage\n        Total : 8192 MiB\n        Used : 10 MiB\n    BAR1 Memory Usage\n        Total : 256 MiB\n        Used : 5 MiB\n";
+
+    let metrics = parse_nvidia_memory(output);
+
+    assert_eq!(metrics[&0], ApertureMetrics { bar1_used_mib: Some(5), bar1_total_mib: Some(256), ..Default::default() });
+}
+
+#[test]
+fn queries_the_memory_section_of_nvidia_smi() {
+    let runner = MockRunner::new().with("nvidia-smi", &["-q", "-d", "MEMORY"], CommandOutput::ok(NVIDIA_MEMORY));
+
+    assert_eq!(query_nvidia(&runner).len(), 2);
+}
+
+#[test]
+fn failing_nvidia_smi_leaves_the_fields_out() {
+    let runner = MockRunner::new().with("nvidia-smi", &["-q", "-d", "MEMORY"], CommandOutput::failed(9, "NVIDIA-SMI has failed"));
+
+    assert!(query_nvidia(&runner).is_empty());
+}
+
+#[test]
+fn reads_visible_vram_from_amdgpu_sysfs() {
+    let device = std::env::temp_dir().join(format!("gpuatop-aperture-{}", std::process::id()));
+    fs::create_dir_all(&device).unwrap();
+    fs::write(device.join("mem_info_vis_vram_used"), "266338304\n").unwrap();
+    fs::write(device.join("mem_info_vis_vram_total"), "268435456\n").unwrap();
+
+    let metrics = read_vis_vram(&device);
+    let missing = read_vis_vram(&device.join("missing"));
+    fs::remove_dir_all(&device).unwrap();
+
+    assert_eq!(metrics, Some(ApertureMetrics { vis_vram_used_mib: Some(254), vis_vram_total_mib: Some(256), ..Default::default() }));
+    assert_eq!(missing, None);
+}
+
+#[test]
+fn fields_select_bar1_and_visible_vram() {
+    assert_eq!(parse_fields("bar1,vis_vram"), Ok(vec![Field::Bar1, Field::VisVram]));
+}
+
+#[test]
+fn aperture_appears_in_text_and_json() {
+    let aperture = ApertureMetrics { reserved_mib: Some(346), bar1_used_mib: Some(254), bar1_total_mib: Some(256), ..Default::default() };
+
+    let text = format_snapshot(&snapshot(Some(aperture)), &context(OutputFormat::Text));
+    let json = format_snapshot(&snapshot(Some(aperture)), &context(OutputFormat::Json));
+
+    assert!(text.ends_with("Memory: 20480/24564 MiB, Reserved: 346 MiB, BAR1: 254/256 MiB"), "{}", text);
+    assert!(json.ends_with(",\"memory_reserved_mib\":346,\"bar1_used_mib\":254,\"bar1_total_mib\":256}"), "{}", json);
+}
+
+#[test]
+fn visible_vram_appears_in_influx() {
+    let aperture = ApertureMetrics { vis_vram_used_mib: Some(254), vis_vram_total_mib: Some(256), ..Default::default() };
+
+    let line = format_snapshot(&snapshot(Some(aperture)), &context(OutputFormat::Influx));
+
+    assert!(line.contains(",vis_vram_used_mib=254i,vis_vram_total_mib=256i "), "{}", line);
+}
+
+#[test]
+fn snapshot_command_keeps_bar1_and_reserved_memory() {
+    let xml = "<nvidia_smi_log><gpu id=\"00000000:3B:00.0\">\
+        <fb_memory_usage><total>24564 MiB</total><reserved>346 MiB</reserved><used>20480 MiB</used></fb_memory_usage>\
+        <bar1_memory_usage><total>256 MiB</total><used>254 MiB</used><free>2 MiB</free></bar1_memory_usage>\
+        </gpu></nvidia_smi_log>";
+
+    let json = build_snapshot(xml, None).unwrap().to_json();
+
+    assert!(json.contains("\"reserved\":\"346 MiB\""), "{}", json);
+    assert!(json.contains("\"bar1_memory_usage\":{\"total\":\"256 MiB\",\"used\":\"254 MiB\",\"free\":\"2 MiB\"}"), "{}", json);
+}
+
+#[test]
+fn help_says_why_to_enable_the_aperture_fields() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).arg("--help").output().unwrap();
+    let help = String::from_utf8(output.stdout).unwrap();
+
+    assert!(help.contains("bar1 (NVIDIA BAR1 and reserved memory) and vis_vram (amdgpu"), "{}", help);
+    assert!(help.contains("tell \"VRAM full\" apart from \"visible aperture\n                               full\""), "{}", help);
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::budget::{parse_energy, parse_gpu_hours, parse_signal, BudgetTracker, Enforcer, Exceeded, Kind, Limits, Usage, EXIT_CODE};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, utilization: f64, power_w: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { power_w, ..common::snapshot(index, "NVIDIA A100-SXM4-80GB", utilization) }
+}
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+#[test]
+fn parses_budgets_and_signals() {
+    assert_eq!(parse_energy("500Wh"), Ok(500.0));
+    assert_eq!(parse_energy("1.5kWh"), Ok(1500.0));
+    assert_eq!(parse_energy("250"), Ok(250.0));
+    assert!(parse_energy("0Wh").is_err());
+    assert!(parse_energy("500J").is_err());
+    assert_eq!(parse_gpu_hours("2"), Ok(2.0));
+    assert_eq!(parse_gpu_hours("0.5h"), Ok(0.5));
+    assert!(parse_gpu_hours("-1").is_err());
+    assert_eq!(parse_signal("SIGINT").as_deref(), Ok("INT"));
+    assert_eq!(parse_signal("term").as_deref(), Ok("TERM"));
+    assert!(parse_signal("STOP").is_err());
+}
+
+#[test]
+fn integrates_power_and_busy_time_per_gpu() {
+    let started = Instant::now();
+    let mut tracker = BudgetTracker::new(Limits::default(), 60 * MINUTE);
+
+    tracker.record(&snapshot(0, 100.0, Some(300.0)), started);
+    tracker.record(&snapshot(1, 0.0, Some(60.0)), started);
+    tracker.record(&snapshot(0, 50.0, Some(100.0)), started + 30 * MINUTE);
+    tracker.record(&snapshot(1, 0.0, Some(60.0)), started + 30 * MINUTE);
+
+    // 200 W and 75% busy on average over half an hour.
+    assert_eq!(tracker.usage(0), Some(Usage { energy_wh: 100.0, gpu_hours: 0.375 }));
+    assert_eq!(tracker.usage(1), Some(Usage { energy_wh: 30.0, gpu_hours: 0.0 }));
+    assert_eq!(tracker.total(), Usage { energy_wh: 130.0, gpu_hours: 0.375 });
+}
+
+#[test]
+fn missed_samples_keep_the_totals_and_long_gaps_are_capped() {
+    let started = Instant::now();
+    let mut tracker = BudgetTracker::new(Limits::default(), 10 * MINUTE);
+
+    tracker.record(&snapshot(0, 100.0, Some(120.0)), started);
+    tracker.record(&snapshot(0, 100.0, Some(120.0)), started + 3 * MINUTE);
+    // Two missed ticks: the gap is bridged.
+    tracker.record(&snapshot(0, 100.0, Some(120.0)), started + 9 * MINUTE);
+    assert_eq!(tracker.usage(0).map(|usage| usage.energy_wh), Some(18.0));
+
+    // A suspend of an hour counts for ten minutes.
+    tracker.record(&snapshot(0, 100.0, Some(120.0)), started + 69 * MINUTE);
+    assert_eq!(tracker.usage(0).map(|usage| usage.energy_wh), Some(38.0));
+
+    // A sample without power still counts the busy time, but no energy.
+    tracker.record(&snapshot(0, 100.0, None), started + 75 * MINUTE);
+    let usage = tracker.usage(0).unwrap();
+    assert_eq!(usage.energy_wh, 38.0);
+    assert!((usage.gpu_hours - 25.0 / 60.0).abs() < 1e-9);
+}
+
+#[test]
+fn each_budget_is_exceeded_once_over_all_gpus() {
+    let started = Instant::now();
+    let limits = Limits { max_energy_wh: Some(25.0), max_gpu_hours: Some(1.0) };
+    let mut tracker = BudgetTracker::new(limits, 10 * MINUTE);
+
+    for gpu in 0..2 {
+        assert_eq!(tracker.record(&snapshot(gpu, 100.0, Some(120.0)), started), []);
+    }
+    assert_eq!(tracker.record(&snapshot(0, 100.0, Some(120.0)), started + 6 * MINUTE), []);
+    let exceeded = tracker.record(&snapshot(1, 100.0, Some(180.0)), started + 6 * MINUTE);
+    assert_eq!(exceeded, [Exceeded { kind: Kind::Energy, limit: 25.0, used: 27.0 }]);
+    assert_eq!(exceeded[0].message(), "Energy budget exceeded: 27.0 Wh of 25 Wh");
+    assert_eq!(tracker.record(&snapshot(0, 100.0, Some(120.0)), started + 12 * MINUTE), []);
+
+    assert_eq!(
+        tracker.format_summary(),
+        ["Budget:", "  Energy: 39.0 of 25 Wh (156%)", "  GPU-hours: 0.300 of 1 (30%)", "  GPU 0: 24.0 Wh, 0.200 GPU-hours", "  GPU 1: 15.0 Wh, 0.100 GPU-hours"]
+    );
+}
+
+#[test]
+fn signals_the_process_group_then_kills_it_after_the_grace_period() {
+    let runner = MockRunner::new().with("kill", &["-s", "TERM", "--", "-4242"], CommandOutput::ok("")).with("kill", &["-s", "KILL", "--", "-4242"], CommandOutput::ok(""));
+    let started = Instant::now();
+    let mut enforcer = Enforcer::new(4242, "TERM".to_string(), Duration::from_secs(10));
+
+    assert!(!enforcer.stopped());
+    enforcer.stop(&runner, started).unwrap();
+    assert!(enforcer.stopped());
+    assert!(!enforcer.tick(&runner, started + Duration::from_secs(9)).unwrap());
+    assert!(enforcer.tick(&runner, started + Duration::from_secs(10)).unwrap());
+    assert!(!enforcer.tick(&runner, started + Duration::from_secs(20)).unwrap());
+
+    let failing = MockRunner::new().with("kill", &["-s", "TERM", "--", "-4242"], CommandOutput::failed(1, "kill: (-4242): No such process"));
+    let error = Enforcer::new(4242, "TERM".to_string(), Duration::from_secs(10)).stop(&failing, started).unwrap_err();
+    assert_eq!(error.to_string(), "kill -s TERM failed: kill: (-4242): No such process");
+}
+
+#[test]
+fn exec_stops_the_command_once_the_budget_is_spent() {
+    let dir = common::fake_tools("budget");
+    let started = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["exec", "--interval", "100ms", "--allow-fast-poll", "--max-energy", "0.001Wh", "--", "sleep", "30"])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(EXIT_CODE), "{}", stdout);
+    assert!(started.elapsed() < Duration::from_secs(20));
+    assert!(stdout.contains("Energy budget exceeded: "), "{}", stdout);
+    assert!(stdout.contains("Stopping the command: SIGTERM to its process group"), "{}", stdout);
+    assert!(stdout.contains("\nBudget:\n  Energy: "), "{}", stdout);
+}
+
+#[test]
+fn budgets_only_report_without_a_command() {
+    let dir = common::fake_tools("budget-monitor");
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["--count", "3", "--interval", "100ms", "--allow-fast-poll", "--max-energy", "0.001Wh"])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(stdout.contains("Energy budget exceeded: "), "{}", stdout);
+    assert!(!stdout.contains("Stopping the command"), "{}", stdout);
+}
+
+#[test]
+fn exec_requires_a_command() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["exec", "--max-energy", "1Wh"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: exec requires a command: gpuatop exec [options] -- <command>\n");
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::Command;
+
+use gpu_auto_top::check::{evaluate, format_perfdata, Expression, Metric, Status, Thresholds};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, utilization: f64, temperature_c: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { temperature_c, ..common::snapshot(index, "NVIDIA A100-SXM4-80GB", utilization) }
+}
+
+fn thresholds(warn: &str, crit: &str) -> Thresholds {
+    Thresholds { warn: warn.parse().unwrap(), crit: crit.parse().unwrap() }
+}
+
+#[test]
+fn perfdata_drops_trailing_empty_thresholds_and_quotes_odd_labels() {
+    assert_eq!(format_perfdata("util", 42.0, "90", "95"), "util=42;90;95");
+    assert_eq!(format_perfdata("temp", 61.5, "", "95"), "temp=61.5;;95");
+    assert_eq!(format_perfdata("util", 42.0, "90", ""), "util=42;90");
+    assert_eq!(format_perfdata("power", 120.504, "", ""), "power=120.5");
+    assert_eq!(format_perfdata("gpu0_mem", 100.0, "", ""), "gpu0_mem=100");
+    assert_eq!(format_perfdata("gpu 0's util", 0.0, "10:", ""), "'gpu 0''s util'=0;10:");
+}
+
+#[test]
+fn expressions_combine_conditions_with_and_and_or() {
+    let expression: Expression = "util>90 or temp >= 85 and util < 10".parse().unwrap();
+
+    assert!(expression.matches(&snapshot(0, 95.0, None)));
+    assert!(expression.matches(&snapshot(0, 5.0, Some(85.0))));
+    assert!(!expression.matches(&snapshot(0, 50.0, Some(90.0))));
+    assert_eq!(expression.range(Metric::Util), "90");
+    assert_eq!(expression.range(Metric::Temp), "85");
+    assert_eq!("util<10".parse::<Expression>().unwrap().range(Metric::Util), "10:");
+    assert_eq!(expression.range(Metric::Power), "");
+
+    assert_eq!("fan>3".parse::<Expression>().unwrap_err(), "Unknown metric: fan (expected util, mem, temp or power)");
+    assert!("temp 85".parse::<Expression>().is_err());
+    assert!("temp>hot".parse::<Expression>().is_err());
+    assert!("temp>85 or".parse::<Expression>().is_err());
+}
+
+#[test]
+fn a_single_gpu_has_plain_labels() {
+    let (status, line) = evaluate(&[Ok(snapshot(0, 42.0, Some(61.0)))], &thresholds("util>90 or temp>85", "temp>95"));
+
+    assert_eq!(status, Status::Ok);
+    assert_eq!(line, "GPU OK - gpu0 util=42% temp=61C | util=42;90 temp=61;85;95");
+}
+
+#[test]
+fn several_gpus_report_the_worst_and_all_perfdata() {
+    let thresholds = thresholds("temp>85", "temp>95");
+    let samples = [Ok(snapshot(0, 42.0, Some(61.0))), Ok(snapshot(1, 99.0, Some(88.0))), Ok(snapshot(2, 10.0, Some(40.0)))];
+
+    let (status, line) = evaluate(&samples, &thresholds);
+
+    assert_eq!((status, status.exit_code()), (Status::Warning, 1));
+    assert_eq!(
+        line,
+        "GPU WARNING - gpu1 util=99% temp=88C | gpu0_util=42 gpu0_temp=61;85;95 gpu1_util=99 gpu1_temp=88;85;95 gpu2_util=10 gpu2_temp=40;85;95"
+    );
+}
+
+#[test]
+fn failed_samples_are_unknown_unless_another_gpu_is_critical() {
+    let thresholds = thresholds("", "temp>95");
+    let failed = Err("GPU 1: nvidia-smi timed out".to_string());
+
+    let (status, line) = evaluate(&[Ok(snapshot(0, 42.0, Some(61.0))), failed.clone()], &thresholds);
+    assert_eq!((status, status.exit_code()), (Status::Unknown, 3));
+    assert!(line.starts_with("GPU UNKNOWN - GPU 1: nvidia-smi timed out | gpu0_util=42"), "{}", line);
+
+    let (status, _) = evaluate(&[Ok(snapshot(0, 42.0, Some(97.0))), failed], &thresholds);
+    assert_eq!((status, status.exit_code()), (Status::Critical, 2));
+
+    assert_eq!(evaluate(&[], &thresholds), (Status::Unknown, "GPU UNKNOWN - no GPU found".to_string()));
+}
+
+#[test]
+fn the_subcommand_prints_one_line_and_exits_with_the_status() {
+    let dir = common::fake_tools("check");
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).env("PATH", common::path_with(&dir)).output().unwrap();
+
+    let ok = run(&["check", "--warn", "util>90 or temp>85", "--crit", "temp>95"]);
+    let critical = run(&["check", "--crit", "util>=45"]);
+    let invalid = run(&["check", "--warn", "fan>3"]);
+    let misplaced = run(&["--warn", "util>90"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&ok.stdout), "GPU OK - gpu0 util=45% temp=60C | util=45;90 mem=4.17 temp=60;85;95 power=120.5\n");
+    assert_eq!(ok.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&critical.stdout).starts_with("GPU CRITICAL - gpu0 util=45% |"));
+    assert_eq!(critical.status.code(), Some(2));
+    assert_eq!(invalid.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&misplaced.stderr).contains("--warn and --crit require the check subcommand"));
+}
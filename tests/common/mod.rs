@@ -0,0 +1,46 @@
+// Each test crate uses its own subset of these helpers.
+#![allow(dead_code)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use gpu_auto_top::{GpuInfo, GpuSnapshot};
+
+const FAKE_LSPCI: &str = "#!/bin/sh\necho '3b:00.0 VGA compatible controller: NVIDIA Corporation GA102'\n";
+const FAKE_NVIDIA_SMI: &str = "#!/bin/sh
+case \"$*\" in
+  pmon*) printf '# gpu pid type sm mem enc dec fb command\\n# Idx # C/G %% %% %% %% MB name\\n    0 %s C 30 10 - - 2048 python\\n' \"$PPID\" ;;
+  *temperature.memory*) echo '0, 60, 78' ;;
+  *utilization.gpu*) echo '0, 45, 1024, 24576, 60, 120.50, [N/A], 00000000:3B:00.0' ;;
+  *pci.bus_id*) echo '0, 00000000:3B:00.0, NVIDIA GeForce RTX 3090' ;;
+  *) exit 1 ;;
+esac
+";
+
+fn write_script(dir: &Path, name: &str, content: &str) {
+    let path = dir.join(name);
+    fs::write(&path, content).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Creates a directory with fake `lspci` and `nvidia-smi` reporting one NVIDIA GPU, busy with
+/// one process: the fake's parent, gpuatop itself.
+pub fn fake_tools(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gpuatop-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    write_script(&dir, "lspci", FAKE_LSPCI);
+    write_script(&dir, "nvidia-smi", FAKE_NVIDIA_SMI);
+    dir
+}
+
+/// `PATH` with the fake tools first.
+pub fn path_with(dir: &Path) -> String {
+    format!("{}:{}", dir.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+/// A sample of GPU `index` called `name` with only its utilization, for tests to fill in with
+/// struct-update syntax.
+pub fn snapshot(index: u32, name: &str, utilization: f64) -> GpuSnapshot {
+    GpuSnapshot::new(GpuInfo { index, name: name.to_string(), bus_id: None, render_offload: None }, utilization)
+}
@@ -0,0 +1,37 @@
+use gpu_auto_top::csv::{decimal, number, parse_line};
+
+#[test]
+fn splits_and_trims_fields() {
+    assert_eq!(parse_line("0, 45, 1024 , 24576"), ["0", "45", "1024", "24576"]);
+    assert_eq!(parse_line("0,,  \t"), ["0", "", ""]);
+    assert_eq!(parse_line(""), [""]);
+}
+
+#[test]
+fn quoted_fields_keep_commas_and_escaped_quotes() {
+    assert_eq!(parse_line("0, \"NVIDIA GeForce RTX 4090, rev A\", 3"), ["0", "NVIDIA GeForce RTX 4090, rev A", "3"]);
+    assert_eq!(parse_line("\"Quadro \"\"Turing\"\"\"\r"), ["Quadro \"Turing\""]);
+    assert_eq!(parse_line("\"unterminated, still one field"), ["unterminated, still one field"]);
+    assert_eq!(parse_line("\"a\"b, c"), ["a", "c"]);
+}
+
+#[test]
+fn numbers_drop_units_and_unavailable_values() {
+    assert_eq!(number::<f32>("45 %"), Some(45.0));
+    assert_eq!(number::<f32>(" 120.50 W "), Some(120.5));
+    assert_eq!(number::<u64>("1024 MiB"), Some(1024));
+    assert_eq!(number::<u32>("7"), Some(7));
+    assert_eq!(number::<f32>("[N/A]"), None);
+    assert_eq!(number::<f32>("[Not Supported]"), None);
+    assert_eq!(number::<f32>(""), None);
+}
+
+#[test]
+fn decimals_accept_a_decimal_comma() {
+    assert_eq!(decimal("42.37 %"), Some(42.37));
+    assert_eq!(decimal("42,37"), Some(42.37));
+    assert_eq!(decimal("7"), Some(7.0));
+    assert_eq!(decimal("1,234.5"), None);
+    assert_eq!(decimal("NaN"), None);
+    assert_eq!(decimal("[N/A]"), None);
+}
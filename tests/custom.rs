@@ -0,0 +1,115 @@
+#![cfg(feature = "cli")]
+
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::config;
+use gpu_auto_top::custom::{from_config, CustomBackend};
+use gpu_auto_top::runner::{CommandOutput, MockRunner, RealRunner};
+use gpu_auto_top::PollResult;
+
+/// The regex example of `default_config.toml`.
+const EXAMPLE_REGEX: &str = r"^(?P<name>[^,]+), (?P<util>[\d.]+), (?P<mem_used_mib>\d+), (?P<mem_total_mib>\d+), (?P<temp_c>\d+)$";
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/custom/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn backend(table: &str) -> Result<CustomBackend, String> {
+    let document = config::parse(&format!("[[custom_backend]]\nname = \"test\"\n{}", table))?;
+    Ok(from_config(&document)?.remove(0))
+}
+
+fn regex_backend(path: &str, regex: &str) -> CustomBackend {
+    backend(&format!("command = [\"cat\", \"{}\"]\nregex = '{}'\n", path, regex)).unwrap()
+}
+
+#[test]
+fn the_example_regex_reads_every_device() {
+    let backend = regex_backend(&fixture("query.csv"), EXAMPLE_REGEX);
+
+    let gpus = backend.enumerate(&RealRunner, 2).unwrap();
+    assert_eq!(gpus.iter().map(|gpu| (gpu.index, gpu.name.as_str())).collect::<Vec<_>>(), [(2, "NVIDIA GeForce RTX 3090"), (3, "Tesla T4")]);
+
+    let results = backend.poll(&RealRunner, &gpus[1..], &gpus);
+    match &results[0] {
+        PollResult::Ok { snapshot, .. } => {
+            assert_eq!((snapshot.utilization, snapshot.memory_used_mib, snapshot.memory_total_mib), (3.0, Some(10), Some(15360)));
+            assert_eq!((snapshot.temperature_c, snapshot.power_w), (Some(40.0), None));
+        }
+        other => panic!("expected a snapshot, got {:?}", other),
+    }
+}
+
+#[test]
+fn json_fields_follow_their_paths() {
+    let table = format!(
+        "command = [\"cat\", \"{}\"]\njson_devices = \".devices\"\nfield.util = \".utilization\"\nfield.mem_used_mib = \".memory.used\"\nfield.name = \".name\"\n",
+        fixture("npu.json")
+    );
+    let backend = backend(&table).unwrap();
+
+    let gpus = backend.enumerate(&RealRunner, 0).unwrap();
+    assert_eq!(gpus[1].name, "NPU 1");
+    let results = backend.poll(&RealRunner, &gpus, &gpus);
+    match (&results[0], &results[1]) {
+        (PollResult::Ok { snapshot: first, .. }, PollResult::Ok { snapshot: second, .. }) => {
+            assert_eq!((first.utilization, first.memory_used_mib), (12.5, Some(2048)));
+            assert_eq!((second.utilization, second.memory_used_mib), (99.0, None));
+        }
+        other => panic!("expected snapshots, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_definitions_are_rejected() {
+    let errors = [
+        ("command = \"x\"\nregex = '(?P<util>\\d+'", "invalid regex"),
+        ("command = \"x\"\nregex = '(?P<fan>\\d+)'", "unknown field 'fan'"),
+        ("command = \"x\"\nregex = '(?P<temp_c>\\d+)'", "must capture 'util'"),
+        ("command = \"x\"\njson_devices = \".devices\"", "requires field.util"),
+        ("regex = '(?P<util>\\d+)'", "requires a command"),
+        ("command = \"x\"", "exactly one of regex or json_devices"),
+    ];
+
+    for (table, message) in errors {
+        let error = backend(table).unwrap_err();
+        assert!(error.starts_with("custom_backend 'test': ") && error.contains(message), "{}: {}", table, error);
+    }
+}
+
+#[test]
+fn output_without_a_value_is_a_transient_error() {
+    let backend = regex_backend(&fixture("npu.json"), EXAMPLE_REGEX);
+
+    assert!(backend.enumerate(&RealRunner, 0).unwrap().is_empty());
+    let gpus = regex_backend(&fixture("query.csv"), EXAMPLE_REGEX).enumerate(&RealRunner, 0).unwrap();
+    assert!(matches!(&backend.poll(&RealRunner, &gpus, &gpus)[0], PollResult::TransientError { message, .. } if message.contains("no value")));
+}
+
+/// Used to overflow the stack of the backtracking regex engine.
+#[test]
+fn large_outputs_parse_quickly() {
+    let unseparated = "9".repeat(100_000);
+    let many = "GPU, 50, 1, 2, 3\n".repeat(2_000);
+    let runner = MockRunner::new().with("cat", &["unseparated.txt"], CommandOutput::ok(&unseparated)).with("cat", &["many.csv"], CommandOutput::ok(&many));
+
+    let started = Instant::now();
+    let unseparated = regex_backend("unseparated.txt", "(?P<util>[^,]*),").enumerate(&runner, 0);
+    let many = regex_backend("many.csv", EXAMPLE_REGEX).enumerate(&runner, 0);
+
+    assert!(unseparated.unwrap().is_empty());
+    assert_eq!(many.unwrap().len(), 2_000);
+    assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+}
+
+#[test]
+fn failing_commands_are_reported() {
+    let backend = backend("command = [\"npu-smi\", \"info\"]\nregex = '(?P<util>\\d+)'\n").unwrap();
+    let runner = MockRunner::new().with("npu-smi", &["info"], CommandOutput::failed(2, "dcmi init failed"));
+
+    assert_eq!(backend.enumerate(&runner, 0).unwrap_err(), "npu-smi exited with code 2");
+
+    let gpus = regex_backend(&fixture("query.csv"), EXAMPLE_REGEX).enumerate(&RealRunner, 0).unwrap();
+    assert!(matches!(&backend.poll(&runner, &gpus, &gpus)[0], PollResult::TransientError { message, .. } if message == "npu-smi exited with code 2"));
+    assert!(matches!(&backend.poll(&MockRunner::new(), &gpus, &gpus)[0], PollResult::PermanentError { .. }));
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::delta::{diff_snapshots, format_json, Change, DeltaTracker, FieldChange};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(utilization: f64, temperature_c: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot {
+        memory_used_mib: Some(1024),
+        memory_total_mib: Some(24576),
+        temperature_c,
+        power_w: Some(120.5),
+        ..common::snapshot(0, "NVIDIA GeForce RTX 3090", utilization)
+    }
+}
+
+#[test]
+fn identical_snapshots_have_no_changes() {
+    assert!(diff_snapshots(&snapshot(45.0, Some(60.0)), &snapshot(45.0, Some(60.0)), 0.0).is_empty());
+}
+
+#[test]
+fn changes_within_the_threshold_are_ignored() {
+    let delta = diff_snapshots(&snapshot(50.0, Some(60.0)), &snapshot(52.0, Some(64.0)), 5.0);
+
+    assert_eq!(delta.changes, vec![FieldChange { field: "temperature_c", previous: Some(60.0), current: Some(64.0) }]);
+}
+
+#[test]
+fn any_change_from_zero_exceeds_the_threshold() {
+    let delta = diff_snapshots(&snapshot(0.0, Some(60.0)), &snapshot(1.0, Some(60.0)), 50.0);
+
+    assert_eq!(delta.changes.len(), 1);
+    assert_eq!(delta.changes[0].field, "utilization");
+}
+
+#[test]
+fn disappearing_metrics_are_changes() {
+    let delta = diff_snapshots(&snapshot(45.0, Some(60.0)), &snapshot(45.0, None), 100.0);
+
+    assert_eq!(delta.changes, vec![FieldChange { field: "temperature_c", previous: Some(60.0), current: None }]);
+}
+
+#[test]
+fn tracker_compares_against_the_last_printed_sample() {
+    let mut tracker = DeltaTracker::new(5.0);
+
+    assert_eq!(tracker.update(&snapshot(50.0, Some(60.0))), Change::First);
+    assert_eq!(tracker.update(&snapshot(51.5, Some(60.0))), Change::Unchanged);
+    // 3% per sample, but 6% since the last printed one.
+    assert!(matches!(tracker.update(&snapshot(53.0, Some(60.0))), Change::Changed(_)));
+    assert_eq!(tracker.update(&snapshot(53.0, Some(60.0))), Change::Unchanged);
+}
+
+#[test]
+fn json_delta_holds_only_changed_fields() {
+    let context = OutputContext { format: OutputFormat::Ndjson, hostname: Some("node1".to_string()), labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 };
+    let delta = diff_snapshots(&snapshot(45.0, Some(60.0)), &snapshot(47.5, None), 0.0);
+
+    assert_eq!(format_json(&delta, &context), r#"{"schema_version":1,"hostname":"node1","gpu":0,"delta":{"utilization":47.5,"temperature_c":null}}"#);
+}
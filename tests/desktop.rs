@@ -0,0 +1,52 @@
+use gpu_auto_top::config;
+use gpu_auto_top::desktop::{DesktopClassifier, UsageSplit};
+use gpu_auto_top::process::GpuProcess;
+
+fn process(gpu_index: u32, pid: u32, name: &str, utilization: Option<f32>) -> GpuProcess {
+    GpuProcess { gpu_index, pid, name: name.to_string(), utilization, memory_used_mib: None, kind: None }
+}
+
+#[test]
+fn compositors_are_desktop() {
+    let classifier = DesktopClassifier::default();
+    for name in ["Xorg", "Xwayland", "gnome-shell", "kwin_wayland", "sway", "Hyprland", "mutter", "/usr/lib/xorg/Xorg"] {
+        assert!(classifier.is_desktop(name), "{}", name);
+    }
+    assert!(!classifier.is_desktop("python3"));
+    assert!(!classifier.is_desktop("Xorg-wrapper"));
+}
+
+#[test]
+fn truncated_names_match_their_full_entry() {
+    let config = config::parse("[desktop]\nprocesses = [\"xdg-desktop-portal-gnome\"]\n").unwrap();
+    let classifier = DesktopClassifier::from_config(&config).unwrap();
+    assert!(classifier.is_desktop("xdg-desktop-por"));
+    assert!(!classifier.is_desktop("xdg-desktop"));
+}
+
+#[test]
+fn config_extends_the_default_list() {
+    let config = config::parse("[desktop]\nprocesses = [\"picom\"]\n").unwrap();
+    let classifier = DesktopClassifier::from_config(&config).unwrap();
+    assert!(classifier.is_desktop("picom"));
+    assert!(classifier.is_desktop("Xorg"));
+
+    let invalid = config::parse("[desktop]\nprocesses = \"picom\"\n").unwrap();
+    assert!(DesktopClassifier::from_config(&invalid).is_err());
+}
+
+#[test]
+fn utilization_is_split_per_gpu() {
+    let processes = [
+        process(0, 100, "Xorg", Some(5.0)),
+        process(0, 101, "Xwayland", Some(2.0)),
+        process(0, 200, "blender", Some(60.0)),
+        process(1, 300, "python3", Some(90.0)),
+        process(2, 400, "ollama", None),
+    ];
+
+    let splits = DesktopClassifier::default().split(&processes);
+    assert_eq!(splits.get(&0), Some(&UsageSplit { desktop: 7.0, apps: 60.0 }));
+    assert_eq!(splits.get(&1), Some(&UsageSplit { desktop: 0.0, apps: 90.0 }));
+    assert_eq!(splits.get(&2), None);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::time::Duration;
+
+use gpu_auto_top::display::detail::{format_detail, sparkline, History};
+use gpu_auto_top::process::{ContextKind, GpuProcess};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(utilization: f64, temperature_c: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(11500), memory_total_mib: Some(23034), temperature_c, ..common::snapshot(1, "NVIDIA L4", utilization) }
+}
+
+#[test]
+fn sparklines_scale_to_the_maximum() {
+    assert_eq!(sparkline(&[Some(0.0), Some(50.0), Some(100.0), None, Some(150.0)], 100.0), "▁▅█ █");
+    assert_eq!(sparkline(&[Some(0.0), Some(0.0)], 0.0), "▁▁");
+}
+
+#[test]
+fn history_keeps_the_last_minute() {
+    let mut history = History::new(Duration::from_secs(10));
+    for utilization in [0.0, 100.0, 0.0, 0.0, 0.0, 0.0, 100.0, 50.0] {
+        history.record(&snapshot(utilization, Some(40.0)));
+    }
+
+    assert_eq!(
+        history.sparklines(1),
+        ["  Last 60s:", "    Utilization: ▁▁▁▁█▅", "    Memory:      ▄▄▄▄▄▄", "    Temperature: ██████", "    Headroom:    11534 MiB free, at least 11534 MiB"]
+    );
+    assert!(history.sparklines(0).is_empty());
+}
+
+#[test]
+fn headroom_is_the_least_free_memory_in_the_window() {
+    let mut history = History::new(Duration::from_secs(10));
+    // The 20000 MiB peak scrolls out of the six-point window with the seventh sample.
+    for used in [20000, 12000, 14000, 11500, 13000, 12500, 11000] {
+        history.record(&GpuSnapshot { memory_used_mib: Some(used), ..snapshot(50.0, None) });
+    }
+    assert_eq!(history.headroom(1), Some((12034, 9034)));
+
+    history.record(&GpuSnapshot { memory_used_mib: None, ..snapshot(50.0, None) });
+    assert_eq!(history.headroom(1), None);
+    assert_eq!(history.headroom(0), None);
+}
+
+#[test]
+fn short_intervals_show_at_most_sixty_points() {
+    let mut history = History::new(Duration::from_millis(250));
+    for _ in 0..100 {
+        history.record(&snapshot(20.0, None));
+    }
+
+    let lines = history.sparklines(1);
+    assert_eq!(lines[0], "  Last 15s:");
+    assert_eq!(lines[1].chars().filter(|c| *c == '▂').count(), 60);
+}
+
+#[test]
+fn the_detail_view_has_the_block_history_and_processes() {
+    let mut history = History::new(Duration::from_secs(1));
+    history.record(&snapshot(45.0, None));
+    let process = GpuProcess { gpu_index: 1, pid: 4242, name: "python".to_string(), utilization: Some(45.0), memory_used_mib: Some(11000), kind: Some(ContextKind::Compute) };
+
+    let lines = format_detail(&snapshot(45.0, None), &history, &[&process]);
+
+    assert_eq!(lines[0], "GPU 1 (NVIDIA L4)");
+    assert!(lines.iter().any(|line| line == "  Last 60s:"), "{:?}", lines);
+    assert!(lines.iter().any(|line| line == "  Processes: 1 compute, 0 graphics"), "{:?}", lines);
+    assert!(lines.last().unwrap().contains("python"), "{:?}", lines);
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::process::Command;
+
+use gpu_auto_top::display::decide;
+
+#[test]
+fn flags_win_over_no_color_and_the_terminal() {
+    let no_color = Some(OsStr::new("1"));
+
+    assert!(decide(false, false, None, true));
+    assert!(!decide(false, false, None, false));
+    assert!(!decide(false, false, no_color, true));
+    // An empty NO_COLOR does not count, by the convention.
+    assert!(decide(false, false, Some(OsStr::new("")), true));
+    assert!(!decide(false, true, None, true));
+    assert!(decide(true, false, no_color, false));
+    assert!(decide(true, true, None, false));
+}
+
+#[test]
+fn force_color_highlights_the_total_row_in_a_pipe() {
+    let dir = common::fake_tools("display");
+    let gpuatop = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+            .args(["-q", "--count", "1", "--aggregate"])
+            .args(extra)
+            .env("PATH", common::path_with(&dir))
+            .env("XDG_RUNTIME_DIR", &dir)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let plain = gpuatop(&[]);
+    let forced = gpuatop(&["--force-color"]);
+    let disabled = gpuatop(&["--force-color", "--no-color"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(plain.contains("Total") && !plain.contains('\x1b'), "{:?}", plain);
+    assert!(forced.contains("\x1b[1;7m"), "{:?}", forced);
+    assert!(disabled.contains('\x1b'), "--force-color is checked first: {:?}", disabled);
+}
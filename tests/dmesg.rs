@@ -0,0 +1,91 @@
+#![cfg(feature = "cli")]
+
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::dmesg::{format_message, parse_dmesg, query, KernelLog, KernelMessage, Level, DMESG_ARGS, POLL_INTERVAL};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+
+const DMESG: &str = "\
+kern  :warn  : 2026-10-16T14:01:02,104211+00:00 nvidia: loading out-of-tree module taints kernel.
+kern  :err   : 2026-10-16T14:02:40,512345+00:00 usb 1-2: device descriptor read/64, error -71
+kern  :info  : 2026-10-16T14:02:41,000001+00:00 [drm] Initialized nvidia-drm 0.0.0 for 0000:3b:00.0
+kern  :err   : 2026-10-16T14:03:12,512345+00:00 NVRM: Xid (PCI:0000:3b:00): 79, pid=1234, GPU has fallen off the bus.
+kern  :crit  : amdgpu 0000:0a:00.0: amdgpu: GPU reset begin!
+";
+
+fn message(level: Level, text: &str) -> KernelMessage {
+    KernelMessage { level, time: None, text: text.to_string() }
+}
+
+#[test]
+fn keeps_the_gpu_drivers_errors_and_warnings() {
+    let messages = parse_dmesg(DMESG);
+
+    assert_eq!(
+        messages,
+        [
+            KernelMessage { level: Level::Warning, time: Some("2026-10-16T14:01:02".to_string()), text: "nvidia: loading out-of-tree module taints kernel.".to_string() },
+            KernelMessage {
+                level: Level::Error,
+                time: Some("2026-10-16T14:03:12".to_string()),
+                text: "NVRM: Xid (PCI:0000:3b:00): 79, pid=1234, GPU has fallen off the bus.".to_string()
+            },
+            message(Level::Error, "amdgpu 0000:0a:00.0: amdgpu: GPU reset begin!"),
+        ]
+    );
+}
+
+#[test]
+fn messages_are_cut_to_the_width_and_colored_by_level() {
+    let xid = &parse_dmesg(DMESG)[1];
+
+    assert_eq!(format_message(xid, 120, false), "[kernel] 2026-10-16T14:03:12 NVRM: Xid (PCI:0000:3b:00): 79, pid=1234, GPU has fallen off the bus.");
+    assert_eq!(format_message(xid, 40, false), "[kernel] 2026-10-16T14:03:12 NVRM: Xid …");
+    assert_eq!(format_message(&message(Level::Error, "drm: hang"), 80, true), "\x1b[31m[kernel] drm: hang\x1b[0m");
+    assert_eq!(format_message(&message(Level::Warning, "drm: slow"), 80, true), "\x1b[33m[kernel] drm: slow\x1b[0m");
+}
+
+#[test]
+fn shows_the_backlog_then_only_new_messages() {
+    let all: Vec<KernelMessage> = (0..15).map(|n| message(Level::Warning, &format!("i915: warning {}", n))).collect();
+    let mut log = KernelLog::new();
+
+    let backlog = log.update(all[..12].to_vec());
+    assert_eq!(backlog, all[2..12]);
+    assert_eq!(log.update(all.clone()), all[12..]);
+    assert!(log.update(all.clone()).is_empty());
+
+    // The last message shown rotated out of the buffer: everything read is new.
+    let rotated = vec![message(Level::Error, "drm: a"), message(Level::Error, "drm: b")];
+    assert_eq!(log.update(rotated.clone()), rotated);
+
+    log.close();
+    assert_eq!(log.update(all.clone()), all[5..]);
+}
+
+#[test]
+fn polls_every_interval_until_dmesg_fails() {
+    let start = Instant::now();
+    let mut log = KernelLog::new();
+
+    assert!(!log.is_open());
+    assert!(log.due(start));
+    assert!(log.is_open());
+    assert!(!log.due(start + Duration::from_secs(1)));
+    assert!(log.due(start + POLL_INTERVAL));
+
+    log.fail();
+    assert!(!log.due(start + POLL_INTERVAL * 3));
+    log.close();
+    assert!(!log.is_open());
+    assert!(log.due(start + POLL_INTERVAL * 3));
+}
+
+#[test]
+fn reports_why_dmesg_failed() {
+    let denied = MockRunner::new().with("dmesg", &DMESG_ARGS, CommandOutput::failed(1, "dmesg: read kernel buffer failed: Operation not permitted\n"));
+    let readable = MockRunner::new().with("dmesg", &DMESG_ARGS, CommandOutput::ok(DMESG));
+
+    assert_eq!(query(&denied), Err("dmesg: read kernel buffer failed: Operation not permitted".to_string()));
+    assert_eq!(query(&readable).unwrap().len(), 3);
+}
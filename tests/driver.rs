@@ -0,0 +1,38 @@
+use gpu_auto_top::driver::{parse_nvidia_smi_version, query_nvidia_version, requires_driver_version, DriverVersion, Metric};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+
+const NVIDIA_SMI_VERSION: &str = "NVIDIA-SMI version  : 535.129.03
+NVML version        : 535.129
+DRIVER version      : 535.129.03
+CUDA Version        : 12.2
+";
+
+#[test]
+fn parses_and_orders_versions_like_semver() {
+    assert_eq!("535.129.03".parse(), Ok(DriverVersion::new(535, 129, 3)));
+    assert_eq!("470.57".parse(), Ok(DriverVersion::new(470, 57, 0)));
+    assert_eq!("550".parse(), Ok(DriverVersion::new(550, 0, 0)));
+    assert!("535.129.03.1".parse::<DriverVersion>().is_err());
+    assert_eq!("r535".parse::<DriverVersion>(), Err("Invalid driver version: r535".to_string()));
+
+    assert!(DriverVersion::new(470, 199, 2) < DriverVersion::new(535, 54, 3));
+    assert!(DriverVersion::new(535, 54, 3) < DriverVersion::new(535, 129, 3));
+    assert_eq!(DriverVersion::new(470, 57, 2).to_string(), "470.57.02");
+}
+
+#[test]
+fn newer_metrics_require_a_newer_driver() {
+    assert!(requires_driver_version(Metric::NvLinkThroughput, DriverVersion::new(460, 91, 3)));
+    assert!(!requires_driver_version(Metric::NvLinkThroughput, DriverVersion::new(465, 19, 1)));
+    assert!(requires_driver_version(Metric::MemoryTemperature, DriverVersion::new(450, 80, 2)));
+    assert!(!requires_driver_version(Metric::MemoryTemperature, DriverVersion::new(535, 129, 3)));
+}
+
+#[test]
+fn reads_the_driver_version_from_nvidia_smi() {
+    assert_eq!(parse_nvidia_smi_version(NVIDIA_SMI_VERSION), Some(DriverVersion::new(535, 129, 3)));
+    assert_eq!(parse_nvidia_smi_version("NVIDIA-SMI has failed"), None);
+
+    let runner = MockRunner::new().with("nvidia-smi", &["--version"], CommandOutput::ok(NVIDIA_SMI_VERSION));
+    assert_eq!(query_nvidia_version(&runner), Some(DriverVersion::new(535, 129, 3)));
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::{Duration, Instant};
+
+fn run(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn stops_on_the_clock_and_prints_the_summary() {
+    let started = Instant::now();
+    let output = run("duration", &["--duration", "1s", "--interval", "100ms", "--display-interval", "100ms", "--allow-fast-poll"]);
+    let elapsed = started.elapsed();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    let samples = stdout.matches("Utilization (percent): 45.0").count();
+    assert!((5..=11).contains(&samples), "{}", stdout);
+    assert!(stdout.contains("[--duration elapsed]\n"), "{}", stdout);
+    assert!(stdout.contains("Samples"), "{}", stdout);
+}
+
+#[test]
+fn the_duration_ends_a_sampling_window_early() {
+    let started = Instant::now();
+    let output = run("duration-window", &["--duration", "500ms", "--interval", "100ms", "--display-interval", "1m", "--allow-fast-poll"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().matches("Utilization (percent): 45.0").count(), 1);
+}
+
+#[test]
+fn exec_ends_with_the_command_when_it_exits_first() {
+    let output = run("duration-exec", &["exec", "--duration", "1m", "--interval", "100ms", "--allow-fast-poll", "--", "sleep", "0.3"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(stdout.contains("[The command exited before the --duration elapsed]\n"), "{}", stdout);
+    assert!(!stdout.contains("[--duration elapsed]"), "{}", stdout);
+}
+
+#[test]
+fn exec_leaves_the_command_running_when_the_duration_ends_first() {
+    let started = Instant::now();
+    // Without its output redirected, the command would keep the test's pipes open.
+    let output = run("duration-exec-long", &["exec", "--duration", "300ms", "--interval", "100ms", "--allow-fast-poll", "--", "sh", "-c", "exec sleep 5 > /dev/null 2>&1"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(started.elapsed() < Duration::from_secs(4));
+    assert!(stdout.contains("[--duration elapsed]\n"), "{}", stdout);
+    assert!(stdout.contains("The command keeps running as PID "), "{}", stdout);
+}
+
+#[test]
+fn rejects_invalid_combinations() {
+    let error = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).output().unwrap();
+        assert_eq!(output.status.code(), Some(2));
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert_eq!(error(&["--duration", "10m", "--count", "5"]), "Error: --duration and --count cannot be combined\n");
+    #[cfg(feature = "network")]
+    assert_eq!(error(&["server", "--port", "9400", "--duration", "1h"]), "Error: --duration is not supported by server, which runs until it is stopped\n");
+    assert_eq!(error(&["--duration", "ten"]), "Error: Invalid --duration value: ten (expected e.g. 30s, 10m or 2h)\n");
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::efficiency::{efficiency, find_model, known_models, parse_models, Efficiency, Precision};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{format_snapshot, OutputContext, OutputFormat};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(name: &str, utilization: f64, power_w: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { power_w, ..common::snapshot(0, name, utilization) }
+}
+
+fn context(format: OutputFormat) -> OutputContext {
+    OutputContext { format, hostname: None, labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn the_embedded_table_parses() {
+    let models = known_models();
+
+    assert!(models.len() > 10);
+    assert!(models.iter().all(|model| model.fp16_tflops.is_some()));
+}
+
+#[test]
+fn the_longest_whole_word_match_wins() {
+    let models = known_models();
+    let pattern = |name: &str| find_model(&models, name).map(|model| model.pattern.as_str());
+
+    assert_eq!(pattern("NVIDIA A100-SXM4-80GB"), Some("A100"));
+    assert_eq!(pattern("NVIDIA A10"), Some("A10"));
+    assert_eq!(pattern("NVIDIA H100 PCIe"), Some("H100 PCIe"));
+    assert_eq!(pattern("NVIDIA H100 80GB HBM3"), Some("H100"));
+    assert_eq!(pattern("NVIDIA L40S"), Some("L40S"));
+    assert_eq!(pattern("Tesla V100-PCIE-32GB"), Some("V100-PCIE"));
+    assert_eq!(pattern("nvidia geforce rtx 4090"), Some("RTX 4090"));
+    assert_eq!(pattern("NVIDIA RTX A1000"), None);
+    assert_eq!(pattern("Amd GPU"), None);
+}
+
+#[test]
+fn scales_the_peak_by_utilization_per_watt() {
+    let models = known_models();
+
+    // 312 TFLOPS at 50%, over 200 W.
+    assert_eq!(efficiency(&models, &snapshot("NVIDIA A100-SXM4-80GB", 50.0, Some(200.0)), Precision::Fp16), Efficiency::TflopsPerWatt(0.78));
+    assert_eq!(efficiency(&models, &snapshot("NVIDIA A100-SXM4-80GB", 50.0, Some(200.0)), Precision::Int8), Efficiency::TflopsPerWatt(1.56));
+}
+
+#[test]
+fn is_unavailable_without_a_peak_or_power() {
+    let models = known_models();
+
+    assert_eq!(efficiency(&models, &snapshot("Intel GPU", 50.0, Some(20.0)), Precision::Fp16), Efficiency::Unavailable);
+    assert_eq!(efficiency(&models, &snapshot("Tesla T4", 50.0, Some(40.0)), Precision::Bf16), Efficiency::Unavailable);
+    assert_eq!(efficiency(&models, &snapshot("NVIDIA A100-SXM4-80GB", 50.0, None), Precision::Fp16), Efficiency::Unavailable);
+    assert_eq!(efficiency(&models, &snapshot("NVIDIA A100-SXM4-80GB", 50.0, Some(0.0)), Precision::Fp16), Efficiency::Unavailable);
+}
+
+#[test]
+fn rejects_malformed_tables() {
+    assert_eq!(parse_models("[[gpu]]\nfp16 = 10").unwrap_err(), "A [[gpu]] entry has no match");
+    assert_eq!(parse_models("[[gpu]]\nmatch = \"X1\"\nfp16 = \"fast\"").unwrap_err(), "GPU model X1: fp16 must be a number");
+    assert_eq!("fp32".parse::<Precision>().unwrap_err(), "Invalid --efficiency-precision value: fp32 (expected fp16, bf16 or int8)");
+}
+
+#[test]
+fn text_shows_n_a_and_json_leaves_it_out() {
+    let mut known = snapshot("NVIDIA A100-SXM4-80GB", 50.0, Some(200.0));
+    known.efficiency = Some(Efficiency::TflopsPerWatt(0.78));
+    let mut unknown = snapshot("Intel GPU", 50.0, Some(20.0));
+    unknown.efficiency = Some(Efficiency::Unavailable);
+
+    assert_eq!(format_snapshot(&known, &context(OutputFormat::Text)), "GPU 0 (NVIDIA A100-SXM4-80GB) Utilization (percent): 50.0, Power: 200 W, Efficiency: 0.78 TFLOPS/W");
+    assert_eq!(format_snapshot(&unknown, &context(OutputFormat::Text)), "GPU 0 (Intel GPU) Utilization (percent): 50.0, Power: 20 W, Efficiency: N/A");
+    assert!(format_snapshot(&known, &context(OutputFormat::Ndjson)).ends_with(",\"power_w\":200,\"efficiency_tflops_per_w\":0.78}"));
+    assert!(format_snapshot(&unknown, &context(OutputFormat::Ndjson)).ends_with(",\"power_w\":20}"));
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "cli")]
+
+use gpu_auto_top::alert::Severity;
+use gpu_auto_top::event::{Event, EventCounts, EventKind, ThrottleTracker};
+use gpu_auto_top::json;
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::schema::validate;
+use gpu_auto_top::{GpuInfo, GpuSnapshot};
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 1, name: "AMD Radeon RX 7900 XTX".to_string(), bus_id: None, render_offload: None }
+}
+
+fn context() -> OutputContext {
+    OutputContext { format: OutputFormat::Ndjson, hostname: Some("node1".to_string()), labels: "rack=a1".parse::<Labels>().unwrap(), tick_seq: Some(4), timestamp: None, precision: 1 }
+}
+
+fn every_kind() -> Vec<EventKind> {
+    vec![
+        EventKind::Detached,
+        EventKind::ThrottleStarted { reasons: vec!["power".to_string(), "thermal".to_string()] },
+        EventKind::ThrottleStopped,
+        EventKind::SourceChanged { from: "nvidia-smi".to_string(), to: "nvidia-smi:stream".to_string() },
+        EventKind::ScriptAlert,
+        EventKind::AlertFiring { metric: "temperature_c".to_string(), threshold: 85.0, value: 90.5 },
+        EventKind::AlertResolved { metric: "utilization".to_string(), threshold: 95.0, started: "2026-10-16T14:03:12.512Z".to_string(), peak: 100.0, duration_s: 2.5 },
+    ]
+}
+
+#[test]
+fn every_kind_round_trips_through_json() {
+    for kind in every_kind() {
+        let event = Event::new(&gpu(), Severity::Warning, kind);
+        let record = event.to_json(&context());
+        let parsed = json::parse(&record).unwrap();
+
+        validate(&parsed).unwrap_or_else(|err| panic!("{}\n{}", err, record));
+        assert_eq!(Event::from_json(&parsed), Ok(event), "{}", record);
+    }
+}
+
+#[test]
+fn records_are_typed_events() {
+    let event = Event::new(&gpu(), Severity::Warning, EventKind::Detached);
+    let record = event.to_json(&context());
+
+    assert!(record.starts_with("{\"schema_version\":1,\"hostname\":\"node1\",\"type\":\"event\",\"event\":\"detached\",\"severity\":\"warning\",\"gpu\":1,"), "{}", record);
+    assert!(record.contains("\"message\":\"GPU 1 (AMD Radeon RX 7900 XTX) was lost (driver unloaded or device removed)\""), "{}", record);
+    assert!(record.ends_with(",\"labels\":{\"rack\":\"a1\"},\"tick_seq\":4}"), "{}", record);
+    assert!(Event::from_json(&json::parse("{\"gpu\":1,\"name\":\"x\",\"utilization\":5}").unwrap()).is_err());
+}
+
+#[test]
+fn text_lines_are_timestamped_and_tagged() {
+    let throttle = Event::new(&gpu(), Severity::Info, EventKind::ThrottleStarted { reasons: vec!["power".to_string()] });
+    let line = throttle.format_text();
+
+    assert!(line.starts_with(&throttle.time), "{}", line);
+    assert!(line.ends_with("Z [THROTTLE info] GPU 1 (AMD Radeon RX 7900 XTX) is throttling: power"), "{}", line);
+    let resolved = Event::new(&gpu(), Severity::Critical, every_kind().pop().unwrap());
+    assert!(resolved.format_text().ends_with("[RESOLVED critical] GPU 1 (AMD Radeon RX 7900 XTX) utilization back below 95 after 00:00:02, peak 100"));
+}
+
+#[test]
+fn the_summary_counts_events_by_kind() {
+    let mut counts = EventCounts::default();
+    assert!(counts.format_summary().is_empty());
+
+    for kind in [EventKind::Detached, EventKind::AlertFiring { metric: "utilization".to_string(), threshold: 90.0, value: 99.0 }, EventKind::ThrottleStopped] {
+        counts.record(&Event::new(&gpu(), Severity::Warning, kind));
+    }
+    counts.record(&Event::new(&gpu(), Severity::Info, EventKind::Detached));
+
+    assert_eq!(counts.total(), 4);
+    assert_eq!(counts.format_summary(), ["Events: 4 (1 alert, 2 detached, 1 throttle_stop)"]);
+}
+
+#[test]
+fn throttle_reasons_appearing_changing_and_clearing_are_events() {
+    let mut tracker = ThrottleTracker::default();
+    let mut sample = |reasons: Option<&[&str]>| {
+        let snapshot = GpuSnapshot { throttle_reasons: reasons.map(|reasons| reasons.iter().map(|reason| reason.to_string()).collect()), ..GpuSnapshot::new(gpu(), 50.0) };
+        tracker.update(&snapshot).map(|event| (event.severity, event.kind))
+    };
+    let started = |reasons: &[&str]| Some((Severity::Warning, EventKind::ThrottleStarted { reasons: reasons.iter().map(|reason| reason.to_string()).collect() }));
+
+    assert_eq!(sample(Some(&[])), None);
+    assert_eq!(sample(Some(&["throttled"])), started(&["throttled"]));
+    assert_eq!(sample(Some(&["throttled"])), None);
+    assert_eq!(sample(None), None);
+    assert_eq!(sample(Some(&["throttled", "soft_temp_limit"])), started(&["throttled", "soft_temp_limit"]));
+    assert_eq!(sample(Some(&[])), Some((Severity::Info, EventKind::ThrottleStopped)));
+    assert_eq!(sample(Some(&[])), None);
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+fn gpuatop(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn version_prints_the_package_version() {
+    assert_eq!(gpuatop(&["--version"]), format!("gpuatop {}\n", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn verbose_version_lists_the_enabled_features() {
+    let output = gpuatop(&["--version", "--verbose"]);
+    let features = output.lines().nth(1).and_then(|line| line.strip_prefix("features: ")).unwrap();
+    let features: Vec<&str> = features.split(", ").collect();
+
+    assert!(features.contains(&"cli"));
+    assert_eq!(features.contains(&"web"), cfg!(feature = "web"));
+    assert_eq!(features.contains(&"network"), cfg!(feature = "network"));
+    assert_eq!(features.contains(&"lua"), cfg!(feature = "lua"));
+    assert_eq!(features.contains(&"opencl"), cfg!(feature = "opencl"));
+    assert_eq!(features.contains(&"vulkan"), cfg!(feature = "vulkan"));
+}
+
+#[cfg(not(feature = "network"))]
+#[test]
+fn network_options_name_the_missing_feature() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--send-to", "127.0.0.1:9000"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: --send-to requires a gpuatop built with the network feature\n");
+}
+
+#[cfg(not(feature = "lua"))]
+#[test]
+fn script_names_the_missing_feature() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--script", "alert.lua"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: --script requires a gpuatop built with the lua feature\n");
+}
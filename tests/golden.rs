@@ -0,0 +1,73 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::golden::{compare_snapshots, format_diff, parse_golden, FieldDiff};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(utilization: f64, memory_used_mib: Option<u64>, temperature_c: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib, memory_total_mib: Some(24576), temperature_c, ..common::snapshot(0, "GPU 0", utilization) }
+}
+
+#[test]
+fn identical_snapshots_have_no_diff() {
+    let expected = snapshot(45.0, Some(1024), Some(60.0));
+
+    assert!(compare_snapshots(&expected, &expected, 0.0).is_empty());
+}
+
+#[test]
+fn differences_within_tolerance_are_accepted() {
+    let expected = snapshot(50.0, Some(1000), Some(60.0));
+    let actual = snapshot(52.0, Some(1040), Some(58.0));
+
+    assert!(compare_snapshots(&expected, &actual, 0.05).is_empty());
+}
+
+#[test]
+fn differences_beyond_tolerance_are_listed() {
+    let expected = snapshot(50.0, Some(1000), Some(60.0));
+    let actual = snapshot(60.0, Some(1000), Some(61.0));
+
+    let diff = compare_snapshots(&expected, &actual, 0.05);
+
+    assert_eq!(diff.fields, vec![FieldDiff { field: "utilization", expected: 50.0, actual: Some(60.0) }]);
+    assert_eq!(format_diff(&diff), vec!["GPU 0 utilization: expected 50, got 60"]);
+}
+
+#[test]
+fn missing_fields_diverge_and_extra_fields_do_not() {
+    let expected = snapshot(50.0, Some(1000), None);
+    let actual = snapshot(50.0, None, Some(70.0));
+
+    let diff = compare_snapshots(&expected, &actual, 0.05);
+
+    assert_eq!(diff.fields, vec![FieldDiff { field: "memory_used_mib", expected: 1000.0, actual: None }]);
+}
+
+#[test]
+fn zero_expected_values_need_an_exact_match() {
+    let diff = compare_snapshots(&snapshot(0.0, None, None), &snapshot(0.5, None, None), 0.05);
+
+    assert_eq!(diff.fields.len(), 1);
+}
+
+#[test]
+fn parses_ndjson_and_array_golden_files() {
+    let ndjson = "{\"gpu\":0,\"name\":\"A\",\"utilization\":45,\"memory_used_mib\":1024}\n{\"gpu\":1,\"name\":\"B\",\"utilization\":3}\n";
+    let array = "[{\"gpu\":0,\"name\":\"A\",\"utilization\":45,\"memory_used_mib\":1024},{\"gpu\":1,\"name\":\"B\",\"utilization\":3}]";
+
+    for content in [ndjson, array] {
+        let golden = parse_golden(content).unwrap();
+        assert_eq!(golden.len(), 2);
+        assert_eq!(golden[&0].memory_used_mib, Some(1024));
+        assert_eq!(golden[&1].utilization, 3.0);
+        assert_eq!(golden[&1].temperature_c, None);
+    }
+}
+
+#[test]
+fn rejects_records_without_utilization() {
+    assert!(parse_golden("{\"gpu\":0}").is_err());
+    assert!(parse_golden("not json").is_err());
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::Command;
+
+use gpu_auto_top::display::grid::{decide_unicode, format_grid, snapshot_row, state_row};
+use gpu_auto_top::power::DeviceState;
+use gpu_auto_top::{GpuInfo, GpuSnapshot};
+
+fn gpu(index: u32, name: &str) -> GpuInfo {
+    GpuInfo { index, name: name.to_string(), bus_id: None, render_offload: None }
+}
+
+fn snapshot(index: u32, name: &str, utilization: f64) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(1024), memory_total_mib: Some(24576), temperature_c: Some(60.0), ..GpuSnapshot::new(gpu(index, name), utilization) }
+}
+
+#[test]
+fn rows_are_sorted_by_gpu_in_a_box_drawn_table() {
+    let mut rows = vec![snapshot_row(&snapshot(1, "Tesla T4", 100.0)), snapshot_row(&snapshot(0, "RTX 3090", 45.0))];
+
+    assert_eq!(
+        format_grid(&mut rows, true),
+        [
+            "┌────────────┬───────┬──────────┬───────────┬─────────┬─────────┐",
+            "│ GPU        │ Util% │ Mem Used │ Mem Total │ Temp °C │ Power W │",
+            "├────────────┼───────┼──────────┼───────────┼─────────┼─────────┤",
+            "│ 0 RTX 3090 │  45.0 │ 1024 MiB │ 24576 MiB │      60 │       - │",
+            "│ 1 Tesla T4 │ 100.0 │ 1024 MiB │ 24576 MiB │      60 │       - │",
+            "└────────────┴───────┴──────────┴───────────┴─────────┴─────────┘",
+        ]
+    );
+}
+
+#[test]
+fn unsampled_gpus_show_their_state_in_an_ascii_table() {
+    let mut rows = vec![state_row(&gpu(0, "RTX 3090"), DeviceState::Asleep)];
+
+    let table = format_grid(&mut rows, false);
+    assert_eq!(table[0], "+------------+--------+----------+-----------+---------+---------+");
+    assert_eq!(table[3], "| 0 RTX 3090 | asleep |          |           |         |         |");
+}
+
+#[test]
+fn the_first_locale_variable_set_decides_on_box_drawing() {
+    assert!(decide_unicode(None, None, Some("en_US.UTF-8")));
+    assert!(decide_unicode(None, Some("de_DE.utf8"), Some("C")));
+    assert!(!decide_unicode(Some("C"), None, Some("en_US.UTF-8")));
+    assert!(decide_unicode(Some(""), None, Some("C.UTF-8")));
+    assert!(!decide_unicode(None, None, None));
+}
+
+#[test]
+fn a_single_sample_prints_one_table() {
+    let dir = common::fake_tools("grid");
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["--count", "1", "--format", "table"])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .env_remove("LC_ALL")
+        .env_remove("LC_CTYPE")
+        .env("LANG", "C")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", stdout);
+    assert!(lines[3].starts_with("| 0 NVIDIA GeForce RTX 3090 |  45.0 | 1024 MiB | 24576 MiB |      60 |   120.5 |"), "{}", stdout);
+    assert!(!stdout.contains('\x1b'), "{:?}", stdout);
+}
+
+#[test]
+fn table_rejects_diff_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--format", "table", "--diff-output"]).output().unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: --format table shows every GPU"), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::Duration;
+
+use gpu_auto_top::display::detail::{History, HISTORY_MAGIC, HISTORY_VERSION};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, utilization: f64, temperature_c: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(11500), memory_total_mib: Some(23034), temperature_c, ..common::snapshot(index, "NVIDIA L4", utilization) }
+}
+
+fn run(dir: &std::path::Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).env("PATH", common::path_with(dir)).env("XDG_RUNTIME_DIR", dir).output().unwrap()
+}
+
+#[test]
+fn the_history_survives_a_round_trip() {
+    let mut history = History::new(Duration::from_secs(10));
+    for utilization in [0.0, 100.0, 50.0] {
+        history.record(&snapshot(1, utilization, Some(40.0)));
+    }
+    history.record(&snapshot(0, 25.0, None));
+
+    let mut restored = History::new(Duration::from_secs(10));
+    restored.extend_from_bytes(&history.to_bytes()).unwrap();
+
+    assert_eq!(restored.sparklines(1), history.sparklines(1));
+    assert_eq!(restored.sparklines(0), history.sparklines(0));
+    assert_eq!(restored.headroom(1), Some((11534, 11534)));
+}
+
+#[test]
+fn a_smaller_history_keeps_the_latest_points() {
+    let mut history = History::with_capacity(Duration::from_secs(1), 10);
+    for utilization in [0.0, 0.0, 0.0, 100.0] {
+        history.record(&snapshot(1, utilization, None));
+    }
+
+    let mut restored = History::with_capacity(Duration::from_secs(1), 2);
+    restored.extend_from_bytes(&history.to_bytes()).unwrap();
+
+    assert_eq!(restored.sparklines(1)[..2], ["  Last 2s:", "    Utilization: ▁█"]);
+}
+
+#[test]
+fn rejects_files_in_another_format() {
+    let mut history = History::new(Duration::from_secs(1));
+    history.record(&snapshot(1, 50.0, None));
+    let bytes = history.to_bytes();
+    assert!(bytes.starts_with(HISTORY_MAGIC));
+
+    let mut newer = bytes.clone();
+    newer[8..12].copy_from_slice(&(HISTORY_VERSION + 1).to_le_bytes());
+    let mut restored = History::new(Duration::from_secs(1));
+    assert_eq!(restored.extend_from_bytes(&newer), Err(format!("history format version {} is not supported (expected {})", HISTORY_VERSION + 1, HISTORY_VERSION)));
+    assert!(restored.sparklines(1).is_empty());
+
+    assert_eq!(restored.extend_from_bytes(b"{\"gpu\":0}"), Err("not a gpuatop history file".to_string()));
+    assert_eq!(restored.extend_from_bytes(&bytes[..bytes.len() - 1]), Err("the history file is truncated".to_string()));
+    assert!(restored.extend_from_bytes(&[bytes.as_slice(), &[0]].concat()).unwrap_err().starts_with("invalid history file"));
+    assert!(restored.sparklines(1).is_empty());
+}
+
+#[test]
+fn forged_lengths_do_not_allocate_the_claimed_size() {
+    let mut bytes = History::new(Duration::from_secs(1)).to_bytes();
+    // The number of GPUs, a u64 after the 12-byte header.
+    bytes[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let mut restored = History::new(Duration::from_secs(1));
+    assert_eq!(restored.extend_from_bytes(&bytes), Err("the history file is truncated".to_string()));
+}
+
+#[test]
+fn the_history_is_saved_on_exit_and_loaded_on_startup() {
+    let dir = common::fake_tools("history");
+    let path = dir.join("history.bin");
+    let args = ["-q", "--count", "2", "--interval", "100ms", "--allow-fast-poll", "--persist-history", path.to_str().unwrap()];
+
+    assert_eq!(run(&dir, &args).status.code(), Some(0));
+    let mut history = History::new(Duration::from_secs(1));
+    history.extend_from_bytes(&fs::read(&path).unwrap()).unwrap();
+    assert_eq!(history.sparklines(0)[1], "    Utilization: ▄▄");
+
+    assert_eq!(run(&dir, &args).status.code(), Some(0));
+    let mut history = History::new(Duration::from_secs(1));
+    history.extend_from_bytes(&fs::read(&path).unwrap()).unwrap();
+    assert_eq!(history.sparklines(0)[1], "    Utilization: ▄▄▄▄");
+
+    fs::write(&path, "garbage").unwrap();
+    let output = run(&dir, &args);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout).unwrap().contains(&format!("Warning: Ignoring the history in {}: not a gpuatop history file\n", path.display())));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rejects_a_history_too_short_for_a_sparkline() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--max-util-history", "1"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: Invalid --max-util-history value: 1 (expected at least 2)\n");
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::idle::{format_duration, Activity, IdleTracker};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{format_snapshot, OutputContext, OutputFormat};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, utilization: f64) -> GpuSnapshot {
+    common::snapshot(index, "NVIDIA A100-SXM4-80GB", utilization)
+}
+
+fn context(format: OutputFormat) -> OutputContext {
+    OutputContext { format, hostname: None, labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn counts_from_the_last_tick_above_the_threshold() {
+    let started = Instant::now();
+    let at = |seconds: u64| started + Duration::from_secs(seconds);
+    let mut tracker = IdleTracker::new(5.0, started);
+
+    assert_eq!(tracker.update(&snapshot(0, 80.0), at(10)), Activity::Active);
+    assert_eq!(tracker.update(&snapshot(0, 5.0), at(20)), Activity::Idle(Duration::from_secs(10)));
+    assert_eq!(tracker.update(&snapshot(0, 0.0), at(237)), Activity::Idle(Duration::from_secs(227)));
+    assert_eq!(tracker.update(&snapshot(0, 6.0), at(240)), Activity::Active);
+}
+
+#[test]
+fn a_gpu_never_active_is_idle_since_monitoring_started() {
+    let started = Instant::now();
+    let mut tracker = IdleTracker::new(5.0, started);
+
+    tracker.update(&snapshot(0, 90.0), started + Duration::from_secs(30));
+    assert_eq!(tracker.update(&snapshot(1, 0.0), started + Duration::from_secs(30)), Activity::Idle(Duration::from_secs(30)));
+}
+
+#[test]
+fn formats_hours_minutes_and_seconds() {
+    assert_eq!(format_duration(Duration::from_secs(227)), "00:03:47");
+    assert_eq!(format_duration(Duration::from_millis(3_600_999)), "01:00:00");
+    assert_eq!(format_duration(Duration::from_secs(100 * 3600 + 61)), "100:01:01");
+}
+
+#[test]
+fn text_shows_the_duration_and_json_the_seconds() {
+    let mut idle = snapshot(0, 0.0);
+    idle.activity = Some(Activity::Idle(Duration::from_secs(227)));
+    let mut active = snapshot(1, 80.0);
+    active.activity = Some(Activity::Active);
+
+    assert_eq!(format_snapshot(&idle, &context(OutputFormat::Text)), "GPU 0 (NVIDIA A100-SXM4-80GB) Utilization (percent): 0.0, Idle for 00:03:47");
+    assert_eq!(format_snapshot(&active, &context(OutputFormat::Text)), "GPU 1 (NVIDIA A100-SXM4-80GB) Utilization (percent): 80.0, active");
+    assert!(format_snapshot(&idle, &context(OutputFormat::Ndjson)).ends_with(",\"utilization\":0,\"idle_seconds\":227}"));
+    assert!(format_snapshot(&active, &context(OutputFormat::Ndjson)).ends_with(",\"utilization\":80,\"idle_seconds\":0}"));
+}
+
+#[test]
+fn idle_threshold_adds_the_idle_time_to_each_sample() {
+    let dir = common::fake_tools("idle");
+    let gpuatop = |threshold: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+            .args(["-q", "--count", "1", "--output-fields", "idle", "--idle-threshold", threshold])
+            .env("PATH", common::path_with(&dir))
+            .env("XDG_RUNTIME_DIR", &dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let busy = gpuatop("10");
+    let idle = gpuatop("50");
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(busy, "GPU 0 (NVIDIA GeForce RTX 3090) Utilization (percent): 45.0, active\n");
+    assert_eq!(idle, "GPU 0 (NVIDIA GeForce RTX 3090) Utilization (percent): 45.0, Idle for 00:00:00\n");
+}
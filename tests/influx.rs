@@ -0,0 +1,110 @@
+#![cfg(feature = "network")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use gpu_auto_top::influx::{parse_url, InfluxTarget, InfluxWriter, Response};
+
+fn target(url: &str) -> InfluxTarget {
+    InfluxTarget { url: parse_url(url).unwrap(), org: "my org".to_string(), bucket: "gpumetrics".to_string(), token: Some("s3cr3t".to_string()) }
+}
+
+/// Answers one request with `status` and returns the request.
+fn answer(listener: &TcpListener, status: &str) -> String {
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        head.push_str(&line);
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+    }
+    let length = head.lines().find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length: ").map(|length| length.parse().unwrap())).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    write!(&stream, "HTTP/1.1 {}\r\nRetry-After: 0\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+
+    head + &String::from_utf8(body).unwrap()
+}
+
+#[test]
+fn urls_are_http_or_https_with_an_optional_port_and_prefix() {
+    let url = |value| parse_url(value).map(|url| url.to_string());
+    assert_eq!(url("http://influx:8086"), Ok("http://influx:8086/".to_string()));
+    assert_eq!(url("https://eu-central-1-1.aws.cloud2.influxdata.com"), Ok("https://eu-central-1-1.aws.cloud2.influxdata.com/".to_string()));
+    assert_eq!(url("http://[::1]:8086/influx/"), Ok("http://[::1]:8086/influx/".to_string()));
+    assert!(url("ftp://influx:8086").unwrap_err().starts_with("Invalid --export-influx URL"));
+    assert!(url("influx:8086").is_err());
+    assert!(url("http://influx:port").is_err());
+    assert!(url("http://influx:8086/?org=myorg").is_err());
+}
+
+#[test]
+fn writes_go_to_the_bucket_with_nanosecond_precision() {
+    assert_eq!(target("http://influx:8086").write_url().as_str(), "http://influx:8086/api/v2/write?org=my+org&bucket=gpumetrics&precision=ns");
+    assert_eq!(target("https://proxy/influx/").write_url().as_str(), "https://proxy/influx/api/v2/write?org=my+org&bucket=gpumetrics&precision=ns");
+}
+
+#[test]
+fn rate_limits_and_outages_are_retried() {
+    let response = |status| Response { status, retry_after: None, message: String::new() };
+    assert!(response(429).retryable());
+    assert!(response(503).retryable());
+    assert!(!response(401).retryable());
+    assert!(!response(400).retryable());
+}
+
+#[test]
+fn a_rate_limited_batch_is_written_again() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let writer = InfluxWriter::new(target(&format!("http://{}", listener.local_addr().unwrap())), Duration::from_millis(50)).unwrap();
+    writer.send("gpu,gpu=0 utilization=45 1");
+    writer.send("gpu,gpu=1 utilization=50 1");
+
+    let first = answer(&listener, "429 Too Many Requests");
+    let second = answer(&listener, "204 No Content");
+    drop(writer);
+
+    assert!(first.ends_with("\r\n\r\ngpu,gpu=0 utilization=45 1\ngpu,gpu=1 utilization=50 1\n"), "{}", first);
+    assert!(second.ends_with("\r\n\r\ngpu,gpu=0 utilization=45 1\ngpu,gpu=1 utilization=50 1\n"), "{}", second);
+}
+
+#[test]
+fn export_influx_writes_the_samples() {
+    let dir = common::fake_tools("influx");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = thread::spawn(move || answer(&listener, "204 No Content"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["-q", "--count", "1", "--export-influx", &url, "--influx-bucket", "gpumetrics", "--influx-org", "myorg"])
+        .env("PATH", common::path_with(&dir))
+        .env("INFLUX_TOKEN", "from-env")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /api/v2/write?org=myorg&bucket=gpumetrics&precision=ns "), "{}", request);
+    assert!(request.to_ascii_lowercase().contains("\r\nauthorization: token from-env\r\n"), "{}", request);
+    assert!(request.contains("\r\n\r\ngpu,gpu=0,name=NVIDIA\\ GeForce\\ RTX\\ 3090 utilization=45,"), "{}", request);
+}
+
+#[test]
+fn export_influx_needs_a_bucket_and_an_org() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--export-influx", "http://influx:8086", "--influx-org", "myorg"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: --export-influx requires --influx-bucket and --influx-org"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--influx-bucket", "gpumetrics"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("require --export-influx"));
+}
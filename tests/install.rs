@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use std::io;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::runner::{CommandOutput, CommandRunner, MockRunner, RealRunner};
+use gpu_auto_top::{
+    epel_enabled, epel_release_package, epel_required, identify_installer, immutable_system_from_os_release, install_top_for_gpu_to, is_rhel_family, offline_instructions,
+    GpuType, ImmutableSystem, InstallResult, Installer, PackageManager, INSTALL_OUTPUT_TAIL,
+};
+
+const SILVERBLUE: &str = "NAME=\"Fedora Linux\"\nID=fedora\nVARIANT_ID=silverblue\n";
+const NIXOS: &str = "NAME=NixOS\nID=nixos\n";
+const MICROOS: &str = "NAME=\"openSUSE MicroOS\"\nID=\"opensuse-microos\"\n";
+const UBUNTU: &str = "NAME=\"Ubuntu\"\nID=ubuntu\nID_LIKE=debian\n";
+const ROCKY: &str = "NAME=\"Rocky Linux\"\nID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\nVERSION_ID=\"9.4\"\n";
+const RHEL: &str = "NAME=\"Red Hat Enterprise Linux\"\nID=\"rhel\"\nID_LIKE=\"fedora\"\nVERSION_ID=\"9.4\"\n";
+const FEDORA: &str = "NAME=\"Fedora Linux\"\nID=fedora\nVERSION_ID=40\n";
+const REPOLIST: &str = "repo id                       repo name\nappstream                     Rocky Linux 9 - AppStream\nbaseos                        Rocky Linux 9 - BaseOS\n";
+const REPOLIST_EPEL: &str = "repo id                       repo name\nappstream                     Rocky Linux 9 - AppStream\nepel                          Extra Packages for Enterprise Linux 9 - x86_64\n";
+
+fn which(runner: MockRunner, command: &str) -> MockRunner {
+    runner.with("which", &[command], CommandOutput::ok(&format!("/usr/bin/{}\n", command)))
+}
+
+#[test]
+fn os_release_identifies_immutable_systems() {
+    assert_eq!(immutable_system_from_os_release(SILVERBLUE), Some(ImmutableSystem::RpmOstree));
+    assert_eq!(immutable_system_from_os_release(NIXOS), Some(ImmutableSystem::Nix));
+    assert_eq!(immutable_system_from_os_release(MICROOS), Some(ImmutableSystem::TransactionalUpdate));
+    assert_eq!(immutable_system_from_os_release(UBUNTU), None);
+}
+
+#[test]
+fn os_release_wins_over_present_package_managers() {
+    let runner = which(MockRunner::new(), "dnf");
+
+    assert_eq!(identify_installer(&runner, Some(SILVERBLUE)), Some(Installer::Immutable(ImmutableSystem::RpmOstree)));
+}
+
+#[test]
+fn immutable_tools_are_detected_without_os_release() {
+    let runner = which(which(MockRunner::new(), "zypper"), "transactional-update");
+
+    assert_eq!(identify_installer(&runner, None), Some(Installer::Immutable(ImmutableSystem::TransactionalUpdate)));
+}
+
+#[test]
+fn nix_is_only_used_without_a_package_manager() {
+    let runner = which(MockRunner::new(), "nix-env");
+    assert_eq!(identify_installer(&runner, Some(UBUNTU)), Some(Installer::Immutable(ImmutableSystem::Nix)));
+
+    let runner = which(runner, "apt");
+    assert_eq!(identify_installer(&runner, Some(UBUNTU)), Some(Installer::PackageManager(PackageManager::Apt)));
+
+    assert_eq!(identify_installer(&MockRunner::new(), Some(UBUNTU)), None);
+}
+
+#[test]
+fn dnf_is_preferred_over_yum() {
+    let runner = which(which(MockRunner::new(), "yum"), "dnf");
+
+    assert_eq!(identify_installer(&runner, None), Some(Installer::PackageManager(PackageManager::Dnf)));
+}
+
+#[test]
+fn immutable_systems_print_instructions_without_running_anything() {
+    let printed = RefCell::new(String::new());
+    let result = install_top_for_gpu_to(
+        &MockRunner::new(),
+        &GpuType::Intel,
+        Installer::Immutable(ImmutableSystem::RpmOstree), None,
+        |_| panic!("nothing to confirm"),
+        |instructions| printed.borrow_mut().push_str(instructions),
+    );
+
+    assert_eq!(result, InstallResult::InstructionsPrinted);
+    assert!(printed.borrow().contains("rpm-ostree install igt-gpu-tools && reboot"));
+}
+
+#[test]
+fn nix_instructions_use_the_nixpkgs_attribute() {
+    assert_eq!(ImmutableSystem::Nix.install_command("intel-gpu-tools"), "nix-env -iA nixpkgs.intel-gpu-tools");
+}
+
+#[test]
+fn declining_runs_nothing() {
+    let result = install_top_for_gpu_to(&MockRunner::new(), &GpuType::Amd, Installer::PackageManager(PackageManager::Apt), None, |_| false, |_| {});
+
+    assert_eq!(result, InstallResult::Declined);
+}
+
+#[test]
+fn successful_install_streams_its_output() {
+    let mut printed = Vec::new();
+    let runner = MockRunner::new().with("zypper", &["install", "-y", "radeontop"], CommandOutput::ok("Installing: radeontop\nDone\n"));
+    let result = install_top_for_gpu_to(&runner, &GpuType::Amd, Installer::PackageManager(PackageManager::Zypper), None, |_| true, |line| printed.push(line.to_string()));
+
+    assert_eq!(result, InstallResult::Installed);
+    assert_eq!(printed, ["zypper: Installing: radeontop", "zypper: Done"]);
+}
+
+#[test]
+fn failed_install_keeps_the_last_output_lines() {
+    let stderr: Vec<String> = (1..=30).map(|line| format!("line {}", line)).collect();
+    let runner = MockRunner::new().with("apt", &["install", "-y", "radeontop"], CommandOutput::failed(100, &stderr.join("\n")));
+
+    match install_top_for_gpu_to(&runner, &GpuType::Amd, Installer::PackageManager(PackageManager::Apt), None, |_| true, |_| {}) {
+        InstallResult::Failed { message, output } => {
+            assert_eq!(message, "apt exited with code 100");
+            assert_eq!(output.len(), INSTALL_OUTPUT_TAIL);
+            assert_eq!(output.first().map(String::as_str), Some("line 11"));
+            assert_eq!(output.last().map(String::as_str), Some("line 30"));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn missing_package_manager_is_a_failure() {
+    let result = install_top_for_gpu_to(&MockRunner::new(), &GpuType::Amd, Installer::PackageManager(PackageManager::Pacman), None, |_| true, |_| {});
+
+    assert!(matches!(result, InstallResult::Failed { ref message, .. } if message.starts_with("Failed to run pacman")), "unexpected result: {:?}", result);
+}
+
+#[test]
+fn dpkg_lock_contention_is_reported_as_locked() {
+    let stderr = "E: Could not get lock /var/lib/dpkg/lock-frontend. It is held by process 1234 (unattended-upgr)\n\
+                  E: Unable to acquire the dpkg frontend lock (/var/lib/dpkg/lock-frontend), is another process using it?";
+    let runner = MockRunner::new().with("apt", &["install", "-y", "radeontop"], CommandOutput::failed(100, stderr));
+
+    let result = install_top_for_gpu_to(&runner, &GpuType::Amd, Installer::PackageManager(PackageManager::Apt), None, |_| true, |_| {});
+    assert_eq!(result, InstallResult::Locked);
+}
+
+#[test]
+fn install_commands_answer_the_prompt() {
+    assert_eq!(Installer::PackageManager(PackageManager::Apt).install_command("nvidia-smi"), "apt install -y nvidia-smi");
+    assert_eq!(Installer::PackageManager(PackageManager::Pacman).install_command("radeontop"), "pacman -S --noconfirm radeontop");
+    assert_eq!(Installer::Immutable(ImmutableSystem::Nix).install_command("radeontop"), "nix-env -iA nixpkgs.radeontop");
+}
+
+#[test]
+fn offline_instructions_name_the_package_and_driver_version() {
+    let apt = Installer::PackageManager(PackageManager::Apt);
+
+    assert_eq!(
+        offline_instructions(&GpuType::Nvidia, apt, Some("535.129.03")).as_deref(),
+        Some("Install nvidia-smi version 535.129.03 (the loaded driver's) from a local mirror or bake it into the image with:\n  apt install -y nvidia-smi")
+    );
+    assert_eq!(
+        offline_instructions(&GpuType::Intel, Installer::PackageManager(PackageManager::Dnf), Some("535.129.03")).as_deref(),
+        Some("Install igt-gpu-tools from a local mirror or bake it into the image with:\n  dnf install -y igt-gpu-tools")
+    );
+    assert_eq!(offline_instructions(&GpuType::Unknown("DRM device".to_string()), apt, None), None);
+}
+
+#[test]
+fn a_hanging_command_is_killed_after_the_timeout() {
+    let mut lines = Vec::new();
+    let started = Instant::now();
+    let result = RealRunner.run_streaming("sh", &["-c", "echo waiting for the mirror; sleep 10"], Duration::from_secs(1), &mut |line| lines.push(line.to_string()));
+
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(err.to_string(), "sh did not finish within 1s and was stopped");
+    assert_eq!(lines, ["waiting for the mirror"]);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn rhel_family_systems_are_recognized_from_os_release() {
+    assert!(is_rhel_family(ROCKY));
+    assert!(is_rhel_family(RHEL));
+    assert!(!is_rhel_family(FEDORA));
+    assert!(!is_rhel_family(UBUNTU));
+
+    assert_eq!(epel_release_package(Some(ROCKY)), "epel-release");
+    assert_eq!(epel_release_package(Some(RHEL)), "https://dl.fedoraproject.org/pub/epel/epel-release-latest-9.noarch.rpm");
+}
+
+#[test]
+fn epel_is_only_required_for_its_packages_where_it_is_not_enabled() {
+    let without = MockRunner::new().with("dnf", &["repolist"], CommandOutput::ok(REPOLIST));
+    let with = MockRunner::new().with("dnf", &["repolist"], CommandOutput::ok(REPOLIST_EPEL));
+    let dnf = Installer::PackageManager(PackageManager::Dnf);
+
+    assert!(!epel_enabled(&without, PackageManager::Dnf));
+    assert!(epel_enabled(&with, PackageManager::Dnf));
+    assert!(epel_enabled(&MockRunner::new().with("yum", &["repolist"], CommandOutput::ok("repo id   repo name   status\n!epel/x86_64   EPEL 7   13,791\n")), PackageManager::Yum));
+
+    assert_eq!(epel_required(&without, dnf, "radeontop", Some(ROCKY)).as_deref(), Some("epel-release"));
+    assert_eq!(epel_required(&with, dnf, "radeontop", Some(ROCKY)), None);
+    assert_eq!(epel_required(&without, dnf, "nvidia-smi", Some(ROCKY)), None);
+    assert_eq!(epel_required(&without, dnf, "radeontop", Some(FEDORA)), None);
+    assert_eq!(epel_required(&without, Installer::PackageManager(PackageManager::Apt), "radeontop", Some(ROCKY)), None);
+}
+
+#[test]
+fn fedora_and_rhel_name_intel_gpu_tools_igt_gpu_tools() {
+    assert_eq!(GpuType::Intel.top_package_for(Installer::PackageManager(PackageManager::Dnf)), Some("igt-gpu-tools"));
+    assert_eq!(GpuType::Intel.top_package_for(Installer::PackageManager(PackageManager::Apt)), Some("intel-gpu-tools"));
+    assert_eq!(GpuType::Amd.top_package_for(Installer::PackageManager(PackageManager::Dnf)), Some("radeontop"));
+}
+
+#[test]
+fn epel_release_is_installed_first_under_the_same_confirmation() {
+    let mut prompts = Vec::new();
+    let mut printed = Vec::new();
+    let runner = MockRunner::new()
+        .with("dnf", &["repolist"], CommandOutput::ok(REPOLIST))
+        .with("dnf", &["install", "-y", "epel-release"], CommandOutput::ok("Installed: epel-release-9-7.el9.noarch\n"))
+        .with("dnf", &["install", "-y", "radeontop"], CommandOutput::ok("Installed: radeontop-1.4-9.el9.x86_64\n"));
+
+    let result = install_top_for_gpu_to(
+        &runner,
+        &GpuType::Amd,
+        Installer::PackageManager(PackageManager::Dnf),
+        Some(ROCKY),
+        |prompt| {
+            prompts.push(prompt.to_string());
+            true
+        },
+        |line| printed.push(line.to_string()),
+    );
+
+    assert_eq!(result, InstallResult::Installed);
+    assert_eq!(prompts, ["Install radeontop with Dnf? It is in EPEL, so epel-release is installed first to enable it."]);
+    assert_eq!(printed, ["dnf: Installed: epel-release-9-7.el9.noarch", "dnf: Installed: radeontop-1.4-9.el9.x86_64"]);
+}
+
+#[test]
+fn declining_epel_prints_the_two_commands() {
+    let mut printed = String::new();
+    let runner = MockRunner::new().with("dnf", &["repolist"], CommandOutput::ok(REPOLIST));
+
+    let result = install_top_for_gpu_to(&runner, &GpuType::Intel, Installer::PackageManager(PackageManager::Dnf), Some(RHEL), |_| false, |line| printed.push_str(line));
+
+    assert_eq!(result, InstallResult::Declined);
+    assert_eq!(
+        printed,
+        "igt-gpu-tools is in EPEL, which is not enabled. Enable it and install igt-gpu-tools with:\n  \
+         dnf install -y https://dl.fedoraproject.org/pub/epel/epel-release-latest-9.noarch.rpm\n  dnf install -y igt-gpu-tools"
+    );
+}
+
+#[test]
+fn no_match_from_dnf_becomes_the_epel_hint() {
+    let stderr = "No match for argument: radeontop\nError: Unable to find a match: radeontop";
+    let runner = MockRunner::new().with("dnf", &["install", "-y", "radeontop"], CommandOutput::failed(1, stderr));
+
+    let result = install_top_for_gpu_to(&runner, &GpuType::Amd, Installer::PackageManager(PackageManager::Dnf), None, |_| true, |_| {});
+
+    assert_eq!(
+        result,
+        InstallResult::EpelRequired {
+            hint: "radeontop is in EPEL, which is not enabled. Enable it and install radeontop with:\n  dnf install -y epel-release\n  dnf install -y radeontop".to_string()
+        }
+    );
+}
@@ -0,0 +1,73 @@
+// intel_gpu_top is read through its `-o -` text output rather than `-J`, so the fixtures
+// below use that format.
+use gpu_auto_top::runner::MockRunner;
+use gpu_auto_top::{enumerate_gpus, parse_intel_gpu_top_output, GpuInfo, GpuType};
+
+const INTEL_GPU_TOP: &str = " Freq MHz      IRQ RC6     RCS/0           BCS/0           VCS/0          VECS/0 \n\
+ req  act       /s   %       %  se  wa       %  se  wa       %  se  wa       %  se  wa \n\
+ 350  300       12  85   23.45   0   0    0.00   0   0    1.50   0   0    0.00   0   0 ";
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "Intel GPU".to_string(), bus_id: None, render_offload: None }
+}
+
+#[test]
+fn parses_render_engine_busy() {
+    let snapshot = parse_intel_gpu_top_output(INTEL_GPU_TOP, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 23.45);
+    assert_eq!(snapshot.memory_used_mib, None);
+    assert_eq!(snapshot.power_w, None);
+}
+
+#[test]
+fn finds_the_render_engine_column() {
+    let output = " Freq MHz      IRQ RC6     BCS/0           RCS/0 \n\
+ req  act       /s   %       %  se  wa       %  se  wa \n\
+ 350  300       12  85    2.00   0   0   77.00   0   0 ";
+
+    let snapshot = parse_intel_gpu_top_output(output, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 77.0);
+}
+
+#[test]
+fn rejects_incomplete_output() {
+    let header_only: String = INTEL_GPU_TOP.lines().take(1).collect();
+
+    assert!(parse_intel_gpu_top_output(&header_only, &gpu()).is_err());
+    assert!(parse_intel_gpu_top_output("", &gpu()).is_err());
+}
+
+#[test]
+fn rejects_output_without_render_engine() {
+    let output = " Freq MHz      IRQ RC6     BCS/0 \n req  act       /s   %       %  se  wa \n 350  300       12  85    2.00   0   0 ";
+
+    assert!(parse_intel_gpu_top_output(output, &gpu()).is_err());
+}
+
+#[test]
+fn enumerates_a_single_device() {
+    let gpus = enumerate_gpus(&MockRunner::new(), &GpuType::Intel);
+
+    assert_eq!(gpus.len(), 1);
+    assert_eq!(gpus[0].name, "Intel GPU");
+}
+
+#[test]
+fn parses_imc_bandwidth_when_present() {
+    let output = " Freq MHz      IRQ RC6     IMC MiB/s     RCS/0 \n\
+ req  act       /s   %      rd    wr       %  se  wa \n\
+ 350  300       12  85    1000   500   23.45   0   0 ";
+
+    let bandwidth = parse_intel_gpu_top_output(output, &gpu()).expect("parses").memory_bandwidth.expect("has bandwidth");
+
+    assert!((bandwidth.read_gbps.unwrap() - 1.048576).abs() < 1e-4);
+    assert!((bandwidth.write_gbps.unwrap() - 0.524288).abs() < 1e-4);
+    assert_eq!(bandwidth.utilization_pct, None);
+}
+
+#[test]
+fn bandwidth_is_absent_without_imc_columns() {
+    assert!(parse_intel_gpu_top_output(INTEL_GPU_TOP, &gpu()).unwrap().memory_bandwidth.is_none());
+}
@@ -0,0 +1,216 @@
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use gpu_auto_top::backend::{fits, Backend, IntelArcBackend};
+use gpu_auto_top::rate::Moment;
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::{intel_arc_model, try_identify_gpu_card, GpuInfo, GpuType, PollResult};
+
+const TICK: Duration = Duration::from_millis(500);
+
+const ARC: &str = "0000:03:00.0 VGA compatible controller [0300]: Intel Corporation DG2 [Arc A770] [8086:56a0] (rev 08)\n";
+const IGPU: &str = "0000:00:02.0 VGA compatible controller [0300]: Intel Corporation Raptor Lake-S GT1 [UHD Graphics 770] [8086:a780] (rev 04)\n";
+const NVIDIA: &str = "0000:01:00.0 VGA compatible controller [0300]: NVIDIA Corporation GA107M [GeForce RTX 3050 Mobile] [10de:25a2] (rev a1)\n";
+
+/// A fake sysfs: DRM cards under `drm`, their devices and drivers beside it.
+struct Sysfs {
+    root: PathBuf,
+}
+
+impl Sysfs {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("gpuatop-intel-arc-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("drm")).unwrap();
+        Sysfs { root }
+    }
+
+    fn write(&self, path: &str, contents: &str) {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// An Intel card bound to `driver`, with its device at `bus_id`.
+    fn card(&self, card: &str, bus_id: &str, driver: &str) {
+        let device = self.root.join("devices").join(bus_id);
+        fs::create_dir_all(self.root.join("drivers").join(driver)).unwrap();
+        fs::create_dir_all(&device).unwrap();
+        fs::create_dir_all(self.root.join("drm").join(card)).unwrap();
+        fs::write(device.join("vendor"), "0x8086\n").unwrap();
+        symlink(self.root.join("drivers").join(driver), device.join("driver")).unwrap();
+        symlink(&device, self.root.join("drm").join(card).join("device")).unwrap();
+    }
+
+    fn drm(&self) -> PathBuf {
+        self.root.join("drm")
+    }
+
+    fn rapl(&self) -> PathBuf {
+        self.root.join("powercap/intel-rapl-mmio/energy_uj")
+    }
+}
+
+impl Drop for Sysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "Intel Arc A770".to_string(), bus_id: None, render_offload: None }
+}
+
+fn identify(lspci: &str) -> Option<GpuType> {
+    try_identify_gpu_card(&MockRunner::new().with("lspci", &["-Dnn"], CommandOutput::ok(lspci)))
+}
+
+/// An A770 on i915 as card1, beside the integrated GPU as card0.
+fn i915_arc(name: &str) -> Sysfs {
+    let sysfs = Sysfs::new(name);
+    sysfs.card("card0", "0000:00:02.0", "i915");
+    sysfs.write("drm/card0/gt/gt0/rc6_enable", "1\n");
+    sysfs.write("drm/card0/gt/gt0/rc6_residency_ms", "900\n");
+    sysfs.card("card1", "0000:03:00.0", "i915");
+    sysfs.write("drm/card1/gt/gt0/rc6_enable", "1\n");
+    sysfs.write("drm/card1/gt/gt0/rc6_residency_ms", "1000\n");
+    sysfs.write("drm/card1/gt/gt0/rps_act_freq_mhz", "2400\n");
+    sysfs.write("drm/card1/gt/gt0/rps_cur_freq_mhz", "2450\n");
+    sysfs.write("devices/0000:03:00.0/hwmon/hwmon3/energy1_input", "5000000\n");
+    sysfs.write("devices/0000:03:00.0/hwmon/hwmon3/temp1_input", "52000\n");
+    sysfs
+}
+
+fn sample(results: Vec<PollResult>) -> gpu_auto_top::GpuSnapshot {
+    match results.into_iter().next() {
+        Some(PollResult::Ok { snapshot, retries: 0 }) => snapshot,
+        other => panic!("no sample: {:?}", other),
+    }
+}
+
+#[test]
+fn reads_the_rc6_residency_clock_and_energy_of_an_i915_card() {
+    let sysfs = i915_arc("i915");
+    let at = Moment::now();
+    let mut backend = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), at).expect("opens");
+
+    sysfs.write("drm/card1/gt/gt0/rc6_residency_ms", "1125\n");
+    sysfs.write("devices/0000:03:00.0/hwmon/hwmon3/energy1_input", "15000000\n");
+    let snapshot = sample(backend.poll_at(&[gpu()], at.after(TICK)));
+
+    assert_eq!(snapshot.utilization, 75.0);
+    assert_eq!(snapshot.power_w, Some(20.0));
+    assert_eq!(snapshot.clock_mhz, Some(2400));
+    assert_eq!(snapshot.temperature_c, Some(52.0));
+    assert_eq!(backend.source(), "sysfs:intel_gt");
+}
+
+#[test]
+fn the_clock_falls_back_to_the_requested_frequency() {
+    let sysfs = i915_arc("requested");
+    fs::remove_file(sysfs.root.join("drm/card1/gt/gt0/rps_act_freq_mhz")).unwrap();
+    let at = Moment::now();
+    let mut backend = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), at).expect("opens");
+
+    assert_eq!(sample(backend.poll_at(&[gpu()], at.after(TICK))).clock_mhz, Some(2450));
+}
+
+#[test]
+fn reads_the_gt_idle_time_of_an_xe_card_and_falls_back_to_rapl() {
+    let sysfs = Sysfs::new("xe");
+    sysfs.card("card0", "0000:03:00.0", "xe");
+    sysfs.write("devices/0000:03:00.0/tile0/gt0/gtidle/idle_residency_ms", "4000\n");
+    sysfs.write("devices/0000:03:00.0/tile0/gt0/freq0/act_freq", "2850\n");
+    sysfs.write("powercap/intel-rapl-mmio/energy_uj", "1000000\n");
+    let at = Moment::now();
+    let mut backend = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), at).expect("opens");
+
+    sysfs.write("devices/0000:03:00.0/tile0/gt0/gtidle/idle_residency_ms", "4500\n");
+    sysfs.write("powercap/intel-rapl-mmio/energy_uj", "4000000\n");
+    let snapshot = sample(backend.poll_at(&[gpu()], at.after(TICK)));
+
+    assert_eq!(snapshot.utilization, 0.0);
+    assert_eq!(snapshot.power_w, Some(6.0));
+    assert_eq!(snapshot.clock_mhz, Some(2850));
+    assert_eq!(snapshot.temperature_c, None);
+}
+
+#[test]
+fn a_wrapped_energy_counter_leaves_the_power_out_for_a_tick() {
+    let sysfs = i915_arc("wrap");
+    let at = Moment::now();
+    let mut backend = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), at).expect("opens");
+
+    sysfs.write("devices/0000:03:00.0/hwmon/hwmon3/energy1_input", "100\n");
+    sysfs.write("drm/card1/gt/gt0/rc6_residency_ms", "1500\n");
+    let snapshot = sample(backend.poll_at(&[gpu()], at.after(TICK)));
+
+    assert_eq!(snapshot.utilization, 0.0);
+    assert_eq!(snapshot.power_w, None);
+}
+
+#[test]
+fn a_suspend_gives_no_utilization_for_a_tick() {
+    let sysfs = i915_arc("suspend");
+    let at = Moment::now();
+    let mut backend = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), at).expect("opens");
+
+    let mut resumed = at.after(TICK);
+    resumed.wall += Duration::from_secs(600);
+    assert!(matches!(backend.poll_at(&[gpu()], resumed)[0], PollResult::TransientError { .. }));
+    assert!(matches!(backend.poll_at(&[gpu()], resumed.after(TICK))[0], PollResult::Ok { .. }));
+}
+
+#[test]
+fn a_card_with_rc6_disabled_cannot_be_read() {
+    let sysfs = i915_arc("rc6");
+    sysfs.write("drm/card0/gt/gt0/rc6_enable", "0\n");
+    sysfs.write("drm/card1/gt/gt0/rc6_enable", "0\n");
+
+    let err = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), Moment::now()).expect_err("no GT to read");
+
+    assert!(err.to_string().contains("RC6"), "{}", err);
+}
+
+#[test]
+fn names_the_arc_model_from_lspci() {
+    assert_eq!(intel_arc_model("Intel Corporation DG2 [Arc A770]"), Some("Arc A770".to_string()));
+    assert_eq!(intel_arc_model("Intel Corporation Battlemage G21 [Arc B580]"), Some("Arc B580".to_string()));
+    assert_eq!(intel_arc_model("Intel Corporation DG2"), Some("DG2".to_string()));
+    assert_eq!(intel_arc_model("Intel Corporation Raptor Lake-S GT1 [UHD Graphics 770]"), None);
+    assert_eq!(intel_arc_model("Intel Corporation Meteor Lake-P [Intel Arc Graphics]"), None);
+}
+
+#[test]
+fn an_arc_card_comes_before_integrated_graphics() {
+    assert_eq!(identify(&format!("{}{}", IGPU, ARC)), Some(GpuType::IntelArc("Arc A770".to_string())));
+    assert_eq!(identify(IGPU), Some(GpuType::Intel));
+    assert_eq!(identify(&format!("{}{}", ARC, NVIDIA)), Some(GpuType::Nvidia));
+}
+
+#[test]
+fn only_arc_cards_are_read_from_the_gt_counters() {
+    let arc = GpuType::IntelArc("Arc A770".to_string());
+
+    assert!(fits(&arc, "sysfs:intel_gt"));
+    assert!(fits(&arc, "fdinfo"));
+    assert!(!fits(&GpuType::Intel, "sysfs:intel_gt"));
+    assert!(!fits(&arc, "intel_gpu_top"));
+    assert_eq!(arc.top_tool(), None);
+}
+
+#[test]
+fn the_sysfs_tree_is_matched_to_the_gpu_by_bus_id() {
+    let sysfs = i915_arc("bus-id");
+    let at = Moment::now();
+    let mut backend = IntelArcBackend::open_in(&sysfs.drm(), &sysfs.rapl(), at).expect("opens");
+    let by_bus_id = GpuInfo { bus_id: Some("0000:03:00.0".to_string()), ..gpu() };
+    let elsewhere = GpuInfo { index: 1, ..gpu() };
+
+    let results = backend.poll_at(&[by_bus_id, elsewhere], at.after(TICK));
+
+    assert!(matches!(results[0], PollResult::Ok { .. }));
+    assert!(matches!(results[1], PollResult::PermanentError { .. }));
+}
@@ -0,0 +1,88 @@
+// Sample lines as tegrastats prints them on a Nano (L4T 32), a Xavier NX (L4T 35) and an
+// Orin Nano (L4T 36).
+use gpu_auto_top::{is_jetson_compatible, parse_tegrastats_cpu, parse_tegrastats_output, GpuInfo, GpuType};
+
+const NANO: &str = "RAM 2257/3964MB (lfb 25x4MB) SWAP 0/1982MB (cached 0MB) IRAM 0/252kB(lfb 252kB) CPU [9%@1479,6%@1479,5%@1479,2%@1479] \
+EMC_FREQ 3%@1600 GR3D_FREQ 45%@921 APE 25 PLL@33C CPU@35.5C PMIC@100C GPU@34C AO@41.5C thermal@34.75C POM_5V_IN 2189/2189 POM_5V_GPU 840/812 POM_5V_CPU 447/447";
+
+const XAVIER: &str = "RAM 3009/7772MB (lfb 41x4MB) SWAP 0/3886MB (cached 0MB) CPU [2%@1190,1%@1190,off,off,off,off] EMC_FREQ 0%@1600 GR3D_FREQ 12%@306 \
+APE 150 MTS fg 0% bg 0% AO@40C GPU@40.5C Tdiode@41.5C PMIC@50C AUX@39.5C CPU@41.5C thermal@40.35C Tboard@40C GPU 1234/1200 CPU 620/620 SOC 1241/1241";
+
+const ORIN_NANO: &str = "03-04-2025 10:12:45 RAM 3345/7620MB (lfb 2x4MB) SWAP 0/3810MB (cached 0MB) CPU [1%@729,0%@729,0%@729,0%@729,off,off] \
+EMC_FREQ 0%@2133 GR3D_FREQ 7%@[305] NVDEC off NVJPG off VIC off OFA off APE 200 cpu@47.218C soc2@46.343C soc0@46.968C gpu@47.5C tj@47.875C \
+soc1@47.156C VDD_IN 4285mW/4285mW VDD_CPU_GPU_CV 517mW/517mW VDD_SOC 1360mW/1360mW";
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "NVIDIA Jetson Nano Developer Kit".to_string(), bus_id: None, render_offload: None }
+}
+
+#[test]
+fn parses_a_nano_sample() {
+    let snapshot = parse_tegrastats_output(NANO, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 45.0);
+    assert_eq!(snapshot.memory_used_mib, Some(2257));
+    assert_eq!(snapshot.memory_total_mib, Some(3964));
+    assert_eq!(snapshot.temperature_c, Some(34.0));
+    assert_eq!(snapshot.power_w, Some(0.84));
+    assert_eq!(snapshot.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct), Some(3.0));
+}
+
+#[test]
+fn reads_the_xavier_gpu_rail() {
+    let snapshot = parse_tegrastats_output(XAVIER, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 12.0);
+    assert_eq!(snapshot.temperature_c, Some(40.5));
+    assert_eq!(snapshot.power_w, Some(1.234));
+}
+
+#[test]
+fn parses_an_orin_sample_without_a_gpu_rail() {
+    let snapshot = parse_tegrastats_output(ORIN_NANO, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 7.0);
+    assert_eq!(snapshot.memory_used_mib, Some(3345));
+    assert_eq!(snapshot.temperature_c, Some(47.5));
+    assert_eq!(snapshot.power_w, None);
+}
+
+#[test]
+fn reads_milliwatt_rails() {
+    let output = "RAM 5000/30536MB CPU [3%@1728] EMC_FREQ 1%@3199 GR3D_FREQ 99%@[1300,1300] gpu@52C VDD_GPU_SOC 15200mW/9800mW";
+
+    let snapshot = parse_tegrastats_output(output, &gpu()).expect("parses");
+
+    assert_eq!(snapshot.utilization, 99.0);
+    assert_eq!(snapshot.power_w, Some(15.2));
+}
+
+#[test]
+fn uses_the_last_sample_line() {
+    let output = format!("{}\n{}", NANO, NANO.replace("GR3D_FREQ 45%", "GR3D_FREQ 60%"));
+
+    assert_eq!(parse_tegrastats_output(&output, &gpu()).expect("parses").utilization, 60.0);
+}
+
+#[test]
+fn rejects_output_without_gpu_load() {
+    assert!(parse_tegrastats_output("RAM 2257/3964MB CPU [9%@1479]", &gpu()).is_err());
+    assert!(parse_tegrastats_output("", &gpu()).is_err());
+}
+
+#[test]
+fn parses_cpu_load_per_core() {
+    assert_eq!(parse_tegrastats_cpu(XAVIER), Some(vec![Some(2.0), Some(1.0), None, None, None, None]));
+    assert_eq!(parse_tegrastats_cpu("GR3D_FREQ 0%"), None);
+}
+
+#[test]
+fn recognizes_jetson_device_trees() {
+    assert!(is_jetson_compatible("nvidia,p3768-0000+p3767-0005\0nvidia,p3767-0005\0nvidia,tegra234\0"));
+    assert!(!is_jetson_compatible("raspberrypi,4-model-b\0brcm,bcm2711\0"));
+}
+
+#[test]
+fn tegrastats_is_the_vendor_tool() {
+    assert_eq!(GpuType::JetsonGpu.top_tool(), Some("tegrastats"));
+}
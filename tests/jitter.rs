@@ -0,0 +1,58 @@
+#![cfg(feature = "cli")]
+
+use std::time::Duration;
+
+use gpu_auto_top::jitter::{parse_fraction, Jitter};
+
+const INTERVAL: Duration = Duration::from_secs(10);
+
+fn sequence(hostname: &str, count: usize) -> Vec<Duration> {
+    let mut jitter = Jitter::new(0.2, hostname);
+    (0..count).map(|_| jitter.apply(INTERVAL)).collect()
+}
+
+#[test]
+fn delays_stay_within_the_fraction() {
+    for fraction in [0.0, 0.05, 0.5, 1.0] {
+        let mut jitter = Jitter::new(fraction, "gpu-node-17");
+        let (min, max) = (INTERVAL.mul_f64(1.0 - fraction), INTERVAL.mul_f64(1.0 + fraction));
+
+        for _ in 0..10_000 {
+            let delay = jitter.apply(INTERVAL);
+            assert!(delay >= min && delay <= max, "{:?} outside {:?}..={:?} for ±{}", delay, min, max, fraction);
+        }
+    }
+}
+
+#[test]
+fn delays_spread_over_the_whole_range() {
+    let delays = sequence("gpu-node-17", 10_000);
+    let below = delays.iter().filter(|delay| **delay < INTERVAL).count();
+
+    assert!((4_000..6_000).contains(&below), "{} of 10000 delays below the interval", below);
+    assert!(delays.iter().any(|delay| *delay < INTERVAL.mul_f64(0.82)));
+    assert!(delays.iter().any(|delay| *delay > INTERVAL.mul_f64(1.18)));
+}
+
+#[test]
+fn the_sequence_is_deterministic_per_hostname() {
+    assert_eq!(sequence("gpu-node-17", 100), sequence("gpu-node-17", 100));
+    assert_ne!(sequence("gpu-node-17", 100), sequence("gpu-node-18", 100));
+}
+
+#[test]
+fn zero_jitter_keeps_the_interval() {
+    let mut jitter = Jitter::new(0.0, "gpu-node-17");
+    assert!((0..100).all(|_| jitter.apply(INTERVAL) == INTERVAL));
+}
+
+#[test]
+fn fractions_outside_zero_to_one_are_rejected() {
+    assert_eq!(parse_fraction("0"), Ok(0.0));
+    assert_eq!(parse_fraction("0.1"), Ok(0.1));
+    assert_eq!(parse_fraction("1"), Ok(1.0));
+
+    for value in ["-0.1", "1.01", "10", "NaN", "inf", "", "10%"] {
+        assert_eq!(parse_fraction(value), Err(format!("Invalid --interval-jitter value (expected 0.0 to 1.0): {}", value)));
+    }
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::Duration;
+
+use gpu_auto_top::display::layout::{format_processes, format_snapshot, Layout};
+use gpu_auto_top::idle::Activity;
+use gpu_auto_top::process::GpuProcess;
+use gpu_auto_top::{GpuInfo, GpuSnapshot};
+
+fn snapshot(utilization: f64) -> GpuSnapshot {
+    GpuSnapshot {
+        memory_used_mib: Some(20480),
+        memory_total_mib: Some(81920),
+        temperature_c: Some(61.0),
+        power_w: Some(250.5),
+        activity: Some(Activity::Idle(Duration::from_secs(75))),
+        ..GpuSnapshot::new(GpuInfo { index: 0, name: "NVIDIA A100-SXM4-80GB".to_string(), bus_id: Some("0000:3b:00.0".to_string()), render_offload: None }, utilization)
+    }
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn compact_is_one_block_character() {
+    let blocks: String = [0.0, 10.0, 45.0, 80.0, 100.0, 120.0].into_iter().map(|utilization| format_snapshot(&snapshot(utilization), Layout::Compact)).collect();
+
+    assert_eq!(blocks, "▁▂▄▇██");
+}
+
+#[test]
+fn normal_is_the_usual_line() {
+    assert_eq!(
+        format_snapshot(&snapshot(45.0), Layout::Normal),
+        "GPU 0 (NVIDIA A100-SXM4-80GB) Utilization (percent): 45.0, Memory: 20480/81920 MiB, Temperature: 61°C, Power: 250.5 W, Idle for 00:01:15"
+    );
+}
+
+#[test]
+fn verbose_shows_one_metric_per_line() {
+    assert_eq!(
+        format_snapshot(&snapshot(45.0), Layout::Verbose),
+        "GPU 0 (NVIDIA A100-SXM4-80GB) at 0000:3b:00.0\n  Utilization: 45.0%\n  Memory:      20480/81920 MiB (25.0%)\n  Temperature: 61°C\n  Power:       250.5 W\n  Idle:        for 00:01:15"
+    );
+
+    let process = GpuProcess { gpu_index: 0, pid: 4242, name: "python".to_string(), utilization: Some(30.0), memory_used_mib: Some(2048), kind: None };
+    assert_eq!(format_processes(&[&process]), ["  Processes:", "     PID  Name    Busy    Memory", "    4242  python   30%  2048 MiB"]);
+    assert!(format_processes(&[]).is_empty());
+}
+
+#[test]
+fn layouts_are_parsed_by_name() {
+    assert_eq!("compact".parse(), Ok(Layout::Compact));
+    assert_eq!("verbose".parse(), Ok(Layout::Verbose));
+    assert_eq!("wide".parse::<Layout>(), Err("Unknown layout: wide (expected compact, normal or verbose)".to_string()));
+}
+
+#[test]
+fn layout_changes_the_text_output() {
+    let compact = run("layout-compact", &["-q", "--count", "2", "--layout", "compact"]);
+    assert_eq!(String::from_utf8(compact.stdout).unwrap(), "▄\n▄\n");
+
+    let verbose = run("layout-verbose", &["-q", "--count", "1", "--layout", "verbose"]);
+    let stdout = String::from_utf8(verbose.stdout).unwrap();
+    assert!(stdout.starts_with("GPU 0 (NVIDIA GeForce RTX 3090) at 0000:3b:00.0\n  Utilization: 45.0%\n"), "{}", stdout);
+    assert!(stdout.contains("\n  Processes: 1 compute, 0 graphics\n") && stdout.contains("  python   30%  2048 MiB\n"), "{}", stdout);
+
+    let json = run("layout-json", &["--format", "json", "--count", "1", "--layout", "compact"]);
+    assert!(String::from_utf8_lossy(&json.stderr).contains("Error: --layout requires the text format"));
+}
@@ -0,0 +1,32 @@
+use gpu_auto_top::live::Block;
+
+fn lines(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+#[test]
+fn the_first_draw_erases_nothing() {
+    let mut block = Block::new();
+
+    assert_eq!(block.replace(lines(&["+---+", "| 0 |"])), "+---+\n| 0 |\n");
+    assert!(!block.is_empty());
+}
+
+#[test]
+fn a_redraw_erases_the_lines_drawn_last() {
+    let mut block = Block::new();
+    block.replace(lines(&["+---+", "| 0 |", "+---+"]));
+
+    assert_eq!(block.replace(lines(&["| 1 |"])), "\x1b[3F\x1b[J| 1 |\n");
+    assert_eq!(block.replace(Vec::new()), "\x1b[1F\x1b[J");
+    assert!(block.is_empty());
+}
+
+#[test]
+fn a_message_goes_where_the_block_was_and_the_block_below_it() {
+    let mut block = Block::new();
+    assert_eq!(block.around(), (String::new(), String::new()));
+
+    block.replace(lines(&["+---+", "| 0 |"]));
+    assert_eq!(block.around(), ("\x1b[2F\x1b[J".to_string(), "+---+\n| 0 |\n".to_string()));
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+use gpu_auto_top::metadata::{label_name, Labels};
+
+#[test]
+fn parses_labels_and_names_them_for_prometheus() {
+    let labels: Labels = "rack-id=r1,zone=eu".parse().unwrap();
+
+    assert_eq!(labels.to_json(), r#"{"rack-id":"r1","zone":"eu"}"#);
+    assert_eq!(label_name("rack-id"), "rack_id");
+    assert_eq!(label_name("1u"), "_1u");
+}
+
+#[test]
+fn rejects_the_keys_gpuatop_sets() {
+    for key in ["gpu", "name", "hostname"] {
+        assert_eq!(format!("{}=x", key).parse::<Labels>(), Err(format!("Invalid label key '{}': gpu, name, hostname are set by gpuatop", key)));
+    }
+    assert!("gpu-type=x,gpus=2".parse::<Labels>().is_ok());
+}
+
+#[test]
+fn rejects_keys_that_are_the_same_prometheus_label() {
+    assert_eq!("1u=a,-1u=b".parse::<Labels>(), Err("Label keys '1u' and '-1u' are both the label '_1u' in Prometheus output".to_string()));
+
+    let mut labels: Labels = "1u=a".parse().unwrap();
+    assert!(labels.try_extend("-1u=b".parse().unwrap()).is_err());
+    // A repeated key replaces the earlier value.
+    labels.try_extend("1u=c".parse().unwrap()).unwrap();
+    assert_eq!(labels.to_json(), r#"{"1u":"c"}"#);
+}
+
+#[test]
+fn rejected_labels_stop_gpuatop_before_it_starts() {
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).output().unwrap();
+
+    let output = run(&["--label", "gpu=x", "--format", "prometheus"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: Invalid label key 'gpu': gpu, name, hostname are set by gpuatop\n");
+
+    let output = run(&["--label", "1u=a", "--label", "-1u=b", "--format", "influx"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: Label keys '1u' and '-1u' are both the label '_1u' in Prometheus output\n");
+}
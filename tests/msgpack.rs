@@ -0,0 +1,72 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::backend::EngineBusy;
+use gpu_auto_top::json::{Value, MAX_DEPTH};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::msgpack::{decode, encode_snapshot, read_frame};
+use gpu_auto_top::output::{format_snapshot, OutputContext, OutputFormat};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot() -> GpuSnapshot {
+    GpuSnapshot {
+        memory_used_mib: Some(1024),
+        memory_total_mib: Some(24576),
+        temperature_c: Some(60.0),
+        power_w: Some(120.5),
+        engines: Some(vec![EngineBusy { engine: "gfx".to_string(), ns_total: 250_000_000, busy_pct: Some(25.0) }]),
+        clock_mhz: Some(2400),
+        throttle_reasons: Some(vec!["under_voltage".to_string()]),
+        ..common::snapshot(1, "NVIDIA GeForce RTX 3090", 45.5)
+    }
+}
+
+fn context(format: OutputFormat) -> OutputContext {
+    OutputContext { format, hostname: Some("node1".to_string()), labels: "rack=a1".parse::<Labels>().unwrap(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn frame_decodes_to_the_json_output() {
+    let frame = encode_snapshot(&snapshot(), &context(OutputFormat::Msgpack));
+    let payload = read_frame(&mut frame.as_slice()).unwrap().unwrap();
+
+    assert_eq!(u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize, payload.len());
+    assert_eq!(decode(&payload).unwrap().to_json(), format_snapshot(&snapshot(), &context(OutputFormat::Ndjson)));
+}
+
+#[test]
+fn stream_splits_into_frames() {
+    let mut stream = encode_snapshot(&snapshot(), &context(OutputFormat::Msgpack));
+    stream.extend(encode_snapshot(&snapshot(), &context(OutputFormat::Msgpack)));
+    let mut reader = stream.as_slice();
+
+    assert!(read_frame(&mut reader).unwrap().is_some());
+    assert!(read_frame(&mut reader).unwrap().is_some());
+    assert!(read_frame(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn truncated_frame_is_an_error() {
+    let frame = encode_snapshot(&snapshot(), &context(OutputFormat::Msgpack));
+
+    assert!(read_frame(&mut &frame[..frame.len() - 1]).is_err());
+    assert!(read_frame(&mut &frame[..2]).is_err());
+}
+
+#[test]
+fn decodes_scalars_and_arrays() {
+    assert_eq!(decode(&[0x93, 0xc0, 0xc3, 0xff]).unwrap(), Value::Array(vec![Value::Null, Value::Bool(true), Value::Number(-1.0)]));
+    assert_eq!(decode(&[0xcd, 0x01, 0x00]).unwrap(), Value::Number(256.0));
+    assert!(decode(&[0xc4, 0x00]).is_err());
+    assert!(decode(&[0xc0, 0xc0]).is_err());
+}
+
+#[test]
+fn deeply_nested_values_are_an_error() {
+    let nested = |depth: usize| [vec![0x91; depth - 1], vec![0x90]].concat();
+
+    assert!(decode(&nested(MAX_DEPTH)).is_ok());
+    assert!(decode(&nested(MAX_DEPTH + 1)).is_err());
+    assert!(decode(&[vec![0x91; 500_000], vec![0xc0]].concat()).is_err());
+}
@@ -0,0 +1,124 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::alert::{Alert, AlertKind, Severity};
+use gpu_auto_top::event::Event;
+use gpu_auto_top::notify::{Delivery, Notifier};
+use gpu_auto_top::GpuInfo;
+
+fn alert(kind: AlertKind, severity: Severity, value: f32) -> Alert {
+    Alert {
+        gpu: GpuInfo { index: 0, name: "NVIDIA GeForce RTX 3090".to_string(), bus_id: None, render_offload: None },
+        kind,
+        severity,
+        value,
+        threshold: 85.0,
+        vram_trend: None,
+    }
+}
+
+fn desktop(urgency: &str, message: &str) -> Option<Delivery> {
+    Some(Delivery::Desktop(["-a", "gpuatop", "-u", urgency, "GPU alert", message].iter().map(|arg| arg.to_string()).collect()))
+}
+
+#[test]
+fn alerts_become_desktop_notifications_with_their_urgency() {
+    let mut notifier = Notifier::with_bus(true);
+    let now = Instant::now();
+
+    assert_eq!(
+        notifier.delivery(&alert(AlertKind::Temperature, Severity::Critical, 91.0), now),
+        desktop("critical", "GPU 0 (NVIDIA GeForce RTX 3090) temperature 91°C exceeds 85°C")
+    );
+    assert_eq!(
+        notifier.delivery(&alert(AlertKind::Utilization, Severity::Info, 99.0), now),
+        desktop("low", "GPU 0 (NVIDIA GeForce RTX 3090) utilization 99% exceeds 85%")
+    );
+    assert_eq!(
+        notifier.delivery(&alert(AlertKind::VramNearlyFull, Severity::Warning, 96.0), now),
+        desktop("normal", "GPU 0 (NVIDIA GeForce RTX 3090) VRAM 96.0% full")
+    );
+}
+
+#[test]
+fn alerts_go_to_stderr_without_a_session_bus() {
+    let mut notifier = Notifier::with_bus(false);
+
+    assert_eq!(
+        notifier.delivery(&alert(AlertKind::Temperature, Severity::Critical, 91.0), Instant::now()),
+        Some(Delivery::Stderr("gpuatop critical: GPU 0 (NVIDIA GeForce RTX 3090) temperature 91°C exceeds 85°C".to_string()))
+    );
+}
+
+#[test]
+fn one_notification_per_alert_kind_per_minute() {
+    let mut notifier = Notifier::with_bus(true);
+    let start = Instant::now();
+    let at = |seconds: u64| start + Duration::from_secs(seconds);
+    let hot = alert(AlertKind::Temperature, Severity::Critical, 91.0);
+
+    assert!(notifier.delivery(&hot, at(0)).is_some());
+    assert!(notifier.delivery(&hot, at(1)).is_none());
+    // Another GPU, same kind: still within the minute.
+    assert!(notifier.delivery(&Alert { gpu: GpuInfo { index: 1, ..hot.gpu.clone() }, ..hot.clone() }, at(2)).is_none());
+    // Another kind has its own minute.
+    assert!(notifier.delivery(&alert(AlertKind::Utilization, Severity::Info, 99.0), at(3)).is_some());
+    assert!(notifier.delivery(&hot, at(59)).is_none());
+    assert!(notifier.delivery(&hot, at(60)).is_some());
+}
+
+#[test]
+fn script_alerts_share_a_minute_of_their_own() {
+    let mut notifier = Notifier::with_bus(true);
+    let start = Instant::now();
+    let at = |seconds: u64| start + Duration::from_secs(seconds);
+    let hot = alert(AlertKind::Temperature, Severity::Critical, 91.0);
+    let script_alert = |message: &str| Event::script_alert(&hot.gpu, message);
+
+    assert!(notifier.delivery(&hot, at(0)).is_some());
+    assert_eq!(notifier.script_delivery(&script_alert("GPU 0 hot for 3 cycles"), at(1)), desktop("normal", "GPU 0 hot for 3 cycles"));
+    assert!(notifier.script_delivery(&script_alert("total power above 500 W"), at(2)).is_none());
+    assert!(notifier.script_delivery(&script_alert("total power above 500 W"), at(61)).is_some());
+}
+
+#[test]
+fn suppressed_alerts_do_not_extend_the_rate_limit() {
+    let mut notifier = Notifier::with_bus(false);
+    let start = Instant::now();
+    let hot = alert(AlertKind::Temperature, Severity::Critical, 91.0);
+
+    assert!(notifier.delivery(&hot, start).is_some());
+    for seconds in [10, 20, 30, 40, 50] {
+        assert!(notifier.delivery(&hot, start + Duration::from_secs(seconds)).is_none());
+    }
+    assert!(notifier.delivery(&hot, start + Duration::from_secs(61)).is_some());
+}
+
+#[test]
+fn the_monitor_notifies_when_an_alert_fires_not_while_it_lasts() {
+    let dir = common::fake_tools("notify");
+    let log = dir.join("notify-send.log");
+    let notify_send = dir.join("notify-send");
+    fs::write(&notify_send, format!("#!/bin/sh\nprintf '%s|' \"$@\" >> '{}'\necho >> '{}'\n", log.display(), log.display())).unwrap();
+    fs::set_permissions(&notify_send, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["--notify", "--alert-temp", "55", "--count", "3", "--interval", "200ms"])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .env("DBUS_SESSION_BUS_ADDRESS", "unix:path=/nonexistent")
+        .output()
+        .unwrap();
+    let notifications = fs::read_to_string(&log).unwrap_or_default();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(output.status.code().is_some(), "{}", String::from_utf8_lossy(&output.stderr));
+    // The fake nvidia-smi reports 60°C on every tick: the alert fires once and stays active.
+    assert_eq!(notifications, "-a|gpuatop|-u|critical|GPU alert|GPU 0 (NVIDIA GeForce RTX 3090) temperature 60°C exceeds 55°C|\n");
+}
@@ -0,0 +1,296 @@
+use gpu_auto_top::backend::{probe, Capabilities, SpawnBackend, PROBE_OUTPUT_LINES};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::{enumerate_gpus, poll_gpus, poll_gpus_with_retries, GpuInfo, GpuType, PollResult};
+
+const QUERY: [&str; 2] = ["--query-gpu=index,utilization.gpu,memory.used,memory.total,temperature.gpu,power.draw,utilization.memory,pci.bus_id", "--format=csv,noheader,nounits"];
+const ENUMERATE: [&str; 2] = ["--query-gpu=index,pci.bus_id,name", "--format=csv,noheader"];
+
+const MULTI_GPU: &str = "0, 45, 1024, 24576, 60, 120.50\n1, 3, 10, 10240, 40, 20.00\n2, 100, 80000, 81920, 83, 699.12\n";
+const MULTI_GPU_NAMES: &str =
+    "0, 00000000:3B:00.0, NVIDIA GeForce RTX 3090\n1, 00000000:5E:00.0, NVIDIA GeForce RTX 3080\n2, 00000000:86:00.0, NVIDIA A100-SXM4-80GB\n";
+const NO_DEVICES: &str = "No devices were found\n";
+const PERMISSION_DENIED: &str = "Failed to initialize NVML: Insufficient Permissions\n";
+
+fn gpus(count: u32) -> Vec<GpuInfo> {
+    (0..count).map(|index| GpuInfo { index, name: format!("GPU {}", index), bus_id: None, render_offload: None }).collect()
+}
+
+fn snapshot(result: &PollResult) -> &gpu_auto_top::GpuSnapshot {
+    match result {
+        PollResult::Ok { snapshot, .. } => snapshot,
+        other => panic!("expected a snapshot, got {:?}", other),
+    }
+}
+
+#[test]
+fn enumerates_multiple_gpus() {
+    let runner = MockRunner::new().with("nvidia-smi", &ENUMERATE, CommandOutput::ok(MULTI_GPU_NAMES));
+
+    let gpus = enumerate_gpus(&runner, &GpuType::Nvidia);
+
+    assert_eq!(gpus.len(), 3);
+    assert_eq!(gpus[2].index, 2);
+    assert_eq!(gpus[2].name, "NVIDIA A100-SXM4-80GB");
+    assert_eq!(gpus[0].bus_id.as_deref(), Some("0000:3b:00.0"));
+}
+
+#[test]
+fn enumeration_falls_back_to_a_single_device() {
+    let runner = MockRunner::new().with("nvidia-smi", &ENUMERATE, CommandOutput::failed(6, NO_DEVICES));
+
+    let gpus = enumerate_gpus(&runner, &GpuType::Nvidia);
+
+    assert_eq!(gpus.len(), 1);
+    assert_eq!(gpus[0].name, "Nvidia GPU");
+    assert_eq!(gpus[0].bus_id, None);
+}
+
+#[test]
+fn parses_multi_gpu_output() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(MULTI_GPU));
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(3));
+
+    assert_eq!(results.len(), 3);
+    let first = snapshot(&results[0]);
+    assert_eq!(first.utilization, 45.0);
+    assert_eq!(first.memory_used_mib, Some(1024));
+    assert_eq!(first.memory_total_mib, Some(24576));
+    assert_eq!(first.temperature_c, Some(60.0));
+    assert_eq!(first.power_w, Some(120.5));
+
+    let last = snapshot(&results[2]);
+    assert_eq!(last.gpu.index, 2);
+    assert_eq!(last.utilization, 100.0);
+    assert_eq!(last.memory_used_mib, Some(80000));
+}
+
+#[test]
+fn polls_only_the_requested_gpus() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(MULTI_GPU));
+    let requested = vec![GpuInfo { index: 1, name: "GPU 1".to_string(), bus_id: None, render_offload: None }];
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &requested);
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(snapshot(&results[0]).utilization, 3.0);
+}
+
+#[test]
+fn unsupported_fields_are_none() {
+    let output = "0, 12, [N/A], [N/A], 55, [N/A]\n";
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(output));
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(1));
+
+    let snapshot = snapshot(&results[0]);
+    assert_eq!(snapshot.utilization, 12.0);
+    assert_eq!(snapshot.memory_used_mib, None);
+    assert_eq!(snapshot.power_w, None);
+    assert_eq!(snapshot.temperature_c, Some(55.0));
+}
+
+#[test]
+fn missing_gpu_in_output_is_a_transient_error() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok("0, 45, 1024, 24576, 60, 120.50\n"));
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(2));
+
+    snapshot(&results[0]);
+    assert!(matches!(&results[1], PollResult::TransientError { gpu, .. } if gpu.index == 1));
+}
+
+#[test]
+fn no_devices_found_is_reported() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput { stdout: NO_DEVICES.to_string(), ..CommandOutput::failed(6, "") });
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(1));
+
+    match &results[0] {
+        PollResult::TransientError { message, .. } => assert_eq!(message, "No devices were found"),
+        other => panic!("expected a transient error, got {:?}", other),
+    }
+}
+
+#[test]
+fn permission_denied_is_reported() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::failed(4, PERMISSION_DENIED));
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(2));
+
+    assert_eq!(results.len(), 2);
+    for result in &results {
+        match result {
+            PollResult::TransientError { message, .. } => assert!(message.contains("Insufficient Permissions")),
+            other => panic!("expected a transient error, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn missing_tool_is_a_permanent_error() {
+    let runner = MockRunner::new();
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(2));
+
+    assert!(results.iter().all(|result| matches!(result, PollResult::PermanentError { .. })));
+}
+
+#[test]
+fn retries_turn_persistent_errors_permanent() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::failed(4, PERMISSION_DENIED));
+    let mut polls = 0;
+
+    let results = poll_gpus_with_retries(&gpus(1), 2, |gpus| {
+        polls += 1;
+        poll_gpus(&runner, &GpuType::Nvidia, gpus)
+    });
+
+    assert_eq!(polls, 3);
+    match &results[0] {
+        PollResult::PermanentError { message, retries, .. } => assert!(message.ends_with("(after 2 retries)") && *retries == 2),
+        other => panic!("expected a permanent error, got {:?}", other),
+    }
+}
+
+#[test]
+fn retries_recover_from_transient_errors() {
+    let failing = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::failed(4, PERMISSION_DENIED));
+    let working = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(MULTI_GPU));
+    let mut polls = 0;
+
+    let results = poll_gpus_with_retries(&gpus(2), 3, |gpus| {
+        polls += 1;
+        let runner = if polls == 1 { &failing } else { &working };
+        poll_gpus(runner, &GpuType::Nvidia, gpus)
+    });
+
+    assert_eq!(polls, 2);
+    assert_eq!(snapshot(&results[0]).utilization, 45.0);
+    assert_eq!(snapshot(&results[1]).utilization, 3.0);
+    assert!(results.iter().all(|result| result.retries() == 1), "{:?}", results);
+}
+
+#[test]
+fn a_retry_without_a_result_for_a_gpu_keeps_its_error() {
+    let working = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(MULTI_GPU));
+    let mut polls = 0;
+
+    let results = poll_gpus_with_retries(&gpus(2), 1, |gpus| {
+        polls += 1;
+        match polls {
+            1 => gpus.iter().map(|gpu| PollResult::TransientError { gpu: gpu.clone(), message: "busy".to_string(), retries: 0 }).collect(),
+            // The retry only answers for GPU 1, and first.
+            _ => poll_gpus(&working, &GpuType::Nvidia, gpus).into_iter().rev().take(1).collect(),
+        }
+    });
+
+    assert!(matches!(&results[0], PollResult::PermanentError { message, retries: 1, .. } if message == "busy (after 1 retries)"), "{:?}", results);
+    assert_eq!(snapshot(&results[1]).utilization, 3.0);
+    assert_eq!(results[1].retries(), 1);
+}
+
+#[test]
+fn parses_memory_controller_utilization() {
+    let output = "0, 45, 1024, 24576, 60, 120.50, 78\n1, 3, 10, 10240, 40, 20.00, [N/A]\n";
+
+    let snapshots = gpu_auto_top::parse_nvidia_smi_output(output, &gpus(2)).expect("parses");
+
+    assert_eq!(snapshots[&0].memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct), Some(78.0));
+    assert!(snapshots[&1].memory_bandwidth.is_none());
+}
+
+#[test]
+fn probe_reports_the_sampled_metrics() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(MULTI_GPU));
+
+    let capabilities = probe(&mut SpawnBackend::new(&runner, &GpuType::Nvidia), &gpus(3)).unwrap();
+
+    assert_eq!(capabilities, Capabilities { memory: true, temperature: true, power: true, memory_bandwidth: false });
+}
+
+#[test]
+fn failed_probe_keeps_the_tool_output_and_exit_code() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::failed(4, PERMISSION_DENIED));
+
+    let error = probe(&mut SpawnBackend::new(&runner, &GpuType::Nvidia), &gpus(1)).unwrap_err();
+
+    assert_eq!(error.backend, "nvidia-smi (per tick)");
+    assert!(error.message.contains("Insufficient Permissions"));
+    assert_eq!(error.output, ["Failed to initialize NVML: Insufficient Permissions"]);
+    assert_eq!(error.exit_code(), 4);
+}
+
+#[test]
+fn failed_probe_truncates_unparseable_output() {
+    let garbage: String = (0..25).map(|line| format!("garbage {}\n", line)).collect();
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok(&garbage));
+
+    let error = probe(&mut SpawnBackend::new(&runner, &GpuType::Nvidia), &gpus(1)).unwrap_err();
+
+    assert_eq!(error.output.len(), PROBE_OUTPUT_LINES + 1);
+    assert_eq!(error.output.last().unwrap(), "... 15 more lines");
+    assert_eq!(error.exit_code(), 1);
+}
+
+fn gpus_at(bus_ids: &[&str]) -> Vec<GpuInfo> {
+    bus_ids
+        .iter()
+        .enumerate()
+        .map(|(index, bus_id)| GpuInfo { index: index as u32, name: format!("GPU {}", index), bus_id: Some(bus_id.to_string()), render_offload: None })
+        .collect()
+}
+
+/// Query output in the styles of the 470, 535 and 550 driver series: units left in despite
+/// `nounits` and `[Not Supported]`, trailing padding and `[N/A]`, CRLF line endings.
+#[test]
+fn parses_the_query_output_of_each_driver_series() {
+    let gpus = gpus_at(&["0000:3b:00.0", "0000:5e:00.0"]);
+
+    for fixture in ["query-470.csv", "query-535.csv", "query-550.csv"] {
+        let output = std::fs::read_to_string(format!("{}/tests/fixtures/nvidia-smi/{}", env!("CARGO_MANIFEST_DIR"), fixture)).unwrap();
+        let snapshots = gpu_auto_top::parse_nvidia_smi_output(&output, &gpus).unwrap_or_else(|err| panic!("{}: {}", fixture, err));
+
+        assert_eq!(snapshots.len(), 2, "{}", fixture);
+        let first = &snapshots[&0];
+        assert_eq!((first.utilization, first.memory_used_mib, first.memory_total_mib), (45.0, Some(1024), Some(24576)), "{}", fixture);
+        assert_eq!((first.temperature_c, first.power_w), (Some(60.0), Some(120.5)), "{}", fixture);
+        assert_eq!(first.memory_bandwidth.and_then(|bandwidth| bandwidth.utilization_pct), Some(12.0), "{}", fixture);
+        assert_eq!((snapshots[&1].utilization, snapshots[&1].power_w), (3.0, None), "{}", fixture);
+    }
+}
+
+#[test]
+fn enumeration_reads_quoted_names() {
+    let output = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/nvidia-smi/enumerate-550.csv")).unwrap();
+    let runner = MockRunner::new().with("nvidia-smi", &ENUMERATE, CommandOutput::ok(&output));
+
+    let gpus = enumerate_gpus(&runner, &GpuType::Nvidia);
+
+    assert_eq!(gpus[0].name, "NVIDIA GeForce RTX 4090, rev A");
+    assert_eq!(gpus[1].name, "Quadro RTX 8000 \"Turing\"");
+    assert_eq!(gpus[1].bus_id.as_deref(), Some("0000:5e:00.0"));
+}
+
+#[test]
+fn lines_are_matched_by_bus_id_not_position() {
+    // Detected in another order than nvidia-smi's index order.
+    let gpus = gpus_at(&["0000:5e:00.0", "0000:3b:00.0"]);
+    let output = "0, 45, 1024, 24576, 60, 120.50, 12, 00000000:3B:00.0\n1, 3, 10, 10240, 40, 20.00, 0, 00000000:5E:00.0\n";
+
+    let snapshots = gpu_auto_top::parse_nvidia_smi_output(output, &gpus).unwrap();
+
+    assert_eq!(snapshots[&0].utilization, 3.0);
+    assert_eq!(snapshots[&1].utilization, 45.0);
+    assert_eq!(snapshots[&1].gpu.bus_id.as_deref(), Some("0000:3b:00.0"));
+}
+
+#[test]
+fn a_missing_line_is_reported_for_its_gpu_only() {
+    let runner = MockRunner::new().with("nvidia-smi", &QUERY, CommandOutput::ok("0, 45, 1024, 24576, 60, 120.50\n"));
+
+    let results = poll_gpus(&runner, &GpuType::Nvidia, &gpus(2));
+
+    assert_eq!(snapshot(&results[0]).utilization, 45.0);
+    assert!(matches!(&results[1], PollResult::TransientError { message, .. } if message == "No sample for GPU 1 in the output"), "{:?}", results[1]);
+}
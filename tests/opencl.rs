@@ -0,0 +1,13 @@
+#![cfg(feature = "opencl")]
+
+use gpu_auto_top::opencl::gpu_type_for_vendor;
+use gpu_auto_top::GpuType;
+
+#[test]
+fn vendor_strings_map_to_gpu_types() {
+    assert!(matches!(gpu_type_for_vendor("NVIDIA Corporation"), Some(GpuType::Nvidia)));
+    assert!(matches!(gpu_type_for_vendor("Advanced Micro Devices, Inc."), Some(GpuType::Amd)));
+    assert!(matches!(gpu_type_for_vendor("AMD"), Some(GpuType::Amd)));
+    assert!(matches!(gpu_type_for_vendor("Intel(R) Corporation"), Some(GpuType::Intel)));
+    assert!(gpu_type_for_vendor("Mesa/X.org").is_none());
+}
@@ -0,0 +1,95 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::Duration;
+
+use gpu_auto_top::aperture::ApertureMetrics;
+use gpu_auto_top::idle::Activity;
+use gpu_auto_top::output::FieldSet;
+use gpu_auto_top::{GpuSnapshot, MemoryBandwidthMetrics};
+
+fn snapshot() -> GpuSnapshot {
+    GpuSnapshot {
+        utilization_max: Some(80.0),
+        memory_used_mib: Some(1024),
+        memory_total_mib: Some(24576),
+        temperature_c: Some(60.0),
+        power_w: Some(120.5),
+        memory_bandwidth: Some(MemoryBandwidthMetrics { read_gbps: None, write_gbps: None, utilization_pct: Some(12.0) }),
+        aperture: Some(ApertureMetrics { reserved_mib: Some(346), bar1_used_mib: Some(5), bar1_total_mib: Some(256), ..ApertureMetrics::default() }),
+        activity: Some(Activity::Idle(Duration::from_secs(227))),
+        ..common::snapshot(0, "NVIDIA GeForce RTX 3090", 45.0)
+    }
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).env("PATH", common::path_with(&dir)).env("XDG_RUNTIME_DIR", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn parses_field_lists() {
+    assert_eq!("all".parse::<FieldSet>(), Ok(FieldSet::ALL));
+    assert_eq!(" util, mem ".parse::<FieldSet>(), Ok(FieldSet::UTIL | FieldSet::MEM));
+    assert_eq!(FieldSet::default(), FieldSet::ALL);
+
+    let err = "util,clocks".parse::<FieldSet>().unwrap_err();
+    assert_eq!(err, "Unknown output field: clocks (expected all or util, mem, temp, power, nvlink, split, membw, bar1, vis_vram, idle)");
+}
+
+#[test]
+fn drops_the_metrics_outside_the_set() {
+    let trimmed = (FieldSet::UTIL | FieldSet::MEM).apply(&snapshot());
+
+    assert_eq!((trimmed.utilization, trimmed.utilization_max), (45.0, Some(80.0)));
+    assert_eq!((trimmed.memory_used_mib, trimmed.memory_total_mib), (Some(1024), Some(24576)));
+    assert_eq!((trimmed.temperature_c, trimmed.power_w), (None, None));
+    assert!(trimmed.memory_bandwidth.is_none());
+    assert_eq!(trimmed.aperture, Some(ApertureMetrics::default()));
+
+    let all = FieldSet::ALL.apply(&snapshot());
+    assert_eq!((all.temperature_c, all.aperture), (snapshot().temperature_c, snapshot().aperture));
+}
+
+#[test]
+fn json_records_carry_only_the_requested_fields() {
+    let output = run("fields-ndjson", &["--format", "ndjson", "--count", "1", "--output-fields", "util,mem"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"schema_version\":1,\"gpu\":0,\"name\":\"NVIDIA GeForce RTX 3090\",\"utilization\":45,\"memory_used_mib\":1024,\"memory_total_mib\":24576,\"source\":\"nvidia-smi\",\"tick_seq\":0}\n"
+    );
+}
+
+#[test]
+fn text_lines_skip_the_other_columns() {
+    let output = run("fields-text", &["-q", "--count", "1", "--output-fields", "temp"]);
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "GPU 0 (NVIDIA GeForce RTX 3090) Utilization (percent): 45.0, Temperature: 60°C\n");
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    let output = run("fields-unknown", &["--count", "1", "--output-fields", "fan"]);
+
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown output field: fan"), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn help_lists_every_field() {
+    let output = run("fields-help", &["--help"]);
+    let help = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    for (name, field, description) in FieldSet::NAMES {
+        assert!(help.contains(&format!("      {:<10} {}\n", name, description)), "{}", help);
+        assert_eq!(name.parse::<FieldSet>(), Ok(field));
+    }
+}
@@ -0,0 +1,300 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use assert_cmd::cargo::{cargo_bin, cargo_bin_cmd};
+use assert_cmd::prelude::*;
+use gpu_auto_top::json::{self, Value};
+use predicates::prelude::*;
+
+fn run_unchecked(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = cargo_bin_cmd!("gpu_auto_top").args(args).env("PATH", common::path_with(&dir)).env("XDG_RUNTIME_DIR", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    run_unchecked(name, args).assert().success().get_output().clone()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn ndjson_stdout_is_only_records() {
+    let output = run("mode-ndjson", &["--format", "ndjson", "--count", "2"]);
+    let stdout = stdout(&output);
+
+    assert!(stdout.starts_with('{'), "stdout does not start with a record: {:?}", stdout);
+    let records: Vec<&str> = stdout.lines().collect();
+    assert_eq!(records.len(), 2);
+    for (tick_seq, record) in records.into_iter().enumerate() {
+        assert!(matches!(json::parse(record), Ok(Value::Object(_))), "not a JSON object: {}", record);
+        assert!(record.ends_with(&format!(",\"tick_seq\":{}}}", tick_seq)), "unexpected tick_seq: {}", record);
+    }
+    // The banner still reaches the user, on stderr.
+    assert!(String::from_utf8_lossy(&output.stderr).contains("GPU type: Nvidia"));
+}
+
+#[test]
+fn machine_hostname_tags_records_with_the_system_hostname() {
+    let output = run("mode-hostname", &["--format", "ndjson", "--count", "1", "--machine-hostname"]);
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname").unwrap();
+
+    assert!(stdout(&output).starts_with(&format!("{{\"schema_version\":1,\"hostname\":{:?},", hostname.trim())), "{}", stdout(&output));
+}
+
+#[test]
+fn json_stdout_is_one_document() {
+    let output = run("mode-json", &["--format", "json", "--count", "1"]);
+    let stdout = stdout(&output);
+
+    assert!(stdout.starts_with('{'), "stdout does not start with the document: {:?}", stdout);
+    assert!(matches!(json::parse(&stdout), Ok(Value::Object(_))), "not a JSON document: {}", stdout);
+}
+
+#[test]
+fn msgpack_stdout_decodes_back_to_json() {
+    let output = run("mode-msgpack", &["--format", "msgpack", "--count", "2"]);
+
+    let decoded = cargo_bin_cmd!("gpu_auto_top").arg("--decode-msgpack").write_stdin(output.stdout).assert().success();
+    let decoded = stdout(decoded.get_output());
+    assert_eq!(decoded.matches("\"utilization\": 45").count(), 2, "unexpected JSON: {}", decoded);
+    assert!(decoded.contains("\"name\": \"NVIDIA GeForce RTX 3090\""));
+}
+
+/// Runs gpuatop without an end, reads the first `bytes` of its stdout and closes the pipe, as
+/// `head -c` does. Returns how gpuatop exited, or `None` when it kept running, and its stderr.
+fn run_until_the_reader_closes(name: &str, args: &[&str], bytes: usize) -> (Option<ExitStatus>, String) {
+    let dir = common::fake_tools(name);
+    let mut child = Command::new(cargo_bin!("gpu_auto_top"))
+        .args(["--interval", "100ms", "--allow-fast-poll"])
+        .args(args)
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut head = vec![0; bytes];
+    child.stdout.take().unwrap().read_exact(&mut head).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        match child.try_wait().unwrap() {
+            Some(status) => break Some(status),
+            None if Instant::now() > deadline => {
+                child.kill().unwrap();
+                child.wait().unwrap();
+                break None;
+            }
+            None => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    let mut stderr = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    (status, stderr)
+}
+
+#[test]
+fn a_closed_pipe_stops_monitoring() {
+    let (status, stderr) = run_until_the_reader_closes("mode-closed-pipe", &["--format", "ndjson"], 1);
+
+    let status = status.expect("gpuatop kept running after its reader went away");
+    assert_eq!(status.code(), Some(0), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn a_closed_pipe_stops_msgpack_output() {
+    let (status, stderr) = run_until_the_reader_closes("mode-closed-msgpack", &["--format", "msgpack"], 1);
+
+    let status = status.expect("gpuatop kept running after its reader went away");
+    assert_eq!(status.code(), Some(0), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn the_msgpack_decoder_stops_when_its_reader_closes() {
+    let frames = run("mode-msgpack-decoder", &["--format", "msgpack", "--count", "2"]).stdout.repeat(2000);
+
+    let mut decoder = Command::new(cargo_bin!("gpu_auto_top")).arg("--decode-msgpack").stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let mut stdin = decoder.stdin.take().unwrap();
+    // The decoder stops reading once it exits, which fails the rest of the write.
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(&frames);
+    });
+    let mut head = [0; 1];
+    decoder.stdout.take().unwrap().read_exact(&mut head).unwrap();
+    let decoded = decoder.wait_with_output().unwrap();
+    writer.join().unwrap();
+
+    decoded.assert().code(0).stderr("");
+}
+
+/// Splits a line protocol point at spaces that are not escaped with a backslash.
+fn split_unescaped(line: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in line.chars() {
+        match c {
+            ' ' if !escaped => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    parts
+}
+
+#[test]
+fn influx_stdout_is_only_line_protocol() {
+    let output = run("mode-influx", &["--format", "influx", "--count", "2"]);
+    let stdout = stdout(&output);
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    for line in lines {
+        let parts = split_unescaped(line);
+        assert_eq!(parts.len(), 3, "not a line protocol point: {}", line);
+        assert!(parts[0].starts_with("gpu,gpu=0,"), "unexpected measurement: {}", line);
+        assert!(parts[1].contains("utilization=45"), "unexpected fields: {}", line);
+        assert!(parts[2].parse::<u128>().is_ok(), "invalid timestamp: {}", line);
+    }
+}
+
+#[test]
+fn quiet_text_mode_prints_only_samples() {
+    run_unchecked("mode-quiet", &["--quiet", "--count", "1"]).assert().success().stdout("GPU 0 (NVIDIA GeForce RTX 3090) Utilization (percent): 45.0, Memory: 1024/24576 MiB, Temperature: 60°C, Power: 120.5 W\n");
+}
+
+#[test]
+fn precision_sets_the_decimals_of_the_text_line() {
+    let output = run("mode-precision", &["-q", "--count", "1", "--precision", "2"]);
+
+    assert!(stdout(&output).starts_with("GPU 0 (NVIDIA GeForce RTX 3090) Utilization (percent): 45.00, Memory"), "{}", stdout(&output));
+}
+
+#[test]
+fn precision_is_rejected_outside_the_text_format() {
+    let output = run_unchecked("mode-precision-json", &["--format", "json", "--count", "1", "--precision", "2"]);
+
+    output.assert().code(2).stdout("").stderr(predicate::str::contains("Error: --precision requires the text format"));
+}
+
+#[test]
+fn quiet_machine_mode_keeps_stderr_free_of_the_banner() {
+    let output = run("mode-quiet-ndjson", &["-q", "--format", "ndjson", "--count", "1"]);
+
+    assert_eq!(stdout(&output).lines().count(), 1);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("GPU type"));
+}
+
+#[test]
+fn diff_output_prints_unchanged_samples_once() {
+    let output = run("mode-diff", &["-q", "--diff-output", "--count", "3"]);
+
+    let lines: Vec<String> = stdout(&output).lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 3, "unexpected output: {:?}", lines);
+    assert!(lines[0].starts_with("GPU 0 "));
+    assert_eq!(lines[1..], ["[unchanged]", "[unchanged]"]);
+}
+
+#[test]
+fn aggregate_json_wraps_the_tick_in_one_document() {
+    let output = run("mode-aggregate", &["--aggregate", "--format", "json", "--count", "1"]);
+
+    let stdout = stdout(&output);
+    assert!(stdout.starts_with("{\"schema_version\":1,\"gpus\":[{\"schema_version\":1,\"gpu\":0,"), "unexpected output: {:?}", stdout);
+    assert!(stdout.contains("\"totals\":{\"gpus\":"));
+    assert_eq!(stdout.lines().count(), 1);
+}
+
+#[test]
+fn prometheus_writes_one_page_and_exits() {
+    let stdout = stdout(&run("mode-prometheus", &["--format", "prometheus"]));
+
+    assert!(stdout.starts_with("# HELP gpuatop_utilization_percent "), "unexpected page: {:?}", stdout);
+    assert_eq!(stdout.matches("gpuatop_utilization_percent{").count(), 1);
+    assert!(stdout.ends_with("# EOF\n"));
+}
+
+#[test]
+fn self_stats_add_gpuatops_cpu_time_to_the_page() {
+    let stdout = stdout(&run("mode-prometheus-self-stats", &["--format", "prometheus", "--self-stats"]));
+
+    let sample = stdout.lines().find(|line| line.starts_with("gpuatop_self_cpu_seconds{")).expect("no gpuatop_self_cpu_seconds");
+    assert!(sample.rsplit_once(' ').unwrap().1.parse::<f64>().unwrap() >= 0.0, "{}", sample);
+    assert!(stdout.ends_with("# EOF\n"));
+}
+
+#[test]
+fn format_template_shapes_the_text_line() {
+    let stdout = stdout(&run("mode-template", &["-q", "--count", "1", "--format", "gpu{index}: {util:>3}% {mem_used}/{mem_total}MiB"]));
+
+    assert_eq!(stdout, "gpu0:  45% 1024/24576MiB\n");
+}
+
+#[test]
+fn text_mode_keeps_the_banner_and_summary() {
+    let stdout = stdout(&run("mode-text", &["--count", "1"]));
+
+    assert!(stdout.starts_with("Identifying GPU type...\n"), "unexpected banner: {:?}", stdout);
+    assert!(stdout.contains("Summary:"));
+}
+
+#[test]
+fn statsd_prints_one_gauge_per_metric() {
+    let stdout = stdout(&run("mode-statsd", &["--format", "statsd", "--count", "1", "--statsd-prefix", "Lab.GPU", "--statsd-tags", "env=prod"]));
+
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "lab.gpu.gpu0.utilization:45|g|#env:prod,gpu_name:nvidia_geforce_rtx_3090");
+    assert!(lines.contains(&"lab.gpu.gpu0.power_w:120.5|g|#env:prod,gpu_name:nvidia_geforce_rtx_3090"), "{:?}", lines);
+    assert!(lines.iter().all(|line| line.contains("|g|#")), "{:?}", lines);
+}
+
+#[test]
+fn grace_period_waits_before_the_first_poll() {
+    let started = std::time::Instant::now();
+    let stdout = stdout(&run("mode-grace", &["--count", "1", "--grace-period", "1"]));
+
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+    let waiting = stdout.find("Waiting 1s for GPU driver to initialize...\n").expect("no countdown");
+    assert!(waiting < stdout.find("Utilization").unwrap(), "{:?}", stdout);
+}
+
+#[test]
+fn no_grace_skips_the_grace_period() {
+    let stdout = stdout(&run("mode-no-grace", &["--count", "1", "--grace-period", "5", "--no-grace"]));
+
+    assert!(!stdout.contains("Waiting"), "{:?}", stdout);
+}
+
+#[test]
+fn by_user_adds_a_users_record_per_tick() {
+    let uid = gpu_auto_top::users::parse_status_uid(&fs::read_to_string("/proc/self/status").unwrap()).unwrap();
+    let user = gpu_auto_top::users::UserNames::default().name(uid);
+    let stdout = stdout(&run("mode-by-user", &["--format", "ndjson", "--count", "1", "--by-user"]));
+
+    let records: Vec<&str> = stdout.lines().collect();
+    assert_eq!(records.len(), 2);
+    let expected = format!("{{\"schema_version\":1,\"users\":[{{\"user\":{},\"processes\":1,\"utilization\":30,\"memory_used_mib\":2048}}],\"tick_seq\":0}}", json::Value::String(user).to_json());
+    assert_eq!(records[1], expected);
+}
+
+#[test]
+fn errors_exit_nonzero_on_stderr() {
+    let unknown = run_unchecked("mode-unknown-option", &["--no-such-option"]);
+    unknown.assert().code(2).stdout("").stderr(predicate::str::is_empty().not());
+
+    let missing_gpu = run_unchecked("mode-snapshot-no-gpu", &["snapshot"]);
+    missing_gpu.assert().code(2).stdout("").stderr(predicate::str::starts_with("Error: "));
+}
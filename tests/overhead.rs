@@ -0,0 +1,53 @@
+#![cfg(feature = "cli")]
+
+use std::time::Duration;
+
+use gpu_auto_top::overhead::{read_cpu_times, CpuTimes, SelfStats};
+
+#[test]
+fn reads_the_cpu_times_of_this_process() {
+    let times = read_cpu_times().unwrap();
+    assert!(times.own_seconds >= 0.0 && times.children_seconds >= 0.0);
+}
+
+#[test]
+fn cpu_seconds_count_own_and_child_time_since_the_start() {
+    let stats = SelfStats::starting_at(CpuTimes { own_seconds: 1.0, children_seconds: 0.5 });
+
+    assert_eq!(stats.cpu_seconds_at(CpuTimes { own_seconds: 1.25, children_seconds: 1.5 }), 1.25);
+    assert!(SelfStats::new().cpu_seconds() >= 0.0);
+}
+
+#[test]
+fn summary_reports_cpu_share_and_collection_time() {
+    let mut stats = SelfStats::starting_at(CpuTimes { own_seconds: 1.0, children_seconds: 0.5 });
+    for millis in [4, 10, 7] {
+        stats.record_tick(Duration::from_millis(millis));
+    }
+
+    let summary = stats.format_summary_at(CpuTimes { own_seconds: 1.3, children_seconds: 1.4 }, Duration::from_secs(60));
+
+    assert_eq!(
+        summary,
+        [
+            "  gpuatop: CPU 0.30s own + 0.90s child processes (2.00% of a core), 3 ticks",
+            "  gpuatop: collecting took 7.0 ms per tick on average, 10.0 ms at most",
+        ]
+    );
+}
+
+#[test]
+fn summary_without_ticks_reports_zero() {
+    let stats = SelfStats::starting_at(CpuTimes::default());
+
+    let summary = stats.format_summary_at(CpuTimes::default(), Duration::ZERO);
+
+    assert_eq!(summary[0], "  gpuatop: CPU 0.00s own + 0.00s child processes (0.00% of a core), 0 ticks");
+    assert_eq!(summary[1], "  gpuatop: collecting took 0.0 ms per tick on average, 0.0 ms at most");
+}
+
+#[test]
+fn summary_measures_from_the_start_of_the_session() {
+    let summary = SelfStats::new().format_summary(Duration::from_secs(1));
+    assert!(summary[0].starts_with("  gpuatop: CPU ") && summary[0].ends_with(", 0 ticks"), "{}", summary[0]);
+}
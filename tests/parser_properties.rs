@@ -0,0 +1,151 @@
+// Property tests for the vendor output parsers: arbitrary input must never panic, and any
+// well-formed output must parse back to the values it was generated from.
+use gpu_auto_top::{clamp_percent, parse_intel_gpu_top_output, parse_nvidia_smi_output, parse_radeontop_output, GpuInfo};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+fn gpus(count: u32) -> Vec<GpuInfo> {
+    (0..count).map(|index| GpuInfo { index, name: format!("GPU {}", index), bus_id: None, render_offload: None }).collect()
+}
+
+const NVIDIA_FIXTURE: &str = "0, 45, 1024, 24576, 60, 120.50\n1, 3, 10, 10240, 40, 20.00\n";
+const RADEONTOP_FIXTURE: &str = "Dumping to -, line limit 1.\n1700000000.123456: bus 03, gpu 12.50%, ee 0.00%, vram 10.23% 835.12mb, gtt 0.50% 40.00mb\n";
+const INTEL_FIXTURE: &str = " Freq MHz      IRQ RC6     RCS/0           BCS/0 \n req  act       /s   %       %  se  wa       %  se  wa \n 350  300       12  85   23.45   0   0    0.00   0   0 ";
+
+/// A string biased towards characters that matter to the parsers.
+fn noise(max_len: usize) -> impl Strategy<Value = String> {
+    let character = prop_oneof![7 => "[0159.,% \n\\-:\\[\\]N/AgpumbRCSé\t]", 1 => "\\PC"];
+    proptest::collection::vec(character, 0..=max_len).prop_map(|parts| parts.concat())
+}
+
+/// `input` with a part of it cut, duplicated or overwritten with noise.
+fn mutated(input: &'static str) -> impl Strategy<Value = String> {
+    (any::<Index>(), any::<Index>(), 0..3, noise(8)).prop_map(move |(start, len, mutation, noise)| {
+        let chars: Vec<char> = input.chars().collect();
+        let start = start.index(chars.len() + 1);
+        let end = start + len.index(chars.len() - start + 1);
+        let (head, part, tail): (String, String, String) = (chars[..start].iter().collect(), chars[start..end].iter().collect(), chars[end..].iter().collect());
+        match mutation {
+            0 => head + &tail,
+            1 => head + &part + &part + &tail,
+            _ => head + &noise + &tail,
+        }
+    })
+}
+
+/// A percentage with two decimals, as the vendor tools print them.
+fn percent() -> impl Strategy<Value = f32> {
+    (0..=10000u32).prop_map(|hundredths| hundredths as f32 / 100.0)
+}
+
+/// An nvidia-smi row: utilization, used and total memory, temperature and power.
+fn nvidia_row() -> impl Strategy<Value = (f32, u64, u64, f32, f32)> {
+    (1..=200_000u64).prop_flat_map(|total| (percent(), 0..=total, Just(total), (0..120u32).prop_map(|celsius| celsius as f32), percent().prop_map(|power| power * 7.0)))
+}
+
+#[test]
+fn percentages_at_the_edges_are_clamped() {
+    assert_eq!(clamp_percent(42.37), 42.37);
+    assert_eq!(clamp_percent(100.000000001), 100.0);
+    assert_eq!(clamp_percent(10240.0), 100.0);
+    assert!(clamp_percent(-0.0).is_sign_positive());
+    assert_eq!(clamp_percent(-3.0), 0.0);
+    assert_eq!(clamp_percent(f64::NAN), 0.0);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn parsers_never_panic_on_arbitrary_input(input in noise(200)) {
+        let gpus = gpus(4);
+        let _ = parse_nvidia_smi_output(&input, &gpus);
+        let _ = parse_radeontop_output(&input, &gpus[0]);
+        let _ = parse_intel_gpu_top_output(&input, &gpus[0]);
+    }
+
+    #[test]
+    fn parsers_never_panic_on_mutated_fixtures(nvidia in mutated(NVIDIA_FIXTURE), radeontop in mutated(RADEONTOP_FIXTURE), intel in mutated(INTEL_FIXTURE)) {
+        let gpus = gpus(4);
+        let _ = parse_nvidia_smi_output(&nvidia, &gpus);
+        let _ = parse_radeontop_output(&radeontop, &gpus[0]);
+        let _ = parse_intel_gpu_top_output(&intel, &gpus[0]);
+    }
+
+    #[test]
+    fn percentages_are_clamped_to_the_valid_range(value in any::<f64>()) {
+        let clamped = clamp_percent(value);
+        prop_assert!((0.0..=100.0).contains(&clamped) && clamped.is_sign_positive(), "{} -> {}", value, clamped);
+        if (0.0..=100.0).contains(&value) && value.is_sign_positive() {
+            prop_assert_eq!(clamped, value);
+        }
+    }
+
+    #[test]
+    fn valid_nvidia_smi_output_always_parses(rows in proptest::collection::vec(nvidia_row(), 1..=8)) {
+        let output: String = rows
+            .iter()
+            .enumerate()
+            .map(|(index, (utilization, used, total, temperature, power))| format!("{}, {}, {}, {}, {}, {:.2}\n", index, utilization, used, total, temperature, power))
+            .collect();
+
+        let snapshots = parse_nvidia_smi_output(&output, &gpus(rows.len() as u32)).map_err(|err| TestCaseError::fail(format!("{}: {:?}", err, output)))?;
+
+        prop_assert_eq!(snapshots.len(), rows.len());
+        for (index, (utilization, used, total, temperature, _)) in rows.iter().enumerate() {
+            let snapshot = &snapshots[&(index as u32)];
+            prop_assert_eq!(snapshot.utilization, utilization.to_string().parse::<f64>().unwrap(), "{:?}", output);
+            prop_assert_eq!(snapshot.memory_used_mib, Some(*used));
+            prop_assert_eq!(snapshot.memory_total_mib, Some(*total));
+            prop_assert_eq!(snapshot.temperature_c, Some(*temperature));
+            prop_assert!(snapshot.power_w.is_some());
+        }
+    }
+
+    #[test]
+    fn valid_radeontop_output_always_parses(
+        seconds in 1_700_000_000..1_701_000_000u64,
+        micros in 0..1_000_000u32,
+        bus in any::<u8>(),
+        utilization in percent(),
+        ee in percent(),
+        vram in percent(),
+        vram_mb in 0..65536u64,
+        vram_fraction in 0..100u32,
+        sclk in percent(),
+    ) {
+        let output = format!(
+            "Dumping to -, line limit 1.\n{}.{}: bus {:02x}, gpu {:.2}%, ee {:.2}%, vram {:.2}% {}.{:02}mb, sclk {:.2}% 1.000ghz\n",
+            seconds, micros, bus, utilization, ee, vram, vram_mb, vram_fraction, sclk
+        );
+
+        let snapshot = parse_radeontop_output(&output, &gpus(1)[0]).map_err(|err| TestCaseError::fail(format!("{}: {:?}", err, output)))?;
+
+        prop_assert_eq!(snapshot.utilization, format!("{:.2}", utilization).parse::<f64>().unwrap());
+        prop_assert_eq!(snapshot.memory_used_mib, Some(vram_mb));
+    }
+
+    #[test]
+    fn valid_intel_gpu_top_output_always_parses(
+        other_engines in 0..=4usize,
+        render_position in any::<Index>(),
+        busy in proptest::collection::vec(percent(), 5),
+        frequencies in (0..2000u32, 0..2000u32),
+        irq in 0..10000u32,
+        rc6 in 0..=100u32,
+    ) {
+        let mut engines: Vec<&str> = ["BCS/0", "VCS/0", "VECS/0", "VCS/1"][..other_engines].to_vec();
+        let render_position = render_position.index(engines.len() + 1);
+        engines.insert(render_position, "RCS/0");
+        let busy = &busy[..engines.len()];
+
+        let header = format!(" Freq MHz      IRQ RC6 {}", engines.iter().map(|engine| format!("{:>15}", engine)).collect::<String>());
+        let units = format!(" req  act       /s   % {}", "       %  se  wa".repeat(engines.len()));
+        let values = format!(" {} {} {} {} {}", frequencies.0, frequencies.1, irq, rc6, busy.iter().map(|busy| format!("{:7.2}   0   0 ", busy)).collect::<String>());
+        let output = format!("{}\n{}\n{}", header, units, values);
+
+        let snapshot = parse_intel_gpu_top_output(&output, &gpus(1)[0]).map_err(|err| TestCaseError::fail(format!("{}: {:?}", err, output)))?;
+
+        prop_assert_eq!(snapshot.utilization, format!("{:.2}", busy[render_position]).parse::<f64>().unwrap(), "{}", output);
+    }
+}
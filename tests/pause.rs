@@ -0,0 +1,54 @@
+#![cfg(feature = "cli")]
+
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use gpu_auto_top::display::detail::View;
+use gpu_auto_top::pause::{apply_key, apply_kernel_log_key, apply_view_key, is_paused, kernel_log_shown, typed_index, view, wait_until_resumed, INDEX_KEY_INTERVAL};
+
+#[test]
+fn p_toggles_and_r_resumes() {
+    assert!(apply_key(false, "p\n"));
+    assert!(!apply_key(true, "p"));
+    assert!(!apply_key(true, "r"));
+    assert!(!apply_key(false, "R"));
+    assert!(apply_key(true, ""));
+    assert!(!apply_key(false, "q"));
+}
+
+#[test]
+fn waiting_returns_at_once_unless_paused() {
+    assert!(!is_paused());
+    assert!(!wait_until_resumed(&AtomicBool::new(false)));
+    assert!(wait_until_resumed(&AtomicBool::new(true)));
+}
+
+#[test]
+fn a_gpu_index_zooms_in_and_o_or_escape_zooms_out() {
+    assert_eq!(apply_view_key(View::Overview, "1\n"), View::Detail(1));
+    assert_eq!(apply_view_key(View::Detail(1), "0"), View::Detail(0));
+    assert_eq!(apply_view_key(View::Detail(1), "o"), View::Overview);
+    assert_eq!(apply_view_key(View::Detail(1), "\u{1b}\n"), View::Overview);
+    assert_eq!(apply_view_key(View::Detail(1), "p"), View::Detail(1));
+    assert_eq!(apply_view_key(View::Overview, "-1"), View::Overview);
+    assert_eq!(view(), View::Overview);
+}
+
+#[test]
+fn d_toggles_the_kernel_log() {
+    assert!(apply_kernel_log_key(false, "d\n"));
+    assert!(!apply_kernel_log_key(true, "D"));
+    assert!(apply_kernel_log_key(true, "p"));
+    assert!(!apply_kernel_log_key(false, "1"));
+    assert!(!kernel_log_shown());
+}
+
+#[test]
+fn digits_pressed_in_quick_succession_make_up_one_index() {
+    let quick = Duration::from_millis(200);
+    assert_eq!(typed_index("", '1', quick), "1");
+    assert_eq!(typed_index("1", '2', quick), "12");
+    assert_eq!(typed_index("1", '2', INDEX_KEY_INTERVAL), "2");
+    assert_eq!(typed_index("12", 'o', quick), "");
+    assert_eq!(apply_view_key(View::Overview, &typed_index("1", '2', quick)), View::Detail(12));
+}
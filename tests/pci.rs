@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::Path;
+
+use gpu_auto_top::pci::{format_device_group, group_virtual_functions, list_display_devices_in, lspci_gpus, parse_lspci, LspciDevice, PciDevice};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::{try_identify_gpu_card, GpuType};
+
+fn fixture_devices() -> Vec<PciDevice> {
+    list_display_devices_in(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sriov")).unwrap()
+}
+
+fn lspci_fixture(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lspci").join(name)).unwrap()
+}
+
+fn addresses(devices: &[PciDevice]) -> Vec<&str> {
+    devices.iter().map(|device| device.address.as_str()).collect()
+}
+
+#[test]
+fn lists_display_devices_with_their_physical_function() {
+    let devices = fixture_devices();
+
+    // The audio function at 00:1f.3 is not a display controller, nor is the BMC console at 03:00.0.
+    assert_eq!(addresses(&devices), vec!["0000:00:02.0", "0000:00:02.1", "0000:00:02.2", "0000:00:02.3", "0000:3b:00.0", "0000:c1:00.4"]);
+    assert_eq!(devices[0], PciDevice { address: "0000:00:02.0".to_string(), vendor_id: 0x8086, device_id: 0xa7a0, class: 0x030000, physfn: None });
+    assert_eq!(devices[1].physfn.as_deref(), Some("0000:00:02.0"));
+}
+
+#[test]
+fn groups_virtual_functions_under_their_physical_function() {
+    let groups = group_virtual_functions(&fixture_devices());
+
+    let physical: Vec<_> = groups.iter().map(|group| group.physical.address.as_str()).collect();
+    assert_eq!(physical, vec!["0000:00:02.0", "0000:3b:00.0", "0000:c1:00.4"]);
+    assert_eq!(addresses(&groups[0].virtual_functions), vec!["0000:00:02.1", "0000:00:02.2", "0000:00:02.3"]);
+    assert_eq!(format_device_group(&groups[0]), "0000:00:02.0 Intel [8086:a7a0] (3 virtual functions)");
+}
+
+#[test]
+fn devices_without_physfn_stand_alone() {
+    let groups = group_virtual_functions(&fixture_devices());
+
+    assert!(groups[1].virtual_functions.is_empty());
+    assert_eq!(format_device_group(&groups[1]), "0000:3b:00.0 NVIDIA [10de:2204]");
+}
+
+#[test]
+fn keeps_orphan_virtual_functions_as_standalone_devices() {
+    // A VF whose physical function is elsewhere, e.g. passed through to a guest.
+    let groups = group_virtual_functions(&fixture_devices());
+
+    assert_eq!(groups[2].physical.physfn.as_deref(), Some("0000:c1:00.0"));
+    assert!(groups[2].virtual_functions.is_empty());
+    assert_eq!(format_device_group(&groups[2]), "0000:c1:00.4 AMD [1002:740f]");
+}
+
+#[test]
+fn groups_virtual_functions_listed_before_their_physical_function() {
+    let device = |address: &str, physfn: Option<&str>| PciDevice { address: address.to_string(), vendor_id: 0x10de, device_id: 0x25b6, class: 0x030200, physfn: physfn.map(str::to_string) };
+    let devices = vec![device("0000:ca:00.4", Some("0000:ca:00.0")), device("0000:ca:00.0", None), device("0000:ca:00.5", Some("0000:ca:00.0"))];
+
+    let groups = group_virtual_functions(&devices);
+
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].physical.address, "0000:ca:00.0");
+    assert_eq!(addresses(&groups[0].virtual_functions), vec!["0000:ca:00.4", "0000:ca:00.5"]);
+}
+
+#[test]
+fn missing_devices_directory_is_an_error() {
+    assert!(list_display_devices_in(Path::new("/nonexistent/sys/bus/pci/devices")).is_err());
+}
+
+#[test]
+fn counts_every_gpu_of_a_dgx_and_nothing_else() {
+    let lspci = lspci_fixture("dgx-a100.txt");
+    let gpus = lspci_gpus(&lspci);
+
+    let gpu_addresses: Vec<_> = gpus.iter().map(|gpu| gpu.address).collect();
+    assert_eq!(gpu_addresses, vec!["0000:07:00.0", "0000:0f:00.0", "0000:47:00.0", "0000:4e:00.0", "0000:87:00.0", "0000:90:00.0", "0000:b7:00.0", "0000:bd:00.0"]);
+    assert_eq!(gpus[0], LspciDevice { address: "0000:07:00.0", class: Some(0x0302), vendor_id: Some(0x10de), description: "NVIDIA Corporation GA100 [A100 SXM4 80GB]" });
+
+    // The six NVSwitches are NVIDIA devices, but bridges.
+    let devices = parse_lspci(&lspci);
+    let nvswitches = devices.iter().filter(|device| device.vendor_id == Some(0x10de) && device.class == Some(0x0680)).count();
+    assert_eq!(nvswitches, 6);
+}
+
+#[test]
+fn skips_the_bmc_console_of_a_server() {
+    let lspci = lspci_fixture("server-bmc.txt");
+
+    let gpu_addresses: Vec<_> = lspci_gpus(&lspci).iter().map(|gpu| gpu.address).collect();
+    assert_eq!(gpu_addresses, vec!["0000:3b:00.0", "0000:86:00.0"]);
+    let aspeed = parse_lspci(&lspci).into_iter().find(|device| device.address == "0000:03:00.0").unwrap();
+    assert_eq!(aspeed.class, Some(0x0300));
+    assert!(!aspeed.is_gpu());
+    assert!(lspci_gpus(&lspci_fixture("bmc-only.txt")).is_empty());
+}
+
+#[test]
+fn parses_lspci_without_numeric_ids() {
+    let lspci = "00:02.0 VGA compatible controller: Intel Corporation Alder Lake-P GT2 [Iris Xe Graphics] (rev 0c)
+\tSubsystem: Lenovo Device 22e4
+\tKernel driver in use: i915
+03:00.0 VGA compatible controller: ASPEED Technology, Inc. ASPEED Graphics Family (rev 41)
+3b:00.0 Bridge: NVIDIA Corporation Device 1af1 (rev a1)
+";
+
+    let gpus = lspci_gpus(lspci);
+    assert_eq!(gpus, vec![LspciDevice { address: "00:02.0", class: Some(0x0300), vendor_id: Some(0x8086), description: "Intel Corporation Alder Lake-P GT2 [Iris Xe Graphics]" }]);
+}
+
+#[test]
+fn identifies_the_vendor_by_its_gpus_only() {
+    let runner = MockRunner::new().with("lspci", &["-Dnn"], CommandOutput::ok(&lspci_fixture("dgx-a100.txt")));
+    assert_eq!(try_identify_gpu_card(&runner), Some(GpuType::Nvidia));
+
+    let runner = MockRunner::new().with("lspci", &["-Dnn"], CommandOutput::ok(&lspci_fixture("server-bmc.txt")));
+    assert_eq!(try_identify_gpu_card(&runner), Some(GpuType::Amd));
+}
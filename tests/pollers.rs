@@ -0,0 +1,76 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use gpu_auto_top::pollers::{find_monitors_in, Busy, DeviceLocks, Monitor};
+use gpu_auto_top::GpuInfo;
+
+fn gpu(index: u32, bus_id: Option<&str>) -> GpuInfo {
+    GpuInfo { index, name: "NVIDIA GeForce RTX 3090".to_string(), bus_id: bus_id.map(str::to_string), render_offload: None }
+}
+
+#[test]
+fn finds_well_known_monitors() {
+    let proc_root = std::env::temp_dir().join(format!("gpuatop-pollers-proc-{}", std::process::id()));
+    for (pid, comm) in [("812", "nv-hostengine\n"), ("4100", "python\n"), ("77", "dcgm-exporter\n")] {
+        fs::create_dir_all(proc_root.join(pid)).unwrap();
+        fs::write(proc_root.join(pid).join("comm"), comm).unwrap();
+    }
+    fs::create_dir_all(proc_root.join("self")).unwrap();
+
+    let monitors = find_monitors_in(&proc_root);
+    fs::remove_dir_all(&proc_root).unwrap();
+
+    assert_eq!(
+        monitors,
+        [Monitor { pid: 77, name: "dcgm-exporter".to_string() }, Monitor { pid: 812, name: "nv-hostengine".to_string() }]
+    );
+}
+
+#[test]
+fn a_gpu_is_locked_by_one_instance_at_a_time() {
+    let dir = std::env::temp_dir().join(format!("gpuatop-pollers-locks-{}", std::process::id()));
+    let gpus = [gpu(0, Some("0000:3b:00.0")), gpu(1, None)];
+
+    let (first, busy) = DeviceLocks::acquire(&dir, &gpus[..1]).unwrap();
+    assert!(busy.is_empty());
+    let (_second, busy) = DeviceLocks::acquire(&dir, &gpus).unwrap();
+    assert_eq!(busy, [Busy { gpu: 0, pid: Some(std::process::id()) }]);
+
+    // Released with its holder, without removing the lock file.
+    drop(first);
+    let (_third, busy) = DeviceLocks::acquire(&dir, &gpus[..1]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(busy.is_empty());
+}
+
+#[test]
+fn a_second_gpuatop_needs_allow_multiple() {
+    let dir = common::fake_tools("pollers-second");
+    let gpuatop = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+            .args(["--count", "1"])
+            .args(extra)
+            .env("PATH", common::path_with(&dir))
+            .env("XDG_RUNTIME_DIR", &dir)
+            .output()
+            .unwrap()
+    };
+    // Stands in for a running gpuatop: the fake GPU's lock is held for the rest of the test.
+    let (_running, _) = DeviceLocks::acquire(&dir.join("gpuatop"), &[gpu(0, Some("0000:3b:00.0"))]).unwrap();
+
+    let refused = gpuatop(&[]);
+    let allowed = gpuatop(&["--allow-multiple"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&refused.stdout);
+    assert!(stdout.contains(&format!("Error: GPU 0 is already monitored by another gpuatop (PID {})", std::process::id())), "{}", stdout);
+    assert!(!stdout.contains("Utilization"), "{}", stdout);
+    let stdout = String::from_utf8_lossy(&allowed.stdout);
+    assert!(stdout.contains("Note: GPU 0 is also monitored by another gpuatop"), "{}", stdout);
+    assert!(stdout.contains("Utilization (percent): 45"), "{}", stdout);
+}
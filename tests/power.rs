@@ -0,0 +1,90 @@
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::msgpack::{decode, encode_state, read_frame};
+use gpu_auto_top::output::{format_state, OutputContext, OutputFormat};
+use gpu_auto_top::power::{is_suspended, DeviceState, DeviceWatch};
+use gpu_auto_top::GpuInfo;
+
+const BUS_ID: &str = "0000:01:00.0";
+
+fn gpu(index: u32, bus_id: Option<&str>) -> GpuInfo {
+    GpuInfo { index, name: "NVIDIA GeForce RTX 3050 Laptop GPU".to_string(), bus_id: bus_id.map(str::to_string), render_offload: None }
+}
+
+fn context(format: OutputFormat) -> OutputContext {
+    OutputContext { format, hostname: Some("laptop".to_string()), labels: Labels::default(), tick_seq: Some(7), timestamp: None, precision: 1 }
+}
+
+/// A fake `/sys/bus/pci/devices` with one bound device.
+fn devices(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("gpuatop-power-{}-{}", name, std::process::id()));
+    fs::create_dir_all(root.join(BUS_ID).join("driver")).unwrap();
+    fs::create_dir_all(root.join(BUS_ID).join("power")).unwrap();
+    root
+}
+
+fn set_runtime_status(root: &Path, status: &str) {
+    fs::write(root.join(BUS_ID).join("power").join("runtime_status"), status).unwrap();
+}
+
+#[test]
+fn only_suspended_counts_as_asleep() {
+    assert!(is_suspended("suspended\n"));
+    assert!(!is_suspended("suspending\n"));
+    assert!(!is_suspended("active\n"));
+}
+
+#[test]
+fn follows_runtime_suspend_and_driver_unload() {
+    let root = devices("watch");
+    let gpu = gpu(0, Some(BUS_ID));
+    set_runtime_status(&root, "active\n");
+    let watch = DeviceWatch::with_root(&root, std::slice::from_ref(&gpu));
+
+    let active = watch.state(&gpu);
+    set_runtime_status(&root, "suspended\n");
+    let asleep = watch.state(&gpu);
+    fs::remove_dir_all(root.join(BUS_ID).join("driver")).unwrap();
+    let lost = watch.state(&gpu);
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!((active, asleep, lost), (DeviceState::Active, DeviceState::Asleep, DeviceState::Lost));
+}
+
+#[test]
+fn gpus_without_a_bound_device_at_startup_stay_active() {
+    let root = devices("untracked");
+    let watch = DeviceWatch::with_root(&root, &[gpu(0, Some("0000:3b:00.0")), gpu(1, None)]);
+
+    let states = (watch.state(&gpu(0, Some("0000:3b:00.0"))), watch.state(&gpu(1, None)));
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(states, (DeviceState::Active, DeviceState::Active));
+}
+
+#[test]
+fn state_records_carry_no_metrics() {
+    let gpu = gpu(1, Some(BUS_ID));
+
+    assert_eq!(format_state(&gpu, DeviceState::Asleep, &context(OutputFormat::Text)), "[laptop] GPU 1 (NVIDIA GeForce RTX 3050 Laptop GPU): asleep");
+    assert_eq!(
+        format_state(&gpu, DeviceState::Lost, &context(OutputFormat::Ndjson)),
+        "{\"schema_version\":1,\"hostname\":\"laptop\",\"gpu\":1,\"name\":\"NVIDIA GeForce RTX 3050 Laptop GPU\",\"state\":\"lost\",\"tick_seq\":7}"
+    );
+
+    let influx = format_state(&gpu, DeviceState::Asleep, &context(OutputFormat::Influx));
+    assert!(influx.starts_with("gpu,gpu=1,name=NVIDIA\\ GeForce\\ RTX\\ 3050\\ Laptop\\ GPU,hostname=laptop state=\"asleep\" "), "{}", influx);
+}
+
+#[test]
+fn msgpack_state_decodes_to_the_json_record() {
+    let gpu = gpu(1, Some(BUS_ID));
+    let frame = encode_state(&gpu, DeviceState::Asleep, &context(OutputFormat::Msgpack));
+    let payload = read_frame(&mut frame.as_slice()).unwrap().unwrap();
+
+    assert_eq!(decode(&payload).unwrap().to_json(), format_state(&gpu, DeviceState::Asleep, &context(OutputFormat::Ndjson)));
+}
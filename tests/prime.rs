@@ -0,0 +1,56 @@
+use gpu_auto_top::pci::PciDevice;
+use gpu_auto_top::prime::{is_hybrid, parse_prime_select, render_offload_mode, PrimeProfile, RenderOffloadMode};
+
+const NVIDIA: u16 = 0x10de;
+const INTEL: u16 = 0x8086;
+
+fn device(address: &str, vendor_id: u16) -> PciDevice {
+    PciDevice { address: address.to_string(), vendor_id, device_id: 0x1234, class: 0x030000, physfn: None }
+}
+
+#[test]
+fn parses_prime_select_profiles() {
+    assert_eq!(parse_prime_select("nvidia\n"), Some(PrimeProfile::Nvidia));
+    assert_eq!(parse_prime_select("intel\n"), Some(PrimeProfile::Intel));
+    assert_eq!(parse_prime_select("on-demand\n"), Some(PrimeProfile::OnDemand));
+    assert_eq!(parse_prime_select("unknown\n"), None);
+}
+
+#[test]
+fn intel_with_a_discrete_gpu_is_hybrid() {
+    assert!(is_hybrid(&[device("0000:00:02.0", INTEL), device("0000:01:00.0", NVIDIA)]));
+    assert!(!is_hybrid(&[device("0000:01:00.0", NVIDIA), device("0000:02:00.0", NVIDIA)]));
+    assert!(!is_hybrid(&[device("0000:00:02.0", INTEL)]));
+}
+
+#[test]
+fn on_demand_profile_offloads_to_nvidia() {
+    assert_eq!(render_offload_mode(NVIDIA, Some(PrimeProfile::OnDemand), Some(false)), RenderOffloadMode::OffloadGpu);
+    assert_eq!(render_offload_mode(INTEL, Some(PrimeProfile::OnDemand), Some(true)), RenderOffloadMode::PrimaryGpu);
+}
+
+#[test]
+fn nvidia_profile_makes_nvidia_primary() {
+    assert_eq!(render_offload_mode(NVIDIA, Some(PrimeProfile::Nvidia), None), RenderOffloadMode::PrimaryGpu);
+    assert_eq!(render_offload_mode(INTEL, Some(PrimeProfile::Nvidia), None), RenderOffloadMode::OffloadGpu);
+}
+
+#[test]
+fn without_a_profile_the_boot_vga_device_is_primary() {
+    assert_eq!(render_offload_mode(NVIDIA, None, Some(true)), RenderOffloadMode::PrimaryGpu);
+    assert_eq!(render_offload_mode(NVIDIA, None, Some(false)), RenderOffloadMode::OffloadGpu);
+    assert_eq!(render_offload_mode(NVIDIA, None, None), RenderOffloadMode::Unknown);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn text_output_tags_offload_gpus() {
+    use gpu_auto_top::metadata::Labels;
+    use gpu_auto_top::output::{format_snapshot, OutputContext, OutputFormat};
+    use gpu_auto_top::{GpuInfo, GpuSnapshot};
+
+    let snapshot = GpuSnapshot::new(GpuInfo { index: 0, name: "NVIDIA GeForce RTX 3060 Laptop GPU".to_string(), bus_id: None, render_offload: Some(RenderOffloadMode::OffloadGpu) }, 0.0);
+    let context = OutputContext { format: OutputFormat::Text, hostname: None, labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 };
+
+    assert!(format_snapshot(&snapshot, &context).ends_with(" [PRIME offload]"));
+}
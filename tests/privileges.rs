@@ -0,0 +1,118 @@
+#![cfg(feature = "cli")]
+
+use std::path::{Path, PathBuf};
+
+use gpu_auto_top::privileges::{
+    denied_node, dri_remedy, group_name, parse_identity, parse_perf_event_paranoid, requirement, root_message, sudo_remedy, user_groups, DeviceNode, Identity,
+    PrivilegeCheck,
+};
+use gpu_auto_top::GpuType;
+
+const RENDER: u32 = 109;
+
+fn node(name: &str, gid: u32, mode: u32) -> DeviceNode {
+    DeviceNode { path: PathBuf::from("/dev/dri").join(name), uid: 0, gid, mode }
+}
+
+fn user(groups: &[u32]) -> Identity {
+    Identity { uid: 1000, gid: 1000, groups: groups.to_vec() }
+}
+
+#[test]
+fn intel_gpu_top_needs_root_unless_perf_is_open_to_users() {
+    assert_eq!(parse_perf_event_paranoid("2\n"), Some(2));
+    assert_eq!(parse_perf_event_paranoid("-1\n"), Some(-1));
+
+    assert_eq!(requirement(&GpuType::Intel, Some(2), None), PrivilegeCheck::RequiresRoot);
+    assert_eq!(requirement(&GpuType::Intel, Some(0), None), PrivilegeCheck::SufficientAsUser);
+    assert_eq!(requirement(&GpuType::Intel, None, None), PrivilegeCheck::Unknown);
+}
+
+#[test]
+fn radeontop_needs_a_writable_drm_node() {
+    assert_eq!(requirement(&GpuType::Amd, None, Some(false)), PrivilegeCheck::RequiresRoot);
+    assert_eq!(requirement(&GpuType::Amd, None, Some(true)), PrivilegeCheck::SufficientAsUser);
+    assert_eq!(requirement(&GpuType::Amd, None, None), PrivilegeCheck::Unknown);
+}
+
+#[test]
+fn other_tools_run_as_user() {
+    assert_eq!(requirement(&GpuType::Nvidia, Some(2), Some(false)), PrivilegeCheck::SufficientAsUser);
+    assert_eq!(requirement(&GpuType::JetsonGpu, None, None), PrivilegeCheck::SufficientAsUser);
+    assert_eq!(requirement(&GpuType::Unknown("1234".to_string()), None, None), PrivilegeCheck::Unknown);
+}
+
+#[test]
+fn amd_message_names_the_group_owning_the_device() {
+    let groups = "root:x:0:\nvideo:x:44:alice\nrender:x:109:\n";
+
+    assert_eq!(group_name(groups, 44).as_deref(), Some("video"));
+    assert_eq!(group_name(groups, 7), None);
+    let message = root_message(&GpuType::Amd, Some("video")).unwrap();
+    assert!(message.starts_with("radeontop requires root or access to /dev/dri; re-run with sudo or add yourself to the video group"), "{}", message);
+    assert!(root_message(&GpuType::Amd, None).unwrap().contains("the render group"));
+}
+
+#[test]
+fn intel_message_suggests_sudo() {
+    assert!(root_message(&GpuType::Intel, None).unwrap().starts_with("intel_gpu_top requires root; re-run with sudo"));
+    assert_eq!(root_message(&GpuType::Nvidia, None), None);
+}
+
+#[test]
+fn parses_the_effective_identity_and_the_session_groups() {
+    let status = "Name:\tgpuatop\nUid:\t1000\t0\t0\t0\nGid:\t1000\t1000\t1000\t1000\nGroups:\t4 24 27 1000 \n";
+
+    assert_eq!(parse_identity(status), Some(Identity { uid: 0, gid: 1000, groups: vec![4, 24, 27, 1000] }));
+    assert_eq!(parse_identity("Uid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\nGroups:\t\n").unwrap().groups, Vec::<u32>::new());
+    assert_eq!(parse_identity("Name:\tgpuatop\n"), None);
+}
+
+#[test]
+fn a_node_opens_by_its_owner_group_or_other_bits() {
+    let card = node("card0", RENDER, 0o660);
+
+    assert!(card.opens_for(&user(&[RENDER])));
+    assert!(!card.opens_for(&user(&[44])));
+    assert!(card.opens_for(&Identity { uid: 0, gid: 0, groups: Vec::new() }));
+    assert!(card.opens_for(&Identity { uid: 1000, gid: RENDER, groups: Vec::new() }));
+    assert!(node("card0", RENDER, 0o666).opens_for(&user(&[])));
+    // The group bits decide for a member, even where everyone else may open it.
+    assert!(!node("card0", RENDER, 0o606).opens_for(&user(&[RENDER])));
+    assert!(!node("card0", RENDER, 0o640).opens_for(&user(&[RENDER])));
+}
+
+#[test]
+fn access_needs_one_node_of_each_kind() {
+    let nodes = [node("card0", 44, 0o660), node("card1", RENDER, 0o660), node("renderD128", RENDER, 0o660)];
+
+    assert_eq!(denied_node(&nodes, &user(&[RENDER])), None);
+    assert_eq!(denied_node(&nodes, &user(&[44])).map(|node| node.path.as_path()), Some(Path::new("/dev/dri/renderD128")));
+    assert_eq!(denied_node(&nodes, &user(&[])).map(|node| node.path.as_path()), Some(Path::new("/dev/dri/card0")));
+    assert_eq!(denied_node(&[], &user(&[])), None);
+}
+
+#[test]
+fn lists_the_groups_of_a_user() {
+    let groups = "root:x:0:\nvideo:x:44:alice,bob\nrender:x:109:bob\nalice:x:1000:\n";
+
+    assert_eq!(user_groups(groups, "alice"), vec![44]);
+    assert_eq!(user_groups(groups, "bob"), vec![44, 109]);
+    assert_eq!(user_groups(groups, "carol"), Vec::<u32>::new());
+}
+
+#[test]
+fn the_remedy_names_the_group_and_the_relogin() {
+    assert_eq!(
+        dri_remedy("radeontop", Path::new("/dev/dri/renderD128"), "render", false),
+        "radeontop cannot open /dev/dri/renderD128, which needs the render group; add yourself with `sudo usermod -aG render $USER`, then log out and back in"
+    );
+    assert_eq!(
+        dri_remedy("intel_gpu_top", Path::new("/dev/dri/card0"), "video", true),
+        "intel_gpu_top cannot open /dev/dri/card0: you were added to the video group after this session started; log out and back in, or run `newgrp video`"
+    );
+    assert_eq!(
+        sudo_remedy("radeontop", "alice", "render"),
+        "radeontop needs root here only because alice is not in the render group; after `sudo usermod -aG render alice` and logging in again, gpuatop runs without sudo"
+    );
+}
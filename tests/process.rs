@@ -0,0 +1,86 @@
+#![cfg(feature = "cli")]
+
+use gpu_auto_top::backend::DrmClient;
+use gpu_auto_top::process::{count_contexts, parse_nvidia_pmon, query_compute_apps, query_processes, reports_utilization, ContextKind, GpuProcess};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::GpuType;
+
+fn client(engines: &[(&str, u64)]) -> DrmClient {
+    DrmClient { client_id: 1, pdev: None, engines: engines.iter().map(|(name, busy)| (name.to_string(), *busy)).collect(), vram_bytes: None }
+}
+
+fn process(pid: u32, kind: Option<ContextKind>) -> GpuProcess {
+    GpuProcess { gpu_index: 0, pid, name: "python".to_string(), utilization: None, memory_used_mib: None, kind }
+}
+
+#[test]
+fn pmon_types_are_context_kinds() {
+    let pmon = "# gpu         pid   type     sm    mem    enc    dec    command\n\
+                # Idx           #    C/G      %      %      %      %    name\n\
+                    0        4242     C     30     10      -      -    python\n\
+                    0        1337     G      2      1      -      -    Xorg\n\
+                    0        7777   C+G      5      3      -      -    blender\n\
+                    0        8888     M      -      -      -      -    mps\n";
+    let kinds: Vec<Option<ContextKind>> = parse_nvidia_pmon(pmon).into_iter().map(|process| process.kind).collect();
+
+    assert_eq!(kinds, [Some(ContextKind::Compute), Some(ContextKind::Graphics), Some(ContextKind::Mixed), None]);
+}
+
+#[test]
+fn drm_clients_are_classified_by_the_engines_they_used() {
+    assert_eq!(ContextKind::from_drm_clients(&[client(&[("compute", 5000), ("gfx", 0)])]), Some(ContextKind::Compute));
+    assert_eq!(ContextKind::from_drm_clients(&[client(&[("gfx", 5000), ("compute", 0)])]), Some(ContextKind::Graphics));
+    assert_eq!(ContextKind::from_drm_clients(&[client(&[("render", 5000)])]), Some(ContextKind::Graphics));
+    assert_eq!(ContextKind::from_drm_clients(&[client(&[("render", 5000)]), client(&[("compute", 1)])]), Some(ContextKind::Mixed));
+    // Only the video decoder: neither a compute job nor a graphics client.
+    assert_eq!(ContextKind::from_drm_clients(&[client(&[("video", 5000), ("gfx", 0)])]), None);
+    assert_eq!(ContextKind::from_drm_clients(&[]), None);
+}
+
+#[test]
+fn mixed_processes_count_as_both() {
+    let processes = [process(1, Some(ContextKind::Compute)), process(2, Some(ContextKind::Graphics)), process(3, Some(ContextKind::Mixed)), process(4, None)];
+    let processes: Vec<&GpuProcess> = processes.iter().collect();
+
+    assert_eq!(count_contexts(&processes), (2, 2));
+    assert_eq!(count_contexts(&[]), (0, 0));
+}
+
+const PMON: &str = "# gpu         pid   type     sm    mem    enc    dec    command\n\
+                    # Idx           #    C/G      %      %      %      %    name\n\
+                        0       40712     C     87     41      -      -    python3\n";
+
+const ROCM_SMI_PIDS: &str = "\
+========================= ROCm System Management Interface =========================
+================================ KFD Processes =====================================
+KFD process information:
+PROCESS NAME\tPID\tGPU(s)\tVRAM USED\tSDMA USED\tCU OCCUPANCY
+python3\t4000001\t1\t2147483648\t0\t0
+====================================================================================
+=============================== End of ROCm SMI Log ================================
+";
+
+#[test]
+fn processes_are_queried_through_the_runner() {
+    let runner = MockRunner::new()
+        .with("nvidia-smi", &["pmon", "-c", "1"], CommandOutput::ok(PMON))
+        .with("nvidia-smi", &["--query-compute-apps=pid,used_memory", "--format=csv,noheader,nounits"], CommandOutput::ok("40712, 18432\n"))
+        .with("rocm-smi", &["--showpids"], CommandOutput::ok(ROCM_SMI_PIDS));
+
+    let nvidia = query_processes(&runner, &GpuType::Nvidia).unwrap();
+    assert_eq!((nvidia[0].pid, nvidia[0].name.as_str(), nvidia[0].utilization), (40712, "python3", Some(87.0)));
+    assert_eq!(query_compute_apps(&runner).unwrap(), [(40712, 18432)]);
+
+    let amd = query_processes(&runner, &GpuType::Amd).unwrap();
+    assert_eq!((amd[0].pid, amd[0].memory_used_mib, amd[0].utilization, amd[0].kind), (4000001, Some(2048), None, None));
+
+    assert_eq!(query_processes(&MockRunner::new(), &GpuType::Nvidia).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(query_processes(&runner, &GpuType::Intel).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn only_nvidia_reports_per_process_utilization() {
+    assert!(reports_utilization(&GpuType::Nvidia));
+    assert!(!reports_utilization(&GpuType::Amd));
+    assert!(!reports_utilization(&GpuType::Intel));
+}
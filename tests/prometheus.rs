@@ -0,0 +1,89 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::prometheus::{format_hosts_page, format_page, format_page_with_self_cpu, write_page};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, name: &str, power_w: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(1024), memory_total_mib: Some(24576), temperature_c: Some(60.0), power_w, ..common::snapshot(index, name, 45.0) }
+}
+
+fn context(labels: &str) -> OutputContext {
+    OutputContext { format: OutputFormat::Prometheus, hostname: Some("node1".to_string()), labels: labels.parse::<Labels>().unwrap(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn page_groups_samples_by_family() {
+    let page = format_page(&[snapshot(0, "RTX 3090", Some(120.5)), snapshot(1, "RTX 3090", None)], &context(""));
+
+    let expected = "\
+# HELP gpuatop_utilization_percent GPU utilization.
+# TYPE gpuatop_utilization_percent gauge
+gpuatop_utilization_percent{gpu=\"0\",name=\"RTX 3090\",hostname=\"node1\"} 45
+gpuatop_utilization_percent{gpu=\"1\",name=\"RTX 3090\",hostname=\"node1\"} 45
+# HELP gpuatop_memory_used_bytes Video memory in use.
+# TYPE gpuatop_memory_used_bytes gauge
+gpuatop_memory_used_bytes{gpu=\"0\",name=\"RTX 3090\",hostname=\"node1\"} 1073741824
+gpuatop_memory_used_bytes{gpu=\"1\",name=\"RTX 3090\",hostname=\"node1\"} 1073741824
+# HELP gpuatop_memory_total_bytes Total video memory.
+# TYPE gpuatop_memory_total_bytes gauge
+gpuatop_memory_total_bytes{gpu=\"0\",name=\"RTX 3090\",hostname=\"node1\"} 25769803776
+gpuatop_memory_total_bytes{gpu=\"1\",name=\"RTX 3090\",hostname=\"node1\"} 25769803776
+# HELP gpuatop_temperature_celsius GPU temperature.
+# TYPE gpuatop_temperature_celsius gauge
+gpuatop_temperature_celsius{gpu=\"0\",name=\"RTX 3090\",hostname=\"node1\"} 60
+gpuatop_temperature_celsius{gpu=\"1\",name=\"RTX 3090\",hostname=\"node1\"} 60
+# HELP gpuatop_power_watts GPU power draw.
+# TYPE gpuatop_power_watts gauge
+gpuatop_power_watts{gpu=\"0\",name=\"RTX 3090\",hostname=\"node1\"} 120.5
+# EOF
+";
+    assert_eq!(page, expected);
+}
+
+#[test]
+fn label_values_are_escaped_and_keys_sanitized() {
+    let page = format_page(&[snapshot(0, "GPU \"A\\B\"", None)], &context("rack-id=r1"));
+
+    assert!(page.contains("{gpu=\"0\",name=\"GPU \\\"A\\\\B\\\"\",hostname=\"node1\",rack_id=\"r1\"} 45\n"), "unexpected page: {}", page);
+}
+
+#[test]
+fn self_cpu_seconds_end_the_page_without_gpu_labels() {
+    let page = format_page_with_self_cpu(&[snapshot(0, "RTX 3090", None)], &context("rack=r1"), 0.25);
+
+    assert!(page.starts_with("# HELP gpuatop_utilization_percent "), "unexpected page: {}", page);
+    assert!(page.ends_with(
+        "# HELP gpuatop_self_cpu_seconds CPU time used by gpuatop and the tools it ran since monitoring started.
+# TYPE gpuatop_self_cpu_seconds counter
+gpuatop_self_cpu_seconds{hostname=\"node1\",rack=\"r1\"} 0.25
+# EOF
+"
+    ), "unexpected page: {}", page);
+}
+
+#[test]
+fn hosts_page_labels_each_host_in_one_family() {
+    let hosts = [("node1".to_string(), vec![snapshot(0, "RTX 3090", None)]), ("node2".to_string(), vec![snapshot(0, "A100", None)])];
+
+    let page = format_hosts_page(&hosts, &context("rack=r1"));
+
+    assert_eq!(page.matches("# TYPE gpuatop_utilization_percent gauge\n").count(), 1);
+    assert!(page.contains("gpuatop_utilization_percent{gpu=\"0\",name=\"RTX 3090\",hostname=\"node1\",rack=\"r1\"} 45\n\
+gpuatop_utilization_percent{gpu=\"0\",name=\"A100\",hostname=\"node2\",rack=\"r1\"} 45\n"), "unexpected page: {}", page);
+    assert!(page.ends_with("# EOF\n"));
+}
+
+#[test]
+fn page_file_is_replaced() {
+    let path = std::env::temp_dir().join(format!("gpuatop-prometheus-{}.prom", std::process::id()));
+    std::fs::write(&path, "stale").unwrap();
+
+    write_page(&path, "# EOF\n").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "# EOF\n");
+    std::fs::remove_file(&path).unwrap();
+}
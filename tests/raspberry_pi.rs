@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use gpu_auto_top::backend::{self, fits, Backend, DrmClient};
+use gpu_auto_top::rate::Moment;
+use gpu_auto_top::runner::{CommandOutput, MockRunner, Retry};
+use gpu_auto_top::videocore::{parse_vcgencmd_clock, parse_vcgencmd_temperature, parse_vcgencmd_throttled, raspberry_pi_model, ThrottleStatus, VideoCoreBackend, CLOCK_ARGS, TEMPERATURE_ARGS, THROTTLED_ARGS};
+use gpu_auto_top::{GpuInfo, GpuType, PollResult};
+
+const TICK: Duration = Duration::from_millis(500);
+
+fn pi() -> GpuType {
+    GpuType::Broadcom("Raspberry Pi 5 Model B Rev 1.0".to_string())
+}
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "Raspberry Pi 5 Model B Rev 1.0 VideoCore".to_string(), bus_id: None, render_offload: None }
+}
+
+fn vcgencmd(throttled: &str) -> MockRunner {
+    MockRunner::new()
+        .with("vcgencmd", &THROTTLED_ARGS, CommandOutput::ok(&format!("throttled={}\n", throttled)))
+        .with("vcgencmd", &CLOCK_ARGS, CommandOutput::ok("frequency(0)=960000000\n"))
+        .with("vcgencmd", &TEMPERATURE_ARGS, CommandOutput::ok("temp=61.5'C\n"))
+}
+
+/// A v3d client that has kept the render queue busy for `render_ns`.
+fn client(render_ns: u64) -> DrmClient {
+    DrmClient { client_id: 7, pdev: None, engines: vec![("bin".to_string(), 0), ("render".to_string(), render_ns)], vram_bytes: None }
+}
+
+#[test]
+fn decodes_the_throttle_bits() {
+    let status = parse_vcgencmd_throttled("throttled=0x50005");
+
+    assert!(status.under_voltage);
+    assert!(!status.arm_frequency_capped);
+    assert!(status.throttled);
+    assert!(!status.soft_temp_limit);
+    assert!(status.under_voltage_occurred);
+    assert!(!status.arm_frequency_capped_occurred);
+    assert!(status.throttled_occurred);
+    assert!(!status.soft_temp_limit_occurred);
+    assert_eq!(status.reasons(), ["under_voltage", "throttled"]);
+}
+
+#[test]
+fn flags_that_only_occurred_are_not_reasons() {
+    let status = parse_vcgencmd_throttled("0xa0000");
+
+    assert!(status.arm_frequency_capped_occurred && status.soft_temp_limit_occurred);
+    assert!(status.reasons().is_empty());
+    assert_eq!(parse_vcgencmd_throttled("0x8"), ThrottleStatus { soft_temp_limit: true, ..Default::default() });
+    assert_eq!(parse_vcgencmd_throttled("0x0"), ThrottleStatus::default());
+    assert_eq!(parse_vcgencmd_throttled("error=1"), ThrottleStatus::default());
+}
+
+#[test]
+fn parses_the_clock_and_temperature() {
+    assert_eq!(parse_vcgencmd_clock("frequency(46)=500000000\n"), Some(500));
+    assert_eq!(parse_vcgencmd_clock("frequency(46)=0"), Some(0));
+    assert_eq!(parse_vcgencmd_clock("error=2 error_msg=\"Invalid arguments\""), None);
+    assert_eq!(parse_vcgencmd_temperature("temp=48.3'C\n"), Some(48.3));
+    assert_eq!(parse_vcgencmd_temperature("temp=n/a"), None);
+}
+
+#[test]
+fn detects_a_raspberry_pi_from_the_device_tree_model() {
+    assert_eq!(raspberry_pi_model("Raspberry Pi 4 Model B Rev 1.4\0"), Some("Raspberry Pi 4 Model B Rev 1.4".to_string()));
+    assert_eq!(raspberry_pi_model("Raspberry Pi Compute Module 4 Rev 1.0\0"), Some("Raspberry Pi Compute Module 4 Rev 1.0".to_string()));
+    assert_eq!(raspberry_pi_model("NVIDIA Jetson Orin Nano Developer Kit\0"), None);
+    assert_eq!(raspberry_pi_model("Pine64 RockPro64 v2.1\0"), None);
+}
+
+#[test]
+fn samples_the_clock_throttle_flags_and_v3d_busy_time() {
+    let runner = vcgencmd("0x50005");
+    let at = Moment::now();
+    let mut backend = VideoCoreBackend::open_with(&runner, &[client(0)], at).expect("opens");
+
+    let results = backend.poll_with(&[gpu()], &[client(200_000_000)], at.after(TICK));
+    let PollResult::Ok { snapshot, .. } = &results[0] else { panic!("no sample: {:?}", results) };
+
+    assert_eq!(snapshot.utilization, 40.0);
+    assert_eq!(snapshot.clock_mhz, Some(960));
+    assert_eq!(snapshot.temperature_c, Some(61.5));
+    assert_eq!(snapshot.throttle_reasons, Some(vec!["under_voltage".to_string(), "throttled".to_string()]));
+    assert_eq!(backend.source(), "vcgencmd");
+}
+
+#[test]
+fn a_failing_vcgencmd_is_a_transient_error() {
+    let runner = vcgencmd("0x0").with("vcgencmd", &CLOCK_ARGS, CommandOutput::failed(255, "VCHI initialization failed"));
+    let at = Moment::now();
+    let mut backend = VideoCoreBackend::open_with(&runner, &[], at).expect("opens").with_retry(Retry::ONCE);
+
+    let results = backend.poll_with(&[gpu()], &[], at.after(TICK));
+
+    assert!(matches!(&results[0], PollResult::TransientError { message, .. } if message.contains("VCHI initialization failed")), "{:?}", results);
+}
+
+#[test]
+fn does_not_open_without_vcgencmd() {
+    assert!(VideoCoreBackend::open_with(&MockRunner::new(), &[], Moment::now()).is_err());
+
+    let runner = MockRunner::new().with("vcgencmd", &THROTTLED_ARGS, CommandOutput::failed(255, "failed to open vchiq instance"));
+    assert!(VideoCoreBackend::open_with(&runner, &[], Moment::now()).is_err());
+}
+
+#[test]
+fn vcgencmd_is_selected_for_a_raspberry_pi() {
+    let runner = vcgencmd("0x0");
+
+    assert_eq!(backend::select(&runner, &pi(), false, Duration::from_secs(1), Retry::default()).name(), "vcgencmd (per tick)");
+    assert!(fits(&pi(), "vcgencmd"));
+    assert!(fits(&pi(), "fdinfo"));
+    assert!(!fits(&GpuType::JetsonGpu, "vcgencmd"));
+    assert_eq!(pi().top_tool(), None);
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn text_output_names_the_throttle_reasons() {
+    let runner = vcgencmd("0x8");
+    let at = Moment::now();
+    let mut backend = VideoCoreBackend::open_with(&runner, &[client(0)], at).expect("opens");
+    let results = backend.poll_with(&[gpu()], &[client(0)], at.after(TICK));
+    let PollResult::Ok { snapshot, .. } = &results[0] else { panic!("no sample: {:?}", results) };
+
+    let line = gpu_auto_top::output::format_text(snapshot, 1);
+
+    assert!(line.contains(", Clock: 960 MHz, Throttled: soft_temp_limit"), "{}", line);
+}
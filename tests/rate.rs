@@ -0,0 +1,228 @@
+// Property tests for the counter rates: over synthetic counter series with resets, wraparounds
+// and suspends, every rate must be the increase of a counter between two consecutive ticks,
+// and no rate may span a reset or a suspend. Cases come from a seeded generator so failures
+// are reproducible.
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use gpu_auto_top::backend::{DrmBusyTracker, DrmClient};
+use gpu_auto_top::nvlink::{NvLinkCounters, NvLinkTracker};
+use gpu_auto_top::rate::{Moment, Rate, RateTracker, SUSPEND_TOLERANCE};
+
+const CASES: usize = 500;
+const TICK: Duration = Duration::from_millis(500);
+
+/// splitmix64, seeded per property.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+/// What happens to a counter before a tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Increase(u64),
+    /// The driver reset the counter, or it wrapped around: it starts over from a lower value.
+    Reset(u64),
+    /// The system was suspended for this long, which only the wall clock counts.
+    Suspend(Duration),
+}
+
+fn series(rng: &mut Rng, start: u64) -> Vec<Step> {
+    (0..rng.below(40) + 1)
+        .map(|_| match rng.below(10) {
+            0 => Step::Reset(rng.below(start.max(1))),
+            1 => Step::Suspend(Duration::from_secs(rng.below(3600) + 2)),
+            _ => Step::Increase(rng.below(1 << 40)),
+        })
+        .collect()
+}
+
+fn start() -> Moment {
+    Moment::now()
+}
+
+#[test]
+fn every_rate_is_the_increase_between_consecutive_ticks() {
+    let mut rng = Rng(1);
+
+    for _ in 0..CASES {
+        let mut value = rng.below(u64::MAX / 2) + 1;
+        let steps = series(&mut rng, value);
+        let mut tracker = RateTracker::default();
+        let mut at = start();
+
+        tracker.tick(at);
+        assert_eq!(tracker.update("busy_ns", value), None, "the first reading has no rate");
+
+        for step in steps {
+            let before = value;
+            at = at.after(TICK);
+            match step {
+                Step::Increase(increase) => value = value.wrapping_add(increase),
+                Step::Reset(to) => value = to,
+                Step::Suspend(suspended) => at.wall += suspended,
+            }
+
+            let elapsed = tracker.tick(at);
+            let rate = tracker.update("busy_ns", value);
+            match step {
+                Step::Suspend(_) => {
+                    assert_eq!(elapsed, None, "{:?}", step);
+                    assert_eq!(rate, None, "{:?}", step);
+                }
+                _ if value < before => assert_eq!(rate, None, "{:?} from {} to {}", step, before, value),
+                _ => assert_eq!(rate, Some(Rate { increase: value - before, elapsed: TICK }), "{:?}", step),
+            }
+        }
+    }
+}
+
+#[test]
+fn a_counter_read_again_after_a_missed_tick_starts_over() {
+    let mut tracker = RateTracker::default();
+    let at = start();
+
+    tracker.tick(at);
+    tracker.update((0, "tx"), 100);
+    tracker.update((1, "tx"), 100);
+    tracker.tick(at.after(TICK));
+    assert_eq!(tracker.update((0, "tx"), 150), Some(Rate { increase: 50, elapsed: TICK }));
+    tracker.tick(at.after(TICK * 2));
+    assert_eq!(tracker.update((1, "tx"), 400), None);
+    assert_eq!(tracker.update((0, "tx"), 150), Some(Rate { increase: 0, elapsed: TICK }));
+}
+
+#[test]
+fn counters_that_start_at_zero_count_from_the_previous_tick() {
+    let mut tracker = RateTracker::default();
+    let at = start();
+
+    tracker.tick(at);
+    assert_eq!(tracker.update_from_zero("client 1", 7), None);
+    tracker.tick(at.after(TICK));
+    assert_eq!(tracker.update_from_zero("client 2", 200), Some(Rate { increase: 200, elapsed: TICK }));
+    assert_eq!(tracker.update_from_zero("client 1", 3), None);
+}
+
+#[test]
+fn a_suspend_shows_as_wall_clock_time_the_monotonic_clock_missed() {
+    let at = start();
+
+    assert_eq!(at.after(TICK).since(at), Some(TICK));
+    assert_eq!(at.since(at), None);
+    assert_eq!(at.since(at.after(TICK)), None);
+
+    let mut drifted = at.after(TICK);
+    drifted.wall += SUSPEND_TOLERANCE;
+    assert_eq!(drifted.since(at), Some(TICK));
+    drifted.wall += Duration::from_millis(1);
+    assert_eq!(drifted.since(at), None);
+
+    let set_back = Moment { monotonic: at.after(TICK).monotonic, wall: SystemTime::UNIX_EPOCH };
+    assert_eq!(set_back.since(at), None);
+}
+
+#[test]
+fn rates_are_per_second_and_busy_shares() {
+    let rate = Rate { increase: 250_000_000, elapsed: Duration::from_millis(500) };
+
+    assert_eq!(rate.per_second(), 500_000_000.0);
+    assert_eq!(rate.percent_busy(), 50.0);
+}
+
+fn client(client_id: u64, render_ns: u64) -> DrmClient {
+    DrmClient { client_id, pdev: Some("0000:03:00.0".to_string()), engines: vec![("render".to_string(), render_ns)], vram_bytes: None }
+}
+
+#[test]
+fn engine_totals_never_go_down_as_clients_come_and_go() {
+    let mut rng = Rng(2);
+
+    for _ in 0..CASES {
+        let mut busy = DrmBusyTracker::default();
+        let mut clients: Vec<DrmClient> = Vec::new();
+        let mut at = start();
+        let mut total = 0;
+        let mut next_id = 0;
+
+        busy.update(&clients, at);
+        for _ in 0..rng.below(30) + 1 {
+            match rng.below(4) {
+                0 => {
+                    next_id += 1;
+                    clients.push(client(next_id, 0));
+                }
+                1 if !clients.is_empty() => {
+                    clients.remove(rng.below(clients.len() as u64) as usize);
+                }
+                _ => {}
+            }
+            for client in &mut clients {
+                client.engines[0].1 += rng.below(TICK.as_nanos() as u64);
+            }
+            at = at.after(TICK);
+            busy.update(&clients, at);
+
+            let engines = busy.engines(Some("0000:03:00.0"));
+            let render = engines.iter().find(|engine| engine.engine == "render").map_or(0, |engine| engine.ns_total);
+            assert!(render >= total, "{} after {}", render, total);
+            total = render;
+            assert!(engines.iter().all(|engine| engine.busy_pct.is_some_and(|busy| (0.0..=100.0).contains(&busy))), "{:?}", engines);
+        }
+    }
+}
+
+#[test]
+fn engines_report_the_total_and_the_share_of_the_interval() {
+    let mut busy = DrmBusyTracker::default();
+    let at = start();
+
+    busy.update(&[client(1, 1_000_000_000)], at);
+    assert_eq!(busy.utilization(None), None);
+    assert_eq!(busy.engines(None)[0].busy_pct, None);
+
+    busy.update(&[client(1, 1_100_000_000), client(2, 150_000_000)], at.after(TICK));
+    let engines = busy.engines(Some("0000:03:00.0"));
+    assert_eq!(engines.len(), 1);
+    assert_eq!(engines[0].ns_total, 250_000_000);
+    assert_eq!(engines[0].busy_pct, Some(50.0));
+    assert_eq!(busy.utilization(Some("0000:04:00.0")), Some(0.0));
+
+    // Client 1 was reset: its reading is a new baseline, and only client 2's time counts.
+    let later = at.after(TICK * 2);
+    busy.update(&[client(1, 5), client(2, 200_000_000)], later);
+    assert_eq!(busy.engines(None)[0].ns_total, 300_000_000);
+    assert_eq!(busy.utilization(None), Some(10.0));
+
+    let mut suspended = later.after(TICK);
+    suspended.wall += Duration::from_secs(600);
+    busy.update(&[client(1, 10), client(2, 300_000_000)], suspended);
+    assert_eq!(busy.utilization(None), None);
+    assert_eq!(busy.engines(None)[0].ns_total, 300_000_000);
+}
+
+#[test]
+fn nvlink_skips_a_gpu_whose_counters_were_reset() {
+    let counters = |tx_kib| HashMap::from([(0, NvLinkCounters { tx_kib, rx_kib: 10, replay_errors: 1, crc_errors: 0 })]);
+    let mut tracker = NvLinkTracker::default();
+    let at = start();
+
+    assert!(tracker.update_at(counters(1000), at).is_empty());
+    let metrics = tracker.update_at(counters(1500), at.after(TICK));
+    assert_eq!(metrics[&0].tx_kib_per_s, 1000.0);
+    assert_eq!(metrics[&0].replay_errors, 0);
+    assert!(tracker.update_at(counters(20), at.after(TICK * 2)).is_empty());
+    assert_eq!(tracker.update_at(counters(520), at.after(TICK * 3))[&0].tx_kib_per_s, 1000.0);
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use gpu_auto_top::capabilities::{Capabilities, READ_ONLY_EXIT_CODE};
+use gpu_auto_top::config;
+
+fn gpuatop(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(dir))
+        .env("XDG_RUNTIME_DIR", dir)
+        .env("XDG_CONFIG_HOME", dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn read_only_allows_writes_under_tmp_only() {
+    let read_only = Capabilities::new(true, false);
+
+    assert!(read_only.write("--log-file", Path::new("/tmp/gpuatop.log")).is_ok());
+    let err = read_only.write("--log-file", Path::new("/var/log/gpuatop.log")).unwrap_err();
+    assert_eq!(err, "--read-only forbids writing /var/log/gpuatop.log (--log-file); use a path under /tmp or add --read-only-allow-writes");
+    assert!(read_only.write("--log-file", Path::new("/tmp/../etc/gpuatop.log")).is_err());
+    assert!(read_only.write("--log-file", Path::new("/tmpfs/gpuatop.log")).is_err());
+    assert!(read_only.install().is_err());
+    assert!(read_only.change_device("--wake").is_err());
+
+    let allow_writes = Capabilities::new(true, true);
+    assert!(allow_writes.write("--log-file", Path::new("/var/log/gpuatop.log")).is_ok());
+    assert!(allow_writes.install().is_err());
+
+    let default = Capabilities::default();
+    assert!(default.write("--log-file", Path::new("/var/log/gpuatop.log")).is_ok() && default.install().is_ok());
+}
+
+#[test]
+fn the_configuration_can_turn_read_only_on() {
+    let document = config::parse("read_only = true\n").unwrap();
+    assert_eq!(Capabilities::from_config(&document, false, false), Ok(Capabilities::new(true, false)));
+    assert_eq!(Capabilities::from_config(&config::Document::default(), false, true), Ok(Capabilities::new(false, true)));
+    assert!(Capabilities::from_config(&config::parse("read_only = \"yes\"\n").unwrap(), false, false).is_err());
+}
+
+#[test]
+fn read_only_never_runs_the_package_manager() {
+    let dir = common::fake_tools("read-only-install");
+    // nvidia-smi is missing; a package manager run would leave the marker behind.
+    fs::remove_file(dir.join("nvidia-smi")).unwrap();
+    let marker = dir.join("package-manager-ran");
+    for name in ["sudo", "apt", "dnf", "pacman"] {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\ntouch {}\n", marker.display())).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let output = gpuatop(&dir, &["--read-only", "--yes", "--count", "1"]);
+    let ran = marker.exists();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(READ_ONLY_EXIT_CODE), "{}", stdout);
+    assert!(stdout.contains("Error: nvidia-smi is not installed and --read-only forbids installing the monitoring tool"), "{}", stdout);
+    assert!(!ran, "a package manager was run");
+}
+
+#[test]
+fn conflicting_options_fail_before_monitoring() {
+    let dir = common::fake_tools("read-only-options");
+    let config_file: PathBuf = dir.join("read-only.toml");
+    fs::write(&config_file, "read_only = true\n").unwrap();
+    let tmp_log = dir.join("gpuatop.log");
+
+    let log_file = gpuatop(&dir, &["--read-only", "--count", "1", "--log-file", "/var/log/gpuatop.log"]);
+    let wake = gpuatop(&dir, &["--config", config_file.to_str().unwrap(), "--count", "1", "--wake"]);
+    let allowed = gpuatop(&dir, &["--read-only", "-q", "--count", "1", "--log-file", tmp_log.to_str().unwrap()]);
+    let logged = fs::read_to_string(&tmp_log).unwrap_or_default();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&log_file.stdout);
+    assert_eq!(log_file.status.code(), Some(READ_ONLY_EXIT_CODE));
+    assert!(stdout.contains("(--log-file)") && !stdout.contains("Utilization"), "{}", stdout);
+    let stdout = String::from_utf8_lossy(&wake.stdout);
+    assert_eq!(wake.status.code(), Some(READ_ONLY_EXIT_CODE));
+    assert!(stdout.contains("Error: --read-only forbids --wake, which changes the GPU state"), "{}", stdout);
+    assert_eq!(allowed.status.code(), Some(0));
+    assert!(logged.contains("Utilization (percent): 45"), "{}", logged);
+}
+
+#[test]
+fn an_output_fifo_outside_tmp_is_a_conflict() {
+    let dir = common::fake_tools("read-only-fifo");
+
+    let fifo = gpuatop(&dir, &["--read-only", "--count", "1", "--output-fifo", "/var/run/gpuatop.fifo"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&fifo.stdout);
+    assert_eq!(fifo.status.code(), Some(READ_ONLY_EXIT_CODE));
+    assert!(stdout.contains("(--output-fifo)") && !stdout.contains("Utilization"), "{}", stdout);
+    assert!(!Path::new("/var/run/gpuatop.fifo").exists());
+}
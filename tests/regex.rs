@@ -0,0 +1,83 @@
+#![cfg(feature = "cli")]
+
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::regex::Regex;
+
+fn captures<'t>(regex: &'t Regex, text: &'t str, group: &str) -> Vec<&'t str> {
+    regex.captures_iter(text).filter_map(|captures| captures.name(group)).collect()
+}
+
+#[test]
+fn matches_classes_groups_and_anchors_per_line() {
+    let regex = Regex::new(r"^gpu(?P<index>\d+): (?P<util>[\d.]+)%$").unwrap();
+
+    let text = "gpu0: 45.5%\nnoise gpu9: 1%\ngpu1: 100%\n";
+    assert_eq!(captures(&regex, text, "index"), ["0", "1"]);
+    assert_eq!(captures(&regex, text, "util"), ["45.5", "100"]);
+}
+
+#[test]
+fn prefers_the_leftmost_match_and_the_first_alternative() {
+    let regex = Regex::new("(?<word>ab|abc)").unwrap();
+    assert_eq!(regex.captures_at("xxabc", 0).unwrap().name("word"), Some("ab"));
+
+    let regex = Regex::new("(?P<word>a|b)+").unwrap();
+    assert_eq!(regex.captures_at("cba", 0).unwrap().name("word"), Some("a"));
+}
+
+#[test]
+fn greedy_and_lazy_repetitions() {
+    let greedy = Regex::new("<(?P<tag>.+)>").unwrap();
+    let lazy = Regex::new("<(?P<tag>.+?)>").unwrap();
+
+    assert_eq!(greedy.captures_at("<a><b>", 0).unwrap().name("tag"), Some("a><b"));
+    assert_eq!(lazy.captures_at("<a><b>", 0).unwrap().name("tag"), Some("a"));
+}
+
+#[test]
+fn counted_repetitions() {
+    let regex = Regex::new(r"^(?P<hex>[0-9a-f]{2,4})$").unwrap();
+
+    assert_eq!(captures(&regex, "a\nab\nabcd\nabcde\n", "hex"), ["ab", "abcd"]);
+    assert_eq!(Regex::new("x{3}").unwrap().captures_at("xx", 0).map(|captures| captures.end()), None);
+}
+
+#[test]
+fn empty_repetitions_terminate() {
+    let regex = Regex::new("(?P<all>(a*)*b)").unwrap();
+
+    assert_eq!(regex.captures_at("aaab", 0).unwrap().name("all"), Some("aaab"));
+    assert!(regex.captures_at("aaaa", 0).is_none());
+}
+
+#[test]
+fn searches_from_a_byte_offset_in_multibyte_text() {
+    let regex = Regex::new(r"(?P<temp>\d+)°C").unwrap();
+    let text = "GPU 60°C, HBM 71°C";
+
+    let first = regex.captures_at(text, 0).unwrap();
+    assert_eq!(first.name("temp"), Some("60"));
+    assert_eq!(regex.captures_at(text, first.end()).unwrap().name("temp"), Some("71"));
+}
+
+#[test]
+fn rejects_invalid_patterns() {
+    for pattern in ["(a", "a)", "*a", "[a-", r"a\", "(?x)", "^*", "(?P<>a)"] {
+        assert!(Regex::new(pattern).is_err(), "{}", pattern);
+    }
+    assert!(Regex::new("(a{1000}){1000}").is_err());
+}
+
+/// A backtracking engine recursed once per repetition and restarted at every offset: this
+/// input overflowed the stack, and a quarter of it took seconds.
+#[test]
+fn large_inputs_match_in_linear_time() {
+    let regex = Regex::new("(?P<util>[^,]*),").unwrap();
+    let text = "x".repeat(200_000);
+
+    let started = Instant::now();
+    assert!(regex.captures_at(&text, 0).is_none());
+    assert_eq!(regex.captures_iter(&format!("{},", text)).count(), 1);
+    assert!(started.elapsed() < Duration::from_secs(5), "{:?}", started.elapsed());
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use gpu_auto_top::requirements::{detect_vendors, format_detected, GpuRequirement};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::{GpuSnapshot, GpuType};
+
+fn lspci_fixture(name: &str) -> String {
+    fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lspci").join(name)).unwrap()
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).env("PATH", common::path_with(&dir)).env("XDG_RUNTIME_DIR", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+fn snapshot(index: u32, memory_total_mib: Option<u64>) -> GpuSnapshot {
+    GpuSnapshot { memory_total_mib, ..common::snapshot(index, &format!("GPU {}", index), 0.0) }
+}
+
+#[test]
+fn parses_requirements() {
+    assert_eq!("nvidia".parse(), Ok(GpuRequirement { vendor: GpuType::Nvidia, min_count: 1, min_vram_mib: None }));
+    assert_eq!("amd:count=4".parse(), Ok(GpuRequirement { vendor: GpuType::Amd, min_count: 4, min_vram_mib: None }));
+    assert_eq!("nvidia:count=2,vram=40960".parse(), Ok(GpuRequirement { vendor: GpuType::Nvidia, min_count: 2, min_vram_mib: Some(40960) }));
+    assert_eq!("apple".parse::<GpuRequirement>(), Err("Invalid --require-gpu vendor: apple (expected nvidia, amd, intel, jetson or broadcom)".to_string()));
+    assert_eq!("nvidia:count=0".parse::<GpuRequirement>(), Err("Invalid --require-gpu option: count=0 (expected count=N or vram=MiB)".to_string()));
+    assert!("nvidia:memory=1".parse::<GpuRequirement>().is_err());
+}
+
+#[test]
+fn checks_the_vendor_and_the_count() {
+    let eight = detect_vendors(&MockRunner::new().with("lspci", &["-Dnn"], CommandOutput::ok(&lspci_fixture("dgx-a100.txt"))));
+    assert_eq!(format_detected(&eight), "NVIDIA x8");
+
+    let requirement: GpuRequirement = "nvidia:count=8".parse().unwrap();
+    assert_eq!(requirement.check(&eight), Ok(()));
+    let requirement: GpuRequirement = "nvidia:count=16".parse().unwrap();
+    assert_eq!(requirement.check(&eight), Err("Required 16 GPUs of vendor 'nvidia', found 8. Detected: NVIDIA x8".to_string()));
+
+    let requirement: GpuRequirement = "nvidia".parse().unwrap();
+    assert_eq!(requirement.check(&[GpuType::Intel]), Err("Required GPU vendor 'nvidia' not found. Detected: Intel".to_string()));
+    assert_eq!(requirement.check(&[]), Err("Required GPU vendor 'nvidia' not found. Detected: none".to_string()));
+
+    let requirement: GpuRequirement = "amd".parse().unwrap();
+    assert_eq!(requirement.check(&[GpuType::AmdApu]), Ok(()));
+    assert_eq!(format_detected(&[GpuType::AmdApu]), "AMD APU");
+}
+
+#[test]
+fn the_bmc_console_is_not_a_gpu() {
+    let vendors = detect_vendors(&MockRunner::new().with("lspci", &["-Dnn"], CommandOutput::ok(&lspci_fixture("server-bmc.txt"))));
+
+    assert_eq!(vendors, vec![GpuType::Amd, GpuType::Amd]);
+}
+
+#[test]
+fn checks_the_vram_of_enough_gpus() {
+    let requirement: GpuRequirement = "nvidia:count=2,vram=40960".parse().unwrap();
+
+    assert_eq!(requirement.check_vram(&[snapshot(0, Some(81920)), snapshot(1, Some(40960))]), Ok(()));
+    assert_eq!(
+        requirement.check_vram(&[snapshot(0, Some(81920)), snapshot(1, Some(24576)), snapshot(2, None)]),
+        Err("Required 2 GPUs of vendor 'nvidia' with at least 40960 MiB of VRAM, found 1".to_string())
+    );
+}
+
+#[test]
+fn exits_before_monitoring_without_the_required_gpus() {
+    let output = run("require-vendor", &["--count", "1", "--require-gpu", "intel"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Error: Required GPU vendor 'intel' not found. Detected: NVIDIA\n");
+
+    let output = run("require-count", &["--count", "1", "--require-gpu", "nvidia:count=4"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Error: Required 4 GPUs of vendor 'nvidia', found 1. Detected: NVIDIA\n"));
+
+    let output = run("require-vram", &["--count", "1", "--require-gpu", "nvidia:vram=40960"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Error: Required 1 GPU of vendor 'nvidia' with at least 40960 MiB of VRAM, found 0\n"));
+}
+
+#[test]
+fn monitors_when_the_requirement_holds() {
+    let output = run("require-met", &["--count", "1", "--require-gpu", "nvidia:count=1,vram=16384"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(stdout.contains("Utilization (percent): 45.0"), "{}", stdout);
+}
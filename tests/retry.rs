@@ -0,0 +1,96 @@
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::backend::{Backend, SpawnBackend};
+use gpu_auto_top::runner::{retry_command, CommandOutput, CommandRunner, MockRunner, Retry};
+use gpu_auto_top::{GpuInfo, GpuType, PollResult};
+
+const QUERY: [&str; 2] = ["--query-gpu=index,utilization.gpu,memory.used,memory.total,temperature.gpu,power.draw,utilization.memory,pci.bus_id", "--format=csv,noheader,nounits"];
+
+/// Fails the first `failures` runs, as nvidia-smi does during a GPU reset, then succeeds.
+struct FlakyRunner {
+    failures: u32,
+    runs: Mutex<u32>,
+}
+
+impl FlakyRunner {
+    fn new(failures: u32) -> Self {
+        FlakyRunner { failures, runs: Mutex::new(0) }
+    }
+
+    fn runs(&self) -> u32 {
+        *self.runs.lock().unwrap()
+    }
+}
+
+impl CommandRunner for FlakyRunner {
+    fn run(&self, _program: &str, _args: &[&str]) -> io::Result<CommandOutput> {
+        let mut runs = self.runs.lock().unwrap();
+        *runs += 1;
+        if *runs <= self.failures {
+            // Output of the failed run, which must not be parsed.
+            return Ok(CommandOutput { stdout: "0, 99, 1, 1, 1, 1\n".to_string(), ..CommandOutput::failed(15, "GPU is lost") });
+        }
+        Ok(CommandOutput::ok("0, 45, 1024, 24576, 60, 120.50\n"))
+    }
+}
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "GPU 0".to_string(), bus_id: None, render_offload: None }
+}
+
+#[test]
+fn retries_until_the_command_succeeds() {
+    let runner = FlakyRunner::new(2);
+    let started = Instant::now();
+
+    let output = retry_command(&runner, "nvidia-smi", &QUERY, 3, Duration::from_millis(20)).unwrap();
+
+    assert!(output.success);
+    assert_eq!(runner.runs(), 3);
+    assert!(started.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+fn returns_the_last_failure_after_the_last_attempt() {
+    let runner = FlakyRunner::new(5);
+
+    let output = retry_command(&runner, "nvidia-smi", &QUERY, 3, Duration::ZERO).unwrap();
+
+    assert_eq!(output.code, Some(15));
+    assert_eq!(runner.runs(), 3);
+}
+
+#[test]
+fn a_missing_tool_is_not_retried() {
+    let started = Instant::now();
+
+    let error = retry_command(&MockRunner::new(), "nvidia-smi", &QUERY, 3, Duration::from_secs(5)).unwrap_err();
+
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn the_backend_retries_a_failed_poll_and_never_parses_it() {
+    let runner = FlakyRunner::new(1);
+    let mut backend = SpawnBackend::new(&runner, &GpuType::Nvidia).with_retry(Retry { max_attempts: 2, delay: Duration::ZERO });
+
+    match &backend.poll(&[gpu()])[0] {
+        PollResult::Ok { snapshot, .. } => assert_eq!(snapshot.utilization, 45.0),
+        other => panic!("expected a snapshot, got {:?}", other),
+    }
+
+    let runner = FlakyRunner::new(1);
+    let mut backend = SpawnBackend::new(&runner, &GpuType::Nvidia).with_retry(Retry::ONCE);
+    assert!(matches!(&backend.poll(&[gpu()])[0], PollResult::TransientError { message, .. } if message == "GPU is lost"));
+}
+
+#[test]
+fn failed_radeontop_output_is_not_parsed() {
+    let runner = MockRunner::new().with("radeontop", &["-d", "-", "-l", "1"], CommandOutput { stdout: "gpu 50.00%\n".to_string(), ..CommandOutput::failed(1, "Cannot access GPU memory") });
+    let mut backend = SpawnBackend::new(&runner, &GpuType::Amd).with_retry(Retry { max_attempts: 2, delay: Duration::ZERO });
+
+    assert!(matches!(&backend.poll(&[gpu()])[0], PollResult::TransientError { message, .. } if message == "radeontop failed: Cannot access GPU memory"));
+}
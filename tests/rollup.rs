@@ -0,0 +1,177 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use gpu_auto_top::json;
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::rollup::{csv_path, parse_window, Rollup, RollupCsv, RollupTracker};
+use gpu_auto_top::schema::{validate, SCHEMA_VERSION};
+use gpu_auto_top::GpuSnapshot;
+
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// 2023-11-14T22:14:00Z, on a minute boundary.
+fn minute() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_040)
+}
+
+fn seconds(seconds: u64) -> SystemTime {
+    minute() + Duration::from_secs(seconds)
+}
+
+fn snapshot(index: u32, utilization: f64, temperature_c: Option<f32>, memory_used_mib: Option<u64>, power_w: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib, memory_total_mib: Some(81920), temperature_c, power_w, ..common::snapshot(index, "NVIDIA A100-SXM4-80GB", utilization) }
+}
+
+fn assert_close(actual: Option<f64>, expected: f64) {
+    let actual = actual.expect("energy");
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+#[test]
+fn parses_windows_and_names_the_csv() {
+    assert_eq!(parse_window("1m"), Ok(MINUTE));
+    assert_eq!(parse_window("300"), Ok(Duration::from_secs(300)));
+    assert!(parse_window("0s").is_err());
+    assert!(parse_window("1500ms").is_err());
+    assert!(parse_window("soon").is_err());
+    assert_eq!(csv_path("/tmp/samples.csv"), "/tmp/samples.rollup.csv");
+    assert_eq!(csv_path("/tmp/samples"), "/tmp/samples.rollup.csv");
+}
+
+#[test]
+fn folds_samples_across_a_window_boundary() {
+    let mut tracker = RollupTracker::new(MINUTE, 10 * MINUTE);
+
+    assert_eq!(tracker.record(&snapshot(0, 20.0, Some(50.0), Some(1000), Some(100.0)), seconds(30)), None);
+    assert_eq!(tracker.record(&snapshot(0, 40.0, Some(60.0), Some(3000), Some(100.0)), seconds(50)), None);
+    let first = tracker.record(&snapshot(0, 80.0, Some(70.0), Some(2000), Some(200.0)), seconds(70)).expect("the first window closes");
+    assert_eq!(tracker.record(&snapshot(0, 60.0, None, None, Some(200.0)), seconds(90)), None);
+    let last = tracker.finish();
+
+    // Monitoring started half a minute in.
+    assert_eq!((first.start, first.end, first.partial, first.samples), (minute(), seconds(60), true, 2));
+    assert_eq!((first.utilization_mean, first.utilization_min, first.utilization_max), (30.0, 20.0, 40.0));
+    assert_eq!((first.temperature_mean, first.temperature_max, first.memory_used_max_mib), (Some(55.0), Some(60.0), Some(3000)));
+    // 100 W for 20 s, then up to the 150 W interpolated at the boundary over 10 s.
+    assert_close(first.energy_wh, (100.0 * 20.0 + 125.0 * 10.0) / 3600.0);
+
+    // Monitoring ended before the second window did.
+    let [second] = last.as_slice() else { panic!("{:?}", last) };
+    assert_eq!((second.start, second.end, second.partial, second.samples), (seconds(60), seconds(120), true, 2));
+    assert_eq!((second.utilization_mean, second.utilization_min, second.utilization_max), (70.0, 60.0, 80.0));
+    assert_eq!((second.temperature_mean, second.temperature_max, second.memory_used_max_mib), (Some(70.0), Some(70.0), Some(2000)));
+    assert_close(second.energy_wh, (175.0 * 10.0 + 200.0 * 20.0) / 3600.0);
+    assert!(tracker.finish().is_empty());
+}
+
+#[test]
+fn whole_windows_are_not_partial_and_gpus_are_separate() {
+    let mut tracker = RollupTracker::new(MINUTE, 10 * MINUTE);
+
+    tracker.record(&snapshot(0, 10.0, None, None, None), seconds(0));
+    tracker.record(&snapshot(1, 90.0, None, None, Some(300.0)), seconds(15));
+    tracker.record(&snapshot(0, 30.0, None, None, None), seconds(45));
+    let whole = tracker.record(&snapshot(0, 50.0, None, None, None), seconds(60)).expect("closes");
+    // GPU 1 has a single sample, and no energy without a second one.
+    let single = tracker.record(&snapshot(1, 70.0, None, None, Some(300.0)), seconds(61 + 15 * 60)).expect("closes");
+
+    assert!(!whole.partial);
+    assert_eq!((whole.gpu, whole.samples, whole.utilization_mean, whole.energy_wh), (0, 2, 20.0, None));
+    assert_eq!((whole.temperature_mean, whole.memory_used_max_mib), (None, None));
+    assert!(single.partial);
+    assert_eq!((single.gpu, single.samples, single.utilization_max, single.energy_wh), (1, 1, 90.0, None));
+
+    // The gap of a quarter of an hour adds no energy to GPU 1's next window either.
+    let open = tracker.finish();
+    assert_eq!(open.iter().map(|rollup| (rollup.gpu, rollup.start, rollup.energy_wh)).collect::<Vec<_>>(), [(0, seconds(60), None), (1, seconds(15 * 60 + 60), None)]);
+}
+
+fn rollup() -> Rollup {
+    let mut tracker = RollupTracker::new(MINUTE, 10 * MINUTE);
+    tracker.record(&snapshot(0, 40.0, Some(61.0), Some(2048), Some(250.0)), seconds(10));
+    tracker.record(&snapshot(0, 60.0, Some(63.0), Some(4096), Some(250.0)), seconds(46));
+    tracker.finish().remove(0)
+}
+
+#[test]
+fn json_and_text_name_the_window() {
+    let context = OutputContext { format: OutputFormat::Ndjson, hostname: Some("node1".to_string()), labels: "rack=a1".parse::<Labels>().unwrap(), tick_seq: None, timestamp: None, precision: 1 };
+
+    let record = rollup().to_json(&context);
+
+    assert_eq!(
+        record,
+        format!(
+            "{{\"schema_version\":{},\"hostname\":\"node1\",\"type\":\"rollup\",\"gpu\":0,\"name\":\"NVIDIA A100-SXM4-80GB\",\"window_start\":\"2023-11-14T22:14:00Z\",\"window_end\":\"2023-11-14T22:15:00Z\",\"partial\":true,\"samples\":2,\"utilization_mean\":50,\"utilization_min\":40,\"utilization_max\":60,\"temperature_mean\":62,\"temperature_max\":63,\"memory_used_max_mib\":4096,\"energy_wh\":2.5,\"labels\":{{\"rack\":\"a1\"}}}}",
+            SCHEMA_VERSION
+        )
+    );
+    validate(&json::parse(&record).unwrap()).unwrap_or_else(|err| panic!("{}\n{}", err, record));
+    assert_eq!(
+        rollup().format_text(1),
+        "GPU 0 (NVIDIA A100-SXM4-80GB) 2023-11-14T22:14:00Z to 2023-11-14T22:15:00Z (partial): Utilization (percent): mean 50.0, min 40.0, max 60.0, Temperature: mean 62.0°C, max 63°C, Memory: max 4096 MiB, Energy: 2.500 Wh"
+    );
+}
+
+#[test]
+fn csv_rows_are_written_as_windows_close() {
+    let path = std::env::temp_dir().join(format!("gpuatop-rollup-{}.rollup.csv", std::process::id()));
+    let mut csv = RollupCsv::create(path.to_str().unwrap()).unwrap();
+
+    csv.write(&rollup()).unwrap();
+    let written = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        written.lines().collect::<Vec<_>>(),
+        [
+            format!("# schema_version={}", SCHEMA_VERSION).as_str(),
+            RollupCsv::HEADER,
+            "2023-11-14T22:14:00Z,2023-11-14T22:15:00Z,0,true,2,50,40,60,62,63,4096,2.5"
+        ]
+    );
+}
+
+#[test]
+fn rollup_only_prints_the_windows_instead_of_the_samples() {
+    let dir = common::fake_tools("rollup");
+    let raw = dir.join("samples.csv");
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["--count", "3", "--interval", "100ms", "--allow-fast-poll", "--format", "ndjson", "--rollup", "1h", "--rollup-only", "--dump-raw", raw.to_str().unwrap()])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    let csv = fs::read_to_string(dir.join("samples.rollup.csv")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    // A run this short fits in one window, unless it straddles the hour.
+    let records: Vec<&str> = stdout.lines().collect();
+    assert!(!records.is_empty() && records.len() <= 2, "{}", stdout);
+    for record in &records {
+        assert!(record.contains("\"type\":\"rollup\"") && record.contains("\"partial\":true"), "{}", record);
+        assert!(record.contains("\"utilization_max\":45"), "{}", record);
+    }
+    assert_eq!(csv.lines().count(), 2 + records.len(), "{}", csv);
+}
+
+#[test]
+fn rollup_only_requires_a_window_and_a_json_or_text_format() {
+    let run = |args: &[&str]| Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).output().unwrap();
+
+    let output = run(&["--rollup-only"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: --rollup-only requires --rollup\n");
+
+    let output = run(&["--rollup", "1m", "--format", "statsd"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: --rollup supports the text, ndjson and json formats\n");
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::Duration;
+
+use gpu_auto_top::sampling::{aggregate, clamp_interval, parse_duration, RawSample, RingBuffer, MIN_INTERVAL};
+use gpu_auto_top::schema::SCHEMA_VERSION;
+use gpu_auto_top::GpuSnapshot;
+
+fn run(name: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(args).env("PATH", common::path_with(&dir)).env("XDG_RUNTIME_DIR", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success(), "gpuatop failed: {}", String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+#[test]
+fn durations_take_a_unit() {
+    assert_eq!(parse_duration("50ms"), Ok(Duration::from_millis(50)));
+    assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+    assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    assert!(parse_duration("0ms").is_err());
+    assert!(parse_duration("5d").is_err());
+}
+
+#[test]
+fn fast_intervals_are_raised_to_the_minimum() {
+    assert_eq!(MIN_INTERVAL, Duration::from_millis(50));
+    assert_eq!(clamp_interval(Duration::from_millis(10), false), MIN_INTERVAL);
+    assert_eq!(clamp_interval(Duration::from_millis(50), false), MIN_INTERVAL);
+    assert_eq!(clamp_interval(Duration::from_millis(200), false), Duration::from_millis(200));
+    assert_eq!(clamp_interval(Duration::from_millis(10), true), Duration::from_millis(10));
+}
+
+#[test]
+fn a_clamped_interval_is_reported() {
+    let clamped = run("fast-poll", &["--count", "1", "--interval", "10ms"]);
+    let stdout = String::from_utf8_lossy(&clamped.stdout);
+    assert!(stdout.contains("Warning: --interval 10ms is below 50ms and would slow down the GPU, polling every 50ms instead"), "{}", stdout);
+
+    let allowed = run("fast-poll-allowed", &["--count", "1", "--interval", "10ms", "--allow-fast-poll"]);
+    assert!(!String::from_utf8_lossy(&allowed.stdout).contains("--interval"));
+}
+
+#[test]
+fn help_explains_the_cost_of_fast_polling() {
+    let help = String::from_utf8(run("fast-poll-help", &["--help"]).stdout).unwrap();
+
+    assert!(help.contains("polling faster slows down what is being monitored"), "{}", help);
+    assert!(help.contains("An --interval below 50ms is raised to 50ms with a warning\n  unless --allow-fast-poll is given."), "{}", help);
+    assert!(help.contains("(high-frequency mode)"), "{}", help);
+}
+
+fn gpu_snapshot(utilization: f64, memory_used_mib: Option<u64>, temperature_c: Option<f32>, power_w: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib, memory_total_mib: Some(24576), temperature_c, power_w, ..common::snapshot(1, "NVIDIA GeForce RTX 3090", utilization) }
+}
+
+/// The data rows `write_csv` writes for `buffer`.
+fn csv_rows(buffer: &RingBuffer, name: &str) -> Vec<String> {
+    let path = std::env::temp_dir().join(format!("gpuatop-{}-{}.csv", name, std::process::id()));
+    buffer.write_csv(path.to_str().unwrap()).unwrap();
+    let content = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut lines = content.lines();
+    assert_eq!(lines.next(), Some(format!("# schema_version={}", SCHEMA_VERSION).as_str()));
+    assert_eq!(lines.next(), Some("time_s,tick_seq,gpu,utilization,memory_used_mib,temperature_c,power_w"));
+    lines.map(str::to_string).collect()
+}
+
+fn push_ticks(buffer: &mut RingBuffer, ticks: std::ops::Range<u64>) {
+    for tick in ticks {
+        let snapshot = gpu_snapshot(tick as f64, Some(1000 + tick), None, Some(100.5));
+        buffer.push(RawSample::new(Duration::from_millis(tick * 50), tick, &snapshot));
+    }
+}
+
+#[test]
+fn ring_buffer_keeps_every_sample_up_to_its_capacity() {
+    let mut buffer = RingBuffer::new(3);
+    push_ticks(&mut buffer, 0..3);
+
+    assert_eq!(buffer.dropped(), 0);
+    assert_eq!(csv_rows(&buffer, "ring-full"), ["0.000,0,1,0,1000,,100.5", "0.050,1,1,1,1001,,100.5", "0.100,2,1,2,1002,,100.5"]);
+}
+
+#[test]
+fn ring_buffer_overwrites_the_oldest_samples_when_full() {
+    let mut buffer = RingBuffer::new(3);
+    push_ticks(&mut buffer, 0..8);
+
+    assert_eq!(buffer.dropped(), 5);
+    assert_eq!(csv_rows(&buffer, "ring-wrapped"), ["0.250,5,1,5,1005,,100.5", "0.300,6,1,6,1006,,100.5", "0.350,7,1,7,1007,,100.5"]);
+}
+
+#[test]
+fn ring_buffer_of_one_keeps_the_latest_sample() {
+    let mut buffer = RingBuffer::new(1);
+    push_ticks(&mut buffer, 0..4);
+
+    assert_eq!(buffer.dropped(), 3);
+    assert_eq!(csv_rows(&buffer, "ring-one"), ["0.150,3,1,3,1003,,100.5"]);
+}
+
+#[test]
+fn aggregate_of_nothing_is_none() {
+    assert!(aggregate(&[]).is_none());
+}
+
+#[test]
+fn aggregate_averages_utilization_and_power_and_keeps_peaks() {
+    let samples = [
+        gpu_snapshot(10.0, Some(2048), Some(61.0), Some(100.0)),
+        gpu_snapshot(90.0, Some(4096), Some(67.0), None),
+        gpu_snapshot(20.0, None, Some(64.0), Some(200.0)),
+    ];
+
+    let aggregated = aggregate(&samples).unwrap();
+
+    assert_eq!(aggregated.gpu.index, 1);
+    assert_eq!(aggregated.utilization, 40.0);
+    assert_eq!(aggregated.utilization_max, Some(90.0));
+    assert_eq!(aggregated.memory_used_mib, Some(4096));
+    assert_eq!(aggregated.memory_total_mib, Some(24576));
+    assert_eq!(aggregated.temperature_c, Some(67.0));
+    // The sample without a power reading does not count towards the mean.
+    assert_eq!(aggregated.power_w, Some(150.0));
+}
+
+#[test]
+fn aggregate_of_samples_without_a_metric_has_none() {
+    let aggregated = aggregate(&[gpu_snapshot(5.0, None, None, None), gpu_snapshot(7.0, None, None, None)]).unwrap();
+
+    assert_eq!(aggregated.utilization, 6.0);
+    assert_eq!((aggregated.memory_used_mib, aggregated.temperature_c, aggregated.power_w), (None, None, None));
+}
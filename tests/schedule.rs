@@ -0,0 +1,86 @@
+#![cfg(feature = "cli")]
+
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::schedule::{Diagnostics, TickSchedule, REPORT_INTERVAL};
+
+const SECOND: Duration = Duration::from_secs(1);
+
+fn ms(milliseconds: u64) -> Duration {
+    Duration::from_millis(milliseconds)
+}
+
+#[test]
+fn ticks_are_due_at_fixed_times_whatever_the_collection_time() {
+    let start = Instant::now();
+    let mut schedule = TickSchedule::new(start, SECOND);
+
+    // Collection takes 50 to 150 ms; the next tick stays due on the whole second.
+    assert_eq!(schedule.advance(start + ms(150)), start + SECOND);
+    assert_eq!(schedule.advance(start + SECOND + ms(50)), start + 2 * SECOND);
+    assert_eq!(schedule.advance(start + 2 * SECOND + ms(120)), start + 3 * SECOND);
+    assert_eq!(schedule.seq(), 3);
+    assert_eq!(schedule.skipped(), 0);
+}
+
+#[test]
+fn a_tick_less_than_one_interval_late_runs_immediately() {
+    let start = Instant::now();
+    let mut schedule = TickSchedule::new(start, SECOND);
+
+    let deadline = schedule.advance(start + ms(1800));
+
+    assert_eq!(deadline, start + SECOND);
+    assert_eq!(schedule.seq(), 1);
+    assert_eq!(schedule.skipped(), 0);
+}
+
+#[test]
+fn slow_collection_skips_the_missed_ticks() {
+    let start = Instant::now();
+    let mut schedule = TickSchedule::new(start, SECOND);
+
+    // Tick 0 took 3.5 s: ticks 1 and 2 are skipped, tick 3 is caught up right away.
+    let deadline = schedule.advance(start + ms(3500));
+
+    assert_eq!(deadline, start + 3 * SECOND);
+    assert_eq!(schedule.seq(), 3);
+    assert_eq!(schedule.skipped(), 2);
+
+    // Back on schedule afterwards.
+    assert_eq!(schedule.advance(start + 3 * SECOND + ms(100)), start + 4 * SECOND);
+    assert_eq!(schedule.skipped(), 2);
+}
+
+#[test]
+fn drift_is_how_late_the_tick_started() {
+    let start = Instant::now();
+    let mut schedule = TickSchedule::new(start, SECOND);
+    schedule.advance(start + ms(100));
+
+    assert_eq!(schedule.drift(start + SECOND + ms(3)), ms(3));
+    assert_eq!(schedule.drift(start + SECOND - ms(1)), Duration::ZERO);
+}
+
+#[test]
+fn diagnostics_report_once_per_period() {
+    let start = Instant::now();
+    let mut schedule = TickSchedule::new(start, SECOND);
+    let mut diagnostics = Diagnostics::new(start);
+
+    diagnostics.record(ms(50));
+    diagnostics.record(ms(150));
+    schedule.advance(start + ms(3500));
+
+    assert_eq!(diagnostics.report(start + ms(3500), &schedule), None);
+
+    let now = start + REPORT_INTERVAL + ms(2);
+    let line = diagnostics.report(now, &schedule).expect("a minute passed");
+    assert_eq!(line, format!("Schedule: 2 ticks, collection mean 100.0 ms, max 150.0 ms, 2 skipped, drift {:.1} ms", schedule.drift(now).as_secs_f64() * 1000.0));
+
+    // The next period starts empty.
+    assert_eq!(diagnostics.report(now + REPORT_INTERVAL, &schedule), None);
+    diagnostics.record(ms(10));
+    let line = diagnostics.report(now + REPORT_INTERVAL, &schedule).expect("a tick was recorded");
+    assert!(line.contains(", 0 skipped,"), "{}", line);
+}
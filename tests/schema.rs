@@ -0,0 +1,167 @@
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+
+use gpu_auto_top::aperture::ApertureMetrics;
+use gpu_auto_top::backend::EngineBusy;
+use gpu_auto_top::desktop::UsageSplit;
+use gpu_auto_top::efficiency::Efficiency;
+use gpu_auto_top::idle::Activity;
+use gpu_auto_top::json::{self, Value};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::nvlink::NvLinkMetrics;
+use gpu_auto_top::output::{format_snapshot, format_state, OutputContext, OutputFormat, Timestamp};
+use gpu_auto_top::power::DeviceState;
+use gpu_auto_top::sampling::{RawSample, RingBuffer};
+use gpu_auto_top::schema::{validate, SCHEMA, SCHEMA_VERSION};
+use gpu_auto_top::temperature::Sensor;
+use gpu_auto_top::users::UserUsage;
+use gpu_auto_top::{aggregate, delta, users, GpuInfo, GpuSnapshot, MemoryBandwidthMetrics};
+
+fn context() -> OutputContext {
+    OutputContext { format: OutputFormat::Ndjson, hostname: Some("node1".to_string()), labels: "rack=a1".parse::<Labels>().unwrap(), tick_seq: Some(4), timestamp: Some(Timestamp::Iso8601("2023-10-31T15:17:12Z".to_string())), precision: 1 }
+}
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "NVIDIA A100-SXM4-80GB".to_string(), bus_id: None, render_offload: None }
+}
+
+/// A sample with every optional metric set.
+fn full_snapshot() -> GpuSnapshot {
+    GpuSnapshot {
+        gpu: gpu(),
+        utilization: 45.5,
+        utilization_max: Some(80.0),
+        memory_used_mib: Some(20480),
+        memory_total_mib: Some(81920),
+        temperature_c: Some(61.0),
+        power_w: Some(250.5),
+        nvlink: Some(NvLinkMetrics { tx_kib_per_s: 1024.0, rx_kib_per_s: 512.0, replay_errors: 0, crc_errors: 1 }),
+        usage_split: Some(UsageSplit { desktop: 2.0, apps: 43.5 }),
+        memory_bandwidth: Some(MemoryBandwidthMetrics { read_gbps: Some(120.5), write_gbps: Some(80.25), utilization_pct: Some(30.0) }),
+        aperture: Some(ApertureMetrics {
+            reserved_mib: Some(346),
+            bar1_used_mib: Some(254),
+            bar1_total_mib: Some(256),
+            vis_vram_used_mib: Some(200),
+            vis_vram_total_mib: Some(256),
+        }),
+        temperatures: Some([(Sensor::Gpu, 61.0), (Sensor::Mem, 70.0)].into()),
+        activity: Some(Activity::Idle(Duration::from_secs(227))),
+        efficiency: Some(Efficiency::TflopsPerWatt(0.62)),
+        engines: Some(vec![
+            EngineBusy { engine: "render".to_string(), ns_total: 1_250_000_000, busy_pct: Some(45.5) },
+            EngineBusy { engine: "video".to_string(), ns_total: 0, busy_pct: None },
+        ]),
+        clock_mhz: Some(1980),
+        throttle_reasons: Some(vec!["soft_temp_limit".to_string()]),
+        source: Some("nvidia-smi".to_string()),
+    }
+}
+
+fn parse(record: &str) -> Value {
+    json::parse(record).unwrap_or_else(|err| panic!("invalid JSON {}: {}", record, err))
+}
+
+fn keys(value: &Value) -> Vec<String> {
+    match value {
+        Value::Object(members) => members.iter().map(|(key, _)| key.clone()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn member<'a>(value: &'a Value, key: &str) -> &'a Value {
+    match value {
+        Value::Object(members) => &members.iter().find(|(name, _)| name == key).unwrap_or_else(|| panic!("no {}", key)).1,
+        _ => panic!("not an object"),
+    }
+}
+
+#[test]
+fn every_record_type_matches_the_schema() {
+    let snapshot = full_snapshot();
+    let mut lighter = full_snapshot();
+    lighter.utilization = 50.0;
+    lighter.temperature_c = None;
+    let usage = vec![
+        UserUsage { user: "alice".to_string(), processes: 2, utilization: Some(43.5), memory_used_mib: 12288 },
+        UserUsage { user: "bob".to_string(), processes: 1, utilization: None, memory_used_mib: 0 },
+    ];
+
+    let records = [
+        format_snapshot(&snapshot, &context()),
+        format_state(&gpu(), DeviceState::Asleep, &context()),
+        format_state(&gpu(), DeviceState::Lost, &context()),
+        delta::format_json(&delta::diff_snapshots(&snapshot, &lighter, 0.0), &context()),
+        users::format_json(&usage, &context()),
+        aggregate::format_json(&mut [snapshot.clone(), lighter], &context()),
+    ];
+
+    for record in &records {
+        let value = parse(record);
+        assert_eq!(member(&value, "schema_version"), &Value::Number(SCHEMA_VERSION as f64), "{}", record);
+        if let Err(err) = validate(&value) {
+            panic!("{}\n{}", err, record);
+        }
+    }
+}
+
+#[test]
+fn schema_lists_exactly_the_sample_fields() {
+    let schema = parse(SCHEMA);
+    let properties = keys(member(member(member(&schema, "$defs"), "sample"), "properties"));
+
+    let sample = keys(&parse(&format_snapshot(&full_snapshot(), &context())));
+
+    assert_eq!(properties, sample);
+}
+
+#[test]
+fn rejects_records_that_break_the_schema() {
+    let record = |text: &str| validate(&parse(text));
+
+    assert!(record(r#"{"schema_version":1,"gpu":0,"name":"A","utilization":45}"#).is_ok());
+    assert!(record(r#"{"schema_version":1,"gpu":0,"name":"A","utilization":"45"}"#).is_err());
+    assert!(record(r#"{"schema_version":1,"gpu":0,"name":"A","utilization":45,"fan_speed":30}"#).is_err());
+    assert!(record(r#"{"schema_version":2,"gpu":0,"name":"A","utilization":45}"#).is_err());
+    assert!(record(r#"{"schema_version":1,"gpu":0,"name":"A","state":"idle"}"#).is_err());
+    assert!(record(r#"{"schema_version":1,"name":"A"}"#).is_err());
+}
+
+/// Sessions recorded with the previous release, before `schema_version` was added, must keep
+/// matching the schema.
+#[test]
+fn previous_release_sessions_match_the_schema() {
+    let session = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/session-0.1.0.ndjson")).unwrap();
+
+    for line in session.lines() {
+        if let Err(err) = validate(&parse(line)) {
+            panic!("{}\n{}", err, line);
+        }
+    }
+}
+
+#[test]
+fn csv_starts_with_the_schema_version() {
+    let path = std::env::temp_dir().join(format!("gpuatop-schema-{}.csv", std::process::id()));
+    let mut samples = RingBuffer::new(4);
+    samples.push(RawSample::new(Duration::from_millis(50), 0, &full_snapshot()));
+
+    samples.write_csv(path.to_str().unwrap()).unwrap();
+    let csv = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some(format!("# schema_version={}", SCHEMA_VERSION).as_str()));
+    assert_eq!(lines.next(), Some("time_s,tick_seq,gpu,utilization,memory_used_mib,temperature_c,power_w"));
+}
+
+#[test]
+fn schema_flag_prints_the_schema() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).arg("--schema").output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(parse(&String::from_utf8(output.stdout).unwrap()), parse(SCHEMA));
+}
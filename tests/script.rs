@@ -0,0 +1,132 @@
+#![cfg(feature = "lua")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+
+use gpu_auto_top::script::{Action, Script};
+
+const RECORD: &str = r#"{"gpu":0,"name":"Tesla T4","utilization":45.5,"memory_used_mib":1024,"temperature_c":60,"labels":{"rack":"a1"}}"#;
+
+/// Runs gpuatop for two cycles with `script` as `--script` and the options `args`.
+fn run(name: &str, script: &str, args: &[&str]) -> Output {
+    let dir = common::fake_tools(name);
+    let path = dir.join("script.lua");
+    fs::write(&path, script).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["--count", "2", "--interval", "100ms", "--allow-fast-poll"])
+        .args(args)
+        .arg("--script")
+        .arg(&path)
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn snapshots_are_tables_of_the_record_fields() {
+    let mut script = Script::new(
+        "fields.lua",
+        "function on_snapshot(snapshots)
+           local s = snapshots[1]
+           print(#snapshots, s.gpu, s.name, s.utilization, math.type(s.memory_used_mib), s.labels.rack, s.power_w)
+         end",
+    )
+    .unwrap();
+
+    assert_eq!(script.on_snapshot(&[RECORD.to_string()]), Ok(vec![Action::Print("1\t0\tTesla T4\t45.5\tinteger\ta1\tnil".to_string())]));
+}
+
+#[test]
+fn alert_and_exit_are_actions_and_exit_stops_the_script() {
+    let mut script = Script::new(
+        "exit.lua",
+        "local cycles = 0
+         function on_snapshot(snapshots)
+           cycles = cycles + 1
+           if cycles == 2 then
+             alert('GPU 0 is hot')
+             alert('GPU 1 is hot', 1)
+             exit(7)
+             print('not reached')
+           end
+         end",
+    )
+    .unwrap();
+
+    assert_eq!(script.on_snapshot(&[]), Ok(vec![]));
+    let alert = |message: &str, gpu| Action::Alert { message: message.to_string(), gpu };
+    assert_eq!(script.on_snapshot(&[]), Ok(vec![alert("GPU 0 is hot", None), alert("GPU 1 is hot", Some(1)), Action::Exit(7)]));
+}
+
+#[test]
+fn scripts_that_cannot_run_are_errors() {
+    assert_eq!(Script::new("empty.lua", "").err(), Some("--script empty.lua does not define an on_snapshot function".to_string()));
+    assert!(Script::new("syntax.lua", "function on_snapshot(").err().unwrap().starts_with("Failed to load --script syntax.lua: syntax error"));
+    assert!(Script::load("/nonexistent/script.lua").err().unwrap().starts_with("Failed to read --script /nonexistent/script.lua"));
+
+    let mut script = Script::new("error.lua", "function on_snapshot(snapshots) error('no luck') end").unwrap();
+    assert!(script.on_snapshot(&[]).unwrap_err().ends_with("error.lua\"]:1: no luck"));
+}
+
+#[test]
+fn the_script_sees_every_cycle_and_its_output_is_printed() {
+    let script = "local cycles = 0
+function on_snapshot(snapshots)
+  cycles = cycles + 1
+  print('cycle ' .. cycles)
+  for _, snapshot in ipairs(snapshots) do
+    if snapshot.utilization == 45 then
+      alert('GPU ' .. snapshot.gpu .. ' at 45%')
+    end
+  end
+end
+";
+    let output = run("script", script, &["-q"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout.matches("Utilization (percent): 45.0").count(), 2, "{}", stdout);
+    assert_eq!(stdout.matches(" [SCRIPT warning] GPU 0 at 45%\n").count(), 2, "{}", stdout);
+    assert!(stdout.find("cycle 1\n").unwrap() < stdout.find("cycle 2\n").unwrap(), "{}", stdout);
+}
+
+#[test]
+fn the_script_sets_the_exit_code() {
+    let output = run("script-exit", "function on_snapshot(snapshots) exit(7) end", &["-q"]);
+
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn a_script_without_on_snapshot_stops_gpuatop() {
+    let output = run("script-invalid", "print('hello')", &["-q"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with(".lua does not define an on_snapshot function\n"), "{}", stdout);
+}
+
+#[test]
+fn script_alerts_are_events_that_quiet_does_not_silence() {
+    let output = run("script-alert-event", "function on_snapshot(snapshots) alert('rack a1 is hot', 0) end", &["-q", "-q", "--format", "ndjson"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    let events: Vec<&str> = stdout.lines().filter(|line| line.contains("\"type\":\"event\"")).collect();
+    assert_eq!(events.len(), 2, "{}", stdout);
+    assert!(events[0].contains("\"event\":\"script_alert\",\"severity\":\"warning\",\"gpu\":0,\"name\":\"NVIDIA GeForce RTX 3090\",\"message\":\"rack a1 is hot\""), "{}", events[0]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[SCRIPT warning] rack a1 is hot"));
+}
+
+#[test]
+fn script_alerts_about_unmonitored_gpus_are_errors() {
+    let output = run("script-alert-unknown", "function on_snapshot(snapshots) alert('hot', 7) end", &["-q", "-q"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(stdout.matches("Error: --script alert about GPU 7, which is not monitored: hot\n").count(), 2, "{}", stdout);
+}
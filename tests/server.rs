@@ -0,0 +1,144 @@
+#![cfg(feature = "network")]
+
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::json::{self, MAX_DEPTH};
+use gpu_auto_top::server::{parse_record, read_line, Fleet, HISTORY, MAX_LINE_LEN};
+
+const RECORD: &str = "{\"gpu\":0,\"name\":\"NVIDIA A100-SXM4-80GB\",\"utilization\":45.5,\"memory_used_mib\":20480,\"memory_total_mib\":81920,\"hostname\":\"node1\"}";
+
+fn record(gpu: u32, utilization: f64) -> String {
+    format!("{{\"gpu\":{},\"name\":\"NVIDIA A100-SXM4-80GB\",\"utilization\":{}}}", gpu, utilization)
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+#[test]
+fn records_carry_the_machine_and_the_sample() {
+    let (hostname, snapshot) = parse_record(RECORD).expect("is a sample");
+
+    assert_eq!(hostname.as_deref(), Some("node1"));
+    assert_eq!(snapshot.gpu.index, 0);
+    assert_eq!(snapshot.utilization, 45.5);
+    assert_eq!(parse_record(&record(1, 10.0)).map(|(hostname, _)| hostname), Some(None));
+    assert!(parse_record("{\"gpu\":0,\"state\":\"asleep\"}").is_none());
+    assert!(parse_record("not json").is_none());
+}
+
+#[test]
+fn hostile_input_is_refused_instead_of_exhausting_the_server() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(json::parse(&nested(MAX_DEPTH)).is_ok());
+    assert_eq!(json::parse(&nested(MAX_DEPTH + 1)), Err(format!("Nested too deeply at byte {}", MAX_DEPTH)));
+    assert!(parse_record(&"[".repeat(300_000)).is_none());
+
+    let lines = format!("{}\n{}\n", "x".repeat(MAX_LINE_LEN - 1), "x".repeat(MAX_LINE_LEN));
+    let mut reader = BufReader::new(lines.as_bytes());
+    assert_eq!(read_line(&mut reader).unwrap().map(|line| line.len()), Some(MAX_LINE_LEN));
+    assert!(read_line(&mut reader).is_err());
+}
+
+#[test]
+fn machines_are_listed_with_their_gpus_and_history() {
+    let start = Instant::now();
+    let mut fleet = Fleet::default();
+    fleet.connect("node2");
+    fleet.connect("node1");
+    for (seconds, utilization) in [(0, 20.0), (60, 80.0), (120, 50.0)] {
+        fleet.record("node1", parse_record(&record(0, utilization)).unwrap().1, start + Duration::from_secs(seconds));
+    }
+    fleet.record("node1", parse_record(&record(1, 5.0)).unwrap().1, start + Duration::from_secs(120));
+
+    let lines = fleet.format(start + Duration::from_secs(122), 1);
+
+    assert_eq!(
+        lines,
+        [
+            "node1 (last sample 2s ago)",
+            "  GPU 0 (NVIDIA A100-SXM4-80GB) Utilization (percent): 50.0 | 3 min: mean 50.0%, peak 80.0%",
+            "  GPU 1 (NVIDIA A100-SXM4-80GB) Utilization (percent): 5.0 | 1 min: mean 5.0%, peak 5.0%",
+            "node2 (connected, no samples yet)",
+        ]
+    );
+    let latest = fleet.latest();
+    assert_eq!(latest.len(), 1);
+    assert_eq!(latest[0].1.iter().map(|snapshot| snapshot.utilization).collect::<Vec<_>>(), [50.0, 5.0]);
+}
+
+#[test]
+fn history_is_kept_for_an_hour() {
+    let start = Instant::now();
+    let mut fleet = Fleet::default();
+    fleet.connect("node1");
+    fleet.record("node1", parse_record(&record(0, 90.0)).unwrap().1, start);
+    fleet.record("node1", parse_record(&record(0, 10.0)).unwrap().1, start + Duration::from_secs(600));
+
+    fleet.expire(start + HISTORY + Duration::from_secs(1));
+    assert!(fleet.format(start + HISTORY, 1)[1].ends_with("mean 10.0%, peak 10.0%"));
+
+    // A disconnected machine stays listed until its history runs out.
+    fleet.disconnect("node1");
+    assert!(fleet.latest().is_empty());
+    assert_eq!(fleet.format(start + HISTORY, 1)[0], "node1 (disconnected)");
+    fleet.expire(start + HISTORY + Duration::from_secs(601));
+    assert!(fleet.format(start + HISTORY, 1).is_empty());
+}
+
+#[test]
+fn server_shows_and_exports_what_clients_send() {
+    let (port, prometheus_port) = (free_port(), free_port());
+    let server = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["-q", "server", "--port", &port.to_string(), "--prometheus-port", &prometheus_port.to_string(), "--interval", "300ms", "--count", "5"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let connect = |port: u16| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            match TcpStream::connect(("127.0.0.1", port)) {
+                Ok(stream) => break stream,
+                Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+                Err(err) => panic!("server did not listen: {}", err),
+            }
+        }
+    };
+    // A client sending garbage is dropped without taking the server down.
+    let mut hostile = connect(port);
+    // The server hangs up once the line is too long, so the write may fail.
+    let _ = writeln!(hostile, "GPUATOP/1.0\n{}", "[".repeat(300_000));
+    let mut client = connect(port);
+    writeln!(client, "GPUATOP/1.0\n{}", RECORD).unwrap();
+    // A connection without the handshake is ignored.
+    writeln!(connect(port), "{}", RECORD.replace("node1", "intruder")).unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+
+    let mut scrape = connect(prometheus_port);
+    write!(scrape, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    scrape.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("gpuatop_utilization_percent{gpu=\"0\",name=\"NVIDIA A100-SXM4-80GB\",hostname=\"node1\"} 45.5\n"), "{}", response);
+    assert!(!response.contains("intruder"), "{}", response);
+
+    let output = server.wait_with_output().unwrap();
+    drop(client);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.iter().any(|line| line.starts_with("node1 (last sample ")), "{}", stdout);
+    assert!(lines.contains(&"  GPU 0 (NVIDIA A100-SXM4-80GB) Utilization (percent): 45.5, Memory: 20480/81920 MiB | 1 min: mean 45.5%, peak 45.5%"), "{}", stdout);
+}
+
+#[test]
+fn server_needs_a_port() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).arg("server").output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: server requires --port <port> to listen on"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--prometheus-port", "9100"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: --port and --prometheus-port require the server subcommand"));
+}
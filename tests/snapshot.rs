@@ -0,0 +1,199 @@
+#![cfg(feature = "cli")]
+
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+use gpu_auto_top::json::Value;
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::snapshot::{build_snapshot, diff_snapshots, element_to_value, format_change, format_text, query_snapshot_xml, FieldChange};
+use gpu_auto_top::xml::parse;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/nvidia-smi/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn member<'a>(value: &'a Value, key: &str) -> &'a Value {
+    match value {
+        Value::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value).unwrap_or_else(|| panic!("no member {}", key)),
+        other => panic!("expected an object, got {:?}", other),
+    }
+}
+
+fn text(value: &str) -> Value {
+    Value::String(value.to_string())
+}
+
+#[test]
+fn builds_a_snapshot_of_every_gpu() {
+    let snapshot = build_snapshot(&fixture("query-550.xml"), None).unwrap();
+
+    assert_eq!(member(&snapshot, "driver_version"), &text("550.54.15"));
+    assert_eq!(member(&snapshot, "cuda_version"), &text("12.4"));
+
+    let Value::Array(gpus) = member(&snapshot, "gpus") else { panic!("gpus is not an array") };
+    assert_eq!(gpus.len(), 2);
+    assert_eq!(member(&gpus[0], "id"), &text("00000000:3B:00.0"));
+    assert_eq!(member(&gpus[1], "product_name"), &text("NVIDIA A100-SXM4-80GB"));
+    assert_eq!(member(member(&gpus[1], "fb_memory_usage"), "used"), &text("80000 MiB"));
+}
+
+#[test]
+fn repeated_elements_become_arrays() {
+    let snapshot = build_snapshot(&fixture("query-550.xml"), Some(0)).unwrap();
+    let Value::Array(gpus) = member(&snapshot, "gpus") else { panic!("gpus is not an array") };
+
+    let Value::Array(processes) = member(member(&gpus[0], "processes"), "process_info") else { panic!("process_info is not an array") };
+    assert_eq!(processes.len(), 2);
+    assert_eq!(member(&processes[1], "pid"), &text("40712"));
+}
+
+#[test]
+fn limits_the_snapshot_to_one_gpu() {
+    let snapshot = build_snapshot(&fixture("query-550.xml"), Some(1)).unwrap();
+    let Value::Array(gpus) = member(&snapshot, "gpus") else { panic!("gpus is not an array") };
+
+    assert_eq!(gpus.len(), 1);
+    assert_eq!(member(&gpus[0], "id"), &text("00000000:86:00.0"));
+    assert_eq!(build_snapshot(&fixture("query-550.xml"), Some(2)).unwrap_err(), "GPU 2 not found");
+}
+
+#[test]
+fn rejects_other_documents() {
+    assert_eq!(build_snapshot("<rocm/>", None).unwrap_err(), "Unexpected root element <rocm>");
+}
+
+#[test]
+fn keeps_children_named_like_an_attribute_or_the_text() {
+    let element = parse(r#"<gpu id="0">busy<id>GPU-1</id><value>7</value><value>8</value><name>A</name></gpu>"#).unwrap();
+
+    let value = element_to_value(&element);
+
+    assert_eq!(
+        value,
+        Value::Object(vec![
+            ("id".to_string(), text("0")),
+            ("value".to_string(), text("busy")),
+            ("<id>".to_string(), text("GPU-1")),
+            ("<value>".to_string(), Value::Array(vec![text("7"), text("8")])),
+            ("name".to_string(), text("A")),
+        ])
+    );
+}
+
+#[test]
+fn formats_nested_fields_as_indented_lines() {
+    let snapshot = build_snapshot(&fixture("query-550.xml"), Some(1)).unwrap();
+    let lines = format_text(&snapshot);
+
+    assert!(lines.contains("\ndriver_version: 550.54.15\n"));
+    assert!(lines.contains("\ngpus[0]\n  id: 00000000:86:00.0\n"));
+    assert!(lines.contains("\n  fb_memory_usage\n    total: 81920 MiB\n"));
+}
+
+fn changed_fixture(replacements: &[(&str, &str)]) -> Value {
+    let xml = replacements.iter().fold(fixture("query-550.xml"), |xml, (from, to)| {
+        assert!(xml.contains(from), "fixture has no {}", from);
+        xml.replacen(from, to, 1)
+    });
+    build_snapshot(&xml, None).unwrap()
+}
+
+fn paths(changes: &[FieldChange]) -> Vec<&str> {
+    changes.iter().map(|change| change.path.as_str()).collect()
+}
+
+#[test]
+fn identical_snapshots_have_no_changes() {
+    let snapshot = build_snapshot(&fixture("query-550.xml"), None).unwrap();
+    assert!(diff_snapshots(&snapshot, &snapshot.clone(), true).is_empty());
+}
+
+#[test]
+fn reports_changed_fields() {
+    let before = build_snapshot(&fixture("query-550.xml"), None).unwrap();
+    let after = changed_fixture(&[("<driver_version>550.54.15", "<driver_version>550.90.07"), ("<persistence_mode>Enabled", "<persistence_mode>Disabled")]);
+
+    let changes = diff_snapshots(&before, &after, false);
+
+    assert_eq!(
+        changes,
+        vec![
+            FieldChange { path: "driver_version".to_string(), old: Some("550.54.15".to_string()), new: Some("550.90.07".to_string()), volatile: false },
+            FieldChange { path: "gpus[0].persistence_mode".to_string(), old: Some("Enabled".to_string()), new: Some("Disabled".to_string()), volatile: false },
+        ]
+    );
+    assert_eq!(format_change(&changes[0]), "driver_version: 550.54.15 → 550.90.07");
+}
+
+#[test]
+fn reports_added_and_removed_fields() {
+    let before = build_snapshot(&fixture("query-550.xml"), None).unwrap();
+    let after = changed_fixture(&[("<serial>N/A</serial>", ""), ("<compute_mode>Default</compute_mode>", "<compute_mode>Default</compute_mode><c2c_mode>Disabled</c2c_mode>")]);
+
+    let changes = diff_snapshots(&before, &after, false);
+
+    assert_eq!(paths(&changes), vec!["gpus[0].serial", "gpus[0].c2c_mode"]);
+    assert_eq!((changes[0].old.as_deref(), changes[0].new.as_deref()), (Some("N/A"), None));
+    assert_eq!((changes[1].old.as_deref(), changes[1].new.as_deref()), (None, Some("Disabled")));
+    assert_eq!(format_change(&changes[0]), "gpus[0].serial: N/A → (missing)");
+}
+
+#[test]
+fn skips_volatile_fields_unless_asked() {
+    let before = build_snapshot(&fixture("query-550.xml"), None).unwrap();
+    let after = changed_fixture(&[
+        ("Wed Oct 14 09:12:44 2026", "Wed Oct 14 09:13:02 2026"),
+        ("<gpu_util>45 %", "<gpu_util>3 %"),
+        ("<pid>40712</pid>", "<pid>40755</pid>"),
+        ("<graphics_clock>210 MHz", "<graphics_clock>1395 MHz"),
+    ]);
+
+    assert!(diff_snapshots(&before, &after, false).is_empty());
+
+    let changes = diff_snapshots(&before, &after, true);
+    assert_eq!(
+        paths(&changes),
+        vec!["timestamp", "gpus[0].utilization.gpu_util", "gpus[0].clocks.graphics_clock", "gpus[0].processes.process_info[1].pid"]
+    );
+    assert!(changes.iter().all(|change| change.volatile));
+}
+
+#[test]
+fn snapshot_diff_exits_nonzero_on_configuration_changes() {
+    let dir = std::env::temp_dir().join(format!("gpuatop-snapshot-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let report = dir.join("report.xml");
+    let nvidia_smi = dir.join("nvidia-smi");
+    std::fs::write(&nvidia_smi, format!("#!/bin/sh\ncat '{}'\n", report.display())).unwrap();
+    std::fs::set_permissions(&nvidia_smi, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", dir.display(), std::env::var("PATH").unwrap_or_default());
+    let saved = dir.join("saved.json");
+
+    let run = |xml: String, args: &[&str]| {
+        std::fs::write(&report, xml).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).arg("snapshot").args(args).env("PATH", &path).output().unwrap()
+    };
+
+    let save = run(fixture("query-550.xml"), &["--save", saved.to_str().unwrap()]);
+    let unchanged = run(fixture("query-550.xml"), &["--diff", saved.to_str().unwrap()]);
+    let volatile = run(fixture("query-550.xml").replacen("<gpu_util>45 %", "<gpu_util>3 %", 1), &["--diff", saved.to_str().unwrap()]);
+    let changed = run(fixture("query-550.xml").replacen("<driver_version>550.54.15", "<driver_version>550.90.07", 1), &["--diff", saved.to_str().unwrap()]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(save.status.code(), Some(0));
+    assert_eq!(unchanged.status.code(), Some(0));
+    assert!(unchanged.stdout.is_empty());
+    assert_eq!(volatile.status.code(), Some(0));
+    assert!(volatile.stdout.is_empty());
+    assert_eq!(changed.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&changed.stdout), "driver_version: 550.54.15 → 550.90.07\n");
+}
+
+#[test]
+fn queries_the_report_through_the_runner() {
+    let runner = MockRunner::new().with("nvidia-smi", &["-q", "-x"], CommandOutput::ok(&fixture("query-550.xml")));
+    assert_eq!(query_snapshot_xml(&runner).unwrap(), fixture("query-550.xml"));
+
+    let failing = MockRunner::new().with("nvidia-smi", &["-q", "-x"], CommandOutput::failed(9, "NVIDIA-SMI has failed because it couldn't communicate with the NVIDIA driver.\n"));
+    assert_eq!(query_snapshot_xml(&failing).unwrap_err().to_string(), "NVIDIA-SMI has failed because it couldn't communicate with the NVIDIA driver.");
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn connect(path: &Path) -> UnixStream {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match UnixStream::connect(path) {
+            Ok(stream) => return stream,
+            Err(err) if Instant::now() > deadline => panic!("could not connect to {}: {}", path.display(), err),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+#[test]
+fn socket_client_can_disconnect_without_stopping_the_monitor() {
+    let dir = common::fake_tools("socket");
+    let socket = dir.join("gpuatop.sock");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["--count", "4", "--output-socket"])
+        .arg(&socket)
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let client = connect(&socket);
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let lines: Vec<String> = BufReader::new(client).lines().take(2).map(Result::unwrap).collect();
+    assert_eq!(lines.len(), 2);
+    for line in &lines {
+        assert!(line.starts_with("{\"schema_version\":1,\"gpu\":0,"), "unexpected record: {}", line);
+        assert!(line.contains("\"utilization\":45"), "unexpected record: {}", line);
+    }
+
+    // The client is gone; the monitor has to finish its remaining samples and exit cleanly.
+    let status = child.wait().unwrap();
+    assert!(status.success());
+    assert!(!socket.exists(), "socket file was not cleaned up");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::process::{Command, Output};
+use std::time::Duration;
+
+use gpu_auto_top::backend::{fallback, fits, forced_source, parse_source};
+use gpu_auto_top::config;
+use gpu_auto_top::event::{Event, EventKind};
+use gpu_auto_top::runner::{CommandOutput, MockRunner, Retry};
+use gpu_auto_top::{alert, GpuInfo, GpuType};
+
+fn run(name: &str, args: &[&str], config: Option<&str>) -> Output {
+    let dir = common::fake_tools(name);
+    let mut command = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"));
+    if let Some(config) = config {
+        fs::write(dir.join("config.toml"), config).unwrap();
+        command.arg("--config").arg(dir.join("config.toml"));
+    }
+    let output = command.args(args).env("PATH", common::path_with(&dir)).env("XDG_RUNTIME_DIR", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+fn gpu() -> GpuInfo {
+    GpuInfo { index: 0, name: "NVIDIA GeForce RTX 3090".to_string(), bus_id: None, render_offload: None }
+}
+
+#[test]
+fn parses_sources() {
+    assert_eq!(parse_source("amd-smi"), Ok("amd-smi"));
+    assert_eq!(parse_source("sysfs"), Ok("sysfs:gpu_busy_percent"));
+    assert!(parse_source("nvml").unwrap_err().starts_with("Unknown backend: nvml (expected one of nvidia-smi, nvidia-smi:stream, "));
+}
+
+#[test]
+fn a_source_only_fits_its_vendor() {
+    assert!(fits(&GpuType::Nvidia, "nvidia-smi:stream"));
+    assert!(fits(&GpuType::Intel, "fdinfo"));
+    assert!(fits(&GpuType::Amd, "amd-smi"));
+    assert!(!fits(&GpuType::Nvidia, "radeontop"));
+    assert!(!fits(&GpuType::Intel, "amd-smi"));
+}
+
+#[test]
+fn the_flag_overrides_the_vendor_key() {
+    let document = config::parse("[backend]\nnvidia = \"nvidia-smi:stream\"\namd = \"sysfs\"\n").unwrap();
+
+    assert_eq!(forced_source(&document, &GpuType::Nvidia, None), Ok(Some("nvidia-smi:stream")));
+    assert_eq!(forced_source(&document, &GpuType::Amd, None), Ok(Some("sysfs:gpu_busy_percent")));
+    assert_eq!(forced_source(&document, &GpuType::Intel, None), Ok(None));
+    assert_eq!(forced_source(&document, &GpuType::Nvidia, Some("nvidia-smi")), Ok(Some("nvidia-smi")));
+
+    let invalid = config::parse("[backend]\nnvidia = 3\n").unwrap();
+    assert_eq!(forced_source(&invalid, &GpuType::Nvidia, None), Err("backend.nvidia must be a string".to_string()));
+}
+
+#[test]
+fn falls_back_to_another_source() {
+    let runner = MockRunner::new().with(
+        "nvidia-smi",
+        &["--query-gpu=index,utilization.gpu,memory.used,memory.total,temperature.gpu,power.draw,utilization.memory,pci.bus_id", "--format=csv,noheader,nounits"],
+        CommandOutput::ok("0, 45, 1024, 24576, 60, 120.50\n"),
+    );
+
+    let (backend, _) = fallback(&runner, &GpuType::Nvidia, "nvidia-smi:stream", Duration::from_secs(1), Retry::ONCE, &[gpu()]).unwrap();
+
+    assert_eq!(backend.source(), "nvidia-smi");
+}
+
+#[test]
+fn the_source_change_names_both_sources() {
+    let kind = EventKind::SourceChanged { from: "nvidia-smi".to_string(), to: "nvidia-smi:stream".to_string() };
+    let event = Event::new(&gpu(), alert::Severity::Warning, kind);
+
+    assert_eq!(event.message, "GPU 0 (NVIDIA GeForce RTX 3090) is now sampled from nvidia-smi:stream (nvidia-smi stopped working)");
+    assert!(event.format_text().ends_with(" [SOURCE warning] GPU 0 (NVIDIA GeForce RTX 3090) is now sampled from nvidia-smi:stream (nvidia-smi stopped working)"));
+}
+
+#[test]
+fn samples_and_the_banner_name_the_source() {
+    let output = run("source", &["--format", "ndjson", "--count", "1", "--backend", "nvidia-smi"], None);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout.contains(",\"source\":\"nvidia-smi\","), "{}", stdout);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("GPU 0 (NVIDIA GeForce RTX 3090): sampled from nvidia-smi\n"));
+}
+
+#[test]
+fn rejects_a_source_for_another_vendor() {
+    let output = run("source-vendor", &["--count", "1", "--backend", "radeontop"], None);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("Error: The radeontop backend cannot monitor this GPU\n"));
+
+    let output = run("source-config", &["--count", "1"], Some("[backend]\nnvidia = \"amd-smi\"\n"));
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run("source-unknown", &["--backend", "nvml"], None);
+    assert_eq!(output.status.code(), Some(2));
+}
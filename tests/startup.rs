@@ -0,0 +1,83 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::startup::timed;
+
+/// Fake tools whose nvidia-smi takes `delay` seconds on every call, like a driver waking up.
+fn slow_tools(name: &str, delay: &str) -> PathBuf {
+    let dir = common::fake_tools(name);
+    fs::rename(dir.join("nvidia-smi"), dir.join("nvidia-smi.awake")).unwrap();
+    let script = format!("#!/bin/sh\nsleep {}\nexec {} \"$@\"\n", delay, dir.join("nvidia-smi.awake").display());
+    fs::write(dir.join("nvidia-smi"), script).unwrap();
+    fs::set_permissions(dir.join("nvidia-smi"), fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+fn gpuatop(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(dir))
+        .env("XDG_RUNTIME_DIR", dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn the_notice_is_only_printed_for_slow_queries() {
+    let noticed = AtomicBool::new(false);
+    let (result, took) = timed(Duration::from_millis(50), || noticed.store(true, Ordering::Relaxed), || 42);
+    assert_eq!(result, 42);
+    assert!(took < Duration::from_millis(50));
+    assert!(!noticed.load(Ordering::Relaxed));
+
+    let (_, took) = timed(Duration::from_millis(50), || noticed.store(true, Ordering::Relaxed), || thread::sleep(Duration::from_millis(150)));
+    assert!(took >= Duration::from_millis(150));
+    assert!(noticed.load(Ordering::Relaxed));
+}
+
+#[test]
+fn a_slow_driver_wake_up_is_reported() {
+    let dir = slow_tools("startup-slow", "0.6");
+    let output = gpuatop(&dir, &["--count", "1"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stderr.contains("Waiting for the first sample..."), "{}", stderr);
+    assert!(stdout.contains("GPU driver wake-up took "), "{}", stdout);
+    assert!(stdout.contains("; persistence mode avoids it (gpuatop fix-persistence)"), "{}", stdout);
+    assert!(stdout.contains("Utilization (percent): 45.0"), "{}", stdout);
+}
+
+#[test]
+fn max_startup_wait_prints_n_a_quickly() {
+    let dir = slow_tools("startup-max-wait", "3");
+    let started = Instant::now();
+    let output = gpuatop(&dir, &["-q", "--count", "1", "--max-startup-wait", "300ms"]);
+    let took = started.elapsed();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "n/a\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(took < Duration::from_secs(2), "took {:?}", took);
+}
+
+#[test]
+fn max_startup_wait_leaves_a_prompt_sample_alone() {
+    let dir = common::fake_tools("startup-prompt");
+    let output = gpuatop(&dir, &["-q", "--count", "1", "--max-startup-wait", "10s", "--output-fields", "util"]);
+    let follow = gpuatop(&dir, &["--count", "2", "--max-startup-wait", "1s"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "GPU 0 (NVIDIA GeForce RTX 3090) Utilization (percent): 45.0\n");
+    assert!(String::from_utf8_lossy(&follow.stderr).contains("Error: --max-startup-wait requires --count 1"));
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::statsd::{format_metrics, parse_prefix, sanitize, StatsdOptions};
+use gpu_auto_top::{GpuSnapshot, UsageSplit};
+
+fn snapshot() -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(1024), temperature_c: Some(60.0), ..common::snapshot(1, "NVIDIA GeForce RTX 3090", 45.3) }
+}
+
+fn context(hostname: Option<&str>, labels: &str) -> OutputContext {
+    OutputContext { format: OutputFormat::Statsd, hostname: hostname.map(str::to_string), labels: labels.parse::<Labels>().unwrap(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+#[test]
+fn writes_plain_gauges_without_tags() {
+    let lines = format_metrics(&snapshot(), &StatsdOptions::default(), &context(None, ""));
+
+    assert_eq!(lines, "gpuatop.gpu1.utilization:45.3|g\ngpuatop.gpu1.memory_used_mib:1024|g\ngpuatop.gpu1.temperature_c:60|g");
+}
+
+#[test]
+fn includes_the_optional_metric_groups() {
+    let snapshot = GpuSnapshot { usage_split: Some(UsageSplit { desktop: 5.0, apps: 40.3 }), ..snapshot() };
+
+    let lines = format_metrics(&snapshot, &StatsdOptions::default(), &context(None, ""));
+
+    assert!(lines.ends_with("\ngpuatop.gpu1.desktop_utilization:5|g\ngpuatop.gpu1.apps_utilization:40.3|g"), "{}", lines);
+}
+
+#[test]
+fn tags_are_written_dogstatsd_style() {
+    let options = StatsdOptions { prefix: "gpuatop".to_string(), tags: "env=prod,rack=a1".parse().unwrap() };
+
+    let lines = format_metrics(&snapshot(), &options, &context(Some("Node1"), "rack=b2,team=ml"));
+
+    assert!(
+        lines.starts_with("gpuatop.gpu1.utilization:45.3|g|#host:node1,env:prod,rack:a1,team:ml,gpu_name:nvidia_geforce_rtx_3090\n"),
+        "{}",
+        lines
+    );
+}
+
+#[test]
+fn sanitizes_names() {
+    assert_eq!(sanitize("NVIDIA GeForce RTX 3090"), "nvidia_geforce_rtx_3090");
+    assert_eq!(sanitize("a:b|c@d#e,f"), "a_b_c_d_e_f");
+}
+
+#[test]
+fn parses_prefixes() {
+    assert_eq!(parse_prefix("My Lab.GPUs"), Ok("my_lab.gpus".to_string()));
+    assert!(parse_prefix("lab..gpus").is_err());
+    assert!(parse_prefix("").is_err());
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::syslog::{format_timestamp, sample_params, Facility, Message, Severity};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot() -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(2048), memory_total_mib: Some(40960), power_w: Some(250.0), ..common::snapshot(1, "NVIDIA A100", 42.5) }
+}
+
+fn message(params: Vec<(String, String)>) -> Message<'static> {
+    Message {
+        facility: Facility::default(),
+        severity: Severity::Info,
+        timestamp: UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+        hostname: Some("node1"),
+        procid: 4242,
+        msgid: "sample",
+        params,
+        text: "GPU 1 utilization 42.5%",
+    }
+}
+
+#[test]
+fn timestamps_are_rfc3339_utc() {
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)), "2023-11-14T22:13:20.123Z");
+    assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00.000Z");
+}
+
+#[test]
+fn facilities_parse_by_name() {
+    assert_eq!("daemon".parse::<Facility>(), Ok(Facility::default()));
+    assert!("local7".parse::<Facility>().is_ok());
+    assert!("local8".parse::<Facility>().is_err());
+    assert!("bogus".parse::<Facility>().is_err());
+}
+
+#[test]
+fn sample_message_has_structured_data() {
+    let params = sample_params(&snapshot(), &Labels::default());
+    assert_eq!(
+        message(params).format(),
+        "<30>1 2023-11-14T22:13:20.123Z node1 gpuatop 4242 sample \
+         [gpuatop@32473 gpu=\"1\" name=\"NVIDIA A100\" utilization=\"42.5\" memory_used_mib=\"2048\" memory_total_mib=\"40960\" power_w=\"250\"] \
+         GPU 1 utilization 42.5%"
+    );
+}
+
+#[test]
+fn priority_combines_facility_and_severity() {
+    let mut alert = message(Vec::new());
+    alert.facility = "local0".parse().unwrap();
+    alert.severity = Severity::Warning;
+    assert!(alert.format().starts_with("<132>1 "));
+}
+
+#[test]
+fn missing_fields_use_nil_values() {
+    let mut message = message(Vec::new());
+    message.hostname = None;
+    assert_eq!(message.format(), "<30>1 2023-11-14T22:13:20.123Z - gpuatop 4242 sample - GPU 1 utilization 42.5%");
+}
+
+#[test]
+fn labels_are_appended_and_escaped() {
+    let labels: Labels = "rack=a\"]\\b".parse().unwrap();
+    let params = sample_params(&snapshot(), &labels);
+    let formatted = message(params).format();
+    assert!(formatted.contains(" rack=\"a\\\"\\]\\\\b\"]"), "{}", formatted);
+}
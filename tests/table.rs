@@ -0,0 +1,93 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::display::table::{Align, Column, Table, Width};
+use gpu_auto_top::stats::Statistics;
+use gpu_auto_top::GpuSnapshot;
+
+fn table() -> Table {
+    let mut table = Table::new(vec![Column::new("GPU"), Column::new("Name"), Column::new("Util").align(Align::Right)]);
+    table.row(vec!["0".to_string(), "NVIDIA A100-SXM4-80GB".to_string(), "45.0%".to_string()]);
+    table.row(vec!["1".to_string(), "Tesla T4".to_string(), "100.0%".to_string()]);
+    table
+}
+
+#[test]
+fn columns_fit_their_content() {
+    assert_eq!(
+        table().render(),
+        ["GPU  Name                     Util", "0    NVIDIA A100-SXM4-80GB   45.0%", "1    Tesla T4               100.0%"]
+    );
+}
+
+#[test]
+fn borders_draw_an_ascii_grid() {
+    assert_eq!(
+        table().borders(true).render(),
+        [
+            "+-----+-----------------------+--------+",
+            "| GPU | Name                  |   Util |",
+            "+-----+-----------------------+--------+",
+            "| 0   | NVIDIA A100-SXM4-80GB |  45.0% |",
+            "| 1   | Tesla T4              | 100.0% |",
+            "+-----+-----------------------+--------+",
+        ]
+    );
+}
+
+#[test]
+fn box_drawing_replaces_the_ascii_borders() {
+    assert_eq!(
+        table().borders(true).box_drawing(true).render(),
+        [
+            "┌─────┬───────────────────────┬────────┐",
+            "│ GPU │ Name                  │   Util │",
+            "├─────┼───────────────────────┼────────┤",
+            "│ 0   │ NVIDIA A100-SXM4-80GB │  45.0% │",
+            "│ 1   │ Tesla T4              │ 100.0% │",
+            "└─────┴───────────────────────┴────────┘",
+        ]
+    );
+}
+
+#[test]
+fn limited_widths_cut_long_cells() {
+    let mut table = Table::new(vec![Column::new("Name").width(Width::Max(10)), Column::new("PID").width(Width::Fixed(5))]).without_header().indent(2);
+    table.row(vec!["NVIDIA A100-SXM4-80GB".to_string(), "4242".to_string()]);
+    table.row(vec!["T4".to_string()]);
+
+    assert_eq!(table.render(), ["  NVIDIA A1…  4242", "  T4"]);
+}
+
+fn record(statistics: &mut Statistics, utilization: f64) {
+    statistics.record(&GpuSnapshot { memory_used_mib: Some(1024), memory_total_mib: Some(15360), power_w: Some(35.5), ..common::snapshot(0, "Tesla T4", utilization) });
+}
+
+#[test]
+fn the_summary_is_a_table() {
+    let mut statistics = Statistics::default();
+    for utilization in [20.0, 70.0] {
+        record(&mut statistics, utilization);
+    }
+
+    assert_eq!(
+        statistics.format_summary(1),
+        [
+            "Summary:",
+            "  GPU  Name      Samples  Min util  Avg util  Max util  Peak memory  Max temp  Max power",
+            "  0    Tesla T4        2     20.0%     45.0%     70.0%     1024 MiB         -     35.5 W",
+        ]
+    );
+}
+
+#[test]
+fn the_summary_rounds_utilization_to_the_precision() {
+    let mut statistics = Statistics::default();
+    for utilization in [12.344, 87.656] {
+        record(&mut statistics, utilization);
+    }
+
+    assert_eq!(statistics.format_summary(0)[2], "  0    Tesla T4        2       12%       50%       88%     1024 MiB         -     35.5 W");
+    assert_eq!(statistics.format_summary(2)[2], "  0    Tesla T4        2    12.34%    50.00%    87.66%     1024 MiB         -     35.5 W");
+}
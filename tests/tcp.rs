@@ -0,0 +1,117 @@
+#![cfg(feature = "network")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Lines};
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+use std::time::Duration;
+
+use gpu_auto_top::tcp::{parse_buffer_size, parse_target, Backoff, Outbox, TcpSender, HANDSHAKE, MAX_BACKOFF};
+
+fn accept(listener: &TcpListener) -> Lines<BufReader<TcpStream>> {
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    BufReader::new(stream).lines()
+}
+
+fn next(lines: &mut Lines<BufReader<TcpStream>>) -> String {
+    lines.next().expect("a line").unwrap()
+}
+
+#[test]
+fn targets_need_a_host_and_a_port() {
+    assert_eq!(parse_target("logger:9000"), Ok("logger:9000".to_string()));
+    assert_eq!(parse_target("logger"), Err("Invalid --send-to-tcp address: logger (expected host:port)".to_string()));
+    assert!(parse_target("logger:0").is_err());
+    assert_eq!(parse_buffer_size("500"), Ok(500));
+    assert_eq!(parse_buffer_size("0"), Err("Invalid --tcp-buffer-size value: 0".to_string()));
+}
+
+#[test]
+fn backoff_doubles_up_to_a_minute() {
+    let mut backoff = Backoff::default();
+    let delays: Vec<u64> = (0..9).map(|_| backoff.fail().as_secs()).collect();
+
+    assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60, 60]);
+    assert_eq!(MAX_BACKOFF, Duration::from_secs(60));
+
+    backoff.reset();
+    assert_eq!(backoff.fail(), Duration::from_secs(1));
+}
+
+#[test]
+fn a_full_outbox_drops_the_oldest_records() {
+    let mut outbox = Outbox::new(2);
+    for record in ["a", "b", "c"] {
+        outbox.push(record.to_string());
+    }
+
+    assert_eq!(outbox.dropped(), 1);
+    assert_eq!(outbox.pop().as_deref(), Some("b"));
+
+    // A record that failed to send goes back to the front while there is room.
+    outbox.requeue("b".to_string());
+    assert_eq!(outbox.len(), 2);
+    outbox.requeue("a".to_string());
+    assert_eq!(outbox.dropped(), 2);
+    assert_eq!(outbox.pop().as_deref(), Some("b"));
+    assert_eq!(outbox.pop().as_deref(), Some("c"));
+    assert!(outbox.is_empty());
+}
+
+#[test]
+fn streams_start_with_the_handshake() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let sender = TcpSender::new(&listener.local_addr().unwrap().to_string(), 10, false).unwrap();
+    sender.send("{\"gpu_index\":0}");
+    sender.send("{\"gpu_index\":1}");
+
+    let mut lines = accept(&listener);
+    assert_eq!(next(&mut lines), HANDSHAKE);
+    assert_eq!(next(&mut lines), "{\"gpu_index\":0}");
+    assert_eq!(next(&mut lines), "{\"gpu_index\":1}");
+}
+
+#[test]
+fn records_sent_while_the_server_is_down_arrive_after_it_returns() {
+    // Reserve a port nobody listens on yet.
+    let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let sender = TcpSender::new(&address.to_string(), 10, false).unwrap();
+    sender.send("{\"gpu_index\":0}");
+    std::thread::sleep(Duration::from_millis(200));
+    sender.send("{\"gpu_index\":1}");
+
+    let listener = TcpListener::bind(address).unwrap();
+    let mut lines = accept(&listener);
+    assert_eq!(next(&mut lines), HANDSHAKE);
+    assert_eq!(next(&mut lines), "{\"gpu_index\":0}");
+    assert_eq!(next(&mut lines), "{\"gpu_index\":1}");
+}
+
+#[test]
+fn send_to_tcp_streams_ndjson() {
+    let dir = common::fake_tools("tcp");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let sent = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["-q", "--count", "1", "--send-to-tcp", &listener.local_addr().unwrap().to_string()])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(sent.status.success(), "{}", String::from_utf8_lossy(&sent.stderr));
+
+    let mut lines = accept(&listener);
+    assert_eq!(next(&mut lines), HANDSHAKE);
+    let record = next(&mut lines);
+    assert!(record.starts_with('{') && record.contains("\"utilization\":45"), "{}", record);
+}
+
+#[test]
+fn tcp_buffer_size_requires_send_to_tcp() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--tcp-buffer-size", "10"]).output().unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: --tcp-buffer-size requires --send-to-tcp"));
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use gpu_auto_top::alert::{parse_severities, parse_temperature_threshold, rules, AlertKind, AlertTracker, Severity};
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{format_snapshot, parse_fields, Field, FieldSet, OutputContext, OutputFormat};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::temperature::{hottest, parse_nvidia, query_nvidia, read_device, read_hwmon, Sensor, Temperatures, NVIDIA_QUERY};
+use gpu_auto_top::GpuSnapshot;
+
+fn fixture(card: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/amdgpu").join(card)
+}
+
+fn snapshot(temperatures: Option<Temperatures>) -> GpuSnapshot {
+    GpuSnapshot { temperature_c: Some(61.0), temperatures, ..common::snapshot(0, "AMD Radeon RX 7900 XTX", 97.0) }
+}
+
+fn context(format: OutputFormat) -> OutputContext {
+    OutputContext { format, hostname: None, labels: Labels::default(), tick_seq: None, timestamp: None, precision: 1 }
+}
+
+fn gpuatop(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(dir))
+        .env("XDG_RUNTIME_DIR", dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn reads_the_labelled_hwmon_sensors_of_amdgpu_cards() {
+    let rx6800 = read_device(&fixture("rx6800"));
+    let rx7900xtx = read_hwmon(&fixture("rx7900xtx").join("hwmon/hwmon4"));
+
+    assert_eq!(rx6800, [(Sensor::Edge, 54.0), (Sensor::Junction, 67.0), (Sensor::Mem, 62.0)].into());
+    assert_eq!(rx7900xtx, [(Sensor::Edge, 61.0), (Sensor::Junction, 88.0), (Sensor::Mem, 74.0)].into());
+    assert_eq!(hottest(&rx7900xtx), Some(88.0));
+    assert!(read_device(&fixture("missing")).is_empty());
+}
+
+#[test]
+fn parses_the_nvidia_core_and_memory_sensors() {
+    let temperatures = parse_nvidia("0, 45, 71\n1, 38, [N/A]\n2, [N/A], [N/A]\n");
+
+    assert_eq!(temperatures.len(), 2);
+    assert_eq!(temperatures[&0], [(Sensor::Gpu, 45.0), (Sensor::Mem, 71.0)].into());
+    assert_eq!(temperatures[&1], [(Sensor::Gpu, 38.0)].into());
+
+    let runner = MockRunner::new().with("nvidia-smi", &[NVIDIA_QUERY, "--format=csv,noheader,nounits"], CommandOutput::failed(2, "Field \"temperature.memory\" is not a valid field to query."));
+    assert!(query_nvidia(&runner).is_empty());
+}
+
+#[test]
+fn text_shows_the_hottest_sensor_and_the_breakdown() {
+    let temperatures: Temperatures = [(Sensor::Edge, 61.0), (Sensor::Junction, 88.0), (Sensor::Mem, 74.0)].into();
+
+    let plain = format_snapshot(&snapshot(None), &context(OutputFormat::Text));
+    let text = format_snapshot(&snapshot(Some(temperatures.clone())), &context(OutputFormat::Text));
+    let json = format_snapshot(&snapshot(Some(temperatures.clone())), &context(OutputFormat::Json));
+    let trimmed = format_snapshot(&FieldSet::UTIL.apply(&snapshot(Some(temperatures))), &context(OutputFormat::Json));
+
+    assert!(plain.ends_with("Temperature: 61°C"), "{}", plain);
+    assert!(text.ends_with("Temperature: 88°C (edge 61°C, junction 88°C, mem 74°C)"), "{}", text);
+    assert!(json.ends_with(",\"temperature_c\":61,\"temperatures\":{\"edge\":61,\"junction\":88,\"mem\":74}}"), "{}", json);
+    assert!(!trimmed.contains("temperatures"), "{}", trimmed);
+    assert_eq!(parse_fields("temps"), Ok(vec![Field::Temps]));
+}
+
+#[test]
+fn alerts_can_watch_one_sensor() {
+    assert_eq!(parse_temperature_threshold("95"), Ok((None, 95.0)));
+    assert_eq!(parse_temperature_threshold("junction=105"), Ok((Some(Sensor::Junction), 105.0)));
+    assert!(parse_temperature_threshold("hbm=95").is_err());
+    assert_eq!(parse_severities("temp.junction=warning"), Ok(vec![(AlertKind::SensorTemperature(Sensor::Junction), Severity::Warning)]));
+
+    let mut tracker = AlertTracker::new(rules(Some(85.0), &[(Sensor::Junction, 105.0)], None, None, &[]));
+    let cool = tracker.update(&snapshot(Some([(Sensor::Edge, 70.0), (Sensor::Junction, 95.0)].into())));
+    let hot = tracker.update(&snapshot(Some([(Sensor::Edge, 72.0), (Sensor::Junction, 108.0)].into())));
+
+    assert!(cool.fired.is_empty());
+    assert_eq!(hot.fired.len(), 1);
+    assert_eq!(hot.fired[0].kind.metric(), "temp.junction");
+    assert_eq!(hot.fired[0].message(), "GPU 0 (AMD Radeon RX 7900 XTX) junction temperature 108°C exceeds 105°C");
+}
+
+#[test]
+fn the_flag_queries_the_sensors_and_alerts_read_them() {
+    let dir = common::fake_tools("temperature");
+
+    let fields = gpuatop(&dir, &["-q", "--count", "1", "--fields", "temps"]);
+    let alert = gpuatop(&dir, &["-q", "--count", "1", "--format", "ndjson", "--alert-temp", "mem=75"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&fields.stdout);
+    assert!(stdout.contains("Temperature: 78°C (gpu 60°C, mem 78°C)"), "{}", stdout);
+
+    let stdout = String::from_utf8_lossy(&alert.stdout);
+    assert!(stdout.contains("\"metric\":\"temp.mem\",\"threshold\":75,\"value\":78"), "{}", stdout);
+    assert!(!stdout.contains("\"temperatures\""), "{}", stdout);
+}
+
+#[test]
+fn an_old_driver_skips_the_memory_sensor_with_a_warning() {
+    let dir = common::fake_tools("temperature-old-driver");
+    // nvidia-smi of a 450 driver, which fails the whole query over temperature.memory.
+    let fake = "#!/bin/sh
+case \"$*\" in
+  --version) printf 'NVIDIA-SMI version  : 450.80.02\\nDRIVER version      : 450.80.02\\n' ;;
+  *temperature.memory*) echo 'Field \"temperature.memory\" is not a valid field to query.'; exit 2 ;;
+  *utilization.gpu*) echo '0, 45, 1024, 24576, 60, 120.50, [N/A], 00000000:3B:00.0' ;;
+  *pci.bus_id*) echo '0, 00000000:3B:00.0, Tesla V100-SXM2-32GB' ;;
+  *) exit 1 ;;
+esac
+";
+    std::fs::write(dir.join("nvidia-smi"), fake).unwrap();
+
+    let output = gpuatop(&dir, &["--count", "2", "--interval", "100ms", "--display-interval", "100ms", "--allow-fast-poll", "--fields", "temps"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(stdout.contains("Warning: --fields temps and --alert-temp sensor=... need NVIDIA driver 460 or newer, 450.80.02 is installed; they are skipped\n"), "{}", stdout);
+    assert_eq!(stdout.matches("Temperature: 60°C, Power").count(), 2, "{}", stdout);
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use gpu_auto_top::template::{is_template, Template, TemplateError};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot() -> GpuSnapshot {
+    GpuSnapshot { memory_used_mib: Some(1024), memory_total_mib: Some(24576), power_w: Some(120.5), ..common::snapshot(0, "NVIDIA GeForce RTX 3090", 7.5) }
+}
+
+fn render(template: &str) -> String {
+    Template::parse(template).unwrap().render(&snapshot())
+}
+
+fn error(template: &str) -> TemplateError {
+    Template::parse(template).unwrap_err()
+}
+
+#[test]
+fn renders_the_example_template() {
+    assert_eq!(render("gpu{index}: {util:>3}% {mem_used}/{mem_total}MiB {temp|--}°C"), "gpu0: 7.5% 1024/24576MiB --°C");
+}
+
+#[test]
+fn literal_text_is_kept() {
+    assert_eq!(render("GPU status"), "GPU status");
+    assert_eq!(render(""), "");
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    assert_eq!(render("{{{index}}}"), "{0}");
+    assert_eq!(render("{{util}}"), "{util}");
+}
+
+#[test]
+fn sample_field_names_and_aliases_are_accepted() {
+    assert_eq!(render("{utilization}|{util}"), "7.5|7.5");
+    assert_eq!(render("{memory_used_mib}|{mem_used}"), "1024|1024");
+    assert_eq!(render("{power_w}|{power}"), "120.5|120.5");
+}
+
+#[test]
+fn missing_fields_render_as_na() {
+    assert_eq!(render("{temp}°C"), "n/a°C");
+    assert_eq!(render("{bus_id}"), "n/a");
+}
+
+#[test]
+fn defaults_replace_missing_fields_only() {
+    assert_eq!(render("{temp|--}"), "--");
+    assert_eq!(render("{power|--}"), "120.5");
+    assert_eq!(render("{temp|}"), "");
+}
+
+#[test]
+fn numbers_align_right_and_text_left_by_default() {
+    assert_eq!(render("[{index:3}]"), "[  0]");
+    assert_eq!(render("[{name:25}]"), "[NVIDIA GeForce RTX 3090  ]");
+}
+
+#[test]
+fn explicit_alignment_and_fill() {
+    assert_eq!(render("[{util:<6}]"), "[7.5   ]");
+    assert_eq!(render("[{util:>6}]"), "[   7.5]");
+    assert_eq!(render("[{util:^7}]"), "[  7.5  ]");
+    assert_eq!(render("[{index:0>3}]"), "[000]");
+    assert_eq!(render("[{index:*^5}]"), "[**0**]");
+}
+
+#[test]
+fn width_never_truncates() {
+    assert_eq!(render("{mem_total:2}"), "24576");
+}
+
+#[test]
+fn precision_rounds_floats() {
+    assert_eq!(render("{util:.0}"), "8");
+    assert_eq!(render("{util:.2}"), "7.50");
+    assert_eq!(render("{util:>6.1}"), "   7.5");
+}
+
+#[test]
+fn precision_is_ignored_for_integers_and_truncates_text() {
+    assert_eq!(render("{mem_used:.2}"), "1024");
+    assert_eq!(render("{name:.6}"), "NVIDIA");
+}
+
+#[test]
+fn defaults_are_padded() {
+    assert_eq!(render("[{temp|--:>4}]"), "[  --]");
+    assert_eq!(render("[{temp:>4}]"), "[ n/a]");
+}
+
+#[test]
+fn multibyte_text_is_padded_by_characters() {
+    assert_eq!(render("{index}°{index:>2}"), "0° 0");
+}
+
+#[test]
+fn unknown_field_points_at_the_name() {
+    let template = "gpu{index}: {utilisation}%";
+    let err = error(template);
+
+    assert_eq!(err.message, "Unknown field 'utilisation'");
+    assert_eq!(err.position, 13);
+    assert_eq!(err.with_caret(template), "Unknown field 'utilisation'\n  gpu{index}: {utilisation}%\n               ^");
+}
+
+#[test]
+fn unclosed_brace_points_at_the_brace() {
+    let err = error("gpu {index");
+
+    assert!(err.message.starts_with("Unclosed '{'"));
+    assert_eq!(err.position, 4);
+}
+
+#[test]
+fn unmatched_closing_brace_is_an_error() {
+    let err = error("gpu} {index}");
+
+    assert!(err.message.starts_with("Unmatched '}'"));
+    assert_eq!(err.position, 3);
+}
+
+#[test]
+fn empty_placeholder_is_an_error() {
+    let err = error("a{}");
+
+    assert_eq!(err.message, "Expected a field name");
+    assert_eq!(err.position, 2);
+}
+
+#[test]
+fn invalid_spec_points_at_the_bad_character() {
+    let err = error("{util:>3x}");
+    assert_eq!(err.message, "Unexpected 'x' in format spec");
+    assert_eq!(err.position, 8);
+
+    let err = error("{util:.}");
+    assert_eq!(err.message, "Expected a precision after '.'");
+    assert_eq!(err.position, 7);
+}
+
+#[test]
+fn positions_count_characters_not_bytes() {
+    assert_eq!(error("°C {nope}").position, 4);
+}
+
+#[test]
+fn format_names_are_not_templates() {
+    assert!(!is_template("json"));
+    assert!(is_template("{util}"));
+    assert!(is_template("}}"));
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::terminal::{format_title, tmux_passthrough, Bell, TitleSetter, BELL_INTERVAL};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot(index: u32, utilization: f64, temperature_c: Option<f32>) -> GpuSnapshot {
+    GpuSnapshot { temperature_c, ..common::snapshot(index, "NVIDIA GeForce RTX 3090", utilization) }
+}
+
+#[test]
+fn the_bell_rings_when_the_expression_trips() {
+    let mut bell = Bell::new("temp>90".parse().unwrap());
+    let start = Instant::now();
+    let hot = [snapshot(0, 50.0, Some(60.0)), snapshot(1, 50.0, Some(95.0))];
+    let cool = [snapshot(0, 50.0, Some(60.0)), snapshot(1, 50.0, Some(70.0))];
+
+    assert!(!bell.update(&cool, start));
+    assert!(bell.update(&hot, start));
+    // Still holding: no second bell.
+    assert!(!bell.update(&hot, start + Duration::from_secs(60)));
+}
+
+#[test]
+fn a_flapping_expression_rings_at_most_every_interval() {
+    let mut bell = Bell::new("util>=90 and temp>80".parse().unwrap());
+    let start = Instant::now();
+    let busy = [snapshot(0, 95.0, Some(85.0))];
+    let idle = [snapshot(0, 5.0, Some(85.0))];
+
+    assert!(bell.update(&busy, start));
+    assert!(!bell.update(&idle, start + Duration::from_secs(1)));
+    assert!(!bell.update(&busy, start + Duration::from_secs(2)));
+    assert!(!bell.update(&idle, start + Duration::from_secs(3)));
+    assert!(bell.update(&busy, start + BELL_INTERVAL));
+}
+
+#[test]
+fn titles_show_each_gpu() {
+    assert_eq!(format_title(&[snapshot(0, 42.4, Some(61.0)), snapshot(1, 3.0, None)]), "gpu0 42% 61°C | gpu1 3%");
+}
+
+#[test]
+fn titles_use_osc_0_and_pass_through_tmux() {
+    let plain = TitleSetter::new(false);
+    assert_eq!(plain.title("gpu0 42%\x07\x1b]"), "\x1b]0;gpu0 42%]\x07");
+    assert_eq!(plain.save(), "\x1b[22;0t");
+    assert_eq!(plain.restore(), "\x1b[23;0t");
+
+    assert_eq!(TitleSetter::new(true).title("gpu0 42%"), "\x1bPtmux;\x1b\x1b]0;gpu0 42%\x07\x1b\\");
+    assert_eq!(tmux_passthrough("\x07"), "\x1bPtmux;\x07\x1b\\");
+}
+
+#[test]
+fn nothing_is_written_when_stdout_is_not_a_terminal() {
+    let dir = common::fake_tools("terminal");
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["-q", "--count", "1", "--set-title", "--bell-on", "util>10"])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("45"), "{}", stdout);
+    assert!(!stdout.contains('\x1b') && !stdout.contains('\x07'), "{:?}", stdout);
+}
+
+#[test]
+fn bell_on_rejects_invalid_expressions() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--bell-on", "heat>90"]).output().unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: Unknown metric: heat"), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::time::{Duration, UNIX_EPOCH};
+
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{format_iso8601, format_snapshot, OutputContext, OutputFormat, Timestamp, TimestampFormat};
+use gpu_auto_top::GpuSnapshot;
+
+fn snapshot() -> GpuSnapshot {
+    common::snapshot(0, "NVIDIA A100-SXM4-80GB", 45.0)
+}
+
+fn context(format: OutputFormat, timestamp: Option<Timestamp>) -> OutputContext {
+    OutputContext { format, hostname: Some("node1".to_string()), labels: Labels::default(), tick_seq: Some(2), timestamp, precision: 1 }
+}
+
+fn gpuatop(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(args)
+        .env("PATH", common::path_with(dir))
+        .env("XDG_RUNTIME_DIR", dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn stamps_a_tick_in_every_format() {
+    let now = UNIX_EPOCH + Duration::from_millis(1_698_765_432_123);
+    let since_start = Duration::from_millis(2500);
+    let stamp = |format: &str| format.parse::<TimestampFormat>().unwrap().stamp(now, since_start).map(|timestamp| timestamp.to_string());
+
+    assert_eq!(stamp("iso8601").as_deref(), Some("2023-10-31T15:17:12Z"));
+    assert_eq!(stamp("unix").as_deref(), Some("1698765432"));
+    assert_eq!(stamp("unix-ms").as_deref(), Some("1698765432123"));
+    assert_eq!(stamp("relative").as_deref(), Some("2.500"));
+    assert_eq!(stamp("none"), None);
+    assert_eq!(TimestampFormat::default(), TimestampFormat::Iso8601);
+    assert!("rfc3339".parse::<TimestampFormat>().is_err());
+
+    assert_eq!(format_iso8601(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(format_iso8601(UNIX_EPOCH + Duration::from_secs(951_827_696)), "2000-02-29T12:34:56Z");
+}
+
+#[test]
+fn json_has_a_string_or_numeric_ts() {
+    let iso = format_snapshot(&snapshot(), &context(OutputFormat::Ndjson, Some(Timestamp::Iso8601("2023-10-31T15:17:12Z".to_string()))));
+    let unix = format_snapshot(&snapshot(), &context(OutputFormat::Ndjson, Some(Timestamp::Unix(1_698_765_432))));
+    let relative = format_snapshot(&snapshot(), &context(OutputFormat::Ndjson, Some(Timestamp::Relative(Duration::from_millis(12_050)))));
+    let none = format_snapshot(&snapshot(), &context(OutputFormat::Ndjson, None));
+
+    assert!(iso.ends_with(",\"tick_seq\":2,\"ts\":\"2023-10-31T15:17:12Z\"}"), "{}", iso);
+    assert!(unix.ends_with(",\"tick_seq\":2,\"ts\":1698765432}"), "{}", unix);
+    assert!(relative.ends_with(",\"ts\":12.050}"), "{}", relative);
+    assert!(!none.contains("\"ts\""), "{}", none);
+}
+
+#[test]
+fn text_lines_start_with_the_timestamp() {
+    let line = format_snapshot(&snapshot(), &context(OutputFormat::Text, Some(Timestamp::Unix(1_698_765_432))));
+
+    assert_eq!(line, "1698765432 [node1] GPU 0 (NVIDIA A100-SXM4-80GB) Utilization (percent): 45.0");
+}
+
+#[test]
+fn the_flag_stamps_stdout_and_the_log_file() {
+    let dir = common::fake_tools("timestamp-flag");
+    let log = dir.join("gpuatop.log");
+
+    let ndjson = gpuatop(&dir, &["-q", "--count", "1", "--format", "ndjson", "--timestamp-format", "unix"]);
+    let text = gpuatop(&dir, &["-q", "--count", "1", "--timestamp-format", "relative", "--log-file", log.to_str().unwrap()]);
+    let logged = fs::read_to_string(&log).unwrap();
+    let influx = gpuatop(&dir, &["--count", "1", "--format", "influx", "--timestamp-format", "unix"]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&ndjson.stdout);
+    let ts = stdout.trim_end().rsplit("\"ts\":").next().unwrap().trim_end_matches('}');
+    assert!(ts.parse::<u64>().is_ok(), "{}", stdout);
+
+    let stdout = String::from_utf8_lossy(&text.stdout);
+    let line = stdout.lines().find(|line| line.contains("Utilization")).unwrap();
+    assert!(line.split(' ').next().unwrap().parse::<f64>().is_ok(), "{}", line);
+    assert!(logged.contains(line), "{}", logged);
+
+    assert!(String::from_utf8_lossy(&influx.stderr).contains("--timestamp-format supports the text, ndjson, json and msgpack formats"));
+}
+
+#[test]
+fn text_log_files_default_to_iso8601() {
+    let dir = common::fake_tools("timestamp-log");
+    let stamped = dir.join("stamped.log");
+    let plain = dir.join("plain.log");
+
+    let default = gpuatop(&dir, &["-q", "--count", "1", "--log-file", stamped.to_str().unwrap()]);
+    gpuatop(&dir, &["-q", "--count", "1", "--timestamp-format", "none", "--log-file", plain.to_str().unwrap()]);
+    let (stamped, plain) = (fs::read_to_string(&stamped).unwrap(), fs::read_to_string(&plain).unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8_lossy(&default.stdout);
+    let line = stdout.lines().find(|line| line.contains("Utilization")).unwrap();
+    assert!(line.starts_with("GPU 0"), "{}", line);
+
+    let (time, logged) = stamped.lines().find(|line| line.contains("Utilization")).unwrap().split_once(' ').unwrap();
+    assert_eq!(logged, line);
+    assert!(time.len() == 20 && time.ends_with('Z') && time.as_bytes()[10] == b'T', "{}", time);
+    assert!(plain.lines().any(|logged| logged == line), "{}", plain);
+}
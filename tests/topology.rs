@@ -0,0 +1,124 @@
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::process::Command;
+
+use gpu_auto_top::json::{self, Value};
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::topology::{format_topology, parse_rocm_topology, parse_topology, query_topology, to_json, LinkType};
+use gpu_auto_top::{GpuInfo, GpuType};
+
+const NVIDIA_TOPO: &str = "\
+\x1b[4mGPU0\tGPU1\tGPU2\tNIC0\tCPU Affinity\tNUMA Affinity\tGPU NUMA ID\x1b[0m
+GPU0\t X \tNV12\tSYS\tPXB\t0-31\t0\t\tN/A
+GPU1\tNV12\t X \tSYS\tPXB\t0-31\t0\t\tN/A
+GPU2\tSYS\tSYS\t X \tPHB\t32-63\t1\t\tN/A
+NIC0\tPXB\tPXB\tPHB\t X \t\t\t\t
+
+Legend:
+
+  X    = Self
+  SYS  = Connection traversing PCIe as well as the SMP interconnect between NUMA nodes (e.g., QPI/UPI)
+  NV#  = Connection traversing a bonded set of # NVLinks
+";
+
+const ROCM_TOPO: &str = "\
+============================ ROCm System Management Interface ============================
+=============================== Weight between two GPUs ================================
+       GPU0         GPU1
+GPU0   0            15
+GPU1   15           0
+
+============================== Link Type between two GPUs ==============================
+       GPU0         GPU1
+GPU0   0            XGMI
+GPU1   XGMI         0
+
+====================================== Numa Nodes ======================================
+GPU[0]\t\t: (Topology) Numa Node: 0
+GPU[0]\t\t: (Topology) Numa Affinity: 0
+GPU[1]\t\t: (Topology) Numa Node: 1
+GPU[1]\t\t: (Topology) Numa Affinity: 1
+================================== End of ROCm SMI Log ===================================
+";
+
+#[test]
+fn parses_nvidia_links_without_nics() {
+    let matrix = parse_topology(NVIDIA_TOPO).unwrap();
+
+    assert_eq!(matrix.gpus.iter().map(|gpu| gpu.index).collect::<Vec<_>>(), [0, 1, 2]);
+    assert_eq!(matrix.links[0], [LinkType::Same, LinkType::NvLink(12), LinkType::SysMem]);
+    assert_eq!(matrix.links[2], [LinkType::SysMem, LinkType::SysMem, LinkType::Same]);
+    assert_eq!(matrix.cpu_affinity[2].as_deref(), Some("32-63"));
+    assert_eq!(matrix.numa_affinity[2].as_deref(), Some("1"));
+}
+
+#[test]
+fn pcie_codes_map_to_pcie() {
+    let matrix = parse_topology("GPU0\tGPU1\tCPU Affinity\nGPU0\t X \tPIX\t0-7\nGPU1\tPIX\t X \t0-7\n").unwrap();
+
+    assert_eq!(matrix.links[0][1], LinkType::Pcie);
+}
+
+#[test]
+fn short_nvidia_row_is_an_error() {
+    assert!(parse_topology("GPU0\tGPU1\tCPU Affinity\nGPU0\t X \n").is_err());
+}
+
+#[test]
+fn parses_rocm_smi_link_types() {
+    let matrix = parse_rocm_topology(ROCM_TOPO).unwrap();
+
+    assert_eq!(matrix.links, [[LinkType::Same, LinkType::Xgmi], [LinkType::Xgmi, LinkType::Same]]);
+    assert_eq!(matrix.numa_affinity[1].as_deref(), Some("1"));
+    assert_eq!(matrix.cpu_affinity[0], None);
+}
+
+#[test]
+fn query_names_gpus_from_enumeration() {
+    let runner = MockRunner::new().with("nvidia-smi", &["topo", "-m"], CommandOutput::ok(NVIDIA_TOPO));
+    let gpus = vec![GpuInfo { index: 1, name: "NVIDIA A100-SXM4-80GB".to_string(), bus_id: None, render_offload: None }];
+
+    let matrix = query_topology(&runner, &GpuType::Nvidia, &gpus).unwrap();
+
+    assert_eq!(matrix.gpus[0].name, "GPU0");
+    assert_eq!(matrix.gpus[1].name, "NVIDIA A100-SXM4-80GB");
+}
+
+#[test]
+fn query_is_unsupported_for_intel() {
+    assert!(query_topology(&MockRunner::new(), &GpuType::Intel, &[]).is_err());
+}
+
+#[test]
+fn table_shows_link_codes_and_legend() {
+    let table = format_topology(&parse_topology(NVIDIA_TOPO).unwrap());
+    let lines: Vec<&str> = table.lines().collect();
+
+    assert_eq!(lines[0], "      GPU0  GPU1  GPU2  CPU Affinity  NUMA Affinity");
+    assert_eq!(lines[1], "GPU0  X     NV12  SYS   0-31          0");
+    assert!(table.contains("Legend:\n  X    = Self\n"));
+}
+
+#[test]
+fn json_has_typed_links() {
+    let document = json::parse(&to_json(&parse_topology(NVIDIA_TOPO).unwrap())).unwrap();
+    let Value::Object(members) = document else { panic!("not an object") };
+
+    let Value::Array(links) = &members[1].1 else { panic!("links is not an array") };
+    let Value::Array(row) = &links[0] else { panic!("row is not an array") };
+    assert_eq!(row[1].to_json(), "{\"type\":\"nvlink\",\"links\":12}");
+    assert_eq!(row[2].to_json(), "{\"type\":\"sysmem\"}");
+}
+
+#[test]
+fn topology_without_a_gpu_is_an_error() {
+    // Without lspci on the PATH, no GPU is detected.
+    let dir = std::env::temp_dir().join(format!("gpuatop-topology-none-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).arg("topology").env("PATH", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: GPU not found\n");
+}
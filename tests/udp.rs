@@ -0,0 +1,166 @@
+#![cfg(feature = "network")]
+
+mod common;
+
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, UdpSocket};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::udp::{datagrams, messages, parse_port, parse_target, Reassembler, UdpSender, FRAGMENT_SIZE, MAX_MESSAGE, MAX_PARTS, MAX_PENDING, REASSEMBLY_TIMEOUT};
+
+fn source(port: u16) -> SocketAddr {
+    SocketAddr::from(([192, 0, 2, 10], port))
+}
+
+fn record(gpu: usize) -> String {
+    format!("{{\"gpu_index\":{},\"gpu_name\":\"NVIDIA A100-SXM4-80GB\",\"padding\":\"{}\"}}", gpu, "x".repeat(200))
+}
+
+#[test]
+fn targets_need_a_host_and_a_port() {
+    assert_eq!(parse_target("collector:9999"), Ok("collector:9999".to_string()));
+    assert_eq!(parse_target("[::1]:9999"), Ok("[::1]:9999".to_string()));
+    assert_eq!(parse_target("collector"), Err("Invalid --send-to address: collector (expected host:port)".to_string()));
+    assert!(parse_target(":9999").is_err());
+    assert!(parse_target("collector:0").is_err());
+    assert_eq!(parse_port("9999"), Ok(9999));
+    assert!(parse_port("70000").is_err());
+}
+
+#[test]
+fn short_messages_are_sent_as_they_are() {
+    let payload = record(0);
+
+    assert_eq!(datagrams(7, payload.as_bytes()), [payload.as_bytes()]);
+    assert_eq!(Reassembler::new().push(source(1), payload.as_bytes(), Instant::now()), Some(payload.into_bytes()));
+}
+
+#[test]
+fn long_messages_are_fragmented_and_reassembled_in_any_order() {
+    let payload = (0..8).map(record).collect::<Vec<_>>().join("\n");
+    let fragments = datagrams(7, payload.as_bytes());
+
+    assert_eq!(fragments.len(), payload.len().div_ceil(FRAGMENT_SIZE));
+    assert!(fragments[0].starts_with(b"#gpuatop 7 1/2\n"));
+    assert!(fragments.iter().all(|fragment| fragment.len() <= FRAGMENT_SIZE + 20));
+
+    let now = Instant::now();
+    let mut reassembler = Reassembler::new();
+    assert_eq!(reassembler.push(source(1), &fragments[1], now), None);
+    // Another sender's fragment of the same message number is kept apart.
+    assert_eq!(reassembler.push(source(2), &fragments[0], now), None);
+    assert_eq!(reassembler.push(source(1), &fragments[0], now), Some(payload.into_bytes()));
+}
+
+#[test]
+fn incomplete_messages_expire() {
+    let payload = (0..8).map(record).collect::<Vec<_>>().join("\n");
+    let fragments = datagrams(3, payload.as_bytes());
+    let start = Instant::now();
+    let mut reassembler = Reassembler::new();
+
+    assert_eq!(reassembler.push(source(1), &fragments[0], start), None);
+    assert_eq!(reassembler.push(source(1), &fragments[1], start + REASSEMBLY_TIMEOUT), None);
+}
+
+#[test]
+fn forged_fragment_headers_are_not_trusted() {
+    let now = Instant::now();
+    let mut reassembler = Reassembler::new();
+
+    // Not a fragment gpuatop sends, so taken as a message of its own.
+    for header in ["#gpuatop 1 1/18446744073709551615", &format!("#gpuatop 1 1/{}", MAX_PARTS + 1), "#gpuatop 1 3/2", "#gpuatop 1 0/2"] {
+        let datagram = format!("{}\nx", header);
+        assert_eq!(reassembler.push(source(1), datagram.as_bytes(), now), Some(datagram.into_bytes()));
+    }
+}
+
+#[test]
+fn a_sender_has_a_bounded_number_of_incomplete_messages() {
+    let start = Instant::now();
+    let mut reassembler = Reassembler::new();
+    for message in 0..=MAX_PENDING as u32 {
+        let datagram = format!("#gpuatop {} 1/2\na", message);
+        assert_eq!(reassembler.push(source(1), datagram.as_bytes(), start + Duration::from_millis(message.into())), None);
+    }
+
+    // Message 0 was the oldest and made room for the last one; message 1 is still complete.
+    assert_eq!(reassembler.push(source(1), b"#gpuatop 0 2/2\nb", start), None);
+    assert_eq!(reassembler.push(source(1), b"#gpuatop 1 2/2\nb", start), None);
+    assert_eq!(reassembler.push(source(1), b"#gpuatop 2 2/2\nb", start), Some(b"ab".to_vec()));
+}
+
+#[test]
+fn large_ticks_are_split_between_records() {
+    let records: Vec<String> = (0..300).map(record).collect();
+    let split = messages(&records);
+
+    assert!(split.len() > 1);
+    assert!(split.iter().all(|message| message.len() <= MAX_MESSAGE && datagrams(0, message.as_bytes()).len() <= MAX_PARTS));
+    assert_eq!(split.join("\n"), records.join("\n"));
+    assert!(messages(&[]).is_empty());
+}
+
+#[test]
+fn the_sender_sends_each_tick_as_one_message() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut sender = UdpSender::new(&receiver.local_addr().unwrap().to_string()).unwrap();
+    let records: Vec<String> = (0..8).map(record).collect();
+
+    sender.send(&records);
+
+    let mut reassembler = Reassembler::new();
+    let mut buffer = [0; 2048];
+    let message = loop {
+        let (length, from) = receiver.recv_from(&mut buffer).unwrap();
+        if let Some(message) = reassembler.push(from, &buffer[..length], Instant::now()) {
+            break message;
+        }
+    };
+    assert_eq!(String::from_utf8(message).unwrap(), records.join("\n"));
+}
+
+#[test]
+fn send_to_streams_ndjson_and_receive_prints_it() {
+    let dir = common::fake_tools("udp");
+    let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+    collector.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+
+    let sent = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["-q", "--count", "1", "--send-to", &collector.local_addr().unwrap().to_string()])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(sent.status.success(), "{}", String::from_utf8_lossy(&sent.stderr));
+
+    let mut buffer = [0; 2048];
+    let length = collector.recv(&mut buffer).unwrap();
+    let record = String::from_utf8_lossy(&buffer[..length]).to_string();
+    assert!(record.starts_with('{') && record.contains("\"utilization\":45"), "{}", record);
+
+    // The receiver prints the same record.
+    let port = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut receiver = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["-q", "--receive", &port.to_string()]).stdout(Stdio::piped()).spawn().unwrap();
+    let stdout = receiver.stdout.take().unwrap();
+    let (lines, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let _ = lines.send(line.unwrap());
+        }
+    });
+
+    let mut sender = UdpSender::new(&format!("127.0.0.1:{}", port)).unwrap();
+    let printed = (0..50).find_map(|_| {
+        sender.send(std::slice::from_ref(&record));
+        received.recv_timeout(Duration::from_millis(100)).ok()
+    });
+    receiver.kill().unwrap();
+    receiver.wait().unwrap();
+
+    assert_eq!(printed, Some(record));
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use gpu_auto_top::backend::{drm_utilization, parse_drm_fdinfo, DrmClient};
+use gpu_auto_top::runner::MockRunner;
+use gpu_auto_top::{check_top_exists_local, enumerate_gpus, install_top_for_gpu_to, lspci_display_controller, poll_gpus, GpuType, InstallResult, Installer, PackageManager, PollResult};
+
+const LSPCI: &str = "00:00.0 Host bridge: Advanced Micro Devices, Inc. [AMD] Starship/Matisse Root Complex\n\
+01:00.0 VGA compatible controller: Moore Threads Technology Co.,Ltd MTT S80\n";
+
+const FDINFO: &str = "pos:\t0\nflags:\t02100002\nmnt_id:\t24\nino:\t1006\n\
+drm-driver:\tmtgpu\ndrm-pdev:\t0000:01:00.0\ndrm-client-id:\t42\n\
+drm-engine-render:\t2500000 ns\ndrm-engine-capacity-render:\t2\ndrm-engine-video:\t100 ns\n";
+
+fn unknown() -> GpuType {
+    GpuType::Unknown("0x1ed5 (Moore Threads)".to_string())
+}
+
+fn client(client_id: u64, render_ns: u64) -> DrmClient {
+    DrmClient { client_id, pdev: Some("0000:01:00.0".to_string()), engines: vec![("render".to_string(), render_ns)], vram_bytes: None }
+}
+
+#[test]
+fn lspci_display_controller_is_described() {
+    assert_eq!(lspci_display_controller(LSPCI), Some("Moore Threads Technology Co.,Ltd MTT S80"));
+    assert_eq!(lspci_display_controller("00:00.0 Host bridge: Something\n"), None);
+}
+
+#[test]
+fn unknown_gpu_needs_no_vendor_tool() {
+    assert_eq!(unknown().top_tool(), None);
+    assert!(check_top_exists_local(&MockRunner::new(), &unknown()).unwrap());
+
+    let result = install_top_for_gpu_to(&MockRunner::new(), &unknown(), Installer::PackageManager(PackageManager::Apt), None, |_| panic!("nothing to install"), |_| {});
+    assert!(matches!(result, InstallResult::Failed { .. }), "unexpected result: {:?}", result);
+}
+
+#[test]
+fn unknown_gpu_is_named_after_its_description() {
+    let gpus = enumerate_gpus(&MockRunner::new(), &unknown());
+
+    assert_eq!(gpus.len(), 1);
+    assert_eq!(gpus[0].name, "0x1ed5 (Moore Threads)");
+}
+
+#[test]
+fn polling_an_unknown_gpu_with_a_vendor_tool_is_a_permanent_error() {
+    let gpus = enumerate_gpus(&MockRunner::new(), &unknown());
+    let results = poll_gpus(&MockRunner::new(), &unknown(), &gpus);
+
+    assert!(matches!(results.as_slice(), [PollResult::PermanentError { .. }]), "unexpected results: {:?}", results);
+}
+
+#[test]
+fn parses_drm_fdinfo() {
+    let client = parse_drm_fdinfo(FDINFO).expect("is a DRM client");
+
+    assert_eq!(client.client_id, 42);
+    assert_eq!(client.pdev.as_deref(), Some("0000:01:00.0"));
+    assert_eq!(client.engines, vec![("render".to_string(), 2_500_000), ("video".to_string(), 100)]);
+}
+
+#[test]
+fn fdinfo_reports_resident_vram() {
+    let amdgpu = format!("{}drm-memory-vram:\t4096 KiB\ndrm-memory-gtt:\t128 KiB\n", FDINFO);
+    assert_eq!(parse_drm_fdinfo(&amdgpu).unwrap().vram_bytes, Some(4 << 20));
+
+    let resident = format!("{}drm-total-vram:\t8 MiB\ndrm-resident-vram:\t6 MiB\ndrm-memory-vram:\t8192 KiB\n", FDINFO);
+    assert_eq!(parse_drm_fdinfo(&resident).unwrap().vram_bytes, Some(6 << 20));
+
+    let intel = format!("{}drm-resident-local0:\t1048576\n", FDINFO);
+    assert_eq!(parse_drm_fdinfo(&intel).unwrap().vram_bytes, Some(1 << 20));
+    assert_eq!(parse_drm_fdinfo(FDINFO).unwrap().vram_bytes, None);
+}
+
+#[test]
+fn other_files_are_not_drm_clients() {
+    assert_eq!(parse_drm_fdinfo("pos:\t0\nflags:\t02\nmnt_id:\t24\n"), None);
+}
+
+#[test]
+fn utilization_is_the_busiest_engine_share_of_the_interval() {
+    let previous = [client(1, 1_000_000), client(2, 0)];
+    // Client 1 was busy for 2 ms, client 2 for 3 ms, client 3 started since and used 1 ms.
+    let current = [client(1, 3_000_000), client(2, 3_000_000), client(3, 1_000_000)];
+
+    assert_eq!(drm_utilization(&previous, &current, Duration::from_millis(10), None), 60.0);
+    assert_eq!(drm_utilization(&previous, &current, Duration::from_millis(10), Some("0000:02:00.0")), 0.0);
+}
+
+#[test]
+fn utilization_is_capped_at_100_percent() {
+    let current = [client(1, 50_000_000)];
+
+    assert_eq!(drm_utilization(&[], &current, Duration::from_millis(10), Some("0000:01:00.0")), 100.0);
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "cli")]
+
+use std::time::Duration;
+
+use gpu_auto_top::desktop::DesktopClassifier;
+use gpu_auto_top::metadata::Labels;
+use gpu_auto_top::output::{OutputContext, OutputFormat};
+use gpu_auto_top::process::GpuProcess;
+use gpu_auto_top::users::{aggregate, format_json, format_table, parse_passwd, parse_status_uid, GpuHours, UserUsage};
+
+fn process(gpu_index: u32, pid: u32, name: &str, utilization: Option<f32>, memory_used_mib: Option<u64>) -> GpuProcess {
+    GpuProcess { gpu_index, pid, name: name.to_string(), utilization, memory_used_mib, kind: None }
+}
+
+fn processes() -> Vec<GpuProcess> {
+    vec![
+        process(0, 1200, "Xorg", Some(3.0), Some(120)),
+        process(0, 4100, "python", Some(40.0), Some(8192)),
+        process(1, 4100, "python", Some(25.0), Some(4096)),
+        process(1, 5200, "blender", Some(10.0), Some(2048)),
+        process(0, 9999, "ghost", None, None),
+    ]
+}
+
+fn owner(pid: u32) -> Option<String> {
+    match pid {
+        1200 => Some("root".to_string()),
+        4100 => Some("alice".to_string()),
+        5200 => Some("bob".to_string()),
+        _ => None,
+    }
+}
+
+#[test]
+fn reads_the_real_uid_and_passwd_names() {
+    let status = "Name:\tpython\nUid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\n";
+    let passwd = "# local users\nroot:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash\n";
+
+    assert_eq!(parse_status_uid(status), Some(1000));
+    let names = parse_passwd(passwd);
+    assert_eq!((names[&0].as_str(), names[&1000].as_str()), ("root", "alice"));
+}
+
+#[test]
+fn sums_memory_and_busy_time_per_user() {
+    let users = aggregate(&processes(), owner, None);
+
+    let alice = UserUsage { user: "alice".to_string(), processes: 2, utilization: Some(65.0), memory_used_mib: 12288 };
+    assert_eq!(users[0], alice);
+    assert_eq!(users.iter().map(|usage| usage.user.as_str()).collect::<Vec<_>>(), ["alice", "bob", "root", "(unknown)"]);
+    assert_eq!(users[3].utilization, None);
+}
+
+#[test]
+fn desktop_processes_can_be_excluded() {
+    let users = aggregate(&processes(), owner, Some(&DesktopClassifier::default()));
+
+    assert!(users.iter().all(|usage| usage.user != "root"), "{:?}", users);
+}
+
+#[test]
+fn formats_the_table_and_json_record() {
+    let users = aggregate(&processes(), owner, None);
+    let context = OutputContext { format: OutputFormat::Ndjson, hostname: None, labels: Labels::default(), tick_seq: Some(3), timestamp: None, precision: 1 };
+
+    let table = format_table(&users[..2]);
+    assert_eq!(table, ["User   Processes  Busy   Memory", "alice  2          65.0%  12288 MiB", "bob    1          10.0%  2048 MiB"]);
+    assert_eq!(
+        format_json(&users[2..], &context),
+        "{\"schema_version\":1,\"users\":[{\"user\":\"root\",\"processes\":1,\"utilization\":3,\"memory_used_mib\":120},{\"user\":\"(unknown)\",\"processes\":1,\"memory_used_mib\":0}],\"tick_seq\":3}"
+    );
+}
+
+#[test]
+fn integrates_busy_time_into_gpu_hours() {
+    let mut hours = GpuHours::default();
+    let busy = vec![UserUsage { user: "alice".to_string(), processes: 1, utilization: Some(150.0), memory_used_mib: 0 }];
+
+    for _ in 0..60 {
+        hours.record(&busy, Duration::from_secs(60));
+    }
+
+    assert!((hours.get("alice").unwrap() - 1.5).abs() < 1e-9);
+    assert_eq!(hours.format_summary(), ["GPU-hours by user:", "  alice: 1.500"]);
+}
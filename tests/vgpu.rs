@@ -0,0 +1,61 @@
+#![cfg(feature = "cli")]
+
+use gpu_auto_top::runner::{CommandOutput, MockRunner};
+use gpu_auto_top::vgpu::{format_vgpu, query_vgpus, query_virtualization_info, VirtualizationInfo, VirtualizationMode};
+
+const GUEST_QUERY: &str = "
+==============NVSMI LOG==============
+
+Driver Version                            : 550.54.15
+Attached GPUs                             : 1
+GPU 00000000:02:00.0
+    Product Name                          : GRID A100-4C
+    GPU Virtualization Mode
+        Virtualization Mode               : VGPU
+        Host VGPU Mode                    : N/A
+    vGPU Software Licensed Product
+        Product Name                      : NVIDIA Virtual Compute Server
+        License Status                    : Licensed (Expiry: 2026-11-2 10:15:31 GMT)
+";
+
+const VGPU_QUERY: &str = "
+GPU 00000000:3B:00.0
+    Active vGPUs                          : 2
+    vGPU ID                               : 3251634213
+        VM ID                             : 2390
+        vGPU Name                         : GRID A100-4C
+        Utilization
+            Gpu                           : 37 %
+            Memory                        : 12 %
+        FBC Stats
+            Average FPS                   : 0
+    vGPU ID                               : 3251634214
+        vGPU Name                         : GRID A100-8C
+        Utilization
+            Gpu                           : 5 %
+";
+
+#[test]
+fn reads_the_virtualization_mode_through_the_runner() {
+    let runner = MockRunner::new().with("nvidia-smi", &["-q"], CommandOutput::ok(GUEST_QUERY));
+
+    assert_eq!(
+        query_virtualization_info(&runner),
+        Some(VirtualizationInfo { mode: VirtualizationMode::Guest, license_status: Some("Licensed (Expiry: 2026-11-2 10:15:31 GMT)".to_string()) })
+    );
+    assert_eq!(query_virtualization_info(&MockRunner::new().with("nvidia-smi", &["-q"], CommandOutput::failed(9, "NVML error"))), None);
+    assert_eq!(query_virtualization_info(&MockRunner::new()), None);
+}
+
+#[test]
+fn samples_vgpus_through_the_runner() {
+    let runner = MockRunner::new().with("nvidia-smi", &["vgpu", "-q"], CommandOutput::ok(VGPU_QUERY));
+
+    let vgpus = query_vgpus(&runner);
+
+    assert_eq!(vgpus.len(), 2);
+    assert_eq!(vgpus[0].parent_bus_id, "0000:3b:00.0");
+    assert_eq!(format_vgpu(&vgpus[0]), "  vGPU 3251634213 (GRID A100-4C) Utilization (percent): 37, FPS: 0");
+    assert_eq!(format_vgpu(&vgpus[1]), "  vGPU 3251634214 (GRID A100-8C) Utilization (percent): 5");
+    assert!(query_vgpus(&MockRunner::new()).is_empty());
+}
@@ -0,0 +1,14 @@
+#![cfg(feature = "vulkan")]
+
+use gpu_auto_top::vulkan::gpu_type_for_vendor_id;
+use gpu_auto_top::GpuType;
+
+#[test]
+fn vendor_ids_map_to_gpu_types() {
+    assert!(matches!(gpu_type_for_vendor_id(0x10de), Some(GpuType::Nvidia)));
+    assert!(matches!(gpu_type_for_vendor_id(0x1002), Some(GpuType::Amd)));
+    assert!(matches!(gpu_type_for_vendor_id(0x8086), Some(GpuType::Intel)));
+    // ARM Mali and Qualcomm Adreno have no supported top tool.
+    assert!(gpu_type_for_vendor_id(0x13b5).is_none());
+    assert!(gpu_type_for_vendor_id(0x5143).is_none());
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "cli")]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use gpu_auto_top::backend::DrmClient;
+use gpu_auto_top::process::{parse_compute_apps, parse_start_time, GpuProcess};
+use gpu_auto_top::watch::{drm_usage, format_csv, format_line, newest_named_in, nvidia_usage, Summary, Target, Usage};
+
+fn process(gpu_index: u32, pid: u32, utilization: Option<f32>, memory_used_mib: Option<u64>) -> GpuProcess {
+    GpuProcess { gpu_index, pid, name: "python".to_string(), utilization, memory_used_mib, kind: None }
+}
+
+fn client(pdev: &str, client_id: u64, render_ns: u64, vram_bytes: Option<u64>) -> DrmClient {
+    DrmClient { client_id, pdev: Some(pdev.to_string()), engines: vec![("gfx".to_string(), render_ns)], vram_bytes }
+}
+
+fn stat(comm: &str, start_time: u64) -> String {
+    format!("4242 ({}) S 1 4242 4242 0 -1 4194560 1000 0 0 0 10 5 0 0 20 0 1 0 {} 123456 789 18446744073709551615\n", comm, start_time)
+}
+
+fn target() -> Target {
+    Target::new(std::process::id()).unwrap()
+}
+
+#[test]
+fn start_time_is_field_22() {
+    assert_eq!(parse_start_time(&stat("python", 987654)), Some(987654));
+    assert_eq!(parse_start_time(&stat("a) S (b", 55)), Some(55));
+    assert_eq!(parse_start_time("4242 (python) S 1"), None);
+}
+
+#[test]
+fn comm_picks_the_newest_process_of_that_name() {
+    let proc_root = std::env::temp_dir().join(format!("gpuatop-watch-proc-{}", std::process::id()));
+    for (pid, comm, start_time) in [(100, "python", 500), (200, "python", 900), (300, "bash", 1000), (50, "python", 700)] {
+        let dir = proc_root.join(pid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        fs::write(dir.join("stat"), stat(comm, start_time)).unwrap();
+    }
+
+    assert_eq!(newest_named_in(&proc_root, "python"), Some(200));
+    assert_eq!(newest_named_in(&proc_root, "bash"), Some(300));
+    assert_eq!(newest_named_in(&proc_root, "ollama"), None);
+    fs::remove_dir_all(&proc_root).unwrap();
+}
+
+#[test]
+fn nvidia_usage_sums_the_gpus_of_the_process() {
+    let processes = [process(0, 42, Some(30.0), Some(2048)), process(1, 42, Some(50.0), Some(1024)), process(0, 7, Some(90.0), Some(512))];
+
+    assert_eq!(nvidia_usage(42, &processes, &[]), Usage { memory_mib: Some(3072), busy: Some(80.0) });
+    assert_eq!(nvidia_usage(99, &processes, &[]), Usage::default());
+}
+
+#[test]
+fn nvidia_memory_falls_back_to_compute_apps() {
+    let processes = [process(0, 42, Some(30.0), None)];
+    let compute_apps = parse_compute_apps("42, 2048\n7, 512\n42, 1024\n[N/A], 3\n");
+
+    assert_eq!(compute_apps, [(42, 2048), (7, 512), (42, 1024)]);
+    assert_eq!(nvidia_usage(42, &processes, &compute_apps), Usage { memory_mib: Some(3072), busy: Some(30.0) });
+}
+
+#[test]
+fn drm_usage_adds_up_the_devices() {
+    let previous = [client("0000:03:00.0", 1, 0, None), client("0000:04:00.0", 2, 0, None)];
+    let current = [client("0000:03:00.0", 1, 250_000_000, Some(1 << 30)), client("0000:04:00.0", 2, 500_000_000, Some(512 << 20))];
+
+    let usage = drm_usage(&previous, &current, Duration::from_secs(1));
+
+    assert_eq!(usage.memory_mib, Some(1536));
+    assert!((usage.busy.unwrap() - 75.0).abs() < 1e-9, "{:?}", usage);
+    assert_eq!(drm_usage(&current, &[], Duration::from_secs(1)), Usage::default());
+}
+
+#[test]
+fn summary_tracks_the_peak_mean_and_gpu_seconds() {
+    let mut summary = Summary::default();
+    summary.record(&Usage { memory_mib: Some(1024), busy: Some(50.0) }, Duration::from_secs(2));
+    summary.record(&Usage::default(), Duration::from_secs(2));
+    summary.record(&Usage { memory_mib: Some(4096), busy: Some(100.0) }, Duration::from_secs(3));
+    summary.record(&Usage { memory_mib: Some(2048), busy: Some(0.0) }, Duration::from_secs(1));
+
+    assert_eq!(summary.peak_memory_mib, Some(4096));
+    assert_eq!(summary.mean_busy(), Some(50.0));
+    assert_eq!(summary.gpu_seconds, 4.0);
+
+    let target = target();
+    let lines = summary.format(&target, Duration::from_secs(3725));
+    assert_eq!(lines[0], format!("PID {} ({}) exited after 01:02:05", target.pid, target.name));
+    assert_eq!(&lines[1..], ["  Peak VRAM:   4096 MiB", "  Mean busy:   50.0%", "  GPU-seconds: 4.0"]);
+}
+
+#[test]
+fn a_process_never_on_the_gpu_has_no_peak_or_mean() {
+    let lines = Summary::default().format(&target(), Duration::from_secs(5));
+
+    assert_eq!(&lines[1..], ["  Peak VRAM:   n/a", "  Mean busy:   n/a", "  GPU-seconds: 0.0"]);
+}
+
+#[test]
+fn lines_show_the_usage_or_its_absence() {
+    let target = target();
+    let prefix = format!("PID {} ({})", target.pid, target.name);
+
+    assert_eq!(format_line(&target, &Usage { memory_mib: Some(2048), busy: Some(30.25) }), format!("{} VRAM: 2048 MiB, Busy: 30.2%", prefix));
+    assert_eq!(format_line(&target, &Usage { memory_mib: Some(2048), busy: None }), format!("{} VRAM: 2048 MiB, Busy: n/a", prefix));
+    assert_eq!(format_line(&target, &Usage::default()), format!("{} not using the GPU", prefix));
+    assert_eq!(format_csv(&target, &Usage { memory_mib: Some(2048), busy: Some(30.25) }, Duration::from_millis(1500)), format!("1.5,{},2048,30.25", target.pid));
+    assert_eq!(format_csv(&target, &Usage::default(), Duration::from_secs(2)), format!("2.0,{},,", target.pid));
+}
+
+#[test]
+fn a_reused_pid_is_not_the_watched_process() {
+    let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+    let watched = Target::new(child.id()).unwrap();
+    assert!(watched.is_running());
+
+    child.kill().unwrap();
+    // Until it is reaped the child is a zombie, which counts as exited too.
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    while watched.is_running() && std::time::Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!watched.is_running());
+    child.wait().unwrap();
+    assert!(!watched.is_running());
+}
+
+#[test]
+fn watch_pid_follows_the_process_until_it_exits() {
+    let dir = common::fake_tools("watch");
+    let mut child = Command::new("sleep").arg("1").spawn().unwrap();
+    let pmon = format!(
+        "#!/bin/sh\nprintf '# gpu pid type sm mem enc dec fb command\\n# Idx # C/G %% %% %% %% MB name\\n    0 {} C 30 10 - - 2048 sleep\\n'\n",
+        child.id()
+    );
+    fs::write(dir.join("nvidia-smi"), pmon).unwrap();
+    fs::set_permissions(dir.join("nvidia-smi"), fs::Permissions::from_mode(0o755)).unwrap();
+
+    let watcher = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["-q", "watch-pid", &child.id().to_string(), "--interval", "200ms"])
+        .env("PATH", common::path_with(&dir))
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.wait().unwrap();
+    let output = watcher.wait_with_output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let prefix = format!("PID {} (sleep)", child.id());
+    assert!(stdout.contains(&format!("{} VRAM: 2048 MiB, Busy: 30.0%\n", prefix)), "{}", stdout);
+    assert!(stdout.contains(&format!("{} exited after 00:00:0", prefix)), "{}", stdout);
+    assert!(stdout.contains("  Peak VRAM:   2048 MiB\n  Mean busy:   30.0%\n"), "{}", stdout);
+}
+
+#[test]
+fn watch_pid_without_a_gpu_is_an_error() {
+    // Without lspci on the PATH, no GPU is detected.
+    let dir = std::env::temp_dir().join(format!("gpuatop-watch-none-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["watch-pid", &std::process::id().to_string()]).env("PATH", &dir).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: GPU not found\n");
+}
+
+#[test]
+fn watch_pid_needs_a_pid_or_a_name() {
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).arg("watch-pid").output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: watch-pid requires either a PID or --comm <name>"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top")).args(["--comm", "python"]).output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: --comm and --output csv require the watch-pid subcommand"));
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "web")]
+
+mod common;
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gpu_auto_top::web::origin_allowed;
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn start(name: &str, port: u16) -> (Child, std::path::PathBuf) {
+    let dir = common::fake_tools(name);
+    let child = Command::new(env!("CARGO_BIN_EXE_gpu_auto_top"))
+        .args(["web", "--count", "5", "--listen", &format!("127.0.0.1:{}", port)])
+        .env("PATH", common::path_with(&dir))
+        .env("XDG_RUNTIME_DIR", &dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    (child, dir)
+}
+
+fn connect(port: u16) -> TcpStream {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+                return stream;
+            }
+            Err(err) if Instant::now() > deadline => panic!("could not connect: {}", err),
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+/// Sends `request` on a new connection and returns the status line of the response.
+fn status(port: u16, request: &[u8]) -> String {
+    let mut stream = connect(port);
+    stream.write_all(request).unwrap();
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).unwrap();
+    line.trim_end().to_string()
+}
+
+#[test]
+fn only_same_origin_pages_may_open_the_websocket() {
+    assert!(origin_allowed(None, Some("localhost:8080")));
+    assert!(origin_allowed(Some("http://localhost:8080"), Some("localhost:8080")));
+    assert!(origin_allowed(Some("https://GPU-Box:8080"), Some("gpu-box:8080")));
+    assert!(!origin_allowed(Some("https://evil.example"), Some("localhost:8080")));
+    assert!(!origin_allowed(Some("http://localhost:9090"), Some("localhost:8080")));
+    assert!(!origin_allowed(Some("null"), Some("localhost:8080")));
+    assert!(!origin_allowed(Some("http://localhost:8080"), None));
+}
+
+#[test]
+fn serves_dashboard_and_streams_samples() {
+    let port = free_port();
+    let (mut child, dir) = start("web", port);
+
+    let mut page = connect(port);
+    page.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    page.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains("new WebSocket("));
+
+    let mut socket = connect(port);
+    socket
+        .write_all(b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+        .unwrap();
+
+    let mut reader = BufReader::new(socket);
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        headers.push(line.trim().to_string());
+    }
+    assert_eq!(headers[0], "HTTP/1.1 101 Switching Protocols");
+    assert!(headers.iter().any(|header| header.eq_ignore_ascii_case("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")), "{:?}", headers);
+
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header).unwrap();
+    assert_eq!(header[0], 0x81, "expected a text frame");
+    let length = match header[1] & 0x7f {
+        126 => {
+            let mut length = [0u8; 2];
+            reader.read_exact(&mut length).unwrap();
+            u16::from_be_bytes(length) as usize
+        }
+        length => length as usize,
+    };
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload).unwrap();
+    let sample = String::from_utf8(payload).unwrap();
+    assert!(sample.starts_with("{\"schema_version\":1,\"gpu\":0,"), "{}", sample);
+    assert!(sample.contains("\"utilization\":45"), "{}", sample);
+
+    assert!(child.wait().unwrap().success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn refuses_cross_origin_websockets_and_oversized_requests() {
+    let port = free_port();
+    let (mut child, dir) = start("web-refusals", port);
+
+    let cross_origin = "GET /ws HTTP/1.1\r\nHost: localhost\r\nOrigin: https://evil.example\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+    assert_eq!(status(port, cross_origin.as_bytes()), "HTTP/1.1 403 Forbidden");
+
+    let oversized = format!("GET / HTTP/1.1\r\nHost: localhost\r\nCookie: {}\r\n\r\n", "a".repeat(16 * 1024));
+    assert_eq!(status(port, oversized.as_bytes()), "HTTP/1.1 431 Request Header Fields Too Large");
+
+    assert_eq!(status(port, b"GET /ws HTTP/1.1\r\nHost: localhost\r\n\r\n"), "HTTP/1.1 426 Upgrade Required");
+
+    assert!(child.wait().unwrap().success());
+    fs::remove_dir_all(&dir).unwrap();
+}
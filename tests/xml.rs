@@ -0,0 +1,61 @@
+#![cfg(feature = "cli")]
+
+use gpu_auto_top::xml::parse;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/nvidia-smi/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+#[test]
+fn parses_an_nvidia_smi_report() {
+    let root = parse(&fixture("query-550.xml")).unwrap();
+
+    assert_eq!(root.name, "nvidia_smi_log");
+    let driver = root.children_named("driver_version").next().unwrap();
+    assert_eq!(driver.text, "550.54.15");
+
+    let gpus: Vec<_> = root.children_named("gpu").collect();
+    assert_eq!(gpus.len(), 2);
+    assert_eq!(gpus[0].attributes, vec![("id".to_string(), "00000000:3B:00.0".to_string())]);
+    assert_eq!(gpus[1].children_named("product_name").next().unwrap().text, "NVIDIA A100-SXM4-80GB");
+}
+
+#[test]
+fn decodes_predefined_entities() {
+    let root = parse(&fixture("query-550.xml")).unwrap();
+    let gpu = root.children_named("gpu").next().unwrap();
+    let processes = gpu.children_named("processes").next().unwrap();
+    let names: Vec<_> = processes.children_named("process_info").map(|info| info.children_named("process_name").next().unwrap().text.clone()).collect();
+
+    assert_eq!(names, vec!["/usr/lib/xorg/Xorg", "python3 train.py --data=<cache>"]);
+}
+
+#[test]
+fn decodes_numeric_character_references() {
+    let root = parse("<name>Caf&#233; &#x2013; &#X41;&#65;</name>").unwrap();
+    assert_eq!(root.text, "Café – AA");
+
+    let root = parse("<gpu label='&#x263A;&amp;&#38;'/>").unwrap();
+    assert_eq!(root.attributes, vec![("label".to_string(), "☺&&".to_string())]);
+}
+
+#[test]
+fn decodes_each_entity_once() {
+    let root = parse("<name>&amp;lt; &amp;#65;</name>").unwrap();
+    assert_eq!(root.text, "&lt; &#65;");
+}
+
+#[test]
+fn keeps_unknown_and_invalid_references_as_written() {
+    let root = parse("<name>a &nbsp; b &#xD800; c &#; d & e &#99999999;</name>").unwrap();
+    assert_eq!(root.text, "a &nbsp; b &#xD800; c &#; d & e &#99999999;");
+}
+
+#[test]
+fn rejects_malformed_documents() {
+    assert!(parse("<a><b></a>").unwrap_err().contains("Mismatched"));
+    assert!(parse("<a>").unwrap_err().contains("Unclosed"));
+    assert!(parse("</a>").unwrap_err().contains("Unexpected closing tag"));
+    assert!(parse("<a b=c/>").unwrap_err().contains("Unquoted"));
+    assert!(parse("just text").is_err());
+}